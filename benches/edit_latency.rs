@@ -0,0 +1,143 @@
+//! Measures how long it takes from setting voxels until the chunks they are in have been
+//! remeshed, for single edits and for bulk edits spanning several chunks.
+//!
+//! Run with `cargo bench --bench edit_latency`.
+use std::time::{Duration, Instant};
+
+use bevy::{
+    ecs::system::RunSystemOnce, prelude::*, render::render_resource::Shader, utils::HashSet,
+};
+use bevy_voxel_world::prelude::*;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+#[derive(Resource, Clone, Default)]
+struct BenchWorld;
+
+impl VoxelWorldConfig for BenchWorld {
+    type MaterialIndex = u8;
+
+    fn spawning_distance(&self) -> u32 {
+        3
+    }
+
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+        Box::new(|_| {
+            Box::new(|pos| {
+                if pos.y < 0 {
+                    WorldVoxel::Solid(0)
+                } else {
+                    WorldVoxel::Air
+                }
+            })
+        })
+    }
+}
+
+/// A headless app that generates and meshes chunks around a camera at the origin
+fn setup_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Shader>()
+        .init_asset::<Mesh>()
+        .init_asset::<StandardMaterial>()
+        .add_plugins(
+            VoxelWorldPlugin::with_config(BenchWorld).with_material(StandardMaterial::default()),
+        )
+        .add_systems(Startup, |mut commands: Commands| {
+            commands.spawn((
+                Camera3dBundle::default(),
+                VoxelWorldCamera::<BenchWorld>::default(),
+            ));
+        });
+
+    // Let the chunks around the camera finish before measuring edits
+    while app
+        .world_mut()
+        .resource_mut::<Events<FirstChunksReady<BenchWorld>>>()
+        .drain()
+        .next()
+        .is_none()
+    {
+        app.update();
+    }
+    app
+}
+
+/// Set the given voxels, and update the app until every chunk they are in has been remeshed
+fn edit_until_meshed(app: &mut App, voxels: Vec<(IVec3, WorldVoxel)>) -> Duration {
+    let mut pending: HashSet<IVec3> = voxels
+        .iter()
+        .map(|(position, _)| position.div_euclid(IVec3::splat(32)))
+        .collect();
+
+    let started = Instant::now();
+    app.world_mut()
+        .run_system_once(move |mut voxel_world: VoxelWorld<BenchWorld>| {
+            voxel_world.set_voxels(voxels.clone());
+        });
+    while !pending.is_empty() {
+        app.update();
+        for event in app
+            .world_mut()
+            .resource_mut::<Events<ChunkMeshReady<BenchWorld>>>()
+            .drain()
+        {
+            pending.remove(&event.chunk_key);
+        }
+    }
+    started.elapsed()
+}
+
+fn edit_latency(c: &mut Criterion) {
+    let mut group = c.benchmark_group("edit_latency");
+
+    let mut app = setup_app();
+    let mut material = 0;
+    group.bench_function("single_edit", |b| {
+        b.iter_custom(|iterations| {
+            (0..iterations)
+                .map(|_| {
+                    material = (material + 1) % 4;
+                    edit_until_meshed(
+                        &mut app,
+                        vec![(IVec3::new(4, -1, 4), WorldVoxel::Solid(material))],
+                    )
+                })
+                .sum()
+        })
+    });
+
+    // A 48x4x48 patch of ground, spanning four chunks
+    group.bench_function("bulk_edit", |b| {
+        b.iter_custom(|iterations| {
+            (0..iterations)
+                .map(|_| {
+                    material = (material + 1) % 4;
+                    let voxels = (-24..24)
+                        .flat_map(|x| (-4..0).flat_map(move |y| (-24..24).map(move |z| (x, y, z))))
+                        .map(|(x, y, z)| (IVec3::new(x, y, z), WorldVoxel::Solid(material)))
+                        .collect();
+                    edit_until_meshed(&mut app, voxels)
+                })
+                .sum()
+        })
+    });
+
+    group.finish();
+
+    let edit_latency = app
+        .world()
+        .resource::<ChunkTimings<BenchWorld>>()
+        .edit_latency();
+    println!(
+        "edit latency over the last {} remeshed chunks: mean {:?}, p90 {:?}",
+        edit_latency.samples, edit_latency.mean, edit_latency.p90
+    );
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = edit_latency
+}
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+//! Measures encoding and decoding chunk network payloads with each codec, for a terrain chunk
+//! with a few materials, and prints the payload sizes.
+//!
+//! Run with `cargo bench --bench network_payload`.
+use bevy_voxel_world::{prelude::*, storage::ChunkData};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+/// A chunk with rolling hills of stone under dirt and grass, cut through its middle
+fn terrain_chunk() -> ChunkData<u8> {
+    let padded = CHUNK_SIZE_I + 2;
+    let mut diff = Vec::new();
+    for z in 0..padded {
+        for y in 0..padded {
+            for x in 0..padded {
+                let height = 16.0 + 6.0 * ((x as f32 * 0.2).sin() + (z as f32 * 0.15).cos());
+                let voxel = match y as f32 {
+                    y if y > height => WorldVoxel::Air,
+                    y if y > height - 1.0 => WorldVoxel::Solid(2),
+                    y if y > height - 4.0 => WorldVoxel::Solid(1),
+                    _ => WorldVoxel::Solid(0),
+                };
+                diff.push(((x + padded * (y + padded * z)) as usize, voxel));
+            }
+        }
+    }
+
+    let mut chunk_data = ChunkData::new();
+    chunk_data.apply_diff(&diff).unwrap();
+    chunk_data
+}
+
+fn network_payload(c: &mut Criterion) {
+    let chunk_data = terrain_chunk();
+
+    let codecs = [
+        ("raw", NetworkCodec::Raw),
+        ("deflate", NetworkCodec::Deflate),
+        ("zstd", NetworkCodec::Zstd),
+    ];
+    for (name, codec) in codecs {
+        let bytes = chunk_data.to_network_bytes(codec);
+        println!("{name} payload: {} bytes", bytes.len());
+
+        c.bench_function(&format!("encode {name}"), |b| {
+            b.iter(|| black_box(&chunk_data).to_network_bytes(codec))
+        });
+        c.bench_function(&format!("decode {name}"), |b| {
+            b.iter(|| ChunkData::<u8>::from_network_bytes(black_box(&bytes)).unwrap())
+        });
+    }
+}
+
+criterion_group!(benches, network_payload);
+criterion_main!(benches);
@@ -154,31 +154,16 @@ fn explosion(
         let vox = voxel_world.get_voxel(impact_point - IVec3::Y);
 
         // Dig out a spherical volume centered around the impact point
-        let radius = 10;
-        for x in -radius..=radius {
-            for y in -radius..=radius {
-                for z in -radius..=radius {
-                    let pos =
-                        IVec3::new(x + impact_point.x, y + impact_point.y, z + impact_point.z);
-
-                    if pos.distance_squared(impact_point) <= radius.pow(2) {
-                        voxel_world.set_voxel(pos, WorldVoxel::Air);
-                    }
-                }
-            }
-        }
+        voxel_world.fill_sphere(impact_point, 10, WorldVoxel::Air);
 
         // Spread some voxels out around the impact zone
         let num_voxels = 50;
-        match vox {
-            WorldVoxel::Solid(mat) => {
-                for _ in 0..num_voxels {
-                    if let Some(rand_vox) = voxel_world.get_random_surface_voxel(impact_point, 25) {
-                        voxel_world.set_voxel(rand_vox.0 + IVec3::Y, WorldVoxel::Solid(mat));
-                    }
+        if let WorldVoxel::Solid(mat) = vox {
+            for _ in 0..num_voxels {
+                if let Some(rand_vox) = voxel_world.get_random_surface_voxel(impact_point, 25) {
+                    voxel_world.set_voxel(rand_vox.0 + IVec3::Y, WorldVoxel::Solid(mat));
                 }
             }
-            _ => {}
         }
     }
 }
@@ -28,7 +28,7 @@ impl VoxelWorldConfig for MyMainWorld {
         Arc::new(|vox_mat: u8| match vox_mat {
             RED => [1, 1, 1],
             GREEN => [2, 2, 2],
-            BLUE | _ => [3, 3, 3],
+            _ => [3, 3, 3],
         })
     }
 }
@@ -179,7 +179,7 @@ fn draw_trace(trace: Res<VoxelTrace>, mut gizmos: Gizmos) {
                 css::PINK,
             );
 
-            if let Ok(normal) = face.try_into() {
+            if let Some(normal) = face.map(Vec3::from) {
                 gizmos.circle(
                     voxel_center + (normal * VOXEL_SIZE / 2.),
                     Dir3::new(normal).unwrap(),
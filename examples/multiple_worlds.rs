@@ -48,7 +48,8 @@ impl VoxelWorldConfig for SecondWorld {
         Arc::new(|vox_mat: u8| match vox_mat {
             RED => [1, 1, 1],
             GREEN => [2, 2, 2],
-            BLUE | _ => [3, 3, 3],
+            BLUE => [3, 3, 3],
+            _ => [3, 3, 3],
         })
     }
 }
@@ -1,9 +1,21 @@
-use bevy::{pbr::CascadeShadowConfigBuilder, prelude::*, utils::HashMap};
+use bevy::{pbr::CascadeShadowConfigBuilder, prelude::*};
 use bevy_voxel_world::prelude::*;
 use noise::{HybridMulti, NoiseFn, Perlin};
 
-#[derive(Resource, Clone, Default)]
-struct MainWorld;
+#[derive(Resource, Clone)]
+struct MainWorld {
+    // We use this to cache the noise value for each y column, so we only need
+    // to calculate it once per x/z coordinate, even across neighboring chunks
+    heights: ColumnCache<f64>,
+}
+
+impl Default for MainWorld {
+    fn default() -> Self {
+        Self {
+            heights: ColumnCache::new(1 << 20),
+        }
+    }
+}
 
 impl VoxelWorldConfig for MainWorld {
     type MaterialIndex = u8;
@@ -13,14 +25,14 @@ impl VoxelWorldConfig for MainWorld {
     }
 
     fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
-        Box::new(move |_chunk_pos| get_voxel_fn())
+        get_voxel_delegate(&self.heights)
     }
 }
 
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
-        .add_plugins(VoxelWorldPlugin::with_config(MainWorld))
+        .add_plugins(VoxelWorldPlugin::with_config(MainWorld::default()))
         .add_systems(Startup, setup)
         .add_systems(Update, move_camera)
         .run();
@@ -58,7 +70,7 @@ fn setup(mut commands: Commands) {
     });
 }
 
-fn get_voxel_fn() -> Box<dyn FnMut(IVec3) -> WorldVoxel + Send + Sync> {
+fn get_voxel_delegate(heights: &ColumnCache<f64>) -> VoxelLookupDelegate {
     // Set up some noise to use as the terrain height map
     let mut noise = HybridMulti::<Perlin>::new(1234);
     noise.octaves = 5;
@@ -66,37 +78,28 @@ fn get_voxel_fn() -> Box<dyn FnMut(IVec3) -> WorldVoxel + Send + Sync> {
     noise.lacunarity = 2.8;
     noise.persistence = 0.4;
 
-    // We use this to cache the noise value for each y column so we only need
-    // to calculate it once per x/z coordinate
-    let mut cache = HashMap::<(i32, i32), f64>::new();
-
-    // Then we return this boxed closure that captures the noise and the cache
-    // This will get sent off to a separate thread for meshing by bevy_voxel_world
-    Box::new(move |pos: IVec3| {
-        // Sea level
-        if pos.y < 1 {
-            return WorldVoxel::Solid(3);
-        }
-
-        let [x, y, z] = pos.as_dvec3().to_array();
-
-        // If y is less than the noise sample, we will set the voxel to solid
-        let is_ground = y < match cache.get(&(pos.x, pos.z)) {
-            Some(sample) => *sample,
-            None => {
-                let sample = noise.get([x / 1000.0, z / 1000.0]) * 50.0;
-                cache.insert((pos.x, pos.z), sample);
-                sample
+    // The cache calls the first closure once per x/z column, and the second one for every voxel.
+    // The returned delegate will get sent off to separate threads for meshing by bevy_voxel_world
+    heights.delegate(
+        move |column: IVec2| {
+            let [x, z] = column.as_dvec2().to_array();
+            noise.get([x / 1000.0, z / 1000.0]) * 50.0
+        },
+        |pos: IVec3, height: &f64| {
+            // Sea level
+            if pos.y < 1 {
+                return WorldVoxel::Solid(3);
             }
-        };
 
-        if is_ground {
-            // Solid voxel of material type 0
-            WorldVoxel::Solid(0)
-        } else {
-            WorldVoxel::Air
-        }
-    })
+            // If y is less than the noise sample, we will set the voxel to solid
+            if (pos.y as f64) < *height {
+                // Solid voxel of material type 0
+                WorldVoxel::Solid(0)
+            } else {
+                WorldVoxel::Air
+            }
+        },
+    )
 }
 
 fn move_camera(
@@ -2,12 +2,17 @@ use bevy::prelude::*;
 use bevy_voxel_world::prelude::*;
 use std::sync::Arc;
 
-// Using enum for material index allows for more than u8::MAX number of materials.
-#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Default)]
+// `VoxelIndex` derives the traits a material index needs, along with the texture indexes
+// for each variant. `#[repr(u8)]` lets `WorldVoxel<BlockTexture>` fit in a single byte, which the
+// derive checks at compile time.
+#[derive(VoxelIndex, Debug)]
+#[repr(u8)]
 enum BlockTexture {
-    #[default]
+    #[voxel(default, textures = [0, 1, 2])]
     SnowyBrick,
+    #[voxel(textures = 2)]
     FullBrick,
+    #[voxel(textures = 3)]
     Grass,
 }
 
@@ -18,11 +23,7 @@ impl VoxelWorldConfig for MyMainWorld {
     type MaterialIndex = BlockTexture;
 
     fn texture_index_mapper(&self) -> Arc<dyn Fn(Self::MaterialIndex) -> [u32; 3] + Send + Sync> {
-        Arc::new(|vox_mat| match vox_mat {
-            BlockTexture::SnowyBrick => [0, 1, 2],
-            BlockTexture::FullBrick => [2, 2, 2],
-            BlockTexture::Grass => [3, 3, 3],
-        })
+        BlockTexture::texture_index_mapper()
     }
 
     fn voxel_texture(&self) -> Option<(String, u32)> {
@@ -0,0 +1,186 @@
+//! Derive macros for `bevy_voxel_world`. These are re-exported from the main crate, so there
+//! should be no need to depend on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Expr, ExprArray, Fields, LitStr,
+};
+
+/// Derives everything an enum needs to be used as a `VoxelWorldConfig::MaterialIndex`
+/// (`Clone`, `Copy`, `PartialEq`, `Eq`, `Hash` and `Default`), along with the `VoxelIndex` trait.
+///
+/// Variants can be annotated with `#[voxel(...)]`:
+/// - `default` marks the default variant. Without it, the first variant is the default.
+/// - `name = "..."` sets the display name. Defaults to the variant identifier.
+/// - `textures = [top, sides, bottom]` or `textures = index` sets the texture indexes used by
+///   `VoxelIndex::texture_index_mapper`.
+///
+/// On a `#[repr(u8)]` enum, the derive also checks at compile time that `WorldVoxel` of the enum
+/// fits in a single byte, like `assert_voxel_size!(YourEnum, 1)`.
+#[proc_macro_derive(VoxelIndex, attributes(voxel))]
+pub fn derive_voxel_index(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct VariantMeta {
+    ident: syn::Ident,
+    name: String,
+    textures: Option<TokenStream2>,
+    is_default: bool,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new(
+            input.span(),
+            "VoxelIndex can only be derived for enums",
+        ));
+    };
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(
+            input.generics.span(),
+            "VoxelIndex can not be derived for generic enums",
+        ));
+    }
+
+    if data.variants.is_empty() {
+        return Err(Error::new(
+            input.span(),
+            "VoxelIndex needs at least one variant",
+        ));
+    }
+
+    let mut variants = Vec::with_capacity(data.variants.len());
+    for variant in data.variants.iter() {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new(
+                variant.span(),
+                "VoxelIndex only supports unit variants",
+            ));
+        }
+
+        let mut meta = VariantMeta {
+            ident: variant.ident.clone(),
+            name: variant.ident.to_string(),
+            textures: None,
+            is_default: false,
+        };
+
+        for attr in variant.attrs.iter().filter(|a| a.path().is_ident("voxel")) {
+            attr.parse_nested_meta(|nested| {
+                if nested.path.is_ident("default") {
+                    meta.is_default = true;
+                } else if nested.path.is_ident("name") {
+                    meta.name = nested.value()?.parse::<LitStr>()?.value();
+                } else if nested.path.is_ident("textures") {
+                    meta.textures = Some(match nested.value()?.parse::<Expr>()? {
+                        Expr::Array(ExprArray { elems, .. }) if elems.len() == 3 => {
+                            quote!([#elems])
+                        }
+                        Expr::Array(array) => {
+                            return Err(Error::new(
+                                array.span(),
+                                "expected [top, sides, bottom] texture indexes",
+                            ))
+                        }
+                        index => quote!([#index; 3]),
+                    });
+                } else {
+                    return Err(nested.error("unknown voxel attribute"));
+                }
+                Ok(())
+            })?;
+        }
+
+        variants.push(meta);
+    }
+
+    let defaults: Vec<_> = variants.iter().filter(|v| v.is_default).collect();
+    let default_variant = match defaults.as_slice() {
+        [] => &variants[0].ident,
+        [default] => &default.ident,
+        [_, second, ..] => {
+            return Err(Error::new(
+                second.ident.span(),
+                "only one variant can be marked #[voxel(default)]",
+            ))
+        }
+    };
+
+    let ident = &input.ident;
+    let is_repr_u8 = input.attrs.iter().any(|attr| {
+        attr.path().is_ident("repr")
+            && attr
+                .parse_args::<syn::Ident>()
+                .is_ok_and(|repr| repr == "u8")
+    });
+    let size_check = is_repr_u8.then(|| quote!(::bevy_voxel_world::assert_voxel_size!(#ident, 1);));
+    let variant_idents: Vec<_> = variants.iter().map(|v| &v.ident).collect();
+    let ordinals = 0..variants.len();
+    let names = variants.iter().map(|v| &v.name);
+    let textures = variants.iter().map(|v| match &v.textures {
+        Some(textures) => quote!(::core::option::Option::Some(#textures)),
+        None => quote!(::core::option::Option::None),
+    });
+
+    Ok(quote! {
+        #size_check
+
+        impl ::core::clone::Clone for #ident {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl ::core::marker::Copy for #ident {}
+
+        impl ::core::cmp::PartialEq for #ident {
+            fn eq(&self, other: &Self) -> bool {
+                ::bevy_voxel_world::prelude::VoxelIndex::ordinal(self)
+                    == ::bevy_voxel_world::prelude::VoxelIndex::ordinal(other)
+            }
+        }
+
+        impl ::core::cmp::Eq for #ident {}
+
+        impl ::core::hash::Hash for #ident {
+            fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+                ::bevy_voxel_world::prelude::VoxelIndex::ordinal(self).hash(state);
+            }
+        }
+
+        impl ::core::default::Default for #ident {
+            fn default() -> Self {
+                #ident::#default_variant
+            }
+        }
+
+        impl ::bevy_voxel_world::prelude::VoxelIndex for #ident {
+            const VARIANTS: &'static [Self] = &[#(#ident::#variant_idents),*];
+
+            fn ordinal(&self) -> usize {
+                match self {
+                    #(#ident::#variant_idents => #ordinals,)*
+                }
+            }
+
+            fn name(&self) -> &'static str {
+                match self {
+                    #(#ident::#variant_idents => #names,)*
+                }
+            }
+
+            fn texture_indexes(&self) -> ::core::option::Option<[u32; 3]> {
+                match self {
+                    #(#ident::#variant_idents => #textures,)*
+                }
+            }
+        }
+    })
+}
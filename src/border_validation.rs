@@ -0,0 +1,227 @@
+///
+/// Border validation
+/// Debug tooling that checks that neighboring chunks agree on the voxels along their borders.
+///
+use std::{collections::VecDeque, hash::Hash, marker::PhantomData};
+
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    chunk::{Chunk, ChunkData, CHUNK_SIZE_I},
+    chunk_map::ChunkMap,
+    configuration::VoxelWorldConfig,
+    voxel::WorldVoxel,
+    voxel_world::{ChunkWillDespawn, ChunkWillSpawn},
+    voxel_world_internal::ModifiedVoxels,
+};
+
+/// The number of mismatches kept in `BorderMismatches`. Older ones are dropped first.
+pub const MAX_BORDER_MISMATCHES: usize = 1024;
+
+/// Add this next to `VoxelWorldPlugin` to check the borders of every chunk that gets generated.
+/// Each chunk holds a copy of the voxels just outside of it, which are used to cull faces and
+/// calculate ambient occlusion. If that copy doesn't match the voxels of the neighboring chunk,
+/// there will be holes or stray faces between the chunks. This usually means that the lookup
+/// delegate is not deterministic, or that voxels on a chunk border were changed without the
+/// neighboring chunk getting remeshed.
+///
+/// Mismatches are logged, and collected in the `BorderMismatches` resource. Chunks at a level of
+/// detail above 0 are skipped, since they never match their neighbors exactly.
+pub struct VoxelWorldBorderValidationPlugin<C> {
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for VoxelWorldBorderValidationPlugin<C> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: VoxelWorldConfig> Plugin for VoxelWorldBorderValidationPlugin<C> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BorderMismatches<C>>()
+            .add_systems(Update, validate_chunk_borders::<C>);
+    }
+}
+
+/// A voxel that two neighboring chunks disagree about
+#[derive(Clone, Debug, PartialEq)]
+pub struct BorderMismatch<I> {
+    /// The world position of the voxel
+    pub position: IVec3,
+
+    /// The chunk that holds the voxel in its padding
+    pub chunk_position: IVec3,
+
+    /// The voxel in the padding of that chunk
+    pub padding_voxel: WorldVoxel<I>,
+
+    /// The chunk the voxel belongs to
+    pub owner_position: IVec3,
+
+    /// The voxel in the chunk it belongs to
+    pub owner_voxel: WorldVoxel<I>,
+
+    /// What the voxel should be. This is the modified voxel if it was changed with `set_voxel`,
+    /// and otherwise what the lookup delegate returns for it now.
+    pub expected_voxel: WorldVoxel<I>,
+}
+
+/// The most recent border mismatches found by `VoxelWorldBorderValidationPlugin`
+#[derive(Resource)]
+pub struct BorderMismatches<C: VoxelWorldConfig> {
+    pub mismatches: VecDeque<BorderMismatch<C::MaterialIndex>>,
+}
+
+impl<C: VoxelWorldConfig> Default for BorderMismatches<C> {
+    fn default() -> Self {
+        Self {
+            mismatches: VecDeque::new(),
+        }
+    }
+}
+
+const FACE_DIRECTIONS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+#[allow(clippy::too_many_arguments)]
+fn validate_chunk_borders<C: VoxelWorldConfig>(
+    mut ev_chunk_will_spawn: EventReader<ChunkWillSpawn<C>>,
+    mut ev_chunk_will_despawn: EventReader<ChunkWillDespawn<C>>,
+    mut generated: Local<HashSet<IVec3>>,
+    mut border_mismatches: ResMut<BorderMismatches<C>>,
+    chunks: Query<&Chunk<C>>,
+    chunk_map: Res<ChunkMap<C, C::MaterialIndex>>,
+    modified_voxels: Res<ModifiedVoxels<C, C::MaterialIndex>>,
+    configuration: Res<C>,
+) {
+    for event in ev_chunk_will_despawn.read() {
+        generated.remove(&event.chunk_key);
+    }
+
+    // Chunks only hold their voxels once they have been generated
+    let spawned: Vec<IVec3> = ev_chunk_will_spawn
+        .read()
+        .map(|event| event.chunk_key)
+        .collect();
+    generated.extend(spawned.iter().copied());
+
+    let read_lock = chunk_map.get_read_lock();
+    let full_detail = |chunk_data: &ChunkData<C::MaterialIndex>| {
+        chunks
+            .get(chunk_data.entity)
+            .is_ok_and(|chunk| chunk.lod_level == 0)
+    };
+
+    for chunk_position in spawned {
+        let Some(chunk_data) = ChunkMap::<C, C::MaterialIndex>::get(&chunk_position, &read_lock)
+        else {
+            continue;
+        };
+        if !full_detail(&chunk_data) {
+            continue;
+        }
+
+        for direction in FACE_DIRECTIONS {
+            let neighbor_position = chunk_position + direction;
+            if !generated.contains(&neighbor_position) {
+                continue;
+            }
+            let Some(neighbor_data) =
+                ChunkMap::<C, C::MaterialIndex>::get(&neighbor_position, &read_lock)
+            else {
+                continue;
+            };
+            if !full_detail(&neighbor_data) {
+                continue;
+            }
+
+            let mut mismatches = border_layer_mismatches(&chunk_data, &neighbor_data, direction);
+            mismatches.extend(border_layer_mismatches(
+                &neighbor_data,
+                &chunk_data,
+                -direction,
+            ));
+            if mismatches.is_empty() {
+                continue;
+            }
+
+            warn!(
+                "Chunks {} and {} disagree about {} border voxels, the first one at {}",
+                chunk_position,
+                neighbor_position,
+                mismatches.len(),
+                mismatches[0].position
+            );
+
+            for mut mismatch in mismatches {
+                mismatch.expected_voxel = modified_voxels
+                    .get_voxel(&mismatch.position)
+                    .unwrap_or_else(|| {
+                        (configuration.voxel_lookup_delegate())(mismatch.owner_position)(
+                            mismatch.position,
+                        )
+                    });
+
+                if border_mismatches.mismatches.len() == MAX_BORDER_MISMATCHES {
+                    border_mismatches.mismatches.pop_front();
+                }
+                border_mismatches.mismatches.push_back(mismatch);
+            }
+        }
+    }
+}
+
+/// Compare the padding of `chunk` towards `direction` with the voxels of `owner`, the neighbor
+/// in that direction. The expected voxel of the mismatches is left for the caller to fill in.
+fn border_layer_mismatches<I: Hash + Copy + PartialEq>(
+    chunk: &ChunkData<I>,
+    owner: &ChunkData<I>,
+    direction: IVec3,
+) -> Vec<BorderMismatch<I>> {
+    let padded_min = |chunk_data: &ChunkData<I>| chunk_data.position * CHUNK_SIZE_I - 1;
+    let axis = (0..3).find(|&axis| direction[axis] != 0).unwrap();
+
+    // The layer of voxels just outside of the chunk, in the given direction
+    let layer = if direction[axis] > 0 {
+        CHUNK_SIZE_I
+    } else {
+        -1
+    };
+
+    let mut mismatches = Vec::new();
+    for u in 0..CHUNK_SIZE_I {
+        for v in 0..CHUNK_SIZE_I {
+            let mut local = IVec3::splat(layer);
+            local[(axis + 1) % 3] = u;
+            local[(axis + 2) % 3] = v;
+            let position = chunk.position * CHUNK_SIZE_I + local;
+
+            let padding_voxel = chunk.get_voxel((position - padded_min(chunk)).as_uvec3());
+            let owner_voxel = owner.get_voxel((position - padded_min(owner)).as_uvec3());
+
+            // Unset and air both count as empty
+            let matches = padding_voxel == owner_voxel
+                || (!padding_voxel.is_solid() && !owner_voxel.is_solid());
+            if !matches {
+                mismatches.push(BorderMismatch {
+                    position,
+                    chunk_position: chunk.position,
+                    padding_voxel,
+                    owner_position: owner.position,
+                    owner_voxel,
+                    expected_voxel: WorldVoxel::Unset,
+                });
+            }
+        }
+    }
+    mismatches
+}
@@ -1,12 +1,28 @@
-use bevy::{prelude::*, render::primitives::Aabb, tasks::Task, utils::HashSet};
-use ndshape::{ConstShape, ConstShape3u32};
+use bevy::{
+    prelude::*,
+    render::primitives::Aabb,
+    tasks::Task,
+    utils::{HashMap, HashSet, Instant},
+};
+use futures_lite::future;
+use ndshape::ConstShape;
 use std::{
     hash::{Hash, Hasher},
+    io,
     marker::PhantomData,
     sync::Arc,
 };
 
-use crate::{meshing, voxel::WorldVoxel, voxel_world_internal::ModifiedVoxels};
+use crate::{
+    chunk_collider::{self, ChunkColliderShape},
+    chunk_shape::{ChunkShape, ConstChunkShape},
+    chunk_storage::ChunkVoxels,
+    configuration::{ChunkColliderStrategy, ChunkDecoratorFn, MaterialGroup, MeshingAlgorithm},
+    meshing::{self, MeshingOptions},
+    voxel::WorldVoxel,
+    voxel_lighting::{lit_bounds, ChunkLight},
+    voxel_world_internal::ModifiedVoxels,
+};
 
 // The size of a chunk in voxels
 // TODO: implement a way to change this though the configuration
@@ -16,20 +32,69 @@ pub const CHUNK_SIZE_F: f32 = CHUNK_SIZE_U as f32;
 
 // A chunk with 1-voxel boundary padding.
 pub(crate) const PADDED_CHUNK_SIZE: u32 = CHUNK_SIZE_U + 2;
-pub(crate) type PaddedChunkShape =
-    ConstShape3u32<PADDED_CHUNK_SIZE, PADDED_CHUNK_SIZE, PADDED_CHUNK_SIZE>;
+/// The shape of the chunks the world spawns, and the default shape of `ChunkData`
+pub type PaddedChunkShape = ConstChunkShape<CHUNK_SIZE_U>;
+
+/// The highest number of LOD levels. At the last level a chunk is a single voxel.
+pub(crate) const MAX_LOD_LEVELS: u8 = CHUNK_SIZE_U.trailing_zeros() as u8 + 1;
+
+/// Every voxel of a chunk and of the one voxel thick padding around it
+pub type VoxelArray<I> = [WorldVoxel<I>; PaddedChunkShape::SIZE as usize];
 
-pub(crate) type VoxelArray<I> = [WorldVoxel<I>; PaddedChunkShape::SIZE as usize];
+// The default material index leaves no niche, so each voxel takes two bytes.
+crate::assert_voxel_size!(u8, 2);
 
 #[derive(Component)]
 #[component(storage = "SparseSet")]
-pub(crate) struct ChunkThread<C, I>(pub Task<ChunkTask<C, I>>, PhantomData<C>);
+pub(crate) struct ChunkThread<C, I>(pub ChunkJob<C, I>, pub Instant, PhantomData<C>);
 
 impl<C, I> ChunkThread<C, I>
 where
     C: Send + Sync + 'static,
 {
-    pub fn new(task: Task<ChunkTask<C, I>>, _pos: IVec3) -> Self {
+    pub fn new(job: ChunkJob<C, I>, _pos: IVec3) -> Self {
+        Self(job, Instant::now(), PhantomData)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        match &self.0 {
+            ChunkJob::Running(task) => task.is_finished(),
+            ChunkJob::Done(_) => true,
+        }
+    }
+
+    /// Take the finished chunk task, or `None` if it is still running
+    pub fn poll(&mut self) -> Option<ChunkTask<C, I>> {
+        match &mut self.0 {
+            ChunkJob::Running(task) => future::block_on(future::poll_once(task)),
+            ChunkJob::Done(chunk_task) => chunk_task.take().map(|chunk_task| *chunk_task),
+        }
+    }
+
+    /// Wait for the chunk task to finish, and take it
+    pub fn block(&mut self) -> ChunkTask<C, I> {
+        match &mut self.0 {
+            ChunkJob::Running(task) => future::block_on(task),
+            ChunkJob::Done(chunk_task) => *chunk_task.take().expect("chunk task was taken before"),
+        }
+    }
+}
+
+/// The work of a `ChunkThread`: a task on the task pool, or a chunk task that was already run on
+/// the main thread, like the ones requested with `VoxelWorld::generate_now`
+pub(crate) enum ChunkJob<C, I> {
+    Running(Task<ChunkTask<C, I>>),
+    Done(Option<Box<ChunkTask<C, I>>>),
+}
+
+/// A task that bakes new light into copies of the meshes of a chunk, for each mesh entity of the
+/// chunk. `None` if the lookup panicked.
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub(crate) struct RelightThread<C>(pub Task<Option<Vec<(Entity, Mesh)>>>, PhantomData<C>);
+
+impl<C> RelightThread<C> {
+    pub fn new(task: Task<Option<Vec<(Entity, Mesh)>>>) -> Self {
         Self(task, PhantomData)
     }
 }
@@ -38,10 +103,227 @@ where
 #[component(storage = "SparseSet")]
 pub struct NeedsRemesh;
 
+/// Marks chunks whose voxels didn't change, but that light from changed voxels around them
+/// reaches. These only get new light baked into their meshes, see `Internals::relight_chunks`.
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub(crate) struct NeedsRelight;
+
 #[derive(Component)]
 pub struct NeedsDespawn;
 
+/// Marks chunks that are out of range, but hidden instead of despawned because they are within
+/// `VoxelWorldConfig::soft_despawn_distance`. Hidden chunks are not remeshed until they are
+/// back in range. Chunks waiting to be despawned, with `NeedsDespawn`, are hidden too.
+#[derive(Component)]
+pub struct HiddenChunk;
+
+/// The parent entity of the chunks in a region, with `ChunkHierarchy::Regions`. `position` is the
+/// chunk position of the region divided by the region size.
+#[derive(Component)]
+pub struct ChunkRegion<C> {
+    pub position: IVec3,
+    _marker: PhantomData<C>,
+}
+
+impl<C> ChunkRegion<C> {
+    pub(crate) fn new(position: IVec3) -> Self {
+        Self {
+            position,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Progress of the `ChunkSpawnTransition` of a chunk that just got its first mesh, from 0.0 to 1.0
+#[derive(Component)]
+pub(crate) struct SpawnTransition<C> {
+    pub progress: f32,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for SpawnTransition<C> {
+    fn default() -> Self {
+        Self {
+            progress: 0.0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Marks chunks queued by the startup pre-seed. These get meshed synchronously instead of
+/// being polled over several frames.
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub(crate) struct PreseedChunk;
+
+/// Marks chunks in a region passed to `VoxelWorld::generate_now`. They skip the task budgets,
+/// are waited for within the per-frame budget of the region, and are not despawned or moved to
+/// another level of detail while the region is requested.
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub(crate) struct GenerateNowChunk;
+
+/// A non-voxel prop placed in a chunk by `VoxelWorldConfig::chunk_decorator`
 #[derive(Clone, Debug)]
+pub struct Decoration {
+    /// User defined kind of decoration, for example an index into a list of prop meshes
+    pub kind: u32,
+    /// Transform of the decoration, in world space
+    pub transform: Transform,
+}
+
+impl Decoration {
+    pub fn new(kind: u32, position: Vec3) -> Self {
+        Self {
+            kind,
+            transform: Transform::from_translation(position),
+        }
+    }
+}
+
+/// Marks a decoration entity spawned from a `Decoration`. Decoration entities are children of
+/// their chunk, so they get despawned along with it.
+#[derive(Component, Clone, Debug)]
+pub struct ChunkDecoration<C> {
+    pub kind: u32,
+    pub chunk_position: IVec3,
+    _marker: PhantomData<C>,
+}
+
+impl<C> ChunkDecoration<C> {
+    pub fn new(kind: u32, chunk_position: IVec3) -> Self {
+        Self {
+            kind,
+            chunk_position,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// The mesh of one material group in a chunk, other than `MaterialGroup::Opaque`, when a
+/// `VoxelWorldConfig::material_group` hook is configured. The entity is a child of the chunk, and
+/// gets replaced whenever the chunk is remeshed.
+#[derive(Component, Clone, Debug)]
+pub struct ChunkGroupMesh<C> {
+    pub group: MaterialGroup,
+    pub chunk_position: IVec3,
+    _marker: PhantomData<C>,
+}
+
+impl<C> ChunkGroupMesh<C> {
+    pub fn new(group: MaterialGroup, chunk_position: IVec3) -> Self {
+        Self {
+            group,
+            chunk_position,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// The shadow-only mesh of a chunk, when `VoxelWorldConfig::shadow_lod` is configured. The entity
+/// is a child of the chunk, and gets replaced whenever the chunk is remeshed.
+#[derive(Component, Clone, Debug)]
+pub struct ChunkShadowMesh<C> {
+    pub lod_level: u8,
+    pub chunk_position: IVec3,
+    _marker: PhantomData<C>,
+}
+
+impl<C> ChunkShadowMesh<C> {
+    pub fn new(lod_level: u8, chunk_position: IVec3) -> Self {
+        Self {
+            lod_level,
+            chunk_position,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Marks the placeholder that debug builds show in place of a chunk that failed to generate,
+/// because a delegate or mapper panicked. The entity is a child of the chunk, and gets despawned
+/// when the chunk is remeshed.
+#[derive(Component, Clone, Debug)]
+pub struct ChunkErrorPlaceholder<C> {
+    pub chunk_position: IVec3,
+    _marker: PhantomData<C>,
+}
+
+impl<C> ChunkErrorPlaceholder<C> {
+    pub fn new(chunk_position: IVec3) -> Self {
+        Self {
+            chunk_position,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Marks the placeholder shown for a chunk while its first mesh is being built, when
+/// `VoxelWorldConfig::chunk_placeholder` is set. The entity is a child of the chunk, and gets
+/// despawned when the chunk task finishes.
+#[derive(Component, Clone, Debug)]
+pub struct ChunkLoadingPlaceholder<C> {
+    pub chunk_position: IVec3,
+    _marker: PhantomData<C>,
+}
+
+impl<C> ChunkLoadingPlaceholder<C> {
+    pub fn new(chunk_position: IVec3) -> Self {
+        Self {
+            chunk_position,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// All decorations of one kind in a chunk, when `VoxelWorldConfig::decoration_instancing` is
+/// enabled. The entity is a child of the chunk. Once a prop mesh for its kind is registered in
+/// `DecorationMeshes`, it draws that mesh at every transform in a single instanced draw call.
+#[derive(Component, Clone, Debug)]
+pub struct ChunkDecorationInstances<C> {
+    pub kind: u32,
+    pub chunk_position: IVec3,
+    /// Instance transforms, relative to the chunk
+    pub transforms: Vec<Transform>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> ChunkDecorationInstances<C> {
+    pub fn new(kind: u32, chunk_position: IVec3, transforms: Vec<Transform>) -> Self {
+        Self {
+            kind,
+            chunk_position,
+            transforms,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Prop meshes to use for instanced decorations, by decoration kind. The meshes are shared by all
+/// chunks. Instances are drawn with the vertex colors of the prop mesh (white if it has none),
+/// lit by the ambient light and the directional lights of the scene, and don't cast shadows.
+#[derive(Resource)]
+pub struct DecorationMeshes<C> {
+    pub meshes: HashMap<u32, Handle<Mesh>>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> DecorationMeshes<C> {
+    pub fn insert(&mut self, kind: u32, mesh: Handle<Mesh>) {
+        self.meshes.insert(kind, mesh);
+    }
+}
+
+impl<C> Default for DecorationMeshes<C> {
+    fn default() -> Self {
+        Self {
+            meshes: HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum FillType<I> {
     Empty,
     Mixed,
@@ -50,19 +332,36 @@ pub enum FillType<I> {
 
 /// This is used to lookup voxel data from spawned chunks. Does not persist after
 /// the chunk is despawned.
+///
+/// The voxels are laid out by `shape`, which defaults to the `CHUNK_SIZE_U` chunks the world
+/// spawns. A `ConstChunkShape` of another size gets the same constant indexing, while a
+/// `DynamicChunkShape` picks the chunk size at runtime.
 #[derive(Clone, Debug)]
-pub struct ChunkData<I> {
+pub struct ChunkData<I, S = PaddedChunkShape> {
     pub position: IVec3,
-    pub voxels: Option<Arc<VoxelArray<I>>>,
+    pub voxels: Option<Arc<ChunkVoxels<I>>>,
     pub voxels_hash: u64,
     pub is_full: bool,
     pub is_empty: bool,
     pub fill_type: FillType<I>,
     pub entity: Entity,
+    pub shape: S,
 }
 
-impl<I: Hash + Copy> ChunkData<I> {
+impl<I: Hash + Copy, S: ChunkShape + Default> ChunkData<I, S> {
     pub fn new() -> Self {
+        Self::with_shape(S::default())
+    }
+
+    pub fn with_entity(entity: Entity) -> Self {
+        let new = Self::new();
+        Self { entity, ..new }
+    }
+}
+
+impl<I: Hash + Copy, S: ChunkShape> ChunkData<I, S> {
+    /// An empty chunk with the given shape
+    pub fn with_shape(shape: S) -> Self {
         Self {
             position: IVec3::ZERO,
             voxels: None,
@@ -71,14 +370,10 @@ impl<I: Hash + Copy> ChunkData<I> {
             is_empty: true,
             fill_type: FillType::Empty,
             entity: Entity::PLACEHOLDER,
+            shape,
         }
     }
 
-    pub fn with_entity(entity: Entity) -> Self {
-        let new = Self::new();
-        Self { entity, ..new }
-    }
-
     pub fn generate_hash(&mut self) {
         if let Some(voxels) = &self.voxels {
             let mut hasher = std::collections::hash_map::DefaultHasher::new();
@@ -87,9 +382,20 @@ impl<I: Hash + Copy> ChunkData<I> {
         }
     }
 
+    /// Whether the chunk holds every voxel, instead of one voxel for each block of voxels like
+    /// chunks above LOD level 0 do
+    pub fn is_full_detail(&self) -> bool {
+        !matches!(self.voxels.as_deref(), Some(ChunkVoxels::Sampled { .. }))
+    }
+
     pub fn get_voxel(&self, position: UVec3) -> WorldVoxel<I> {
-        if self.voxels.is_some() {
-            self.voxels.as_ref().unwrap()[PaddedChunkShape::linearize(position.to_array()) as usize]
+        self.get_voxel_at_index(self.shape.linearize(position.to_array()) as usize)
+    }
+
+    /// The voxel at the given index of the padded chunk array
+    pub fn get_voxel_at_index(&self, index: usize) -> WorldVoxel<I> {
+        if let Some(voxels) = &self.voxels {
+            voxels.get(index)
         } else {
             match self.fill_type {
                 FillType::Uniform(voxel) => voxel,
@@ -100,12 +406,12 @@ impl<I: Hash + Copy> ChunkData<I> {
     }
 
     pub fn world_position(&self) -> Vec3 {
-        self.position.as_vec3() * CHUNK_SIZE_F
+        self.shape.chunk_min(self.position).as_vec3()
     }
 
     pub fn aabb(&self) -> Aabb {
         let min = Vec3::ZERO;
-        let max = min + Vec3::splat(CHUNK_SIZE_F);
+        let max = min + Vec3::splat(self.shape.chunk_size() as f32);
         Aabb::from_min_max(min, max)
     }
 
@@ -123,7 +429,85 @@ impl<I: Hash + Copy> ChunkData<I> {
     }
 }
 
-impl<I: Hash + Copy> Default for ChunkData<I> {
+impl<I: Hash + Copy + Eq, S: ChunkShape> ChunkData<I, S> {
+    /// The voxels that are different in `other`, as indices into the padded chunk array with the
+    /// voxel of `other`. Applying the diff to this chunk with `apply_diff` gives the voxels of
+    /// `other`, which makes it a small way to send chunk changes over the network or to keep undo
+    /// steps. Chunks sharing their voxels are not compared at all, and paletted voxels are
+    /// compared a word at a time.
+    pub fn diff(&self, other: &ChunkData<I, S>) -> Vec<(usize, WorldVoxel<I>)> {
+        match (&self.voxels, &other.voxels) {
+            (Some(voxels), Some(other_voxels)) if Arc::ptr_eq(voxels, other_voxels) => Vec::new(),
+            (Some(voxels), Some(other_voxels)) => voxels.diff(other_voxels),
+            (None, None) if self.fill_type == other.fill_type => Vec::new(),
+            _ => (0..self.shape.usize())
+                .filter_map(|i| {
+                    let other_voxel = other.get_voxel_at_index(i);
+                    (self.get_voxel_at_index(i) != other_voxel).then_some((i, other_voxel))
+                })
+                .collect(),
+        }
+    }
+
+    /// Set the voxels of a diff made with `diff`, updating the fill type and the hash. A diff with
+    /// an index outside of the padded chunk array, like one garbled on its way over the network,
+    /// is an `InvalidData` error, and leaves the chunk as it was.
+    pub fn apply_diff(&mut self, diff: &[(usize, WorldVoxel<I>)]) -> io::Result<()> {
+        if diff.is_empty() {
+            return Ok(());
+        }
+        if diff.iter().any(|(i, _)| *i >= self.shape.usize()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "diff index outside of the chunk",
+            ));
+        }
+
+        let mut voxels: Vec<WorldVoxel<I>> = (0..self.shape.usize())
+            .map(|i| self.get_voxel_at_index(i))
+            .collect();
+        for &(i, voxel) in diff {
+            voxels[i] = voxel;
+        }
+        self.set_voxels(&voxels);
+        Ok(())
+    }
+
+    /// Replace all voxels of the chunk, updating the fill type and the hash. There must be one
+    /// voxel for each index of `shape`, like a `VoxelArray` for the default shape.
+    pub fn set_voxels(&mut self, voxels: &[WorldVoxel<I>]) {
+        assert_eq!(
+            voxels.len(),
+            self.shape.usize(),
+            "voxels don't fit the chunk"
+        );
+        let mut materials = HashSet::new();
+        for voxel in voxels.iter() {
+            if let WorldVoxel::Solid(material) = voxel {
+                materials.insert(*material);
+            }
+        }
+        let filled_count = voxels.iter().filter(|voxel| voxel.is_solid()).count();
+
+        self.is_empty = filled_count == 0;
+        self.is_full = filled_count == voxels.len();
+        if self.is_full && materials.len() == 1 {
+            self.fill_type = FillType::Uniform(voxels[0]);
+            self.voxels = None;
+        } else if filled_count > 0 {
+            self.fill_type = FillType::Mixed;
+            self.voxels = Some(Arc::new(ChunkVoxels::from_dense(voxels)));
+        } else {
+            self.fill_type = FillType::Empty;
+            self.voxels = None;
+        }
+
+        self.voxels_hash = 0;
+        self.generate_hash();
+    }
+}
+
+impl<I: Hash + Copy, S: ChunkShape + Default> Default for ChunkData<I, S> {
     fn default() -> Self {
         Self::new()
     }
@@ -134,6 +518,8 @@ impl<I: Hash + Copy> Default for ChunkData<I> {
 pub struct Chunk<C> {
     pub position: IVec3,
     pub entity: Entity,
+    /// The level of detail the chunk is generated at. See `VoxelWorldConfig::lod_levels`.
+    pub lod_level: u8,
     _marker: PhantomData<C>,
 }
 
@@ -142,6 +528,7 @@ impl<C> Chunk<C> {
         Self {
             position,
             entity,
+            lod_level: 0,
             _marker: PhantomData,
         }
     }
@@ -150,6 +537,7 @@ impl<C> Chunk<C> {
         Self {
             position: chunk.position,
             entity: chunk.entity,
+            lod_level: chunk.lod_level,
             _marker: PhantomData,
         }
     }
@@ -166,8 +554,25 @@ impl<C> Chunk<C> {
 pub(crate) struct ChunkTask<C, I> {
     pub position: IVec3,
     pub chunk_data: ChunkData<I>,
+    /// The uncompressed voxels of mixed chunks, for meshing. These are dropped with the task,
+    /// and only the compact `chunk_data.voxels` are kept in the chunk map.
+    pub voxels: Option<Arc<VoxelArray<I>>>,
     pub modified_voxels: ModifiedVoxels<C, I>,
     pub mesh: Option<Mesh>,
+    pub mesh_aabb: Option<Aabb>,
+    /// Meshes for the material groups other than `MaterialGroup::Opaque`, when a `material_group`
+    /// hook is configured
+    pub group_meshes: Vec<(MaterialGroup, Mesh)>,
+    /// The decimated mesh that casts the shadows of the chunk, when `shadow_lod` is configured
+    pub shadow_mesh: Option<Mesh>,
+    pub mesh_cache_key: u64,
+    pub decorations: Option<Vec<Decoration>>,
+    pub collider: Option<ChunkColliderShape>,
+    pub lod_level: u8,
+    /// The panic message, when a delegate or mapper panicked while the chunk was generated
+    pub error: Option<String>,
+    /// The light around the chunk, when `voxel_lighting` is configured
+    pub light: Option<ChunkLight>,
     _marker: PhantomData<C>,
 }
 
@@ -176,8 +581,18 @@ impl<C: Send + Sync + 'static, I: Hash + Copy + Eq> ChunkTask<C, I> {
         Self {
             position,
             chunk_data: ChunkData::with_entity(entity),
+            voxels: None,
             modified_voxels,
             mesh: None,
+            mesh_aabb: None,
+            group_meshes: Vec::new(),
+            shadow_mesh: None,
+            mesh_cache_key: 0,
+            decorations: None,
+            collider: None,
+            lod_level: 0,
+            error: None,
+            light: None,
             _marker: PhantomData,
         }
     }
@@ -185,25 +600,49 @@ impl<C: Send + Sync + 'static, I: Hash + Copy + Eq> ChunkTask<C, I> {
     /// Generate voxel data for the chunk. The supplied `modified_voxels` map is first checked,
     /// and where no voxeles are modified, the `voxel_data_fn` is called to get data from the
     /// consumer.
+    ///
+    /// Above LOD level 0, the modified voxels and the `voxel_data_fn` are only sampled once for
+    /// every block of `2^lod_level` voxels along each axis, and the whole block gets the sampled
+    /// voxel. The chunk data then only keeps one voxel per block.
     pub fn generate<F>(&mut self, mut voxel_data_fn: F)
     where
-        F: FnMut(IVec3) -> WorldVoxel<I> + Send + 'static,
+        F: FnMut(IVec3) -> WorldVoxel<I>,
     {
         let mut filled_count = 0;
-        let modified_voxels = (*self.modified_voxels).read().unwrap();
         let mut voxels = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
         let mut material_count = HashSet::new();
+        let stride = IVec3::splat(1 << self.lod_level);
+        let mut lod_samples = HashMap::new();
 
-        for i in 0..PaddedChunkShape::SIZE {
+        let block_position = |i: u32| {
             let chunk_block = PaddedChunkShape::delinearize(i);
-
-            let block_pos = IVec3 {
+            IVec3 {
                 x: chunk_block[0] as i32 + (self.position.x * CHUNK_SIZE_I) - 1,
                 y: chunk_block[1] as i32 + (self.position.y * CHUNK_SIZE_I) - 1,
                 z: chunk_block[2] as i32 + (self.position.z * CHUNK_SIZE_I) - 1,
-            };
+            }
+        };
+
+        // Snap to the world grid, so that neighboring chunks sample the same positions
+        let sample_position = |position: IVec3| position.div_euclid(stride) * stride;
+
+        // Copy the modified voxels first, so the lock isn't held while the delegate runs. Above
+        // LOD level 0 they are sampled like the rest, so that each block stays uniform.
+        let modified_voxels: Vec<Option<WorldVoxel<I>>> = {
+            let modified_voxels = (*self.modified_voxels).read().unwrap();
+            (0..PaddedChunkShape::SIZE)
+                .map(|i| {
+                    modified_voxels
+                        .get(&sample_position(block_position(i)))
+                        .copied()
+                })
+                .collect()
+        };
 
-            if let Some(voxel) = modified_voxels.get(&block_pos) {
+        for i in 0..PaddedChunkShape::SIZE {
+            let block_pos = block_position(i);
+
+            if let Some(voxel) = &modified_voxels[i as usize] {
                 voxels[i as usize] = *voxel;
                 if !voxel.is_unset() && !voxel.is_air() {
                     filled_count += 1;
@@ -211,7 +650,14 @@ impl<C: Send + Sync + 'static, I: Hash + Copy + Eq> ChunkTask<C, I> {
                 continue;
             }
 
-            let voxel = voxel_data_fn(block_pos);
+            let voxel = if self.lod_level == 0 {
+                voxel_data_fn(block_pos)
+            } else {
+                let sample_pos = sample_position(block_pos);
+                *lod_samples
+                    .entry(sample_pos)
+                    .or_insert_with(|| voxel_data_fn(sample_pos))
+            };
 
             voxels[i as usize] = voxel;
 
@@ -227,28 +673,198 @@ impl<C: Send + Sync + 'static, I: Hash + Copy + Eq> ChunkTask<C, I> {
         if self.chunk_data.is_full && material_count.len() == 1 {
             self.chunk_data.fill_type = FillType::Uniform(voxels[0]);
             self.chunk_data.voxels = None;
+            self.voxels = None;
         } else if filled_count > 0 {
             self.chunk_data.fill_type = FillType::Mixed;
-            self.chunk_data.voxels = Some(Arc::new(voxels));
+            self.chunk_data.voxels = Some(Arc::new(if self.lod_level > 0 {
+                ChunkVoxels::sampled(
+                    &voxels,
+                    &self.chunk_data.shape,
+                    self.position,
+                    self.lod_level,
+                )
+            } else {
+                ChunkVoxels::from_dense(&voxels)
+            }));
+            self.voxels = Some(Arc::new(voxels));
         } else {
             self.chunk_data.fill_type = FillType::Empty;
             self.chunk_data.voxels = None;
+            self.voxels = None;
         };
 
         self.chunk_data.generate_hash();
     }
 
-    /// Generate a mesh for the chunk based on the currect voxel data
-    pub fn mesh(&mut self, texture_index_mapper: Arc<dyn Fn(I) -> [u32; 3] + Send + Sync>) {
-        if self.mesh.is_none() && self.chunk_data.voxels.is_some() {
-            self.mesh = Some(meshing::generate_chunk_mesh(
-                self.chunk_data.voxels.as_ref().unwrap().clone(),
-                self.position,
-                texture_index_mapper,
-            ));
+    /// Flood-fill the light in and around the chunk, when `voxel_lighting` is configured. The
+    /// voxels of the padded chunk are already known, and the voxels around it come from the
+    /// modified voxels, or from `voxel_data_fn`. Chunks above LOD level 0 are not lit.
+    pub fn light<F>(&mut self, meshing_options: &MeshingOptions<I>, mut voxel_data_fn: F)
+    where
+        F: FnMut(IVec3) -> WorldVoxel<I>,
+    {
+        let (Some(lighting), Some(emission), Some(voxels), 0) = (
+            meshing_options.lighting,
+            &meshing_options.voxel_emission,
+            &self.voxels,
+            self.lod_level,
+        ) else {
+            return;
+        };
+
+        let padded_min = self.position * CHUNK_SIZE_I - IVec3::ONE;
+        let padded_max = padded_min + IVec3::splat(PADDED_CHUNK_SIZE as i32 - 1);
+        let (lit_min, lit_max) = lit_bounds(self.position, lighting);
+
+        // Copy the modified voxels around the chunk first, so the lock isn't held while the
+        // delegate runs
+        let modified_voxels = modified_voxels_within(&self.modified_voxels, lit_min, lit_max);
+
+        self.light = Some(ChunkLight::compute(
+            self.position,
+            |position| {
+                if position.cmpge(padded_min).all() && position.cmple(padded_max).all() {
+                    let local = (position - padded_min).as_uvec3().to_array();
+                    voxels[PaddedChunkShape::linearize(local) as usize]
+                } else if let Some(voxel) = modified_voxels.get(&position) {
+                    *voxel
+                } else {
+                    voxel_data_fn(position)
+                }
+            },
+            meshing_options,
+            emission.as_ref(),
+            lighting,
+        ));
+    }
+
+    /// Generate a mesh for the chunk based on the currect voxel data. Chunks above LOD level 0
+    /// are made of large uniform blocks, so these always use the greedy mesher. The bounds of the
+    /// mesh are calculated here as well, so that the main thread only has to add the mesh asset.
+    pub fn mesh(&mut self, meshing_options: &MeshingOptions<I>) {
+        if let (None, Some(voxels)) = (&self.mesh, &self.voxels) {
+            let mut meshing_options = meshing_options.clone();
+            if self.lod_level > 0 {
+                meshing_options.algorithm = MeshingAlgorithm::Greedy;
+                meshing_options.voxel_shape = None;
+            }
+            if let Some(material_group) = &meshing_options.material_group {
+                let mut group_meshes = meshing::generate_chunk_group_meshes(
+                    voxels.clone(),
+                    &self.chunk_data.shape,
+                    self.position,
+                    &meshing_options,
+                    material_group,
+                );
+                // The opaque group always comes first, and goes into the chunk entity
+                self.mesh = Some(group_meshes.remove(0).1);
+                self.group_meshes = group_meshes;
+            } else {
+                self.mesh = Some(meshing::generate_chunk_mesh(
+                    voxels.clone(),
+                    &self.chunk_data.shape,
+                    self.position,
+                    &meshing_options,
+                ));
+            }
+
+            if let Some(light) = &self.light {
+                let group_meshes = self.group_meshes.iter_mut().map(|(_, mesh)| mesh);
+                for mesh in self.mesh.iter_mut().chain(group_meshes) {
+                    light.apply(mesh);
+                }
+            }
+
+            // The bounds need the positions before they are compacted
+            self.mesh_aabb = self.mesh.as_ref().and_then(Mesh::compute_aabb);
+
+            #[cfg(feature = "compact_vertices")]
+            if meshing_options.compact_vertices {
+                let group_meshes = self.group_meshes.iter_mut().map(|(_, mesh)| mesh);
+                for mesh in self.mesh.iter_mut().chain(group_meshes) {
+                    meshing::compact_vertex_attributes(mesh);
+                }
+            }
         }
     }
 
+    /// Generate the shadow mesh of the chunk at `lod_level`, by taking one voxel for every block
+    /// of `2^lod_level` voxels along each axis, snapped to the world grid like `generate` does.
+    /// Only voxels in the opaque material group cast shadows through it. Chunks that are already
+    /// at `lod_level` or above cast their own shadows, and get no shadow mesh.
+    pub fn mesh_shadow(&mut self, meshing_options: &MeshingOptions<I>, lod_level: u8) {
+        let Some(voxels) = &self.voxels else {
+            return;
+        };
+        if self.lod_level >= lod_level || self.shadow_mesh.is_some() {
+            return;
+        }
+
+        let stride = IVec3::splat(1 << lod_level.min(MAX_LOD_LEVELS - 1));
+        let padded_min = self.position * CHUNK_SIZE_I - 1;
+        let mut shadow_voxels = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
+        for (i, shadow_voxel) in shadow_voxels.iter_mut().enumerate() {
+            let local = UVec3::from(PaddedChunkShape::delinearize(i as u32)).as_ivec3();
+            // Blocks reaching past the padding are sampled at the edge of the padding instead
+            let sample = ((padded_min + local).div_euclid(stride) * stride - padded_min)
+                .max(IVec3::ZERO)
+                .as_uvec3();
+            *shadow_voxel = match voxels[PaddedChunkShape::linearize(sample.into()) as usize] {
+                WorldVoxel::Solid(material)
+                    if meshing_options
+                        .material_group
+                        .as_ref()
+                        .is_some_and(|group| group(material) != MaterialGroup::Opaque) =>
+                {
+                    WorldVoxel::Air
+                }
+                voxel => voxel,
+            };
+        }
+
+        let mut meshing_options = meshing_options.clone();
+        meshing_options.algorithm = MeshingAlgorithm::Greedy;
+        meshing_options.voxel_shape = None;
+        self.shadow_mesh = Some(meshing::generate_chunk_mesh(
+            Arc::new(shadow_voxels),
+            &self.chunk_data.shape,
+            self.position,
+            &meshing_options,
+        ));
+    }
+
+    /// Place decorations based on the current voxel data. Decorations outside of the chunk
+    /// are dropped, so that the same spot in the padding of neighboring chunks doesn't get
+    /// decorated twice.
+    pub fn decorate(&mut self, decorator: &ChunkDecoratorFn<I>) {
+        let lookup = voxel_lookup(self.position, &self.chunk_data);
+
+        let chunk_min = self.position * CHUNK_SIZE_I;
+        let chunk_max = chunk_min + CHUNK_SIZE_I;
+        let decorations = decorator(self.position, &lookup)
+            .into_iter()
+            .filter(|decoration| {
+                let position = decoration.transform.translation.floor().as_ivec3();
+                position.cmpge(chunk_min).all() && position.cmplt(chunk_max).all()
+            })
+            .collect();
+
+        self.decorations = Some(decorations);
+    }
+
+    /// Build the collision shape of the chunk based on the current voxel data
+    pub fn build_collider(&mut self, strategy: &ChunkColliderStrategy<I>) {
+        self.collider = match strategy {
+            ChunkColliderStrategy::None => None,
+            ChunkColliderStrategy::Trimesh => chunk_collider::trimesh_shape(&self.chunk_data),
+            ChunkColliderStrategy::Cuboids => chunk_collider::cuboids_shape(&self.chunk_data),
+            ChunkColliderStrategy::Custom(collider_fn) => collider_fn(
+                self.position,
+                &voxel_lookup(self.position, &self.chunk_data),
+            ),
+        };
+    }
+
     pub fn is_empty(&self) -> bool {
         self.chunk_data.is_empty
     }
@@ -260,4 +876,67 @@ impl<C: Send + Sync + 'static, I: Hash + Copy + Eq> ChunkTask<C, I> {
     pub fn voxels_hash(&self) -> u64 {
         self.chunk_data.voxels_hash
     }
+
+    /// A hash of everything the chunk mesh is built from: the voxels, and the light if the chunk
+    /// is lit
+    pub fn mesh_hash(&self) -> u64 {
+        match &self.light {
+            Some(light) => {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                (self.voxels_hash(), light.light_hash()).hash(&mut hasher);
+                hasher.finish()
+            }
+            None => self.voxels_hash(),
+        }
+    }
+}
+
+/// A lookup for the voxels of the chunk at `chunk_position` and its padding, by world position
+fn voxel_lookup<I: Hash + Copy>(
+    chunk_position: IVec3,
+    chunk_data: &ChunkData<I>,
+) -> impl Fn(IVec3) -> WorldVoxel<I> + '_ {
+    let padded_min = chunk_position * CHUNK_SIZE_I - 1;
+    move |position: IVec3| {
+        let local = position - padded_min;
+        if local.cmplt(IVec3::ZERO).any()
+            || local.cmpge(IVec3::splat(PADDED_CHUNK_SIZE as i32)).any()
+        {
+            return WorldVoxel::Unset;
+        }
+        chunk_data.get_voxel(local.as_uvec3())
+    }
+}
+
+/// Copy the modified voxels within `min` and `max`, inclusive, looking only at the chunks that
+/// overlap them
+pub(crate) fn modified_voxels_within<C, I: Copy>(
+    modified_voxels: &ModifiedVoxels<C, I>,
+    min: IVec3,
+    max: IVec3,
+) -> HashMap<IVec3, WorldVoxel<I>> {
+    let modified_voxels = modified_voxels.read().unwrap();
+    let (min_chunk, max_chunk) = (
+        min.div_euclid(IVec3::splat(CHUNK_SIZE_I)),
+        max.div_euclid(IVec3::splat(CHUNK_SIZE_I)),
+    );
+    let mut within = HashMap::new();
+    for z in min_chunk.z..=max_chunk.z {
+        for y in min_chunk.y..=max_chunk.y {
+            for x in min_chunk.x..=max_chunk.x {
+                let Some(chunk_voxels) = modified_voxels.in_chunk(IVec3::new(x, y, z)) else {
+                    continue;
+                };
+                within.extend(
+                    chunk_voxels
+                        .iter()
+                        .filter(|(position, _)| {
+                            position.cmpge(min).all() && position.cmple(max).all()
+                        })
+                        .map(|(position, voxel)| (*position, *voxel)),
+                );
+            }
+        }
+    }
+    within
 }
@@ -0,0 +1,246 @@
+///
+/// Chunk colliders
+/// Collision shapes for chunks, built in the chunk threads for use with physics engines.
+///
+use std::{hash::Hash, marker::PhantomData};
+
+use bevy::prelude::*;
+use block_mesh::{
+    greedy_quads, GreedyQuadsBuffer, MergeVoxel, Voxel, VoxelVisibility, RIGHT_HANDED_Y_UP_CONFIG,
+};
+use ndshape::ConstShape;
+
+use crate::chunk::{ChunkData, PaddedChunkShape, CHUNK_SIZE_U};
+
+/// The collision shape of a chunk. Positions are relative to the chunk entity, the same as the
+/// positions of the chunk mesh.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChunkColliderShape {
+    /// A triangle mesh of the surface of the chunk
+    Trimesh {
+        vertices: Vec<Vec3>,
+        indices: Vec<[u32; 3]>,
+    },
+
+    /// Boxes covering the solid voxels of the chunk, as `(center, half_extents)`
+    Cuboids(Vec<(Vec3, Vec3)>),
+}
+
+/// The collision shape of a chunk, kept in sync whenever the chunk is remeshed. Added to chunk
+/// entities when `VoxelWorldConfig::chunk_collider_strategy` is set, and removed again when the
+/// chunk has nothing to collide with.
+///
+/// With the `bevy_rapier3d` feature enabled, a rapier `Collider` is attached from this
+/// automatically. For other physics engines, build colliders in a system that queries for
+/// `Changed<ChunkCollider<MyWorld>>`.
+#[derive(Component, Clone, Debug)]
+pub struct ChunkCollider<C> {
+    pub shape: ChunkColliderShape,
+    _marker: PhantomData<C>,
+}
+
+impl<C> ChunkCollider<C> {
+    pub fn new(shape: ChunkColliderShape) -> Self {
+        Self {
+            shape,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Only solidity matters for collisions, so every solid voxel merges with every other
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct ColliderVoxel(bool);
+
+impl Voxel for ColliderVoxel {
+    fn get_visibility(&self) -> VoxelVisibility {
+        if self.0 {
+            VoxelVisibility::Opaque
+        } else {
+            VoxelVisibility::Empty
+        }
+    }
+}
+
+impl MergeVoxel for ColliderVoxel {
+    type MergeValue = ();
+
+    fn merge_value(&self) -> Self::MergeValue {}
+}
+
+/// Build a triangle mesh of the surface of the chunk, merging coplanar faces. Returns `None` if
+/// the chunk has no visible surface.
+pub(crate) fn trimesh_shape<I: Hash + Copy + PartialEq>(
+    chunk_data: &ChunkData<I>,
+) -> Option<ChunkColliderShape> {
+    if chunk_data.is_empty || chunk_data.is_full {
+        return None;
+    }
+
+    let voxels: Vec<ColliderVoxel> = (0..PaddedChunkShape::SIZE)
+        .map(|i| {
+            let padded_pos = UVec3::from(PaddedChunkShape::delinearize(i));
+            ColliderVoxel(chunk_data.get_voxel(padded_pos).is_solid())
+        })
+        .collect();
+
+    let faces = RIGHT_HANDED_Y_UP_CONFIG.faces;
+    let mut buffer = GreedyQuadsBuffer::new(voxels.len());
+    greedy_quads(
+        &voxels,
+        &PaddedChunkShape {},
+        [0; 3],
+        [CHUNK_SIZE_U + 1; 3],
+        &faces,
+        &mut buffer,
+    );
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for (group, face) in buffer.quads.groups.iter().zip(faces) {
+        for quad in group {
+            let quad_indices = face.quad_mesh_indices(vertices.len() as u32);
+            indices.push([quad_indices[0], quad_indices[1], quad_indices[2]]);
+            indices.push([quad_indices[3], quad_indices[4], quad_indices[5]]);
+            vertices.extend(face.quad_mesh_positions(quad, 1.0).map(Vec3::from));
+        }
+    }
+
+    if indices.is_empty() {
+        return None;
+    }
+
+    Some(ChunkColliderShape::Trimesh { vertices, indices })
+}
+
+/// Cover the solid voxels of the chunk with as few boxes as a greedy search finds. Returns `None`
+/// if the chunk has no solid voxels.
+pub(crate) fn cuboids_shape<I: Hash + Copy + PartialEq>(
+    chunk_data: &ChunkData<I>,
+) -> Option<ChunkColliderShape> {
+    if chunk_data.is_empty {
+        return None;
+    }
+
+    let size = CHUNK_SIZE_U as usize;
+    let index = |x: usize, y: usize, z: usize| x + y * size + z * size * size;
+
+    // Only the voxels inside the chunk, the padding belongs to the neighbors
+    let mut solid = vec![false; size * size * size];
+    for z in 0..size {
+        for y in 0..size {
+            for x in 0..size {
+                let padded_pos = UVec3::new(x as u32, y as u32, z as u32) + 1;
+                solid[index(x, y, z)] = chunk_data.get_voxel(padded_pos).is_solid();
+            }
+        }
+    }
+
+    let mut cuboids = Vec::new();
+    for z in 0..size {
+        for y in 0..size {
+            for x in 0..size {
+                if !solid[index(x, y, z)] {
+                    continue;
+                }
+
+                // Grow the box along x, then y, then z, as long as it stays solid
+                let mut x1 = x + 1;
+                while x1 < size && solid[index(x1, y, z)] {
+                    x1 += 1;
+                }
+                let mut y1 = y + 1;
+                while y1 < size && (x..x1).all(|x| solid[index(x, y1, z)]) {
+                    y1 += 1;
+                }
+                let mut z1 = z + 1;
+                while z1 < size && (y..y1).all(|y| (x..x1).all(|x| solid[index(x, y, z1)])) {
+                    z1 += 1;
+                }
+
+                for bz in z..z1 {
+                    for by in y..y1 {
+                        for bx in x..x1 {
+                            solid[index(bx, by, bz)] = false;
+                        }
+                    }
+                }
+
+                let min = Vec3::new(x as f32, y as f32, z as f32) + 1.0;
+                let half_extents =
+                    Vec3::new((x1 - x) as f32, (y1 - y) as f32, (z1 - z) as f32) / 2.0;
+                cuboids.push((min + half_extents, half_extents));
+            }
+        }
+    }
+
+    Some(ChunkColliderShape::Cuboids(cuboids))
+}
+
+#[cfg(feature = "bevy_rapier3d")]
+impl ChunkColliderShape {
+    /// Convert the shape into a rapier collider
+    pub fn to_rapier(&self) -> bevy_rapier3d::prelude::Collider {
+        use bevy_rapier3d::prelude::Collider;
+
+        match self {
+            ChunkColliderShape::Trimesh { vertices, indices } => {
+                Collider::trimesh(vertices.clone(), indices.clone())
+            }
+            ChunkColliderShape::Cuboids(cuboids) => Collider::compound(
+                cuboids
+                    .iter()
+                    .map(|(center, half_extents)| {
+                        (
+                            *center,
+                            Quat::IDENTITY,
+                            Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+                        )
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Keeps the rapier colliders of chunks in sync with their `ChunkCollider`
+#[cfg(feature = "bevy_rapier3d")]
+pub(crate) fn sync_rapier_colliders<C: Send + Sync + 'static>(
+    mut commands: Commands,
+    changed: Query<(Entity, &ChunkCollider<C>), Changed<ChunkCollider<C>>>,
+    mut removed: RemovedComponents<ChunkCollider<C>>,
+) {
+    for entity in removed.read() {
+        if let Some(mut entity_commands) = commands.get_entity(entity) {
+            entity_commands.remove::<bevy_rapier3d::prelude::Collider>();
+        }
+    }
+
+    for (entity, collider) in changed.iter() {
+        commands
+            .entity(entity)
+            .try_insert(collider.shape.to_rapier());
+    }
+}
+
+/// Disables the rigid bodies of entities while `FreezeUntilGenerated` holds them in place
+#[cfg(feature = "bevy_rapier3d")]
+pub(crate) fn sync_rapier_freeze<C: Send + Sync + 'static>(
+    mut commands: Commands,
+    changed: Query<
+        (Entity, &crate::voxel_world::FreezeUntilGenerated<C>),
+        Changed<crate::voxel_world::FreezeUntilGenerated<C>>,
+    >,
+) {
+    for (entity, freeze) in changed.iter() {
+        if freeze.is_frozen() {
+            commands
+                .entity(entity)
+                .try_insert(bevy_rapier3d::prelude::RigidBodyDisabled);
+        } else {
+            commands
+                .entity(entity)
+                .remove::<bevy_rapier3d::prelude::RigidBodyDisabled>();
+        }
+    }
+}
@@ -0,0 +1,94 @@
+///
+/// Chunk generator
+/// A buffer for generating the voxels of a whole chunk at once, as a faster alternative to
+/// looking up every voxel on its own.
+///
+use bevy::prelude::*;
+use ndshape::ConstShape;
+
+use crate::{
+    chunk::{PaddedChunkShape, CHUNK_SIZE_I, PADDED_CHUNK_SIZE},
+    configuration::ChunkGeneratorFn,
+    voxel::WorldVoxel,
+};
+
+/// The voxels of a chunk and the one voxel wide padding around it, handed to the
+/// `VoxelWorldConfig::chunk_generator` to fill in. Positions are world voxel positions, and
+/// voxels that are not set stay `Unset`.
+pub struct ChunkBuffer<I> {
+    chunk_position: IVec3,
+    voxels: Vec<WorldVoxel<I>>,
+}
+
+impl<I: Copy> ChunkBuffer<I> {
+    pub(crate) fn new(chunk_position: IVec3) -> Self {
+        Self {
+            chunk_position,
+            voxels: vec![WorldVoxel::Unset; PaddedChunkShape::SIZE as usize],
+        }
+    }
+
+    /// Run the generator over a new buffer for the given chunk
+    pub(crate) fn generate(chunk_position: IVec3, generator: &ChunkGeneratorFn<I>) -> Self {
+        let mut buffer = Self::new(chunk_position);
+        generator(&mut buffer);
+        buffer
+    }
+
+    /// The position of the chunk being generated
+    pub fn chunk_position(&self) -> IVec3 {
+        self.chunk_position
+    }
+
+    /// The lowest position in the buffer, inclusive
+    pub fn min(&self) -> IVec3 {
+        self.chunk_position * CHUNK_SIZE_I - IVec3::ONE
+    }
+
+    /// The highest position in the buffer, inclusive
+    pub fn max(&self) -> IVec3 {
+        self.min() + IVec3::splat(PADDED_CHUNK_SIZE as i32 - 1)
+    }
+
+    /// Whether a position is within the buffer
+    pub fn contains(&self, position: IVec3) -> bool {
+        position.cmpge(self.min()).all() && position.cmple(self.max()).all()
+    }
+
+    /// Get the voxel at a position. Positions outside of the buffer get the voxel at the
+    /// closest position inside of it.
+    pub fn get(&self, position: IVec3) -> WorldVoxel<I> {
+        self.voxels[self.index(position.clamp(self.min(), self.max()))]
+    }
+
+    /// Set the voxel at a position. Positions outside of the buffer are ignored.
+    pub fn set(&mut self, position: IVec3, voxel: WorldVoxel<I>) {
+        if self.contains(position) {
+            let index = self.index(position);
+            self.voxels[index] = voxel;
+        }
+    }
+
+    /// The `x` and `z` positions of every column of voxels in the buffer
+    pub fn columns(&self) -> impl Iterator<Item = IVec2> {
+        let (min, max) = (self.min(), self.max());
+        (min.z..=max.z).flat_map(move |z| (min.x..=max.x).map(move |x| IVec2::new(x, z)))
+    }
+
+    /// Set every voxel of a column to the voxel `voxel_at` returns for its `y` position
+    pub fn set_column(&mut self, column: IVec2, mut voxel_at: impl FnMut(i32) -> WorldVoxel<I>) {
+        for y in self.min().y..=self.max().y {
+            self.set(IVec3::new(column.x, y, column.y), voxel_at(y));
+        }
+    }
+
+    /// Fill a column with `voxel` below `height`, and with air from `height` up, like for terrain
+    /// from a heightmap
+    pub fn fill_column(&mut self, column: IVec2, height: i32, voxel: WorldVoxel<I>) {
+        self.set_column(column, |y| if y < height { voxel } else { WorldVoxel::Air });
+    }
+
+    fn index(&self, position: IVec3) -> usize {
+        PaddedChunkShape::linearize((position - self.min()).as_uvec3().to_array()) as usize
+    }
+}
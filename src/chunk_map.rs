@@ -59,7 +59,7 @@ impl<C: Send + Sync + 'static, I: Copy> ChunkMap<C, I> {
         world_bounds
     }
 
-    pub fn get_read_lock(&self) -> RwLockReadGuard<ChunkMapData<I>> {
+    pub fn get_read_lock(&self) -> RwLockReadGuard<'_, ChunkMapData<I>> {
         self.map.read().unwrap()
     }
 
@@ -89,11 +89,8 @@ impl<C: Send + Sync + 'static, I: Copy> ChunkMap<C, I> {
                 );
 
                 let position_f = Vec3A::from(position.as_vec3());
-                if position_f.cmplt(write_lock.bounds.min).any() {
-                    write_lock.bounds.min = position_f.min(write_lock.bounds.min);
-                } else if position_f.cmpgt(write_lock.bounds.max).any() {
-                    write_lock.bounds.max = position_f.max(write_lock.bounds.max);
-                }
+                write_lock.bounds.min = position_f.min(write_lock.bounds.min);
+                write_lock.bounds.max = position_f.max(write_lock.bounds.max);
             }
             insert_buffer.clear();
 
@@ -107,11 +104,8 @@ impl<C: Send + Sync + 'static, I: Copy> ChunkMap<C, I> {
                 );
 
                 let position_f = Vec3A::from(position.as_vec3());
-                if position_f.cmplt(write_lock.bounds.min).any() {
-                    write_lock.bounds.min = position_f.min(write_lock.bounds.min);
-                } else if position_f.cmpgt(write_lock.bounds.max).any() {
-                    write_lock.bounds.max = position_f.max(write_lock.bounds.max);
-                }
+                write_lock.bounds.min = position_f.min(write_lock.bounds.min);
+                write_lock.bounds.max = position_f.max(write_lock.bounds.max);
 
                 ev_chunk_will_spawn.send((*evt).clone());
             }
@@ -121,12 +115,16 @@ impl<C: Send + Sync + 'static, I: Copy> ChunkMap<C, I> {
             for position in remove_buffer.iter() {
                 write_lock.data.remove(position);
 
-                need_rebuild_aabb = write_lock.bounds.min.floor().as_ivec3() == *position
-                    || write_lock.bounds.max.floor().as_ivec3() == *position;
+                // The bounds can only shrink if the chunk was on one of their faces
+                let position_f = Vec3A::from(position.as_vec3());
+                need_rebuild_aabb |= position_f.cmpeq(write_lock.bounds.min).any()
+                    || position_f.cmpeq(write_lock.bounds.max).any();
             }
             remove_buffer.clear();
 
-            if need_rebuild_aabb {
+            if need_rebuild_aabb && write_lock.data.is_empty() {
+                write_lock.bounds = Aabb3d::new(Vec3::ZERO, Vec3::ZERO);
+            } else if need_rebuild_aabb {
                 let mut tmp_vec = Vec::with_capacity(write_lock.data.len());
                 for v in write_lock.data.keys() {
                     tmp_vec.push(Vec3A::from(v.as_vec3()));
@@ -162,5 +160,16 @@ pub(crate) struct ChunkMapUpdateBuffer<C, I>(
     PhantomData<C>,
 );
 
+impl<C, I: Clone> ChunkMapUpdateBuffer<C, I> {
+    /// The newest data of a chunk that is still waiting to be written to the chunk map. The map
+    /// can't be written while a thread reads it, so this can take more than a frame.
+    pub(crate) fn latest(&self, position: IVec3) -> Option<chunk::ChunkData<I>> {
+        self.iter()
+            .rev()
+            .find(|(pos, ..)| *pos == position)
+            .map(|(_, chunk_data, _)| chunk_data.clone())
+    }
+}
+
 #[derive(Resource, Deref, DerefMut, Default)]
 pub(crate) struct ChunkMapRemoveBuffer<C>(#[deref] Vec<IVec3>, PhantomData<C>);
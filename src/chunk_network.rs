@@ -0,0 +1,191 @@
+///
+/// Chunk network payloads
+/// A compact, versioned wire format for sending whole chunks between peers.
+///
+use std::{
+    hash::Hash,
+    io::{self, Read, Write},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use ndshape::ConstShape;
+
+use crate::{
+    chunk::{ChunkData, PaddedChunkShape, VoxelArray},
+    chunk_persistence::{invalid, read_varint, write_varint, PersistentIndex},
+    voxel::WorldVoxel,
+};
+
+const MAGIC: &[u8; 4] = b"BVWN";
+
+/// The version of the chunk network format written by `ChunkData::to_network_bytes`. Payloads
+/// of a newer version are rejected by `ChunkData::from_network_bytes`.
+pub const CHUNK_NETWORK_VERSION: u8 = 1;
+
+/// How the body of a chunk network payload is compressed, after it has been palette and
+/// run-length encoded
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NetworkCodec {
+    /// No compression beyond the palette and the runs, for the cheapest encoding
+    Raw,
+
+    /// Deflate compressed, for small payloads
+    #[default]
+    Deflate,
+
+    /// Zstandard compressed, for payloads as small as with deflate that are faster to encode and
+    /// decode
+    Zstd,
+}
+
+impl NetworkCodec {
+    fn tag(self) -> u8 {
+        match self {
+            Self::Raw => 0,
+            Self::Deflate => 1,
+            Self::Zstd => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> io::Result<Self> {
+        match tag {
+            0 => Ok(Self::Raw),
+            1 => Ok(Self::Deflate),
+            2 => Ok(Self::Zstd),
+            _ => Err(invalid("unknown codec")),
+        }
+    }
+}
+
+impl<I: PersistentIndex + Hash + Copy + Eq> ChunkData<I> {
+    /// Encode the position and voxels of the chunk, padding included, for sending over the
+    /// network. The voxels are stored as a palette with run-length encoded palette indices, and
+    /// then compressed with `codec`. Decode the payload with `from_network_bytes`.
+    ///
+    /// The payload starts with a magic number, the format version and the codec, so peers on
+    /// different versions can tell the payloads apart. Materials use the stable voxel encoding,
+    /// so the width of the index type doesn't matter either.
+    pub fn to_network_bytes(&self, codec: NetworkCodec) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(64);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(CHUNK_NETWORK_VERSION);
+        bytes.push(codec.tag());
+
+        // Writing to a vector doesn't fail
+        match codec {
+            NetworkCodec::Raw => self.write_network_body(&mut bytes).unwrap(),
+            NetworkCodec::Deflate => {
+                let mut encoder = DeflateEncoder::new(bytes, Compression::default());
+                self.write_network_body(&mut encoder).unwrap();
+                bytes = encoder.finish().unwrap();
+            }
+            NetworkCodec::Zstd => {
+                let mut encoder = zstd::Encoder::new(bytes, 0).unwrap();
+                self.write_network_body(&mut encoder).unwrap();
+                bytes = encoder.finish().unwrap();
+            }
+        }
+        bytes
+    }
+
+    /// Decode a chunk encoded with `to_network_bytes`. The chunk has no entity, and gets its fill
+    /// type and hash from the decoded voxels.
+    pub fn from_network_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < MAGIC.len() + 2 {
+            return Err(invalid("payload too short"));
+        }
+        let (header, body) = bytes.split_at(MAGIC.len() + 2);
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(invalid("not a chunk payload"));
+        }
+        let version = header[MAGIC.len()];
+        if version == 0 || version > CHUNK_NETWORK_VERSION {
+            return Err(invalid("unsupported chunk payload version"));
+        }
+
+        match NetworkCodec::from_tag(header[MAGIC.len() + 1])? {
+            NetworkCodec::Raw => Self::read_network_body(&mut &body[..]),
+            NetworkCodec::Deflate => Self::read_network_body(&mut DeflateDecoder::new(body)),
+            NetworkCodec::Zstd => Self::read_network_body(&mut zstd::Decoder::with_buffer(body)?),
+        }
+    }
+
+    fn write_network_body(&self, w: &mut impl Write) -> io::Result<()> {
+        for coordinate in self.position.to_array() {
+            w.write_all(&coordinate.to_le_bytes())?;
+        }
+
+        let mut palette: Vec<WorldVoxel<I>> = Vec::new();
+        let mut palette_indices = HashMap::new();
+        let indices: Vec<u32> = (0..PaddedChunkShape::SIZE as usize)
+            .map(|i| {
+                let voxel = self.get_voxel_at_index(i);
+                *palette_indices.entry(voxel).or_insert_with(|| {
+                    palette.push(voxel);
+                    palette.len() as u32 - 1
+                })
+            })
+            .collect();
+
+        write_varint(w, palette.len() as u32)?;
+        for voxel in palette.iter() {
+            voxel.encode(w)?;
+        }
+
+        let runs = || indices.chunk_by(|a, b| a == b);
+        write_varint(w, runs().count() as u32)?;
+        for run in runs() {
+            write_varint(w, run.len() as u32)?;
+            write_varint(w, run[0])?;
+        }
+        Ok(())
+    }
+
+    fn read_network_body(r: &mut impl Read) -> io::Result<Self> {
+        let mut position = [0; 3];
+        for coordinate in position.iter_mut() {
+            let mut bytes = [0; 4];
+            r.read_exact(&mut bytes)?;
+            *coordinate = i32::from_le_bytes(bytes);
+        }
+
+        let palette_len = read_varint(r)?;
+        if palette_len as usize > PaddedChunkShape::SIZE as usize {
+            return Err(invalid("palette too large"));
+        }
+        let palette = (0..palette_len)
+            .map(|_| WorldVoxel::decode(r))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut voxels: VoxelArray<I> = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
+        // Every run covers at least one voxel, so a broken payload can't make this loop for long
+        let run_count = read_varint(r)?;
+        if run_count as usize > voxels.len() {
+            return Err(invalid("too many runs"));
+        }
+        let mut index = 0;
+        for _ in 0..run_count {
+            let run_len = read_varint(r)? as usize;
+            if run_len == 0 {
+                return Err(invalid("empty run"));
+            }
+            if run_len > voxels.len() - index {
+                return Err(invalid("too many voxels"));
+            }
+            let voxel = *palette
+                .get(read_varint(r)? as usize)
+                .ok_or_else(|| invalid("palette index out of range"))?;
+            voxels[index..index + run_len].fill(voxel);
+            index += run_len;
+        }
+        if index != voxels.len() {
+            return Err(invalid("too few voxels"));
+        }
+
+        let mut chunk_data = Self::new();
+        chunk_data.position = IVec3::from_array(position);
+        chunk_data.set_voxels(&voxels);
+        Ok(chunk_data)
+    }
+}
@@ -0,0 +1,397 @@
+///
+/// Chunk persistence
+/// Saves modified voxels to disk, so that edits survive restarts.
+///
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    marker::PhantomData,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    chunk::{CHUNK_SIZE_I, CHUNK_SIZE_U},
+    voxel::{VoxelIndex, WorldVoxel},
+    voxel_world_internal::ModifiedVoxelMap,
+};
+
+/// Storage for the modified voxels of chunks. Set one up through
+/// `VoxelWorldConfig::chunk_persistence`.
+///
+/// Modified voxels of a chunk are saved when the chunk despawns and when the app exits, and get
+/// loaded back before the chunk is generated again. Positions are in world voxel coordinates.
+pub trait ChunkPersistence<I>: Send + Sync {
+    /// Load the modified voxels of the chunk at `chunk_position`. Returns `None` if nothing
+    /// was saved for this chunk.
+    fn load(&self, chunk_position: IVec3) -> Option<Vec<(IVec3, WorldVoxel<I>)>>;
+
+    /// Save the modified voxels of the chunk at `chunk_position`, replacing anything saved
+    /// for it before.
+    fn save(&self, chunk_position: IVec3, voxels: &[(IVec3, WorldVoxel<I>)]);
+}
+
+/// Chunks whose saved voxels have been merged into `ModifiedVoxels`. Chunks are only loaded
+/// once per session, after which `ModifiedVoxels` holds their latest state.
+#[derive(Resource, Deref)]
+pub(crate) struct PersistedChunks<C>(#[deref] Arc<RwLock<HashSet<IVec3>>>, PhantomData<C>);
+
+impl<C> Default for PersistedChunks<C> {
+    fn default() -> Self {
+        Self(Arc::new(RwLock::new(HashSet::new())), PhantomData)
+    }
+}
+
+/// Merge the saved voxels of a chunk into the modified voxels, unless that already happened.
+/// Voxels modified during this session take precedence over saved ones. The chunk is only marked
+/// as persisted once its voxels are merged, so that other threads don't generate it without them.
+pub(crate) fn load_persisted_chunk<I: Copy>(
+    persistence: &dyn ChunkPersistence<I>,
+    persisted_chunks: &RwLock<HashSet<IVec3>>,
+    modified_voxels: &RwLock<ModifiedVoxelMap<I>>,
+    chunk_position: IVec3,
+) {
+    if persisted_chunks.read().unwrap().contains(&chunk_position) {
+        return;
+    }
+
+    let voxels = persistence.load(chunk_position);
+
+    let mut modified_voxels = modified_voxels.write().unwrap();
+    // Merging twice is harmless, but another thread may have done it while this one was loading
+    if !persisted_chunks.write().unwrap().insert(chunk_position) {
+        return;
+    }
+    for (position, voxel) in voxels.into_iter().flatten() {
+        if !modified_voxels.contains_key(&position) {
+            modified_voxels.insert(position, voxel);
+        }
+    }
+}
+
+/// Get the modified voxels within the given chunk
+pub(crate) fn chunk_modified_voxels<I: Copy>(
+    modified_voxels: &RwLock<ModifiedVoxelMap<I>>,
+    chunk_position: IVec3,
+) -> Vec<(IVec3, WorldVoxel<I>)> {
+    modified_voxels
+        .read()
+        .unwrap()
+        .in_chunk(chunk_position)
+        .map(|voxels| {
+            voxels
+                .iter()
+                .map(|(position, voxel)| (*position, *voxel))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Material index types that `FileChunkPersistence` knows how to write to disk
+pub trait PersistentIndex: Sized {
+    fn to_bits(&self) -> u32;
+    fn from_bits(bits: u32) -> Option<Self>;
+}
+
+impl PersistentIndex for u8 {
+    fn to_bits(&self) -> u32 {
+        *self as u32
+    }
+
+    fn from_bits(bits: u32) -> Option<Self> {
+        bits.try_into().ok()
+    }
+}
+
+impl PersistentIndex for u16 {
+    fn to_bits(&self) -> u32 {
+        *self as u32
+    }
+
+    fn from_bits(bits: u32) -> Option<Self> {
+        bits.try_into().ok()
+    }
+}
+
+impl PersistentIndex for u32 {
+    fn to_bits(&self) -> u32 {
+        *self
+    }
+
+    fn from_bits(bits: u32) -> Option<Self> {
+        Some(bits)
+    }
+}
+
+impl<T: VoxelIndex> PersistentIndex for T {
+    fn to_bits(&self) -> u32 {
+        self.ordinal() as u32
+    }
+
+    fn from_bits(bits: u32) -> Option<Self> {
+        T::VARIANTS.get(bits as usize).copied()
+    }
+}
+
+/// The stable encoding of voxels, for saves and network protocols. A voxel is a tag byte, `0` for
+/// `Unset`, `1` for `Air` and `2` for `Solid`, followed for solid voxels by the
+/// `PersistentIndex` bits of the material as a LEB128 varint. The encoding doesn't depend on how
+/// Rust lays out the enum or on the width of the index type, so data written with `u8` indexes
+/// reads back as `u16` indexes, and hashing the bytes gives the same hash on every version.
+impl<I: PersistentIndex> WorldVoxel<I> {
+    pub fn encode(&self, w: &mut impl Write) -> io::Result<()> {
+        match self {
+            WorldVoxel::Unset => w.write_all(&[VOXEL_TAG_UNSET]),
+            WorldVoxel::Air => w.write_all(&[VOXEL_TAG_AIR]),
+            WorldVoxel::Solid(material) => {
+                w.write_all(&[VOXEL_TAG_SOLID])?;
+                write_varint(w, material.to_bits())
+            }
+        }
+    }
+
+    pub fn decode(r: &mut impl Read) -> io::Result<Self> {
+        let mut tag = [0; 1];
+        r.read_exact(&mut tag)?;
+        match tag[0] {
+            VOXEL_TAG_UNSET => Ok(WorldVoxel::Unset),
+            VOXEL_TAG_AIR => Ok(WorldVoxel::Air),
+            VOXEL_TAG_SOLID => I::from_bits(read_varint(r)?)
+                .map(WorldVoxel::Solid)
+                .ok_or_else(|| invalid("unknown material index")),
+            _ => Err(invalid("unknown voxel type")),
+        }
+    }
+
+    /// The encoded bytes of the voxel
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(6);
+        self.encode(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// Decode a voxel from the start of `bytes`
+    pub fn from_bytes(mut bytes: &[u8]) -> io::Result<Self> {
+        Self::decode(&mut bytes)
+    }
+}
+
+const VOXEL_TAG_UNSET: u8 = 0;
+const VOXEL_TAG_AIR: u8 = 1;
+const VOXEL_TAG_SOLID: u8 = 2;
+
+/// Stores each chunk with modifications as a file in a directory. Chunks are stored as a palette
+/// of the voxels used, followed by run-length encoded palette indexes for every voxel in the
+/// chunk, so sparse edits and large filled areas both stay small.
+pub struct FileChunkPersistence<I> {
+    directory: PathBuf,
+    _marker: PhantomData<I>,
+}
+
+impl<I> FileChunkPersistence<I> {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            _marker: PhantomData,
+        }
+    }
+
+    fn chunk_path(&self, chunk_position: IVec3) -> PathBuf {
+        self.directory.join(format!(
+            "{}_{}_{}.chunk",
+            chunk_position.x, chunk_position.y, chunk_position.z
+        ))
+    }
+}
+
+impl<I> ChunkPersistence<I> for FileChunkPersistence<I>
+where
+    I: PersistentIndex + Copy + PartialEq + Send + Sync,
+{
+    fn load(&self, chunk_position: IVec3) -> Option<Vec<(IVec3, WorldVoxel<I>)>> {
+        let path = self.chunk_path(chunk_position);
+        if !path.exists() {
+            return None;
+        }
+
+        match read_chunk_file(&path, chunk_position) {
+            Ok(voxels) => Some(voxels),
+            Err(err) => {
+                warn!("Ignoring chunk file {:?}: {}", path, err);
+                None
+            }
+        }
+    }
+
+    fn save(&self, chunk_position: IVec3, voxels: &[(IVec3, WorldVoxel<I>)]) {
+        let path = self.chunk_path(chunk_position);
+        let result = fs::create_dir_all(&self.directory)
+            .and_then(|_| write_chunk_file(&path, chunk_position, voxels));
+        if let Err(err) = result {
+            warn!("Failed to write chunk file {:?}: {}", path, err);
+        }
+    }
+}
+
+const CHUNK_FILE_MAGIC: &[u8; 4] = b"BVWC";
+// Version 1 stored the material of palette voxels as a fixed `u32`, version 2 uses the stable
+// voxel encoding
+const CHUNK_FILE_VERSION: u32 = 2;
+const CHUNK_VOLUME: usize = (CHUNK_SIZE_U * CHUNK_SIZE_U * CHUNK_SIZE_U) as usize;
+
+fn local_index(chunk_position: IVec3, position: IVec3) -> usize {
+    let local = (position - chunk_position * CHUNK_SIZE_I).as_uvec3();
+    (local.x + local.y * CHUNK_SIZE_U + local.z * CHUNK_SIZE_U * CHUNK_SIZE_U) as usize
+}
+
+fn local_position(chunk_position: IVec3, index: usize) -> IVec3 {
+    let index = index as i32;
+    chunk_position * CHUNK_SIZE_I
+        + IVec3::new(
+            index % CHUNK_SIZE_I,
+            (index / CHUNK_SIZE_I) % CHUNK_SIZE_I,
+            index / (CHUNK_SIZE_I * CHUNK_SIZE_I),
+        )
+}
+
+fn write_chunk_file<I: PersistentIndex + Copy + PartialEq>(
+    path: &PathBuf,
+    chunk_position: IVec3,
+    voxels: &[(IVec3, WorldVoxel<I>)],
+) -> io::Result<()> {
+    // Palette index 0 means the voxel is not modified
+    let mut palette: Vec<WorldVoxel<I>> = Vec::new();
+    let mut indexes = vec![0u16; CHUNK_VOLUME];
+    for (position, voxel) in voxels {
+        let palette_index = match palette.iter().position(|v| v == voxel) {
+            Some(palette_index) => palette_index,
+            None => {
+                palette.push(*voxel);
+                palette.len() - 1
+            }
+        };
+        indexes[local_index(chunk_position, *position)] = palette_index as u16 + 1;
+    }
+
+    let mut w = BufWriter::new(File::create(path)?);
+    w.write_all(CHUNK_FILE_MAGIC)?;
+    w.write_all(&CHUNK_FILE_VERSION.to_le_bytes())?;
+
+    w.write_all(&(palette.len() as u16).to_le_bytes())?;
+    for voxel in palette.iter() {
+        voxel.encode(&mut w)?;
+    }
+
+    let runs = || indexes.chunk_by(|a, b| a == b);
+    w.write_all(&(runs().count() as u32).to_le_bytes())?;
+    for run in runs() {
+        w.write_all(&(run.len() as u32).to_le_bytes())?;
+        w.write_all(&run[0].to_le_bytes())?;
+    }
+
+    w.flush()
+}
+
+fn read_chunk_file<I: PersistentIndex + Copy>(
+    path: &PathBuf,
+    chunk_position: IVec3,
+) -> io::Result<Vec<(IVec3, WorldVoxel<I>)>> {
+    let mut r = BufReader::new(File::open(path)?);
+
+    let mut magic = [0; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != CHUNK_FILE_MAGIC {
+        return Err(invalid("not a chunk file"));
+    }
+    let version = read_u32(&mut r)?;
+    if version == 0 || version > CHUNK_FILE_VERSION {
+        return Err(invalid("unsupported chunk file version"));
+    }
+
+    let palette_len = read_u16(&mut r)?;
+    let mut palette = Vec::with_capacity(palette_len as usize);
+    for _ in 0..palette_len {
+        if version >= 2 {
+            palette.push(WorldVoxel::decode(&mut r)?);
+            continue;
+        }
+        let mut tag = [0; 1];
+        r.read_exact(&mut tag)?;
+        let bits = read_u32(&mut r)?;
+        palette.push(match tag[0] {
+            VOXEL_TAG_UNSET => WorldVoxel::Unset,
+            VOXEL_TAG_AIR => WorldVoxel::Air,
+            VOXEL_TAG_SOLID => WorldVoxel::Solid(
+                I::from_bits(bits).ok_or_else(|| invalid("unknown material index"))?,
+            ),
+            _ => return Err(invalid("unknown voxel type")),
+        });
+    }
+
+    let mut voxels = Vec::new();
+    let mut index = 0;
+    for _ in 0..read_u32(&mut r)? {
+        let run_len = read_u32(&mut r)? as usize;
+        let palette_index = read_u16(&mut r)? as usize;
+        if index + run_len > CHUNK_VOLUME {
+            return Err(invalid("too many voxels"));
+        }
+        if palette_index > 0 {
+            let voxel = palette
+                .get(palette_index - 1)
+                .ok_or_else(|| invalid("palette index out of range"))?;
+            for i in index..index + run_len {
+                voxels.push((local_position(chunk_position, i), *voxel));
+            }
+        }
+        index += run_len;
+    }
+
+    Ok(voxels)
+}
+
+fn read_u16(r: &mut impl Read) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    r.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn write_varint(w: &mut impl Write, mut value: u32) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return w.write_all(&[byte]);
+        }
+        w.write_all(&[byte | 0x80])?;
+    }
+}
+
+pub(crate) fn read_varint(r: &mut impl Read) -> io::Result<u32> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let mut byte = [0; 1];
+        r.read_exact(&mut byte)?;
+        let bits = (byte[0] & 0x7f) as u32;
+        if shift == 28 && bits > 0xf {
+            return Err(invalid("varint too large"));
+        }
+        value |= bits << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("varint too long"))
+}
+
+pub(crate) fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
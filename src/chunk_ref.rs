@@ -0,0 +1,290 @@
+///
+/// Chunk ref
+/// A copy of the voxels of a loaded chunk, for reading and editing a whole chunk at once instead
+/// of going through `get_voxel` and `set_voxel` for every voxel.
+///
+use std::sync::{Arc, RwLock, Weak};
+
+use bevy::prelude::*;
+use ndshape::ConstShape;
+
+use crate::{
+    chunk::{ChunkData, PaddedChunkShape, CHUNK_SIZE_I, CHUNK_SIZE_U},
+    chunk_map::ChunkMapData,
+    voxel::WorldVoxel,
+    voxel_world_internal::ModifiedVoxelMap,
+};
+
+const CHUNK_VOLUME: usize = (CHUNK_SIZE_U * CHUNK_SIZE_U * CHUNK_SIZE_U) as usize;
+
+/// The voxels of a loaded chunk, including edits that have not been applied yet, as returned by
+/// `VoxelWorld::get_chunk` and handed to the closure of `VoxelWorld::modify_chunk`. Positions are
+/// world voxel positions. The padding around the chunk is not included.
+#[derive(Clone, Debug)]
+pub struct ChunkRef<I> {
+    chunk_position: IVec3,
+    voxels: Vec<WorldVoxel<I>>,
+}
+
+impl<I: Copy + PartialEq + std::hash::Hash> ChunkRef<I> {
+    /// Copy the voxels of a chunk from its generated data, the modified voxels, and the writes
+    /// that are still pending, in the order they were made
+    pub(crate) fn from_chunk_data<'a>(
+        chunk_position: IVec3,
+        chunk_data: &ChunkData<I>,
+        modified_voxels: &ModifiedVoxelMap<I>,
+        pending_writes: impl IntoIterator<Item = &'a (IVec3, WorldVoxel<I>)>,
+    ) -> Self
+    where
+        I: 'a,
+    {
+        let chunk_min = chunk_position * CHUNK_SIZE_I;
+        Self::with_changes(
+            chunk_position,
+            |position| chunk_data.get_voxel((position - chunk_min).as_uvec3() + 1),
+            modified_voxels,
+            pending_writes,
+        )
+    }
+
+    /// Copy the voxels of a chunk handed to `VoxelWorld::insert_chunk`, leaving out the padding
+    pub(crate) fn from_inserted_data(chunk_position: IVec3, chunk_data: &ChunkData<I>) -> Self {
+        let chunk_min = chunk_position * CHUNK_SIZE_I;
+        Self::new(chunk_position, |position| {
+            chunk_data.get_voxel((position - chunk_min).as_uvec3() + 1)
+        })
+    }
+
+    /// Copy the voxels of a chunk given with `VoxelWorld::insert_chunk`, with the voxels modified
+    /// and written since
+    pub(crate) fn from_inserted<'a>(
+        inserted: &Self,
+        modified_voxels: &ModifiedVoxelMap<I>,
+        pending_writes: impl IntoIterator<Item = &'a (IVec3, WorldVoxel<I>)>,
+    ) -> Self
+    where
+        I: 'a,
+    {
+        let chunk_min = inserted.min();
+        Self::with_changes(
+            inserted.chunk_position,
+            |position| inserted.voxels[linearize(position - chunk_min)],
+            modified_voxels,
+            pending_writes,
+        )
+    }
+
+    fn with_changes<'a>(
+        chunk_position: IVec3,
+        base: impl Fn(IVec3) -> WorldVoxel<I>,
+        modified_voxels: &ModifiedVoxelMap<I>,
+        pending_writes: impl IntoIterator<Item = &'a (IVec3, WorldVoxel<I>)>,
+    ) -> Self
+    where
+        I: 'a,
+    {
+        let mut chunk = Self::new(chunk_position, |position| {
+            modified_voxels
+                .get(&position)
+                .copied()
+                .unwrap_or_else(|| base(position))
+        });
+        for &(position, voxel) in pending_writes {
+            chunk.set(position, voxel);
+        }
+        chunk
+    }
+
+    /// A chunk with every voxel set to `voxel`, for building the voxels of a chunk to hand to
+    /// `VoxelWorld::insert_chunk` as a `ChunkData`
+    pub fn filled(chunk_position: IVec3, voxel: WorldVoxel<I>) -> Self {
+        Self::new(chunk_position, |_| voxel)
+    }
+
+    fn new(chunk_position: IVec3, mut voxel_at: impl FnMut(IVec3) -> WorldVoxel<I>) -> Self {
+        let min = chunk_position * CHUNK_SIZE_I;
+        Self {
+            chunk_position,
+            voxels: (0..CHUNK_VOLUME)
+                .map(|index| voxel_at(min + delinearize(index)))
+                .collect(),
+        }
+    }
+
+    /// The position of the chunk
+    pub fn chunk_position(&self) -> IVec3 {
+        self.chunk_position
+    }
+
+    /// The lowest position in the chunk, inclusive
+    pub fn min(&self) -> IVec3 {
+        self.chunk_position * CHUNK_SIZE_I
+    }
+
+    /// The highest position in the chunk, inclusive
+    pub fn max(&self) -> IVec3 {
+        self.min() + IVec3::splat(CHUNK_SIZE_I - 1)
+    }
+
+    /// Whether a position is within the chunk
+    pub fn contains(&self, position: IVec3) -> bool {
+        position.cmpge(self.min()).all() && position.cmple(self.max()).all()
+    }
+
+    /// Get the voxel at a position, or `None` if the position is outside of the chunk
+    pub fn get(&self, position: IVec3) -> Option<WorldVoxel<I>> {
+        self.contains(position)
+            .then(|| self.voxels[linearize(position - self.min())])
+    }
+
+    /// Set the voxel at a position. Positions outside of the chunk are ignored.
+    pub fn set(&mut self, position: IVec3, voxel: WorldVoxel<I>) {
+        if self.contains(position) {
+            let index = linearize(position - self.min());
+            self.voxels[index] = voxel;
+        }
+    }
+
+    /// Iterate over the position and voxel of every voxel in the chunk
+    pub fn iter(&self) -> impl Iterator<Item = (IVec3, WorldVoxel<I>)> + '_ {
+        let min = self.min();
+        self.voxels
+            .iter()
+            .enumerate()
+            .map(move |(index, voxel)| (min + delinearize(index), *voxel))
+    }
+
+    /// The voxels that differ from `original`, a copy of the same chunk
+    pub(crate) fn changes_since<'a>(
+        &'a self,
+        original: &'a Self,
+    ) -> impl Iterator<Item = (IVec3, WorldVoxel<I>)> + 'a {
+        let min = self.min();
+        self.voxels
+            .iter()
+            .zip(&original.voxels)
+            .enumerate()
+            .filter(|(_, (voxel, old))| voxel != old)
+            .map(move |(index, (voxel, _))| (min + delinearize(index), *voxel))
+    }
+}
+
+/// A handle to the generated voxels of a spawned chunk, as returned by
+/// `VoxelWorld::chunk_data_handle`. Unlike a `ChunkRef`, the voxels are not copied. The handle
+/// shares them with the chunk map, and keeps them alive after the chunk is despawned or
+/// regenerated, until the handle is dropped. That makes it cheap to hold on to chunks across
+/// frames, for analyses that take a while. Positions are world voxel positions.
+///
+/// The voxels are those of the chunk when it was last generated. Voxels set since then are not
+/// included, and once they are applied the chunk is regenerated, which makes the handle stale.
+#[derive(Clone)]
+pub struct ChunkDataHandle<I> {
+    chunk_position: IVec3,
+    chunk_data: ChunkData<I>,
+    chunk_map: Weak<RwLock<ChunkMapData<I>>>,
+}
+
+impl<I: Copy + PartialEq + std::hash::Hash> ChunkDataHandle<I> {
+    pub(crate) fn new(
+        chunk_position: IVec3,
+        chunk_data: ChunkData<I>,
+        chunk_map: &Arc<RwLock<ChunkMapData<I>>>,
+    ) -> Self {
+        Self {
+            chunk_position,
+            chunk_data,
+            chunk_map: Arc::downgrade(chunk_map),
+        }
+    }
+
+    /// The position of the chunk
+    pub fn chunk_position(&self) -> IVec3 {
+        self.chunk_position
+    }
+
+    /// The lowest position in the chunk, inclusive
+    pub fn min(&self) -> IVec3 {
+        self.chunk_position * CHUNK_SIZE_I
+    }
+
+    /// The highest position in the chunk, inclusive
+    pub fn max(&self) -> IVec3 {
+        self.min() + IVec3::splat(CHUNK_SIZE_I - 1)
+    }
+
+    /// Whether a position is within the chunk
+    pub fn contains(&self, position: IVec3) -> bool {
+        position.cmpge(self.min()).all() && position.cmple(self.max()).all()
+    }
+
+    /// Get the voxel at a position, or `None` if the position is outside of the chunk
+    pub fn get(&self, position: IVec3) -> Option<WorldVoxel<I>> {
+        self.contains(position).then(|| {
+            self.chunk_data
+                .get_voxel((position - self.min()).as_uvec3() + 1)
+        })
+    }
+
+    /// Iterate over the position and voxel of every voxel in the chunk
+    pub fn iter(&self) -> impl Iterator<Item = (IVec3, WorldVoxel<I>)> + '_ {
+        let min = self.min();
+        (0..CHUNK_VOLUME).map(move |index| {
+            let local = delinearize(index);
+            (min + local, self.chunk_data.get_voxel(local.as_uvec3() + 1))
+        })
+    }
+
+    /// The chunk data the handle shares with the chunk map, for comparing chunks with
+    /// `ChunkData::diff`
+    pub fn chunk_data(&self) -> &ChunkData<I> {
+        &self.chunk_data
+    }
+
+    /// Whether the chunk has been despawned or regenerated since the handle was taken, so that
+    /// the voxels of the handle may be out of date
+    pub fn is_stale(&self) -> bool {
+        let Some(chunk_map) = self.chunk_map.upgrade() else {
+            return true;
+        };
+        let chunk_map = chunk_map.read().unwrap();
+        let Some(current) = chunk_map.get(&self.chunk_position) else {
+            return true;
+        };
+        let same_voxels = match (&current.voxels, &self.chunk_data.voxels) {
+            (Some(current), Some(voxels)) => Arc::ptr_eq(current, voxels),
+            (None, None) => current.fill_type == self.chunk_data.fill_type,
+            _ => false,
+        };
+        current.entity != self.chunk_data.entity || !same_voxels
+    }
+}
+
+impl<I: Copy + Eq + std::hash::Hash> From<ChunkRef<I>> for ChunkData<I> {
+    /// The voxels of the chunk, with the padding around it left unset
+    fn from(chunk: ChunkRef<I>) -> Self {
+        let mut voxels = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
+        for (index, voxel) in chunk.voxels.iter().enumerate() {
+            let padded = (delinearize(index) + IVec3::ONE).as_uvec3().to_array();
+            voxels[PaddedChunkShape::linearize(padded) as usize] = *voxel;
+        }
+        let mut chunk_data = ChunkData {
+            position: chunk.chunk_position,
+            ..ChunkData::new()
+        };
+        chunk_data.set_voxels(&voxels);
+        chunk_data
+    }
+}
+
+fn linearize(local: IVec3) -> usize {
+    (local.x + CHUNK_SIZE_I * (local.y + CHUNK_SIZE_I * local.z)) as usize
+}
+
+fn delinearize(index: usize) -> IVec3 {
+    let index = index as i32;
+    IVec3::new(
+        index % CHUNK_SIZE_I,
+        (index / CHUNK_SIZE_I) % CHUNK_SIZE_I,
+        index / (CHUNK_SIZE_I * CHUNK_SIZE_I),
+    )
+}
@@ -0,0 +1,122 @@
+///
+/// Chunk shape
+/// The layout of the padded voxel array of a chunk, for a chunk edge length known at compile
+/// time or only at runtime.
+///
+use bevy::prelude::*;
+use ndshape::{ConstShape, Shape};
+
+use crate::chunk::CHUNK_SIZE_U;
+
+/// The shape of the voxel array of a chunk, with the one voxel thick padding around the chunk.
+/// `ChunkData` and the mesher index voxels through it, so they work for any chunk edge length.
+pub trait ChunkShape: Shape<3, Coord = u32> + Clone + Send + Sync + 'static {
+    /// The number of voxels along each edge of the chunk, without the padding
+    fn chunk_size(&self) -> u32;
+
+    /// The number of voxels along each edge of the padded chunk
+    fn padded_size(&self) -> u32 {
+        self.chunk_size() + 2
+    }
+
+    /// The world position of the first voxel of the chunk at `chunk_position`
+    fn chunk_min(&self, chunk_position: IVec3) -> IVec3 {
+        chunk_position * self.chunk_size() as i32
+    }
+}
+
+/// A chunk with `N` voxels along each edge. The strides are constants, so indexing compiles down
+/// to constant multiplications, and loops over the chunk can be unrolled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ConstChunkShape<const N: u32>;
+
+impl<const N: u32> ConstChunkShape<N> {
+    const PADDED: u32 = N + 2;
+}
+
+impl<const N: u32> ConstShape<3> for ConstChunkShape<N> {
+    type Coord = u32;
+
+    const ARRAY: [u32; 3] = [Self::PADDED; 3];
+    const SIZE: u32 = Self::PADDED * Self::PADDED * Self::PADDED;
+    const USIZE: usize = Self::SIZE as usize;
+
+    #[inline]
+    fn linearize([x, y, z]: [u32; 3]) -> u32 {
+        x + Self::PADDED.wrapping_mul(y) + (Self::PADDED * Self::PADDED).wrapping_mul(z)
+    }
+
+    #[inline]
+    fn delinearize(i: u32) -> [u32; 3] {
+        [
+            i % Self::PADDED,
+            (i / Self::PADDED) % Self::PADDED,
+            i / (Self::PADDED * Self::PADDED),
+        ]
+    }
+}
+
+impl<const N: u32> ChunkShape for ConstChunkShape<N> {
+    #[inline]
+    fn chunk_size(&self) -> u32 {
+        N
+    }
+}
+
+/// A chunk with an edge length chosen at runtime. The voxels are laid out the same way as for a
+/// `ConstChunkShape` of that size. Defaults to `CHUNK_SIZE_U`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DynamicChunkShape {
+    chunk_size: u32,
+}
+
+impl DynamicChunkShape {
+    pub fn new(chunk_size: u32) -> Self {
+        assert!(chunk_size > 0, "chunks need at least one voxel");
+        Self { chunk_size }
+    }
+}
+
+impl Default for DynamicChunkShape {
+    fn default() -> Self {
+        Self::new(CHUNK_SIZE_U)
+    }
+}
+
+impl Shape<3> for DynamicChunkShape {
+    type Coord = u32;
+
+    #[inline]
+    fn size(&self) -> u32 {
+        self.padded_size().pow(3)
+    }
+
+    #[inline]
+    fn usize(&self) -> usize {
+        self.size() as usize
+    }
+
+    #[inline]
+    fn as_array(&self) -> [u32; 3] {
+        [self.padded_size(); 3]
+    }
+
+    #[inline]
+    fn linearize(&self, [x, y, z]: [u32; 3]) -> u32 {
+        let padded = self.padded_size();
+        x + padded.wrapping_mul(y) + (padded * padded).wrapping_mul(z)
+    }
+
+    #[inline]
+    fn delinearize(&self, i: u32) -> [u32; 3] {
+        let padded = self.padded_size();
+        [i % padded, (i / padded) % padded, i / (padded * padded)]
+    }
+}
+
+impl ChunkShape for DynamicChunkShape {
+    #[inline]
+    fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+}
@@ -0,0 +1,243 @@
+///
+/// Chunk storage
+/// Compact storage for the voxels of spawned chunks.
+///
+use std::hash::{Hash, Hasher};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{chunk::VoxelArray, chunk_shape::ChunkShape, voxel::WorldVoxel};
+
+/// The voxels of a chunk, as kept in the chunk map. Most chunks only hold a few different voxels,
+/// so each voxel is stored as a packed index into a palette of the voxels in the chunk. Chunks
+/// with so many different voxels that the indices wouldn't be smaller than the voxels themselves
+/// are stored densely instead.
+#[derive(Clone, Debug)]
+pub enum ChunkVoxels<I> {
+    /// `bits` wide indices into `palette`, packed into words without crossing word boundaries
+    Paletted {
+        palette: Vec<WorldVoxel<I>>,
+        bits: u32,
+        words: Vec<u64>,
+        /// The number of voxels
+        voxel_count: usize,
+    },
+    Dense(Box<[WorldVoxel<I>]>),
+    /// A chunk above LOD level 0, where every block of `stride` voxels along each axis, aligned to
+    /// the world grid, holds the same voxel. Only one voxel is kept for each block.
+    Sampled {
+        stride: u32,
+        /// Where the padded chunk starts within its first block
+        offset: UVec3,
+        /// The number of blocks along each axis
+        side: u32,
+        /// The number of voxels along each edge of the padded chunk
+        padded_size: u32,
+        samples: Box<ChunkVoxels<I>>,
+    },
+}
+
+impl<I: Hash + Copy + Eq> ChunkVoxels<I> {
+    /// Store the voxels of a padded chunk array, laid out by any `ChunkShape`
+    pub fn from_dense(voxels: &[WorldVoxel<I>]) -> Self {
+        Self::pack(voxels)
+    }
+
+    /// Keep one voxel for each block of `2^lod_level` voxels of a chunk generated at that level.
+    /// Blocks are never larger than the chunk.
+    pub fn sampled<S: ChunkShape>(
+        voxels: &[WorldVoxel<I>],
+        shape: &S,
+        chunk_position: IVec3,
+        lod_level: u8,
+    ) -> Self {
+        let stride = 1 << lod_level.min(shape.chunk_size().trailing_zeros() as u8);
+        let padded_size = shape.padded_size();
+        let padded_min = shape.chunk_min(chunk_position) - IVec3::ONE;
+        let offset = padded_min
+            .rem_euclid(IVec3::splat(stride as i32))
+            .as_uvec3();
+        let side = (padded_size + stride - 2) / stride + 1;
+
+        let samples: Vec<WorldVoxel<I>> = (0..side * side * side)
+            .map(|i| {
+                let block = UVec3::new(i % side, (i / side) % side, i / (side * side));
+                // The first voxel of the block that is in the padded chunk. Blocks past the end
+                // of the chunk are never read.
+                let local = (block * stride)
+                    .saturating_sub(offset)
+                    .min(UVec3::splat(padded_size - 1));
+                voxels[shape.linearize(local.to_array()) as usize]
+            })
+            .collect();
+
+        Self::Sampled {
+            stride,
+            offset,
+            side,
+            padded_size,
+            samples: Box::new(Self::pack(&samples)),
+        }
+    }
+
+    fn pack(voxels: &[WorldVoxel<I>]) -> Self {
+        let mut palette = Vec::new();
+        let mut palette_indices = HashMap::new();
+        let indices: Vec<u64> = voxels
+            .iter()
+            .map(|voxel| {
+                *palette_indices.entry(*voxel).or_insert_with(|| {
+                    palette.push(*voxel);
+                    palette.len() as u64 - 1
+                })
+            })
+            .collect();
+
+        let bits = u32::BITS - (palette.len() as u32 - 1).leading_zeros();
+        if bits as usize >= 8 * std::mem::size_of::<WorldVoxel<I>>() {
+            return Self::Dense(voxels.into());
+        }
+
+        let mut words = Vec::new();
+        if let Some(per_word) = u64::BITS.checked_div(bits) {
+            let per_word = per_word as usize;
+            words = vec![0; voxels.len().div_ceil(per_word)];
+            for (i, index) in indices.into_iter().enumerate() {
+                words[i / per_word] |= index << ((i % per_word) as u32 * bits);
+            }
+        }
+
+        Self::Paletted {
+            palette,
+            bits,
+            words,
+            voxel_count: voxels.len(),
+        }
+    }
+}
+
+impl<I> ChunkVoxels<I> {
+    /// The number of voxels in the padded chunk array
+    pub fn voxel_count(&self) -> usize {
+        match self {
+            Self::Paletted { voxel_count, .. } => *voxel_count,
+            Self::Dense(voxels) => voxels.len(),
+            Self::Sampled { padded_size, .. } => padded_size.pow(3) as usize,
+        }
+    }
+}
+
+impl<I: Copy> ChunkVoxels<I> {
+    /// The voxel at the given index of the padded chunk array
+    pub fn get(&self, index: usize) -> WorldVoxel<I> {
+        match self {
+            Self::Dense(voxels) => voxels[index],
+            Self::Sampled {
+                stride,
+                offset,
+                side,
+                padded_size,
+                samples,
+            } => {
+                let index = index as u32;
+                let local = UVec3::new(
+                    index % padded_size,
+                    (index / padded_size) % padded_size,
+                    index / (padded_size * padded_size),
+                );
+                let block = (local + *offset) / *stride;
+                samples.get((block.x + block.y * side + block.z * side * side) as usize)
+            }
+            Self::Paletted {
+                palette, bits: 0, ..
+            } => palette[0],
+            Self::Paletted {
+                palette,
+                bits,
+                words,
+                ..
+            } => {
+                let per_word = (u64::BITS / bits) as usize;
+                let word = words[index / per_word];
+                let palette_index =
+                    (word >> ((index % per_word) as u32 * bits)) & ((1 << bits) - 1);
+                palette[palette_index as usize]
+            }
+        }
+    }
+}
+
+impl<I: Copy + PartialEq> ChunkVoxels<I> {
+    /// The indices where `other` holds a different voxel, with the voxel of `other`. Paletted
+    /// voxels with the same palette are compared a word at a time, and only the words that differ
+    /// are unpacked.
+    pub fn diff(&self, other: &Self) -> Vec<(usize, WorldVoxel<I>)> {
+        match (self, other) {
+            (
+                Self::Paletted {
+                    palette,
+                    bits,
+                    words,
+                    ..
+                },
+                Self::Paletted {
+                    palette: other_palette,
+                    bits: other_bits,
+                    words: other_words,
+                    ..
+                },
+            ) if palette == other_palette && bits == other_bits => {
+                let mut diff = Vec::new();
+                if *bits == 0 {
+                    return diff;
+                }
+                let per_word = (u64::BITS / bits) as usize;
+                let mask = (1 << bits) - 1;
+                for (w, (word, other_word)) in words.iter().zip(other_words).enumerate() {
+                    let changed = word ^ other_word;
+                    if changed == 0 {
+                        continue;
+                    }
+                    for j in 0..per_word {
+                        let shift = j as u32 * bits;
+                        if (changed >> shift) & mask != 0 {
+                            let palette_index = (other_word >> shift) & mask;
+                            diff.push((w * per_word + j, palette[palette_index as usize]));
+                        }
+                    }
+                }
+                diff
+            }
+            (Self::Dense(voxels), Self::Dense(other_voxels)) => voxels
+                .iter()
+                .zip(other_voxels.iter())
+                .enumerate()
+                .filter(|(_, (voxel, other_voxel))| voxel != other_voxel)
+                .map(|(i, (_, other_voxel))| (i, *other_voxel))
+                .collect(),
+            _ => (0..self.voxel_count())
+                .filter_map(|i| {
+                    let other_voxel = other.get(i);
+                    (self.get(i) != other_voxel).then_some((i, other_voxel))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<I: Hash + Copy + Eq> From<VoxelArray<I>> for ChunkVoxels<I> {
+    fn from(voxels: VoxelArray<I>) -> Self {
+        Self::from_dense(&voxels)
+    }
+}
+
+/// Hashes the same as the dense array, so that the mesh cache keys don't depend on how the
+/// voxels are stored
+impl<I: Hash + Copy> Hash for ChunkVoxels<I> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.voxel_count());
+        for i in 0..self.voxel_count() {
+            self.get(i).hash(state);
+        }
+    }
+}
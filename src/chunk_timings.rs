@@ -0,0 +1,175 @@
+///
+/// Chunk timings
+/// Rolling timing statistics for chunk generation, meshing and edits.
+///
+use std::{
+    collections::VecDeque,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, Instant},
+};
+
+/// The number of most recent samples the statistics are based on
+const TIMING_WINDOW: usize = 256;
+
+/// The mean and percentiles over the most recent samples of a chunk task
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TimingStats {
+    pub samples: usize,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+#[derive(Default)]
+struct TimingWindow(VecDeque<Duration>);
+
+impl TimingWindow {
+    fn record(&mut self, duration: Duration) {
+        if self.0.len() == TIMING_WINDOW {
+            self.0.pop_front();
+        }
+        self.0.push_back(duration);
+    }
+
+    fn stats(&self) -> TimingStats {
+        let mut sorted: Vec<Duration> = self.0.iter().copied().collect();
+        sorted.sort_unstable();
+
+        let percentile = |p: usize| {
+            sorted
+                .get((sorted.len() * p / 100).min(sorted.len().saturating_sub(1)))
+                .copied()
+                .unwrap_or_default()
+        };
+
+        TimingStats {
+            samples: sorted.len(),
+            mean: sorted
+                .iter()
+                .sum::<Duration>()
+                .checked_div(sorted.len() as u32)
+                .unwrap_or_default(),
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: sorted.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ChunkTimingsData {
+    generation: TimingWindow,
+    meshing: TimingWindow,
+    edit_latency: TimingWindow,
+    /// When the oldest edit that is not visible yet was made, by chunk
+    pending_edits: HashMap<IVec3, Instant>,
+}
+
+/// Timings of the chunk tasks of a world, over a rolling window of recent chunks. Use this to
+/// find out whether the `voxel_lookup_delegate` or the mesher is the bottleneck, or how long
+/// edits take to show up.
+///
+/// ```
+/// use bevy::prelude::*;
+/// use bevy_voxel_world::prelude::*;
+///
+/// fn print_timings(timings: Res<ChunkTimings<DefaultWorld>>) {
+///     let generation = timings.generation();
+///     let meshing = timings.meshing();
+///     println!("generation p90: {:?}, meshing p90: {:?}", generation.p90, meshing.p90);
+/// }
+/// ```
+#[derive(Resource)]
+pub struct ChunkTimings<C> {
+    data: Arc<Mutex<ChunkTimingsData>>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for ChunkTimings<C> {
+    fn default() -> Self {
+        Self {
+            data: Arc::new(Mutex::new(ChunkTimingsData::default())),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> Clone for ChunkTimings<C> {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> ChunkTimings<C> {
+    /// Timings of generating voxel data, which includes calls to the `voxel_lookup_delegate`
+    pub fn generation(&self) -> TimingStats {
+        self.data.lock().unwrap().generation.stats()
+    }
+
+    /// Timings of meshing chunks. Chunks that don't need a mesh, or whose mesh was found in the
+    /// mesh cache, are not counted.
+    pub fn meshing(&self) -> TimingStats {
+        self.data.lock().unwrap().meshing.stats()
+    }
+
+    /// Time from a voxel being set through `VoxelWorld` until the chunk it is in gets a mesh with
+    /// the change. When several edits hit a chunk before it is remeshed, the oldest one counts.
+    pub fn edit_latency(&self) -> TimingStats {
+        self.data.lock().unwrap().edit_latency.stats()
+    }
+
+    /// Note that voxels in the given chunks were set, the first of them at `edited`. Called once
+    /// per flush of the written voxels, so the lock isn't taken for every voxel.
+    pub(crate) fn record_edits(
+        &self,
+        chunk_positions: impl IntoIterator<Item = IVec3>,
+        edited: Instant,
+    ) {
+        let pending_edits = &mut self.data.lock().unwrap().pending_edits;
+        for chunk_position in chunk_positions {
+            pending_edits.entry(chunk_position).or_insert(edited);
+        }
+    }
+
+    /// Forget the edits of a chunk that won't get meshed, because it isn't spawned
+    pub(crate) fn discard_edits(&self, chunk_position: IVec3) {
+        self.data
+            .lock()
+            .unwrap()
+            .pending_edits
+            .remove(&chunk_position);
+    }
+
+    /// Note that a chunk got a mesh from a task started at `task_started`, which includes the
+    /// edits made before it
+    pub(crate) fn record_mesh_ready(&self, chunk_position: IVec3, task_started: Instant) {
+        let mut data = self.data.lock().unwrap();
+        let Some(edited) = data.pending_edits.get(&chunk_position).copied() else {
+            return;
+        };
+        if edited <= task_started {
+            data.pending_edits.remove(&chunk_position);
+            data.edit_latency.record(edited.elapsed());
+        }
+    }
+
+    pub(crate) fn record_generation(&self, duration: Duration) {
+        self.data.lock().unwrap().generation.record(duration);
+    }
+
+    pub(crate) fn record_meshing(&self, duration: Duration) {
+        self.data.lock().unwrap().meshing.record(duration);
+    }
+}
@@ -1,11 +1,34 @@
 use std::hash::Hash;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::voxel::WorldVoxel;
-use bevy::prelude::*;
+use crate::chunk::Decoration;
+use crate::chunk_collider::ChunkColliderShape;
+use crate::chunk_generator::ChunkBuffer;
+use crate::chunk_persistence::ChunkPersistence;
+use crate::voxel::{FaceTextureIndex, VoxelFace, VoxelNeighbors, WorldVoxel, VOXEL_SIZE};
+use bevy::{prelude::*, render::render_asset::RenderAssetUsages};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
 
 pub type VoxelLookupFn<I = u8> = Box<dyn FnMut(IVec3) -> WorldVoxel<I> + Send + Sync>;
 pub type VoxelLookupDelegate<I = u8> = Box<dyn Fn(IVec3) -> VoxelLookupFn<I> + Send + Sync>;
+pub type ChunkGeneratorFn<I = u8> = Arc<dyn Fn(&mut ChunkBuffer<I>) + Send + Sync>;
+
+pub type TextureIndexMapperFn<I = u8> = Arc<dyn Fn(I) -> [u32; 3] + Send + Sync>;
+pub type FaceTextureMapperFn<I = u8> = Arc<dyn Fn(I, VoxelFace) -> u32 + Send + Sync>;
+pub type FaceVisibleFn<I = u8> = Arc<dyn Fn(I, I, VoxelFace) -> bool + Send + Sync>;
+pub type FacesMergeFn<I = u8> = Arc<dyn Fn(I, I, VoxelFace) -> bool + Send + Sync>;
+pub type MaterialGroupFn<I = u8> = Arc<dyn Fn(I) -> MaterialGroup + Send + Sync>;
+pub type VoxelTransparencyFn<I = u8> = Arc<dyn Fn(I) -> VoxelTransparency + Send + Sync>;
+pub type VoxelShapeFn<I = u8> = Arc<dyn Fn(I) -> VoxelShape + Send + Sync>;
+pub type ContextualTextureFn<I = u8> =
+    Arc<dyn Fn(IVec3, I, &VoxelNeighbors<I>) -> FaceTextureIndex + Send + Sync>;
+pub type ChunkDecoratorFn<I = u8> =
+    Arc<dyn Fn(IVec3, &dyn Fn(IVec3) -> WorldVoxel<I>) -> Vec<Decoration> + Send + Sync>;
+pub type SurfaceHeightFn = Arc<dyn Fn(IVec2) -> Option<f32> + Send + Sync>;
+pub type ChunkColliderFn<I = u8> =
+    Arc<dyn Fn(IVec3, &dyn Fn(IVec3) -> WorldVoxel<I>) -> Option<ChunkColliderShape> + Send + Sync>;
 
 #[derive(Default, PartialEq, Eq)]
 pub enum ChunkDespawnStrategy {
@@ -18,6 +41,389 @@ pub enum ChunkDespawnStrategy {
     FarAway,
 }
 
+/// How chunk meshes are built from voxels
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MeshingAlgorithm {
+    /// One quad per visible voxel face
+    #[default]
+    Simple,
+
+    /// Adjacent coplanar faces with the same texture index and ambient occlusion are merged into
+    /// larger quads. This cuts vertex counts dramatically for large flat areas. Textures repeat
+    /// across merged quads, so custom shaders need to wrap the UVs, e.g. with `fract`.
+    Greedy,
+}
+
+/// Where the voxels just outside of a chunk come from when it is meshed. The faces on the border
+/// of a chunk are only culled right if these match the voxels of the neighboring chunk. See
+/// `VoxelWorldConfig::padding_source`.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PaddingSource {
+    /// Look them up from `voxel_lookup_delegate` or the chunk generator, like the voxels of the
+    /// chunk itself
+    #[default]
+    Delegate,
+
+    /// Read them from the neighboring chunks that are already spawned, so border faces follow the
+    /// voxels those chunks actually got, even when the delegate doesn't return the same voxel
+    /// every time. Voxels of neighbors that are not spawned yet, or only hold a sample of their
+    /// voxels because they are above LOD level 0, are looked up from the delegate.
+    LoadedNeighbors,
+
+    /// Treat everything outside of the chunk as air, so that every border face gets meshed
+    Air,
+}
+
+/// How the texture coordinates of voxel faces are laid out
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UvMode {
+    /// Every voxel face shows the whole texture
+    #[default]
+    PerFace,
+
+    /// Texture coordinates follow the world position of faces, so that a texture spans
+    /// `tile_size` voxels before it repeats. Use this for large terrain textures that stretch
+    /// across many voxels. Top and bottom faces are mapped from `x` and `z`, and side faces from
+    /// the horizontal axis along the face and `y`.
+    World { tile_size: u32 },
+}
+
+/// How the texture of the built-in voxel material is laid out, see
+/// `VoxelWorldConfig::voxel_texture_mode`
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VoxelTextureMode {
+    /// A vertical strip of square textures, which is turned into an array texture with one layer
+    /// per texture. Texture indexes pick the layer.
+    #[default]
+    ArrayTexture,
+
+    /// A grid of `columns` by `rows` square tiles of `tile_size` pixels. Texture indexes pick a
+    /// tile, counting left to right and then top to bottom, and the mesher points the texture
+    /// coordinates of each face into its tile. `padding` pixels along the edges of every tile are
+    /// left out, so that neighboring tiles don't bleed in when the texture is filtered.
+    ///
+    /// Texture coordinates can't repeat within a tile, so faces are never merged by the greedy
+    /// mesher and `uv_mode` is ignored.
+    Atlas {
+        tile_size: u32,
+        columns: u32,
+        rows: u32,
+        padding: u32,
+    },
+}
+
+impl VoxelTextureMode {
+    /// Map a texture coordinate within a face to the atlas tile with the given index. Coordinates
+    /// are returned unchanged for `ArrayTexture`.
+    pub fn tex_coord(self, uv: [f32; 2], texture_index: u32) -> [f32; 2] {
+        let VoxelTextureMode::Atlas {
+            tile_size,
+            columns,
+            rows,
+            padding,
+        } = self
+        else {
+            return uv;
+        };
+        let columns = columns.max(1);
+        let tile = UVec2::new(texture_index % columns, texture_index / columns).as_vec2();
+        let tile_size = tile_size.max(1) as f32;
+        let padding = (padding as f32).min(tile_size / 2.0);
+
+        let texel = tile * tile_size + padding + Vec2::from(uv) * (tile_size - 2.0 * padding);
+        (texel / (UVec2::new(columns, rows.max(1)).as_vec2() * tile_size)).to_array()
+    }
+}
+
+/// The surface response of the built-in voxel material, see
+/// `VoxelWorldConfig::voxel_material_surface`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoxelMaterialSurface {
+    /// From 0.0 for mirror-like surfaces to 1.0 for completely rough ones
+    pub perceptual_roughness: f32,
+    /// How much light non-metallic surfaces reflect when seen straight on, from 0.0 to 1.0
+    pub reflectance: f32,
+    /// From 0.0 for dielectric surfaces to 1.0 for metals
+    pub metallic: f32,
+}
+
+impl Default for VoxelMaterialSurface {
+    fn default() -> Self {
+        Self {
+            perceptual_roughness: 0.95,
+            reflectance: 0.05,
+            metallic: 0.05,
+        }
+    }
+}
+
+/// Flood-filled voxel lighting, see `VoxelWorldConfig::voxel_lighting`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VoxelLighting {
+    /// Whether sky light shines down from above. Without it, scene lighting reaches everywhere
+    /// like it does without voxel lighting, and only the light of emissive voxels is added.
+    pub sky_light: bool,
+
+    /// How far above the voxels around a chunk each column is looked up for a voxel that shades
+    /// it from the sky, so that caves under thick ground stay dark. Open voxels under a ceiling
+    /// that starts further up than this still get sky light. A column is only looked up until
+    /// its first light blocking voxel, but chunks under open sky look up all of it.
+    pub sky_scan_height: u32,
+}
+
+impl Default for VoxelLighting {
+    fn default() -> Self {
+        Self {
+            sky_light: false,
+            sky_scan_height: 64,
+        }
+    }
+}
+
+/// How voxels of a material are meshed and drawn. Each group present in a chunk gets a mesh of
+/// its own, and faces between voxels are culled according to the groups on both sides.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MaterialGroup {
+    /// Solid blocks that hide everything behind them
+    #[default]
+    Opaque,
+
+    /// Blocks with fully transparent holes, like leaves. Faces between two cutout voxels are kept,
+    /// since they can be seen through the holes.
+    Cutout,
+
+    /// Blocks that are seen through, like glass
+    Transparent,
+
+    /// Liquids, like water
+    Fluid,
+
+    /// A group of your own, for example to draw some materials with a different shader
+    Custom(u8),
+}
+
+impl MaterialGroup {
+    /// Whether the face of a voxel in this group is meshed when it is covered by a voxel in the
+    /// `neighbor` group. Faces covered by opaque voxels are always culled, and so are faces between
+    /// voxels of the same group, except for cutout voxels.
+    pub fn face_visible_against(self, neighbor: MaterialGroup) -> bool {
+        match (self, neighbor) {
+            (_, MaterialGroup::Opaque) => false,
+            (MaterialGroup::Cutout, MaterialGroup::Cutout) => true,
+            (group, neighbor) => group != neighbor,
+        }
+    }
+
+    /// The alpha mode the built-in material uses for this group
+    pub fn alpha_mode(self) -> AlphaMode {
+        match self {
+            MaterialGroup::Opaque | MaterialGroup::Custom(_) => AlphaMode::Opaque,
+            MaterialGroup::Cutout => AlphaMode::Mask(0.5),
+            MaterialGroup::Transparent | MaterialGroup::Fluid => AlphaMode::Blend,
+        }
+    }
+}
+
+/// Whether voxels of a material can be seen through, see `VoxelWorldConfig::voxel_transparency`
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VoxelTransparency {
+    #[default]
+    Opaque,
+
+    /// Drawn in a second mesh with alpha blending, like glass or water
+    Transparent,
+}
+
+impl From<VoxelTransparency> for MaterialGroup {
+    fn from(transparency: VoxelTransparency) -> Self {
+        match transparency {
+            VoxelTransparency::Opaque => MaterialGroup::Opaque,
+            VoxelTransparency::Transparent => MaterialGroup::Transparent,
+        }
+    }
+}
+
+/// How voxels of a material can be moved over, for pathfinding and AI. See
+/// `VoxelWorldConfig::voxel_navigation`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoxelNavigation {
+    /// Whether the top of the voxel can be stood and walked on
+    pub walkable: bool,
+
+    /// The cost of moving across the voxel, relative to `1.0` for plain ground
+    pub movement_cost: f32,
+
+    /// Whether standing on the voxel does harm, like lava or spikes. Path finders should only go
+    /// over hazards when there is no other way.
+    pub hazard: bool,
+}
+
+impl VoxelNavigation {
+    /// Ground that can be walked on at the normal cost
+    pub const GROUND: Self = Self {
+        walkable: true,
+        movement_cost: 1.0,
+        hazard: false,
+    };
+
+    /// Voxels that can't be stood on, like fences or the tops of walls
+    pub const BLOCKED: Self = Self {
+        walkable: false,
+        movement_cost: f32::INFINITY,
+        hazard: false,
+    };
+}
+
+impl Default for VoxelNavigation {
+    fn default() -> Self {
+        Self::GROUND
+    }
+}
+
+/// The geometry a solid voxel is meshed with, see `VoxelWorldConfig::voxel_shape`. Shapes that
+/// have a direction take a horizontal `VoxelFace`, other faces are treated as `Forward`.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoxelShape {
+    #[default]
+    FullCube,
+
+    /// The lower half of a voxel, or the upper half if `top` is set
+    Slab { top: bool },
+
+    /// A slab with a step on top of it, with the step on the `facing` side
+    Stairs { facing: VoxelFace },
+
+    /// A slope rising from the bottom of the voxel to the top of its `facing` side
+    Ramp { facing: VoxelFace },
+}
+
+impl VoxelShape {
+    /// Whether the side of the voxel in the given direction is completely covered by the shape.
+    /// Faces of neighbors against a covered side are culled like faces against full cubes.
+    pub fn covers_side(self, direction: IVec3) -> bool {
+        match self {
+            VoxelShape::FullCube => true,
+            VoxelShape::Slab { top } => direction == if top { IVec3::Y } else { IVec3::NEG_Y },
+            VoxelShape::Stairs { facing } | VoxelShape::Ramp { facing } => {
+                direction == IVec3::NEG_Y || direction == horizontal_facing(facing)
+            }
+        }
+    }
+}
+
+/// The direction of a horizontal `VoxelFace`, with vertical faces treated as `Forward`
+pub(crate) fn horizontal_facing(facing: VoxelFace) -> IVec3 {
+    match facing {
+        VoxelFace::Left => IVec3::NEG_X,
+        VoxelFace::Right => IVec3::X,
+        VoxelFace::Back => IVec3::NEG_Z,
+        _ => IVec3::Z,
+    }
+}
+
+/// Casts the shadows of chunks from a coarser mesh than the one that is drawn. Each chunk below
+/// `lod_level` gets a child entity with a `ChunkShadowMesh` generated at that level, and its full
+/// mesh gets `NotShadowCaster`.
+///
+/// Bevy has no shadow-only meshes, so the shadow mesh is put on `render_layer` only. Add that
+/// layer to the `RenderLayers` of your lights, but not to your cameras.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowLod {
+    pub lod_level: u8,
+    pub render_layer: usize,
+}
+
+impl Default for ShadowLod {
+    fn default() -> Self {
+        Self {
+            lod_level: 1,
+            render_layer: 1,
+        }
+    }
+}
+
+/// A transition that plays when a chunk gets its first mesh, to hide chunks popping in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChunkSpawnTransition {
+    /// Fade the chunk in by animating the alpha of its material. Chunks are drawn with alpha
+    /// blending while fading. This only works with the built-in voxel material.
+    FadeIn { duration: Duration },
+
+    /// Move the chunk up into place, starting `depth` units below it
+    RiseFromBelow { depth: f32, duration: Duration },
+}
+
+impl ChunkSpawnTransition {
+    pub fn duration(&self) -> Duration {
+        match self {
+            Self::FadeIn { duration } | Self::RiseFromBelow { duration, .. } => *duration,
+        }
+    }
+}
+
+/// A cheap stand-in for chunks that are still being generated and have no mesh yet. See
+/// `VoxelWorldConfig::chunk_placeholder`.
+#[derive(Clone)]
+pub enum ChunkPlaceholder {
+    /// A flat box of `color`, filling the chunk from its bottom up to the terrain surface.
+    /// `surface_height` is called with the voxel x and z at the middle of the chunk, and returns
+    /// the height of the surface there in voxels, or `None` for no placeholder. Only the chunk
+    /// that the surface passes through gets a box.
+    FlatBox {
+        color: Color,
+        surface_height: SurfaceHeightFn,
+    },
+}
+
+/// How the collision shapes of chunks are built. See `VoxelWorldConfig::chunk_collider_strategy`.
+#[derive(Default)]
+pub enum ChunkColliderStrategy<I = u8> {
+    /// No colliders
+    #[default]
+    None,
+
+    /// A triangle mesh of the chunk surface, with coplanar faces merged
+    Trimesh,
+
+    /// A compound of boxes covering the solid voxels of the chunk
+    Cuboids,
+
+    /// A function called from the chunk thread with the chunk position and a lookup for the
+    /// voxels of the chunk (and one voxel around it) by world position. Positions in the returned
+    /// shape are relative to the chunk entity, which sits one voxel below the chunk position on
+    /// every axis.
+    Custom(ChunkColliderFn<I>),
+}
+
+impl<I> Clone for ChunkColliderStrategy<I> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::None => Self::None,
+            Self::Trimesh => Self::Trimesh,
+            Self::Cuboids => Self::Cuboids,
+            Self::Custom(collider_fn) => Self::Custom(collider_fn.clone()),
+        }
+    }
+}
+
+/// How chunk entities are named and grouped in the hierarchy, to make large worlds easier to
+/// navigate in an entity inspector. See `VoxelWorldConfig::chunk_hierarchy`.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkHierarchy {
+    /// Chunks are unnamed children of the world root
+    #[default]
+    Flat,
+
+    /// Chunks are children of the world root, with a `Name` made from the world config type and
+    /// the chunk position
+    Named,
+
+    /// Chunks are named like `Named`, and grouped under `ChunkRegion` entities of `region_size`
+    /// chunks along each axis. Regions are named after their position, are children of the world
+    /// root, and get despawned with their last chunk.
+    Regions { region_size: u32 },
+}
+
 #[derive(Default, PartialEq, Eq)]
 pub enum ChunkSpawnStrategy {
     /// Spawn chunks that are within `spawning_distance` of the camera
@@ -32,6 +438,74 @@ pub enum ChunkSpawnStrategy {
     Close,
 }
 
+/// The most chunks a single camera can keep loaded with the given config: the chunks within
+/// `spawning_distance`, or `soft_despawn_distance` if that is further, of the chunk the camera is
+/// in. The chunks an orthographic camera has in view are within that distance too. Chunks around
+/// other cameras and spawn anchors come on top of this. `max_loaded_chunks` caps the distances,
+/// but this counts the chunks for the configured distances.
+pub fn theoretical_chunk_count(config: &impl VoxelWorldConfig) -> usize {
+    let distance = config
+        .soft_despawn_distance()
+        .map_or(config.spawning_distance(), |distance| {
+            distance.max(config.spawning_distance())
+        });
+    sphere_chunk_count(distance)
+}
+
+/// The number of chunk offsets within `radius` chunks of the origin
+fn sphere_chunk_count(radius: u32) -> usize {
+    let radius = radius as i64;
+    let mut count = 0;
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            let rest = radius.pow(2) - x.pow(2) - y.pow(2);
+            if rest >= 0 {
+                count += 2 * (rest as f64).sqrt() as usize + 1;
+            }
+        }
+    }
+    count
+}
+
+/// Whether the configured distances load more than `max_loaded_chunks`. The bounding cube is
+/// checked first, since this runs for every chunk spawn anchors consider.
+fn exceeds_max_loaded_chunks(config: &impl VoxelWorldConfig) -> bool {
+    let distance = config
+        .soft_despawn_distance()
+        .unwrap_or(0)
+        .max(config.spawning_distance()) as usize;
+    let cube = (2 * distance + 1).checked_pow(3).unwrap_or(usize::MAX);
+    cube > config.max_loaded_chunks()
+        && theoretical_chunk_count(config) > config.max_loaded_chunks()
+}
+
+/// The largest distance that stays within `max_loaded_chunks`
+fn max_loaded_distance(config: &impl VoxelWorldConfig) -> u32 {
+    let max_chunks = config.max_loaded_chunks();
+    let mut distance = (max_chunks as f64 * 3.0 / (4.0 * std::f64::consts::PI)).cbrt() as u32 + 1;
+    while distance > 0 && sphere_chunk_count(distance) > max_chunks {
+        distance -= 1;
+    }
+    distance
+}
+
+/// `spawning_distance`, lowered to fit within `max_loaded_chunks`
+pub(crate) fn capped_spawning_distance(config: &impl VoxelWorldConfig) -> u32 {
+    if !exceeds_max_loaded_chunks(config) {
+        return config.spawning_distance();
+    }
+    config.spawning_distance().min(max_loaded_distance(config))
+}
+
+/// `soft_despawn_distance`, lowered to fit within `max_loaded_chunks`
+pub(crate) fn capped_soft_despawn_distance(config: &impl VoxelWorldConfig) -> Option<u32> {
+    let distance = config.soft_despawn_distance()?;
+    if !exceeds_max_loaded_chunks(config) {
+        return Some(distance);
+    }
+    Some(distance.min(max_loaded_distance(config)))
+}
+
 /// `bevy_voxel_world` configuation structs need to implement this trait
 pub trait VoxelWorldConfig: Resource + Default + Clone {
     type MaterialIndex: Copy + Hash + PartialEq + Eq + Default + Send + Sync;
@@ -41,6 +515,39 @@ pub trait VoxelWorldConfig: Resource + Default + Clone {
         10
     }
 
+    /// How many seconds of camera movement to look ahead when spawning chunks. Chunks are spawned
+    /// and despawned around the point the camera is heading to, instead of around the camera,
+    /// so that more chunks are ready ahead of the camera and fewer are kept behind it. The look
+    /// ahead is limited to half the `spawning_distance`. `0.0` disables this.
+    fn prefetch_lookahead(&self) -> f32 {
+        0.0
+    }
+
+    /// The most chunks that `spawning_distance` and `soft_despawn_distance` may keep loaded around a
+    /// single camera, as counted by `theoretical_chunk_count`. Distances that would load more are
+    /// lowered until they fit, with a warning at startup, so that a typo in the distance doesn't
+    /// fill up memory and the task queues. Level of detail makes far chunks cheaper, and is the
+    /// better way to see far.
+    fn max_loaded_chunks(&self) -> usize {
+        50_000
+    }
+
+    /// The most chunks handed to `VoxelWorld::insert_chunk` before they spawned that are kept
+    /// until they do. Past that, the chunks inserted first are dropped, and are looked up as usual
+    /// when they spawn.
+    fn max_unspawned_inserted_chunks(&self) -> usize {
+        256
+    }
+
+    /// Chunks further away than `spawning_distance`, but within this distance, are hidden instead
+    /// of despawned. Hidden chunks keep their voxel data and mesh, so they show up again right
+    /// away when the camera comes back, instead of being generated and meshed again. Chunks are
+    /// only despawned once they are further away than this. `None` despawns chunks as soon as
+    /// they are out of range.
+    fn soft_despawn_distance(&self) -> Option<u32> {
+        None
+    }
+
     /// Strategy for despawning chunks
     fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
         ChunkDespawnStrategy::default()
@@ -66,26 +573,354 @@ pub trait VoxelWorldConfig: Resource + Default + Clone {
         100
     }
 
+    /// The random number generator behind everything random the world does, like the directions
+    /// of the spawning rays and `VoxelWorld::get_random_surface_voxel`. Return a seeded
+    /// generator, like `StdRng::seed_from_u64(seed)`, to make replays and tests reproducible, or
+    /// one forked from `bevy_rand`. It is created once, when the world is set up, and can be
+    /// swapped later with `VoxelWorld::set_rng`. By default it is seeded by the operating system.
+    fn rng(&self) -> Box<dyn RngCore + Send + Sync> {
+        Box::new(StdRng::from_entropy())
+    }
+
     /// How far outside of the viewports spawning rays should get cast. Higher values will
     /// will reduce the likelyhood of chunks popping in, but will also increase cpu load.
     fn spawning_ray_margin(&self) -> u32 {
         25
     }
 
+    /// How many chunks beyond the edges of the viewports get spawned. Every chunk a spawning ray
+    /// passes through also spawns the chunks within this many chunks of it, so the margin is the
+    /// same at any screen resolution and distance. When this returns `Some`, rays are only cast
+    /// within the viewports and `spawning_ray_margin` is ignored.
+    fn spawning_chunk_margin(&self) -> Option<u32> {
+        None
+    }
+
+    /// Maximum number of chunks that can be generated and meshed in the background at once. Dirty
+    /// chunks beyond the budget wait for a later frame, closest to a camera first, and chunks in
+    /// front of a camera before those behind it. Pre-seeded chunks are not limited.
+    fn generation_task_budget(&self) -> usize {
+        usize::MAX
+    }
+
+    /// Maximum number of finished chunks that get their meshes, decorations and colliders
+    /// inserted in a given frame, closest to a camera first. Lowering it spreads the cost of a
+    /// burst of chunks, like after teleporting the camera, over several frames. Pre-seeded chunks
+    /// are not limited.
+    fn max_mesh_inserts_per_frame(&self) -> usize {
+        usize::MAX
+    }
+
+    /// Maximum number of chunks that tasks started with `VoxelWorld::spawn_task` are started on
+    /// in a given frame, so that a task over a large region doesn't copy all of its chunks at
+    /// once. Chunks of the region that are skipped because they are not spawned count as well.
+    fn max_task_chunks_per_frame(&self) -> usize {
+        16
+    }
+
+    /// Maximum number of retired chunks that get despawned in a given frame. Despawning many chunk
+    /// entities at once, like after teleporting the camera or lowering `spawning_distance`, can
+    /// cause a hitch, so lowering this spreads the despawns over several frames. Retired chunks
+    /// are hidden right away, and get despawned once it is their turn.
+    fn max_despawn_per_frame(&self) -> usize {
+        usize::MAX
+    }
+
+    /// The number of recently despawned chunks whose voxels are kept in memory, so that moving
+    /// back and forth along a path doesn't run the lookup delegate or chunk generator for the same
+    /// chunks over and over. The voxels are kept in the same palette-compressed form as those of
+    /// spawned chunks, so most chunks take a few kilobytes. `invalidate_all` and
+    /// `invalidate_region` drop the cached chunks too. `0` turns the cache off.
+    fn despawned_chunk_cache_size(&self) -> usize {
+        0
+    }
+
+    /// The number of entities of despawned chunks kept around for new chunks to reuse. When the
+    /// camera moves fast, chunks get despawned and spawned every frame, and reusing the entities
+    /// saves spawning and despawning them. Pooled entities have all their components removed,
+    /// including the ones added by the app, and their children despawned. `0` turns the pool off.
+    fn chunk_entity_pool_size(&self) -> usize {
+        0
+    }
+
+    /// How long a chunk may take to generate and mesh, counted from when it gets queued, before it
+    /// is given up on, for example because the lookup delegate is stuck in a loop. The chunk is
+    /// then logged and failed with a `ChunkGenerationFailed` event, and no longer counts against
+    /// `generation_task_budget`. The stuck task can't be stopped, so it keeps its worker thread
+    /// busy. `None` waits forever.
+    fn chunk_generation_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// The size of a voxel in world units. The world is built in voxel coordinates, and scaled by
+    /// this on the root entity, so chunks cover `32 * voxel_size` world units. Positions passed to
+    /// `get_voxel` and friends are voxel coordinates, see `VoxelWorld::world_to_voxel`.
+    fn voxel_size(&self) -> f32 {
+        VOXEL_SIZE
+    }
+
+    /// The region chunks can exist in, as the minimum and maximum chunk position, inclusive.
+    /// Chunks outside of it are never spawned, not even around the camera, `set_voxel` outside of
+    /// it is rejected, and the lookup delegate isn't called for voxels outside of it, which are
+    /// air instead. `None` leaves the world unbounded.
+    fn world_bounds(&self) -> Option<(IVec3, IVec3)> {
+        None
+    }
+
+    /// Radius in chunks around the initial camera position that will be generated and meshed
+    /// before the first frame is rendered, so the world doesn't start out as an empty void while
+    /// the regular async spawning catches up. `0` disables pre-seeding.
+    fn startup_preseed_radius(&self) -> u32 {
+        0
+    }
+
+    /// Radius in chunks around the camera chunk that needs to be meshed before the
+    /// `FirstChunksReady` event is sent. `0` only waits for the chunk the camera is in.
+    fn first_chunks_radius(&self) -> u32 {
+        1
+    }
+
+    /// Number of levels of detail for chunks. Level 0 is full detail, and each level after it
+    /// halves the voxel resolution of a chunk, by sampling the `voxel_lookup_delegate` only once
+    /// for every 2x2x2, 4x4x4, 8x8x8... block of voxels. Chunks get remeshed at the new level
+    /// as the camera moves. The default of `1` disables LOD.
+    ///
+    /// Voxel lookups in chunks above level 0 return the sampled voxels, which is all those chunks
+    /// keep in memory. Decorations are only placed at full detail, and are removed when a chunk
+    /// moves further away.
+    fn lod_levels(&self) -> u8 {
+        1
+    }
+
+    /// Distance in chunks from the camera beyond which chunks use the given level of detail.
+    /// Only called for levels `1..lod_levels()`, and should increase with the level.
+    fn lod_distance(&self, level: u8) -> u32 {
+        level as u32 * 8
+    }
+
+    /// Cast the shadows of chunks from a decimated mesh, to cut the vertex cost of shadow maps.
+    /// See `ShadowLod`.
+    fn shadow_lod(&self) -> Option<ShadowLod> {
+        None
+    }
+
+    /// A transition to play for chunks appearing for the first time. Remeshing a chunk that is
+    /// already visible does not play it again.
+    fn chunk_spawn_transition(&self) -> Option<ChunkSpawnTransition> {
+        None
+    }
+
+    /// Show a placeholder for chunks while their first mesh is being built, so that moving the
+    /// camera quickly shows approximate terrain instead of holes. Chunks that already have a mesh,
+    /// such as chunks changing their level of detail, keep showing it instead.
+    fn chunk_placeholder(&self) -> Option<ChunkPlaceholder> {
+        None
+    }
+
+    /// Attach a `ChunkCollider` with the collision shape of each chunk to the chunk entity, and
+    /// keep it in sync when the chunk is remeshed. With the `bevy_rapier3d` feature enabled, a
+    /// rapier `Collider` is attached as well.
+    fn chunk_collider_strategy(&self) -> ChunkColliderStrategy<Self::MaterialIndex> {
+        ChunkColliderStrategy::None
+    }
+
     /// Debugging aids
     fn debug_draw_chunks(&self) -> bool {
         false
     }
 
+    /// How chunk entities are named and grouped, for debugging with an entity inspector
+    fn chunk_hierarchy(&self) -> ChunkHierarchy {
+        ChunkHierarchy::default()
+    }
+
+    /// Flood-fill light from emissive voxels, and optionally the sky, through the voxels of each
+    /// chunk and bake it into the vertices of chunk meshes. Light levels go from 0 to `MAX_LIGHT`,
+    /// and drop by one for every voxel the light travels. Sky light travels straight down without
+    /// getting dimmer. Light passes through air and through voxels that are not in the opaque
+    /// material group or that have a shape, and reaches across chunk borders.
+    ///
+    /// The built-in material adds the block light as emissive light, and dims scene lighting where
+    /// there is no sky light. Custom materials can read the light from `ATTRIBUTE_VOXEL_LIGHT`.
+    ///
+    /// Each chunk looks up the voxels up to `MAX_LIGHT - 1` voxels around it to find the light
+    /// reaching it, which makes generation considerably slower. Sky light enters from the top of
+    /// that area, where the columns above are looked up for overhangs up to
+    /// `VoxelLighting::sky_scan_height` voxels further. Faces are never merged by the greedy
+    /// mesher when lighting is on, and chunks above LOD level 0 are not lit.
+    fn voxel_lighting(&self) -> Option<VoxelLighting> {
+        None
+    }
+
+    /// The light level a voxel material emits, from 0 to `MAX_LIGHT`, when `voxel_lighting` is on
+    fn voxel_emission(&self, _index: Self::MaterialIndex) -> u8 {
+        0
+    }
+
     /// A function that maps voxel materials to texture coordinates.
     /// The input is the material index, and the output is a slice of three indexes into an array texture.
     /// The three values correspond to the top, sides and bottom of the voxel. For example,
     /// if the slice is `[1,2,2]`, the top will use texture index 1 and the sides and bottom will use texture
     /// index 2.
+    /// With `VoxelTextureMode::Atlas`, the indexes are tiles of the atlas instead.
     fn texture_index_mapper(&self) -> Arc<dyn Fn(Self::MaterialIndex) -> [u32; 3] + Send + Sync> {
         Arc::new(|_mat| [0, 0, 0])
     }
 
+    /// An alternative to `texture_index_mapper` that gets called at mesh time for every visible
+    /// face, with the material index and the direction of the face. It returns a single texture
+    /// index for that face. When this returns `Some`, it takes precedence over `texture_index_mapper`.
+    fn texture_index_face_mapper(&self) -> Option<FaceTextureMapperFn<Self::MaterialIndex>> {
+        None
+    }
+
+    /// A hook for picking textures based on the surroundings of a voxel. It gets called at mesh time
+    /// with the world position of the voxel, its material index and its six neighbors. This can be
+    /// used to for example render grass that is covered by another voxel as dirt, without needing an
+    /// extra material index for it. When this returns `Some`, it takes precedence over both
+    /// `texture_index_face_mapper` and `texture_index_mapper`.
+    fn contextual_texture(&self) -> Option<ContextualTextureFn<Self::MaterialIndex>> {
+        None
+    }
+
+    /// Decides whether a face gets meshed between two solid voxels. It is called at mesh time with the
+    /// material index of the voxel the face belongs to, the material index of the neighbor that
+    /// covers it, and which face of the voxel it is. This can be used to for example render the
+    /// faces between water and glass, while still culling the faces between two glass blocks. Faces
+    /// next to air are always meshed, and without this hook, faces between two solid voxels never
+    /// are.
+    fn face_visible(&self) -> Option<FaceVisibleFn<Self::MaterialIndex>> {
+        None
+    }
+
+    /// Sorts materials into groups, see `MaterialGroup`. Voxels in the `Opaque` group are meshed
+    /// into the chunk entity as usual, and every other group present in a chunk gets a child entity
+    /// with a `ChunkGroupMesh` component. With the built-in material, the child entities are drawn
+    /// with the alpha mode of their group. With a custom material, they get the same material as
+    /// the chunk, which can be swapped out by querying for `ChunkGroupMesh`. When `face_visible` is
+    /// also supplied, it decides about faces between solid voxels instead of the group rules.
+    fn material_group(&self) -> Option<MaterialGroupFn<Self::MaterialIndex>> {
+        None
+    }
+
+    /// Whether and at what cost the top of a voxel material can be walked on. Query it with
+    /// `VoxelWorld::get_voxel_navigation` and `VoxelWorld::standing_navigation`, so that AI can
+    /// tell terrain types apart without a lookup table of its own.
+    fn voxel_navigation(&self, _index: Self::MaterialIndex) -> VoxelNavigation {
+        VoxelNavigation::GROUND
+    }
+
+    /// A simpler alternative to `material_group`, for worlds that only need opaque and transparent
+    /// voxels. Transparent voxels are meshed separately and drawn with `AlphaMode::Blend`, and the
+    /// faces of opaque voxels behind them are kept. Faces between two transparent voxels are
+    /// culled. This is ignored when `material_group` is supplied.
+    fn voxel_transparency(&self) -> Option<VoxelTransparencyFn<Self::MaterialIndex>> {
+        None
+    }
+
+    /// The height of the surface of fluids, as a fraction of a voxel. The top faces of voxels in
+    /// the `MaterialGroup::Fluid` group that have no solid voxel above them are lowered to this
+    /// height, along with the top edges of their side faces, so that the surface sits below the
+    /// shore instead of z-fighting with it. Fluid faces are never merged by the greedy mesher.
+    fn fluid_surface_height(&self) -> f32 {
+        0.875
+    }
+
+    /// Gives voxels of a material a shape other than a full cube, see `VoxelShape`. Shaped voxels
+    /// are meshed with their own geometry, and the faces of neighbors against them are only culled
+    /// where the shape covers the whole side. Shapes only change how voxels look, raycasts and
+    /// colliders still treat them as full cubes. Chunks above LOD level 0 and shadow meshes draw
+    /// them as full cubes too.
+    fn voxel_shape(&self) -> Option<VoxelShapeFn<Self::MaterialIndex>> {
+        None
+    }
+
+    /// Chunk meshes are cached by their voxel content, and reused for chunks with identical voxels.
+    /// If the texture mapping changes at runtime, bump this version so that meshes generated with
+    /// the previous mapping are not reused. When persisting the mesh cache with `mesh_cache_path`,
+    /// also bump it whenever the meshing callbacks, like `face_visible`, `faces_merge`,
+    /// `voxel_shape` or `contextual_texture`, start returning something different, since the
+    /// cache file can't tell.
+    fn texture_mapping_version(&self) -> u64 {
+        0
+    }
+
+    /// Whether the chunk position should be part of the mesh cache key. This is needed when the mesh
+    /// of a chunk depends on where in the world it is, and not only on its voxels. Defaults to `true`
+    /// when a `contextual_texture` hook is used, since it receives the voxel world position.
+    fn mesh_cache_per_chunk(&self) -> bool {
+        self.contextual_texture().is_some()
+    }
+
+    /// A file to persist the mesh cache in between runs. When set, the cache is restored from this
+    /// file at startup and written back when the app exits, so that commonly repeated chunks (flat
+    /// ground, ocean) don't need to be remeshed every session. The file is ignored if it was written
+    /// by a different world config, texture mapping version or version of the mesher.
+    fn mesh_cache_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// The algorithm used to build chunk meshes. See `MeshingAlgorithm`.
+    fn meshing_algorithm(&self) -> MeshingAlgorithm {
+        MeshingAlgorithm::default()
+    }
+
+    /// Decides whether the greedy mesher may merge two adjacent faces into one quad. It is called
+    /// with the material index of the voxel the quad starts at, the material index of the voxel it
+    /// would grow over, and the direction of the faces. Faces are only merged when their texture
+    /// index and ambient occlusion also match, so this is for materials that share a texture but
+    /// still look different, for example through a tint or an overlay in a custom shader.
+    /// Without this hook, such faces are merged and drawn like the first voxel of the quad.
+    fn faces_merge(&self) -> Option<FacesMergeFn<Self::MaterialIndex>> {
+        None
+    }
+
+    /// Where the voxels just outside of a chunk come from when it is meshed, which decides the
+    /// faces on its border. See `PaddingSource`.
+    fn padding_source(&self) -> PaddingSource {
+        PaddingSource::default()
+    }
+
+    /// How the texture coordinates of voxel faces are laid out. See `UvMode`.
+    fn uv_mode(&self) -> UvMode {
+        UvMode::default()
+    }
+
+    /// How the texture from `voxel_texture` is laid out, as an array texture strip or an atlas.
+    /// See `VoxelTextureMode`.
+    fn voxel_texture_mode(&self) -> VoxelTextureMode {
+        VoxelTextureMode::default()
+    }
+
+    /// A function that places decorations, such as rocks, trees or grass, in a chunk. It is called
+    /// from the chunk thread with the chunk position and a lookup for the generated voxels of the
+    /// chunk (and one voxel around it) by world position.
+    ///
+    /// Each `Decoration` gets spawned as a child entity of the chunk, with a `ChunkDecoration<Self>`
+    /// component and a `SpatialBundle`, so it streams in and out along with the chunk. Add meshes
+    /// or sprites to them in a system that queries for `Added<ChunkDecoration<Self>>`. Decorations
+    /// are placed again whenever the chunk is remeshed.
+    fn chunk_decorator(&self) -> Option<ChunkDecoratorFn<Self::MaterialIndex>> {
+        None
+    }
+
+    /// Group the decorations of each kind in a chunk into a single `ChunkDecorationInstances`
+    /// entity, instead of spawning one `ChunkDecoration` entity per decoration. Combined with
+    /// prop meshes registered in the `DecorationMeshes` resource, each chunk then makes one
+    /// instanced draw call per decoration kind, which keeps entity counts down at high view
+    /// distances.
+    fn decoration_instancing(&self) -> bool {
+        false
+    }
+
+    /// Storage for modified voxels, so that edits made with `set_voxel` survive restarts. The
+    /// modified voxels of a chunk are saved when it despawns and when the app exits, and loaded
+    /// back before the chunk is generated again. See `FileChunkPersistence` for a file based
+    /// implementation.
+    fn chunk_persistence(&self) -> Option<Arc<dyn ChunkPersistence<Self::MaterialIndex>>> {
+        None
+    }
+
     /// A function that returns a function that returns true if a voxel exists at the given position
     /// The delegate will be called every time a new chunk needs to be computed. The delegate should
     /// return a function that can be called to check if a voxel exists at a given position. This function
@@ -94,11 +929,65 @@ pub trait VoxelWorldConfig: Resource + Default + Clone {
         Box::new(|_| Box::new(|_| WorldVoxel::Unset))
     }
 
+    /// Reuse the functions built by `voxel_lookup_delegate` for later chunks, instead of building
+    /// a new one for every chunk. This pays off when building the function is expensive, like
+    /// setting up noise generators or loading data. Up to one function per worker thread is kept.
+    /// Only turn this on if the function doesn't depend on the chunk position it was built for,
+    /// since it gets called for the voxels of other chunks too. Anything the function remembers
+    /// stays with it from chunk to chunk, so it should be bounded, like the local samples of
+    /// `ColumnCache::delegate` are.
+    fn reuse_voxel_lookup_fns(&self) -> bool {
+        false
+    }
+
+    /// An alternative to `voxel_lookup_delegate` that generates a whole chunk at once. It is called
+    /// from the chunk thread with a `ChunkBuffer` covering the chunk and the voxels around it,
+    /// which it fills in, for example one column at a time with `ChunkBuffer::fill_column`. This
+    /// lets terrain generators sample a heightmap once per column, or use the batch APIs of noise
+    /// libraries, instead of being called once for every voxel. When this returns `Some`,
+    /// `voxel_lookup_delegate` is not used.
+    ///
+    /// Chunks above LOD level 0 are still generated at full detail, and then sampled like the
+    /// lookup delegate would be.
+    fn chunk_generator(&self) -> Option<ChunkGeneratorFn<Self::MaterialIndex>> {
+        None
+    }
+
+    /// The roughness, reflectance and metalness of the built-in voxel material. The defaults give
+    /// a matte look. This has no effect on custom materials.
+    fn voxel_material_surface(&self) -> VoxelMaterialSurface {
+        VoxelMaterialSurface::default()
+    }
+
     /// A tuple of the path to the texture and the number of indexes in the texture. `None` if no texture is used.
+    /// The number of indexes is ignored for texture atlases, see `voxel_texture_mode`.
     fn voxel_texture(&self) -> Option<(String, u32)> {
         None
     }
 
+    /// The `RenderAssetUsages` of chunk meshes. By default, chunk meshes keep their CPU side
+    /// copy after being uploaded to the GPU, which is needed for things like exporting meshes or
+    /// building colliders from them. Using `RenderAssetUsages::RENDER_WORLD` instead roughly halves
+    /// the memory used by chunk meshes, but the meshes can then no longer be read back from
+    /// `Assets<Mesh>`. Meshes that are no longer available on the CPU are also left out when
+    /// persisting the mesh cache through `mesh_cache_path`.
+    fn chunk_mesh_asset_usage(&self) -> RenderAssetUsages {
+        RenderAssetUsages::default()
+    }
+
+    /// Store the normals of chunk meshes as four `i8`s, their texture coordinates as half
+    /// precision floats and their colors as four `u8`s, which takes the vertices from 60 to 36
+    /// bytes. The built-in material reads both layouts. Custom materials have to use
+    /// `compact_vertex_layout` and the `ATTRIBUTE_COMPACT_*` attributes from
+    /// `bevy_voxel_world::rendering` instead of the standard mesh attributes. Compact meshes are
+    /// not persisted through `mesh_cache_path`.
+    ///
+    /// This needs the `compact_vertices` feature. Without it, the meshes keep the standard layout
+    /// whatever this returns.
+    fn compact_vertex_attributes(&self) -> bool {
+        false
+    }
+
     /// Custom material will not get initialized if this returns false. When this is false,
     /// `VoxelWorldMaterialHandle` needs to be manually added with a reference to the material handle.
     ///
@@ -129,3 +1018,22 @@ impl VoxelWorldConfig for DefaultWorld {
         })
     }
 }
+
+/// Implement this next to `VoxelWorldConfig` to draw the chunks of a world with a material type
+/// of your own, for example an `ExtendedMaterial<StandardMaterial, MyExtension>`, and add the
+/// plugin with `VoxelWorldPlugin::with_chunk_material`. The material is built once when the
+/// plugin is added, and every chunk gets a `Handle<Self::ChunkMaterial>` to it.
+pub trait ChunkMaterialConfig: VoxelWorldConfig {
+    type ChunkMaterial: Material;
+
+    /// Build the material for the chunks. The world can be used to load or add the textures the
+    /// material needs. The `MaterialPlugin` for the material type needs to be added separately.
+    fn chunk_material(&self, world: &mut World) -> Self::ChunkMaterial;
+}
+
+/// Implement this for worlds that store extra data on voxels, like the contents of a chest or the
+/// growth stage of a crop. Metadata is set and read with `VoxelWorld::set_voxel_metadata` and
+/// `VoxelWorld::get_voxel_metadata`, and handed out in `ChunkMetadataWillDespawn` events.
+pub trait VoxelMetadataConfig: VoxelWorldConfig {
+    type VoxelMetadata: Clone + Send + Sync + 'static;
+}
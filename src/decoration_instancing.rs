@@ -0,0 +1,379 @@
+///
+/// Decoration instancing
+/// Draws the prop mesh of each `ChunkDecorationInstances` entity once per decoration in a single
+/// instanced draw call, from one buffer of instance transforms per entity.
+///
+use std::sync::Arc;
+
+use bevy::{
+    asset::load_internal_asset,
+    core_pipeline::{
+        core_3d::Transparent3d,
+        prepass::{DeferredPrepass, DepthPrepass, MotionVectorPrepass, NormalPrepass},
+        tonemapping::{DebandDither, Tonemapping},
+    },
+    ecs::{
+        entity::EntityHashMap,
+        query::QueryItem,
+        system::{lifetimeless::*, SystemParamItem},
+    },
+    math::Vec3A,
+    pbr::{
+        tonemapping_pipeline_key, MeshPipeline, MeshPipelineKey, RenderMeshInstances,
+        SetMeshBindGroup, SetMeshViewBindGroup,
+    },
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        mesh::{GpuBufferInfo, GpuMesh, MeshVertexBufferLayoutRef},
+        primitives::Aabb,
+        render_asset::RenderAssets,
+        render_phase::{
+            AddRenderCommand, DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand,
+            RenderCommandResult, SetItemPipeline, TrackedRenderPass, ViewSortedRenderPhases,
+        },
+        render_resource::*,
+        renderer::RenderDevice,
+        view::ExtractedView,
+        Render, RenderApp, RenderSet,
+    },
+};
+use bytemuck::{Pod, Zeroable};
+
+pub(crate) const DECORATION_INSTANCING_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(6998301138411443009);
+
+/// The world transform of one decoration instance, as the rows of an affine matrix
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct DecorationInstance {
+    rows: [[f32; 4]; 3],
+}
+
+impl DecorationInstance {
+    pub fn from_transform(transform: &GlobalTransform) -> Self {
+        let columns = transform.affine().to_cols_array_2d();
+        Self {
+            rows: [0, 1, 2].map(|row| {
+                [
+                    columns[0][row],
+                    columns[1][row],
+                    columns[2][row],
+                    columns[3][row],
+                ]
+            }),
+        }
+    }
+}
+
+/// The instance transforms of a `ChunkDecorationInstances` entity, in world space. These are
+/// rebuilt when the entity moves, and shared with the render world without copying.
+#[derive(Component, Clone, Debug)]
+pub(crate) struct DecorationInstanceData(pub Arc<[DecorationInstance]>);
+
+impl ExtractComponent for DecorationInstanceData {
+    type QueryData = (&'static DecorationInstanceData, &'static ViewVisibility);
+    type QueryFilter = ();
+    type Out = Self;
+
+    fn extract_component(
+        (instances, view_visibility): QueryItem<'_, Self::QueryData>,
+    ) -> Option<Self> {
+        view_visibility.get().then(|| instances.clone())
+    }
+}
+
+/// Bounds that cover a prop with the given bounds at each of the given transforms
+pub(crate) fn instances_aabb(prop: Aabb, transforms: &[Transform]) -> Aabb {
+    if transforms.is_empty() {
+        return Aabb::default();
+    }
+
+    let corners = [-1.0, 1.0]
+        .into_iter()
+        .flat_map(|x| [-1.0, 1.0].into_iter().map(move |y| (x, y)))
+        .flat_map(|(x, y)| [-1.0, 1.0].into_iter().map(move |z| Vec3A::new(x, y, z)))
+        .map(|sign| prop.center + prop.half_extents * sign)
+        .collect::<Vec<_>>();
+
+    let mut min = Vec3A::splat(f32::MAX);
+    let mut max = Vec3A::splat(f32::MIN);
+    for transform in transforms {
+        let matrix = transform.compute_affine();
+        for corner in &corners {
+            let corner = matrix.transform_point3a(*corner);
+            min = min.min(corner);
+            max = max.max(corner);
+        }
+    }
+
+    Aabb::from_min_max(min.into(), max.into())
+}
+
+/// Draws the `DecorationInstanceData` of every world. Added once, by the first
+/// `VoxelWorldPlugin` that spawns meshes.
+pub(crate) struct DecorationInstancingPlugin;
+
+impl Plugin for DecorationInstancingPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            DECORATION_INSTANCING_SHADER_HANDLE,
+            "shaders/decoration_instancing.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.add_plugins(ExtractComponentPlugin::<DecorationInstanceData>::default());
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .add_render_command::<Transparent3d, DrawDecorationInstances>()
+            .init_resource::<SpecializedMeshPipelines<DecorationInstancingPipeline>>()
+            .init_resource::<DecorationInstanceBuffers>()
+            .add_systems(
+                Render,
+                (
+                    queue_decoration_instances.in_set(RenderSet::QueueMeshes),
+                    prepare_decoration_instance_buffers.in_set(RenderSet::PrepareResources),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<DecorationInstancingPipeline>();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn queue_decoration_instances(
+    transparent_3d_draw_functions: Res<DrawFunctions<Transparent3d>>,
+    instancing_pipeline: Res<DecorationInstancingPipeline>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<DecorationInstancingPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    meshes: Res<RenderAssets<GpuMesh>>,
+    render_mesh_instances: Res<RenderMeshInstances>,
+    instanced_decorations: Query<Entity, With<DecorationInstanceData>>,
+    mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent3d>>,
+    views: Query<(
+        Entity,
+        &ExtractedView,
+        Option<&Tonemapping>,
+        Option<&DebandDither>,
+        (
+            Has<DepthPrepass>,
+            Has<NormalPrepass>,
+            Has<MotionVectorPrepass>,
+            Has<DeferredPrepass>,
+        ),
+    )>,
+) {
+    let draw_function = transparent_3d_draw_functions
+        .read()
+        .id::<DrawDecorationInstances>();
+    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples());
+
+    for (view_entity, view, tonemapping, dither, prepasses) in views.iter() {
+        let Some(transparent_phase) = transparent_render_phases.get_mut(&view_entity) else {
+            continue;
+        };
+
+        // The view bind group layout depends on the prepasses of the view
+        let (depth_prepass, normal_prepass, motion_vector_prepass, deferred_prepass) = prepasses;
+        let mut view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
+        if depth_prepass {
+            view_key |= MeshPipelineKey::DEPTH_PREPASS;
+        }
+        if normal_prepass {
+            view_key |= MeshPipelineKey::NORMAL_PREPASS;
+        }
+        if motion_vector_prepass {
+            view_key |= MeshPipelineKey::MOTION_VECTOR_PREPASS;
+        }
+        if deferred_prepass {
+            view_key |= MeshPipelineKey::DEFERRED_PREPASS;
+        }
+        if !view.hdr {
+            if let Some(tonemapping) = tonemapping {
+                view_key |= MeshPipelineKey::TONEMAP_IN_SHADER;
+                view_key |= tonemapping_pipeline_key(*tonemapping);
+            }
+            if let Some(DebandDither::Enabled) = dither {
+                view_key |= MeshPipelineKey::DEBAND_DITHER;
+            }
+        }
+
+        let rangefinder = view.rangefinder3d();
+        for entity in instanced_decorations.iter() {
+            let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(entity) else {
+                continue;
+            };
+            let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+                continue;
+            };
+            let key =
+                view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology());
+            let pipeline = match pipelines.specialize(
+                &pipeline_cache,
+                &instancing_pipeline,
+                key,
+                &mesh.layout,
+            ) {
+                Ok(pipeline) => pipeline,
+                Err(err) => {
+                    error!("{}", err);
+                    continue;
+                }
+            };
+            transparent_phase.add(Transparent3d {
+                entity,
+                pipeline,
+                draw_function,
+                distance: rangefinder.distance_translation(&mesh_instance.translation),
+                batch_range: 0..1,
+                extra_index: PhaseItemExtraIndex::NONE,
+            });
+        }
+    }
+}
+
+#[derive(Component)]
+struct DecorationInstanceBuffer {
+    buffer: Buffer,
+    length: usize,
+}
+
+/// GPU buffers of the instance transforms, kept until the instances change so that they are
+/// only uploaded once
+#[derive(Resource, Default)]
+struct DecorationInstanceBuffers(EntityHashMap<(Arc<[DecorationInstance]>, Buffer)>);
+
+fn prepare_decoration_instance_buffers(
+    mut commands: Commands,
+    query: Query<(Entity, &DecorationInstanceData)>,
+    render_device: Res<RenderDevice>,
+    mut buffers: ResMut<DecorationInstanceBuffers>,
+) {
+    let mut prepared = EntityHashMap::default();
+
+    for (entity, instance_data) in query.iter() {
+        let instances = &instance_data.0;
+        let buffer = match buffers.0.remove(&entity) {
+            Some((uploaded, buffer)) if Arc::ptr_eq(&uploaded, instances) => buffer,
+            _ => render_device.create_buffer_with_data(&BufferInitDescriptor {
+                label: Some("decoration instance buffer"),
+                contents: bytemuck::cast_slice(instances),
+                usage: BufferUsages::VERTEX,
+            }),
+        };
+
+        commands.entity(entity).insert(DecorationInstanceBuffer {
+            buffer: buffer.clone(),
+            length: instances.len(),
+        });
+        prepared.insert(entity, (instances.clone(), buffer));
+    }
+
+    // Buffers of decorations that were not extracted this frame are dropped
+    buffers.0 = prepared;
+}
+
+#[derive(Resource)]
+struct DecorationInstancingPipeline {
+    mesh_pipeline: MeshPipeline,
+}
+
+impl FromWorld for DecorationInstancingPipeline {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            mesh_pipeline: world.resource::<MeshPipeline>().clone(),
+        }
+    }
+}
+
+impl SpecializedMeshPipeline for DecorationInstancingPipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayoutRef,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+
+        descriptor.vertex.shader = DECORATION_INSTANCING_SHADER_HANDLE;
+        // Locations 0 to 7 are used by the mesh attributes
+        descriptor.vertex.buffers.push(VertexBufferLayout {
+            array_stride: std::mem::size_of::<DecorationInstance>() as u64,
+            step_mode: VertexStepMode::Instance,
+            attributes: (0..3)
+                .map(|row| VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: row * VertexFormat::Float32x4.size(),
+                    shader_location: 8 + row as u32,
+                })
+                .collect(),
+        });
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            fragment.shader = DECORATION_INSTANCING_SHADER_HANDLE;
+        }
+        Ok(descriptor)
+    }
+}
+
+type DrawDecorationInstances = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    DrawMeshInstanced,
+);
+
+struct DrawMeshInstanced;
+
+impl<P: PhaseItem> RenderCommand<P> for DrawMeshInstanced {
+    type Param = (SRes<RenderAssets<GpuMesh>>, SRes<RenderMeshInstances>);
+    type ViewQuery = ();
+    type ItemQuery = Read<DecorationInstanceBuffer>;
+
+    #[inline]
+    fn render<'w>(
+        item: &P,
+        _view: (),
+        instance_buffer: Option<&'w DecorationInstanceBuffer>,
+        (meshes, render_mesh_instances): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(item.entity())
+        else {
+            return RenderCommandResult::Failure;
+        };
+        let Some(gpu_mesh) = meshes.into_inner().get(mesh_instance.mesh_asset_id) else {
+            return RenderCommandResult::Failure;
+        };
+        let Some(instance_buffer) = instance_buffer else {
+            return RenderCommandResult::Failure;
+        };
+
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
+
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed {
+                buffer,
+                index_format,
+                count,
+            } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, 0..instance_buffer.length as u32);
+            }
+            GpuBufferInfo::NonIndexed => {
+                pass.draw(0..gpu_mesh.vertex_count, 0..instance_buffer.length as u32);
+            }
+        }
+        RenderCommandResult::Success
+    }
+}
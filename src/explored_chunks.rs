@@ -0,0 +1,88 @@
+///
+/// Explored chunks
+/// The set of chunks of a world that have been meshed at least once, for map reveal and
+/// fog-of-war mechanics.
+///
+use std::marker::PhantomData;
+
+use bevy::{prelude::*, utils::HashSet};
+
+/// Add this resource to the app to keep track of every chunk that has been meshed, even after it
+/// despawns. A `ChunkExplored` event is sent the first time each chunk is meshed. Save the set
+/// with `to_bytes` and insert it again with `from_bytes` to keep the explored area across
+/// sessions.
+#[derive(Resource)]
+pub struct ExploredChunks<C> {
+    chunks: HashSet<IVec3>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for ExploredChunks<C> {
+    fn default() -> Self {
+        Self {
+            chunks: HashSet::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> ExploredChunks<C> {
+    /// Whether the chunk at the given chunk position has been explored
+    pub fn contains(&self, chunk_position: IVec3) -> bool {
+        self.chunks.contains(&chunk_position)
+    }
+
+    /// Mark a chunk as explored. Returns `true` if it was not explored before.
+    pub fn insert(&mut self, chunk_position: IVec3) -> bool {
+        self.chunks.insert(chunk_position)
+    }
+
+    /// The positions of all explored chunks, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.chunks.iter().copied()
+    }
+
+    /// The number of explored chunks
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Forget all explored chunks
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+    }
+
+    /// The explored chunk positions, as little endian `x`, `y` and `z` triples
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.chunks
+            .iter()
+            .flat_map(|position| position.to_array())
+            .flat_map(i32::to_le_bytes)
+            .collect()
+    }
+
+    /// Read a set written by `to_bytes`. Returns `None` if the length of `bytes` doesn't fit.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() % 12 != 0 {
+            return None;
+        }
+        let coordinate = |bytes: &[u8]| i32::from_le_bytes(bytes.try_into().unwrap());
+        Some(Self {
+            chunks: bytes
+                .chunks_exact(12)
+                .map(|position| {
+                    IVec3::new(
+                        coordinate(&position[0..4]),
+                        coordinate(&position[4..8]),
+                        coordinate(&position[8..12]),
+                    )
+                })
+                .collect(),
+            _marker: PhantomData,
+        })
+    }
+}
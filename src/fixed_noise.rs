@@ -0,0 +1,245 @@
+///
+/// Fixed point noise
+/// Integer noise and hashing for terrain that has to come out bit-identical on every platform,
+/// like procedural worlds shared between multiplayer clients that only exchange a seed.
+///
+/// Floating point noise can round differently between CPUs, compilers and libm versions, so
+/// everything here is done in integers. Values between 0 and 1 are fixed point numbers with 16
+/// fractional bits, where `FIXED_ONE` stands for 1.0. The outputs for a given seed and position
+/// are part of the API: a change to them is a breaking change.
+///
+use std::hash::{Hash, Hasher};
+
+use bevy::prelude::*;
+
+use crate::voxel::WorldVoxel;
+
+/// 1.0 as a fixed point number with 16 fractional bits
+pub const FIXED_ONE: i32 = 1 << 16;
+
+const FIXED_SHIFT: u32 = 16;
+
+/// The SplitMix64 finalizer, which spreads every input bit over the whole output
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+/// Hash a 2D position with a seed
+pub fn hash_2d(seed: u64, x: i32, z: i32) -> u64 {
+    mix(seed ^ mix(x as u32 as u64 | (z as u32 as u64) << 32))
+}
+
+/// Hash a 3D position with a seed
+pub fn hash_3d(seed: u64, x: i32, y: i32, z: i32) -> u64 {
+    mix(hash_2d(seed, x, z) ^ mix(y as u32 as u64))
+}
+
+/// Derive an independent seed from a world seed and a label, so that each use of noise, like
+/// `"height"` or `"caves"`, gets its own values from one world seed
+pub fn derive_seed(seed: u64, label: &str) -> u64 {
+    let mut hasher = StableHasher::default();
+    hasher.write(label.as_bytes());
+    mix(seed ^ hasher.finish())
+}
+
+/// Smoothstep on a fixed point value between 0 and `FIXED_ONE`
+fn fade(t: i64) -> i64 {
+    let one = FIXED_ONE as i64;
+    (((t * t) >> FIXED_SHIFT) * (3 * one - 2 * t)) >> FIXED_SHIFT
+}
+
+fn lerp(a: i64, b: i64, t: i64) -> i64 {
+    a + (((b - a) * t) >> FIXED_SHIFT)
+}
+
+/// Value noise on a grid of `cell_size` voxels. Samples are fixed point values from 0 up to,
+/// but not including, `FIXED_ONE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ValueNoise {
+    seed: u64,
+    cell_size: i32,
+}
+
+impl ValueNoise {
+    pub fn new(seed: u64, cell_size: u32) -> Self {
+        Self {
+            seed,
+            cell_size: cell_size.clamp(1, i32::MAX as u32) as i32,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn cell_size(&self) -> u32 {
+        self.cell_size as u32
+    }
+
+    /// The cell a coordinate is in, and how far into the cell it is as a faded fixed point value
+    fn cell(&self, coordinate: i32) -> (i32, i64) {
+        let offset = coordinate.rem_euclid(self.cell_size) as i64;
+        (
+            coordinate.div_euclid(self.cell_size),
+            fade((offset << FIXED_SHIFT) / self.cell_size as i64),
+        )
+    }
+
+    fn lattice_2d(&self, x: i32, z: i32) -> i64 {
+        (hash_2d(self.seed, x, z) >> (64 - FIXED_SHIFT)) as i64
+    }
+
+    fn lattice_3d(&self, x: i32, y: i32, z: i32) -> i64 {
+        (hash_3d(self.seed, x, y, z) >> (64 - FIXED_SHIFT)) as i64
+    }
+
+    /// Sample the noise at a column
+    pub fn sample_2d(&self, x: i32, z: i32) -> i32 {
+        let ((cx, tx), (cz, tz)) = (self.cell(x), self.cell(z));
+        let near = lerp(self.lattice_2d(cx, cz), self.lattice_2d(cx + 1, cz), tx);
+        let far = lerp(
+            self.lattice_2d(cx, cz + 1),
+            self.lattice_2d(cx + 1, cz + 1),
+            tx,
+        );
+        lerp(near, far, tz) as i32
+    }
+
+    /// Sample the noise at a voxel
+    pub fn sample_3d(&self, x: i32, y: i32, z: i32) -> i32 {
+        let ((cx, tx), (cy, ty), (cz, tz)) = (self.cell(x), self.cell(y), self.cell(z));
+        let layer = |cy: i32| {
+            let near = lerp(
+                self.lattice_3d(cx, cy, cz),
+                self.lattice_3d(cx + 1, cy, cz),
+                tx,
+            );
+            let far = lerp(
+                self.lattice_3d(cx, cy, cz + 1),
+                self.lattice_3d(cx + 1, cy, cz + 1),
+                tx,
+            );
+            lerp(near, far, tz)
+        };
+        lerp(layer(cy), layer(cy + 1), ty) as i32
+    }
+}
+
+/// Several octaves of `ValueNoise`, each with half the cell size and half the weight of the one
+/// before. Samples are fixed point values from 0 up to, but not including, `FIXED_ONE`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FractalNoise {
+    octaves: Vec<ValueNoise>,
+}
+
+impl FractalNoise {
+    /// `cell_size` is the cell size of the first, coarsest octave
+    pub fn new(seed: u64, cell_size: u32, octaves: u32) -> Self {
+        Self {
+            octaves: (0..octaves.max(1))
+                .map(|octave| {
+                    ValueNoise::new(
+                        mix(seed ^ octave as u64),
+                        cell_size.checked_shr(octave).unwrap_or(0),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    pub fn octaves(&self) -> &[ValueNoise] {
+        &self.octaves
+    }
+
+    fn combine(&self, sample: impl Fn(&ValueNoise) -> i32) -> i32 {
+        let (sum, weights) = self.octaves.iter().enumerate().fold(
+            (0i64, 0i64),
+            |(sum, weights), (octave, noise)| {
+                let weight = (FIXED_ONE as i64) >> octave.min(FIXED_SHIFT as usize);
+                (sum + sample(noise) as i64 * weight, weights + weight)
+            },
+        );
+        (sum / weights.max(1)) as i32
+    }
+
+    /// Sample the noise at a column
+    pub fn sample_2d(&self, x: i32, z: i32) -> i32 {
+        self.combine(|noise| noise.sample_2d(x, z))
+    }
+
+    /// Sample the noise at a voxel
+    pub fn sample_3d(&self, x: i32, y: i32, z: i32) -> i32 {
+        self.combine(|noise| noise.sample_3d(x, y, z))
+    }
+
+    /// A terrain height between `min` and `max` for a column
+    pub fn height(&self, x: i32, z: i32, min: i32, max: i32) -> i32 {
+        min + (((max - min) as i64 * self.sample_2d(x, z) as i64) >> FIXED_SHIFT) as i32
+    }
+}
+
+/// A 64 bit FNV-1a hasher that gives the same hashes on every platform, unlike the std
+/// `DefaultHasher`, which is randomly keyed and may change between Rust versions. `usize` and
+/// `isize` are always hashed as 64 bit values, so derived `Hash` impls hash the same on 32 and
+/// 64 bit targets.
+#[derive(Clone, Copy, Debug)]
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, value: u128) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    fn write_isize(&mut self, value: isize) {
+        self.write_u64(value as i64 as u64);
+    }
+}
+
+/// A checksum of voxels, like the ones of a chunk from `ChunkRef::iter`, that is the same on
+/// every platform. Clients can compare checksums to check that they generated the same world.
+/// The order of the voxels matters. Material indexes have to hash field by field, like integers
+/// and types with a derived `Hash` do.
+pub fn voxel_checksum<I: Hash>(voxels: impl IntoIterator<Item = (IVec3, WorldVoxel<I>)>) -> u64 {
+    let mut hasher = StableHasher::default();
+    for (position, voxel) in voxels {
+        for coordinate in position.to_array() {
+            hasher.write_i32(coordinate);
+        }
+        voxel.hash(&mut hasher);
+    }
+    hasher.finish()
+}
@@ -1,33 +1,128 @@
+// Lets the derive macros refer to `::bevy_voxel_world` from within this crate too.
+extern crate self as bevy_voxel_world;
+
+#[cfg(feature = "border_validation")]
+mod border_validation;
 mod chunk;
+mod chunk_collider;
+mod chunk_generator;
 mod chunk_map;
+mod chunk_network;
+mod chunk_persistence;
+mod chunk_ref;
+mod chunk_shape;
+mod chunk_storage;
+mod chunk_timings;
 mod configuration;
 mod debug_draw;
+mod decoration_instancing;
+mod explored_chunks;
+#[cfg(feature = "generators")]
+mod fixed_noise;
+mod lookup_cache;
 mod mesh_cache;
 mod meshing;
 mod plugin;
+mod positions;
+#[cfg(feature = "vox")]
+mod vox_import;
 mod voxel;
+mod voxel_changes;
+mod voxel_decal;
+mod voxel_lighting;
 mod voxel_material;
+mod voxel_metadata;
+mod voxel_model;
+#[cfg(feature = "picking")]
+mod voxel_picking;
+mod voxel_preview;
+mod voxel_tasks;
 mod voxel_traversal;
 mod voxel_world;
 mod voxel_world_internal;
+mod world_pass;
 
 pub mod prelude {
-    pub use crate::chunk::{Chunk, NeedsDespawn};
+    pub use crate::chunk::{
+        Chunk, ChunkDecoration, ChunkDecorationInstances, ChunkErrorPlaceholder, ChunkGroupMesh,
+        ChunkLoadingPlaceholder, ChunkRegion, ChunkShadowMesh, Decoration, DecorationMeshes,
+        HiddenChunk, NeedsDespawn, CHUNK_SIZE_F, CHUNK_SIZE_I, CHUNK_SIZE_U,
+    };
+    pub use crate::chunk_collider::{ChunkCollider, ChunkColliderShape};
+    pub use crate::chunk_generator::ChunkBuffer;
+    pub use crate::chunk_network::{NetworkCodec, CHUNK_NETWORK_VERSION};
+    pub use crate::chunk_persistence::{ChunkPersistence, FileChunkPersistence, PersistentIndex};
+    pub use crate::chunk_ref::{ChunkDataHandle, ChunkRef};
+    pub use crate::chunk_timings::{ChunkTimings, TimingStats};
     pub use crate::configuration::*;
+    pub use crate::explored_chunks::ExploredChunks;
+    pub use crate::lookup_cache::{
+        ColumnCache, VoxelCache, DEFAULT_COLUMN_CACHE_CAPACITY, DEFAULT_VOXEL_CACHE_CAPACITY,
+    };
     pub use crate::plugin::VoxelWorldPlugin;
-    pub use crate::voxel::{VoxelFace, WorldVoxel, VOXEL_SIZE};
-    pub use crate::voxel_world::{ChunkWillDespawn, ChunkWillRemesh, ChunkWillSpawn};
-    pub use crate::voxel_world::{VoxelRaycastResult, VoxelWorld, VoxelWorldCamera};
+    pub use crate::positions::{ChunkPos, VoxelPos};
+    #[cfg(feature = "vox")]
+    pub use crate::vox_import::{spawn_vox_parts, VoxPart, VoxSplit};
+    pub use crate::voxel::{
+        FaceTextureIndex, VoxelAabb, VoxelFace, VoxelIndex, VoxelNeighbors, WorldVoxel, VOXEL_SIZE,
+    };
+    pub use crate::voxel_changes::{VoxelChange, VoxelChangeTracker};
+    pub use crate::voxel_decal::{ChunkDecalMesh, VoxelDecal, VoxelDecals, DECAL_OFFSET};
+    pub use crate::voxel_lighting::MAX_LIGHT;
+    pub use crate::voxel_metadata::{ChunkMetadataWillDespawn, VoxelMetadataMap};
+    pub use crate::voxel_model::{ModelRotation, VoxelModel, MAX_MODEL_VOXELS};
+    pub use crate::voxel_preview::{VoxelModelMesh, VoxelPreview, PREVIEW_RENDER_LAYERS_START};
+    pub use crate::voxel_tasks::{VoxelTaskFinished, VoxelTaskId, VoxelTaskResult};
+    pub use crate::voxel_world::{
+        flush_voxel_writes, FilterFn, RaycastFn, RigSpawnVolume, VoxelRaycastResult, VoxelWorld,
+        VoxelWorldCamera, VoxelWorldCameraRig, VoxelWorldMotionHint, VoxelWorldSet,
+        VoxelWorldSpawnAnchor, SURFACE_SEARCH_RADIUS,
+    };
+    pub use crate::voxel_world::{
+        CameraAreaEvent, CameraEnteredGeneratedArea, CameraLeftGeneratedArea, ChunkEvent,
+        ChunkExplored, ChunkGenerationFailed, ChunkMeshReady, ChunkModified, ChunkWillDespawn,
+        ChunkWillRemesh, ChunkWillSpawn, EnteredGeneratedArea, Explored, FirstChunksReady,
+        FreezeUntilGenerated, LeftGeneratedArea, MeshReady, Modified, WillDespawn, WillRemesh,
+        WillSpawn,
+    };
+    pub use crate::world_pass::{VoxelRegion, WorldPassFinished, WorldPassId, WorldPassProgress};
+    pub use bevy_voxel_world_macros::VoxelIndex;
 }
 
 pub mod debug {
+    #[cfg(feature = "border_validation")]
+    pub use crate::border_validation::*;
     pub use crate::debug_draw::*;
 }
 
+#[cfg(feature = "generators")]
+pub mod generators {
+    pub use crate::fixed_noise::*;
+}
+
+#[cfg(feature = "picking")]
+pub mod picking {
+    pub use crate::voxel_picking::*;
+}
+
 pub mod rendering {
     pub use crate::plugin::VoxelWorldMaterialHandle;
     pub use crate::voxel_material::vertex_layout;
+    pub use crate::voxel_material::ATTRIBUTE_VOXEL_LIGHT;
     pub use crate::voxel_material::VOXEL_TEXTURE_SHADER_HANDLE;
+    #[cfg(feature = "compact_vertices")]
+    pub use crate::voxel_material::{
+        compact_vertex_layout, ATTRIBUTE_COMPACT_COLOR, ATTRIBUTE_COMPACT_NORMAL,
+        ATTRIBUTE_COMPACT_UV,
+    };
+}
+
+/// The voxel data of chunks, for diffing chunks, sending them over the network and handing them
+/// to `VoxelWorld::insert_chunk`
+pub mod storage {
+    pub use crate::chunk::{ChunkData, FillType, PaddedChunkShape, VoxelArray};
+    pub use crate::chunk_shape::{ChunkShape, ConstChunkShape, DynamicChunkShape};
+    pub use crate::chunk_storage::ChunkVoxels;
 }
 
 pub mod traversal_alg {
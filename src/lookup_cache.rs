@@ -0,0 +1,373 @@
+///
+/// Lookup cache
+/// Memoizes expensive work of lookup delegates, shared between chunks.
+///
+use std::{
+    collections::BTreeMap,
+    hash::Hash,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    chunk::{ChunkData, CHUNK_SIZE_U},
+    configuration::{VoxelLookupDelegate, VoxelLookupFn},
+    voxel::WorldVoxel,
+};
+
+/// The number of columns a `ColumnCache` holds by default, enough for the columns of 64 chunks
+pub const DEFAULT_COLUMN_CACHE_CAPACITY: usize = 64 * (CHUNK_SIZE_U * CHUNK_SIZE_U) as usize;
+
+/// The number of voxels a `VoxelCache` holds by default, enough for the voxels of 8 chunks
+pub const DEFAULT_VOXEL_CACHE_CAPACITY: usize =
+    8 * (CHUNK_SIZE_U * CHUNK_SIZE_U * CHUNK_SIZE_U) as usize;
+
+/// The most columns a lookup function from `ColumnCache::delegate` keeps locally, those of a chunk
+/// and the chunks around it. Functions reused with `VoxelWorldConfig::reuse_voxel_lookup_fns` are
+/// handed on from chunk to chunk, so they start over once they have this many.
+const MAX_LOCAL_COLUMNS: usize = (3 * CHUNK_SIZE_U * 3 * CHUNK_SIZE_U) as usize;
+
+/// Caches are split into up to this many independently locked shards, so that chunk threads
+/// rarely wait for each other
+const CACHE_SHARDS: usize = 16;
+
+/// Caches smaller than this many entries per shard aren't split, so that they drop their least
+/// recently used entries as a whole
+const MIN_SHARD_CAPACITY: usize = 1024;
+
+/// A map that drops the least recently used entry once it is full
+struct LruMap<K, V> {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<K, (V, u64)>,
+    by_tick: BTreeMap<u64, K>,
+}
+
+impl<K: Hash + Eq + Copy, V: Clone> LruMap<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            tick: 0,
+            entries: HashMap::new(),
+            by_tick: BTreeMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        self.tick += 1;
+        let (value, tick) = self.entries.get_mut(key)?;
+        self.by_tick.remove(tick);
+        self.by_tick.insert(self.tick, *key);
+        *tick = self.tick;
+        Some(value.clone())
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        let (value, tick) = self.entries.remove(key)?;
+        self.by_tick.remove(&tick);
+        Some(value)
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        self.tick += 1;
+        if let Some((_, tick)) = self.entries.insert(key, (value, self.tick)) {
+            self.by_tick.remove(&tick);
+        }
+        self.by_tick.insert(self.tick, key);
+
+        while self.entries.len() > self.capacity {
+            let Some((_, oldest)) = self.by_tick.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+/// An LRU map split into shards by key. Each shard drops its own least recently used entries.
+struct ShardedLru<K, V> {
+    hasher: ahash::RandomState,
+    shards: Vec<Mutex<LruMap<K, V>>>,
+}
+
+impl<K: Hash + Eq + Copy, V: Clone> ShardedLru<K, V> {
+    fn new(capacity: usize) -> Self {
+        let shard_count = (capacity / MIN_SHARD_CAPACITY).clamp(1, CACHE_SHARDS);
+        let shard_capacity = capacity.div_ceil(shard_count);
+        Self {
+            hasher: ahash::RandomState::new(),
+            shards: (0..shard_count)
+                .map(|_| Mutex::new(LruMap::new(shard_capacity)))
+                .collect(),
+        }
+    }
+
+    fn shard(&self, key: &K) -> &Mutex<LruMap<K, V>> {
+        &self.shards[self.hasher.hash_one(key) as usize % self.shards.len()]
+    }
+
+    fn get_or_insert_with(&self, key: K, value: impl FnOnce() -> V) -> V {
+        let shard = self.shard(&key);
+        if let Some(value) = shard.lock().unwrap().get(&key) {
+            return value;
+        }
+
+        // Compute without holding the lock, so that other chunk threads aren't blocked
+        let value = value();
+        shard.lock().unwrap().insert(key, value.clone());
+        value
+    }
+
+    fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().entries.len())
+            .sum()
+    }
+}
+
+/// Caches a value per `x`/`z` column of voxels, for example a terrain height sampled from noise.
+/// Every chunk looks up the voxels of its padding too, so without a cache shared between chunks,
+/// the columns along chunk borders get sampled once for every neighboring chunk.
+///
+/// Clones share the same cache, so keep one in your `VoxelWorldConfig` and build the lookup
+/// delegate with `ColumnCache::delegate`. The sampled values are reused, so they need to be
+/// deterministic. When the cache is full, the least recently used columns are dropped. The cache
+/// is sharded, so chunk threads can use it at the same time without waiting on a single lock.
+pub struct ColumnCache<T> {
+    columns: Arc<ShardedLru<IVec2, T>>,
+}
+
+impl<T> Clone for ColumnCache<T> {
+    fn clone(&self) -> Self {
+        Self {
+            columns: self.columns.clone(),
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> Default for ColumnCache<T> {
+    fn default() -> Self {
+        Self::new(DEFAULT_COLUMN_CACHE_CAPACITY)
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> ColumnCache<T> {
+    /// Create a cache holding at most `capacity` columns
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            columns: Arc::new(ShardedLru::new(capacity)),
+        }
+    }
+
+    /// Get the cached value of a column, sampling and caching it if it isn't cached yet
+    pub fn get_or_insert_with(&self, column: IVec2, sample: impl FnOnce() -> T) -> T {
+        self.columns.get_or_insert_with(column, sample)
+    }
+
+    /// The number of columns currently cached
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Build a lookup delegate from a column `sample` function and a `voxel` function, which gets
+    /// the position of a voxel and the sample of its column. Each chunk keeps the samples it used
+    /// locally as well, so the shared cache is only locked once per column and chunk. The local
+    /// samples are bounded, so the lookup functions can be reused for other chunks.
+    pub fn delegate<I, S, V>(&self, sample: S, voxel: V) -> VoxelLookupDelegate<I>
+    where
+        S: Fn(IVec2) -> T + Send + Sync + 'static,
+        V: Fn(IVec3, &T) -> WorldVoxel<I> + Send + Sync + 'static,
+    {
+        let cache = self.clone();
+        let sample = Arc::new(sample);
+        let voxel = Arc::new(voxel);
+
+        Box::new(move |_chunk_pos| {
+            let cache = cache.clone();
+            let sample = sample.clone();
+            let voxel = voxel.clone();
+            let mut chunk_columns = HashMap::<IVec2, T>::new();
+
+            Box::new(move |pos: IVec3| {
+                let column = pos.xz();
+                if chunk_columns.len() >= MAX_LOCAL_COLUMNS && !chunk_columns.contains_key(&column)
+                {
+                    chunk_columns.clear();
+                }
+                let value = chunk_columns
+                    .entry(column)
+                    .or_insert_with(|| cache.get_or_insert_with(column, || sample(column)));
+                voxel(pos, value)
+            })
+        })
+    }
+}
+
+/// Caches the voxels returned by a lookup delegate by their world position. Neighboring chunks
+/// look up each others border voxels for their padding, and chunks get generated again when they
+/// are respawned, so this saves calling the delegate for positions that were looked up recently.
+///
+/// Like `ColumnCache`, clones share the same sharded cache, so keep one in your `VoxelWorldConfig`.
+/// Wrap your delegate with `VoxelCache::delegate`. The delegate needs to be deterministic.
+/// Voxels changed with `set_voxel` don't go through the delegate, and are never cached.
+pub struct VoxelCache<I> {
+    voxels: Arc<ShardedLru<IVec3, WorldVoxel<I>>>,
+}
+
+impl<I> Clone for VoxelCache<I> {
+    fn clone(&self) -> Self {
+        Self {
+            voxels: self.voxels.clone(),
+        }
+    }
+}
+
+impl<I: Copy + Send + Sync + 'static> Default for VoxelCache<I> {
+    fn default() -> Self {
+        Self::new(DEFAULT_VOXEL_CACHE_CAPACITY)
+    }
+}
+
+impl<I: Copy + Send + Sync + 'static> VoxelCache<I> {
+    /// Create a cache holding at most `capacity` voxels
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            voxels: Arc::new(ShardedLru::new(capacity)),
+        }
+    }
+
+    /// The number of voxels currently cached
+    pub fn len(&self) -> usize {
+        self.voxels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Wrap a lookup delegate, so that the voxels it returns are cached. The lookup function of
+    /// a chunk is only created once a voxel misses the cache.
+    pub fn delegate(&self, delegate: VoxelLookupDelegate<I>) -> VoxelLookupDelegate<I> {
+        let cache = self.clone();
+        let delegate = Arc::new(delegate);
+
+        Box::new(move |chunk_pos| {
+            let cache = cache.clone();
+            let delegate = delegate.clone();
+            let mut lookup = None;
+
+            Box::new(move |pos: IVec3| {
+                cache.voxels.get_or_insert_with(pos, || {
+                    lookup.get_or_insert_with(|| delegate(chunk_pos))(pos)
+                })
+            })
+        })
+    }
+}
+
+/// The voxels of recently despawned chunks, kept in the compact form of the chunk map, so that
+/// chunks coming back into view don't have to be generated again. Holds at most
+/// `VoxelWorldConfig::despawned_chunk_cache_size` chunks.
+#[derive(Resource)]
+pub(crate) struct DespawnedChunks<C, I> {
+    chunks: Option<LruMap<IVec3, ChunkData<I>>>,
+    _marker: PhantomData<C>,
+}
+
+impl<C, I: Clone> DespawnedChunks<C, I> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            chunks: (capacity > 0).then(|| LruMap::new(capacity)),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn insert(&mut self, position: IVec3, chunk_data: ChunkData<I>) {
+        if let Some(chunks) = &mut self.chunks {
+            chunks.insert(position, chunk_data);
+        }
+    }
+
+    /// Take the voxels of a chunk out of the cache. The chunk is spawned again, so it gets back
+    /// into the cache the next time it despawns.
+    pub fn take(&mut self, position: IVec3) -> Option<ChunkData<I>> {
+        self.chunks.as_mut()?.remove(&position)
+    }
+
+    /// Drop the chunks between `min` and `max`, inclusive, or every chunk for `None`
+    pub fn invalidate(&mut self, region: Option<(IVec3, IVec3)>) {
+        let Some(chunks) = &mut self.chunks else {
+            return;
+        };
+        let invalidated: Vec<IVec3> = chunks
+            .entries
+            .keys()
+            .filter(|position| {
+                region
+                    .is_none_or(|(min, max)| position.cmpge(min).all() && position.cmple(max).all())
+            })
+            .copied()
+            .collect();
+        for position in invalidated {
+            chunks.remove(&position);
+        }
+    }
+}
+
+/// The lookup functions of finished chunk tasks, kept for the next chunks to reuse when
+/// `VoxelWorldConfig::reuse_voxel_lookup_fns` is on. Holds one function per worker thread, since
+/// that is the most that can be in use at once.
+#[derive(Resource)]
+pub(crate) struct LookupFnPool<C, I> {
+    fns: Arc<Mutex<Vec<VoxelLookupFn<I>>>>,
+    capacity: usize,
+    _marker: PhantomData<C>,
+}
+
+impl<C, I: 'static> LookupFnPool<C, I> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            fns: Arc::new(Mutex::new(Vec::new())),
+            capacity,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Take a lookup function from the pool, or build one with `build` if the pool is empty. The
+    /// function goes back into the pool when the returned one is dropped at the end of the task.
+    pub fn take(&self, build: impl FnOnce() -> VoxelLookupFn<I>) -> VoxelLookupFn<I> {
+        let lookup = self.fns.lock().unwrap().pop().unwrap_or_else(build);
+        let mut pooled = PooledLookupFn {
+            lookup: Some(lookup),
+            fns: self.fns.clone(),
+            capacity: self.capacity,
+        };
+        Box::new(move |position| (pooled.lookup.as_mut().unwrap())(position))
+    }
+}
+
+struct PooledLookupFn<I> {
+    lookup: Option<VoxelLookupFn<I>>,
+    fns: Arc<Mutex<Vec<VoxelLookupFn<I>>>>,
+    capacity: usize,
+}
+
+impl<I> Drop for PooledLookupFn<I> {
+    fn drop(&mut self) {
+        // A function that panicked may have been left in a broken state
+        if std::thread::panicking() {
+            return;
+        }
+        let mut fns = self.fns.lock().unwrap();
+        if fns.len() < self.capacity {
+            fns.extend(self.lookup.take());
+        }
+    }
+}
@@ -1,11 +1,30 @@
 use std::{
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{self, BufReader, BufWriter, Read, Write},
     marker::PhantomData,
+    path::Path,
     sync::{Arc, RwLock, Weak},
 };
 
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, MeshVertexAttribute, VertexAttributeValues},
+        render_asset::RenderAssetUsages,
+        render_resource::{PrimitiveTopology, VertexFormat},
+    },
+    utils::HashMap,
+};
 use weak_table::WeakValueHashMap;
 
+use crate::{
+    chunk::CHUNK_SIZE_I,
+    configuration::{UvMode, VoxelWorldConfig},
+    meshing::MESHER_VERSION,
+    voxel_material::ATTRIBUTE_TEX_INDEX,
+};
+
 /// This is used to keep a reference to a mesh handle in each chunk entity. This ensures that the WeakMap
 /// we use to look up mesh handles can drop handles that no chunks are using anymore.
 #[derive(Component)]
@@ -13,6 +32,90 @@ pub(crate) struct MeshRef(pub Arc<Handle<Mesh>>);
 
 type WeakMeshMap = WeakValueHashMap<u64, Weak<Handle<Mesh>>>;
 
+/// Determines what, apart from the voxel content of a chunk, goes into its mesh cache key.
+/// Meshes are only shared between chunks of the same world config and texture mapping version,
+/// and optionally only within the same chunk position.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct MeshCacheScope {
+    world_hash: u64,
+    per_chunk: bool,
+    uv_mode: UvMode,
+}
+
+impl MeshCacheScope {
+    pub fn from_config<C: VoxelWorldConfig>(configuration: &C) -> Self {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        std::any::type_name::<C>().hash(&mut hasher);
+        configuration.texture_mapping_version().hash(&mut hasher);
+        configuration.meshing_algorithm().hash(&mut hasher);
+        configuration.uv_mode().hash(&mut hasher);
+        configuration.voxel_texture_mode().hash(&mut hasher);
+        configuration.voxel_lighting().hash(&mut hasher);
+        #[cfg(feature = "compact_vertices")]
+        configuration.compact_vertex_attributes().hash(&mut hasher);
+        configuration
+            .fluid_surface_height()
+            .to_bits()
+            .hash(&mut hasher);
+
+        // Callbacks can't be compared between runs, but whether they are set changes the meshes.
+        // Changes to what they do are covered by `texture_mapping_version`.
+        (
+            configuration.texture_index_face_mapper().is_some(),
+            configuration.contextual_texture().is_some(),
+            configuration.face_visible().is_some(),
+            configuration.faces_merge().is_some(),
+            configuration.voxel_shape().is_some(),
+            configuration.voxel_transparency().is_some(),
+            configuration.material_group().is_some(),
+        )
+            .hash(&mut hasher);
+
+        Self {
+            world_hash: hasher.finish(),
+            per_chunk: configuration.mesh_cache_per_chunk(),
+            uv_mode: configuration.uv_mode(),
+        }
+    }
+
+    /// Identifies the scope in persisted mesh cache files
+    fn persistence_id(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.world_hash.hash(&mut hasher);
+        self.per_chunk.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Scope the keys to the number of times the appearance of the world was invalidated, so
+    /// that meshes from before are not reused
+    pub fn with_appearance_version(mut self, version: u64) -> Self {
+        if version > 0 {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            self.world_hash.hash(&mut hasher);
+            version.hash(&mut hasher);
+            self.world_hash = hasher.finish();
+        }
+        self
+    }
+
+    /// Get the mesh cache key for a chunk with the given voxels hash
+    pub fn key(&self, voxels_hash: u64, chunk_position: IVec3) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.world_hash.hash(&mut hasher);
+        voxels_hash.hash(&mut hasher);
+        if self.per_chunk {
+            chunk_position.hash(&mut hasher);
+        } else if let UvMode::World { tile_size } = self.uv_mode {
+            // World space texture coordinates are the same for chunks at the same spot in a tile
+            let tile_size = tile_size.max(1) as i32;
+            (chunk_position * CHUNK_SIZE_I)
+                .rem_euclid(IVec3::splat(tile_size))
+                .hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
 /// MeshCache uses a weak map to keep track of mesh handles generated for a certain configuration of voxels.
 /// Using this map, we can avoid generating the same mesh multiple times, and reusing mesh handles
 /// should allow Bevy to automatically batch draw identical chunks (large flat areas for example)
@@ -24,7 +127,7 @@ pub(crate) struct MeshCache<C> {
 
 impl<C: Send + Sync + 'static> MeshCache<C> {
     pub fn apply_buffers(&self, insert_buffer: &mut MeshCacheInsertBuffer<C>) {
-        if insert_buffer.len() == 0 {
+        if insert_buffer.is_empty() {
             return;
         }
 
@@ -36,13 +139,23 @@ impl<C: Send + Sync + 'static> MeshCache<C> {
         }
     }
 
-    pub fn get(&self, voxels_hash: &u64) -> Option<Arc<Handle<Mesh>>> {
-        self.map.read().unwrap().get(voxels_hash)
+    pub fn get(&self, mesh_cache_key: &u64) -> Option<Arc<Handle<Mesh>>> {
+        self.map.read().unwrap().get(mesh_cache_key)
     }
 
     pub fn get_map(&self) -> Arc<RwLock<WeakMeshMap>> {
         self.map.clone()
     }
+
+    /// Get all currently cached meshes, keyed by mesh cache key
+    pub fn collect_meshes(&self, mesh_assets: &Assets<Mesh>) -> Vec<(u64, Mesh)> {
+        self.map
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|(key, handle)| Some((*key, mesh_assets.get(&*handle)?.clone())))
+            .collect()
+    }
 }
 
 impl<C> Default for MeshCache<C> {
@@ -56,3 +169,255 @@ impl<C> Default for MeshCache<C> {
 
 #[derive(Resource, Deref, DerefMut, Default)]
 pub(crate) struct MeshCacheInsertBuffer<C>(#[deref] Vec<(u64, Arc<Handle<Mesh>>)>, PhantomData<C>);
+
+/// Meshes restored from a persisted mesh cache file. These are used in place of meshing a chunk
+/// whenever the mesh cache key matches.
+#[derive(Resource)]
+pub(crate) struct PersistedMeshes<C> {
+    pub meshes: Arc<HashMap<u64, Mesh>>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for PersistedMeshes<C> {
+    fn default() -> Self {
+        Self {
+            meshes: Arc::new(HashMap::new()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: VoxelWorldConfig> PersistedMeshes<C> {
+    /// Restore the persisted meshes from the file configured by `mesh_cache_path`, if any
+    pub fn load(configuration: &C) -> Self {
+        let Some(path) = configuration.mesh_cache_path() else {
+            return Self::default();
+        };
+
+        if !path.exists() {
+            return Self::default();
+        }
+
+        let scope = MeshCacheScope::from_config(configuration);
+        match read_mesh_cache_file(&path, scope.persistence_id()) {
+            Ok(meshes) => Self {
+                meshes: Arc::new(meshes),
+                _marker: PhantomData,
+            },
+            Err(err) => {
+                warn!("Ignoring mesh cache file {:?}: {}", path, err);
+                Self::default()
+            }
+        }
+    }
+
+    /// Write the given meshes, along with any restored meshes, to the configured mesh cache file
+    pub fn save(&self, configuration: &C, meshes: Vec<(u64, Mesh)>) {
+        let Some(path) = configuration.mesh_cache_path() else {
+            return;
+        };
+
+        let mut all_meshes: HashMap<u64, &Mesh> =
+            self.meshes.iter().map(|(key, mesh)| (*key, mesh)).collect();
+        for (key, mesh) in meshes.iter() {
+            all_meshes.insert(*key, mesh);
+        }
+
+        let scope = MeshCacheScope::from_config(configuration);
+        if let Err(err) = write_mesh_cache_file(&path, scope.persistence_id(), &all_meshes) {
+            warn!("Failed to write mesh cache file {:?}: {}", path, err);
+        }
+    }
+}
+
+const MESH_CACHE_MAGIC: &[u8; 4] = b"BVWM";
+
+/// The vertex attributes that get persisted. Meshes with any other attributes are skipped.
+const PERSISTED_ATTRIBUTES: [MeshVertexAttribute; 5] = [
+    Mesh::ATTRIBUTE_POSITION,
+    Mesh::ATTRIBUTE_NORMAL,
+    Mesh::ATTRIBUTE_UV_0,
+    Mesh::ATTRIBUTE_COLOR,
+    ATTRIBUTE_TEX_INDEX,
+];
+
+fn write_mesh_cache_file(
+    path: &Path,
+    scope_id: u64,
+    meshes: &HashMap<u64, &Mesh>,
+) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    w.write_all(MESH_CACHE_MAGIC)?;
+    w.write_all(&MESHER_VERSION.to_le_bytes())?;
+    w.write_all(&scope_id.to_le_bytes())?;
+
+    let meshes: Vec<_> = meshes
+        .iter()
+        .filter(|(_, mesh)| is_persistable(mesh))
+        .collect();
+    w.write_all(&(meshes.len() as u32).to_le_bytes())?;
+
+    for (key, mesh) in meshes {
+        w.write_all(&key.to_le_bytes())?;
+        w.write_all(&(mesh.attributes().count() as u32).to_le_bytes())?;
+        for (id, values) in mesh.attributes() {
+            let attribute = PERSISTED_ATTRIBUTES.iter().find(|a| a.id == id).unwrap();
+            w.write_all(&(attribute.name.len() as u32).to_le_bytes())?;
+            w.write_all(attribute.name.as_bytes())?;
+            w.write_all(&(values.len() as u32).to_le_bytes())?;
+            for word in attribute_words(values) {
+                w.write_all(&word.to_le_bytes())?;
+            }
+        }
+
+        let indices: Vec<u32> = mesh
+            .indices()
+            .map(|indices| indices.iter().map(|i| i as u32).collect())
+            .unwrap_or_default();
+        w.write_all(&(indices.len() as u32).to_le_bytes())?;
+        for index in indices {
+            w.write_all(&index.to_le_bytes())?;
+        }
+    }
+
+    w.flush()
+}
+
+/// Flatten vertex attribute values into 32 bit words
+fn attribute_words(values: &VertexAttributeValues) -> Vec<u32> {
+    match values {
+        VertexAttributeValues::Float32x2(v) => v.iter().flatten().map(|f| f.to_bits()).collect(),
+        VertexAttributeValues::Float32x3(v) => v.iter().flatten().map(|f| f.to_bits()).collect(),
+        VertexAttributeValues::Float32x4(v) => v.iter().flatten().map(|f| f.to_bits()).collect(),
+        VertexAttributeValues::Uint32x3(v) => v.iter().flatten().copied().collect(),
+        _ => unreachable!("Checked by is_persistable"),
+    }
+}
+
+fn is_persistable(mesh: &Mesh) -> bool {
+    mesh.attributes().all(|(id, values)| {
+        PERSISTED_ATTRIBUTES.iter().any(|a| a.id == id)
+            && matches!(
+                values,
+                VertexAttributeValues::Float32x2(_)
+                    | VertexAttributeValues::Float32x3(_)
+                    | VertexAttributeValues::Float32x4(_)
+                    | VertexAttributeValues::Uint32x3(_)
+            )
+    })
+}
+
+fn read_mesh_cache_file(path: &Path, scope_id: u64) -> io::Result<HashMap<u64, Mesh>> {
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut r = BufReader::new(file);
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    // Counts are checked against the size of the file before anything is allocated for them, so
+    // that a corrupted count is an error instead of a huge allocation
+    let check_count = |count: u64, item_size: u64| {
+        count
+            .checked_mul(item_size)
+            .filter(|size| *size <= file_len)
+            .map(|_| count as usize)
+            .ok_or_else(|| invalid("count larger than the file"))
+    };
+    let max_name_len = PERSISTED_ATTRIBUTES
+        .iter()
+        .map(|attribute| attribute.name.len())
+        .max()
+        .unwrap_or_default();
+
+    let mut magic = [0; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != MESH_CACHE_MAGIC {
+        return Err(invalid("not a mesh cache file"));
+    }
+    if read_u32(&mut r)? != MESHER_VERSION {
+        return Err(invalid("written by a different mesher version"));
+    }
+    if read_u64(&mut r)? != scope_id {
+        return Err(invalid("written by a different world configuration"));
+    }
+
+    // Each mesh has at least a key, an attribute count and an index count
+    let mesh_count = check_count(read_u32(&mut r)?.into(), 16)?;
+    let mut meshes = HashMap::with_capacity(mesh_count);
+
+    for _ in 0..mesh_count {
+        let key = read_u64(&mut r)?;
+        let mut mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        );
+
+        for _ in 0..read_u32(&mut r)? {
+            let name_len = read_u32(&mut r)? as usize;
+            if name_len > max_name_len {
+                return Err(invalid("unknown vertex attribute"));
+            }
+            let mut name = vec![0; name_len];
+            r.read_exact(&mut name)?;
+            let attribute = PERSISTED_ATTRIBUTES
+                .iter()
+                .find(|a| a.name.as_bytes() == name)
+                .ok_or_else(|| invalid("unknown vertex attribute"))?;
+
+            let components = attribute.format.size() / 4;
+            let word_count = check_count(u64::from(read_u32(&mut r)?) * components, 4)?;
+            let mut words = Vec::with_capacity(word_count);
+            for _ in 0..word_count {
+                words.push(read_u32(&mut r)?);
+            }
+
+            let float = |word: &u32| f32::from_bits(*word);
+            let values = match attribute.format {
+                VertexFormat::Float32x2 => VertexAttributeValues::Float32x2(
+                    words
+                        .chunks(2)
+                        .map(|c| [float(&c[0]), float(&c[1])])
+                        .collect(),
+                ),
+                VertexFormat::Float32x3 => VertexAttributeValues::Float32x3(
+                    words
+                        .chunks(3)
+                        .map(|c| [float(&c[0]), float(&c[1]), float(&c[2])])
+                        .collect(),
+                ),
+                VertexFormat::Float32x4 => VertexAttributeValues::Float32x4(
+                    words
+                        .chunks(4)
+                        .map(|c| [float(&c[0]), float(&c[1]), float(&c[2]), float(&c[3])])
+                        .collect(),
+                ),
+                VertexFormat::Uint32x3 => VertexAttributeValues::Uint32x3(
+                    words.chunks(3).map(|c| [c[0], c[1], c[2]]).collect(),
+                ),
+                _ => return Err(invalid("unsupported vertex format")),
+            };
+            mesh.insert_attribute(attribute.clone(), values);
+        }
+
+        let index_count = check_count(read_u32(&mut r)?.into(), 4)?;
+        let mut indices = Vec::with_capacity(index_count);
+        for _ in 0..index_count {
+            indices.push(read_u32(&mut r)?);
+        }
+        mesh.insert_indices(Indices::U32(indices));
+
+        meshes.insert(key, mesh);
+    }
+
+    Ok(meshes)
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
@@ -1,10 +1,21 @@
-use std::sync::Arc;
+use std::{collections::BTreeSet, sync::Arc};
 
 use block_mesh::{
-    visible_block_faces, OrientedBlockFace, UnitQuadBuffer, Voxel, VoxelVisibility,
-    RIGHT_HANDED_Y_UP_CONFIG,
+    greedy_quads, visible_block_faces, GreedyQuadsBuffer, MergeVoxel, OrientedBlockFace,
+    UnitQuadBuffer, UnorientedQuad, Voxel, VoxelVisibility, RIGHT_HANDED_Y_UP_CONFIG,
 };
 
+use crate::{
+    chunk::CHUNK_SIZE_I,
+    chunk_shape::ChunkShape,
+    configuration::{
+        horizontal_facing, ContextualTextureFn, FaceTextureMapperFn, FaceVisibleFn, FacesMergeFn,
+        MaterialGroup, MaterialGroupFn, MeshingAlgorithm, TextureIndexMapperFn, UvMode,
+        VoxelLighting, VoxelShape, VoxelShapeFn, VoxelTextureMode, VoxelWorldConfig,
+    },
+    voxel::{VoxelFace, VoxelNeighbors, WorldVoxel},
+    voxel_material::ATTRIBUTE_TEX_INDEX,
+};
 use bevy::{
     prelude::*,
     render::{
@@ -13,46 +24,590 @@ use bevy::{
         render_resource::PrimitiveTopology,
     },
 };
-use ndshape::ConstShape;
 
-use crate::{
-    chunk::{PaddedChunkShape, CHUNK_SIZE_U},
-    voxel::WorldVoxel,
-    voxel_material::ATTRIBUTE_TEX_INDEX,
-};
+/// The voxels of a padded chunk, laid out by the `ChunkShape` they are meshed with
+type VoxelArray<I> = Arc<[WorldVoxel<I>]>;
+
+/// Bump this whenever the mesher output changes, to invalidate persisted mesh caches. Version 2
+/// covers the face ordering, lighting and `faces_merge` changes.
+pub(crate) const MESHER_VERSION: u32 = 2;
 
-type VoxelArray<I> = Arc<[WorldVoxel<I>; PaddedChunkShape::SIZE as usize]>;
+/// The meshing related callbacks of a world configuration, collected so that they can be
+/// sent along to the meshing threads.
+pub(crate) struct MeshingOptions<I> {
+    pub texture_index_mapper: TextureIndexMapperFn<I>,
+    pub texture_index_face_mapper: Option<FaceTextureMapperFn<I>>,
+    pub contextual_texture: Option<ContextualTextureFn<I>>,
+    pub face_visible: Option<FaceVisibleFn<I>>,
+    pub faces_merge: Option<FacesMergeFn<I>>,
+    pub material_group: Option<MaterialGroupFn<I>>,
+    pub voxel_shape: Option<VoxelShapeFn<I>>,
+    pub fluid_surface_height: f32,
+    pub algorithm: MeshingAlgorithm,
+    pub uv_mode: UvMode,
+    pub texture_mode: VoxelTextureMode,
+    pub lighting: Option<VoxelLighting>,
+    pub voxel_emission: Option<Arc<dyn Fn(I) -> u8 + Send + Sync>>,
+    #[cfg(feature = "compact_vertices")]
+    pub compact_vertices: bool,
+}
+
+impl<I> Clone for MeshingOptions<I> {
+    fn clone(&self) -> Self {
+        Self {
+            texture_index_mapper: self.texture_index_mapper.clone(),
+            texture_index_face_mapper: self.texture_index_face_mapper.clone(),
+            contextual_texture: self.contextual_texture.clone(),
+            face_visible: self.face_visible.clone(),
+            faces_merge: self.faces_merge.clone(),
+            material_group: self.material_group.clone(),
+            voxel_shape: self.voxel_shape.clone(),
+            fluid_surface_height: self.fluid_surface_height,
+            algorithm: self.algorithm,
+            uv_mode: self.uv_mode,
+            texture_mode: self.texture_mode,
+            lighting: self.lighting,
+            voxel_emission: self.voxel_emission.clone(),
+            #[cfg(feature = "compact_vertices")]
+            compact_vertices: self.compact_vertices,
+        }
+    }
+}
+
+impl<I: Copy> MeshingOptions<I> {
+    pub fn from_config<C: VoxelWorldConfig<MaterialIndex = I>>(configuration: &C) -> Self
+    where
+        I: 'static,
+    {
+        Self {
+            texture_index_mapper: configuration.texture_index_mapper(),
+            texture_index_face_mapper: configuration.texture_index_face_mapper(),
+            contextual_texture: configuration.contextual_texture(),
+            face_visible: configuration.face_visible(),
+            faces_merge: configuration.faces_merge(),
+            material_group: configuration.material_group().or_else(|| {
+                configuration
+                    .voxel_transparency()
+                    .map(|voxel_transparency| {
+                        Arc::new(move |material| voxel_transparency(material).into())
+                            as MaterialGroupFn<I>
+                    })
+            }),
+            voxel_shape: configuration.voxel_shape(),
+            fluid_surface_height: configuration.fluid_surface_height(),
+            algorithm: configuration.meshing_algorithm(),
+            uv_mode: configuration.uv_mode(),
+            texture_mode: configuration.voxel_texture_mode(),
+            lighting: configuration.voxel_lighting(),
+            voxel_emission: configuration.voxel_lighting().map(|_| {
+                let configuration = configuration.clone();
+                Arc::new(move |material| configuration.voxel_emission(material))
+                    as Arc<dyn Fn(I) -> u8 + Send + Sync>
+            }),
+            #[cfg(feature = "compact_vertices")]
+            compact_vertices: configuration.compact_vertex_attributes(),
+        }
+    }
+
+    /// Whether adjacent faces get merged into larger quads. Faces are never merged with a texture
+    /// atlas, since textures can't repeat within an atlas tile, or with voxel lighting, since the
+    /// light is baked into the corners of each face.
+    fn merges_faces(&self) -> bool {
+        self.algorithm == MeshingAlgorithm::Greedy
+            && !matches!(self.texture_mode, VoxelTextureMode::Atlas { .. })
+            && self.lighting.is_none()
+    }
+
+    /// The texture coordinates of a face, from its per face texture coordinates, normal and
+    /// position in the padded chunk. `chunk_min` is the world position of the first voxel of the
+    /// chunk.
+    fn tex_coord(
+        &self,
+        uv: [f32; 2],
+        position: Vec3,
+        normal: IVec3,
+        chunk_min: IVec3,
+        texture_indexes: [u32; 3],
+    ) -> [f32; 2] {
+        match (self.texture_mode, self.uv_mode) {
+            (VoxelTextureMode::Atlas { .. }, _) => self
+                .texture_mode
+                .tex_coord(uv, texture_indexes[texture_slot(normal)]),
+            (_, UvMode::PerFace) => uv,
+            (_, UvMode::World { tile_size }) => {
+                world_tex_coord(position, normal, chunk_min, tile_size)
+            }
+        }
+    }
+
+    /// Whether faces between solid voxels can be visible. If so, chunks that are completely
+    /// filled with solid voxels still need to be meshed.
+    pub fn meshes_full_chunks(&self) -> bool {
+        self.face_visible.is_some() || self.material_group.is_some() || self.voxel_shape.is_some()
+    }
+
+    pub fn shape(&self, material: I) -> VoxelShape {
+        self.voxel_shape
+            .as_ref()
+            .map_or(VoxelShape::FullCube, |voxel_shape| voxel_shape(material))
+    }
+
+    /// Whether the face of a voxel pointing along `normal` is hidden by the `neighbor` covering it.
+    /// Solid neighbors hide the face when their shape covers the side against it, unless
+    /// `visible_against` says the face is visible between the two materials.
+    fn face_hidden_by(
+        &self,
+        material: I,
+        neighbor: WorldVoxel<I>,
+        normal: IVec3,
+        visible_against: impl Fn(I, I, VoxelFace) -> bool,
+    ) -> bool {
+        match neighbor {
+            WorldVoxel::Solid(neighbor) => {
+                self.shape(neighbor).covers_side(-normal)
+                    && !visible_against(material, neighbor, axis_face(normal))
+            }
+            _ => false,
+        }
+    }
+
+    /// Get the `[top, sides, bottom]` texture indexes for the given face of a voxel. `position` is
+    /// the world position of the voxel and `padded_pos` its position in the padded voxel array.
+    fn texture_indexes<S: ChunkShape>(
+        &self,
+        material: I,
+        face: VoxelFace,
+        position: IVec3,
+        padded_pos: [u32; 3],
+        voxels: &VoxelArray<I>,
+        shape: &S,
+    ) -> [u32; 3] {
+        if let Some(contextual_texture) = &self.contextual_texture {
+            let neighbors = voxel_neighbors(padded_pos, voxels, shape);
+            [contextual_texture(position, material, &neighbors).get(face); 3]
+        } else if let Some(face_mapper) = &self.texture_index_face_mapper {
+            // The shader picks the index to use based on the face normal, so when the index is
+            // chosen per face it can simply be used in all three slots.
+            [face_mapper(material, face); 3]
+        } else {
+            (self.texture_index_mapper)(material)
+        }
+    }
+}
+
+/// Get the face neighbors of the voxel at the given position in the padded voxel array
+fn voxel_neighbors<I: Copy, S: ChunkShape>(
+    [x, y, z]: [u32; 3],
+    voxels: &VoxelArray<I>,
+    shape: &S,
+) -> VoxelNeighbors<I> {
+    let voxel_at = |p: [u32; 3]| voxels[shape.linearize(p) as usize];
+    VoxelNeighbors {
+        above: voxel_at([x, y + 1, z]),
+        below: voxel_at([x, y - 1, z]),
+        left: voxel_at([x - 1, y, z]),
+        right: voxel_at([x + 1, y, z]),
+        back: voxel_at([x, y, z - 1]),
+        forward: voxel_at([x, y, z + 1]),
+    }
+}
+
+/// The texture index slot the shader picks for a face with the given normal
+fn texture_slot(normal: IVec3) -> usize {
+    match normal.y {
+        1.. => 0,
+        0 => 1,
+        _ => 2,
+    }
+}
 
 /// Generate a mesh for the given chunks, or None of the chunk is empty
-pub(super) fn generate_chunk_mesh<I: PartialEq + Copy>(
+pub(super) fn generate_chunk_mesh<I: PartialEq + Copy, S: ChunkShape>(
     voxels: VoxelArray<I>,
-    _pos: IVec3,
-    texture_index_mapper: Arc<dyn Fn(I) -> [u32; 3] + Send + Sync>,
+    shape: &S,
+    pos: IVec3,
+    meshing_options: &MeshingOptions<I>,
 ) -> Mesh {
     let faces = RIGHT_HANDED_Y_UP_CONFIG.faces;
-    let mut buffer = UnitQuadBuffer::new();
 
-    visible_block_faces(
-        &*voxels,
-        &PaddedChunkShape {},
+    let visible_against = |material, neighbor, face| {
+        meshing_options
+            .face_visible
+            .as_ref()
+            .is_some_and(|face_visible| face_visible(material, neighbor, face))
+    };
+    // The greedy quads of `block_mesh` can only merge equal faces, so faces that need asking
+    // whether they merge go through the custom visibility mesher too
+    let custom_visibility = meshing_options.face_visible.is_some()
+        || meshing_options.voxel_shape.is_some()
+        || (meshing_options.merges_faces() && meshing_options.faces_merge.is_some());
+
+    let quads = match (meshing_options.merges_faces(), custom_visibility) {
+        (merges_faces, true) => {
+            let greedy_voxels =
+                merges_faces.then(|| greedy_voxels(&voxels, shape, pos, &faces, meshing_options));
+            custom_visibility_quad_groups(
+                &voxels,
+                shape,
+                &faces,
+                |material, neighbor, normal| {
+                    meshing_options.shape(material) == VoxelShape::FullCube
+                        && !meshing_options.face_hidden_by(
+                            material,
+                            neighbor,
+                            normal,
+                            visible_against,
+                        )
+                },
+                greedy_voxels.as_deref(),
+                meshing_options.faces_merge.as_ref(),
+            )
+        }
+        (false, false) => {
+            let mut buffer = UnitQuadBuffer::new();
+            visible_block_faces(
+                &voxels,
+                shape,
+                [0; 3],
+                [shape.chunk_size() + 1; 3],
+                &faces,
+                &mut buffer,
+            );
+            buffer
+                .groups
+                .map(|group| group.into_iter().map(UnorientedQuad::from).collect())
+        }
+        (true, false) => greedy_quad_groups(&voxels, shape, pos, &faces, meshing_options),
+    };
+
+    let mut mesh = mesh_from_quads(quads, faces, voxels.clone(), shape, pos, meshing_options);
+    if meshing_options.voxel_shape.is_some() {
+        mesh.merge(&shaped_voxel_mesh(
+            &voxels,
+            shape,
+            pos,
+            meshing_options,
+            |_| true,
+            visible_against,
+        ));
+    }
+    mesh
+}
+
+/// Generate one mesh for each material group in the chunk. The `Opaque` group always comes
+/// first, even if it has no faces, while other groups are left out when they have no faces.
+pub(super) fn generate_chunk_group_meshes<I: PartialEq + Copy, S: ChunkShape>(
+    voxels: VoxelArray<I>,
+    shape: &S,
+    pos: IVec3,
+    meshing_options: &MeshingOptions<I>,
+    material_group: &MaterialGroupFn<I>,
+) -> Vec<(MaterialGroup, Mesh)> {
+    let faces = RIGHT_HANDED_Y_UP_CONFIG.faces;
+    let greedy_voxels = meshing_options
+        .merges_faces()
+        .then(|| greedy_voxels(&voxels, shape, pos, &faces, meshing_options));
+
+    let mut groups: BTreeSet<MaterialGroup> = voxels
+        .iter()
+        .filter_map(|voxel| match voxel {
+            WorldVoxel::Solid(material) => Some(material_group(*material)),
+            _ => None,
+        })
+        .collect();
+    groups.insert(MaterialGroup::Opaque);
+
+    groups
+        .into_iter()
+        .filter_map(|group| {
+            let visible_against = |material, neighbor, face| match &meshing_options.face_visible {
+                Some(face_visible) => face_visible(material, neighbor, face),
+                None => group.face_visible_against(material_group(neighbor)),
+            };
+            let quads = custom_visibility_quad_groups(
+                &voxels,
+                shape,
+                &faces,
+                |material, neighbor, normal| {
+                    material_group(material) == group
+                        && meshing_options.shape(material) == VoxelShape::FullCube
+                        && !meshing_options.face_hidden_by(
+                            material,
+                            neighbor,
+                            normal,
+                            visible_against,
+                        )
+                },
+                // Fluid faces are kept apart, so that the surface can be lowered voxel by voxel
+                greedy_voxels
+                    .as_deref()
+                    .filter(|_| group != MaterialGroup::Fluid),
+                meshing_options.faces_merge.as_ref(),
+            );
+            let shaped_mesh = meshing_options.voxel_shape.as_ref().map(|_| {
+                shaped_voxel_mesh(
+                    &voxels,
+                    shape,
+                    pos,
+                    meshing_options,
+                    |material| material_group(material) == group,
+                    visible_against,
+                )
+            });
+
+            if group != MaterialGroup::Opaque
+                && quads.iter().all(Vec::is_empty)
+                && shaped_mesh
+                    .as_ref()
+                    .is_none_or(|mesh| mesh.count_vertices() == 0)
+            {
+                return None;
+            }
+
+            let surface_quads: Option<Vec<bool>> = (group == MaterialGroup::Fluid).then(|| {
+                quads
+                    .iter()
+                    .flatten()
+                    .map(|quad| {
+                        let above = [quad.minimum[0], quad.minimum[1] + 1, quad.minimum[2]];
+                        !matches!(
+                            voxels[shape.linearize(above) as usize],
+                            WorldVoxel::Solid(_)
+                        )
+                    })
+                    .collect()
+            });
+
+            let mut mesh =
+                mesh_from_quads(quads, faces, voxels.clone(), shape, pos, meshing_options);
+            if let Some(surface_quads) = surface_quads {
+                lower_fluid_surface(
+                    &mut mesh,
+                    &surface_quads,
+                    1.0 - meshing_options.fluid_surface_height,
+                );
+            }
+            if let Some(shaped_mesh) = shaped_mesh {
+                mesh.merge(&shaped_mesh);
+            }
+            Some((group, mesh))
+        })
+        .collect()
+}
+
+/// A voxel as seen by the greedy mesher. Faces are only merged if their texture indexes and
+/// ambient occlusion match, so that merged quads look the same as unmerged ones.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct GreedyVoxel {
+    visibility: VoxelVisibility,
+    texture_indexes: [u32; 6],
+    aos: [u8; 24],
+}
+
+impl Voxel for GreedyVoxel {
+    fn get_visibility(&self) -> VoxelVisibility {
+        self.visibility
+    }
+}
+
+impl MergeVoxel for GreedyVoxel {
+    type MergeValue = ([u32; 6], [u8; 24]);
+
+    fn merge_value(&self) -> Self::MergeValue {
+        (self.texture_indexes, self.aos)
+    }
+}
+
+/// Find the visible faces of the chunk, merging adjacent faces that look the same into larger quads
+fn greedy_quad_groups<I: PartialEq + Copy, S: ChunkShape>(
+    voxels: &VoxelArray<I>,
+    shape: &S,
+    chunk_pos: IVec3,
+    faces: &[OrientedBlockFace; 6],
+    meshing_options: &MeshingOptions<I>,
+) -> [Vec<UnorientedQuad>; 6] {
+    let greedy_voxels = greedy_voxels(voxels, shape, chunk_pos, faces, meshing_options);
+
+    let mut buffer = GreedyQuadsBuffer::new(greedy_voxels.len());
+    greedy_quads(
+        &greedy_voxels,
+        shape,
         [0; 3],
-        [CHUNK_SIZE_U + 1; 3],
-        &faces,
+        [shape.chunk_size() + 1; 3],
+        faces,
         &mut buffer,
     );
 
-    mesh_from_quads(buffer, faces, voxels, texture_index_mapper)
+    buffer.quads.groups
+}
+
+/// Get the greedy mesher view of every voxel in the padded voxel array
+fn greedy_voxels<I: PartialEq + Copy, S: ChunkShape>(
+    voxels: &VoxelArray<I>,
+    shape: &S,
+    chunk_pos: IVec3,
+    faces: &[OrientedBlockFace; 6],
+    meshing_options: &MeshingOptions<I>,
+) -> Vec<GreedyVoxel> {
+    (0..shape.size())
+        .map(|i| {
+            let padded_pos = shape.delinearize(i);
+            let mut greedy_voxel = GreedyVoxel {
+                visibility: voxels[i as usize].get_visibility(),
+                texture_indexes: [0; 6],
+                aos: [0; 24],
+            };
+
+            // Only voxels inside the chunk get meshed, the padding is just for visibility
+            let in_chunk = padded_pos
+                .iter()
+                .all(|&c| (1..=shape.chunk_size()).contains(&c));
+            if let (true, WorldVoxel::Solid(material)) = (in_chunk, voxels[i as usize]) {
+                let position =
+                    shape.chunk_min(chunk_pos) + IVec3::from(padded_pos.map(|v| v as i32)) - 1;
+                for (n, face) in faces.iter().enumerate() {
+                    let normal = IVec3::from(face.signed_normal().to_array());
+                    greedy_voxel.texture_indexes[n] = meshing_options.texture_indexes(
+                        material,
+                        axis_face(normal),
+                        position,
+                        padded_pos,
+                        voxels,
+                        shape,
+                    )[texture_slot(normal)];
+                    let aos = face_aos(&padded_pos, &normal, voxels, shape);
+                    for (corner, ao) in aos.iter().enumerate() {
+                        greedy_voxel.aos[n * 4 + corner] = *ao as u8;
+                    }
+                }
+            }
+
+            greedy_voxel
+        })
+        .collect()
+}
+
+/// Find the visible faces of the chunk, when the mesher can't decide which faces between solid
+/// voxels are visible by itself. `needs_face` is called with the material of a voxel, the
+/// neighbor covering the face and the normal of the face. Adjacent faces are merged into larger
+/// quads when `greedy_voxels` are supplied and their merge values match, and `faces_merge` agrees
+/// if it is supplied.
+fn custom_visibility_quad_groups<I: Copy, S: ChunkShape>(
+    voxels: &VoxelArray<I>,
+    shape: &S,
+    faces: &[OrientedBlockFace; 6],
+    needs_face: impl Fn(I, WorldVoxel<I>, IVec3) -> bool,
+    greedy_voxels: Option<&[GreedyVoxel]>,
+    faces_merge: Option<&FacesMergeFn<I>>,
+) -> [Vec<UnorientedQuad>; 6] {
+    let in_chunk =
+        |p: UVec3| p.cmpge(UVec3::ONE).all() && p.cmple(UVec3::splat(shape.chunk_size())).all();
+    let index = |p: UVec3| shape.linearize(p.to_array()) as usize;
+    let merge_key = |i: usize| greedy_voxels.map(|greedy_voxels| greedy_voxels[i].merge_value());
+
+    faces.map(|face| {
+        let normal = IVec3::from(face.signed_normal().to_array());
+
+        // The directions a quad on this face grows in
+        let corners = face.quad_corners(&UnorientedQuad {
+            minimum: [0; 3],
+            width: 1,
+            height: 1,
+        });
+        let [u, v] = [corners[1], corners[2]].map(|c| UVec3::from((c - corners[0]).to_array()));
+
+        let needs_face = |p: UVec3| match voxels[index(p)] {
+            WorldVoxel::Solid(material) => needs_face(
+                material,
+                voxels[index((p.as_ivec3() + normal).as_uvec3())],
+                normal,
+            ),
+            _ => false,
+        };
+
+        let mut visited = vec![false; shape.usize()];
+        let mut quads = Vec::new();
+        for i in 0..shape.size() {
+            let minimum = UVec3::from(shape.delinearize(i));
+            if !in_chunk(minimum) || visited[i as usize] || !needs_face(minimum) {
+                continue;
+            }
+
+            let key = merge_key(i as usize);
+            let faces_merge = |p: UVec3| match (faces_merge, voxels[i as usize], voxels[index(p)]) {
+                (Some(faces_merge), WorldVoxel::Solid(material), WorldVoxel::Solid(other)) => {
+                    faces_merge(material, other, axis_face(normal))
+                }
+                _ => true,
+            };
+            let can_merge = |p: UVec3| {
+                key.is_some()
+                    && in_chunk(p)
+                    && !visited[index(p)]
+                    && needs_face(p)
+                    && merge_key(index(p)) == key
+                    && faces_merge(p)
+            };
+
+            let mut width = 1;
+            while can_merge(minimum + u * width) {
+                width += 1;
+            }
+            let mut height = 1;
+            while (0..width).all(|w| can_merge(minimum + u * w + v * height)) {
+                height += 1;
+            }
+
+            for h in 0..height {
+                for w in 0..width {
+                    visited[index(minimum + u * w + v * h)] = true;
+                }
+            }
+
+            quads.push(UnorientedQuad {
+                minimum: minimum.to_array(),
+                width,
+                height,
+            });
+        }
+        quads
+    })
 }
 
-/// Convert a QuadBuffer into a Bevy Mesh
-fn mesh_from_quads<I: PartialEq + Copy>(
-    quads: UnitQuadBuffer,
+/// The `UvMode::World` texture coordinate of a position in the padded chunk. The chunk origin is
+/// only taken modulo `tile_size`, which keeps the coordinates small without making them wrap
+/// within a quad, so the shader can wrap them with `fract`.
+fn world_tex_coord(position: Vec3, normal: IVec3, chunk_min: IVec3, tile_size: u32) -> [f32; 2] {
+    let tile_size = tile_size.max(1) as i32;
+    let offset = (chunk_min - IVec3::ONE)
+        .rem_euclid(IVec3::splat(tile_size))
+        .as_vec3();
+
+    let p = position + offset;
+    // Textures are upright on side faces, with v pointing down
+    let uv = if normal.y != 0 {
+        Vec2::new(p.x, p.z)
+    } else if normal.x != 0 {
+        Vec2::new(p.z, -p.y)
+    } else {
+        Vec2::new(p.x, -p.y)
+    };
+    (uv / tile_size as f32).to_array()
+}
+
+/// Convert groups of quads into a Bevy Mesh
+fn mesh_from_quads<I: PartialEq + Copy, S: ChunkShape>(
+    quads: [Vec<UnorientedQuad>; 6],
     faces: [OrientedBlockFace; 6],
     voxels: VoxelArray<I>,
-    texture_index_mapper: Arc<dyn Fn(I) -> [u32; 3] + Send + Sync>,
+    shape: &S,
+    chunk_pos: IVec3,
+    meshing_options: &MeshingOptions<I>,
 ) -> Mesh {
-    let num_indices = quads.num_quads() * 6;
-    let num_vertices = quads.num_quads() * 4;
+    let chunk_min = shape.chunk_min(chunk_pos);
+    let num_quads: usize = quads.iter().map(Vec::len).sum();
+    let num_indices = num_quads * 6;
+    let num_vertices = num_quads * 4;
 
     let mut indices = Vec::with_capacity(num_indices);
     let mut positions = Vec::with_capacity(num_vertices);
@@ -61,7 +616,7 @@ fn mesh_from_quads<I: PartialEq + Copy>(
     let mut material_types = Vec::with_capacity(num_vertices);
     let mut aos = Vec::with_capacity(num_vertices);
 
-    for (group, face) in quads.groups.into_iter().zip(faces.into_iter()) {
+    for (group, face) in quads.into_iter().zip(faces) {
         for quad in group.into_iter() {
             let normal = IVec3::from([
                 face.signed_normal().x,
@@ -69,28 +624,44 @@ fn mesh_from_quads<I: PartialEq + Copy>(
                 face.signed_normal().z,
             ]);
 
-            let ao = face_aos(&quad.minimum, &normal, &voxels);
+            let ao = face_aos(&quad.minimum, &normal, &voxels, shape);
             aos.extend_from_slice(&ao);
 
             // TODO: Fix AO anisotropy
             indices.extend_from_slice(&face.quad_mesh_indices(positions.len() as u32));
 
-            positions.extend_from_slice(&face.quad_mesh_positions(&quad.into(), 1.0));
+            let quad_positions = face.quad_mesh_positions(&quad, 1.0);
+            positions.extend_from_slice(&quad_positions);
 
             normals.extend_from_slice(&face.quad_mesh_normals());
 
-            tex_coords.extend_from_slice(&face.tex_coords(
-                RIGHT_HANDED_Y_UP_CONFIG.u_flip_face,
-                true,
-                &quad.into(),
-            ));
-
-            let voxel_index = PaddedChunkShape::linearize(quad.minimum) as usize;
+            let voxel_index = shape.linearize(quad.minimum) as usize;
             let material_type = match voxels[voxel_index] {
-                WorldVoxel::Solid(mt) => texture_index_mapper(mt),
+                WorldVoxel::Solid(mt) => meshing_options.texture_indexes(
+                    mt,
+                    axis_face(normal),
+                    chunk_min + IVec3::from(quad.minimum.map(|v| v as i32)) - IVec3::ONE,
+                    quad.minimum,
+                    &voxels,
+                    shape,
+                ),
                 _ => [0, 0, 0],
             };
-            material_types.extend(std::iter::repeat(material_type).take(4));
+            material_types.extend(std::iter::repeat_n(material_type, 4));
+
+            let face_tex_coords =
+                face.tex_coords(RIGHT_HANDED_Y_UP_CONFIG.u_flip_face, true, &quad);
+            tex_coords.extend(face_tex_coords.into_iter().zip(quad_positions).map(
+                |(uv, position)| {
+                    meshing_options.tex_coord(
+                        uv,
+                        Vec3::from(position),
+                        normal,
+                        chunk_min,
+                        material_type,
+                    )
+                },
+            ));
         }
     }
 
@@ -137,6 +708,432 @@ fn mesh_from_quads<I: PartialEq + Copy>(
     render_mesh
 }
 
+/// Lower the top faces of the quads flagged in `surface_quads` by `depth`, along with the top
+/// edges of their side faces. The quads must not be merged, and be in the order `mesh_from_quads`
+/// put them in.
+fn lower_fluid_surface(mesh: &mut Mesh, surface_quads: &[bool], depth: f32) {
+    let Some(VertexAttributeValues::Float32x3(normals)) =
+        mesh.attribute(Mesh::ATTRIBUTE_NORMAL).cloned()
+    else {
+        return;
+    };
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return;
+    };
+
+    for (quad, _) in surface_quads
+        .iter()
+        .enumerate()
+        .filter(|(_, surface)| **surface)
+    {
+        let vertices = quad * 4..quad * 4 + 4;
+        if normals[quad * 4][1] < 0.0 {
+            continue;
+        }
+        let top = positions[vertices.clone()]
+            .iter()
+            .map(|position| position[1])
+            .fold(f32::MIN, f32::max);
+        for position in &mut positions[vertices] {
+            if position[1] == top {
+                position[1] -= depth;
+            }
+        }
+    }
+}
+
+/// A polygon of a voxel shape, in a unit cube. `side` is set for polygons that lie on a side
+/// of the cube, which get culled when the neighbor on that side hides them.
+struct ShapeFace {
+    corners: Vec<Vec3>,
+    normal: Vec3,
+    side: Option<IVec3>,
+}
+
+/// A rectangle from `min` to `max`, which are equal along the axis of `normal`
+fn shape_rect(normal: IVec3, min: Vec3, max: Vec3) -> ShapeFace {
+    let corners = if normal.x != 0 {
+        vec![
+            min,
+            Vec3::new(min.x, max.y, min.z),
+            max,
+            Vec3::new(min.x, min.y, max.z),
+        ]
+    } else if normal.y != 0 {
+        vec![
+            min,
+            Vec3::new(max.x, min.y, min.z),
+            max,
+            Vec3::new(min.x, min.y, max.z),
+        ]
+    } else {
+        vec![
+            min,
+            Vec3::new(max.x, min.y, min.z),
+            max,
+            Vec3::new(min.x, max.y, min.z),
+        ]
+    };
+    let plane = min.dot(normal.abs().as_vec3());
+    let on_side = if normal.cmpgt(IVec3::ZERO).any() {
+        plane == 1.0
+    } else {
+        plane == 0.0
+    };
+    ShapeFace {
+        corners,
+        normal: normal.as_vec3(),
+        side: on_side.then_some(normal),
+    }
+}
+
+/// The polygons of a shape. Directional shapes are built facing `+Z` and turned around the
+/// center of the voxel.
+fn shape_faces(shape: VoxelShape) -> Vec<ShapeFace> {
+    let rect = shape_rect;
+    let cuboid = |min: Vec3, max: Vec3| {
+        vec![
+            rect(IVec3::NEG_X, min, Vec3::new(min.x, max.y, max.z)),
+            rect(IVec3::X, Vec3::new(max.x, min.y, min.z), max),
+            rect(IVec3::NEG_Y, min, Vec3::new(max.x, min.y, max.z)),
+            rect(IVec3::Y, Vec3::new(min.x, max.y, min.z), max),
+            rect(IVec3::NEG_Z, min, Vec3::new(max.x, max.y, min.z)),
+            rect(IVec3::Z, Vec3::new(min.x, min.y, max.z), max),
+        ]
+    };
+
+    let (faces, facing) = match shape {
+        VoxelShape::FullCube => (cuboid(Vec3::ZERO, Vec3::ONE), IVec3::Z),
+        VoxelShape::Slab { top: false } => (cuboid(Vec3::ZERO, Vec3::new(1.0, 0.5, 1.0)), IVec3::Z),
+        VoxelShape::Slab { top: true } => (cuboid(Vec3::new(0.0, 0.5, 0.0), Vec3::ONE), IVec3::Z),
+        VoxelShape::Stairs { facing } => (
+            vec![
+                rect(IVec3::NEG_Y, Vec3::ZERO, Vec3::new(1.0, 0.0, 1.0)),
+                rect(IVec3::Y, Vec3::new(0.0, 0.5, 0.0), Vec3::new(1.0, 0.5, 0.5)),
+                rect(IVec3::Y, Vec3::new(0.0, 1.0, 0.5), Vec3::ONE),
+                rect(IVec3::NEG_Z, Vec3::ZERO, Vec3::new(1.0, 0.5, 0.0)),
+                rect(
+                    IVec3::NEG_Z,
+                    Vec3::new(0.0, 0.5, 0.5),
+                    Vec3::new(1.0, 1.0, 0.5),
+                ),
+                rect(IVec3::Z, Vec3::new(0.0, 0.0, 1.0), Vec3::ONE),
+                rect(IVec3::NEG_X, Vec3::ZERO, Vec3::new(0.0, 0.5, 1.0)),
+                rect(
+                    IVec3::NEG_X,
+                    Vec3::new(0.0, 0.5, 0.5),
+                    Vec3::new(0.0, 1.0, 1.0),
+                ),
+                rect(IVec3::X, Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.0, 0.5, 1.0)),
+                rect(IVec3::X, Vec3::new(1.0, 0.5, 0.5), Vec3::ONE),
+            ],
+            horizontal_facing(facing),
+        ),
+        VoxelShape::Ramp { facing } => {
+            let wedge_side = |x: f32, normal: IVec3| ShapeFace {
+                corners: vec![
+                    Vec3::new(x, 0.0, 0.0),
+                    Vec3::new(x, 0.0, 1.0),
+                    Vec3::new(x, 1.0, 1.0),
+                ],
+                normal: normal.as_vec3(),
+                side: Some(normal),
+            };
+            (
+                vec![
+                    rect(IVec3::NEG_Y, Vec3::ZERO, Vec3::new(1.0, 0.0, 1.0)),
+                    rect(IVec3::Z, Vec3::new(0.0, 0.0, 1.0), Vec3::ONE),
+                    ShapeFace {
+                        corners: vec![Vec3::ZERO, Vec3::X, Vec3::ONE, Vec3::new(0.0, 1.0, 1.0)],
+                        normal: Vec3::new(0.0, 1.0, -1.0).normalize(),
+                        side: None,
+                    },
+                    wedge_side(0.0, IVec3::NEG_X),
+                    wedge_side(1.0, IVec3::X),
+                ],
+                horizontal_facing(facing),
+            )
+        }
+    };
+
+    let angle = match facing {
+        IVec3::X => std::f32::consts::FRAC_PI_2,
+        IVec3::NEG_X => -std::f32::consts::FRAC_PI_2,
+        IVec3::NEG_Z => std::f32::consts::PI,
+        _ => return faces,
+    };
+    let rotation = Quat::from_rotation_y(angle);
+    // Corners are on a half voxel grid, so snap them back onto it after turning
+    let turn = |p: Vec3| ((rotation * (p - 0.5) + 0.5) * 2.0).round() / 2.0;
+    faces
+        .into_iter()
+        .map(|face| ShapeFace {
+            corners: face.corners.into_iter().map(turn).collect(),
+            normal: rotation * face.normal,
+            side: face
+                .side
+                .map(|side| (rotation * side.as_vec3()).round().as_ivec3()),
+        })
+        .collect()
+}
+
+/// The axis aligned direction closest to the given normal, preferring up and down on ties
+pub(crate) fn dominant_axis(normal: Vec3) -> IVec3 {
+    let abs = normal.abs();
+    if abs.y >= abs.x && abs.y >= abs.z {
+        IVec3::new(0, normal.y.signum() as i32, 0)
+    } else if abs.x >= abs.z {
+        IVec3::new(normal.x.signum() as i32, 0, 0)
+    } else {
+        IVec3::new(0, 0, normal.z.signum() as i32)
+    }
+}
+
+/// The face pointing along `axis`, which is one of the six unit axes
+fn axis_face(axis: IVec3) -> VoxelFace {
+    VoxelFace::from_normal(axis).expect("voxel faces point along a unit axis")
+}
+
+/// Mesh the voxels of the chunk that have a shape other than `VoxelShape::FullCube`, for the
+/// materials that `include` accepts. Polygons on the sides of a voxel are culled like cube faces,
+/// with `visible_against` deciding for solid neighbors. Shaped voxels have no ambient occlusion.
+fn shaped_voxel_mesh<I: PartialEq + Copy, S: ChunkShape>(
+    voxels: &VoxelArray<I>,
+    chunk_shape: &S,
+    chunk_pos: IVec3,
+    meshing_options: &MeshingOptions<I>,
+    include: impl Fn(I) -> bool,
+    visible_against: impl Fn(I, I, VoxelFace) -> bool,
+) -> Mesh {
+    let index = |p: IVec3| chunk_shape.linearize(p.as_uvec3().to_array()) as usize;
+    let chunk_min = chunk_shape.chunk_min(chunk_pos);
+
+    let mut indices = Vec::new();
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut tex_coords = Vec::new();
+    let mut material_types = Vec::new();
+
+    for i in 0..chunk_shape.size() {
+        let padded_pos = chunk_shape.delinearize(i);
+        if !padded_pos
+            .iter()
+            .all(|&c| (1..=chunk_shape.chunk_size()).contains(&c))
+        {
+            continue;
+        }
+        let WorldVoxel::Solid(material) = voxels[i as usize] else {
+            continue;
+        };
+        let shape = meshing_options.shape(material);
+        if shape == VoxelShape::FullCube || !include(material) {
+            continue;
+        }
+
+        let local = IVec3::from(padded_pos.map(|v| v as i32));
+        let position = chunk_min + local - IVec3::ONE;
+        for face in shape_faces(shape) {
+            let hidden = face.side.is_some_and(|side| {
+                meshing_options.face_hidden_by(
+                    material,
+                    voxels[index(local + side)],
+                    side,
+                    &visible_against,
+                )
+            });
+            if hidden {
+                continue;
+            }
+
+            let axis = dominant_axis(face.normal);
+            let material_type = meshing_options.texture_indexes(
+                material,
+                axis_face(axis),
+                position,
+                padded_pos,
+                voxels,
+                chunk_shape,
+            );
+
+            // Fan out triangles from the first corner, wound counter-clockwise around the normal
+            let first = positions.len() as u32;
+            for n in 1..face.corners.len() as u32 - 1 {
+                let [a, b, c] = [0, n, n + 1].map(|c| face.corners[c as usize]);
+                if (b - a).cross(c - a).dot(face.normal) >= 0.0 {
+                    indices.extend([first, first + n, first + n + 1]);
+                } else {
+                    indices.extend([first, first + n + 1, first + n]);
+                }
+            }
+            for corner in face.corners {
+                let vertex = local.as_vec3() + corner;
+                positions.push(vertex.to_array());
+                normals.push(face.normal.to_array());
+                let uv = if axis.y != 0 {
+                    [corner.x, corner.z]
+                } else if axis.x != 0 {
+                    [corner.z, 1.0 - corner.y]
+                } else {
+                    [corner.x, 1.0 - corner.y]
+                };
+                tex_coords.push(meshing_options.tex_coord(
+                    uv,
+                    vertex,
+                    axis,
+                    chunk_min,
+                    material_type,
+                ));
+                material_types.push(material_type);
+            }
+        }
+    }
+
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        VertexAttributeValues::Float32x3(positions),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        VertexAttributeValues::Float32x3(normals),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        VertexAttributeValues::Float32x2(tex_coords),
+    );
+    mesh.insert_attribute(
+        ATTRIBUTE_TEX_INDEX,
+        VertexAttributeValues::Uint32x3(material_types),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_COLOR,
+        vec![[1.0, 1.0, 1.0, 1.0]; mesh.count_vertices()],
+    );
+    mesh.insert_indices(Indices::U32(indices));
+    mesh
+}
+
+/// Replace the normals, texture coordinates and colors of a chunk mesh with their compact
+/// versions. See `VoxelWorldConfig::compact_vertex_attributes`.
+#[cfg(feature = "compact_vertices")]
+pub(crate) fn compact_vertex_attributes(mesh: &mut Mesh) {
+    use crate::voxel_material::{
+        ATTRIBUTE_COMPACT_COLOR, ATTRIBUTE_COMPACT_NORMAL, ATTRIBUTE_COMPACT_UV,
+    };
+
+    let snorm = |value: f32| (value.clamp(-1.0, 1.0) * 127.0).round() as i8;
+    let unorm = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    if let Some(VertexAttributeValues::Float32x3(normals)) =
+        mesh.remove_attribute(Mesh::ATTRIBUTE_NORMAL)
+    {
+        mesh.insert_attribute(
+            ATTRIBUTE_COMPACT_NORMAL,
+            VertexAttributeValues::Snorm8x4(
+                normals
+                    .into_iter()
+                    .map(|[x, y, z]| [snorm(x), snorm(y), snorm(z), 0])
+                    .collect(),
+            ),
+        );
+    }
+    if let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.remove_attribute(Mesh::ATTRIBUTE_UV_0)
+    {
+        mesh.insert_attribute(
+            ATTRIBUTE_COMPACT_UV,
+            VertexAttributeValues::Uint16x2(uvs.into_iter().map(|uv| uv.map(f16_bits)).collect()),
+        );
+    }
+    if let Some(VertexAttributeValues::Float32x4(colors)) =
+        mesh.remove_attribute(Mesh::ATTRIBUTE_COLOR)
+    {
+        mesh.insert_attribute(
+            ATTRIBUTE_COMPACT_COLOR,
+            VertexAttributeValues::Unorm8x4(
+                colors.into_iter().map(|color| color.map(unorm)).collect(),
+            ),
+        );
+    }
+}
+
+/// The bits of the half precision float closest to `value`, rounding ties to even
+#[cfg(feature = "compact_vertices")]
+pub(crate) fn f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    // Infinity and NaN
+    if exponent == 0xff {
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let round = |value: u32, rest: u32, halfway: u32| {
+        value + (rest > halfway || (rest == halfway && value & 1 == 1)) as u32
+    };
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        sign | 0x7c00
+    } else if exponent <= 0 {
+        // Too small for a normal half precision float
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let rest = mantissa & ((1 << shift) - 1);
+        sign | round(mantissa >> shift, rest, 1 << (shift - 1)) as u16
+    } else {
+        // A carry out of the mantissa correctly bumps the exponent, up to infinity
+        let half = ((exponent as u32) << 10) | (mantissa >> 13);
+        sign | round(half, mantissa & 0x1fff, 0x1000) as u16
+    }
+}
+
+/// A magenta box filling a chunk, shown in place of chunks that failed to generate. Positions are
+/// relative to the chunk entity, the same as the positions of the chunk mesh.
+pub(crate) fn error_placeholder_mesh() -> Mesh {
+    let mut mesh = Mesh::from(Cuboid::from_size(Vec3::splat(CHUNK_SIZE_I as f32)))
+        .translated_by(Vec3::splat(1.0 + CHUNK_SIZE_I as f32 / 2.0));
+    let vertex_count = mesh.count_vertices();
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_COLOR,
+        vec![[1.0, 0.0, 1.0, 1.0]; vertex_count],
+    );
+    mesh.insert_attribute(
+        ATTRIBUTE_TEX_INDEX,
+        VertexAttributeValues::Uint32x3(vec![[0; 3]; vertex_count]),
+    );
+    mesh
+}
+
+/// A flat box of the given color covering a chunk from its bottom up to `height` voxels, shown
+/// while the chunk is generated. Positions are relative to the chunk entity.
+pub(crate) fn loading_placeholder_mesh(height: f32, color: Color) -> Mesh {
+    let size = CHUNK_SIZE_I as f32;
+    let mut mesh = Mesh::from(Cuboid::new(size, height, size)).translated_by(Vec3::new(
+        1.0 + size / 2.0,
+        1.0 + height / 2.0,
+        1.0 + size / 2.0,
+    ));
+    let vertex_count = mesh.count_vertices();
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_COLOR,
+        vec![color.to_linear().to_f32_array(); vertex_count],
+    );
+    mesh.insert_attribute(
+        ATTRIBUTE_TEX_INDEX,
+        VertexAttributeValues::Uint32x3(vec![[0; 3]; vertex_count]),
+    );
+    mesh
+}
+
 fn ao_value(side1: bool, corner: bool, side2: bool) -> u32 {
     match (side1, corner, side2) {
         (true, _, true) => 0,
@@ -166,73 +1163,75 @@ fn side_aos<I: PartialEq>(neighbours: [WorldVoxel<I>; 8]) -> [u32; 4] {
     ]
 }
 
-fn face_aos<I: PartialEq + Copy>(
+fn face_aos<I: PartialEq + Copy, S: ChunkShape>(
     voxel_pos: &[u32; 3],
     face_normal: &IVec3,
     voxels: &VoxelArray<I>,
+    shape: &S,
 ) -> [u32; 4] {
     let [x, y, z] = *voxel_pos;
+    let voxel_at = |p: [u32; 3]| voxels[shape.linearize(p) as usize];
 
     match *face_normal {
         IVec3::NEG_X => side_aos([
-            voxels[PaddedChunkShape::linearize([x - 1, y, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y - 1, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y - 1, z]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y - 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y + 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y + 1, z]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y + 1, z - 1]) as usize],
+            voxel_at([x - 1, y, z - 1]),
+            voxel_at([x - 1, y - 1, z - 1]),
+            voxel_at([x - 1, y - 1, z]),
+            voxel_at([x - 1, y - 1, z + 1]),
+            voxel_at([x - 1, y, z + 1]),
+            voxel_at([x - 1, y + 1, z + 1]),
+            voxel_at([x - 1, y + 1, z]),
+            voxel_at([x - 1, y + 1, z - 1]),
         ]),
         IVec3::X => side_aos([
-            voxels[PaddedChunkShape::linearize([x + 1, y, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y - 1, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y - 1, z]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y - 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y + 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y + 1, z]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y + 1, z - 1]) as usize],
+            voxel_at([x + 1, y, z - 1]),
+            voxel_at([x + 1, y - 1, z - 1]),
+            voxel_at([x + 1, y - 1, z]),
+            voxel_at([x + 1, y - 1, z + 1]),
+            voxel_at([x + 1, y, z + 1]),
+            voxel_at([x + 1, y + 1, z + 1]),
+            voxel_at([x + 1, y + 1, z]),
+            voxel_at([x + 1, y + 1, z - 1]),
         ]),
         IVec3::NEG_Y => side_aos([
-            voxels[PaddedChunkShape::linearize([x, y - 1, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y - 1, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y - 1, z]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y - 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x, y - 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y - 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y - 1, z]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y - 1, z - 1]) as usize],
+            voxel_at([x, y - 1, z - 1]),
+            voxel_at([x - 1, y - 1, z - 1]),
+            voxel_at([x - 1, y - 1, z]),
+            voxel_at([x - 1, y - 1, z + 1]),
+            voxel_at([x, y - 1, z + 1]),
+            voxel_at([x + 1, y - 1, z + 1]),
+            voxel_at([x + 1, y - 1, z]),
+            voxel_at([x + 1, y - 1, z - 1]),
         ]),
         IVec3::Y => side_aos([
-            voxels[PaddedChunkShape::linearize([x, y + 1, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y + 1, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y + 1, z]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y + 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x, y + 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y + 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y + 1, z]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y + 1, z - 1]) as usize],
+            voxel_at([x, y + 1, z - 1]),
+            voxel_at([x - 1, y + 1, z - 1]),
+            voxel_at([x - 1, y + 1, z]),
+            voxel_at([x - 1, y + 1, z + 1]),
+            voxel_at([x, y + 1, z + 1]),
+            voxel_at([x + 1, y + 1, z + 1]),
+            voxel_at([x + 1, y + 1, z]),
+            voxel_at([x + 1, y + 1, z - 1]),
         ]),
         IVec3::NEG_Z => side_aos([
-            voxels[PaddedChunkShape::linearize([x - 1, y, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y - 1, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x, y - 1, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y - 1, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y + 1, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x, y + 1, z - 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y + 1, z - 1]) as usize],
+            voxel_at([x - 1, y, z - 1]),
+            voxel_at([x - 1, y - 1, z - 1]),
+            voxel_at([x, y - 1, z - 1]),
+            voxel_at([x + 1, y - 1, z - 1]),
+            voxel_at([x + 1, y, z - 1]),
+            voxel_at([x + 1, y + 1, z - 1]),
+            voxel_at([x, y + 1, z - 1]),
+            voxel_at([x - 1, y + 1, z - 1]),
         ]),
         IVec3::Z => side_aos([
-            voxels[PaddedChunkShape::linearize([x - 1, y, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y - 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x, y - 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y - 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x + 1, y + 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x, y + 1, z + 1]) as usize],
-            voxels[PaddedChunkShape::linearize([x - 1, y + 1, z + 1]) as usize],
+            voxel_at([x - 1, y, z + 1]),
+            voxel_at([x - 1, y - 1, z + 1]),
+            voxel_at([x, y - 1, z + 1]),
+            voxel_at([x + 1, y - 1, z + 1]),
+            voxel_at([x + 1, y, z + 1]),
+            voxel_at([x + 1, y + 1, z + 1]),
+            voxel_at([x, y + 1, z + 1]),
+            voxel_at([x - 1, y + 1, z + 1]),
         ]),
         _ => unreachable!(),
     }
@@ -1,5 +1,6 @@
 use bevy::{
     asset::load_internal_asset,
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
     pbr::ExtendedMaterial,
     prelude::*,
     render::{
@@ -9,13 +10,20 @@ use bevy::{
 };
 
 use crate::{
-    configuration::{DefaultWorld, VoxelWorldConfig},
+    configuration::{ChunkMaterialConfig, DefaultWorld, VoxelTextureMode, VoxelWorldConfig},
+    decoration_instancing::DecorationInstancingPlugin,
     voxel_material::{
-        prepare_texture, LoadingTexture, StandardVoxelMaterial, TextureLayers,
-        VOXEL_TEXTURE_SHADER_HANDLE,
+        apply_global_tint, prepare_texture, LoadingTexture, StandardVoxelMaterial, TextureLayers,
+        WorldMaterialHandle, VOXEL_TEXTURE_SHADER_HANDLE,
     },
+    voxel_metadata::ChunkMetadataWillDespawn,
+    voxel_preview::{
+        finish_voxel_previews, mesh_voxel_models, render_voxel_previews, NextPreviewLayer,
+    },
+    voxel_tasks::{run_voxel_tasks, VoxelTaskFinished, VoxelTaskResult},
     voxel_world::*,
-    voxel_world_internal::Internals,
+    voxel_world_internal::{FixedRegion, Internals},
+    world_pass::{run_world_passes, WorldPassFinished, WorldPassProgress},
 };
 
 #[derive(Resource)]
@@ -33,7 +41,17 @@ where
     spawn_meshes: bool,
     use_custom_material: bool,
     config: C,
-    material: M,
+    material: PluginMaterial<C, M>,
+    streaming_schedule: InternedScheduleLabel,
+    fixed_region: Option<(IVec3, IVec3)>,
+}
+
+/// Where the plugin gets a custom material from
+enum PluginMaterial<C, M> {
+    Value(M),
+    FromConfig(fn(&C, &mut World) -> M),
+    /// Supplied later by the app, through `VoxelWorldMaterialHandle`
+    Deferred,
 }
 
 impl<C> VoxelWorldPlugin<C, StandardMaterial>
@@ -45,7 +63,9 @@ where
             config,
             spawn_meshes: true,
             use_custom_material: false,
-            material: StandardMaterial::default(),
+            material: PluginMaterial::Value(StandardMaterial::default()),
+            streaming_schedule: PreUpdate.intern(),
+            fixed_region: None,
         }
     }
 
@@ -54,7 +74,9 @@ where
             spawn_meshes: false,
             use_custom_material: false,
             config: C::default(),
-            material: StandardMaterial::default(),
+            material: PluginMaterial::Value(StandardMaterial::default()),
+            streaming_schedule: PreUpdate.intern(),
+            fixed_region: None,
         }
     }
 }
@@ -78,7 +100,63 @@ where
             spawn_meshes: self.spawn_meshes,
             use_custom_material: true,
             config: self.config,
-            material,
+            material: PluginMaterial::Value(material),
+            streaming_schedule: self.streaming_schedule,
+            fixed_region: self.fixed_region,
+        }
+    }
+
+    /// Don't create any material for the chunks, neither the built-in one nor a custom one. The
+    /// built-in material, its texture and its material plugin are not set up at all. Chunks are
+    /// meshed as usual, but not drawn until the app inserts a
+    /// `VoxelWorldMaterialHandle<CustomMaterial>` resource, which then applies to every chunk,
+    /// including the ones meshed before it. Use this when the material depends on something that
+    /// isn't ready when the plugin is built, like a texture that is still loading. The material
+    /// plugin of `CustomMaterial` has to be added by the app.
+    pub fn without_default_material<CustomMaterial: Material>(
+        self,
+    ) -> VoxelWorldPlugin<C, CustomMaterial> {
+        VoxelWorldPlugin {
+            spawn_meshes: self.spawn_meshes,
+            use_custom_material: true,
+            config: self.config,
+            material: PluginMaterial::Deferred,
+            streaming_schedule: self.streaming_schedule,
+            fixed_region: self.fixed_region,
+        }
+    }
+
+    /// Run the systems that spawn, generate and despawn chunks and apply voxel edits in the given
+    /// schedule, instead of `PreUpdate`. With `FixedUpdate`, a server-side world streams in step
+    /// with the simulation, while the meshes of a client world are still inserted in `Update`.
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.streaming_schedule = schedule.intern();
+        self
+    }
+
+    /// Spawn the chunks between `min_chunk` and `max_chunk`, inclusive, and nothing else. The
+    /// chunks are spawned whether a camera is near them or not, and stay spawned, which suits
+    /// structure editors and prefab design screens that show a small world with full rendering.
+    /// `set_voxel` outside of the region is rejected, like outside of `world_bounds`.
+    pub fn with_fixed_region(mut self, min_chunk: IVec3, max_chunk: IVec3) -> Self {
+        self.fixed_region = Some((min_chunk, max_chunk));
+        self
+    }
+}
+
+impl<C> VoxelWorldPlugin<C, C::ChunkMaterial>
+where
+    C: ChunkMaterialConfig,
+{
+    /// Use the material type and material from the `ChunkMaterialConfig` of the world.
+    pub fn with_chunk_material(config: C) -> Self {
+        Self {
+            config,
+            spawn_meshes: true,
+            use_custom_material: true,
+            material: PluginMaterial::FromConfig(|config, world| config.chunk_material(world)),
+            streaming_schedule: PreUpdate.intern(),
+            fixed_region: None,
         }
     }
 }
@@ -89,7 +167,9 @@ impl Default for VoxelWorldPlugin<DefaultWorld, StandardMaterial> {
             spawn_meshes: true,
             use_custom_material: false,
             config: DefaultWorld,
-            material: StandardMaterial::default(),
+            material: PluginMaterial::Value(StandardMaterial::default()),
+            streaming_schedule: PreUpdate.intern(),
+            fixed_region: None,
         }
     }
 }
@@ -100,19 +180,37 @@ where
     M: Material,
 {
     fn build(&self, app: &mut App) {
+        if let Some((min_chunk, max_chunk)) = self.fixed_region {
+            app.insert_resource(FixedRegion::<C>::new(min_chunk, max_chunk));
+        }
+
         app.init_resource::<C>()
             .add_systems(PreStartup, Internals::<C>::setup)
             .add_systems(
-                PreUpdate,
+                self.streaming_schedule,
                 (
                     (
-                        (Internals::<C>::spawn_chunks, Internals::<C>::retire_chunks).chain(),
+                        Internals::<C>::update_spawning_distances,
+                        (
+                            Internals::<C>::preseed_chunks,
+                            Internals::<C>::update_spawn_focus,
+                            Internals::<C>::spawn_chunks,
+                            Internals::<C>::retire_chunks,
+                            Internals::<C>::update_chunk_lods,
+                        )
+                            .chain()
+                            .run_if(not(resource_exists::<FixedRegion<C>>)),
+                        Internals::<C>::spawn_fixed_region
+                            .run_if(resource_exists::<FixedRegion<C>>),
+                        Internals::<C>::spawn_generate_now_regions,
                         Internals::<C>::remesh_dirty_chunks,
                     )
                         .chain(),
                     (
-                        Internals::<C>::flush_voxel_write_buffer,
+                        Internals::<C>::flush_voxel_write_buffer.in_set(VoxelWorldSet::FlushWrites),
+                        Internals::<C>::invalidate_chunks,
                         Internals::<C>::despawn_retired_chunks,
+                        Internals::<C>::despawn_empty_regions,
                         (
                             Internals::<C>::flush_chunk_map_buffers,
                             Internals::<C>::flush_mesh_cache_buffers,
@@ -121,9 +219,47 @@ where
                         .chain(),
                 ),
             )
+            .add_systems(
+                PostUpdate,
+                (
+                    Internals::<C>::track_ready_chunks,
+                    Internals::<C>::track_generated_area,
+                )
+                    .chain()
+                    .before(TransformSystem::TransformPropagate),
+            )
+            .add_systems(
+                Last,
+                (
+                    Internals::<C>::track_first_chunks,
+                    Internals::<C>::track_explored_chunks,
+                    run_voxel_tasks::<C>,
+                    run_world_passes::<C>,
+                    Internals::<C>::save_modified_chunks,
+                ),
+            )
             .add_event::<ChunkWillSpawn<C>>()
             .add_event::<ChunkWillDespawn<C>>()
-            .add_event::<ChunkWillRemesh<C>>();
+            .add_event::<ChunkMetadataWillDespawn<C>>()
+            .add_event::<ChunkWillRemesh<C>>()
+            .add_event::<ChunkMeshReady<C>>()
+            .add_event::<ChunkGenerationFailed<C>>()
+            .add_event::<ChunkModified<C>>()
+            .add_event::<ChunkExplored<C>>()
+            .add_event::<VoxelTaskResult<C>>()
+            .add_event::<VoxelTaskFinished<C>>()
+            .add_event::<WorldPassProgress<C>>()
+            .add_event::<WorldPassFinished<C>>()
+            .add_event::<FirstChunksReady<C>>()
+            .add_event::<CameraLeftGeneratedArea<C>>()
+            .add_event::<CameraEnteredGeneratedArea<C>>();
+
+        #[cfg(feature = "bevy_rapier3d")]
+        app.add_systems(
+            PostUpdate,
+            crate::chunk_collider::sync_rapier_freeze::<C>
+                .after(Internals::<C>::track_generated_area),
+        );
 
         // Spawning of meshes is optional, mainly to simplify testing.
         // This makes voxel_world work with a MinimalPlugins setup.
@@ -135,7 +271,42 @@ where
                 Shader::from_wgsl
             );
 
-            app.add_systems(Update, Internals::<C>::spawn_meshes);
+            if !app.is_plugin_added::<DecorationInstancingPlugin>() {
+                app.add_plugins(DecorationInstancingPlugin);
+            }
+
+            app.add_systems(
+                Update,
+                (
+                    Internals::<C>::spawn_chunk_placeholders,
+                    Internals::<C>::spawn_meshes,
+                    Internals::<C>::relight_chunks,
+                    Internals::<C>::spawn_relit_meshes,
+                    Internals::<C>::build_decoration_instances,
+                    Internals::<C>::update_chunk_decals,
+                    Internals::<C>::animate_chunk_spawns,
+                )
+                    .chain(),
+            )
+            .add_systems(Last, Internals::<C>::save_mesh_cache);
+
+            app.init_resource::<NextPreviewLayer<C>>()
+                .add_systems(
+                    Update,
+                    (render_voxel_previews::<C>, mesh_voxel_models::<C>)
+                        .before(Internals::<C>::spawn_meshes),
+                )
+                .add_systems(
+                    Update,
+                    finish_voxel_previews::<C>.after(Internals::<C>::spawn_meshes),
+                );
+
+            #[cfg(feature = "bevy_rapier3d")]
+            app.add_systems(
+                Update,
+                crate::chunk_collider::sync_rapier_colliders::<C>
+                    .after(Internals::<C>::spawn_meshes),
+            );
         }
 
         if !self.use_custom_material && self.spawn_meshes {
@@ -151,9 +322,19 @@ where
             let mut preloaded_texture = true;
             let texture_conf = self.config.voxel_texture();
             let mut texture_layers = 0;
+            // An atlas is a single image, which is used as an array texture with one layer
+            let atlas = matches!(
+                self.config.voxel_texture_mode(),
+                VoxelTextureMode::Atlas { .. }
+            );
 
-            // Use built-in default texture if no texture is specified.
-            let image_handle = if texture_conf.is_none() {
+            let image_handle = if let Some((img_path, layers)) = texture_conf {
+                texture_layers = if atlas { 1 } else { layers };
+                let asset_server = app.world().get_resource::<AssetServer>().unwrap();
+                preloaded_texture = false;
+                asset_server.load(img_path)
+            } else {
+                // Use built-in default texture if no texture is specified.
                 let mut image = Image::from_buffer(
                     include_bytes!("shaders/default_texture.png"),
                     ImageType::MimeType("image/png"),
@@ -163,15 +344,9 @@ where
                     RenderAssetUsages::default(),
                 )
                 .unwrap();
-                image.reinterpret_stacked_2d_as_array(4);
+                image.reinterpret_stacked_2d_as_array(if atlas { 1 } else { 4 });
                 let mut image_assets = app.world_mut().resource_mut::<Assets<Image>>();
                 image_assets.add(image)
-            } else {
-                let (img_path, layers) = texture_conf.unwrap();
-                texture_layers = layers;
-                let asset_server = app.world().get_resource::<AssetServer>().unwrap();
-                preloaded_texture = false;
-                asset_server.load(img_path)
             };
 
             let mut material_assets = app
@@ -179,15 +354,18 @@ where
                 .resource_mut::<Assets<ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>>>(
                 );
 
+            let surface = self.config.voxel_material_surface();
             let mat_handle = material_assets.add(ExtendedMaterial {
                 base: StandardMaterial {
-                    reflectance: 0.05,
-                    metallic: 0.05,
-                    perceptual_roughness: 0.95,
+                    reflectance: surface.reflectance,
+                    metallic: surface.metallic,
+                    perceptual_roughness: surface.perceptual_roughness,
                     ..default()
                 },
                 extension: StandardVoxelMaterial {
                     voxels_texture: image_handle.clone(),
+                    tint: LinearRgba::WHITE,
+                    atlas,
                 },
             });
 
@@ -195,34 +373,69 @@ where
                 is_loaded: preloaded_texture,
                 handle: image_handle,
             });
+            app.insert_resource(WorldMaterialHandle::<C, _>::new(mat_handle.clone()));
             app.insert_resource(VoxelWorldMaterialHandle { handle: mat_handle });
             app.insert_resource(TextureLayers(texture_layers));
 
             app.insert_resource(self.config.clone());
 
-            app.add_systems(Update, prepare_texture);
+            app.add_systems(Update, (prepare_texture, apply_global_tint::<C>));
 
             app.add_systems(
                 Update,
                 Internals::<C>::assign_material::<
                     ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>,
-                >,
+                >
+                    .after(Internals::<C>::spawn_meshes),
+            );
+
+            app.add_systems(
+                Update,
+                Internals::<C>::assign_group_materials.after(
+                    Internals::<C>::assign_material::<
+                        ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>,
+                    >,
+                ),
+            );
+
+            app.add_systems(
+                Update,
+                Internals::<C>::fade_chunk_spawns
+                    .after(Internals::<C>::animate_chunk_spawns)
+                    .after(
+                        Internals::<C>::assign_material::<
+                            ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>,
+                        >,
+                    ),
             );
         }
 
         if self.use_custom_material {
             if self.config.init_custom_materials() {
-                let mut custom_material_assets = app.world_mut().resource_mut::<Assets<M>>();
-                let handle = custom_material_assets.add(self.material.clone());
-                app.insert_resource(VoxelWorldMaterialHandle { handle });
+                let material = match &self.material {
+                    PluginMaterial::Value(material) => Some(material.clone()),
+                    PluginMaterial::FromConfig(chunk_material) => {
+                        Some(chunk_material(&self.config, app.world_mut()))
+                    }
+                    PluginMaterial::Deferred => None,
+                };
+                if let Some(material) = material {
+                    let mut custom_material_assets = app.world_mut().resource_mut::<Assets<M>>();
+                    let handle = custom_material_assets.add(material);
+                    app.insert_resource(VoxelWorldMaterialHandle { handle });
+                }
             }
 
             app.insert_resource(LoadingTexture {
                 is_loaded: true,
                 handle: Handle::default(),
             });
+            app.insert_resource(self.config.clone());
 
-            app.add_systems(Update, Internals::<C>::assign_material::<M>);
+            app.add_systems(
+                Update,
+                Internals::<C>::assign_material::<M>.after(Internals::<C>::spawn_meshes),
+            );
         }
     }
 }
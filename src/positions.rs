@@ -0,0 +1,83 @@
+///
+/// Typed positions
+/// Newtypes for chunk and voxel positions. Methods of `VoxelWorld` that take a position accept
+/// the matching type as well as a plain `IVec3`, so keeping positions in these types turns
+/// passing a voxel position where a chunk position is expected into a compile error.
+///
+use bevy::prelude::*;
+
+use crate::chunk::CHUNK_SIZE_I;
+
+/// The position of a chunk, in chunks
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Deref)]
+pub struct ChunkPos(pub IVec3);
+
+/// The position of a voxel, in voxels
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Deref)]
+pub struct VoxelPos(pub IVec3);
+
+impl ChunkPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self(IVec3::new(x, y, z))
+    }
+
+    /// The lowest voxel in the chunk, inclusive
+    pub fn min_voxel(self) -> VoxelPos {
+        VoxelPos(self.0 * CHUNK_SIZE_I)
+    }
+
+    /// The highest voxel in the chunk, inclusive
+    pub fn max_voxel(self) -> VoxelPos {
+        VoxelPos(self.0 * CHUNK_SIZE_I + IVec3::splat(CHUNK_SIZE_I - 1))
+    }
+
+    /// Whether a voxel is within the chunk
+    pub fn contains(self, voxel: VoxelPos) -> bool {
+        voxel.chunk() == self
+    }
+}
+
+impl VoxelPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self(IVec3::new(x, y, z))
+    }
+
+    /// The voxel containing a world space position, for a world with the given voxel size
+    pub fn from_world(position: Vec3, voxel_size: f32) -> Self {
+        Self((position / voxel_size).floor().as_ivec3())
+    }
+
+    /// The chunk the voxel is in
+    pub fn chunk(self) -> ChunkPos {
+        ChunkPos(self.0.div_euclid(IVec3::splat(CHUNK_SIZE_I)))
+    }
+
+    /// The position of the voxel within its chunk, from `0` to `CHUNK_SIZE - 1` on each axis
+    pub fn local(self) -> UVec3 {
+        self.0.rem_euclid(IVec3::splat(CHUNK_SIZE_I)).as_uvec3()
+    }
+}
+
+impl From<IVec3> for ChunkPos {
+    fn from(position: IVec3) -> Self {
+        Self(position)
+    }
+}
+
+impl From<ChunkPos> for IVec3 {
+    fn from(position: ChunkPos) -> Self {
+        position.0
+    }
+}
+
+impl From<IVec3> for VoxelPos {
+    fn from(position: IVec3) -> Self {
+        Self(position)
+    }
+}
+
+impl From<VoxelPos> for IVec3 {
+    fn from(position: VoxelPos) -> Self {
+        position.0
+    }
+}
@@ -0,0 +1,69 @@
+#import bevy_pbr::mesh_view_bindings::{view, lights}
+#import bevy_core_pipeline::tonemapping::tone_mapping
+
+const PI: f32 = 3.141592653589793;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+#ifdef VERTEX_NORMALS
+    @location(1) normal: vec3<f32>,
+#endif
+#ifdef VERTEX_COLORS
+    @location(5) color: vec4<f32>,
+#endif
+    // The rows of the world transform of the instance
+    @location(8) i_row_x: vec4<f32>,
+    @location(9) i_row_y: vec4<f32>,
+    @location(10) i_row_z: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_normal: vec3<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let local_position = vec4<f32>(vertex.position, 1.0);
+    let world_position = vec3<f32>(
+        dot(vertex.i_row_x, local_position),
+        dot(vertex.i_row_y, local_position),
+        dot(vertex.i_row_z, local_position),
+    );
+
+    var out: VertexOutput;
+    out.clip_position = view.clip_from_world * vec4<f32>(world_position, 1.0);
+#ifdef VERTEX_NORMALS
+    out.world_normal = normalize(vec3<f32>(
+        dot(vertex.i_row_x.xyz, vertex.normal),
+        dot(vertex.i_row_y.xyz, vertex.normal),
+        dot(vertex.i_row_z.xyz, vertex.normal),
+    ));
+#else
+    out.world_normal = vec3<f32>(0.0, 1.0, 0.0);
+#endif
+#ifdef VERTEX_COLORS
+    out.color = vertex.color;
+#else
+    out.color = vec4<f32>(1.0);
+#endif
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Lambert shading from the ambient light and the directional lights of the scene
+    let normal = normalize(in.world_normal);
+    var light = lights.ambient_color.rgb;
+    for (var i = 0u; i < lights.n_directional_lights; i = i + 1u) {
+        let directional = lights.directional_lights[i];
+        light += directional.color.rgb * max(dot(normal, directional.direction_to_light), 0.0) / PI;
+    }
+
+    var color = vec4<f32>(in.color.rgb * light * view.exposure, in.color.a);
+#ifdef TONEMAP_IN_SHADER
+    color = tone_mapping(color, view.color_grading);
+#endif
+    return color;
+}
@@ -24,17 +24,26 @@ var mat_array_texture: texture_2d_array<f32>;
 @group(2) @binding(101)
 var mat_array_texture_sampler: sampler;
 
+@group(2) @binding(102)
+var<uniform> voxel_tint: vec4<f32>;
+
 struct Vertex {
     @builtin(instance_index) instance_index: u32,
 #ifdef VERTEX_POSITIONS
     @location(0) position: vec3<f32>,
 #endif
+#ifdef VOXEL_COMPACT_VERTICES
+    // Snorm8x4 normals and half precision texture coordinates
+    @location(1) normal: vec4<f32>,
+    @location(2) uv: vec2<u32>,
+#else
 #ifdef VERTEX_NORMALS
     @location(1) normal: vec3<f32>,
 #endif
 #ifdef VERTEX_UVS
     @location(2) uv: vec2<f32>,
 #endif
+#endif
 #ifdef VERTEX_UVS_B
     @location(3) uv_b: vec2<f32>,
 #endif
@@ -52,7 +61,10 @@ struct Vertex {
     @builtin(vertex_index) index: u32,
 #endif
 
-    @location(8) tex_idx: vec3<u32>
+    @location(8) tex_idx: vec3<u32>,
+#ifdef VOXEL_LIGHT
+    @location(9) voxel_light: vec2<f32>,
+#endif
 };
 
 struct CustomVertexOutput {
@@ -76,6 +88,9 @@ struct CustomVertexOutput {
 #endif
 
     @location(8) tex_idx: vec3<u32>,
+#ifdef VOXEL_LIGHT
+    @location(9) voxel_light: vec2<f32>,
+#endif
 }
 
 @vertex
@@ -83,8 +98,13 @@ fn vertex(vertex: Vertex) -> CustomVertexOutput {
     var out: CustomVertexOutput;
     var model =  mesh_functions::get_world_from_local(vertex.instance_index);
 
+#ifdef VOXEL_COMPACT_VERTICES
+    let normal = vertex.normal.xyz;
+#else
+    let normal = vertex.normal;
+#endif
     out.world_normal = mesh_functions::mesh_normal_local_to_world(
-        vertex.normal, vertex.instance_index);
+        normal, vertex.instance_index);
 
     out.world_position = mesh_functions::mesh_position_local_to_world(
         model, vec4<f32>(vertex.position, 1.0));
@@ -92,8 +112,12 @@ fn vertex(vertex: Vertex) -> CustomVertexOutput {
     out.position = position_world_to_clip(out.world_position.xyz);
         
 #ifdef VERTEX_UVS
+#ifdef VOXEL_COMPACT_VERTICES
+    out.uv = unpack2x16float(vertex.uv.x | (vertex.uv.y << 16u));
+#else
     out.uv = vertex.uv;
 #endif
+#endif
 
 #ifdef VERTEX_TANGENTS
     out.world_tangent = mesh_functions::mesh_tangent_local_to_world(
@@ -111,6 +135,10 @@ fn vertex(vertex: Vertex) -> CustomVertexOutput {
 
     out.tex_idx = vertex.tex_idx;
 
+#ifdef VOXEL_LIGHT
+    out.voxel_light = vertex.voxel_light;
+#endif
+
     return out;
 }
 
@@ -128,6 +156,9 @@ fn fragment(
     standard_in.instance_index = in.instance_index;
     var pbr_input = pbr_input_from_standard_material(standard_in, is_front);
 
+    // Keep the alpha of the material, used for fading chunks in
+    let material_alpha = pbr_input.material.base_color.a;
+
     var tex_face = 0;
 
     // determine texture index based on normal
@@ -137,8 +168,45 @@ fn fragment(
         tex_face = 2;
     }
 
-    pbr_input.material.base_color = textureSample(mat_array_texture, mat_array_texture_sampler, in.uv, in.tex_idx[tex_face]);
+#ifdef VOXEL_TEXTURE_ATLAS
+    // The UVs already point into the tile of the atlas, which is the only layer of the texture
+    pbr_input.material.base_color = textureSample(
+        mat_array_texture,
+        mat_array_texture_sampler,
+        in.uv,
+        0,
+    );
+#else
+    // Greedy meshing produces quads spanning several voxels, with UVs beyond 1.0. Wrap them so the
+    // texture repeats, using the unwrapped UV gradients to avoid seams at the wrap.
+    pbr_input.material.base_color = textureSampleGrad(
+        mat_array_texture,
+        mat_array_texture_sampler,
+        fract(in.uv),
+        in.tex_idx[tex_face],
+        dpdx(in.uv),
+        dpdy(in.uv),
+    );
+#endif
     pbr_input.material.base_color = pbr_input.material.base_color * in.color;
+    // The tint of the world, for things like day and night color grading
+    pbr_input.material.base_color = vec4<f32>(
+        pbr_input.material.base_color.rgb * voxel_tint.rgb,
+        pbr_input.material.base_color.a,
+    );
+    pbr_input.material.base_color.a = pbr_input.material.base_color.a * material_alpha;
+
+#ifdef VOXEL_LIGHT
+    // Block light glows on its own, while scene lighting only reaches as far as the sky light
+    pbr_input.material.emissive = vec4<f32>(
+        pbr_input.material.emissive.rgb + pbr_input.material.base_color.rgb * in.voxel_light.x,
+        pbr_input.material.emissive.a,
+    );
+    pbr_input.material.base_color = vec4<f32>(
+        pbr_input.material.base_color.rgb * in.voxel_light.y,
+        pbr_input.material.base_color.a,
+    );
+#endif
 
     pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
 
@@ -5,9 +5,11 @@ use crate::mesh_cache::MeshCacheInsertBuffer;
 use crate::prelude::*;
 use crate::voxel_traversal::voxel_line_traversal;
 use crate::{
-    chunk::{ChunkData, FillType},
+    chunk::{ChunkData, ChunkThread, FillType, NeedsRemesh},
     prelude::VoxelWorldCamera,
+    voxel_material::LoadingTexture,
     voxel_world::*,
+    voxel_world_internal::Internals,
 };
 
 fn _test_setup_app() -> App {
@@ -26,6 +28,46 @@ fn _test_setup_app() -> App {
     app
 }
 
+/// An app that meshes the chunks of `C` without a renderer, as if the voxel texture had loaded
+fn _mesh_test_app<C: VoxelWorldConfig>() -> App {
+    _mesh_test_app_with(VoxelWorldPlugin::<C>::minimal())
+}
+
+/// Like `_mesh_test_app`, with a plugin that was set up further
+fn _mesh_test_app_with<C: VoxelWorldConfig>(plugin: VoxelWorldPlugin<C>) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, plugin))
+        .init_resource::<Assets<Mesh>>()
+        .insert_resource(LoadingTexture {
+            is_loaded: true,
+            handle: Handle::default(),
+        })
+        .add_systems(Update, Internals::<C>::spawn_meshes);
+    app
+}
+
+/// Update the app until `done` returns true, giving the chunk tasks a moment to run between
+/// frames. Returns false if that doesn't happen within a couple of seconds.
+fn _update_until(app: &mut App, mut done: impl FnMut(&mut App) -> bool) -> bool {
+    for _ in 0..2000 {
+        app.update();
+        if done(app) {
+            return true;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    false
+}
+
+/// Whether chunks of `C` are still waiting to be meshed or being generated
+fn _is_meshing<C: VoxelWorldConfig>(app: &mut App) -> bool {
+    app.world_mut()
+        .query_filtered::<(), Or<(With<NeedsRemesh>, With<ChunkThread<C, C::MaterialIndex>>)>>()
+        .iter(app.world())
+        .next()
+        .is_some()
+}
+
 #[test]
 fn can_set_get_voxels() {
     let mut app = _test_setup_app();
@@ -54,6 +96,98 @@ fn can_set_get_voxels() {
     app.update();
 }
 
+#[test]
+fn get_voxel_reads_the_last_write_of_the_frame() {
+    #[derive(Resource, Default)]
+    struct Observed(Vec<WorldVoxel>);
+
+    let mut app = _test_setup_app();
+    let position = IVec3::new(3, 4, 5);
+
+    let write = move |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        let before = voxel_world.get_voxel(position);
+        let lookup_before = voxel_world.get_voxel_fn();
+        voxel_world.set_voxel(position, WorldVoxel::Solid(1));
+        voxel_world.set_voxel(position, WorldVoxel::Solid(2));
+        assert_eq!(voxel_world.get_voxel(position), WorldVoxel::Solid(2));
+        assert_eq!(voxel_world.get_voxel_fn()(position), WorldVoxel::Solid(2));
+        // Closures made before keep seeing the voxels from when they were made
+        assert_eq!(lookup_before(position), before);
+        assert_eq!(
+            voxel_world
+                .get_chunk(IVec3::ZERO)
+                .and_then(|chunk| chunk.get(position)),
+            Some(WorldVoxel::Solid(2))
+        );
+    };
+    let read = move |voxel_world: VoxelWorld<DefaultWorld>, mut observed: ResMut<Observed>| {
+        observed.0.push(voxel_world.get_voxel(position));
+    };
+    app.init_resource::<Observed>()
+        .add_systems(Update, (write.run_if(run_once()), read).chain());
+
+    // A later system sees the write in the same frame, and it stays once it is flushed
+    for _ in 0..3 {
+        app.update();
+    }
+    assert_eq!(
+        app.world().resource::<Observed>().0,
+        vec![WorldVoxel::Solid(2); 3]
+    );
+}
+
+#[test]
+fn flushed_writes_are_seen_by_earlier_lookups() {
+    use std::sync::Arc;
+
+    type LookupFn = Arc<dyn Fn(IVec3) -> WorldVoxel + Send + Sync>;
+
+    #[derive(Resource, Default)]
+    struct Observed {
+        lookup: Option<LookupFn>,
+        voxel: Option<WorldVoxel>,
+        modified_chunks: usize,
+    }
+
+    let mut app = _test_setup_app();
+    let position = IVec3::new(3, 4, 5);
+
+    let write = move |mut voxel_world: VoxelWorld<DefaultWorld>, mut observed: ResMut<Observed>| {
+        observed.lookup = Some(voxel_world.get_voxel_fn());
+        voxel_world.set_voxel(position, WorldVoxel::Solid(7));
+    };
+    let read = move |mut observed: ResMut<Observed>| {
+        observed.voxel = observed.lookup.as_ref().map(|lookup| lookup(position));
+    };
+    let count_modified = |mut events: EventReader<ChunkModified<DefaultWorld>>,
+                          mut observed: ResMut<Observed>| {
+        observed.modified_chunks += events.read().count();
+    };
+    app.init_resource::<Observed>().add_systems(
+        Update,
+        (
+            write,
+            flush_voxel_writes::<DefaultWorld>,
+            read,
+            count_modified,
+        )
+            .chain()
+            .run_if(run_once()),
+    );
+    app.add_systems(Last, count_modified);
+
+    app.update();
+    assert_eq!(
+        app.world().resource::<Observed>().voxel,
+        Some(WorldVoxel::Solid(7))
+    );
+
+    // The chunk is marked as modified once, when the streaming systems run
+    app.update();
+    app.update();
+    assert_eq!(app.world().resource::<Observed>().modified_chunks, 1);
+}
+
 #[test]
 fn set_voxel_can_be_found_by_2d_coordinate() {
     let mut app = _test_setup_app();
@@ -145,6 +279,353 @@ fn chunk_will_remesh_event_after_set_voxel() {
     app.update();
 }
 
+#[test]
+fn fill_box_and_sphere_set_voxels_in_bulk() {
+    use crate::voxel_world_internal::ModifiedVoxels;
+
+    let mut app = _test_setup_app();
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.fill_box(IVec3::splat(2), IVec3::splat(-2), WorldVoxel::Solid(1));
+        voxel_world.fill_sphere(IVec3::ZERO, 2, WorldVoxel::Solid(2));
+    });
+
+    // The write buffer gets flushed the frame after
+    app.update();
+    app.update();
+
+    let modified_voxels = app
+        .world()
+        .resource::<ModifiedVoxels<DefaultWorld, u8>>()
+        .clone();
+    let modified_voxels = modified_voxels.read().unwrap();
+    assert_eq!(modified_voxels.len(), 5 * 5 * 5);
+    assert_eq!(modified_voxels[&IVec3::splat(2)], WorldVoxel::Solid(1));
+    assert_eq!(modified_voxels[&IVec3::new(2, 2, 0)], WorldVoxel::Solid(1));
+    assert_eq!(modified_voxels[&IVec3::new(0, -2, 0)], WorldVoxel::Solid(2));
+    assert_eq!(modified_voxels[&IVec3::splat(-1)], WorldVoxel::Solid(2));
+}
+
+#[test]
+fn stamp_places_rotated_models_across_chunks() {
+    use crate::voxel_world_internal::ModifiedVoxels;
+
+    let mut app = _test_setup_app();
+
+    // A 3 voxel long beam along x, with air above its far end, anchored at its near end
+    let mut model = VoxelModel::new(UVec3::new(3, 2, 1));
+    for x in 0..3 {
+        model.set(IVec3::new(x, 0, 0), WorldVoxel::Solid(1));
+    }
+    model.set(IVec3::new(2, 1, 0), WorldVoxel::Air);
+    assert_eq!(model.voxels().count(), 4);
+
+    app.add_systems(Update, move |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.stamp(&model, IVec3::new(31, 0, 0), ModelRotation::None);
+        voxel_world.stamp(&model, IVec3::new(0, 10, 0), ModelRotation::Quarter);
+    });
+
+    app.update();
+    app.update();
+
+    let modified_voxels = app
+        .world()
+        .resource::<ModifiedVoxels<DefaultWorld, u8>>()
+        .clone();
+    let modified_voxels = modified_voxels.read().unwrap();
+    assert_eq!(modified_voxels.len(), 8);
+
+    // Crosses into the next chunk
+    assert_eq!(modified_voxels[&IVec3::new(33, 0, 0)], WorldVoxel::Solid(1));
+    assert_eq!(modified_voxels[&IVec3::new(33, 1, 0)], WorldVoxel::Air);
+
+    // A quarter turn points the beam along -z
+    assert_eq!(
+        modified_voxels[&IVec3::new(0, 10, -2)],
+        WorldVoxel::Solid(1)
+    );
+    assert_eq!(modified_voxels[&IVec3::new(0, 11, -2)], WorldVoxel::Air);
+    assert!(!modified_voxels.contains_key(&IVec3::new(0, 11, 0)));
+}
+
+#[cfg(feature = "generators")]
+#[test]
+fn fixed_noise_is_stable() {
+    use crate::generators::*;
+
+    let seed = derive_seed(1234, "height");
+    assert_eq!(seed, 6833734780725001731);
+    let noise = FractalNoise::new(seed, 64, 4);
+    let samples =
+        [(0, 0), (17, -5), (-300, 1000), (i32::MAX, i32::MIN)].map(|(x, z)| noise.sample_2d(x, z));
+    assert_eq!(samples, [27935, 23986, 17113, 25705]);
+    assert!(samples.iter().all(|sample| (0..FIXED_ONE).contains(sample)));
+    assert_eq!(noise.sample_3d(5, -7, 9), 28895);
+    assert_eq!(noise.height(100, 200, -16, 48), 25);
+
+    // Lattice points of a single octave are the hashed values, halfway between they are blended
+    let value_noise = ValueNoise::new(seed, 8);
+    assert_eq!(
+        value_noise.sample_2d(16, 8),
+        (hash_2d(seed, 2, 1) >> 48) as i32
+    );
+
+    let voxels = (0..4).map(|x| (IVec3::new(x, 0, 0), WorldVoxel::Solid(x as u8)));
+    assert_eq!(voxel_checksum(voxels.clone()), 10331834598098516165);
+    assert_ne!(voxel_checksum(voxels.clone()), voxel_checksum(voxels.rev()));
+}
+
+#[cfg(feature = "vox")]
+#[test]
+fn vox_files_load_as_models() {
+    let chunk = |id: &[u8; 4], content: &[u8], children: &[u8]| {
+        let mut bytes = id.to_vec();
+        bytes.extend((content.len() as u32).to_le_bytes());
+        bytes.extend((children.len() as u32).to_le_bytes());
+        bytes.extend(content);
+        bytes.extend(children);
+        bytes
+    };
+    let size = [2u32, 3, 4].map(u32::to_le_bytes).concat();
+    let mut xyzi = 2u32.to_le_bytes().to_vec();
+    xyzi.extend([1, 0, 3, 7]);
+    xyzi.extend([0, 2, 0, 9]);
+    let children = [chunk(b"SIZE", &size, &[]), chunk(b"XYZI", &xyzi, &[])].concat();
+
+    let mut bytes = b"VOX ".to_vec();
+    bytes.extend(150u32.to_le_bytes());
+    bytes.extend(chunk(b"MAIN", &[], &children));
+
+    // Palette index 9 is not mapped to a material
+    let model = VoxelModel::from_vox_bytes(&bytes, |index| (index == 7).then_some(index)).unwrap();
+
+    // z-up becomes y-up
+    assert_eq!(model.size(), UVec3::new(2, 4, 3));
+    assert_eq!(model.get(IVec3::new(1, 3, 2)), WorldVoxel::Solid(7));
+    assert_eq!(model.voxels().count(), 1);
+
+    assert!(VoxelModel::<u8>::from_vox_bytes(b"nope", Some).is_err());
+
+    // Sizes that are too large to allocate are rejected rather than tried
+    let huge_size = [u32::MAX; 3].map(u32::to_le_bytes).concat();
+    let children = [
+        chunk(b"SIZE", &huge_size, &[]),
+        chunk(b"XYZI", &0u32.to_le_bytes(), &[]),
+    ]
+    .concat();
+    let mut bytes = b"VOX ".to_vec();
+    bytes.extend(150u32.to_le_bytes());
+    bytes.extend(chunk(b"MAIN", &[], &children));
+    let error = VoxelModel::<u8>::from_vox_bytes(&bytes, Some).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[cfg(feature = "vox")]
+#[test]
+fn vox_files_split_into_parts() {
+    let chunk = |id: &[u8; 4], content: &[u8], children: &[u8]| {
+        let mut bytes = id.to_vec();
+        bytes.extend((content.len() as u32).to_le_bytes());
+        bytes.extend((children.len() as u32).to_le_bytes());
+        bytes.extend(content);
+        bytes.extend(children);
+        bytes
+    };
+    let string = |s: &str| {
+        [
+            (s.len() as u32).to_le_bytes().to_vec(),
+            s.as_bytes().to_vec(),
+        ]
+        .concat()
+    };
+    let dict = |pairs: &[(&str, &str)]| {
+        let mut bytes = (pairs.len() as u32).to_le_bytes().to_vec();
+        for (key, value) in pairs {
+            bytes.extend(string(key));
+            bytes.extend(string(value));
+        }
+        bytes
+    };
+    let ints = |values: &[i32]| {
+        values
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>()
+    };
+    let transform = |id: i32, name: &str, child: i32, layer: i32, t: &str| {
+        let name = if name.is_empty() {
+            vec![]
+        } else {
+            vec![("_name", name)]
+        };
+        [
+            ints(&[id]),
+            dict(&name),
+            ints(&[child, -1, layer, 1]),
+            dict(&[("_t", t)]),
+        ]
+        .concat()
+    };
+    let shape =
+        |id: i32, model: i32| [ints(&[id]), dict(&[]), ints(&[1, model]), dict(&[])].concat();
+    let layer = |id: i32, name: &str| [ints(&[id]), dict(&[("_name", name)]), ints(&[-1])].concat();
+
+    let children = [
+        chunk(b"SIZE", &ints(&[2, 2, 2]), &[]),
+        chunk(
+            b"XYZI",
+            &[ints(&[2]), vec![0, 0, 0, 1, 1, 1, 1, 1]].concat(),
+            &[],
+        ),
+        chunk(b"SIZE", &ints(&[1, 1, 1]), &[]),
+        chunk(b"XYZI", &[ints(&[1]), vec![0, 0, 0, 2]].concat(), &[]),
+        chunk(b"nTRN", &transform(0, "", 1, -1, "0 0 0"), &[]),
+        chunk(
+            b"nGRP",
+            &[ints(&[1]), dict(&[]), ints(&[2, 2, 4])].concat(),
+            &[],
+        ),
+        chunk(b"nTRN", &transform(2, "body", 3, 0, "0 0 0"), &[]),
+        chunk(b"nSHP", &shape(3, 0), &[]),
+        chunk(b"nTRN", &transform(4, "head", 5, 0, "0 0 5"), &[]),
+        chunk(b"nSHP", &shape(5, 1), &[]),
+        chunk(b"LAYR", &layer(0, "character"), &[]),
+    ]
+    .concat();
+    let mut bytes = b"VOX ".to_vec();
+    bytes.extend(150u32.to_le_bytes());
+    bytes.extend(chunk(b"MAIN", &[], &children));
+
+    let parts = VoxelModel::parts_from_vox_bytes(&bytes, Some, VoxSplit::Objects).unwrap();
+    let names: Vec<_> = parts.iter().map(|part| part.name.as_str()).collect();
+    assert_eq!(names, ["body", "head"]);
+
+    // Objects are placed by their center, and z-up becomes y-up
+    let body = &parts[0];
+    assert_eq!(body.model.size(), UVec3::splat(2));
+    assert_eq!(body.model.get(IVec3::new(0, 0, 1)), WorldVoxel::Solid(1));
+    assert_eq!(body.model.get(IVec3::new(1, 1, 0)), WorldVoxel::Solid(1));
+    assert_eq!(body.pivot, Vec3::ONE);
+    assert_eq!(body.translation, Vec3::new(0.0, 0.0, 1.0));
+
+    let head = &parts[1];
+    assert_eq!(head.model.size(), UVec3::ONE);
+    assert_eq!(head.model.get(IVec3::ZERO), WorldVoxel::Solid(2));
+    assert_eq!(head.translation, Vec3::new(0.5, 5.5, 0.5));
+
+    // Splitting by layer merges the objects of the layer
+    let parts = VoxelModel::parts_from_vox_bytes(&bytes, Some, VoxSplit::Layers).unwrap();
+    assert_eq!(parts.len(), 1);
+    assert_eq!(parts[0].name, "character");
+    assert_eq!(parts[0].model.size(), UVec3::new(2, 7, 2));
+    assert_eq!(parts[0].model.voxels().count(), 3);
+
+    // Broken lengths and far away objects are rejected before anything is allocated for them
+    let file = |children: Vec<u8>| {
+        let mut bytes = b"VOX ".to_vec();
+        bytes.extend(150u32.to_le_bytes());
+        bytes.extend(chunk(b"MAIN", &[], &children));
+        bytes
+    };
+    let models = [
+        chunk(b"SIZE", &ints(&[1, 1, 1]), &[]),
+        chunk(b"XYZI", &[ints(&[1]), vec![0, 0, 0, 2]].concat(), &[]),
+    ]
+    .concat();
+    let far_away = file(
+        [
+            models.clone(),
+            chunk(b"nTRN", &transform(0, "", 1, -1, "0 0 2000000000"), &[]),
+            chunk(b"nSHP", &shape(1, 0), &[]),
+        ]
+        .concat(),
+    );
+    let error = VoxelModel::parts_from_vox_bytes(&far_away, Some, VoxSplit::Objects).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+    let long_name = file(
+        [
+            models,
+            chunk(
+                b"LAYR",
+                &[ints(&[0, 1, u32::MAX as i32]), b"_name".to_vec()].concat(),
+                &[],
+            ),
+        ]
+        .concat(),
+    );
+    let error = VoxelModel::parts_from_vox_bytes(&long_name, Some, VoxSplit::Layers).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn typed_positions_convert_between_chunks_and_voxels() {
+    let voxel = VoxelPos::new(-1, CHUNK_SIZE_I, 5);
+    assert_eq!(voxel.chunk(), ChunkPos::new(-1, 1, 0));
+    assert_eq!(voxel.local(), UVec3::new(CHUNK_SIZE_U - 1, 0, 5));
+    assert!(voxel.chunk().contains(voxel));
+    assert_eq!(
+        voxel.chunk().min_voxel(),
+        VoxelPos::new(-CHUNK_SIZE_I, CHUNK_SIZE_I, 0)
+    );
+    assert_eq!(
+        VoxelPos::from_world(Vec3::new(-0.5, 1.5, 2.0), 0.5),
+        VoxelPos::new(-1, 3, 4)
+    );
+
+    // The world takes typed positions as well as plain vectors
+    let mut app = _test_setup_app();
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxel(VoxelPos::new(1, 2, 3), WorldVoxel::Solid(4));
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(1, 2, 3)),
+            WorldVoxel::Solid(4)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(VoxelPos::new(1, 2, 3)),
+            WorldVoxel::Solid(4)
+        );
+        assert!(voxel_world.get_chunk(ChunkPos::new(100, 0, 0)).is_none());
+        assert_eq!(IVec3::from(ChunkPos::new(1, 2, 3)), IVec3::new(1, 2, 3));
+    });
+    app.update();
+}
+
+#[test]
+fn chunk_modified_event_once_per_chunk() {
+    let mut app = _test_setup_app();
+
+    for _ in 0..100 {
+        app.update();
+    }
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxels((-3..3).map(|x| (IVec3::new(x, 0, 0), WorldVoxel::Solid(1))));
+    });
+
+    app.update();
+
+    app.add_systems(
+        Update,
+        |mut ev_chunk_modified: EventReader<ChunkModified<DefaultWorld>>,
+         mut ev_chunk_will_spawn: EventReader<ChunkWillSpawn<DefaultWorld>>| {
+            let mut chunk_keys: Vec<IVec3> = ev_chunk_modified
+                .read()
+                .map(|event| event.chunk_key)
+                .collect();
+            chunk_keys.sort_by_key(|key| key.x);
+            assert_eq!(chunk_keys, vec![IVec3::NEG_X, IVec3::ZERO]);
+
+            // Each kind of chunk event has its own queue
+            assert!(ev_chunk_will_spawn
+                .read()
+                .all(|event| event.chunk_key != IVec3::NEG_X && event.chunk_key != IVec3::ZERO));
+        },
+    );
+
+    app.update();
+}
+
 #[test]
 fn chunk_will_despawn_event() {
     let mut app = _test_setup_app();
@@ -176,6 +657,7 @@ fn chunk_will_despawn_event() {
 }
 
 #[test]
+#[allow(clippy::type_complexity)]
 fn raycast_finds_voxel() {
     let mut app = _test_setup_app();
 
@@ -208,12 +690,13 @@ fn raycast_finds_voxel() {
                 IVec3::new(0, 0, 0),
                 ChunkData {
                     position: IVec3::new(0, 0, 0),
-                    voxels: Some(std::sync::Arc::new([WorldVoxel::Unset; 39304])),
+                    voxels: Some(std::sync::Arc::new([WorldVoxel::Unset; 39304].into())),
                     voxels_hash: 0,
                     is_full: false,
                     is_empty: false,
                     fill_type: FillType::Mixed,
                     entity: Entity::PLACEHOLDER,
+                    shape: Default::default(),
                 },
                 ChunkWillSpawn::<DefaultWorld>::new(IVec3::new(0, 0, 0), Entity::PLACEHOLDER),
             ));
@@ -275,7 +758,7 @@ fn visit_voxel_check(
     test_state: &mut VisitVoxelTestState,
     voxel_coords: IVec3,
     time: f32,
-    face: VoxelFace,
+    face: Option<VoxelFace>,
 ) -> bool {
     // println!(
     //     "Traversed {:?} at {} through {:?}",
@@ -297,8 +780,17 @@ fn visit_voxel_check(
         "{}: Infinite loop detected (bc. such a simple trace should be much faster than 1s)",
         test_state.test_name
     );
+    assert_eq!(
+        face.is_none(),
+        test_state.path_step_index == 0,
+        "{}: Expected no face for the start voxel only",
+        test_state.test_name
+    );
     assert!(
-        test_state.path_step_index == 0 || test_state.expected_face.unwrap_or(face) == face,
+        face.is_none()
+            || test_state
+                .expected_face
+                .is_none_or(|expected| face == Some(expected)),
         "{}: Expected entering through {:?}",
         test_state.test_name,
         test_state.expected_face
@@ -480,3 +972,6582 @@ fn voxel_line_traversal_ending_on_voxel_boundary() {
         test_state.test_name
     );
 }
+
+#[derive(Resource, Clone, Default)]
+struct PreseedWorld;
+
+impl VoxelWorldConfig for PreseedWorld {
+    type MaterialIndex = u8;
+
+    fn startup_preseed_radius(&self) -> u32 {
+        1
+    }
+}
+
+#[test]
+fn chunk_generator_fills_whole_chunks() {
+    use bevy::ecs::system::RunSystemOnce;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static GENERATED_CHUNKS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Resource, Clone, Default)]
+    struct GeneratorWorld;
+
+    impl VoxelWorldConfig for GeneratorWorld {
+        type MaterialIndex = u8;
+
+        fn startup_preseed_radius(&self) -> u32 {
+            1
+        }
+
+        fn chunk_generator(&self) -> Option<ChunkGeneratorFn<Self::MaterialIndex>> {
+            Some(std::sync::Arc::new(|buffer: &mut ChunkBuffer<u8>| {
+                GENERATED_CHUNKS.fetch_add(1, Ordering::Relaxed);
+                let columns: Vec<IVec2> = buffer.columns().collect();
+                assert_eq!(columns.len(), 34 * 34);
+                for column in columns {
+                    buffer.fill_column(column, column.x.rem_euclid(4), WorldVoxel::Solid(1));
+                }
+            }))
+        }
+    }
+
+    let mut app = _mesh_test_app::<GeneratorWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<GeneratorWorld>::default(),
+        ));
+    });
+
+    // Pre-seeded chunks are generated on the second frame, and added to the chunk map on the third
+    for _ in 0..3 {
+        app.update();
+    }
+
+    // At most once per chunk, not once per voxel
+    let chunk_count = app
+        .world_mut()
+        .query::<&Chunk<GeneratorWorld>>()
+        .iter(app.world())
+        .count();
+    let generated_chunks = GENERATED_CHUNKS.load(Ordering::Relaxed);
+    assert!(generated_chunks > 0 && generated_chunks <= chunk_count);
+
+    app.world_mut()
+        .run_system_once(|voxel_world: VoxelWorld<GeneratorWorld>| {
+            assert_eq!(
+                voxel_world.get_voxel(IVec3::new(2, 1, 5)),
+                WorldVoxel::Solid(1)
+            );
+            assert_eq!(voxel_world.get_voxel(IVec3::new(2, 2, 5)), WorldVoxel::Air);
+            assert_eq!(
+                voxel_world.get_voxel(IVec3::new(-1, 2, 5)),
+                WorldVoxel::Solid(1)
+            );
+        });
+}
+
+#[test]
+fn whole_chunks_can_be_read_and_modified() {
+    use crate::voxel_world_internal::VoxelWriteBuffer;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Resource, Clone, Default)]
+    struct ChunkRefWorld;
+
+    impl VoxelWorldConfig for ChunkRefWorld {
+        type MaterialIndex = u8;
+
+        fn startup_preseed_radius(&self) -> u32 {
+            1
+        }
+
+        fn chunk_generator(&self) -> Option<ChunkGeneratorFn<Self::MaterialIndex>> {
+            Some(std::sync::Arc::new(|buffer: &mut ChunkBuffer<u8>| {
+                for column in buffer.columns().collect::<Vec<_>>() {
+                    buffer.fill_column(column, 4, WorldVoxel::Solid(1));
+                }
+            }))
+        }
+    }
+
+    let mut app = _mesh_test_app::<ChunkRefWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<ChunkRefWorld>::default(),
+        ));
+    });
+
+    for _ in 0..3 {
+        app.update();
+    }
+
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<ChunkRefWorld>| {
+            assert!(voxel_world.get_chunk(IVec3::splat(100)).is_none());
+
+            // Pending edits are included
+            voxel_world.set_voxel(IVec3::new(1, 8, 1), WorldVoxel::Solid(3));
+            let chunk = voxel_world.get_chunk(IVec3::ZERO).unwrap();
+            assert_eq!(chunk.min(), IVec3::ZERO);
+            assert_eq!(chunk.max(), IVec3::splat(31));
+            assert_eq!(chunk.iter().count(), 32 * 32 * 32);
+            assert_eq!(chunk.get(IVec3::new(2, 3, 5)), Some(WorldVoxel::Solid(1)));
+            assert_eq!(chunk.get(IVec3::new(2, 4, 5)), Some(WorldVoxel::Air));
+            assert_eq!(chunk.get(IVec3::new(1, 8, 1)), Some(WorldVoxel::Solid(3)));
+            assert_eq!(chunk.get(IVec3::new(-1, 3, 5)), None);
+
+            // Let the floating voxel fall down onto the ground
+            assert!(voxel_world.modify_chunk(IVec3::ZERO, |chunk| {
+                let falling: Vec<IVec3> = chunk
+                    .iter()
+                    .filter(|(position, voxel)| {
+                        *voxel == WorldVoxel::Solid(3)
+                            && chunk.get(*position - IVec3::Y) == Some(WorldVoxel::Air)
+                    })
+                    .map(|(position, _)| position)
+                    .collect();
+                for position in falling {
+                    chunk.set(position, WorldVoxel::Air);
+                    chunk.set(position - IVec3::Y, WorldVoxel::Solid(3));
+                }
+            }));
+            assert!(!voxel_world.modify_chunk(IVec3::splat(100), |_| unreachable!()));
+        });
+
+    // Only the changed voxels are written
+    assert_eq!(
+        app.world()
+            .resource::<VoxelWriteBuffer<ChunkRefWorld, u8>>()
+            .len(),
+        3
+    );
+    app.update();
+
+    app.world_mut()
+        .run_system_once(|voxel_world: VoxelWorld<ChunkRefWorld>| {
+            assert_eq!(voxel_world.get_voxel(IVec3::new(1, 8, 1)), WorldVoxel::Air);
+            assert_eq!(
+                voxel_world.get_voxel(IVec3::new(1, 7, 1)),
+                WorldVoxel::Solid(3)
+            );
+        });
+}
+
+#[test]
+fn voxel_tasks_send_a_result_per_chunk() {
+    use bevy::ecs::system::RunSystemOnce;
+    use std::time::Duration;
+
+    #[derive(Resource, Clone, Default)]
+    struct TaskWorld;
+
+    impl VoxelWorldConfig for TaskWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            0
+        }
+
+        fn startup_preseed_radius(&self) -> u32 {
+            1
+        }
+
+        fn max_task_chunks_per_frame(&self) -> usize {
+            2
+        }
+
+        fn chunk_generator(&self) -> Option<ChunkGeneratorFn<Self::MaterialIndex>> {
+            Some(std::sync::Arc::new(|buffer: &mut ChunkBuffer<u8>| {
+                for column in buffer.columns().collect::<Vec<_>>() {
+                    buffer.fill_column(column, 4, WorldVoxel::Solid(1));
+                }
+            }))
+        }
+    }
+
+    let mut app = _mesh_test_app::<TaskWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<TaskWorld>::default(),
+        ));
+    });
+
+    for _ in 0..3 {
+        app.update();
+    }
+
+    // Count the solid voxels of the camera chunk and its six neighbors
+    let task = app
+        .world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<TaskWorld>| {
+            voxel_world.set_voxel(IVec3::new(0, 10, 0), WorldVoxel::Solid(2));
+            voxel_world.spawn_task(IVec3::splat(-1), IVec3::splat(1), |chunk| {
+                chunk
+                    .iter()
+                    .filter(|(_, voxel)| matches!(voxel, WorldVoxel::Solid(_)))
+                    .count()
+            })
+        });
+
+    let mut result_reader = app
+        .world()
+        .resource::<Events<VoxelTaskResult<TaskWorld>>>()
+        .get_reader();
+    let mut finished_reader = app
+        .world()
+        .resource::<Events<VoxelTaskFinished<TaskWorld>>>()
+        .get_reader();
+    let mut counts = bevy::utils::HashMap::new();
+    let mut finished = false;
+    let mut frames = 0;
+    while !finished && frames < 2000 {
+        app.update();
+        frames += 1;
+        let results = app.world().resource::<Events<VoxelTaskResult<TaskWorld>>>();
+        for result in result_reader.read(results) {
+            assert_eq!(result.task, task);
+            counts.insert(result.chunk_position, *result.get::<usize>().unwrap());
+        }
+        let finished_events = app
+            .world()
+            .resource::<Events<VoxelTaskFinished<TaskWorld>>>();
+        finished = finished_reader
+            .read(finished_events)
+            .any(|event| event.task == task);
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    // Only two chunks are started per frame
+    assert!(finished);
+    assert!(frames >= 4);
+    assert_eq!(counts.len(), 7);
+    assert_eq!(counts[&IVec3::ZERO], 32 * 32 * 4 + 1);
+    assert_eq!(counts[&IVec3::NEG_Y], 32 * 32 * 32);
+    assert_eq!(counts[&IVec3::Y], 0);
+}
+
+#[test]
+fn world_passes_iterate_over_a_region() {
+    use bevy::ecs::system::RunSystemOnce;
+    use std::time::Duration;
+
+    #[derive(Resource, Clone, Default)]
+    struct PassWorld;
+
+    impl VoxelWorldConfig for PassWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            0
+        }
+
+        fn startup_preseed_radius(&self) -> u32 {
+            1
+        }
+
+        fn chunk_generator(&self) -> Option<ChunkGeneratorFn<Self::MaterialIndex>> {
+            Some(std::sync::Arc::new(|buffer: &mut ChunkBuffer<u8>| {
+                for column in buffer.columns().collect::<Vec<_>>() {
+                    buffer.fill_column(column, 4, WorldVoxel::Solid(1));
+                }
+            }))
+        }
+    }
+
+    let mut app = _mesh_test_app::<PassWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<PassWorld>::default(),
+        ));
+    });
+
+    for _ in 0..3 {
+        app.update();
+    }
+
+    // Let a floating voxel settle by one voxel per iteration
+    let pass = app
+        .world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<PassWorld>| {
+            voxel_world.set_voxel(IVec3::new(0, 10, 0), WorldVoxel::Solid(2));
+            voxel_world.spawn_world_pass(IVec3::ZERO, IVec3::ZERO, 10, |read, write| {
+                for column in read.columns() {
+                    for y in read.min().y + 1..=read.max().y {
+                        let position = IVec3::new(column.x, y, column.y);
+                        let below = position - IVec3::Y;
+                        if read.get(position).is_solid() && read.get(below).is_air() {
+                            write.set(below, read.get(position));
+                            write.set(position, WorldVoxel::Air);
+                        }
+                    }
+                }
+            })
+        })
+        .unwrap();
+
+    // Regions too large to index are not started
+    assert!(app
+        .world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<PassWorld>| {
+            voxel_world.spawn_world_pass(IVec3::splat(-1000), IVec3::splat(1000), 1, |_, _| {})
+        })
+        .is_none());
+
+    let mut progress_reader = app
+        .world()
+        .resource::<Events<WorldPassProgress<PassWorld>>>()
+        .get_reader();
+    let mut finished_reader = app
+        .world()
+        .resource::<Events<WorldPassFinished<PassWorld>>>()
+        .get_reader();
+    let mut last_iteration = 0;
+    let mut changed_voxels = None;
+    let mut frames = 0;
+    while changed_voxels.is_none() && frames < 2000 {
+        app.update();
+        frames += 1;
+        let progress = app
+            .world()
+            .resource::<Events<WorldPassProgress<PassWorld>>>();
+        for event in progress_reader.read(progress) {
+            assert_eq!(event.pass, pass);
+            assert!(event.iteration > last_iteration);
+            assert_eq!(event.iterations, 10);
+            last_iteration = event.iteration;
+        }
+        let finished = app
+            .world()
+            .resource::<Events<WorldPassFinished<PassWorld>>>();
+        changed_voxels = finished_reader
+            .read(finished)
+            .find(|event| event.pass == pass)
+            .map(|event| event.changed_voxels);
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    assert_eq!(last_iteration, 10);
+    assert_eq!(changed_voxels, Some(2));
+
+    app.update();
+    let (top, settled) = app
+        .world_mut()
+        .run_system_once(|voxel_world: VoxelWorld<PassWorld>| {
+            (
+                voxel_world.get_voxel(IVec3::new(0, 10, 0)),
+                voxel_world.get_voxel(IVec3::new(0, 4, 0)),
+            )
+        });
+    assert_eq!(top, WorldVoxel::Air);
+    assert_eq!(settled, WorldVoxel::Solid(2));
+}
+
+#[test]
+fn invalidated_chunks_are_regenerated() {
+    use bevy::ecs::system::RunSystemOnce;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    static SURFACE_HEIGHT: AtomicI32 = AtomicI32::new(4);
+
+    #[derive(Resource, Clone, Default)]
+    struct RegenWorld;
+
+    impl VoxelWorldConfig for RegenWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            0
+        }
+
+        fn startup_preseed_radius(&self) -> u32 {
+            1
+        }
+
+        fn chunk_generator(&self) -> Option<ChunkGeneratorFn<Self::MaterialIndex>> {
+            Some(std::sync::Arc::new(|buffer: &mut ChunkBuffer<u8>| {
+                let height = SURFACE_HEIGHT.load(Ordering::Relaxed);
+                for column in buffer.columns().collect::<Vec<_>>() {
+                    buffer.fill_column(column, height, WorldVoxel::Solid(1));
+                }
+            }))
+        }
+    }
+
+    let mut app = _mesh_test_app::<RegenWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<RegenWorld>::default(),
+        ));
+    });
+
+    let get_voxel = |app: &mut App, position: IVec3| {
+        app.world_mut()
+            .run_system_once(move |voxel_world: VoxelWorld<RegenWorld>| {
+                voxel_world.get_voxel(position)
+            })
+    };
+    // Update until no chunk is being remeshed anymore
+    let update = |app: &mut App| {
+        _update_until(app, |app| !_is_meshing::<RegenWorld>(app));
+    };
+
+    app.update();
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<RegenWorld>| {
+            voxel_world.set_voxel(IVec3::new(0, 20, 0), WorldVoxel::Solid(2));
+        });
+    update(&mut app);
+    assert_eq!(get_voxel(&mut app, IVec3::new(0, 6, 0)), WorldVoxel::Air);
+
+    // Raise the terrain, but only invalidate the chunk above the camera chunk
+    SURFACE_HEIGHT.store(8, Ordering::Relaxed);
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<RegenWorld>| {
+            voxel_world.invalidate_region(IVec3::Y, IVec3::Y);
+        });
+    update(&mut app);
+    assert_eq!(get_voxel(&mut app, IVec3::new(0, 6, 0)), WorldVoxel::Air);
+
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<RegenWorld>| {
+            voxel_world.invalidate_all();
+        });
+    update(&mut app);
+    assert_eq!(
+        get_voxel(&mut app, IVec3::new(0, 6, 0)),
+        WorldVoxel::Solid(1)
+    );
+    assert_eq!(
+        get_voxel(&mut app, IVec3::new(0, 20, 0)),
+        WorldVoxel::Solid(2)
+    );
+}
+
+#[test]
+fn redundant_remeshes_are_merged() {
+    use bevy::ecs::system::RunSystemOnce;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    static SLOW: AtomicBool = AtomicBool::new(false);
+    static REMESHES: AtomicUsize = AtomicUsize::new(0);
+    static MESHES_READY: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Resource, Clone, Default)]
+    struct SlowWorld;
+
+    impl VoxelWorldConfig for SlowWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            0
+        }
+
+        fn startup_preseed_radius(&self) -> u32 {
+            1
+        }
+
+        fn chunk_generator(&self) -> Option<ChunkGeneratorFn<Self::MaterialIndex>> {
+            Some(std::sync::Arc::new(|buffer: &mut ChunkBuffer<u8>| {
+                if SLOW.load(Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_millis(200));
+                }
+                for column in buffer.columns().collect::<Vec<_>>() {
+                    buffer.fill_column(column, 4, WorldVoxel::Solid(1));
+                }
+            }))
+        }
+    }
+
+    let mut app = _mesh_test_app::<SlowWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<SlowWorld>::default(),
+        ));
+    })
+    .add_systems(
+        Last,
+        |mut ev_will_remesh: EventReader<ChunkWillRemesh<SlowWorld>>,
+         mut ev_mesh_ready: EventReader<ChunkMeshReady<SlowWorld>>| {
+            let is_origin = |chunk_key: IVec3| chunk_key == IVec3::ZERO;
+            REMESHES.fetch_add(
+                ev_will_remesh
+                    .read()
+                    .filter(|ev| is_origin(ev.chunk_key))
+                    .count(),
+                Ordering::Relaxed,
+            );
+            MESHES_READY.fetch_add(
+                ev_mesh_ready
+                    .read()
+                    .filter(|ev| is_origin(ev.chunk_key))
+                    .count(),
+                Ordering::Relaxed,
+            );
+        },
+    );
+
+    // Update until no chunk is being remeshed anymore
+    let update = |app: &mut App| {
+        _update_until(app, |app| !_is_meshing::<SlowWorld>(app));
+    };
+
+    update(&mut app);
+    REMESHES.store(0, Ordering::Relaxed);
+    MESHES_READY.store(0, Ordering::Relaxed);
+
+    // Edit the chunk every frame while its first remesh is still running
+    SLOW.store(true, Ordering::Relaxed);
+    for y in 5..10 {
+        app.world_mut()
+            .run_system_once(move |mut voxel_world: VoxelWorld<SlowWorld>| {
+                voxel_world.set_voxel(IVec3::new(5, y, 5), WorldVoxel::Solid(2));
+            });
+        app.update();
+    }
+    update(&mut app);
+
+    // One remesh for the first edit, and a single one more for all the edits after it
+    assert_eq!(REMESHES.load(Ordering::Relaxed), 2);
+    assert_eq!(MESHES_READY.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn voxel_lookup_fns_are_reused() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static BUILT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Resource, Clone, Default)]
+    struct ReuseWorld;
+
+    impl VoxelWorldConfig for ReuseWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            1
+        }
+
+        fn generation_task_budget(&self) -> usize {
+            1
+        }
+
+        fn reuse_voxel_lookup_fns(&self) -> bool {
+            true
+        }
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                BUILT.fetch_add(1, Ordering::Relaxed);
+                Box::new(|position| {
+                    if position.y < 4 {
+                        WorldVoxel::Solid(1)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = _mesh_test_app::<ReuseWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<ReuseWorld>::default(),
+        ));
+    });
+
+    app.update();
+    _update_until(&mut app, |app| !_is_meshing::<ReuseWorld>(app));
+
+    // With one chunk generated at a time, every chunk after the first reuses its lookup function
+    let chunks = app
+        .world_mut()
+        .query::<&Chunk<ReuseWorld>>()
+        .iter(app.world())
+        .count();
+    assert!(chunks > 1);
+    assert_eq!(BUILT.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn invalidating_appearance_remeshes_without_lookups() {
+    use crate::mesh_cache::MeshRef;
+    use crate::voxel_material::ATTRIBUTE_TEX_INDEX;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::render::mesh::VertexAttributeValues;
+    use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+    static LOOKUPS: AtomicUsize = AtomicUsize::new(0);
+    static TEXTURE: AtomicU32 = AtomicU32::new(1);
+
+    #[derive(Resource, Clone, Default)]
+    struct ReskinnedWorld;
+
+    impl VoxelWorldConfig for ReskinnedWorld {
+        type MaterialIndex = u8;
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|position| {
+                    LOOKUPS.fetch_add(1, Ordering::Relaxed);
+                    if position.y < 4 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+
+        fn texture_index_mapper(
+            &self,
+        ) -> std::sync::Arc<dyn Fn(Self::MaterialIndex) -> [u32; 3] + Send + Sync> {
+            std::sync::Arc::new(|_| [TEXTURE.load(Ordering::Relaxed); 3])
+        }
+    }
+
+    let mut app = _mesh_test_app_with(
+        VoxelWorldPlugin::<ReskinnedWorld>::minimal().with_fixed_region(IVec3::ZERO, IVec3::ZERO),
+    );
+
+    let update_until_meshed = |app: &mut App| {
+        let meshed = _update_until(app, |app| {
+            let has_mesh = app
+                .world_mut()
+                .query_filtered::<(), With<MeshRef>>()
+                .iter(app.world())
+                .next()
+                .is_some();
+            has_mesh && !_is_meshing::<ReskinnedWorld>(app)
+        });
+        assert!(meshed, "the chunk was never meshed");
+    };
+    let texture_indexes = |app: &mut App| {
+        let world = app.world_mut();
+        let mesh_ref = world.query::<&MeshRef>().single(world);
+        let mesh = world.resource::<Assets<Mesh>>().get(&*mesh_ref.0).unwrap();
+        let Some(VertexAttributeValues::Uint32x3(indexes)) = mesh.attribute(ATTRIBUTE_TEX_INDEX)
+        else {
+            panic!("the mesh has no texture indexes");
+        };
+        indexes.clone()
+    };
+
+    update_until_meshed(&mut app);
+    assert!(texture_indexes(&mut app)
+        .iter()
+        .all(|index| *index == [1; 3]));
+    let lookups = LOOKUPS.load(Ordering::Relaxed);
+
+    TEXTURE.store(2, Ordering::Relaxed);
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<ReskinnedWorld>| {
+            voxel_world.invalidate_appearance();
+        });
+    update_until_meshed(&mut app);
+
+    assert!(texture_indexes(&mut app)
+        .iter()
+        .all(|index| *index == [2; 3]));
+    assert_eq!(LOOKUPS.load(Ordering::Relaxed), lookups);
+}
+
+#[test]
+fn inserted_chunks_replace_the_generated_voxels() {
+    use crate::chunk_map::ChunkMap;
+    use crate::mesh_cache::MeshRef;
+    use bevy::ecs::system::RunSystemOnce;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static LOOKUPS_INSIDE: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Resource, Clone, Default)]
+    struct ReceivedWorld;
+
+    impl VoxelWorldConfig for ReceivedWorld {
+        type MaterialIndex = u8;
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|position| {
+                    if position.div_euclid(IVec3::splat(CHUNK_SIZE_I)) == IVec3::ZERO {
+                        LOOKUPS_INSIDE.fetch_add(1, Ordering::Relaxed);
+                    }
+                    if position.y < 4 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = _mesh_test_app_with(
+        VoxelWorldPlugin::<ReceivedWorld>::minimal().with_fixed_region(IVec3::ZERO, IVec3::ZERO),
+    );
+
+    let update_until_meshed = |app: &mut App| {
+        let meshed = _update_until(app, |app| {
+            let has_mesh = app
+                .world_mut()
+                .query_filtered::<(), With<MeshRef>>()
+                .iter(app.world())
+                .next()
+                .is_some();
+            has_mesh && !_is_meshing::<ReceivedWorld>(app)
+        });
+        assert!(meshed, "the chunk was never meshed");
+    };
+
+    update_until_meshed(&mut app);
+    let lookups = LOOKUPS_INSIDE.load(Ordering::Relaxed);
+    let position = IVec3::new(3, 10, 5);
+
+    app.world_mut()
+        .run_system_once(move |mut voxel_world: VoxelWorld<ReceivedWorld>| {
+            voxel_world.set_voxel(position, WorldVoxel::Solid(1));
+            let mut chunk = ChunkRef::filled(IVec3::ZERO, WorldVoxel::Solid(3));
+            chunk.set(IVec3::new(0, 31, 0), WorldVoxel::Air);
+            assert!(voxel_world.insert_chunk(IVec3::ZERO, ChunkData::from(chunk)));
+            assert!(!voxel_world.insert_chunk(
+                IVec3::ONE,
+                ChunkData::from(ChunkRef::filled(IVec3::ONE, WorldVoxel::Air))
+            ));
+
+            // The inserted voxels replace the write from before, and are read back right away
+            assert_eq!(voxel_world.get_voxel(position), WorldVoxel::Solid(3));
+            assert_eq!(voxel_world.get_voxel_fn()(position), WorldVoxel::Solid(3));
+            assert_eq!(
+                voxel_world
+                    .get_chunk(IVec3::ZERO)
+                    .and_then(|chunk| chunk.get(IVec3::new(0, 31, 0))),
+                Some(WorldVoxel::Air)
+            );
+        });
+    update_until_meshed(&mut app);
+    // The new voxels reach the chunk map at the start of the next frame
+    app.update();
+
+    let chunk_map = app.world().resource::<ChunkMap<ReceivedWorld, u8>>();
+    let chunk_data =
+        ChunkMap::<ReceivedWorld, u8>::get(&IVec3::ZERO, &chunk_map.get_read_lock()).unwrap();
+    assert_eq!(
+        chunk_data.get_voxel(position.as_uvec3() + 1),
+        WorldVoxel::Solid(3)
+    );
+    assert_eq!(chunk_data.get_voxel(UVec3::new(1, 32, 1)), WorldVoxel::Air);
+    assert_eq!(LOOKUPS_INSIDE.load(Ordering::Relaxed), lookups);
+}
+
+#[test]
+fn inserted_chunks_that_never_spawn_are_dropped_oldest_first() {
+    use crate::voxel_world_internal::InsertedChunks;
+
+    let mut inserted_chunks = InsertedChunks::<DefaultWorld, u8>::default();
+    for x in 0..3 {
+        inserted_chunks.insert(ChunkRef::filled(IVec3::new(x, 0, 0), WorldVoxel::Solid(1)));
+    }
+    let snapshot = inserted_chunks.snapshot();
+
+    // The spawned chunk is not counted, and the oldest of the others goes
+    inserted_chunks.drop_unspawned(1, |chunk_position| chunk_position == IVec3::ZERO);
+    assert!(inserted_chunks.contains_key(&IVec3::ZERO));
+    assert!(!inserted_chunks.contains_key(&IVec3::X));
+    assert!(inserted_chunks.contains_key(&IVec3::new(2, 0, 0)));
+
+    // Snapshots handed out before are left as they were
+    assert_eq!(snapshot.len(), 3);
+}
+
+#[test]
+fn visible_chunks_are_the_chunks_in_the_view_frustum() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut app = _test_setup_app();
+    app.update();
+
+    app.world_mut()
+        .run_system_once(|voxel_world: VoxelWorld<DefaultWorld>| {
+            // A camera in the middle of chunk 0, looking down -Z
+            let transform = GlobalTransform::from_translation(Vec3::splat(16.0));
+            let chunks: Vec<IVec3> = voxel_world
+                .visible_chunks(&Projection::Perspective(default()), &transform)
+                .collect();
+
+            assert_eq!(chunks[0], IVec3::ZERO);
+            assert!(chunks
+                .windows(2)
+                .all(|pair| { pair[0].length_squared() <= pair[1].length_squared() }));
+            // The chunks around the camera are always in view, even behind it
+            assert!(chunks.contains(&IVec3::Z));
+            assert!(chunks.contains(&IVec3::new(0, 0, -5)));
+            assert!(chunks.contains(&IVec3::new(0, 0, -10)));
+            assert!(!chunks.contains(&IVec3::new(0, 0, -11)));
+            assert!(!chunks.contains(&IVec3::new(0, 0, 5)));
+            assert!(!chunks.contains(&IVec3::new(8, 0, -1)));
+
+            // Orthographic cameras see their view volume up to the spawning distance
+            let chunks: Vec<IVec3> = voxel_world
+                .visible_chunks(
+                    &Projection::Orthographic(OrthographicProjection {
+                        far: 32.0 * 20.0,
+                        ..default()
+                    }),
+                    &transform,
+                )
+                .collect();
+            assert!(chunks.contains(&IVec3::new(0, 0, -10)));
+            assert!(!chunks.contains(&IVec3::new(0, 0, -11)));
+            assert!(!chunks.contains(&IVec3::new(3, 0, -10)));
+        });
+}
+
+#[test]
+fn spawning_chunk_margin_replaces_the_ray_margin() {
+    use bevy::render::camera::Viewport;
+    use bevy::utils::HashSet;
+
+    // A ray margin many times the size of the viewport, which casts rays far to the sides
+    #[derive(Resource, Clone, Default)]
+    struct RayMarginWorld;
+
+    impl VoxelWorldConfig for RayMarginWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            6
+        }
+
+        fn spawning_rays(&self) -> usize {
+            1000
+        }
+
+        fn spawning_ray_margin(&self) -> u32 {
+            1000
+        }
+    }
+
+    #[derive(Resource, Clone, Default)]
+    struct ChunkMarginWorld;
+
+    impl VoxelWorldConfig for ChunkMarginWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            6
+        }
+
+        fn spawning_rays(&self) -> usize {
+            1000
+        }
+
+        fn spawning_ray_margin(&self) -> u32 {
+            1000
+        }
+
+        fn spawning_chunk_margin(&self) -> Option<u32> {
+            Some(1)
+        }
+    }
+
+    // The chunks spawned for a camera in the middle of chunk 0, looking down -Z through a square
+    // viewport of 100 pixels
+    fn spawned_chunks<C: VoxelWorldConfig>() -> HashSet<IVec3> {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<C>::minimal()));
+        app.world_mut().spawn((
+            Camera3dBundle {
+                camera: Camera {
+                    viewport: Some(Viewport {
+                        physical_size: UVec2::splat(100),
+                        ..default()
+                    }),
+                    ..default()
+                },
+                global_transform: GlobalTransform::from_translation(Vec3::splat(16.0)),
+                ..default()
+            },
+            VoxelWorldCamera::<C>::default(),
+        ));
+        for _ in 0..5 {
+            app.update();
+        }
+        app.world_mut()
+            .query::<&Chunk<C>>()
+            .iter(app.world())
+            .map(|chunk| chunk.position)
+            .collect()
+    }
+
+    // Without a chunk margin, the ray margin reaches chunks far to the sides of the frustum
+    let chunks = spawned_chunks::<RayMarginWorld>();
+    assert!(chunks.iter().any(|chunk| chunk.x.abs() >= 4));
+
+    // With a chunk margin, the rays stay in the frustum, 22.5 degrees to either side, which ends
+    // within chunks -2 to 2 at the spawning distance. The margin adds the chunks next to the ones
+    // the rays pass through, like (2, 0, -1) outside of the frustum next to (1, 0, -2) in it.
+    let chunks = spawned_chunks::<ChunkMarginWorld>();
+    assert!(chunks.contains(&IVec3::new(2, 0, -1)));
+    assert!(chunks.contains(&IVec3::new(-2, 0, -1)));
+    assert!(chunks
+        .iter()
+        .all(|chunk| chunk.x.abs() <= 3 && chunk.y.abs() <= 3));
+}
+
+#[test]
+fn padding_can_come_from_loaded_neighbors() {
+    use crate::mesh_cache::MeshRef;
+    use bevy::ecs::system::RunSystemOnce;
+
+    // A lookup that disagrees with itself: each chunk sees its own voxels as solid, and the
+    // voxels of its neighbors as air, like noise with internal randomness
+    fn lookup_delegate() -> VoxelLookupDelegate<u8> {
+        Box::new(|chunk_position| {
+            Box::new(move |position| {
+                if position.div_euclid(IVec3::splat(CHUNK_SIZE_I)) == chunk_position {
+                    WorldVoxel::Solid(0)
+                } else {
+                    WorldVoxel::Air
+                }
+            })
+        })
+    }
+
+    #[derive(Resource, Clone, Default)]
+    struct DelegatePadding;
+
+    impl VoxelWorldConfig for DelegatePadding {
+        type MaterialIndex = u8;
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            lookup_delegate()
+        }
+    }
+
+    #[derive(Resource, Clone, Default)]
+    struct NeighborPadding;
+
+    impl VoxelWorldConfig for NeighborPadding {
+        type MaterialIndex = u8;
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            lookup_delegate()
+        }
+
+        fn padding_source(&self) -> PaddingSource {
+            PaddingSource::LoadedNeighbors
+        }
+    }
+
+    // The vertices of the mesh of chunk 0, once both chunks are spawned and it has been meshed
+    // again with its neighbor around
+    fn border_vertices<W: VoxelWorldConfig<MaterialIndex = u8>>() -> usize {
+        let mut app = _mesh_test_app_with(
+            VoxelWorldPlugin::<W>::minimal().with_fixed_region(IVec3::ZERO, IVec3::X),
+        );
+
+        let update_until_meshed = |app: &mut App| {
+            let meshed = _update_until(app, |app| {
+                let meshed = app
+                    .world_mut()
+                    .query_filtered::<(), (With<Chunk<W>>, With<MeshRef>)>()
+                    .iter(app.world())
+                    .count();
+                meshed == 2 && !_is_meshing::<W>(app)
+            });
+            assert!(meshed, "the chunks were never meshed");
+        };
+
+        update_until_meshed(&mut app);
+        app.world_mut()
+            .run_system_once(|mut voxel_world: VoxelWorld<W>| voxel_world.invalidate_all());
+        update_until_meshed(&mut app);
+
+        let world = app.world_mut();
+        let mesh_ref = world
+            .query::<(&Chunk<W>, &MeshRef)>()
+            .iter(world)
+            .find(|(chunk, _)| chunk.position == IVec3::ZERO)
+            .map(|(_, mesh_ref)| mesh_ref.0.clone())
+            .unwrap();
+        world
+            .resource::<Assets<Mesh>>()
+            .get(&*mesh_ref)
+            .unwrap()
+            .count_vertices()
+    }
+
+    // Without the faces between the two chunks, one of the six sides of the chunk is left
+    let delegate = border_vertices::<DelegatePadding>();
+    let neighbors = border_vertices::<NeighborPadding>();
+    assert_eq!(delegate / 6 * 5, neighbors);
+}
+
+#[test]
+fn chunks_wait_for_a_material_supplied_later() {
+    use crate::mesh_cache::MeshRef;
+    use crate::plugin::VoxelWorldMaterialHandle;
+
+    #[derive(Resource, Clone, Default)]
+    struct DeferredWorld;
+
+    impl VoxelWorldConfig for DeferredWorld {
+        type MaterialIndex = u8;
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|pos| match pos.y {
+                    y if y < 4 => WorldVoxel::Solid(0),
+                    _ => WorldVoxel::Air,
+                })
+            })
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<DeferredWorld>::minimal()
+            .with_fixed_region(IVec3::ZERO, IVec3::ZERO)
+            .without_default_material::<StandardMaterial>(),
+    ))
+    .init_resource::<Assets<Mesh>>()
+    .init_resource::<Assets<StandardMaterial>>()
+    .add_systems(Update, Internals::<DeferredWorld>::spawn_meshes);
+
+    let material_count = |app: &mut App| {
+        app.world_mut()
+            .query_filtered::<(), (With<Chunk<DeferredWorld>>, With<Handle<StandardMaterial>>)>()
+            .iter(app.world())
+            .count()
+    };
+
+    _update_until(&mut app, |app| {
+        let meshed = app
+            .world_mut()
+            .query_filtered::<(), (With<Chunk<DeferredWorld>>, With<MeshRef>)>()
+            .iter(app.world())
+            .count();
+        meshed == 1 && !_is_meshing::<DeferredWorld>(app)
+    });
+
+    // No material is made up front, so the meshed chunk waits for one
+    app.update();
+    assert_eq!(app.world().resource::<Assets<StandardMaterial>>().len(), 0);
+    assert_eq!(material_count(&mut app), 0);
+
+    let handle = app
+        .world_mut()
+        .resource_mut::<Assets<StandardMaterial>>()
+        .add(StandardMaterial::default());
+    app.insert_resource(VoxelWorldMaterialHandle {
+        handle: handle.clone(),
+    });
+    app.update();
+    let world = app.world_mut();
+    let materials: Vec<_> = world
+        .query_filtered::<&Handle<StandardMaterial>, With<Chunk<DeferredWorld>>>()
+        .iter(world)
+        .collect();
+    assert_eq!(materials, [&handle]);
+}
+
+#[test]
+fn entities_are_frozen_until_their_chunk_is_generated() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Resource, Clone, Default)]
+    struct StreamingWorld;
+
+    impl VoxelWorldConfig for StreamingWorld {
+        type MaterialIndex = u8;
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|position| {
+                    if position.y < 4 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    #[derive(Component)]
+    struct Player;
+
+    let mut app = _mesh_test_app_with(
+        VoxelWorldPlugin::<StreamingWorld>::minimal().with_fixed_region(IVec3::ZERO, IVec3::ZERO),
+    );
+    app.add_plugins(TransformPlugin)
+        .add_systems(Startup, |mut commands: Commands| {
+            commands.spawn((
+                Camera3dBundle {
+                    transform: Transform::from_xyz(16.0, 16.0, 16.0),
+                    ..default()
+                },
+                VoxelWorldCamera::<StreamingWorld>::default(),
+            ));
+            commands.spawn((
+                Player,
+                SpatialBundle::from_transform(Transform::from_xyz(16.0, 16.0, 16.0)),
+                FreezeUntilGenerated::<StreamingWorld>::default(),
+            ));
+        })
+        // Gravity, a little bit every frame
+        .add_systems(
+            Update,
+            |mut players: Query<&mut Transform, With<Player>>| {
+                for mut transform in players.iter_mut() {
+                    transform.translation.y -= 0.01;
+                }
+            },
+        );
+
+    let player = |app: &mut App| {
+        let world = app.world_mut();
+        let (transform, freeze) = world
+            .query_filtered::<(&Transform, &FreezeUntilGenerated<StreamingWorld>), With<Player>>()
+            .single(world);
+        (transform.translation.y, freeze.is_frozen())
+    };
+    let is_ready = |app: &mut App| {
+        app.world_mut()
+            .run_system_once(|voxel_world: VoxelWorld<StreamingWorld>| {
+                voxel_world.is_position_ready(IVec3::splat(16))
+            })
+    };
+
+    // The chunk is not ready in the first frames, so the camera is outside of the generated area
+    // and the player is held in place
+    app.update();
+    let (frozen_y, frozen) = player(&mut app);
+    assert!(frozen);
+    app.update();
+    assert!(!is_ready(&mut app));
+    assert_eq!(player(&mut app), (frozen_y, true));
+    let left = app
+        .world()
+        .resource::<Events<CameraLeftGeneratedArea<StreamingWorld>>>()
+        .len();
+    assert_eq!(left, 1);
+
+    _update_until(&mut app, |app| {
+        !_is_meshing::<StreamingWorld>(app) && is_ready(app)
+    });
+    app.update();
+    app.update();
+
+    assert!(is_ready(&mut app));
+    let (y, frozen) = player(&mut app);
+    assert!(y < frozen_y && !frozen);
+    let entered = app
+        .world()
+        .resource::<Events<CameraEnteredGeneratedArea<StreamingWorld>>>()
+        .len();
+    assert_eq!(entered, 1);
+}
+
+#[test]
+fn despawned_chunks_are_not_generated_again() {
+    use bevy::ecs::system::RunSystemOnce;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    static GENERATED: AtomicUsize = AtomicUsize::new(0);
+    static FAR_AWAY: AtomicBool = AtomicBool::new(false);
+
+    #[derive(Resource, Clone, Default)]
+    struct CachedWorld;
+
+    impl VoxelWorldConfig for CachedWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            2
+        }
+
+        fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
+            ChunkDespawnStrategy::FarAway
+        }
+
+        fn despawned_chunk_cache_size(&self) -> usize {
+            64
+        }
+
+        fn chunk_generator(&self) -> Option<ChunkGeneratorFn<Self::MaterialIndex>> {
+            Some(std::sync::Arc::new(|buffer: &mut ChunkBuffer<u8>| {
+                if buffer.chunk_position() == IVec3::ZERO {
+                    GENERATED.fetch_add(1, Ordering::Relaxed);
+                }
+                for column in buffer.columns().collect::<Vec<_>>() {
+                    buffer.fill_column(column, 4, WorldVoxel::Solid(1));
+                }
+            }))
+        }
+    }
+
+    let mut app = _mesh_test_app::<CachedWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<CachedWorld>::default(),
+        ));
+    })
+    .add_systems(
+        First,
+        |mut query: Query<&mut GlobalTransform, With<VoxelWorldCamera<CachedWorld>>>| {
+            let x = if FAR_AWAY.load(Ordering::Relaxed) {
+                20.0 * 32.0
+            } else {
+                16.0
+            };
+            for mut transform in query.iter_mut() {
+                *transform = GlobalTransform::from_translation(Vec3::new(x, 16.0, 16.0));
+            }
+        },
+    );
+
+    // Update until the chunk at the origin is spawned or despawned, and no chunk is busy
+    let update_until = |app: &mut App, spawned: bool| {
+        let done = _update_until(app, |app| {
+            let despawning = app
+                .world_mut()
+                .query_filtered::<(), With<NeedsDespawn>>()
+                .iter(app.world())
+                .next()
+                .is_some();
+            let at_origin = app
+                .world_mut()
+                .query::<&Chunk<CachedWorld>>()
+                .iter(app.world())
+                .any(|chunk| chunk.position == IVec3::ZERO);
+            !_is_meshing::<CachedWorld>(app) && !despawning && at_origin == spawned
+        });
+        assert!(done, "the chunk at the origin was never spawned: {spawned}");
+    };
+
+    update_until(&mut app, true);
+    assert_eq!(GENERATED.load(Ordering::Relaxed), 1);
+
+    FAR_AWAY.store(true, Ordering::Relaxed);
+    update_until(&mut app, false);
+    FAR_AWAY.store(false, Ordering::Relaxed);
+    update_until(&mut app, true);
+
+    // The chunk came back from the cache, without running the generator again
+    assert_eq!(GENERATED.load(Ordering::Relaxed), 1);
+    let voxels = app
+        .world_mut()
+        .run_system_once(|voxel_world: VoxelWorld<CachedWorld>| {
+            [
+                voxel_world.get_voxel(IVec3::new(1, 3, 1)),
+                voxel_world.get_voxel(IVec3::new(1, 5, 1)),
+            ]
+        });
+    assert_eq!(voxels, [WorldVoxel::Solid(1), WorldVoxel::Air]);
+}
+
+#[test]
+fn chunk_data_handles_outlive_their_chunk() {
+    use bevy::ecs::system::RunSystemOnce;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static FAR_AWAY: AtomicBool = AtomicBool::new(false);
+
+    #[derive(Resource, Clone, Default)]
+    struct HandleWorld;
+
+    impl VoxelWorldConfig for HandleWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            1
+        }
+
+        fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
+            ChunkDespawnStrategy::FarAway
+        }
+
+        fn chunk_generator(&self) -> Option<ChunkGeneratorFn<Self::MaterialIndex>> {
+            Some(std::sync::Arc::new(|buffer: &mut ChunkBuffer<u8>| {
+                for column in buffer.columns().collect::<Vec<_>>() {
+                    buffer.fill_column(column, 4, WorldVoxel::Solid(1));
+                }
+            }))
+        }
+    }
+
+    let mut app = _mesh_test_app::<HandleWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<HandleWorld>::default(),
+        ));
+    })
+    .add_systems(
+        First,
+        |mut query: Query<&mut GlobalTransform, With<VoxelWorldCamera<HandleWorld>>>| {
+            let x = if FAR_AWAY.load(Ordering::Relaxed) {
+                20.0 * 32.0
+            } else {
+                16.0
+            };
+            for mut transform in query.iter_mut() {
+                *transform = GlobalTransform::from_translation(Vec3::new(x, 16.0, 16.0));
+            }
+        },
+    );
+
+    // Update until the chunk at the origin is spawned or despawned, and no chunk is busy
+    let update_until = |app: &mut App, spawned: bool| {
+        let done = _update_until(app, |app| {
+            let despawning = app
+                .world_mut()
+                .query_filtered::<(), With<NeedsDespawn>>()
+                .iter(app.world())
+                .next()
+                .is_some();
+            let at_origin = app
+                .world_mut()
+                .query::<&Chunk<HandleWorld>>()
+                .iter(app.world())
+                .any(|chunk| chunk.position == IVec3::ZERO);
+            !_is_meshing::<HandleWorld>(app) && !despawning && at_origin == spawned
+        });
+        assert!(done, "the chunk at the origin was never spawned: {spawned}");
+        // Finished chunks get into the chunk map the frame after
+        app.update();
+    };
+    let take_handle = |app: &mut App| {
+        app.world_mut()
+            .run_system_once(|voxel_world: VoxelWorld<HandleWorld>| {
+                voxel_world.chunk_data_handle(IVec3::ZERO).unwrap()
+            })
+    };
+
+    update_until(&mut app, true);
+    let handle = take_handle(&mut app);
+    assert!(!handle.is_stale());
+    assert_eq!(handle.get(IVec3::new(1, 3, 1)), Some(WorldVoxel::Solid(1)));
+    assert_eq!(handle.get(IVec3::new(-1, 3, 1)), None);
+    assert_eq!(handle.iter().count(), CHUNK_SIZE_U.pow(3) as usize);
+
+    // Editing the chunk regenerates it, while the handle keeps the voxels it was taken with
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<HandleWorld>| {
+            voxel_world.set_voxel(IVec3::new(1, 3, 1), WorldVoxel::Air);
+        });
+    update_until(&mut app, true);
+    assert!(handle.is_stale());
+    assert_eq!(handle.get(IVec3::new(1, 3, 1)), Some(WorldVoxel::Solid(1)));
+
+    let handle = take_handle(&mut app);
+    assert!(!handle.is_stale());
+    FAR_AWAY.store(true, Ordering::Relaxed);
+    update_until(&mut app, false);
+    assert!(handle.is_stale());
+    assert_eq!(handle.get(IVec3::new(1, 3, 1)), Some(WorldVoxel::Air));
+    assert_eq!(handle.get(IVec3::new(1, 2, 1)), Some(WorldVoxel::Solid(1)));
+}
+
+#[test]
+fn preseed_queues_chunks_around_camera() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<PreseedWorld>::minimal()));
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<PreseedWorld>::default(),
+        ));
+    });
+
+    app.update();
+
+    let preseeded = app
+        .world_mut()
+        .query_filtered::<&Chunk<PreseedWorld>, With<crate::chunk::PreseedChunk>>()
+        .iter(app.world())
+        .map(|chunk| chunk.position)
+        .collect::<Vec<_>>();
+
+    // A sphere with a radius of one chunk around the camera chunk
+    assert_eq!(preseeded.len(), 7);
+    assert!(preseeded.contains(&IVec3::ZERO));
+    assert!(preseeded.contains(&IVec3::NEG_Y));
+}
+
+#[test]
+fn preseeded_chunks_are_meshed_before_the_first_frame() {
+    use crate::mesh_cache::MeshRef;
+
+    #[derive(Resource, Clone, Default)]
+    struct PreseedTerrainWorld;
+
+    impl VoxelWorldConfig for PreseedTerrainWorld {
+        type MaterialIndex = u8;
+
+        fn startup_preseed_radius(&self) -> u32 {
+            1
+        }
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|pos| {
+                    if pos.y < 10 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = _mesh_test_app::<PreseedTerrainWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<PreseedTerrainWorld>::default(),
+        ));
+    });
+
+    // Rendering comes after the first update, so the surface chunks have to be meshed by then
+    app.update();
+
+    let mut meshed = app
+        .world_mut()
+        .query_filtered::<&Chunk<PreseedTerrainWorld>, With<MeshRef>>()
+        .iter(app.world())
+        .map(|chunk| chunk.position)
+        .collect::<Vec<_>>();
+    meshed.sort_by_key(|position| position.to_array());
+
+    // The chunks of the pre-seed sphere that the surface at y = 10 passes through
+    assert_eq!(
+        meshed,
+        vec![IVec3::NEG_X, IVec3::NEG_Z, IVec3::ZERO, IVec3::Z, IVec3::X,]
+    );
+}
+
+#[derive(Resource, Clone, Default)]
+struct TinyWorld;
+
+impl VoxelWorldConfig for TinyWorld {
+    type MaterialIndex = u8;
+
+    fn spawning_distance(&self) -> u32 {
+        0
+    }
+
+    fn max_spawn_per_frame(&self) -> usize {
+        0
+    }
+}
+
+#[test]
+fn camera_chunk_and_neighbors_always_spawn() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<TinyWorld>::minimal()));
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<TinyWorld>::default(),
+        ));
+    });
+    app.add_systems(
+        First,
+        |mut query: Query<&mut GlobalTransform, With<VoxelWorldCamera<TinyWorld>>>| {
+            for mut transform in query.iter_mut() {
+                *transform = GlobalTransform::from(Transform::from_xyz(-5.0, 40.0, -5.0));
+            }
+        },
+    );
+
+    for _ in 0..5 {
+        app.update();
+    }
+
+    let chunk_positions = app
+        .world_mut()
+        .query::<&Chunk<TinyWorld>>()
+        .iter(app.world())
+        .map(|chunk| chunk.position)
+        .collect::<Vec<_>>();
+
+    // The camera is in chunk (-1, 1, -1), and none of these get despawned even though they are
+    // further away than the spawning distance
+    let chunk_at_camera = IVec3::new(-1, 1, -1);
+    for offset in [
+        IVec3::ZERO,
+        IVec3::X,
+        IVec3::NEG_X,
+        IVec3::Y,
+        IVec3::NEG_Y,
+        IVec3::Z,
+        IVec3::NEG_Z,
+    ] {
+        assert!(chunk_positions.contains(&(chunk_at_camera + offset)));
+    }
+}
+
+#[derive(Resource, Clone, Default)]
+struct SmallVoxelWorld;
+
+impl VoxelWorldConfig for SmallVoxelWorld {
+    type MaterialIndex = u8;
+
+    fn spawning_distance(&self) -> u32 {
+        0
+    }
+
+    fn max_spawn_per_frame(&self) -> usize {
+        0
+    }
+
+    fn voxel_size(&self) -> f32 {
+        0.25
+    }
+}
+
+#[test]
+fn voxel_size_scales_the_world() {
+    use crate::voxel_world_internal::WorldRoot;
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<SmallVoxelWorld>::minimal(),
+    ));
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<SmallVoxelWorld>::default(),
+        ));
+    });
+    app.add_systems(
+        First,
+        |mut query: Query<&mut GlobalTransform, With<VoxelWorldCamera<SmallVoxelWorld>>>| {
+            for mut transform in query.iter_mut() {
+                *transform = GlobalTransform::from(Transform::from_xyz(8.5, 0.0, 0.0));
+            }
+        },
+    );
+    app.add_systems(Update, |voxel_world: VoxelWorld<SmallVoxelWorld>| {
+        assert_eq!(
+            voxel_world.world_to_voxel(Vec3::new(1.0, -0.1, 0.3)),
+            IVec3::new(4, -1, 1)
+        );
+        assert_eq!(
+            voxel_world.voxel_to_world(IVec3::new(4, -1, 1)),
+            Vec3::new(1.0, -0.25, 0.25)
+        );
+    });
+
+    for _ in 0..5 {
+        app.update();
+    }
+
+    let root_scale = app
+        .world_mut()
+        .query_filtered::<&Transform, With<WorldRoot<SmallVoxelWorld>>>()
+        .single(app.world())
+        .scale;
+    assert_eq!(root_scale, Vec3::splat(0.25));
+
+    // The camera is at voxel (34, 0, 0), which is in chunk (1, 0, 0)
+    let chunk_positions = app
+        .world_mut()
+        .query::<&Chunk<SmallVoxelWorld>>()
+        .iter(app.world())
+        .map(|chunk| chunk.position)
+        .collect::<Vec<_>>();
+    assert!(chunk_positions.contains(&IVec3::new(1, 0, 0)));
+    assert!(chunk_positions.contains(&IVec3::new(2, 0, 0)));
+}
+
+#[test]
+fn first_chunks_ready_once_camera_chunks_are_meshed() {
+    let mut app = _mesh_test_app::<TinyWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<TinyWorld>::default(),
+        ));
+    });
+
+    let mut reader = app
+        .world()
+        .resource::<Events<FirstChunksReady<TinyWorld>>>()
+        .get_reader();
+    let mut read_ready = |app: &App| {
+        let events = app
+            .world()
+            .resource::<Events<FirstChunksReady<TinyWorld>>>();
+        reader
+            .read(events)
+            .map(|event| {
+                (
+                    event.chunk_position,
+                    event.chunk_count,
+                    event.time_to_first_chunk,
+                    event.time_to_ready,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut ready = Vec::new();
+    _update_until(&mut app, |app| {
+        ready.extend(read_ready(app));
+        !ready.is_empty()
+    });
+
+    // The camera chunk and its six neighbors are within the default radius of 1
+    let (chunk_position, chunk_count, time_to_first_chunk, time_to_ready) =
+        *ready.first().expect("first chunks never got ready");
+    assert_eq!(chunk_position, IVec3::ZERO);
+    assert_eq!(chunk_count, 7);
+    assert!(time_to_first_chunk <= time_to_ready);
+
+    // Only sent once
+    for _ in 0..3 {
+        app.update();
+        assert!(read_ready(&app).is_empty());
+    }
+}
+
+#[test]
+fn explored_chunks_are_tracked_once() {
+    use bevy::ecs::system::RunSystemOnce;
+    use std::time::Duration;
+
+    let mut app = _mesh_test_app::<TinyWorld>();
+    app.init_resource::<ExploredChunks<TinyWorld>>()
+        .add_systems(Startup, |mut commands: Commands| {
+            commands.spawn((
+                Camera3dBundle::default(),
+                VoxelWorldCamera::<TinyWorld>::default(),
+            ));
+        });
+
+    let mut reader = app
+        .world()
+        .resource::<Events<ChunkExplored<TinyWorld>>>()
+        .get_reader();
+    let mut explored_events = Vec::new();
+    _update_until(&mut app, |app| {
+        let events = app.world().resource::<Events<ChunkExplored<TinyWorld>>>();
+        explored_events.extend(reader.read(events).map(|event| event.chunk_key));
+        explored_events.len() == 7
+    });
+
+    // The camera chunk and its six neighbors
+    let explored = app.world().resource::<ExploredChunks<TinyWorld>>();
+    assert_eq!(explored.len(), 7);
+    assert!(explored.contains(IVec3::ZERO));
+    assert!(explored_events
+        .iter()
+        .all(|&chunk_position| explored.contains(chunk_position)));
+
+    let restored = ExploredChunks::<TinyWorld>::from_bytes(&explored.to_bytes()).unwrap();
+    assert_eq!(restored.len(), 7);
+    assert!(explored.iter().all(|position| restored.contains(position)));
+    assert!(ExploredChunks::<TinyWorld>::from_bytes(&[0; 5]).is_none());
+
+    // Remeshing an explored chunk doesn't explore it again
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<TinyWorld>| {
+            voxel_world.set_voxel(IVec3::new(1, 1, 1), WorldVoxel::Solid(1));
+        });
+    for _ in 0..50 {
+        app.update();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    let events = app.world().resource::<Events<ChunkExplored<TinyWorld>>>();
+    assert_eq!(reader.read(events).count(), 0);
+    assert_eq!(app.world().resource::<ExploredChunks<TinyWorld>>().len(), 7);
+}
+
+#[derive(Resource, Clone, Default)]
+struct ShadowLodWorld;
+
+impl VoxelWorldConfig for ShadowLodWorld {
+    type MaterialIndex = u8;
+
+    fn spawning_distance(&self) -> u32 {
+        0
+    }
+
+    fn max_spawn_per_frame(&self) -> usize {
+        0
+    }
+
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+        // Bumpy ground, so that the decimated mesh has fewer faces
+        Box::new(|_| {
+            Box::new(|pos: IVec3| {
+                if pos.y < 8 + pos.x.rem_euclid(3) + pos.z.rem_euclid(5) {
+                    WorldVoxel::Solid(0)
+                } else {
+                    WorldVoxel::Air
+                }
+            })
+        })
+    }
+
+    fn shadow_lod(&self) -> Option<ShadowLod> {
+        Some(ShadowLod {
+            lod_level: 2,
+            render_layer: 3,
+        })
+    }
+}
+
+#[test]
+fn shadow_lod_casts_shadows_from_decimated_mesh() {
+    use crate::mesh_cache::MeshRef;
+    use bevy::pbr::NotShadowCaster;
+    use bevy::render::view::RenderLayers;
+
+    let mut app = _mesh_test_app::<ShadowLodWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<ShadowLodWorld>::default(),
+        ));
+    });
+
+    let mut shadow_mesh = None;
+    _update_until(&mut app, |app| {
+        shadow_mesh = app
+            .world_mut()
+            .query::<(
+                &ChunkShadowMesh<ShadowLodWorld>,
+                &MeshRef,
+                &RenderLayers,
+                &Parent,
+            )>()
+            .iter(app.world())
+            .find(|(shadow_mesh, ..)| shadow_mesh.chunk_position == IVec3::ZERO)
+            .map(|(shadow_mesh, mesh_ref, layers, parent)| {
+                (
+                    shadow_mesh.lod_level,
+                    (*mesh_ref.0).clone(),
+                    layers.clone(),
+                    parent.get(),
+                )
+            });
+        shadow_mesh.is_some()
+    });
+
+    let (lod_level, shadow_handle, layers, chunk_entity) =
+        shadow_mesh.expect("no shadow mesh was spawned");
+    assert_eq!(lod_level, 2);
+    assert_eq!(layers, RenderLayers::layer(3));
+
+    let chunk = app.world().entity(chunk_entity);
+    assert!(chunk.contains::<NotShadowCaster>());
+    let full_handle = (*chunk.get::<MeshRef>().unwrap().0).clone();
+
+    let meshes = app.world().resource::<Assets<Mesh>>();
+    let shadow_vertices = meshes.get(&shadow_handle).unwrap().count_vertices();
+    let full_vertices = meshes.get(&full_handle).unwrap().count_vertices();
+    assert!(shadow_vertices > 0);
+    assert!(shadow_vertices < full_vertices);
+}
+
+fn _single_voxel_chunk(voxel: WorldVoxel<u8>) -> std::sync::Arc<crate::chunk::VoxelArray<u8>> {
+    use ndshape::ConstShape;
+    let mut voxels = [WorldVoxel::Unset; crate::chunk::PaddedChunkShape::SIZE as usize];
+    voxels[crate::chunk::PaddedChunkShape::linearize([1, 1, 1]) as usize] = voxel;
+    std::sync::Arc::new(voxels)
+}
+
+fn _mesh_tex_indexes(mesh: &Mesh) -> Vec<[u32; 3]> {
+    match mesh.attribute(crate::voxel_material::ATTRIBUTE_TEX_INDEX) {
+        Some(bevy::render::mesh::VertexAttributeValues::Uint32x3(values)) => values.clone(),
+        _ => panic!("Mesh has no texture index attribute"),
+    }
+}
+
+#[test]
+fn face_mapper_picks_index_per_face() {
+    let mut options = crate::meshing::MeshingOptions::from_config(&DefaultWorld);
+    options.texture_index_face_mapper = Some(std::sync::Arc::new(|_mat, face| match face {
+        VoxelFace::Top => 7,
+        _ => 2,
+    }));
+
+    let mesh = crate::meshing::generate_chunk_mesh(
+        _single_voxel_chunk(WorldVoxel::Solid(1)),
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::ZERO,
+        &options,
+    );
+
+    let tex_indexes = _mesh_tex_indexes(&mesh);
+    assert_eq!(tex_indexes.len(), 6 * 4);
+    assert_eq!(
+        tex_indexes.iter().filter(|idx| **idx == [7, 7, 7]).count(),
+        4
+    );
+    assert_eq!(
+        tex_indexes.iter().filter(|idx| **idx == [2, 2, 2]).count(),
+        20
+    );
+}
+
+#[cfg(feature = "compact_vertices")]
+#[test]
+fn compact_vertex_attributes_shrink_chunk_meshes() {
+    use crate::meshing::f16_bits;
+    use crate::rendering::*;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    assert_eq!(f16_bits(1.0), 0x3c00);
+    assert_eq!(f16_bits(-2.0), 0xc000);
+    assert_eq!(f16_bits(32.5), 0x5010);
+    assert_eq!(f16_bits(65520.0), 0x7c00);
+    assert_eq!(f16_bits(6e-8), 0x0001);
+    assert_eq!(f16_bits(1e-8), 0x0000);
+
+    let options = crate::meshing::MeshingOptions::from_config(&DefaultWorld);
+    let mut mesh = crate::meshing::generate_chunk_mesh(
+        _single_voxel_chunk(WorldVoxel::Solid(1)),
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::ZERO,
+        &options,
+    );
+    let full_size = mesh.get_vertex_size();
+    crate::meshing::compact_vertex_attributes(&mut mesh);
+
+    assert!(mesh.attribute(Mesh::ATTRIBUTE_NORMAL).is_none());
+    assert!(mesh.attribute(Mesh::ATTRIBUTE_UV_0).is_none());
+    assert!(mesh.attribute(Mesh::ATTRIBUTE_COLOR).is_none());
+    assert_eq!(mesh.get_vertex_size(), 36);
+    assert!(mesh.get_vertex_size() < full_size);
+
+    let Some(VertexAttributeValues::Snorm8x4(normals)) = mesh.attribute(ATTRIBUTE_COMPACT_NORMAL)
+    else {
+        panic!("Mesh has no compact normals");
+    };
+    assert!(normals.contains(&[0, 127, 0, 0]));
+    assert!(normals.contains(&[0, -127, 0, 0]));
+    let Some(VertexAttributeValues::Unorm8x4(colors)) = mesh.attribute(ATTRIBUTE_COMPACT_COLOR)
+    else {
+        panic!("Mesh has no compact colors");
+    };
+    assert!(colors.iter().all(|color| color[3] == 255));
+    assert!(mesh.attribute(ATTRIBUTE_COMPACT_UV).is_some());
+}
+
+#[test]
+fn face_visible_hook_meshes_faces_between_materials() {
+    use crate::configuration::MeshingAlgorithm;
+    use ndshape::ConstShape;
+
+    const GLASS: u8 = 1;
+    const WATER: u8 = 2;
+
+    // Two glass voxels next to two water voxels
+    let mut voxels = [WorldVoxel::Unset; crate::chunk::PaddedChunkShape::SIZE as usize];
+    for (x, material) in [(1, GLASS), (2, GLASS), (3, WATER), (4, WATER)] {
+        voxels[crate::chunk::PaddedChunkShape::linearize([x, 1, 1]) as usize] =
+            WorldVoxel::Solid(material);
+    }
+    let voxels = std::sync::Arc::new(voxels);
+
+    let quad_count = |algorithm: MeshingAlgorithm, face_visible: bool| {
+        let mut options = crate::meshing::MeshingOptions::from_config(&DefaultWorld);
+        options.algorithm = algorithm;
+        if face_visible {
+            options.face_visible = Some(std::sync::Arc::new(|a: u8, b: u8, _| a != b));
+        }
+        let mesh = crate::meshing::generate_chunk_mesh(
+            voxels.clone(),
+            &crate::chunk::PaddedChunkShape {},
+            IVec3::ZERO,
+            &options,
+        );
+        mesh.count_vertices() / 4
+    };
+
+    assert_eq!(quad_count(MeshingAlgorithm::Simple, false), 18);
+    assert_eq!(quad_count(MeshingAlgorithm::Simple, true), 20);
+    assert_eq!(quad_count(MeshingAlgorithm::Greedy, false), 10);
+    assert_eq!(quad_count(MeshingAlgorithm::Greedy, true), 12);
+
+    // The hook is told which face it decides for: glass facing the water is glass's right face
+    let mut options = crate::meshing::MeshingOptions::from_config(&DefaultWorld);
+    options.algorithm = MeshingAlgorithm::Simple;
+    options.face_visible = Some(std::sync::Arc::new(|a: u8, b: u8, face| {
+        a != b && face == VoxelFace::Right
+    }));
+    let mesh = crate::meshing::generate_chunk_mesh(
+        voxels.clone(),
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::ZERO,
+        &options,
+    );
+    assert_eq!(mesh.count_vertices() / 4, 19);
+}
+
+#[test]
+fn faces_merge_hook_keeps_faces_apart() {
+    use crate::configuration::MeshingAlgorithm;
+    use ndshape::ConstShape;
+
+    // Two pairs of materials that share a texture, but are tinted differently
+    let mut voxels = [WorldVoxel::Unset; crate::chunk::PaddedChunkShape::SIZE as usize];
+    for (x, material) in [(1, 1u8), (2, 1), (3, 2), (4, 2)] {
+        voxels[crate::chunk::PaddedChunkShape::linearize([x, 1, 1]) as usize] =
+            WorldVoxel::Solid(material);
+    }
+    let voxels = std::sync::Arc::new(voxels);
+
+    let quad_count = |faces_merge: bool| {
+        let mut options = crate::meshing::MeshingOptions::from_config(&DefaultWorld);
+        options.algorithm = MeshingAlgorithm::Greedy;
+        options.texture_index_mapper = std::sync::Arc::new(|_| [0; 3]);
+        if faces_merge {
+            options.faces_merge = Some(std::sync::Arc::new(|a: u8, b: u8, _| a == b));
+        }
+        let mesh = crate::meshing::generate_chunk_mesh(
+            voxels.clone(),
+            &crate::chunk::PaddedChunkShape {},
+            IVec3::ZERO,
+            &options,
+        );
+        mesh.count_vertices() / 4
+    };
+
+    // The long sides are split where the tint changes
+    assert_eq!(quad_count(false), 6);
+    assert_eq!(quad_count(true), 10);
+}
+
+#[test]
+fn material_groups_get_their_own_meshes() {
+    use crate::chunk::ChunkTask;
+    use crate::voxel_world_internal::ModifiedVoxels;
+    use ndshape::ConstShape;
+
+    const STONE: u8 = 0;
+    const GLASS: u8 = 1;
+    const WATER: u8 = 2;
+
+    let mut options = crate::meshing::MeshingOptions::from_config(&DefaultWorld);
+    options.material_group = Some(std::sync::Arc::new(|material| match material {
+        GLASS => MaterialGroup::Transparent,
+        WATER => MaterialGroup::Fluid,
+        _ => MaterialGroup::Opaque,
+    }));
+
+    // Stone, then two glass voxels, then water
+    let mut voxels = [WorldVoxel::Unset; crate::chunk::PaddedChunkShape::SIZE as usize];
+    for (x, material) in [(1, STONE), (2, GLASS), (3, GLASS), (4, WATER)] {
+        voxels[crate::chunk::PaddedChunkShape::linearize([x, 1, 1]) as usize] =
+            WorldVoxel::Solid(material);
+    }
+
+    let quad_counts: Vec<(MaterialGroup, usize)> = crate::meshing::generate_chunk_group_meshes(
+        std::sync::Arc::new(voxels),
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::ZERO,
+        &options,
+        options.material_group.as_ref().unwrap(),
+    )
+    .iter()
+    .map(|(group, mesh)| (*group, mesh.count_vertices() / 4))
+    .collect();
+
+    // Stone shows its face behind the glass, glass hides its face against the stone and between
+    // the two glass voxels, and glass and water both show the face between them
+    assert_eq!(
+        quad_counts,
+        vec![
+            (MaterialGroup::Opaque, 6),
+            (MaterialGroup::Transparent, 9),
+            (MaterialGroup::Fluid, 6),
+        ]
+    );
+
+    // Full chunks still need meshing when they have faces to show
+    let mut chunk_task = ChunkTask::<DefaultWorld, u8>::new(
+        Entity::PLACEHOLDER,
+        IVec3::ZERO,
+        ModifiedVoxels::default(),
+    );
+    chunk_task.generate(|pos: IVec3| {
+        if pos == IVec3::splat(5) {
+            WorldVoxel::Solid(GLASS)
+        } else {
+            WorldVoxel::Solid(STONE)
+        }
+    });
+    assert!(chunk_task.is_full());
+    assert!(options.meshes_full_chunks());
+    chunk_task.mesh(&options);
+    assert_eq!(chunk_task.mesh.unwrap().count_vertices(), 6 * 4);
+    assert!(chunk_task.group_meshes.is_empty());
+}
+
+#[test]
+fn voxel_transparency_meshes_transparent_voxels_separately() {
+    use ndshape::ConstShape;
+
+    const GLASS: u8 = 1;
+
+    #[derive(Resource, Clone, Default)]
+    struct GlassWorld;
+
+    impl VoxelWorldConfig for GlassWorld {
+        type MaterialIndex = u8;
+
+        fn voxel_transparency(&self) -> Option<VoxelTransparencyFn<Self::MaterialIndex>> {
+            Some(std::sync::Arc::new(|material| match material {
+                GLASS => VoxelTransparency::Transparent,
+                _ => VoxelTransparency::Opaque,
+            }))
+        }
+    }
+
+    let options = crate::meshing::MeshingOptions::from_config(&GlassWorld);
+    let material_group = options.material_group.clone().unwrap();
+
+    // Stone, then two glass voxels
+    let mut voxels = [WorldVoxel::Unset; crate::chunk::PaddedChunkShape::SIZE as usize];
+    for (x, material) in [(1, 0), (2, GLASS), (3, GLASS)] {
+        voxels[crate::chunk::PaddedChunkShape::linearize([x, 1, 1]) as usize] =
+            WorldVoxel::Solid(material);
+    }
+
+    let quad_counts: Vec<(MaterialGroup, usize)> = crate::meshing::generate_chunk_group_meshes(
+        std::sync::Arc::new(voxels),
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::ZERO,
+        &options,
+        &material_group,
+    )
+    .iter()
+    .map(|(group, mesh)| (*group, mesh.count_vertices() / 4))
+    .collect();
+
+    // The stone keeps its face behind the glass, and the glass voxels hide the faces between them
+    assert_eq!(
+        quad_counts,
+        vec![(MaterialGroup::Opaque, 6), (MaterialGroup::Transparent, 9)]
+    );
+    assert_eq!(
+        MaterialGroup::from(VoxelTransparency::Transparent).alpha_mode(),
+        AlphaMode::Blend
+    );
+}
+
+#[test]
+fn column_cache_samples_each_column_once() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let samples = Arc::new(AtomicUsize::new(0));
+    let cache = ColumnCache::<i32>::new(10_000);
+    let delegate = cache.delegate(
+        {
+            let samples = samples.clone();
+            move |column: IVec2| {
+                samples.fetch_add(1, Ordering::Relaxed);
+                column.x + column.y
+            }
+        },
+        |pos: IVec3, height: &i32| {
+            if pos.y < *height {
+                WorldVoxel::Solid(0u8)
+            } else {
+                WorldVoxel::Air
+            }
+        },
+    );
+
+    // Two neighboring chunks, each looking up its padding too
+    for chunk_x in 0..2 {
+        let mut lookup = delegate(IVec3::new(chunk_x, 0, 0));
+        for x in -1..=32 {
+            for z in -1..=32 {
+                for y in -1..=32 {
+                    let pos = IVec3::new(chunk_x * 32 + x, y, z);
+                    let expected = if y < pos.x + pos.z {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    };
+                    assert_eq!(lookup(pos), expected);
+                }
+            }
+        }
+    }
+
+    // The columns where the two chunks overlap are only sampled once
+    let columns = (32 * 2 + 2) * 34;
+    assert_eq!(samples.load(Ordering::Relaxed), columns);
+    assert_eq!(cache.len(), columns);
+
+    // The least recently used columns are dropped first
+    let cache = ColumnCache::<i32>::new(2);
+    cache.get_or_insert_with(IVec2::new(0, 0), || 0);
+    cache.get_or_insert_with(IVec2::new(1, 0), || 1);
+    cache.get_or_insert_with(IVec2::new(0, 0), || unreachable!());
+    cache.get_or_insert_with(IVec2::new(2, 0), || 2);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get_or_insert_with(IVec2::new(0, 0), || -1), 0);
+    assert_eq!(cache.get_or_insert_with(IVec2::new(1, 0), || -1), -1);
+}
+
+#[test]
+fn column_cache_lookup_fns_keep_a_bounded_number_of_columns() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // A shared cache of one column, so that only the local samples of the lookup function keep
+    // a column from being sampled again
+    let samples = Arc::new(AtomicUsize::new(0));
+    let cache = ColumnCache::<i32>::new(1);
+    let delegate = cache.delegate(
+        {
+            let samples = samples.clone();
+            move |_column: IVec2| {
+                samples.fetch_add(1, Ordering::Relaxed);
+                0
+            }
+        },
+        |_pos: IVec3, _height: &i32| WorldVoxel::<u8>::Air,
+    );
+
+    // A reused function that went through the columns of many chunks has forgotten the first one
+    let mut lookup = delegate(IVec3::ZERO);
+    lookup(IVec3::ZERO);
+    lookup(IVec3::ZERO);
+    assert_eq!(samples.load(Ordering::Relaxed), 1);
+    let columns = 16 * CHUNK_SIZE_I * CHUNK_SIZE_I;
+    for x in 1..=columns {
+        lookup(IVec3::new(x, 0, 0));
+    }
+    let sampled = samples.load(Ordering::Relaxed);
+    lookup(IVec3::ZERO);
+    assert_eq!(samples.load(Ordering::Relaxed), sampled + 1);
+}
+
+#[test]
+fn voxel_cache_shares_voxels_between_chunks() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let created = Arc::new(AtomicUsize::new(0));
+    let cache = VoxelCache::<u8>::new(100_000);
+    let delegate = cache.delegate({
+        let lookups = lookups.clone();
+        let created = created.clone();
+        Box::new(move |_chunk_pos| {
+            created.fetch_add(1, Ordering::Relaxed);
+            let lookups = lookups.clone();
+            Box::new(move |pos: IVec3| {
+                lookups.fetch_add(1, Ordering::Relaxed);
+                if pos.y < 0 {
+                    WorldVoxel::Solid(0)
+                } else {
+                    WorldVoxel::Air
+                }
+            })
+        })
+    });
+
+    // Lookups from different threads, over the same row of voxels
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                let mut lookup = delegate(IVec3::ZERO);
+                for x in 0..1000 {
+                    let pos = IVec3::new(x, x % 3 - 1, 0);
+                    assert_eq!(lookup(pos).is_solid(), pos.y < 0);
+                }
+            });
+        }
+    });
+    assert_eq!(cache.len(), 1000);
+    let first_lookups = lookups.load(Ordering::Relaxed);
+    assert!((1000..=4000).contains(&first_lookups));
+
+    // Everything is cached now, so the delegate doesn't even get called for the chunk
+    let created_before = created.load(Ordering::Relaxed);
+    let mut lookup = delegate(IVec3::ONE);
+    for x in 0..1000 {
+        lookup(IVec3::new(x, x % 3 - 1, 0));
+    }
+    assert_eq!(lookups.load(Ordering::Relaxed), first_lookups);
+    assert_eq!(created.load(Ordering::Relaxed), created_before);
+}
+
+#[test]
+fn faces_convert_between_apis() {
+    for face in VoxelFace::ALL {
+        assert_eq!(VoxelFace::from_normal(face.normal()), Some(face));
+        assert_eq!(face.opposite().normal(), -face.normal());
+        assert_eq!(face.neighbor(IVec3::ONE), IVec3::ONE + face.normal());
+        assert_eq!(
+            Vec3::from(face),
+            face.normal().as_vec3(),
+            "{face:?} points along its normal"
+        );
+    }
+    assert_eq!(VoxelFace::from_normal(IVec3::ONE), None);
+    assert_eq!(VoxelFace::from_normal(IVec3::ZERO), None);
+
+    let furnace = FaceTextureIndex::all(1).with_forward(2);
+    assert_eq!(furnace.get(VoxelFace::Forward), 2);
+    assert_eq!(furnace.get(VoxelFace::Back), 1);
+
+    let hit = VoxelRaycastResult {
+        position: Vec3::new(0.5, 1.0, 0.5),
+        normal: Some(Vec3::Y),
+        voxel: WorldVoxel::Solid(1u8),
+    };
+    assert_eq!(hit.face(), Some(VoxelFace::Top));
+}
+
+#[test]
+fn fixed_region_worlds_spawn_without_a_camera() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<DefaultWorld>::minimal()
+            .with_fixed_region(IVec3::new(1, 0, 1), IVec3::new(-1, 0, 0)),
+    ));
+
+    let chunk_positions = |app: &mut App| {
+        let world = app.world_mut();
+        let mut positions: Vec<IVec3> = world
+            .query::<&Chunk<DefaultWorld>>()
+            .iter(world)
+            .map(|chunk| chunk.position)
+            .collect();
+        positions.sort_by_key(|position| position.to_array());
+        positions
+    };
+
+    for _ in 0..3 {
+        app.update();
+    }
+    let expected: Vec<IVec3> = [(-1, 0), (-1, 1), (0, 0), (0, 1), (1, 0), (1, 1)]
+        .into_iter()
+        .map(|(x, z)| IVec3::new(x, 0, z))
+        .collect();
+    assert_eq!(chunk_positions(&mut app), expected);
+
+    // A camera far away neither spawns nor despawns chunks
+    app.world_mut().spawn((
+        Camera3dBundle {
+            transform: Transform::from_xyz(5000.0, 0.0, 0.0),
+            ..default()
+        },
+        VoxelWorldCamera::<DefaultWorld>::default(),
+    ));
+    for _ in 0..3 {
+        app.update();
+    }
+    assert_eq!(chunk_positions(&mut app), expected);
+
+    // Once complete, the region is only spawned again when one of its chunks is despawned
+    let world = app.world_mut();
+    let chunk_entity = world
+        .query::<&Chunk<DefaultWorld>>()
+        .iter(world)
+        .next()
+        .unwrap()
+        .entity;
+    world.entity_mut(chunk_entity).insert(NeedsDespawn);
+    for _ in 0..5 {
+        app.update();
+    }
+    assert_eq!(chunk_positions(&mut app), expected);
+
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<DefaultWorld>| {
+            assert!(voxel_world.set_voxel(IVec3::new(40, 5, 40), WorldVoxel::Solid(1)));
+            assert!(!voxel_world.set_voxel(IVec3::new(40, 40, 40), WorldVoxel::Solid(1)));
+        });
+}
+
+#[test]
+fn voxel_previews_render_into_their_image() {
+    use crate::mesh_cache::MeshRef;
+    use crate::voxel_preview::{
+        finish_voxel_previews, render_voxel_previews, NextPreviewLayer, PreviewCamera,
+        PREVIEW_RENDER_FRAMES,
+    };
+    use crate::voxel_world_internal::NeedsMaterial;
+    use bevy::render::{camera::RenderTarget, view::RenderLayers};
+
+    let mut app = _test_setup_app();
+    app.init_resource::<Assets<Mesh>>()
+        .init_resource::<Assets<Image>>()
+        .init_resource::<NextPreviewLayer<DefaultWorld>>()
+        .add_systems(
+            Update,
+            (
+                render_voxel_previews::<DefaultWorld>,
+                finish_voxel_previews::<DefaultWorld>,
+            )
+                .chain(),
+        );
+
+    let mut model = VoxelModel::new(UVec3::new(2, 1, 1));
+    model.set(IVec3::ZERO, WorldVoxel::Solid(0));
+    model.set(IVec3::X, WorldVoxel::Solid(1));
+    let preview = VoxelPreview::<DefaultWorld>::new(
+        model,
+        UVec2::splat(64),
+        &mut app.world_mut().resource_mut::<Assets<Image>>(),
+    );
+    let image = preview.image();
+    assert_eq!(
+        app.world()
+            .resource::<Assets<Image>>()
+            .get(&image)
+            .unwrap()
+            .size(),
+        UVec2::splat(64)
+    );
+    let preview_entity = app.world_mut().spawn(preview).id();
+    app.update();
+
+    let layers = RenderLayers::none().with(PREVIEW_RENDER_LAYERS_START);
+    let world = app.world_mut();
+    let cameras = world
+        .query::<(&Camera, &RenderLayers, &Parent)>()
+        .iter(world)
+        .filter(|(camera, camera_layers, _)| {
+            matches!(&camera.target, RenderTarget::Image(target) if *target == image)
+                && **camera_layers == layers
+        })
+        .count();
+    assert_eq!(cameras, 1);
+
+    let (mesh_ref, mesh_layers) = world.query::<(&MeshRef, &RenderLayers)>().single(world);
+    assert_eq!(*mesh_layers, layers);
+    let mesh = world
+        .resource::<Assets<Mesh>>()
+        .get(&*mesh_ref.0)
+        .unwrap()
+        .clone();
+    // Two voxels side by side show ten faces
+    assert_eq!(mesh.count_vertices(), 10 * 4);
+
+    // The preview keeps its layer, and is not rendered again until it changes
+    app.update();
+    let world = app.world_mut();
+    assert_eq!(world.query::<&MeshRef>().iter(world).count(), 1);
+
+    // The camera stays on while the mesh waits for its material, and turns off a few frames
+    // after it has it
+    let camera_is_active = |world: &mut World| {
+        world
+            .query_filtered::<&Camera, With<PreviewCamera<DefaultWorld>>>()
+            .single(world)
+            .is_active
+    };
+    assert!(camera_is_active(app.world_mut()));
+    let world = app.world_mut();
+    let mesh_entity = world
+        .query_filtered::<Entity, With<MeshRef>>()
+        .single(world);
+    world
+        .entity_mut(mesh_entity)
+        .remove::<NeedsMaterial<DefaultWorld>>();
+    for _ in 0..PREVIEW_RENDER_FRAMES {
+        app.update();
+        assert!(camera_is_active(app.world_mut()));
+    }
+    app.update();
+    assert!(!camera_is_active(app.world_mut()));
+
+    // The layer of a despawned preview is handed out again
+    app.world_mut()
+        .entity_mut(preview_entity)
+        .despawn_recursive();
+    app.update();
+    let preview = VoxelPreview::<DefaultWorld>::new(
+        VoxelModel::new(UVec3::ONE),
+        UVec2::splat(16),
+        &mut app.world_mut().resource_mut::<Assets<Image>>(),
+    );
+    let preview_entity = app.world_mut().spawn(preview).id();
+    app.update();
+    assert_eq!(
+        app.world()
+            .get::<VoxelPreview<DefaultWorld>>(preview_entity)
+            .unwrap()
+            .render_layer,
+        Some(PREVIEW_RENDER_LAYERS_START)
+    );
+}
+
+#[test]
+fn global_tint_is_applied_to_the_material() {
+    use crate::voxel_material::{apply_global_tint, StandardVoxelMaterial, WorldMaterialHandle};
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::pbr::ExtendedMaterial;
+
+    type VoxelMaterial = ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>;
+
+    // A second world, which keeps its own material
+    struct OtherWorld;
+
+    let mut app = _test_setup_app();
+    app.init_resource::<Assets<VoxelMaterial>>()
+        .init_resource::<crate::voxel_material::GlobalTint<OtherWorld>>()
+        .add_systems(
+            Update,
+            (
+                apply_global_tint::<DefaultWorld>,
+                apply_global_tint::<OtherWorld>,
+            ),
+        );
+    let add_material = |app: &mut App| {
+        app.world_mut()
+            .resource_mut::<Assets<VoxelMaterial>>()
+            .add(ExtendedMaterial {
+                base: StandardMaterial::default(),
+                extension: StandardVoxelMaterial {
+                    voxels_texture: Handle::default(),
+                    tint: LinearRgba::WHITE,
+                    atlas: false,
+                },
+            })
+    };
+    let handle = add_material(&mut app);
+    let other_handle = add_material(&mut app);
+    app.insert_resource(WorldMaterialHandle::<DefaultWorld, _>::new(handle.clone()));
+    app.insert_resource(WorldMaterialHandle::<OtherWorld, _>::new(
+        other_handle.clone(),
+    ));
+    app.update();
+
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<DefaultWorld>| {
+            voxel_world.set_global_tint(LinearRgba::rgb(1.0, 0.5, 0.25));
+            voxel_world.set_global_tint_intensity(0.5);
+        });
+    app.update();
+
+    let tint = app
+        .world()
+        .resource::<Assets<VoxelMaterial>>()
+        .get(&handle)
+        .unwrap()
+        .extension
+        .tint;
+    assert_eq!(tint, LinearRgba::new(0.5, 0.25, 0.125, 1.0));
+    let other_tint = app
+        .world()
+        .resource::<Assets<VoxelMaterial>>()
+        .get(&other_handle)
+        .unwrap()
+        .extension
+        .tint;
+    assert_eq!(other_tint, LinearRgba::WHITE);
+}
+
+#[test]
+fn face_texture_index_builds_in_constants() {
+    const LOG: FaceTextureIndex = FaceTextureIndex::new(1, 2, 1);
+    const FURNACE: FaceTextureIndex = FaceTextureIndex::all(3)
+        .with_top(4)
+        .with_face(VoxelFace::Forward, 5);
+
+    assert_eq!(LOG, FaceTextureIndex::from([1, 2, 1]));
+    assert_eq!(LOG.get(VoxelFace::Left), 2);
+    assert_eq!(
+        FURNACE,
+        FaceTextureIndex::from([3, 4, 3, 3, 3, 5]),
+        "the array is in the order of the VoxelFace variants"
+    );
+    assert_eq!(
+        FaceTextureIndex::all(0).with_sides(6).with_bottom(7),
+        FaceTextureIndex::new(0, 6, 7)
+    );
+}
+
+#[test]
+fn contextual_texture_sees_neighbors() {
+    use ndshape::ConstShape;
+
+    let mut options = crate::meshing::MeshingOptions::from_config(&DefaultWorld);
+    options.contextual_texture = Some(std::sync::Arc::new(|_pos, _mat, neighbors| {
+        if neighbors.above.is_solid() {
+            // Covered grass renders as dirt
+            FaceTextureIndex::all(5)
+        } else {
+            FaceTextureIndex::from([1, 2, 3])
+        }
+    }));
+
+    let mut voxels = *_single_voxel_chunk(WorldVoxel::Solid(1));
+    voxels[crate::chunk::PaddedChunkShape::linearize([1, 2, 1]) as usize] = WorldVoxel::Solid(1);
+
+    let mesh = crate::meshing::generate_chunk_mesh(
+        std::sync::Arc::new(voxels),
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::ZERO,
+        &options,
+    );
+
+    let tex_indexes = _mesh_tex_indexes(&mesh);
+    // The lower voxel has five visible faces, all of which should be dirt
+    assert_eq!(
+        tex_indexes.iter().filter(|idx| **idx == [5, 5, 5]).count(),
+        5 * 4
+    );
+    assert_eq!(
+        tex_indexes.iter().filter(|idx| **idx == [1, 1, 1]).count(),
+        4
+    );
+    assert_eq!(
+        tex_indexes.iter().filter(|idx| **idx == [2, 2, 2]).count(),
+        4 * 4
+    );
+}
+
+#[test]
+fn mesh_cache_keys_are_scoped() {
+    use crate::mesh_cache::MeshCacheScope;
+
+    #[derive(Resource, Clone, Default)]
+    struct OtherWorld;
+
+    impl VoxelWorldConfig for OtherWorld {
+        type MaterialIndex = u8;
+
+        fn mesh_cache_per_chunk(&self) -> bool {
+            true
+        }
+    }
+
+    let default_scope = MeshCacheScope::from_config(&DefaultWorld);
+    let other_scope = MeshCacheScope::from_config(&OtherWorld);
+
+    // Same voxels in different worlds never share a mesh
+    assert_ne!(
+        default_scope.key(42, IVec3::ZERO),
+        other_scope.key(42, IVec3::ZERO)
+    );
+
+    // Only per-chunk scopes take the chunk position into account
+    assert_eq!(
+        default_scope.key(42, IVec3::ZERO),
+        default_scope.key(42, IVec3::ONE)
+    );
+    assert_ne!(
+        other_scope.key(42, IVec3::ZERO),
+        other_scope.key(42, IVec3::ONE)
+    );
+}
+
+#[test]
+fn mesh_cache_scope_covers_meshing_callbacks() {
+    use crate::mesh_cache::MeshCacheScope;
+
+    #[derive(Resource, Clone, Default)]
+    struct CallbackWorld {
+        face_visible: bool,
+    }
+
+    impl VoxelWorldConfig for CallbackWorld {
+        type MaterialIndex = u8;
+
+        fn face_visible(&self) -> Option<FaceVisibleFn<Self::MaterialIndex>> {
+            self.face_visible
+                .then(|| std::sync::Arc::new(|_, _, _| true) as FaceVisibleFn<Self::MaterialIndex>)
+        }
+    }
+
+    // Meshes from a run without the callback are not reused by a run with it
+    let without = MeshCacheScope::from_config(&CallbackWorld {
+        face_visible: false,
+    });
+    let with = MeshCacheScope::from_config(&CallbackWorld { face_visible: true });
+    assert_ne!(without.key(42, IVec3::ZERO), with.key(42, IVec3::ZERO));
+}
+
+#[test]
+fn mesh_cache_persists_between_runs() {
+    use crate::mesh_cache::PersistedMeshes;
+    use std::path::PathBuf;
+
+    #[derive(Resource, Clone, Default)]
+    struct PersistedWorld;
+
+    impl VoxelWorldConfig for PersistedWorld {
+        type MaterialIndex = u8;
+
+        fn mesh_cache_path(&self) -> Option<PathBuf> {
+            Some(std::env::temp_dir().join(format!(
+                "bevy_voxel_world_mesh_cache_{}.bin",
+                std::process::id()
+            )))
+        }
+    }
+
+    let mesh = crate::meshing::generate_chunk_mesh(
+        _single_voxel_chunk(WorldVoxel::Solid(0)),
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::ZERO,
+        &crate::meshing::MeshingOptions::from_config(&PersistedWorld),
+    );
+
+    PersistedMeshes::<PersistedWorld>::default().save(&PersistedWorld, vec![(7, mesh.clone())]);
+    let restored = PersistedMeshes::<PersistedWorld>::load(&PersistedWorld);
+
+    // Corrupted counts are ignored along with the file, instead of being allocated for. These are
+    // the mesh count, the length of the first attribute name and the length of that attribute.
+    let path = PersistedWorld.mesh_cache_path().unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let name_len = u32::from_le_bytes(bytes[32..36].try_into().unwrap()) as usize;
+    for offset in [16, 32, 36 + name_len] {
+        let mut corrupted = bytes.clone();
+        corrupted[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&path, corrupted).unwrap();
+        assert!(PersistedMeshes::<PersistedWorld>::load(&PersistedWorld)
+            .meshes
+            .is_empty());
+    }
+    std::fs::remove_file(path).unwrap();
+
+    let restored_mesh = restored.meshes.get(&7).expect("mesh should be restored");
+    assert_eq!(restored_mesh.count_vertices(), mesh.count_vertices());
+    assert_eq!(_mesh_tex_indexes(restored_mesh), _mesh_tex_indexes(&mesh));
+    assert_eq!(
+        restored_mesh.indices().unwrap().iter().collect::<Vec<_>>(),
+        mesh.indices().unwrap().iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn niche_material_index_halves_chunk_storage() {
+    use crate::chunk::VoxelArray;
+    use std::mem::size_of;
+
+    #[derive(VoxelIndex, Debug)]
+    #[repr(u8)]
+    #[allow(dead_code)]
+    enum BlockTexture {
+        #[voxel(default)]
+        Stone,
+        Grass,
+    }
+
+    assert_eq!(size_of::<WorldVoxel<BlockTexture>>(), 1);
+    assert_eq!(
+        size_of::<VoxelArray<BlockTexture>>() * 2,
+        size_of::<VoxelArray<u8>>()
+    );
+}
+
+#[test]
+fn derived_voxel_index_metadata() {
+    #[derive(VoxelIndex, Debug)]
+    enum BlockTexture {
+        #[voxel(name = "Snowy brick", textures = [0, 1, 2])]
+        SnowyBrick,
+        #[voxel(default)]
+        FullBrick,
+        #[voxel(textures = 3)]
+        Grass,
+    }
+
+    assert_eq!(BlockTexture::default(), BlockTexture::FullBrick);
+    assert_ne!(BlockTexture::Grass, BlockTexture::FullBrick);
+    assert_eq!(
+        BlockTexture::VARIANTS,
+        &[
+            BlockTexture::SnowyBrick,
+            BlockTexture::FullBrick,
+            BlockTexture::Grass
+        ]
+    );
+    assert_eq!(BlockTexture::SnowyBrick.name(), "Snowy brick");
+    assert_eq!(BlockTexture::Grass.name(), "Grass");
+
+    let mapper = BlockTexture::texture_index_mapper();
+    assert_eq!(mapper(BlockTexture::SnowyBrick), [0, 1, 2]);
+    assert_eq!(mapper(BlockTexture::FullBrick), [0, 0, 0]);
+    assert_eq!(mapper(BlockTexture::Grass), [3, 3, 3]);
+}
+
+#[test]
+fn decorations_stay_within_their_chunk() {
+    use crate::chunk::ChunkTask;
+    use crate::configuration::ChunkDecoratorFn;
+    use crate::voxel_world_internal::ModifiedVoxels;
+    use std::sync::Arc;
+
+    // Decorate every spot where grass meets air
+    let decorator: ChunkDecoratorFn = Arc::new(|chunk_position, get_voxel| {
+        let mut decorations = Vec::new();
+        for x in -1..=32 {
+            for z in -1..=32 {
+                let ground = chunk_position * 32 + IVec3::new(x, -1, z);
+                if get_voxel(ground).is_solid() && get_voxel(ground + IVec3::Y).is_air() {
+                    decorations.push(Decoration::new(7, (ground + IVec3::Y).as_vec3()));
+                }
+            }
+        }
+        decorations
+    });
+
+    let mut decorated = Vec::new();
+    for chunk_position in [IVec3::ZERO, IVec3::NEG_Y] {
+        let mut chunk_task = ChunkTask::<DefaultWorld, u8>::new(
+            Entity::PLACEHOLDER,
+            chunk_position,
+            ModifiedVoxels::default(),
+        );
+        chunk_task.generate(|pos: IVec3| {
+            if pos.y < 0 {
+                WorldVoxel::Solid(0)
+            } else {
+                WorldVoxel::Air
+            }
+        });
+        chunk_task.decorate(&decorator);
+        decorated.push(chunk_task.decorations.unwrap());
+    }
+
+    // Only the chunk above the ground gets decorated, even though the chunk below sees the
+    // same surface in its padding
+    assert_eq!(decorated[0].len(), 32 * 32);
+    assert!(decorated[0].iter().all(|d| d.kind == 7));
+    assert!(decorated[1].is_empty());
+}
+
+#[test]
+fn instanced_decorations_share_the_prop_mesh() {
+    use crate::decoration_instancing::DecorationInstanceData;
+    use bevy::render::primitives::Aabb;
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .init_resource::<Assets<Mesh>>()
+        .init_resource::<DecorationMeshes<DefaultWorld>>()
+        .add_systems(
+            Update,
+            Internals::<DefaultWorld>::build_decoration_instances,
+        );
+
+    let prop = app
+        .world_mut()
+        .resource_mut::<Assets<Mesh>>()
+        .add(Cuboid::default());
+    app.world_mut()
+        .resource_mut::<DecorationMeshes<DefaultWorld>>()
+        .insert(7, prop.clone());
+
+    let transforms = vec![
+        Transform::from_xyz(0.0, 0.0, 0.0),
+        Transform::from_xyz(5.0, 0.0, 0.0),
+        Transform::from_xyz(0.0, 0.0, 5.0),
+    ];
+    let entity = app
+        .world_mut()
+        .spawn((
+            ChunkDecorationInstances::<DefaultWorld>::new(7, IVec3::ZERO, transforms),
+            GlobalTransform::from_xyz(32.0, 0.0, 0.0),
+        ))
+        .id();
+
+    app.update();
+
+    // Every chunk draws the registered prop mesh, instead of a baked copy of it
+    let entity_ref = app.world().entity(entity);
+    assert_eq!(entity_ref.get::<Handle<Mesh>>(), Some(&prop));
+    assert_eq!(app.world().resource::<Assets<Mesh>>().len(), 1);
+
+    let aabb = entity_ref.get::<Aabb>().unwrap();
+    assert_eq!(Vec3::from(aabb.min()), Vec3::splat(-0.5));
+    assert_eq!(Vec3::from(aabb.max()), Vec3::new(5.5, 0.5, 5.5));
+
+    let instances = &entity_ref.get::<DecorationInstanceData>().unwrap().0;
+    assert_eq!(instances.len(), 3);
+    assert_eq!(
+        instances[1],
+        crate::decoration_instancing::DecorationInstance::from_transform(
+            &GlobalTransform::from_xyz(37.0, 0.0, 0.0)
+        )
+    );
+}
+
+#[test]
+fn sample_surface_positions_needs_headroom() {
+    use crate::chunk::ChunkTask;
+    use crate::voxel_world_internal::ModifiedVoxels;
+
+    // Far away from the camera, so that it doesn't get spawned by the camera
+    let chunk_position = IVec3::new(100, 0, 100);
+    let origin = chunk_position * 32;
+
+    let mut app = _test_setup_app();
+    app.add_systems(
+        Startup,
+        move |mut chunk_map_update_buffer: ResMut<
+            ChunkMapUpdateBuffer<DefaultWorld, <DefaultWorld as VoxelWorldConfig>::MaterialIndex>,
+        >| {
+            let mut chunk_task = ChunkTask::<DefaultWorld, u8>::new(
+                Entity::PLACEHOLDER,
+                chunk_position,
+                ModifiedVoxels::default(),
+            );
+            // Flat ground just below the chunk, with a single low overhang
+            chunk_task.generate(move |pos: IVec3| {
+                if pos.y < 0 || pos == origin + IVec3::new(3, 1, 3) {
+                    WorldVoxel::Solid(0)
+                } else {
+                    WorldVoxel::Air
+                }
+            });
+
+            chunk_map_update_buffer.push((
+                chunk_position,
+                chunk_task.chunk_data,
+                ChunkWillSpawn::<DefaultWorld>::new(chunk_position, Entity::PLACEHOLDER),
+            ));
+        },
+    );
+
+    app.update();
+
+    app.add_systems(Update, move |voxel_world: VoxelWorld<DefaultWorld>| {
+        let mut rng = rand::thread_rng();
+
+        let all = voxel_world.sample_surface_positions(chunk_position, usize::MAX, &mut rng);
+        // Every column has ground to stand on, except under the overhang, which instead has
+        // room on top of it
+        assert_eq!(all.len(), 32 * 32);
+        assert!(!all.contains(&(origin + IVec3::new(3, 0, 3))));
+        assert!(all.contains(&(origin + IVec3::new(3, 2, 3))));
+
+        let some = voxel_world.sample_surface_positions(chunk_position, 5, &mut rng);
+        assert_eq!(some.len(), 5);
+
+        assert!(voxel_world
+            .sample_surface_positions(IVec3::new(-100, 0, -100), 5, &mut rng)
+            .is_empty());
+    });
+
+    app.update();
+}
+
+#[test]
+fn surface_queries_find_loaded_surfaces() {
+    use crate::chunk::ChunkTask;
+    use crate::voxel_world_internal::ModifiedVoxels;
+
+    // Far away from the camera, so that it doesn't get spawned by the camera
+    let chunk_position = IVec3::new(90, 0, 90);
+    let origin = chunk_position * crate::chunk::CHUNK_SIZE_I;
+
+    let mut app = _test_setup_app();
+    app.add_systems(
+        Startup,
+        move |mut chunk_map_update_buffer: ResMut<
+            ChunkMapUpdateBuffer<DefaultWorld, <DefaultWorld as VoxelWorldConfig>::MaterialIndex>,
+        >| {
+            let mut chunk_task = ChunkTask::<DefaultWorld, u8>::new(
+                Entity::PLACEHOLDER,
+                chunk_position,
+                ModifiedVoxels::default(),
+            );
+            // Flat ground with a pillar, and a floating voxel high above the pillar
+            chunk_task.generate(move |pos: IVec3| {
+                let local = pos - origin;
+                let in_pillar = local.x == 3 && local.z == 3 && local.y < 10;
+                if local.y < 4 || in_pillar || local == IVec3::new(3, 20, 3) {
+                    WorldVoxel::Solid(0)
+                } else {
+                    WorldVoxel::Air
+                }
+            });
+
+            chunk_map_update_buffer.push((
+                chunk_position,
+                chunk_task.chunk_data,
+                ChunkWillSpawn::<DefaultWorld>::new(chunk_position, Entity::PLACEHOLDER),
+            ));
+        },
+    );
+
+    app.update();
+
+    app.add_systems(Update, move |voxel_world: VoxelWorld<DefaultWorld>| {
+        assert_eq!(
+            voxel_world.get_surface_voxel_at_2d(origin.x, origin.z),
+            Some((origin + IVec3::new(0, 3, 0), WorldVoxel::Solid(0)))
+        );
+        assert_eq!(
+            voxel_world.get_surface_voxel_at_2d(origin.x + 3, origin.z + 3),
+            Some((origin + IVec3::new(3, 20, 3), WorldVoxel::Solid(0)))
+        );
+        assert_eq!(voxel_world.get_surface_voxel_at_2d(-1000, -1000), None);
+
+        // The top of the pillar is closer than the floating voxel and the ground
+        let point = (origin + IVec3::new(3, 11, 3)).as_vec3() + 0.5;
+        assert_eq!(
+            voxel_world.get_closest_surface(point),
+            Some((origin + IVec3::new(3, 9, 3), WorldVoxel::Solid(0)))
+        );
+        assert_eq!(voxel_world.get_closest_surface(Vec3::splat(-1000.0)), None);
+
+        let mut rng = rand::thread_rng();
+        let column = IRect::new(origin.x + 3, origin.z + 3, origin.x + 3, origin.z + 3);
+        assert_eq!(
+            voxel_world.get_random_surface_position(&mut rng, column),
+            Some(origin + IVec3::new(3, 21, 3))
+        );
+        let area = IRect::new(origin.x, origin.z, origin.x + 2, origin.z + 2);
+        let position = voxel_world
+            .get_random_surface_position(&mut rng, area)
+            .unwrap();
+        assert_eq!(position.y, 4);
+        // The corners of the rect can be given in any order
+        let inverted = IRect {
+            min: area.max,
+            max: area.min,
+        };
+        let position = voxel_world
+            .get_random_surface_position(&mut rng, inverted)
+            .unwrap();
+        assert_eq!(position.y, 4);
+    });
+
+    app.update();
+}
+
+#[test]
+fn seeded_rng_makes_random_picks_reproducible() {
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+    #[derive(Resource, Clone, Default)]
+    struct SeededWorld;
+
+    impl VoxelWorldConfig for SeededWorld {
+        type MaterialIndex = u8;
+
+        fn rng(&self) -> Box<dyn RngCore + Send + Sync> {
+            Box::new(StdRng::seed_from_u64(7))
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<SeededWorld>::minimal()));
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<SeededWorld>| {
+        voxel_world.set_voxels(
+            (-8..=8)
+                .flat_map(|x| (-8..=8).map(move |z| IVec3::new(x, 0, z)))
+                .map(|position| (position, WorldVoxel::Solid(1))),
+        );
+    });
+    app.update();
+
+    app.add_systems(Update, |voxel_world: VoxelWorld<SeededWorld>| {
+        let picks = || {
+            (0..8)
+                .map(|_| voxel_world.get_random_surface_voxel(IVec3::new(0, 4, 0), 8))
+                .collect::<Vec<_>>()
+        };
+
+        // The generator from the config is the one the world draws from
+        let first = picks();
+        assert!(first.iter().any(Option::is_some));
+
+        voxel_world.set_rng(StdRng::seed_from_u64(7));
+        assert_eq!(picks(), first);
+    });
+    app.update();
+}
+
+#[test]
+fn sun_visibility_is_blocked_by_voxels() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(
+        Startup,
+        |mut voxel_world: VoxelWorld<DefaultWorld>,
+         mut chunk_map_update_buffer: ResMut<
+            ChunkMapUpdateBuffer<DefaultWorld, <DefaultWorld as VoxelWorldConfig>::MaterialIndex>,
+        >| {
+            // A roof over a single column
+            voxel_world.set_voxel(IVec3::new(0, 10, 0), WorldVoxel::Solid(1));
+
+            chunk_map_update_buffer.push((
+                IVec3::ZERO,
+                ChunkData {
+                    position: IVec3::ZERO,
+                    voxels: Some(std::sync::Arc::new([WorldVoxel::Unset; 39304].into())),
+                    voxels_hash: 0,
+                    is_full: false,
+                    is_empty: false,
+                    fill_type: FillType::Mixed,
+                    entity: Entity::PLACEHOLDER,
+                    shape: Default::default(),
+                },
+                ChunkWillSpawn::<DefaultWorld>::new(IVec3::ZERO, Entity::PLACEHOLDER),
+            ));
+        },
+    );
+
+    app.update();
+
+    app.add_systems(Update, |voxel_world: VoxelWorld<DefaultWorld>| {
+        let up = Vec3::Y;
+        assert_eq!(voxel_world.sun_visibility(Vec3::splat(0.5), up), 0.0);
+        assert_eq!(
+            voxel_world.sun_visibility(Vec3::new(5.5, 0.5, 5.5), up),
+            1.0
+        );
+
+        // The voxel the position is in doesn't count
+        assert_eq!(
+            voxel_world.sun_visibility(Vec3::new(0.5, 10.5, 0.5), up),
+            1.0
+        );
+
+        // Straddling the edge of the roof gives partial visibility
+        let edge = voxel_world.sun_visibility(Vec3::new(1.0, 0.5, 0.5), up);
+        assert!(edge > 0.0 && edge < 1.0);
+    });
+
+    app.update();
+}
+
+#[test]
+fn chunk_meshes_can_drop_cpu_data() {
+    use crate::mesh_cache::MeshRef;
+    use bevy::render::render_asset::RenderAssetUsages;
+
+    #[derive(Resource, Clone, Default)]
+    struct GpuOnlyWorld;
+
+    impl VoxelWorldConfig for GpuOnlyWorld {
+        type MaterialIndex = u8;
+
+        fn startup_preseed_radius(&self) -> u32 {
+            1
+        }
+
+        fn chunk_mesh_asset_usage(&self) -> RenderAssetUsages {
+            RenderAssetUsages::RENDER_WORLD
+        }
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|pos| {
+                    if pos.y < 0 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = _mesh_test_app::<GpuOnlyWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<GpuOnlyWorld>::default(),
+        ));
+    });
+
+    // Pre-seeded chunks are meshed on the second frame
+    app.update();
+    app.update();
+
+    let world = app.world_mut();
+    let meshes = world
+        .query::<&MeshRef>()
+        .iter(world)
+        .map(|mesh_ref| mesh_ref.0.clone())
+        .collect::<Vec<_>>();
+    assert!(!meshes.is_empty());
+
+    let mesh_assets = world.resource::<Assets<Mesh>>();
+    for handle in meshes {
+        let mesh = mesh_assets.get(&*handle).unwrap();
+        assert_eq!(mesh.asset_usage, RenderAssetUsages::RENDER_WORLD);
+    }
+}
+
+#[test]
+fn chunk_mesh_bounds_come_from_the_chunk_thread() {
+    use crate::mesh_cache::MeshRef;
+    use bevy::render::primitives::Aabb;
+
+    #[derive(Resource, Clone, Default)]
+    struct BoundsWorld;
+
+    impl VoxelWorldConfig for BoundsWorld {
+        type MaterialIndex = u8;
+
+        fn startup_preseed_radius(&self) -> u32 {
+            1
+        }
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|pos| {
+                    if pos.y < 0 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = _mesh_test_app::<BoundsWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<BoundsWorld>::default(),
+        ));
+    });
+
+    // Pre-seeded chunks are meshed on the second frame
+    app.update();
+    app.update();
+
+    // Bevy's own bounds calculation is not part of the minimal plugins, so the bounds can only
+    // have come with the chunk task
+    let world = app.world_mut();
+    let bounds = world
+        .query_filtered::<&Aabb, With<MeshRef>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    assert!(!bounds.is_empty());
+    for aabb in bounds {
+        // The surface is a flat plane, so the bounds have no height
+        assert_eq!(aabb.min().y, aabb.max().y);
+    }
+}
+
+#[test]
+fn voxels_have_a_stable_encoding() {
+    assert_eq!(WorldVoxel::<u8>::Unset.to_bytes(), [0]);
+    assert_eq!(WorldVoxel::<u8>::Air.to_bytes(), [1]);
+    assert_eq!(WorldVoxel::Solid(5u8).to_bytes(), [2, 5]);
+    // Material indexes are varints, whatever the width of the index type
+    assert_eq!(WorldVoxel::Solid(300u16).to_bytes(), [2, 0xac, 0x02]);
+    assert_eq!(
+        WorldVoxel::Solid(200u8).to_bytes(),
+        WorldVoxel::Solid(200u32).to_bytes()
+    );
+
+    let mut bytes = Vec::new();
+    for voxel in [
+        WorldVoxel::Solid(7u8),
+        WorldVoxel::Air,
+        WorldVoxel::Solid(255),
+    ] {
+        voxel.encode(&mut bytes).unwrap();
+    }
+    let mut r = bytes.as_slice();
+    let decoded: Vec<WorldVoxel<u16>> = (0..3)
+        .map(|_| WorldVoxel::decode(&mut r).unwrap())
+        .collect();
+    assert_eq!(
+        decoded,
+        [
+            WorldVoxel::Solid(7),
+            WorldVoxel::Air,
+            WorldVoxel::Solid(255)
+        ]
+    );
+
+    // Indexes that don't fit the index type don't decode
+    assert!(WorldVoxel::<u8>::from_bytes(&WorldVoxel::Solid(300u16).to_bytes()).is_err());
+    assert!(WorldVoxel::<u8>::from_bytes(&[3]).is_err());
+    assert!(WorldVoxel::<u32>::from_bytes(&[2, 0xff, 0xff, 0xff, 0xff, 0x7f]).is_err());
+}
+
+#[test]
+fn file_chunk_persistence_round_trips_edits() {
+    use crate::chunk_persistence::load_persisted_chunk;
+    use crate::voxel_world_internal::ModifiedVoxelMap;
+    use bevy::utils::HashSet;
+    use std::sync::RwLock;
+
+    let directory =
+        std::env::temp_dir().join(format!("bevy_voxel_world_chunks_{}", std::process::id()));
+    let persistence = FileChunkPersistence::<u8>::new(&directory);
+
+    let chunk_position = IVec3::new(-1, 2, 0);
+    let origin = chunk_position * 32;
+    let mut voxels = vec![
+        (origin, WorldVoxel::Solid(3)),
+        (origin + IVec3::new(31, 31, 31), WorldVoxel::Air),
+    ];
+    // A filled run, to exercise the run-length encoding
+    for x in 0..32 {
+        voxels.push((origin + IVec3::new(x, 5, 7), WorldVoxel::Solid(200)));
+    }
+
+    persistence.save(chunk_position, &voxels);
+    let mut loaded = persistence.load(chunk_position).unwrap();
+    assert!(persistence.load(IVec3::ZERO).is_none());
+
+    voxels.sort_by_key(|(p, _)| (p.x, p.y, p.z));
+    loaded.sort_by_key(|(p, _)| (p.x, p.y, p.z));
+    assert_eq!(loaded, voxels);
+
+    // Edits made before the chunk got loaded win over the saved ones, and chunks only get
+    // loaded once
+    let persisted_chunks = RwLock::new(HashSet::new());
+    let mut modified_voxels = ModifiedVoxelMap::default();
+    modified_voxels.insert(origin, WorldVoxel::Solid(9));
+    let modified_voxels = RwLock::new(modified_voxels);
+    load_persisted_chunk(
+        &persistence,
+        &persisted_chunks,
+        &modified_voxels,
+        chunk_position,
+    );
+    assert_eq!(modified_voxels.read().unwrap().len(), voxels.len());
+    assert_eq!(
+        modified_voxels.read().unwrap().get(&origin),
+        Some(&WorldVoxel::Solid(9))
+    );
+    assert_eq!(
+        modified_voxels.read().unwrap().chunks().collect::<Vec<_>>(),
+        vec![chunk_position]
+    );
+
+    modified_voxels
+        .write()
+        .unwrap()
+        .remove_chunk(chunk_position);
+    load_persisted_chunk(
+        &persistence,
+        &persisted_chunks,
+        &modified_voxels,
+        chunk_position,
+    );
+    assert!(modified_voxels.read().unwrap().is_empty());
+
+    // Chunks are marked as loaded only after their voxels are merged, so that chunk threads
+    // never see a loaded chunk without its voxels
+    struct CheckingPersistence<'a> {
+        files: &'a FileChunkPersistence<u8>,
+        persisted_chunks: &'a RwLock<HashSet<IVec3>>,
+    }
+
+    impl ChunkPersistence<u8> for CheckingPersistence<'_> {
+        fn load(&self, chunk_position: IVec3) -> Option<Vec<(IVec3, WorldVoxel<u8>)>> {
+            assert!(!self
+                .persisted_chunks
+                .read()
+                .unwrap()
+                .contains(&chunk_position));
+            self.files.load(chunk_position)
+        }
+
+        fn save(&self, chunk_position: IVec3, voxels: &[(IVec3, WorldVoxel<u8>)]) {
+            self.files.save(chunk_position, voxels);
+        }
+    }
+
+    let persisted_chunks = RwLock::new(HashSet::new());
+    let modified_voxels = RwLock::new(ModifiedVoxelMap::default());
+    let checking = CheckingPersistence {
+        files: &persistence,
+        persisted_chunks: &persisted_chunks,
+    };
+    load_persisted_chunk(
+        &checking,
+        &persisted_chunks,
+        &modified_voxels,
+        chunk_position,
+    );
+    assert!(persisted_chunks.read().unwrap().contains(&chunk_position));
+    assert_eq!(modified_voxels.read().unwrap().len(), voxels.len());
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn greedy_meshing_merges_flat_ground() {
+    use crate::configuration::MeshingAlgorithm;
+    use crate::meshing::{generate_chunk_mesh, MeshingOptions};
+    use ndshape::ConstShape;
+
+    // A single layer of ground, extending into the padding on all sides
+    let mut voxels = [WorldVoxel::Air; crate::chunk::PaddedChunkShape::SIZE as usize];
+    for x in 0..34 {
+        for z in 0..34 {
+            voxels[crate::chunk::PaddedChunkShape::linearize([x, 5, z]) as usize] =
+                WorldVoxel::Solid(0);
+        }
+    }
+    // One voxel with another material breaks up the top face
+    voxels[crate::chunk::PaddedChunkShape::linearize([10, 5, 10]) as usize] = WorldVoxel::Solid(1);
+    let voxels = std::sync::Arc::new(voxels);
+
+    #[derive(Resource, Clone, Default)]
+    struct GreedyWorld;
+
+    impl VoxelWorldConfig for GreedyWorld {
+        type MaterialIndex = u8;
+
+        fn meshing_algorithm(&self) -> MeshingAlgorithm {
+            MeshingAlgorithm::Greedy
+        }
+
+        fn texture_index_mapper(&self) -> TextureIndexMapperFn {
+            std::sync::Arc::new(|material| [material as u32; 3])
+        }
+    }
+
+    let greedy_options = MeshingOptions::from_config(&GreedyWorld);
+    let mut simple_options = greedy_options.clone();
+    simple_options.algorithm = MeshingAlgorithm::Simple;
+
+    let simple = generate_chunk_mesh(
+        voxels.clone(),
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::ZERO,
+        &simple_options,
+    );
+    let greedy = generate_chunk_mesh(
+        voxels,
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::ZERO,
+        &greedy_options,
+    );
+
+    // 32 * 32 top and bottom faces, each one quad
+    assert_eq!(simple.count_vertices(), 2 * 32 * 32 * 4);
+
+    // Top and bottom are each split up into at most four quads around the odd voxel, plus the
+    // odd voxel itself
+    assert!(greedy.count_vertices() <= 2 * 5 * 4);
+    assert_eq!(
+        _mesh_tex_indexes(&greedy)
+            .iter()
+            .filter(|idx| **idx == [1, 1, 1])
+            .count(),
+        2 * 4
+    );
+}
+
+#[test]
+fn world_uv_mode_maps_faces_by_world_position() {
+    use crate::configuration::UvMode;
+    use crate::mesh_cache::MeshCacheScope;
+    use crate::meshing::{generate_chunk_mesh, MeshingOptions};
+    use bevy::render::mesh::VertexAttributeValues;
+
+    #[derive(Resource, Clone, Default)]
+    struct WorldUvWorld;
+
+    impl VoxelWorldConfig for WorldUvWorld {
+        type MaterialIndex = u8;
+
+        fn uv_mode(&self) -> UvMode {
+            UvMode::World { tile_size: 8 }
+        }
+    }
+
+    #[derive(Resource, Clone, Default)]
+    struct LargeTileWorld;
+
+    impl VoxelWorldConfig for LargeTileWorld {
+        type MaterialIndex = u8;
+
+        fn uv_mode(&self) -> UvMode {
+            UvMode::World { tile_size: 64 }
+        }
+    }
+
+    let top_face_uvs = |mesh: &Mesh| {
+        let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        else {
+            panic!("mesh has no normals");
+        };
+        let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute(Mesh::ATTRIBUTE_UV_0)
+        else {
+            panic!("mesh has no uvs");
+        };
+        let mut top_uvs = normals
+            .iter()
+            .zip(uvs)
+            .filter(|(normal, _)| normal[1] > 0.0)
+            .map(|(_, uv)| Vec2::from(*uv))
+            .collect::<Vec<_>>();
+        top_uvs.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+        top_uvs
+    };
+
+    // The voxel at world (32, 0, 0) starts a new tile, so its top face covers the first eighth
+    let options = MeshingOptions::from_config(&WorldUvWorld);
+    let mesh = generate_chunk_mesh(
+        _single_voxel_chunk(WorldVoxel::Solid(0)),
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::new(1, 0, 0),
+        &options,
+    );
+    let wrapped = top_face_uvs(&mesh)
+        .iter()
+        .map(|uv| (*uv - Vec2::ONE) * 8.0)
+        .collect::<Vec<_>>();
+    assert_eq!(wrapped, [Vec2::ZERO, Vec2::Y, Vec2::X, Vec2::ONE]);
+
+    // Per face UVs cover the whole texture
+    let mut per_face_options = options.clone();
+    per_face_options.uv_mode = UvMode::PerFace;
+    let mesh = generate_chunk_mesh(
+        _single_voxel_chunk(WorldVoxel::Solid(0)),
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::new(1, 0, 0),
+        &per_face_options,
+    );
+    assert!(top_face_uvs(&mesh)
+        .iter()
+        .all(|uv| uv.cmpge(Vec2::ZERO).all() && uv.cmple(Vec2::ONE).all()));
+    assert!(top_face_uvs(&mesh).contains(&Vec2::ONE));
+
+    // Chunks at the same spot in a tile can share meshes, others can't
+    let scope = MeshCacheScope::from_config(&WorldUvWorld);
+    assert_eq!(scope.key(1, IVec3::ZERO), scope.key(1, IVec3::new(1, 0, 0)));
+    let scope = MeshCacheScope::from_config(&LargeTileWorld);
+    assert_ne!(scope.key(1, IVec3::ZERO), scope.key(1, IVec3::new(1, 0, 0)));
+    assert_eq!(scope.key(1, IVec3::ZERO), scope.key(1, IVec3::new(2, 0, 0)));
+}
+
+#[test]
+fn atlas_texture_mode_maps_faces_into_their_tile() {
+    use crate::configuration::{MeshingAlgorithm, VoxelTextureMode};
+    use crate::meshing::{generate_chunk_mesh, MeshingOptions};
+    use bevy::render::mesh::VertexAttributeValues;
+    use ndshape::ConstShape;
+
+    #[derive(Resource, Clone, Default)]
+    struct AtlasWorld;
+
+    impl VoxelWorldConfig for AtlasWorld {
+        type MaterialIndex = u8;
+
+        fn meshing_algorithm(&self) -> MeshingAlgorithm {
+            MeshingAlgorithm::Greedy
+        }
+
+        fn voxel_texture_mode(&self) -> VoxelTextureMode {
+            VoxelTextureMode::Atlas {
+                tile_size: 16,
+                columns: 4,
+                rows: 2,
+                padding: 1,
+            }
+        }
+
+        fn texture_index_mapper(&self) -> std::sync::Arc<dyn Fn(u8) -> [u32; 3] + Send + Sync> {
+            std::sync::Arc::new(|_| [5, 6, 0])
+        }
+    }
+
+    let options = MeshingOptions::from_config(&AtlasWorld);
+    let mesh = generate_chunk_mesh(
+        _single_voxel_chunk(WorldVoxel::Solid(0)),
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::ZERO,
+        &options,
+    );
+    let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+    else {
+        panic!("mesh has no normals");
+    };
+    let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute(Mesh::ATTRIBUTE_UV_0) else {
+        panic!("mesh has no uvs");
+    };
+
+    // The top is tile 5, in the second column of the second row, less one pixel of padding
+    let top_uvs = normals
+        .iter()
+        .zip(uvs)
+        .filter(|(normal, _)| normal[1] > 0.0)
+        .map(|(_, uv)| Vec2::from(*uv));
+    for uv in top_uvs {
+        assert!(uv.x == 17.0 / 64.0 || uv.x == 31.0 / 64.0);
+        assert!(uv.y == 17.0 / 32.0 || uv.y == 31.0 / 32.0);
+    }
+    // The sides are tile 6, next to it
+    assert!(normals
+        .iter()
+        .zip(uvs)
+        .filter(|(normal, _)| normal[1] == 0.0)
+        .all(|(_, uv)| (33.0 / 64.0..=47.0 / 64.0).contains(&uv[0])));
+
+    // Faces are not merged, since the texture can't repeat within a tile
+    let mut voxels = [WorldVoxel::Unset; crate::chunk::PaddedChunkShape::SIZE as usize];
+    for x in 1..=4 {
+        voxels[crate::chunk::PaddedChunkShape::linearize([x, 1, 1]) as usize] =
+            WorldVoxel::Solid(0);
+    }
+    let mesh = generate_chunk_mesh(
+        std::sync::Arc::new(voxels),
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::ZERO,
+        &options,
+    );
+    assert_eq!(mesh.count_vertices() / 4, 18);
+}
+
+#[test]
+fn chunk_timings_report_rolling_percentiles() {
+    use crate::chunk_timings::ChunkTimings;
+    use std::time::Duration;
+
+    let timings = ChunkTimings::<DefaultWorld>::default();
+    assert_eq!(timings.generation().samples, 0);
+
+    for ms in 1..=100 {
+        timings.record_generation(Duration::from_millis(ms));
+    }
+    timings.record_meshing(Duration::from_millis(7));
+
+    let generation = timings.generation();
+    assert_eq!(generation.samples, 100);
+    assert_eq!(generation.p50, Duration::from_millis(51));
+    assert_eq!(generation.p90, Duration::from_millis(91));
+    assert_eq!(generation.max, Duration::from_millis(100));
+    assert_eq!(timings.meshing().p99, Duration::from_millis(7));
+
+    // Old samples fall out of the window
+    for _ in 0..1000 {
+        timings.record_generation(Duration::from_millis(2));
+    }
+    assert_eq!(timings.generation().max, Duration::from_millis(2));
+}
+
+#[test]
+fn lod_chunks_sample_at_a_stride() {
+    use crate::chunk::{ChunkTask, PaddedChunkShape};
+    use crate::chunk_storage::ChunkVoxels;
+    use crate::meshing::MeshingOptions;
+    use crate::voxel_world_internal::ModifiedVoxels;
+    use ndshape::ConstShape;
+
+    // Bumpy ground, with a different height for every voxel column
+    let height = |pos: IVec3| (pos.x * 7 + pos.z * 3).rem_euclid(5);
+    let generate = |lod_level: u8| {
+        let mut chunk_task = ChunkTask::<DefaultWorld, u8>::new(
+            Entity::PLACEHOLDER,
+            IVec3::ZERO,
+            ModifiedVoxels::default(),
+        );
+        chunk_task.lod_level = lod_level;
+
+        chunk_task.generate(move |pos: IVec3| {
+            assert!(lod_level == 0 || pos.rem_euclid(IVec3::splat(4)) == IVec3::ZERO);
+            if pos.y < height(pos) {
+                WorldVoxel::Solid(0)
+            } else {
+                WorldVoxel::Air
+            }
+        });
+        chunk_task.mesh(&MeshingOptions::from_config(&DefaultWorld));
+        chunk_task
+    };
+
+    let full = generate(0);
+    let lod = generate(2);
+
+    // Every 4x4x4 block of the LOD chunk holds a single voxel
+    let voxels = lod.voxels.clone().unwrap();
+    for i in 0..PaddedChunkShape::SIZE {
+        let padded_pos = IVec3::from(PaddedChunkShape::delinearize(i).map(|v| v as i32));
+        let block = (padded_pos - 1).div_euclid(IVec3::splat(4)) * 4 + 1;
+        if block.cmpge(IVec3::ZERO).all() {
+            let block_index = PaddedChunkShape::linearize(block.as_uvec3().to_array());
+            assert_eq!(voxels[i as usize], voxels[block_index as usize]);
+        }
+    }
+
+    // The chunk data only keeps one voxel per block, and reads back the same voxels
+    let stored = lod.chunk_data.voxels.clone().unwrap();
+    let ChunkVoxels::Sampled { side, .. } = stored.as_ref() else {
+        panic!("expected sampled voxels");
+    };
+    assert_eq!(*side, 10);
+    for i in 0..PaddedChunkShape::SIZE as usize {
+        assert_eq!(stored.get(i), voxels[i]);
+    }
+
+    let vertex_count =
+        |task: &ChunkTask<DefaultWorld, u8>| task.mesh.as_ref().unwrap().count_vertices();
+    assert!(vertex_count(&lod) * 4 < vertex_count(&full));
+}
+
+#[test]
+fn soft_despawn_hides_chunks_in_between_distances() {
+    #[derive(Resource, Clone, Default)]
+    struct SoftDespawnWorld;
+
+    impl VoxelWorldConfig for SoftDespawnWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            2
+        }
+
+        fn soft_despawn_distance(&self) -> Option<u32> {
+            Some(8)
+        }
+
+        fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
+            ChunkDespawnStrategy::FarAway
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<SoftDespawnWorld>::minimal(),
+    ));
+    let camera = app
+        .world_mut()
+        .spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<SoftDespawnWorld>::default(),
+        ))
+        .id();
+
+    let move_camera = |app: &mut App, position: Vec3| {
+        *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+            GlobalTransform::from_translation(position);
+        for _ in 0..3 {
+            app.update();
+        }
+    };
+    let chunk_at_origin = |app: &mut App| {
+        app.world_mut()
+            .query::<(&Chunk<SoftDespawnWorld>, Has<HiddenChunk>)>()
+            .iter(app.world())
+            .find(|(chunk, _)| chunk.position == IVec3::ZERO)
+            .map(|(_, is_hidden)| is_hidden)
+    };
+
+    move_camera(&mut app, Vec3::splat(16.0));
+    assert_eq!(chunk_at_origin(&mut app), Some(false));
+
+    // Out of range, but within the soft despawn distance
+    move_camera(&mut app, Vec3::new(5.0 * 32.0, 16.0, 16.0));
+    assert_eq!(chunk_at_origin(&mut app), Some(true));
+
+    // Back in range
+    move_camera(&mut app, Vec3::splat(16.0));
+    assert_eq!(chunk_at_origin(&mut app), Some(false));
+
+    // Beyond the soft despawn distance
+    move_camera(&mut app, Vec3::new(20.0 * 32.0, 16.0, 16.0));
+    assert_eq!(chunk_at_origin(&mut app), None);
+}
+
+#[test]
+fn streaming_runs_in_the_chosen_schedule() {
+    use bevy::ecs::schedule::ScheduleLabel;
+
+    #[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+    struct Tick;
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<DefaultWorld>::minimal().in_schedule(Tick),
+    ))
+    .add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<DefaultWorld>::default(),
+        ));
+    });
+
+    let chunk_count = |app: &mut App| {
+        app.world_mut()
+            .query::<&Chunk<DefaultWorld>>()
+            .iter(app.world())
+            .count()
+    };
+
+    for _ in 0..3 {
+        app.update();
+    }
+    assert_eq!(chunk_count(&mut app), 0);
+
+    app.world_mut().run_schedule(Tick);
+    assert!(chunk_count(&mut app) > 0);
+}
+
+#[test]
+fn chunk_entities_are_reused_from_the_pool() {
+    use bevy::utils::HashSet;
+
+    #[derive(Component)]
+    struct AppMarker;
+
+    #[derive(Resource, Clone, Default)]
+    struct PooledWorld;
+
+    impl VoxelWorldConfig for PooledWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            2
+        }
+
+        fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
+            ChunkDespawnStrategy::FarAway
+        }
+
+        fn chunk_entity_pool_size(&self) -> usize {
+            1000
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<PooledWorld>::minimal()));
+    let camera = app
+        .world_mut()
+        .spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<PooledWorld>::default(),
+        ))
+        .id();
+    *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+        GlobalTransform::from_translation(Vec3::splat(16.0));
+    for _ in 0..3 {
+        app.update();
+    }
+
+    let chunk_entities = |app: &mut App| {
+        app.world_mut()
+            .query::<(Entity, &Chunk<PooledWorld>)>()
+            .iter(app.world())
+            .map(|(entity, chunk)| {
+                assert_eq!(chunk.entity, entity);
+                entity
+            })
+            .collect::<HashSet<_>>()
+    };
+    let first_chunks = chunk_entities(&mut app);
+    assert!(!first_chunks.is_empty());
+    for entity in &first_chunks {
+        app.world_mut().entity_mut(*entity).insert(AppMarker);
+    }
+
+    // Move far away twice. The chunks around the origin are despawned into the pool when the
+    // camera leaves, and the chunks at the second stop are spawned from it.
+    for x in [20.0, 40.0] {
+        *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+            GlobalTransform::from_translation(Vec3::new(x * 32.0, 16.0, 16.0));
+        for _ in 0..3 {
+            app.update();
+        }
+    }
+
+    let second_chunks = chunk_entities(&mut app);
+    assert!(!second_chunks.is_empty());
+    assert!(second_chunks
+        .iter()
+        .any(|entity| first_chunks.contains(entity)));
+
+    // Reused entities start out clean
+    assert_eq!(
+        app.world_mut()
+            .query_filtered::<(), With<AppMarker>>()
+            .iter(app.world())
+            .count(),
+        0
+    );
+    let world_root = app
+        .world_mut()
+        .query_filtered::<Entity, With<crate::voxel_world_internal::WorldRoot<PooledWorld>>>()
+        .single(app.world());
+    for entity in second_chunks {
+        assert_eq!(
+            app.world().get::<Parent>(entity).map(|parent| parent.get()),
+            Some(world_root)
+        );
+    }
+}
+
+#[test]
+fn despawns_are_spread_over_frames() {
+    #[derive(Resource, Clone, Default)]
+    struct DespawnBudgetWorld;
+
+    impl VoxelWorldConfig for DespawnBudgetWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            2
+        }
+
+        fn max_despawn_per_frame(&self) -> usize {
+            4
+        }
+
+        fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
+            ChunkDespawnStrategy::FarAway
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<DespawnBudgetWorld>::minimal(),
+    ));
+    let camera = app
+        .world_mut()
+        .spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<DespawnBudgetWorld>::default(),
+        ))
+        .id();
+    *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+        GlobalTransform::from_translation(Vec3::splat(16.0));
+    for _ in 0..3 {
+        app.update();
+    }
+
+    let origin_chunks = |app: &mut App| {
+        app.world_mut()
+            .query::<(
+                &Chunk<DespawnBudgetWorld>,
+                Has<NeedsDespawn>,
+                Option<&Visibility>,
+            )>()
+            .iter(app.world())
+            .filter(|(chunk, _, _)| chunk.position.length_squared() <= 1)
+            .map(|(_, retiring, visibility)| (retiring, visibility.copied()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(origin_chunks(&mut app).len(), 7);
+
+    *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+        GlobalTransform::from_translation(Vec3::new(20.0 * 32.0, 16.0, 16.0));
+    app.update();
+
+    // Everything around the origin is retired and hidden at once, but only a few chunks are
+    // despawned each frame
+    let retiring = origin_chunks(&mut app);
+    assert!(!retiring.is_empty());
+    assert!(retiring
+        .iter()
+        .all(|(retiring, visibility)| *retiring && *visibility == Some(Visibility::Hidden)));
+
+    let mut frames = 0;
+    let mut remaining = app
+        .world_mut()
+        .query_filtered::<(), With<NeedsDespawn>>()
+        .iter(app.world())
+        .count();
+    while remaining > 0 {
+        app.update();
+        let now_remaining = app
+            .world_mut()
+            .query_filtered::<(), With<NeedsDespawn>>()
+            .iter(app.world())
+            .count();
+        assert!(remaining - now_remaining <= 4);
+        remaining = now_remaining;
+        frames += 1;
+    }
+    assert!(frames > 1);
+    assert!(origin_chunks(&mut app).is_empty());
+}
+
+#[test]
+fn retired_chunks_are_kept_when_back_in_range() {
+    #[derive(Resource, Clone, Default)]
+    struct ReturningWorld;
+
+    impl VoxelWorldConfig for ReturningWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            2
+        }
+
+        fn max_despawn_per_frame(&self) -> usize {
+            1
+        }
+
+        fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
+            ChunkDespawnStrategy::FarAway
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<ReturningWorld>::minimal(),
+    ));
+    let camera = app
+        .world_mut()
+        .spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<ReturningWorld>::default(),
+        ))
+        .id();
+    *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+        GlobalTransform::from_translation(Vec3::splat(16.0));
+    for _ in 0..3 {
+        app.update();
+    }
+
+    let origin_chunks = |app: &mut App| {
+        app.world_mut()
+            .query::<(
+                &Chunk<ReturningWorld>,
+                Has<NeedsDespawn>,
+                Has<HiddenChunk>,
+                Option<&Visibility>,
+            )>()
+            .iter(app.world())
+            .filter(|(chunk, ..)| chunk.position.length_squared() <= 1)
+            .map(|(_, retiring, hidden, visibility)| (retiring, hidden, visibility.copied()))
+            .collect::<Vec<_>>()
+    };
+
+    // Leave for a single frame, so most of the chunks around the origin are retired but not
+    // despawned yet
+    *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+        GlobalTransform::from_translation(Vec3::new(20.0 * 32.0, 16.0, 16.0));
+    app.update();
+    let retiring = origin_chunks(&mut app);
+    assert!(retiring.len() > 1);
+    assert!(retiring.iter().all(|(retiring, ..)| *retiring));
+
+    *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+        GlobalTransform::from_translation(Vec3::splat(16.0));
+    for _ in 0..3 {
+        app.update();
+    }
+
+    // The retired chunks are shown again instead of being despawned, and the despawned one is
+    // spawned again
+    let returned = origin_chunks(&mut app);
+    assert_eq!(returned.len(), 7);
+    assert!(returned.iter().all(|(retiring, hidden, visibility)| {
+        !*retiring && !*hidden && *visibility != Some(Visibility::Hidden)
+    }));
+}
+
+#[test]
+fn chunks_out_of_view_are_retired_once() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Resource, Clone, Default)]
+    struct OutOfViewWorld;
+
+    impl VoxelWorldConfig for OutOfViewWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            3
+        }
+
+        fn max_despawn_per_frame(&self) -> usize {
+            1
+        }
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|pos| {
+                    if pos.y < 16 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    // Stands in for the view frustum, which isn't checked without rendering. Once set, the
+    // chunks two or more chunks in front of the camera are out of view.
+    #[derive(Resource, Default)]
+    struct OutOfView(bool);
+
+    #[derive(Resource, Default)]
+    struct Despawns(Vec<IVec3>);
+
+    let mut app = _mesh_test_app::<OutOfViewWorld>();
+    app.init_resource::<OutOfView>()
+        .init_resource::<Despawns>()
+        .add_systems(
+            First,
+            |mut commands: Commands,
+             chunks: Query<&Chunk<OutOfViewWorld>>,
+             out_of_view: Res<OutOfView>| {
+                for chunk in chunks.iter() {
+                    let mut view_visibility = ViewVisibility::HIDDEN;
+                    if !out_of_view.0 || chunk.position.z > -2 {
+                        view_visibility.set();
+                    }
+                    commands.entity(chunk.entity).insert(view_visibility);
+                }
+            },
+        )
+        .add_systems(
+            Last,
+            |mut ev_chunk_will_despawn: EventReader<ChunkWillDespawn<OutOfViewWorld>>,
+             mut despawns: ResMut<Despawns>| {
+                despawns
+                    .0
+                    .extend(ev_chunk_will_despawn.read().map(|event| event.chunk_key));
+            },
+        );
+    let camera = app
+        .world_mut()
+        .spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<OutOfViewWorld>::default(),
+        ))
+        .id();
+    let look_to = |app: &mut App, direction: Vec3| {
+        *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() = GlobalTransform::from(
+            Transform::from_xyz(16.0, 16.0, 16.0).looking_to(direction, Vec3::Y),
+        );
+    };
+    let is_ready = |app: &mut App| {
+        app.world_mut()
+            .run_system_once(|voxel_world: VoxelWorld<OutOfViewWorld>| {
+                voxel_world.is_position_ready(IVec3::new(16, 8, -48))
+            })
+    };
+    let chunks_in_front = |app: &mut App| {
+        app.world_mut()
+            .query::<&Chunk<OutOfViewWorld>>()
+            .iter(app.world())
+            .map(|chunk| chunk.position)
+            .filter(|position| position.z <= -2)
+            .collect::<Vec<_>>()
+    };
+
+    look_to(&mut app, Vec3::NEG_Z);
+    assert!(_update_until(&mut app, is_ready));
+    let retiring = chunks_in_front(&mut app);
+    assert!(retiring.len() > 1);
+
+    // Turn around, so the chunks in front go out of view. They are retired all at once, and
+    // despawned one per frame. Those waiting for their turn are in range, but stay retired.
+    app.world_mut().resource_mut::<OutOfView>().0 = true;
+    look_to(&mut app, Vec3::Z);
+    for _ in 0..retiring.len() + 5 {
+        app.update();
+    }
+    assert!(chunks_in_front(&mut app).is_empty());
+    assert!(!is_ready(&mut app));
+    let despawns = &app.world().resource::<Despawns>().0;
+    for position in &retiring {
+        let count = despawns
+            .iter()
+            .filter(|despawn| *despawn == position)
+            .count();
+        assert_eq!(count, 1, "chunk {position} was retired {count} times");
+    }
+
+    // Turning back spawns the chunks again, and they become ready
+    app.world_mut().resource_mut::<OutOfView>().0 = false;
+    look_to(&mut app, Vec3::NEG_Z);
+    assert!(_update_until(&mut app, is_ready));
+}
+
+#[test]
+fn chunks_are_named_and_grouped_into_regions() {
+    #[derive(Resource, Clone, Default)]
+    struct RegionWorld;
+
+    impl VoxelWorldConfig for RegionWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            2
+        }
+
+        fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
+            ChunkDespawnStrategy::FarAway
+        }
+
+        fn chunk_hierarchy(&self) -> ChunkHierarchy {
+            ChunkHierarchy::Regions { region_size: 2 }
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<RegionWorld>::minimal()));
+    let camera = app
+        .world_mut()
+        .spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<RegionWorld>::default(),
+        ))
+        .id();
+    *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+        GlobalTransform::from_translation(Vec3::splat(16.0));
+    for _ in 0..3 {
+        app.update();
+    }
+
+    let mut chunks = app
+        .world_mut()
+        .query::<(&Chunk<RegionWorld>, &Name, &Parent)>();
+    let mut regions = app
+        .world_mut()
+        .query::<(&ChunkRegion<RegionWorld>, &Name)>();
+    let chunk_count = chunks.iter(app.world()).count();
+    assert!(chunk_count > 0);
+    for (chunk, name, parent) in chunks.iter(app.world()) {
+        assert_eq!(
+            name.as_str(),
+            format!("RegionWorld chunk {}", chunk.position)
+        );
+        let (region, region_name) = regions.get(app.world(), parent.get()).unwrap();
+        assert_eq!(region.position, chunk.position.div_euclid(IVec3::splat(2)));
+        assert_eq!(
+            region_name.as_str(),
+            format!("RegionWorld region {}", region.position)
+        );
+    }
+    assert!(regions.iter(app.world()).count() < chunk_count);
+
+    // Regions go away with their chunks
+    *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+        GlobalTransform::from_translation(Vec3::new(20.0 * 32.0, 16.0, 16.0));
+    for _ in 0..3 {
+        app.update();
+    }
+    let chunk_regions: std::collections::HashSet<Entity> = chunks
+        .iter(app.world())
+        .map(|(_, _, parent)| parent.get())
+        .collect();
+    assert_eq!(regions.iter(app.world()).count(), chunk_regions.len());
+    assert!(regions
+        .iter(app.world())
+        .all(|(region, _)| region.position.x >= 8));
+}
+
+#[test]
+fn prefetch_spawns_ahead_of_camera_motion() {
+    use crate::voxel_world_internal::SpawnFocus;
+
+    #[derive(Resource, Clone, Default)]
+    struct PrefetchWorld;
+
+    impl VoxelWorldConfig for PrefetchWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            4
+        }
+
+        fn prefetch_lookahead(&self) -> f32 {
+            1.0
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<PrefetchWorld>::minimal()));
+    app.world_mut().spawn((
+        Camera3dBundle::default(),
+        VoxelWorldCamera::<PrefetchWorld>::default(),
+        VoxelWorldMotionHint {
+            velocity: Vec3::X * 1000.0,
+        },
+    ));
+
+    for _ in 0..3 {
+        app.update();
+    }
+
+    // The look ahead is limited to half the spawning distance
+    let focus = app.world().resource::<SpawnFocus<PrefetchWorld>>();
+    assert_eq!(
+        focus.chunk_positions.values().collect::<Vec<_>>(),
+        [&IVec3::new(2, 0, 0)]
+    );
+
+    let chunk_positions: Vec<IVec3> = app
+        .world_mut()
+        .query::<&Chunk<PrefetchWorld>>()
+        .iter(app.world())
+        .map(|chunk| chunk.position)
+        .collect();
+    assert!(chunk_positions.contains(&IVec3::new(3, 0, 0)));
+}
+
+#[test]
+fn spawn_focus_rounds_negative_camera_positions_down() {
+    use crate::voxel_world_internal::{camera_chunk_neighborhood, SpawnFocus};
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<DefaultWorld>::minimal()));
+    let camera_position = Vec3::new(-5.0, 5.0, -CHUNK_SIZE_F - 5.0);
+    app.world_mut().spawn((
+        Camera3dBundle {
+            transform: Transform::from_translation(camera_position),
+            global_transform: GlobalTransform::from_translation(camera_position),
+            ..default()
+        },
+        VoxelWorldCamera::<DefaultWorld>::default(),
+    ));
+    app.update();
+
+    // The spawn focus is the chunk the camera is in, the same one that is guaranteed to spawn
+    let chunk_at_camera = camera_chunk_neighborhood(camera_position)[0];
+    assert_eq!(chunk_at_camera, IVec3::new(-1, 0, -2));
+    let focus = app.world().resource::<SpawnFocus<DefaultWorld>>();
+    assert_eq!(
+        focus.chunk_positions.values().collect::<Vec<_>>(),
+        [&chunk_at_camera]
+    );
+}
+
+#[test]
+fn chunks_spawn_around_all_cameras() {
+    #[derive(Resource, Clone, Default)]
+    struct SplitScreenWorld;
+
+    impl VoxelWorldConfig for SplitScreenWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            2
+        }
+
+        fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
+            ChunkDespawnStrategy::FarAway
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<SplitScreenWorld>::minimal(),
+    ));
+    let mut spawn_camera = |position: Vec3| {
+        app.world_mut()
+            .spawn((
+                Camera3dBundle::default(),
+                VoxelWorldCamera::<SplitScreenWorld>::default(),
+            ))
+            .insert(GlobalTransform::from_translation(position))
+            .id()
+    };
+    spawn_camera(Vec3::splat(16.0));
+    let second_camera = spawn_camera(Vec3::new(20.0 * 32.0 + 16.0, 16.0, 16.0));
+
+    let chunk_positions = |app: &mut App| {
+        app.update();
+        app.update();
+        app.world_mut()
+            .query::<&Chunk<SplitScreenWorld>>()
+            .iter(app.world())
+            .map(|chunk| chunk.position)
+            .collect::<Vec<_>>()
+    };
+
+    let chunks = chunk_positions(&mut app);
+    assert!(chunks.contains(&IVec3::ZERO));
+    assert!(chunks.contains(&IVec3::new(20, 0, 0)));
+
+    // Chunks are only despawned once they are out of range of both cameras
+    *app.world_mut()
+        .get_mut::<GlobalTransform>(second_camera)
+        .unwrap() = GlobalTransform::from_translation(Vec3::new(16.0, 16.0, 40.0 * 32.0 + 16.0));
+    let chunks = chunk_positions(&mut app);
+    assert!(chunks.contains(&IVec3::ZERO));
+    assert!(chunks.contains(&IVec3::new(0, 0, 40)));
+    assert!(!chunks.contains(&IVec3::new(20, 0, 0)));
+}
+
+#[test]
+fn world_bounds_limit_spawning_and_editing() {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    static LOOKUPS: AtomicUsize = AtomicUsize::new(0);
+    static OUT_OF_BOUNDS_LOOKUP: AtomicBool = AtomicBool::new(false);
+
+    #[derive(Resource, Clone, Default)]
+    struct IslandWorld;
+
+    impl VoxelWorldConfig for IslandWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            4
+        }
+
+        fn world_bounds(&self) -> Option<(IVec3, IVec3)> {
+            Some((IVec3::new(-1, 0, -1), IVec3::new(1, 0, 1)))
+        }
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|pos: IVec3| {
+                    LOOKUPS.fetch_add(1, Ordering::Relaxed);
+                    if !(0..32).contains(&pos.y) || !(-32..64).contains(&pos.x) {
+                        OUT_OF_BOUNDS_LOOKUP.store(true, Ordering::Relaxed);
+                    }
+                    if pos.y < 10 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<IslandWorld>::minimal()));
+    app.world_mut().spawn((
+        Camera3dBundle {
+            global_transform: GlobalTransform::from_translation(Vec3::new(16.0, 40.0, 16.0)),
+            ..default()
+        },
+        VoxelWorldCamera::<IslandWorld>::default(),
+    ));
+
+    for _ in 0..500 {
+        app.update();
+        if LOOKUPS.load(Ordering::Relaxed) >= 9 * 32 * 32 * 32 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    let chunk_positions = app
+        .world_mut()
+        .query::<&Chunk<IslandWorld>>()
+        .iter(app.world())
+        .map(|chunk| chunk.position)
+        .collect::<Vec<_>>();
+    assert_eq!(chunk_positions.len(), 9);
+    assert!(chunk_positions
+        .iter()
+        .all(|position| position.y == 0 && position.x.abs() <= 1 && position.z.abs() <= 1));
+    assert!(LOOKUPS.load(Ordering::Relaxed) > 0);
+    assert!(!OUT_OF_BOUNDS_LOOKUP.load(Ordering::Relaxed));
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<IslandWorld>| {
+        assert!(voxel_world.set_voxel(IVec3::new(-32, 31, 63), WorldVoxel::Solid(1)));
+        assert!(!voxel_world.set_voxel(IVec3::new(0, 32, 0), WorldVoxel::Solid(1)));
+        assert!(!voxel_world.set_voxel(IVec3::new(-33, 0, 0), WorldVoxel::Solid(1)));
+        assert!(!voxel_world.in_world_bounds(IVec3::new(0, -1, 0)));
+    });
+    app.update();
+}
+
+#[test]
+fn decals_are_meshed_with_their_chunk() {
+    use crate::mesh_cache::MeshRef;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    let mut app = _mesh_test_app::<DefaultWorld>();
+    app.add_systems(
+        Update,
+        Internals::<DefaultWorld>::update_chunk_decals
+            .after(Internals::<DefaultWorld>::spawn_meshes),
+    )
+    .add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<DefaultWorld>::default(),
+        ));
+    })
+    .add_systems(Startup, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        assert!(voxel_world.place_decal(IVec3::new(1, 0, 2), VoxelFace::Top, 5, Vec2::ONE));
+    });
+
+    let mut decal_mesh = None;
+    _update_until(&mut app, |app| {
+        decal_mesh = app
+            .world_mut()
+            .query::<(&ChunkDecalMesh<DefaultWorld>, &MeshRef)>()
+            .iter(app.world())
+            .next()
+            .map(|(decal_mesh, mesh_ref)| (decal_mesh.chunk_position, (*mesh_ref.0).clone()));
+        decal_mesh.is_some()
+    });
+
+    let (chunk_position, handle) = decal_mesh.expect("no decal mesh was spawned");
+    assert_eq!(chunk_position, IVec3::ZERO);
+
+    let meshes = app.world().resource::<Assets<Mesh>>();
+    let mesh = meshes.get(&handle).unwrap();
+    assert_eq!(mesh.count_vertices(), 4);
+    assert_eq!(_mesh_tex_indexes(mesh), vec![[5; 3]; 4]);
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        panic!("Mesh has no positions");
+    };
+    // The chunk mesh is offset by the padding, so voxel (1, 0, 2) is at (2, 1, 3) in the mesh
+    for position in positions {
+        assert_eq!(position[1], 2.0 + DECAL_OFFSET);
+        assert!((2.0..=3.0).contains(&position[0]));
+        assert!((3.0..=4.0).contains(&position[2]));
+    }
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.remove_decals(IVec3::new(1, 0, 2));
+    });
+    app.update();
+    app.update();
+    assert_eq!(
+        app.world_mut()
+            .query::<&ChunkDecalMesh<DefaultWorld>>()
+            .iter(app.world())
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn panicking_delegates_only_fail_their_chunk() {
+    #[derive(Resource, Clone, Default)]
+    struct PanickingWorld;
+
+    impl VoxelWorldConfig for PanickingWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            1
+        }
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|chunk_position| {
+                Box::new(move |pos: IVec3| {
+                    if chunk_position == IVec3::ZERO {
+                        panic!("no voxels here");
+                    }
+                    if pos.y < 0 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = _mesh_test_app::<PanickingWorld>();
+    app.world_mut().spawn((
+        Camera3dBundle {
+            global_transform: GlobalTransform::from_translation(Vec3::new(16.0, 16.0, 16.0)),
+            ..default()
+        },
+        VoxelWorldCamera::<PanickingWorld>::default(),
+    ));
+
+    let mut failed_reader = app
+        .world()
+        .resource::<Events<ChunkGenerationFailed<PanickingWorld>>>()
+        .get_reader();
+    let mut ready_reader = app
+        .world()
+        .resource::<Events<ChunkMeshReady<PanickingWorld>>>()
+        .get_reader();
+
+    let mut failed = Vec::new();
+    let mut meshed = Vec::new();
+    _update_until(&mut app, |app| {
+        failed.extend(failed_reader.read(app.world().resource()).map(
+            |event: &ChunkGenerationFailed<PanickingWorld>| {
+                (event.chunk_key, event.message.clone())
+            },
+        ));
+        meshed.extend(
+            ready_reader
+                .read(app.world().resource())
+                .map(|event: &ChunkMeshReady<PanickingWorld>| event.chunk_key),
+        );
+        !failed.is_empty() && meshed.contains(&IVec3::NEG_Y)
+    });
+
+    assert_eq!(failed, vec![(IVec3::ZERO, "no voxels here".to_string())]);
+    assert!(meshed.contains(&IVec3::NEG_Y));
+    assert!(!meshed.contains(&IVec3::ZERO));
+
+    let placeholders = app
+        .world_mut()
+        .query::<&ChunkErrorPlaceholder<PanickingWorld>>()
+        .iter(app.world())
+        .map(|placeholder| placeholder.chunk_position)
+        .collect::<Vec<_>>();
+    assert_eq!(placeholders, vec![IVec3::ZERO]);
+}
+
+#[test]
+fn hanging_delegates_time_out() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    static RELEASE: AtomicBool = AtomicBool::new(false);
+
+    #[derive(Resource, Clone, Default)]
+    struct HangingWorld;
+
+    impl VoxelWorldConfig for HangingWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            1
+        }
+
+        fn chunk_generation_timeout(&self) -> Option<Duration> {
+            Some(Duration::from_millis(50))
+        }
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|chunk_position| {
+                Box::new(move |pos: IVec3| {
+                    while chunk_position == IVec3::ZERO && !RELEASE.load(Ordering::Relaxed) {
+                        std::thread::sleep(Duration::from_millis(1));
+                    }
+                    if pos.y < 0 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = _mesh_test_app::<HangingWorld>();
+    app.world_mut().spawn((
+        Camera3dBundle {
+            global_transform: GlobalTransform::from_translation(Vec3::new(16.0, 16.0, 16.0)),
+            ..default()
+        },
+        VoxelWorldCamera::<HangingWorld>::default(),
+    ));
+
+    let mut reader = app
+        .world()
+        .resource::<Events<ChunkGenerationFailed<HangingWorld>>>()
+        .get_reader();
+    let mut failed = Vec::new();
+    _update_until(&mut app, |app| {
+        failed.extend(reader.read(app.world().resource()).map(
+            |event: &ChunkGenerationFailed<HangingWorld>| (event.chunk_key, event.message.clone()),
+        ));
+        !failed.is_empty()
+    });
+    RELEASE.store(true, Ordering::Relaxed);
+
+    // With few worker threads, chunks queued behind the stuck one may time out as well
+    assert!(failed
+        .iter()
+        .any(|(chunk_key, message)| *chunk_key == IVec3::ZERO && message.starts_with("timed out")));
+}
+
+#[test]
+fn generation_and_mesh_inserts_respect_budgets() {
+    #[derive(Resource, Clone, Default)]
+    struct BudgetWorld;
+
+    impl VoxelWorldConfig for BudgetWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            3
+        }
+
+        fn generation_task_budget(&self) -> usize {
+            2
+        }
+
+        fn max_mesh_inserts_per_frame(&self) -> usize {
+            1
+        }
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|pos: IVec3| {
+                    if pos.y < 0 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = _mesh_test_app::<BudgetWorld>();
+    app.world_mut().spawn((
+        Camera3dBundle {
+            global_transform: GlobalTransform::from_translation(Vec3::new(16.0, 16.0, 16.0)),
+            ..default()
+        },
+        VoxelWorldCamera::<BudgetWorld>::default(),
+    ));
+
+    let mut reader = app
+        .world()
+        .resource::<Events<ChunkMeshReady<BudgetWorld>>>()
+        .get_reader();
+    let mut meshed = Vec::new();
+    _update_until(&mut app, |app| {
+        let running_tasks = app
+            .world_mut()
+            .query::<&ChunkThread<BudgetWorld, u8>>()
+            .iter(app.world())
+            .count();
+        assert!(running_tasks <= 2);
+
+        let ready = reader
+            .read(
+                app.world()
+                    .resource::<Events<ChunkMeshReady<BudgetWorld>>>(),
+            )
+            .map(|event| event.chunk_key)
+            .collect::<Vec<_>>();
+        assert!(ready.len() <= 1);
+        meshed.extend(ready);
+
+        meshed.len() >= 10
+    });
+
+    assert!(meshed.len() >= 10);
+    // The chunk the camera is in and its neighbors come first
+    let camera_chunks = crate::voxel_world_internal::camera_chunk_neighborhood(Vec3::splat(16.0));
+    assert!(meshed[..7]
+        .iter()
+        .all(|chunk_position| camera_chunks.contains(chunk_position)));
+}
+
+#[test]
+fn finished_chunks_are_inserted_nearest_first() {
+    use std::time::Duration;
+
+    #[derive(Resource, Clone, Default)]
+    struct InsertOrderWorld;
+
+    impl VoxelWorldConfig for InsertOrderWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            3
+        }
+
+        fn max_mesh_inserts_per_frame(&self) -> usize {
+            1
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<InsertOrderWorld>::minimal(),
+    ))
+    .init_resource::<Assets<Mesh>>()
+    .insert_resource(LoadingTexture {
+        is_loaded: true,
+        handle: Handle::default(),
+    });
+    let camera_position = Vec3::splat(16.0);
+    app.world_mut().spawn((
+        Camera3dBundle {
+            global_transform: GlobalTransform::from_translation(camera_position),
+            ..default()
+        },
+        VoxelWorldCamera::<InsertOrderWorld>::default(),
+    ));
+
+    // Let every chunk finish generating before any gets inserted
+    for _ in 0..20 {
+        app.update();
+    }
+    for _ in 0..2000 {
+        let all_finished = app
+            .world_mut()
+            .query::<&ChunkThread<InsertOrderWorld, u8>>()
+            .iter(app.world())
+            .all(|thread| thread.is_finished());
+        if all_finished {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    app.add_systems(Update, Internals::<InsertOrderWorld>::spawn_meshes);
+
+    let mut reader = app
+        .world()
+        .resource::<Events<ChunkMeshReady<InsertOrderWorld>>>()
+        .get_reader();
+    let mut meshed = Vec::new();
+    for _ in 0..40 {
+        app.update();
+        meshed.extend(
+            reader
+                .read(
+                    app.world()
+                        .resource::<Events<ChunkMeshReady<InsertOrderWorld>>>(),
+                )
+                .map(|event| event.chunk_key),
+        );
+    }
+
+    // After the chunks around the camera, the chunks behind it are not left for last
+    assert!(meshed.len() >= 30);
+    let distance = |chunk_position: &IVec3| {
+        (chunk_position.as_vec3() * 32.0 + 16.0).distance(camera_position) as u32
+    };
+    assert!(meshed[7..]
+        .windows(2)
+        .all(|pair| distance(&pair[0]) <= distance(&pair[1])));
+}
+
+#[test]
+fn raycast_distance_stops_at_max_distance_and_filters_materials() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        // A glass pane in front of a wall
+        voxel_world.set_voxel(IVec3::new(0, 0, -5), WorldVoxel::Solid(9));
+        voxel_world.set_voxel(IVec3::new(0, 0, -10), WorldVoxel::Solid(1));
+    });
+    app.update();
+
+    app.add_systems(Update, |voxel_world: VoxelWorld<DefaultWorld>| {
+        let origin = Vec3::new(0.5, 0.5, 0.5);
+
+        assert_eq!(
+            voxel_world.raycast_distance(origin, Vec3::NEG_Z, 20.0),
+            Some((
+                IVec3::new(0, 0, -5),
+                WorldVoxel::Solid(9),
+                Some(VoxelFace::Forward)
+            ))
+        );
+        assert_eq!(voxel_world.raycast_distance(origin, Vec3::NEG_Z, 4.0), None);
+        assert_eq!(
+            voxel_world.raycast_distance_filtered(origin, Vec3::NEG_Z, 20.0, |m| m != 9),
+            Some((
+                IVec3::new(0, 0, -10),
+                WorldVoxel::Solid(1),
+                Some(VoxelFace::Forward)
+            ))
+        );
+
+        // Starting inside a voxel hits it right away
+        assert_eq!(
+            voxel_world.raycast_distance(Vec3::new(0.5, 0.5, -4.5), Vec3::X, 20.0),
+            Some((IVec3::new(0, 0, -5), WorldVoxel::Solid(9), None))
+        );
+    });
+    app.update();
+}
+
+#[test]
+fn chunks_rise_into_place_when_spawned() {
+    use crate::chunk::SpawnTransition;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    #[derive(Resource, Clone, Default)]
+    struct RisingWorld;
+
+    impl VoxelWorldConfig for RisingWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            1
+        }
+
+        fn chunk_spawn_transition(&self) -> Option<ChunkSpawnTransition> {
+            Some(ChunkSpawnTransition::RiseFromBelow {
+                depth: 16.0,
+                duration: Duration::from_secs(1),
+            })
+        }
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|pos| {
+                    if pos.y < 0 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = _mesh_test_app::<RisingWorld>();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        100,
+    )))
+    .add_systems(
+        Update,
+        Internals::<RisingWorld>::animate_chunk_spawns
+            .after(Internals::<RisingWorld>::spawn_meshes),
+    )
+    .add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<RisingWorld>::default(),
+        ));
+    });
+
+    let chunk_base = |chunk: &Chunk<RisingWorld>| chunk.position.as_vec3() * 32.0 - 1.0;
+
+    // Wait for a chunk to get meshed, and check that it starts out below its place
+    let mut rising = None;
+    for _ in 0..500 {
+        app.update();
+        let world = app.world_mut();
+        rising = world
+            .query_filtered::<(&Chunk<RisingWorld>, &Transform), With<SpawnTransition<RisingWorld>>>()
+            .iter(world)
+            .map(|(chunk, transform)| (chunk.entity, chunk_base(chunk), transform.translation))
+            .next();
+        if rising.is_some() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    let (entity, base, translation) = rising.expect("no chunk got meshed");
+    assert!(translation.y < base.y);
+
+    for _ in 0..12 {
+        app.update();
+    }
+    let world = app.world();
+    assert!(world.get::<SpawnTransition<RisingWorld>>(entity).is_none());
+    assert_eq!(world.get::<Transform>(entity).unwrap().translation, base);
+}
+
+#[test]
+fn chunk_colliders_cover_solid_voxels() {
+    use crate::chunk::ChunkTask;
+    use crate::voxel_world_internal::ModifiedVoxels;
+
+    let collider = |chunk_position: IVec3, strategy: ChunkColliderStrategy<u8>| {
+        let mut chunk_task = ChunkTask::<DefaultWorld, u8>::new(
+            Entity::PLACEHOLDER,
+            chunk_position,
+            ModifiedVoxels::default(),
+        );
+        // The bottom half of the chunk below the origin is solid
+        chunk_task.generate(|pos: IVec3| {
+            if pos.y < -16 {
+                WorldVoxel::Solid(0)
+            } else {
+                WorldVoxel::Air
+            }
+        });
+        chunk_task.build_collider(&strategy);
+        chunk_task.collider
+    };
+
+    let below = IVec3::new(0, -1, 0);
+
+    assert_eq!(
+        collider(below, ChunkColliderStrategy::Cuboids),
+        Some(ChunkColliderShape::Cuboids(vec![(
+            Vec3::new(17.0, 9.0, 17.0),
+            Vec3::new(16.0, 8.0, 16.0)
+        )]))
+    );
+
+    match collider(below, ChunkColliderStrategy::Trimesh) {
+        Some(ChunkColliderShape::Trimesh { vertices, indices }) => {
+            assert!(!indices.is_empty());
+            assert!(indices
+                .iter()
+                .flatten()
+                .all(|i| (*i as usize) < vertices.len()));
+        }
+        other => panic!("expected a trimesh, got {:?}", other),
+    }
+
+    assert_eq!(collider(below, ChunkColliderStrategy::None), None);
+    assert_eq!(collider(IVec3::ZERO, ChunkColliderStrategy::Cuboids), None);
+    assert_eq!(collider(IVec3::ZERO, ChunkColliderStrategy::Trimesh), None);
+}
+
+#[test]
+fn chunk_material_config_supplies_the_material() {
+    use crate::rendering::VoxelWorldMaterialHandle;
+
+    #[derive(Resource, Clone, Default)]
+    struct TintedWorld {
+        tint: Color,
+    }
+
+    impl VoxelWorldConfig for TintedWorld {
+        type MaterialIndex = u8;
+    }
+
+    impl ChunkMaterialConfig for TintedWorld {
+        type ChunkMaterial = StandardMaterial;
+
+        fn chunk_material(&self, _world: &mut World) -> Self::ChunkMaterial {
+            StandardMaterial {
+                base_color: self.tint,
+                ..default()
+            }
+        }
+    }
+
+    let tint = Color::srgb(0.2, 0.4, 0.6);
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Shader>()
+        .init_asset::<StandardMaterial>()
+        .add_plugins(VoxelWorldPlugin::with_chunk_material(TintedWorld { tint }));
+
+    let world = app.world();
+    let handle = &world
+        .resource::<VoxelWorldMaterialHandle<StandardMaterial>>()
+        .handle;
+    let material = world.resource::<Assets<StandardMaterial>>().get(handle);
+    assert_eq!(material.unwrap().base_color, tint);
+    assert_eq!(world.resource::<TintedWorld>().tint, tint);
+}
+
+#[cfg(feature = "border_validation")]
+#[test]
+fn border_validation_catches_non_deterministic_delegates() {
+    use crate::debug::{BorderMismatches, VoxelWorldBorderValidationPlugin};
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    #[derive(Resource, Clone, Default)]
+    struct FlickeringWorld;
+
+    impl VoxelWorldConfig for FlickeringWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            1
+        }
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            static CALLS: AtomicU8 = AtomicU8::new(0);
+            Box::new(|_| {
+                // A different material every time a chunk is generated
+                let material = CALLS.fetch_add(1, Ordering::Relaxed);
+                Box::new(move |pos| {
+                    if pos.y < 0 {
+                        WorldVoxel::Solid(material)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = _mesh_test_app::<FlickeringWorld>();
+    app.add_plugins(VoxelWorldBorderValidationPlugin::<FlickeringWorld>::default());
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<FlickeringWorld>::default(),
+        ));
+    });
+
+    // Wait for neighboring chunks to get generated
+    _update_until(&mut app, |app| {
+        let border_mismatches = app.world().resource::<BorderMismatches<FlickeringWorld>>();
+        !border_mismatches.mismatches.is_empty()
+    });
+
+    let border_mismatches = app.world().resource::<BorderMismatches<FlickeringWorld>>();
+    assert!(!border_mismatches.mismatches.is_empty());
+    for mismatch in border_mismatches.mismatches.iter() {
+        assert!(mismatch.position.y < 0);
+        assert_ne!(mismatch.padding_voxel, mismatch.owner_voxel);
+        assert_eq!(
+            mismatch.chunk_position - mismatch.owner_position,
+            (mismatch.chunk_position - mismatch.owner_position).clamp(IVec3::NEG_ONE, IVec3::ONE)
+        );
+    }
+}
+
+#[cfg(feature = "picking")]
+#[test]
+fn picking_resolves_the_voxel_under_the_pointer() {
+    use crate::picking::{VoxelPickEvent, VoxelPickKind, VoxelWorldPickingPlugin};
+    use bevy::window::PrimaryWindow;
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<DefaultWorld>::minimal(),
+        VoxelWorldPickingPlugin::<DefaultWorld>::default(),
+    ))
+    .init_resource::<ButtonInput<MouseButton>>();
+
+    let mut window = Window::default();
+    window.resolution.set(200.0, 100.0);
+    window.set_cursor_position(Some(Vec2::new(100.0, 50.0)));
+    app.world_mut().spawn((window, PrimaryWindow));
+    app.world_mut().spawn((
+        Camera3dBundle {
+            global_transform: GlobalTransform::from_translation(Vec3::new(0.5, 0.5, 0.0)),
+            ..default()
+        },
+        VoxelWorldCamera::<DefaultWorld>::default(),
+    ));
+
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxel(IVec3::new(0, 0, -5), WorldVoxel::Solid(3));
+    });
+    app.update();
+
+    let mut reader = app
+        .world()
+        .resource::<Events<VoxelPickEvent<DefaultWorld>>>()
+        .get_reader();
+    let mut read_events = |app: &mut App| {
+        reader
+            .read(
+                app.world()
+                    .resource::<Events<VoxelPickEvent<DefaultWorld>>>(),
+            )
+            .map(|event| (event.kind, event.position, event.face, event.material))
+            .collect::<Vec<_>>()
+    };
+
+    app.world_mut()
+        .resource_mut::<ButtonInput<MouseButton>>()
+        .press(MouseButton::Left);
+    app.update();
+    assert_eq!(
+        read_events(&mut app),
+        vec![
+            (
+                VoxelPickKind::Over,
+                IVec3::new(0, 0, -5),
+                Some(VoxelFace::Forward),
+                3
+            ),
+            (
+                VoxelPickKind::Press(MouseButton::Left),
+                IVec3::new(0, 0, -5),
+                Some(VoxelFace::Forward),
+                3
+            ),
+        ]
+    );
+
+    // Moving the pointer off the voxel
+    app.world_mut()
+        .resource_mut::<ButtonInput<MouseButton>>()
+        .clear();
+    app.world_mut()
+        .query_filtered::<&mut Window, With<PrimaryWindow>>()
+        .single_mut(app.world_mut())
+        .set_cursor_position(Some(Vec2::new(1.0, 1.0)));
+    app.update();
+    assert_eq!(
+        read_events(&mut app),
+        vec![(
+            VoxelPickKind::Out,
+            IVec3::new(0, 0, -5),
+            Some(VoxelFace::Forward),
+            3
+        )]
+    );
+}
+
+#[test]
+fn shaped_voxels_are_meshed_with_their_own_geometry() {
+    use crate::meshing::{generate_chunk_mesh, MeshingOptions};
+    use ndshape::ConstShape;
+
+    const CUBE: u8 = 1;
+    const SLAB: u8 = 2;
+    const RAMP: u8 = 3;
+
+    // A cube with a slab next to it, and a ramp on its own
+    let mut voxels = [WorldVoxel::Unset; crate::chunk::PaddedChunkShape::SIZE as usize];
+    for (position, material) in [([1, 1, 1], CUBE), ([2, 1, 1], SLAB), ([1, 1, 3], RAMP)] {
+        voxels[crate::chunk::PaddedChunkShape::linearize(position) as usize] =
+            WorldVoxel::Solid(material);
+    }
+    let voxels = std::sync::Arc::new(voxels);
+
+    let mut options = MeshingOptions::from_config(&DefaultWorld);
+    assert_eq!(
+        generate_chunk_mesh(
+            voxels.clone(),
+            &crate::chunk::PaddedChunkShape {},
+            IVec3::ZERO,
+            &options
+        )
+        .count_vertices(),
+        16 * 4
+    );
+
+    options.voxel_shape = Some(std::sync::Arc::new(|material| match material {
+        SLAB => VoxelShape::Slab { top: false },
+        RAMP => VoxelShape::Ramp {
+            facing: VoxelFace::Right,
+        },
+        _ => VoxelShape::FullCube,
+    }));
+    assert!(options.meshes_full_chunks());
+    let mesh = generate_chunk_mesh(
+        voxels,
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::ZERO,
+        &options,
+    );
+
+    // The cube keeps its face against the slab, while the slab side against the cube is culled.
+    // The ramp has a bottom, a back wall, the slope and two triangles.
+    assert_eq!(mesh.count_vertices(), 6 * 4 + 5 * 4 + (3 * 4 + 2 * 3));
+
+    let Some(bevy::render::mesh::VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        panic!("Mesh has no positions");
+    };
+    let slab_top = positions
+        .iter()
+        .filter(|p| p[0] == 3.0 && p[2] <= 2.0)
+        .map(|p| p[1])
+        .fold(f32::MIN, f32::max);
+    assert_eq!(slab_top, 1.5);
+
+    let Some(bevy::render::mesh::VertexAttributeValues::Float32x3(normals)) =
+        mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+    else {
+        panic!("Mesh has no normals");
+    };
+    // The ramp rises towards +X, so its slope faces up and towards -X
+    assert!(normals
+        .iter()
+        .any(|n| Vec3::from(*n).distance(Vec3::new(-1.0, 1.0, 0.0).normalize()) < 1e-5));
+
+    // All triangles are wound counter-clockwise around their normal
+    let Some(bevy::render::mesh::Indices::U32(indices)) = mesh.indices() else {
+        panic!("Mesh has no indices");
+    };
+    for triangle in indices.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(positions[triangle[i] as usize]));
+        assert!(
+            (b - a)
+                .cross(c - a)
+                .dot(Vec3::from(normals[triangle[0] as usize]))
+                > 0.0
+        );
+    }
+}
+
+#[test]
+fn fluid_surfaces_are_lowered_below_the_shore() {
+    use crate::configuration::MeshingAlgorithm;
+    use ndshape::ConstShape;
+
+    const STONE: u8 = 0;
+    const WATER: u8 = 1;
+
+    let mut options = crate::meshing::MeshingOptions::from_config(&DefaultWorld);
+    options.algorithm = MeshingAlgorithm::Greedy;
+    options.material_group = Some(std::sync::Arc::new(|material| match material {
+        WATER => MaterialGroup::Fluid,
+        _ => MaterialGroup::Opaque,
+    }));
+
+    // Two water voxels, with a stone on top of the second one
+    let mut voxels = [WorldVoxel::Unset; crate::chunk::PaddedChunkShape::SIZE as usize];
+    for (position, material) in [([1, 1, 1], WATER), ([2, 1, 1], WATER), ([2, 2, 1], STONE)] {
+        voxels[crate::chunk::PaddedChunkShape::linearize(position) as usize] =
+            WorldVoxel::Solid(material);
+    }
+
+    let group_meshes = crate::meshing::generate_chunk_group_meshes(
+        std::sync::Arc::new(voxels),
+        &crate::chunk::PaddedChunkShape {},
+        IVec3::ZERO,
+        &options,
+        options.material_group.as_ref().unwrap(),
+    );
+    let (group, fluid_mesh) = &group_meshes[1];
+    assert_eq!(*group, MaterialGroup::Fluid);
+
+    // Fluid faces are not merged, and the top of the covered voxel is culled
+    assert_eq!(fluid_mesh.count_vertices(), 9 * 4);
+
+    let Some(bevy::render::mesh::VertexAttributeValues::Float32x3(positions)) =
+        fluid_mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        panic!("Mesh has no positions");
+    };
+    let top_at = |x: f32| {
+        positions
+            .iter()
+            .filter(|p| p[0] == x)
+            .map(|p| p[1])
+            .fold(f32::MIN, f32::max)
+    };
+    // The open voxel is lowered, while the one under the stone keeps its full height
+    assert_eq!(top_at(1.0), 1.875);
+    assert_eq!(top_at(3.0), 2.0);
+}
+
+#[test]
+fn voxel_lighting_floods_from_emitters_and_the_sky() {
+    use crate::chunk::ChunkTask;
+    use crate::meshing::MeshingOptions;
+    use crate::rendering::ATTRIBUTE_VOXEL_LIGHT;
+    use crate::voxel_world_internal::ModifiedVoxels;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    #[derive(Resource, Clone, Default)]
+    struct LitWorld;
+
+    impl VoxelWorldConfig for LitWorld {
+        type MaterialIndex = u8;
+
+        fn voxel_lighting(&self) -> Option<VoxelLighting> {
+            Some(VoxelLighting {
+                sky_light: true,
+                ..default()
+            })
+        }
+
+        fn voxel_emission(&self, index: u8) -> u8 {
+            if index == 2 {
+                MAX_LIGHT
+            } else {
+                0
+            }
+        }
+    }
+
+    let lit_mesh = |lookup: fn(IVec3) -> WorldVoxel<u8>| {
+        let meshing_options = MeshingOptions::from_config(&LitWorld);
+        let mut chunk_task = ChunkTask::<LitWorld, u8>::new(
+            Entity::PLACEHOLDER,
+            IVec3::ZERO,
+            ModifiedVoxels::default(),
+        );
+        chunk_task.generate(lookup);
+        chunk_task.light(&meshing_options, lookup);
+        chunk_task.mesh(&meshing_options);
+        chunk_task.mesh.unwrap()
+    };
+    // The light of the upward facing vertex at a position relative to the padded chunk
+    let floor_light = |mesh: &Mesh, position: [f32; 3]| {
+        let (
+            Some(VertexAttributeValues::Float32x3(positions)),
+            Some(VertexAttributeValues::Float32x3(normals)),
+            Some(VertexAttributeValues::Float32x2(lights)),
+        ) = (
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+            mesh.attribute(ATTRIBUTE_VOXEL_LIGHT),
+        )
+        else {
+            panic!("lit meshes have voxel light");
+        };
+        let index = (0..positions.len())
+            .find(|&i| positions[i] == position && normals[i] == [0.0, 1.0, 0.0])
+            .unwrap();
+        lights[index]
+    };
+
+    // A sealed cave reaching into the next chunk, with an emissive voxel on its floor over there
+    let cave = lit_mesh(|pos| {
+        if pos == IVec3::new(-4, 4, 8) {
+            WorldVoxel::Solid(2)
+        } else if (-6..12).contains(&pos.x) && (4..12).contains(&pos.y) && (4..12).contains(&pos.z)
+        {
+            WorldVoxel::Air
+        } else {
+            WorldVoxel::Solid(1)
+        }
+    });
+    // The floor corner at (0, 4, 8) touches open voxels 3, 4, 4 and 5 steps from the emitter
+    let [block, sky] = floor_light(&cave, [1.0, 5.0, 9.0]);
+    assert!((block - 11.0 / 15.0).abs() < 1e-6);
+    assert_eq!(sky, 0.0);
+
+    // Open ground gets the full sky light, and no block light
+    let ground = lit_mesh(|pos| {
+        if pos.y < 2 {
+            WorldVoxel::Solid(1)
+        } else {
+            WorldVoxel::Air
+        }
+    });
+    assert_eq!(floor_light(&ground, [6.0, 3.0, 6.0]), [0.0, 1.0]);
+
+    // A cave whose ceiling is far above the chunk, out of reach of the light flooding around it
+    let deep_cave = lit_mesh(|pos| {
+        if (4..=50).contains(&pos.y) || pos.y > 60 {
+            WorldVoxel::Air
+        } else {
+            WorldVoxel::Solid(1)
+        }
+    });
+    assert_eq!(floor_light(&deep_cave, [6.0, 5.0, 6.0]), [0.0, 0.0]);
+}
+
+#[test]
+fn light_changes_relight_meshes_without_remeshing() {
+    use crate::chunk::{ChunkTask, NeedsRelight, RelightThread};
+    use crate::mesh_cache::MeshRef;
+    use crate::meshing::MeshingOptions;
+    use crate::rendering::ATTRIBUTE_VOXEL_LIGHT;
+    use crate::voxel_world_internal::ModifiedVoxels;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    #[derive(Resource, Clone, Default)]
+    struct LitWorld;
+
+    impl VoxelWorldConfig for LitWorld {
+        type MaterialIndex = u8;
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<u8> {
+            Box::new(|_| {
+                Box::new(|pos| {
+                    if pos.y < 2 {
+                        WorldVoxel::Solid(1)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+
+        fn voxel_lighting(&self) -> Option<VoxelLighting> {
+            Some(VoxelLighting::default())
+        }
+
+        fn voxel_emission(&self, index: u8) -> u8 {
+            if index == 2 {
+                MAX_LIGHT
+            } else {
+                0
+            }
+        }
+    }
+
+    // The block light of the upward facing vertex at a position relative to the padded chunk
+    let floor_light = |mesh: &Mesh, position: [f32; 3]| {
+        let (
+            Some(VertexAttributeValues::Float32x3(positions)),
+            Some(VertexAttributeValues::Float32x3(normals)),
+            Some(VertexAttributeValues::Float32x2(lights)),
+        ) = (
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+            mesh.attribute(ATTRIBUTE_VOXEL_LIGHT),
+        )
+        else {
+            panic!("lit meshes have voxel light");
+        };
+        let index = (0..positions.len())
+            .find(|&i| positions[i] == position && normals[i] == [0.0, 1.0, 0.0])
+            .unwrap();
+        lights[index][0]
+    };
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<LitWorld>::minimal()))
+        .init_resource::<Assets<Mesh>>();
+    app.update();
+
+    // A lit chunk of flat ground, spawned by hand since there is no camera
+    let entity = app.world_mut().spawn_empty().id();
+    let mut chunk_task =
+        ChunkTask::<LitWorld, u8>::new(entity, IVec3::ZERO, ModifiedVoxels::default());
+    let meshing_options = MeshingOptions::from_config(&LitWorld);
+    let mut lookup = (LitWorld.voxel_lookup_delegate())(IVec3::ZERO);
+    chunk_task.generate(&mut lookup);
+    chunk_task.light(&meshing_options, &mut lookup);
+    chunk_task.mesh(&meshing_options);
+    let mesh = chunk_task.mesh.take().unwrap();
+    assert_eq!(floor_light(&mesh, [1.0, 3.0, 7.0]), 0.0);
+    let mesh_handle = app.world_mut().resource_mut::<Assets<Mesh>>().add(mesh);
+    app.world_mut().entity_mut(entity).insert((
+        Chunk::<LitWorld>::new(IVec3::ZERO, entity),
+        MeshRef(std::sync::Arc::new(mesh_handle.clone())),
+    ));
+    app.world_mut()
+        .resource_mut::<ChunkMapUpdateBuffer<LitWorld, u8>>()
+        .push((
+            IVec3::ZERO,
+            chunk_task.chunk_data,
+            ChunkWillSpawn::<LitWorld>::new(IVec3::ZERO, entity),
+        ));
+    app.update();
+
+    // An emitter on the ground, two voxels past the border of the chunk
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<LitWorld>| {
+            voxel_world.set_voxel(IVec3::new(-2, 2, 6), WorldVoxel::Solid(2));
+        });
+    app.update();
+    assert!(app.world().entity(entity).contains::<NeedsRelight>());
+    assert!(!app.world().entity(entity).contains::<NeedsRemesh>());
+
+    app.world_mut()
+        .run_system_once(Internals::<LitWorld>::relight_chunks);
+    while app
+        .world()
+        .entity(entity)
+        .contains::<RelightThread<LitWorld>>()
+    {
+        app.world_mut()
+            .run_system_once(Internals::<LitWorld>::spawn_relit_meshes);
+    }
+
+    // The chunk got a copy of its mesh with the light of the emitter, and was not remeshed
+    let world = app.world();
+    let relit_handle = (*world.get::<MeshRef>(entity).unwrap().0).clone();
+    assert_ne!(relit_handle, mesh_handle);
+    let meshes = world.resource::<Assets<Mesh>>();
+    let (mesh, relit_mesh) = (
+        meshes.get(&mesh_handle).unwrap(),
+        meshes.get(&relit_handle).unwrap(),
+    );
+    assert_eq!(
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap().len(),
+        relit_mesh
+            .attribute(Mesh::ATTRIBUTE_POSITION)
+            .unwrap()
+            .len()
+    );
+    // The floor corner at (0, 2, 6) touches open voxels 1, 2, 2 and 3 steps from the emitter
+    assert!((floor_light(relit_mesh, [1.0, 3.0, 7.0]) - 13.0 / 15.0).abs() < 1e-6);
+    assert!(!world.entity(entity).contains::<NeedsRemesh>());
+}
+
+#[test]
+fn voxel_metadata_survives_chunk_despawns() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Resource, Clone, Default)]
+    struct MetadataWorld;
+
+    impl VoxelWorldConfig for MetadataWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            2
+        }
+    }
+
+    impl VoxelMetadataConfig for MetadataWorld {
+        type VoxelMetadata = String;
+    }
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<MetadataWorld>::minimal()));
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<MetadataWorld>::default(),
+        ));
+    });
+    app.update();
+
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<MetadataWorld>| {
+            assert!(voxel_world.set_voxel_metadata(IVec3::new(1, 2, 3), "chest".to_string()));
+            assert_eq!(
+                voxel_world.get_voxel_metadata(IVec3::new(1, 2, 3)),
+                Some(&"chest".to_string())
+            );
+            assert_eq!(voxel_world.get_voxel_metadata(IVec3::new(3, 2, 1)), None);
+        });
+
+    // Move the camera away, so that the chunk with the metadata despawns
+    let mut reader = app
+        .world()
+        .resource::<Events<ChunkMetadataWillDespawn<MetadataWorld>>>()
+        .get_reader();
+    app.add_systems(
+        First,
+        |mut query: Query<&mut GlobalTransform, With<VoxelWorldCamera<MetadataWorld>>>| {
+            for mut transform in query.iter_mut() {
+                *transform = GlobalTransform::from_translation(Vec3::splat(1000.0));
+            }
+        },
+    );
+    app.update();
+
+    let despawned: Vec<(IVec3, IVec3, String)> = reader
+        .read(
+            app.world()
+                .resource::<Events<ChunkMetadataWillDespawn<MetadataWorld>>>(),
+        )
+        .flat_map(|event| {
+            event
+                .metadata()
+                .map(|(position, metadata)| (event.chunk_key, position, metadata.clone()))
+        })
+        .collect();
+    assert_eq!(
+        despawned,
+        vec![(IVec3::ZERO, IVec3::new(1, 2, 3), "chest".to_string())]
+    );
+
+    // The metadata is still there for when the chunk comes back
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<MetadataWorld>| {
+            assert_eq!(
+                voxel_world.remove_voxel_metadata(IVec3::new(1, 2, 3)),
+                Some("chest".to_string())
+            );
+            assert_eq!(voxel_world.get_voxel_metadata(IVec3::new(1, 2, 3)), None);
+        });
+}
+
+#[test]
+fn orthographic_cameras_spawn_their_view_volume() {
+    #[derive(Resource, Clone, Default)]
+    struct TopDownWorld;
+
+    impl VoxelWorldConfig for TopDownWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            8
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<TopDownWorld>::minimal()));
+    app.add_systems(Startup, |mut commands: Commands| {
+        // High above the ground, with the ground at the edge of the spawning distance
+        commands.spawn((
+            Camera3dBundle {
+                projection: Projection::Orthographic(OrthographicProjection {
+                    area: Rect::new(-40.0, -40.0, 40.0, 40.0),
+                    far: 300.0,
+                    ..default()
+                }),
+                global_transform: GlobalTransform::from(
+                    Transform::from_xyz(0.0, 200.0, 0.0).looking_to(Vec3::NEG_Y, Vec3::Z),
+                ),
+                ..default()
+            },
+            VoxelWorldCamera::<TopDownWorld>::default(),
+        ));
+    });
+
+    let chunk_positions = |app: &mut App| {
+        app.world_mut()
+            .query::<&Chunk<TopDownWorld>>()
+            .iter(app.world())
+            .map(|chunk| chunk.position)
+            .collect::<bevy::utils::HashSet<_>>()
+    };
+
+    for _ in 0..3 {
+        app.update();
+    }
+    let spawned = chunk_positions(&mut app);
+    assert!(spawned.contains(&IVec3::ZERO));
+    assert!(spawned.contains(&IVec3::new(1, -1, -2)));
+    assert!(!spawned.contains(&IVec3::new(3, 0, 0)));
+    // The view volume reaches further down, but is cut off at the spawning distance
+    assert!(!spawned.contains(&IVec3::new(0, -3, 0)));
+
+    // The chunks in view are kept, even though they are far from the camera
+    for _ in 0..3 {
+        app.update();
+    }
+    assert!(chunk_positions(&mut app).is_superset(&spawned));
+}
+
+#[test]
+fn change_tracker_records_local_changes_only() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut app = _test_setup_app();
+    app.init_resource::<VoxelChangeTracker<DefaultWorld>>();
+    app.update();
+
+    let position = IVec3::new(1000, 1000, 1000);
+    app.world_mut()
+        .run_system_once(move |mut voxel_world: VoxelWorld<DefaultWorld>| {
+            voxel_world.set_voxel(position, WorldVoxel::Solid(1));
+            voxel_world.set_voxel(position, WorldVoxel::Solid(2));
+        });
+    app.update();
+
+    // Writing the same voxel again is not a change
+    app.world_mut()
+        .run_system_once(move |mut voxel_world: VoxelWorld<DefaultWorld>| {
+            voxel_world.set_voxel(position, WorldVoxel::Solid(2));
+        });
+    app.update();
+
+    let changes = app
+        .world_mut()
+        .resource_mut::<VoxelChangeTracker<DefaultWorld>>()
+        .drain();
+    assert_eq!(
+        changes,
+        vec![
+            VoxelChange {
+                position,
+                old: WorldVoxel::Unset,
+                new: WorldVoxel::Solid(1),
+            },
+            VoxelChange {
+                position,
+                old: WorldVoxel::Solid(1),
+                new: WorldVoxel::Solid(2),
+            },
+        ]
+    );
+
+    // Remote changes are applied, but not recorded again
+    app.world_mut()
+        .run_system_once(move |mut voxel_world: VoxelWorld<DefaultWorld>| {
+            voxel_world.apply_remote_changes([VoxelChange {
+                position,
+                old: WorldVoxel::Solid(2),
+                new: WorldVoxel::Air,
+            }]);
+        });
+    app.update();
+
+    assert!(app
+        .world()
+        .resource::<VoxelChangeTracker<DefaultWorld>>()
+        .changes()
+        .is_empty());
+    app.world_mut()
+        .run_system_once(move |voxel_world: VoxelWorld<DefaultWorld>| {
+            assert_eq!(voxel_world.get_voxel(position), WorldVoxel::Air);
+        });
+}
+
+#[test]
+fn split_screen_cameras_spawn_the_chunks_in_their_own_views() {
+    use bevy::render::camera::Viewport;
+
+    #[derive(Resource, Clone, Default)]
+    struct SplitScreenViewsWorld;
+
+    impl VoxelWorldConfig for SplitScreenViewsWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            5
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<SplitScreenViewsWorld>::minimal(),
+    ));
+
+    // Two players, on the left and right half of the screen, far apart and looking different ways
+    for (index, transform) in [
+        Transform::from_xyz(16.0, 16.0, 16.0).looking_to(Vec3::NEG_Z, Vec3::Y),
+        Transform::from_xyz(20.0 * 32.0 + 16.0, 16.0, 16.0).looking_to(Vec3::X, Vec3::Y),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        app.world_mut().spawn((
+            Camera3dBundle {
+                camera: Camera {
+                    order: index as isize,
+                    viewport: Some(Viewport {
+                        physical_position: UVec2::new(index as u32 * 640, 0),
+                        physical_size: UVec2::new(640, 720),
+                        ..default()
+                    }),
+                    ..default()
+                },
+                global_transform: GlobalTransform::from(transform),
+                ..default()
+            },
+            VoxelWorldCamera::<SplitScreenViewsWorld>::default(),
+        ));
+    }
+
+    for _ in 0..5 {
+        app.update();
+    }
+    let chunks: Vec<IVec3> = app
+        .world_mut()
+        .query::<&Chunk<SplitScreenViewsWorld>>()
+        .iter(app.world())
+        .map(|chunk| chunk.position)
+        .collect();
+
+    // Each player gets the chunks in front of them, but not the ones behind them
+    assert!(chunks.contains(&IVec3::new(0, 0, -4)));
+    assert!(chunks.contains(&IVec3::new(24, 0, 0)));
+    assert!(!chunks.contains(&IVec3::new(0, 0, 4)));
+    assert!(!chunks.contains(&IVec3::new(16, 0, 0)));
+}
+
+#[test]
+fn chunk_voxels_are_stored_in_a_palette() {
+    use crate::chunk::{ChunkTask, PaddedChunkShape};
+    use crate::chunk_storage::ChunkVoxels;
+    use crate::voxel_world_internal::ModifiedVoxels;
+    use ndshape::ConstShape;
+    use std::hash::{Hash, Hasher};
+
+    let mut chunk_task = ChunkTask::<DefaultWorld, u8>::new(
+        Entity::PLACEHOLDER,
+        IVec3::ZERO,
+        ModifiedVoxels::default(),
+    );
+    chunk_task.generate(|pos| match pos.y {
+        y if y < 8 => WorldVoxel::Solid(0),
+        y if y < 12 => WorldVoxel::Solid(1),
+        _ => WorldVoxel::Air,
+    });
+
+    let dense = chunk_task.voxels.clone().unwrap();
+    let stored = chunk_task.chunk_data.voxels.clone().unwrap();
+
+    // Three different voxels fit in two bits each
+    let ChunkVoxels::Paletted { bits, words, .. } = stored.as_ref() else {
+        panic!("expected paletted voxels");
+    };
+    assert_eq!(*bits, 2);
+    assert!(words.len() * 8 * 4 < std::mem::size_of_val(dense.as_ref()));
+
+    for i in 0..PaddedChunkShape::SIZE {
+        let position = UVec3::from(PaddedChunkShape::delinearize(i));
+        assert_eq!(chunk_task.chunk_data.get_voxel(position), dense[i as usize]);
+    }
+
+    // The hash doesn't depend on the storage, so cached meshes stay valid
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    dense.hash(&mut hasher);
+    assert_eq!(hasher.finish(), chunk_task.chunk_data.voxels_hash);
+
+    // Even chunks with every material take half the space of the two byte voxels
+    let mut varied = [WorldVoxel::Air; PaddedChunkShape::SIZE as usize];
+    for (i, voxel) in varied.iter_mut().enumerate() {
+        *voxel = WorldVoxel::Solid((i % 251) as u8);
+    }
+    assert!(matches!(
+        ChunkVoxels::from(varied),
+        ChunkVoxels::Paletted { bits: 8, .. }
+    ));
+}
+
+#[test]
+fn chunk_data_diffs_apply_to_give_the_other_chunk() {
+    use crate::chunk::ChunkTask;
+    use crate::voxel_world_internal::ModifiedVoxels;
+
+    let generate = |voxel_at: fn(IVec3) -> WorldVoxel| {
+        let mut chunk_task = ChunkTask::<DefaultWorld, u8>::new(
+            Entity::PLACEHOLDER,
+            IVec3::ZERO,
+            ModifiedVoxels::default(),
+        );
+        chunk_task.generate(voxel_at);
+        chunk_task.chunk_data
+    };
+    let terrain = generate(|pos| match pos.y {
+        y if y < 8 => WorldVoxel::Solid(0),
+        _ => WorldVoxel::Air,
+    });
+    let dug = generate(|pos| match pos.y {
+        y if y < 8 && pos.x != 3 => WorldVoxel::Solid(0),
+        _ => WorldVoxel::Air,
+    });
+
+    // Only the dug out wall differs, with the padding below the chunk
+    let diff = terrain.diff(&dug);
+    assert_eq!(diff.len(), 9 * (CHUNK_SIZE_U as usize + 2));
+    assert!(diff.iter().all(|(_, voxel)| *voxel == WorldVoxel::Air));
+    assert!(terrain.diff(&terrain).is_empty());
+
+    let mut patched = terrain.clone();
+    patched.apply_diff(&diff).unwrap();
+    assert!(patched.diff(&dug).is_empty());
+    assert_eq!(patched.voxels_hash, dug.voxels_hash);
+
+    // Diffs between differently stored chunks work too
+    let solid = generate(|_| WorldVoxel::Solid(2));
+    assert_eq!(solid.fill_type, FillType::Uniform(WorldVoxel::Solid(2)));
+    let mut patched = solid.clone();
+    patched.apply_diff(&solid.diff(&dug)).unwrap();
+    assert!(patched.diff(&dug).is_empty());
+    patched.apply_diff(&dug.diff(&solid)).unwrap();
+    assert_eq!(patched.fill_type, FillType::Uniform(WorldVoxel::Solid(2)));
+
+    // Indices past the padded chunk are rejected without touching the chunk
+    let garbled = [(0, WorldVoxel::Air), (usize::MAX, WorldVoxel::Air)];
+    let error = patched.apply_diff(&garbled).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(patched.fill_type, FillType::Uniform(WorldVoxel::Solid(2)));
+}
+
+#[test]
+fn chunk_data_and_meshing_take_any_chunk_size() {
+    use crate::chunk::PaddedChunkShape;
+    use crate::chunk_shape::{ChunkShape, ConstChunkShape, DynamicChunkShape};
+    use crate::chunk_storage::ChunkVoxels;
+    use ndshape::Shape;
+
+    // A slope of mixed materials that fits in a 16 voxel chunk, with air around it
+    fn chunk<S: ChunkShape>(shape: S) -> ChunkData<u8, S> {
+        let voxels: Vec<WorldVoxel> = (0..shape.size())
+            .map(|i| match shape.delinearize(i) {
+                [x, y, z] if x.max(y).max(z) <= 16 && y < x.min(z) => {
+                    WorldVoxel::Solid((x % 3) as u8)
+                }
+                _ => WorldVoxel::Air,
+            })
+            .collect();
+        let mut chunk_data = ChunkData::with_shape(shape);
+        chunk_data.position = IVec3::new(1, 0, -1);
+        chunk_data.set_voxels(&voxels);
+        chunk_data
+    }
+    fn mesh<S: ChunkShape>(chunk_data: &ChunkData<u8, S>) -> Vec<([f32; 3], [f32; 3])> {
+        let voxels: Vec<WorldVoxel> = (0..chunk_data.shape.usize())
+            .map(|i| chunk_data.get_voxel_at_index(i))
+            .collect();
+        let mesh = crate::meshing::generate_chunk_mesh(
+            voxels.into(),
+            &chunk_data.shape,
+            IVec3::ZERO,
+            &crate::meshing::MeshingOptions::from_config(&DefaultWorld),
+        );
+        let (
+            Some(bevy::render::mesh::VertexAttributeValues::Float32x3(positions)),
+            Some(bevy::render::mesh::VertexAttributeValues::Float32x3(normals)),
+        ) = (
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+        )
+        else {
+            panic!("Mesh has no positions or normals");
+        };
+        let mut vertices: Vec<_> = positions
+            .iter()
+            .copied()
+            .zip(normals.iter().copied())
+            .collect();
+        vertices.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        vertices
+    }
+
+    let small = chunk(ConstChunkShape::<16>);
+    let dynamic = chunk(DynamicChunkShape::new(16));
+    let default = chunk(PaddedChunkShape::default());
+
+    assert_eq!(small.shape.usize(), 18 * 18 * 18);
+    assert_eq!(small.voxels.as_ref().unwrap().voxel_count(), 18 * 18 * 18);
+    assert_eq!(small.world_position(), Vec3::new(16.0, 0.0, -16.0));
+    assert_eq!(default.world_position(), Vec3::new(32.0, 0.0, -32.0));
+    assert_eq!(small.get_voxel(UVec3::new(5, 2, 3)), WorldVoxel::Solid(2));
+    assert_eq!(small.voxels_hash, dynamic.voxels_hash);
+    assert!(small.diff(&small).is_empty());
+    assert!(small
+        .clone()
+        .apply_diff(&[(18 * 18 * 18, WorldVoxel::Air)])
+        .is_err());
+
+    // Both kinds of shape mesh the same, and the same as the corner of a full size chunk
+    let small_mesh = mesh(&small);
+    assert!(!small_mesh.is_empty());
+    assert_eq!(small_mesh, mesh(&dynamic));
+    assert_eq!(small_mesh, mesh(&default));
+
+    // The runtime shape defaults to the size of the chunks the world spawns
+    let shape = DynamicChunkShape::default();
+    assert_eq!(shape.chunk_size(), CHUNK_SIZE_U);
+    assert_eq!(shape.usize(), PaddedChunkShape::default().usize());
+    for position in [[0, 0, 0], [1, 2, 3], [33, 0, 17], [33, 33, 33]] {
+        assert_eq!(
+            shape.linearize(position),
+            PaddedChunkShape::default().linearize(position)
+        );
+    }
+
+    // Chunks above LOD level 0 sample blocks of their own size
+    let voxels: Vec<WorldVoxel> = (0..small.shape.usize())
+        .map(|i| small.get_voxel_at_index(i))
+        .collect();
+    let sampled = ChunkVoxels::sampled(&voxels, &small.shape, IVec3::ZERO, 2);
+    assert_eq!(sampled.voxel_count(), 18 * 18 * 18);
+    let ChunkVoxels::Sampled { side, .. } = sampled else {
+        panic!("expected sampled voxels");
+    };
+    assert_eq!(side, 6);
+}
+
+#[test]
+fn xr_rig_eyes_share_one_spawn_anchor() {
+    use crate::voxel_world_internal::SpawnFocus;
+
+    #[derive(Resource, Clone, Default)]
+    struct XrWorld;
+
+    impl VoxelWorldConfig for XrWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            4
+        }
+
+        fn spawning_rays(&self) -> usize {
+            1000
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<XrWorld>::minimal()));
+
+    // Two eyes on either side of a chunk border, looking down -Z
+    let rig = app.world_mut().spawn_empty().id();
+    for x in [31.97, 32.03] {
+        app.world_mut().spawn((
+            Camera3dBundle {
+                global_transform: GlobalTransform::from(
+                    Transform::from_xyz(x, 16.0, 16.0).looking_to(Vec3::NEG_Z, Vec3::Y),
+                ),
+                ..default()
+            },
+            VoxelWorldCamera::<XrWorld>::default(),
+            VoxelWorldCameraRig {
+                rig,
+                spawn_volume: RigSpawnVolume::Sphere,
+            },
+        ));
+    }
+
+    for _ in 0..5 {
+        app.update();
+    }
+
+    // The eyes are spawned around as one, from the point between them
+    let spawn_focus = app.world().resource::<SpawnFocus<XrWorld>>();
+    assert_eq!(spawn_focus.chunk_positions.len(), 1);
+    assert_eq!(spawn_focus.chunk_positions[&rig], IVec3::new(1, 0, 0));
+
+    // A sphere spawns the chunks behind the eyes too
+    let chunks: Vec<IVec3> = app
+        .world_mut()
+        .query::<&Chunk<XrWorld>>()
+        .iter(app.world())
+        .map(|chunk| chunk.position)
+        .collect();
+    assert!(chunks.contains(&IVec3::new(1, 0, -3)));
+    assert!(chunks.contains(&IVec3::new(1, 0, 3)));
+}
+
+#[test]
+fn spawn_anchors_keep_chunks_loaded_without_a_camera() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Resource, Clone, Default)]
+    struct HeadlessWorld;
+
+    impl VoxelWorldConfig for HeadlessWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_rays(&self) -> usize {
+            1000
+        }
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|pos| {
+                    if pos.y < 0 {
+                        WorldVoxel::Solid(1)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = _mesh_test_app::<HeadlessWorld>();
+    let anchor = app
+        .world_mut()
+        .spawn((
+            GlobalTransform::from_xyz(40.0, 16.0, 16.0),
+            VoxelWorldSpawnAnchor::<HeadlessWorld>::new(2),
+        ))
+        .id();
+
+    let chunk_positions = |app: &mut App| {
+        app.world_mut()
+            .query_filtered::<&Chunk<HeadlessWorld>, Without<crate::chunk::NeedsDespawn>>()
+            .iter(app.world())
+            .map(|chunk| chunk.position)
+            .collect::<Vec<_>>()
+    };
+
+    // The voxels around the anchor can be looked up once their chunks are generated
+    let ground_below_anchor = |app: &mut App| {
+        app.world_mut()
+            .run_system_once(|voxel_world: VoxelWorld<HeadlessWorld>| {
+                voxel_world.get_voxel(IVec3::new(40, -1, 16))
+            })
+    };
+    _update_until(&mut app, |app| {
+        ground_below_anchor(app) != WorldVoxel::Unset
+    });
+    assert_eq!(ground_below_anchor(&mut app), WorldVoxel::Solid(1));
+
+    // Chunks are spawned in every direction within the radius, and none beyond it
+    let chunks = chunk_positions(&mut app);
+    for chunk_position in [
+        IVec3::new(1, 0, 0),
+        IVec3::new(-1, 0, 0),
+        IVec3::new(1, 0, 2),
+    ] {
+        assert!(chunks.contains(&chunk_position));
+    }
+    assert!(chunks
+        .iter()
+        .all(|chunk| chunk.distance_squared(IVec3::new(1, 0, 0)) <= 4));
+
+    // Chunks the anchor moved away from are despawned
+    *app.world_mut().get_mut::<GlobalTransform>(anchor).unwrap() =
+        GlobalTransform::from_xyz(1000.0, 16.0, 16.0);
+    for _ in 0..5 {
+        app.update();
+    }
+    let chunks = chunk_positions(&mut app);
+    assert!(!chunks.contains(&IVec3::new(1, 0, 0)));
+    assert!(chunks.contains(&IVec3::new(31, 0, 0)));
+}
+
+#[test]
+fn edit_latency_is_measured_until_the_chunk_is_remeshed() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Resource, Clone, Default)]
+    struct LatencyWorld;
+
+    impl VoxelWorldConfig for LatencyWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            1
+        }
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|pos| {
+                    if pos.y < 0 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = _mesh_test_app::<LatencyWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        commands.spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<LatencyWorld>::default(),
+        ));
+    });
+
+    let ground = IVec3::new(0, -1, 0);
+    let update_until_meshed = |app: &mut App| {
+        for _ in 0..500 {
+            app.update();
+            let meshed = app
+                .world_mut()
+                .resource_mut::<Events<ChunkMeshReady<LatencyWorld>>>()
+                .drain()
+                .any(|event| event.chunk_key == ground);
+            if meshed {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        panic!("the ground chunk was not meshed");
+    };
+
+    // Generating chunks doesn't count as an edit
+    update_until_meshed(&mut app);
+    let timings = app.world().resource::<ChunkTimings<LatencyWorld>>().clone();
+    assert_eq!(timings.edit_latency().samples, 0);
+
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<LatencyWorld>| {
+            voxel_world.set_voxel(IVec3::new(4, -1, 4), WorldVoxel::Air);
+            voxel_world.set_voxel(IVec3::new(5, -1, 4), WorldVoxel::Air);
+        });
+    update_until_meshed(&mut app);
+
+    // Both edits were in one chunk, so they show up together
+    let edit_latency = timings.edit_latency();
+    assert_eq!(edit_latency.samples, 1);
+    assert!(edit_latency.mean > std::time::Duration::ZERO);
+    assert_eq!(edit_latency.mean, edit_latency.max);
+}
+
+#[test]
+fn placeholders_show_while_chunks_generate() {
+    use crate::mesh_cache::MeshRef;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    static GENERATE: AtomicBool = AtomicBool::new(false);
+
+    #[derive(Resource, Clone, Default)]
+    struct PlaceholderWorld;
+
+    impl VoxelWorldConfig for PlaceholderWorld {
+        type MaterialIndex = u8;
+
+        fn chunk_placeholder(&self) -> Option<ChunkPlaceholder> {
+            Some(ChunkPlaceholder::FlatBox {
+                color: Color::srgb(0.3, 0.6, 0.2),
+                surface_height: std::sync::Arc::new(|_| Some(10.0)),
+            })
+        }
+
+        fn chunk_generator(&self) -> Option<ChunkGeneratorFn<Self::MaterialIndex>> {
+            Some(std::sync::Arc::new(|buffer: &mut ChunkBuffer<u8>| {
+                while !GENERATE.load(Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                for column in buffer.columns().collect::<Vec<_>>() {
+                    buffer.fill_column(column, 10, WorldVoxel::Solid(1));
+                }
+            }))
+        }
+    }
+
+    let mut app = _mesh_test_app_with(
+        VoxelWorldPlugin::<PlaceholderWorld>::minimal()
+            .with_fixed_region(IVec3::new(0, -1, 0), IVec3::new(0, 1, 0)),
+    );
+    app.add_systems(
+        Update,
+        Internals::<PlaceholderWorld>::spawn_chunk_placeholders
+            .before(Internals::<PlaceholderWorld>::spawn_meshes),
+    );
+
+    let placeholders = |app: &mut App| {
+        app.world_mut()
+            .query::<&ChunkLoadingPlaceholder<PlaceholderWorld>>()
+            .iter(app.world())
+            .map(|placeholder| placeholder.chunk_position)
+            .collect::<Vec<_>>()
+    };
+
+    // Only the chunk the surface passes through gets a placeholder while the chunks generate
+    app.update();
+    assert_eq!(placeholders(&mut app), vec![IVec3::ZERO]);
+
+    GENERATE.store(true, Ordering::Relaxed);
+    _update_until(&mut app, |app| {
+        let busy = app
+            .world_mut()
+            .query_filtered::<(), With<ChunkThread<PlaceholderWorld, u8>>>()
+            .iter(app.world())
+            .next()
+            .is_some();
+        !busy
+    });
+
+    // The placeholder is gone once the chunk got its mesh
+    assert!(placeholders(&mut app).is_empty());
+    let meshed = app
+        .world_mut()
+        .query::<(&Chunk<PlaceholderWorld>, &MeshRef)>()
+        .iter(app.world())
+        .map(|(chunk, _)| chunk.position)
+        .collect::<Vec<_>>();
+    assert_eq!(meshed, vec![IVec3::ZERO]);
+}
+
+#[test]
+fn generate_now_spawns_and_keeps_a_region() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Resource, Clone, Default)]
+    struct CutsceneWorld;
+
+    impl VoxelWorldConfig for CutsceneWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            1
+        }
+
+        fn max_spawn_per_frame(&self) -> usize {
+            2
+        }
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|pos| {
+                    if pos.y < 4 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = _mesh_test_app::<CutsceneWorld>();
+    app.add_systems(Startup, |mut commands: Commands| {
+        // Far away from the region, so the streaming would despawn it
+        commands.spawn((
+            Camera3dBundle {
+                global_transform: GlobalTransform::from_xyz(10000.0, 0.0, 0.0),
+                ..default()
+            },
+            VoxelWorldCamera::<CutsceneWorld>::default(),
+        ));
+    });
+
+    let (min_chunk, max_chunk) = (ChunkPos::new(0, 0, 0), ChunkPos::new(1, 0, 1));
+    let generate_now = move |app: &mut App| {
+        app.world_mut()
+            .run_system_once(move |mut voxel_world: VoxelWorld<CutsceneWorld>| {
+                voxel_world.generate_now(min_chunk, max_chunk, 50)
+            })
+    };
+    let region_chunks = |app: &mut App| {
+        app.world_mut()
+            .query::<&Chunk<CutsceneWorld>>()
+            .iter(app.world())
+            .filter(|chunk| chunk.position.y == 0 && (0..=1).contains(&chunk.position.x))
+            .filter(|chunk| (0..=1).contains(&chunk.position.z))
+            .count()
+    };
+
+    app.update();
+    // The region is spawned within `max_spawn_per_frame`
+    assert!(!generate_now(&mut app));
+    app.update();
+    assert_eq!(region_chunks(&mut app), 2);
+
+    let mut frames = 0;
+    while !generate_now(&mut app) {
+        app.update();
+        frames += 1;
+        // At least one chunk of the region is generated on the main thread each frame
+        assert!(frames <= 8, "the region was not generated");
+    }
+    assert_eq!(region_chunks(&mut app), 4);
+
+    // Regions too large to keep loaded are refused
+    assert!(!app
+        .world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<CutsceneWorld>| {
+            voxel_world.generate_now(IVec3::splat(-1000), IVec3::splat(1000), 50)
+        }));
+
+    // The region stays while it is requested, and goes back to streaming after that
+    for _ in 0..5 {
+        assert!(generate_now(&mut app));
+        app.update();
+    }
+    assert_eq!(region_chunks(&mut app), 4);
+    assert!(app
+        .world_mut()
+        .run_system_once(|voxel_world: VoxelWorld<CutsceneWorld>| {
+            voxel_world.is_position_ready(IVec3::new(20, 3, 20))
+        }));
+
+    for _ in 0..5 {
+        app.update();
+    }
+    assert_eq!(region_chunks(&mut app), 0);
+}
+
+#[test]
+fn materials_have_navigation_costs() {
+    #[derive(Resource, Clone, Default)]
+    struct NavigationWorld;
+
+    impl VoxelWorldConfig for NavigationWorld {
+        type MaterialIndex = u8;
+
+        fn voxel_navigation(&self, index: u8) -> VoxelNavigation {
+            match index {
+                1 => VoxelNavigation {
+                    movement_cost: 3.0,
+                    ..VoxelNavigation::GROUND
+                },
+                2 => VoxelNavigation {
+                    hazard: true,
+                    ..VoxelNavigation::GROUND
+                },
+                3 => VoxelNavigation::BLOCKED,
+                _ => VoxelNavigation::GROUND,
+            }
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<NavigationWorld>::minimal(),
+    ));
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<NavigationWorld>| {
+        for (x, material) in (0..4).enumerate() {
+            voxel_world.set_voxel(IVec3::new(x as i32, 0, 0), WorldVoxel::Solid(material));
+        }
+        voxel_world.set_voxel(IVec3::new(0, 2, 0), WorldVoxel::Solid(0));
+
+        assert_eq!(
+            voxel_world.get_voxel_navigation(IVec3::new(1, 0, 0)),
+            Some(VoxelNavigation {
+                movement_cost: 3.0,
+                ..VoxelNavigation::GROUND
+            })
+        );
+        assert_eq!(voxel_world.get_voxel_navigation(IVec3::new(0, 1, 0)), None);
+
+        // Standing needs walkable ground below and room in the voxel itself
+        let standing = |x: i32, y: i32| voxel_world.standing_navigation(IVec3::new(x, y, 0));
+        assert_eq!(standing(0, 1), Some(VoxelNavigation::GROUND));
+        assert!(standing(2, 1).is_some_and(|navigation| navigation.hazard));
+        assert_eq!(standing(3, 1), None);
+        assert_eq!(standing(4, 1), None);
+        assert_eq!(standing(0, 2), None);
+    });
+
+    app.update();
+}
+
+#[test]
+fn large_spawning_distances_are_clamped() {
+    use crate::configuration::{capped_soft_despawn_distance, capped_spawning_distance};
+
+    #[derive(Resource, Clone, Default)]
+    struct FarWorld {
+        spawning_distance: u32,
+        soft_despawn_distance: Option<u32>,
+    }
+
+    impl VoxelWorldConfig for FarWorld {
+        type MaterialIndex = u8;
+
+        fn spawning_distance(&self) -> u32 {
+            self.spawning_distance
+        }
+
+        fn soft_despawn_distance(&self) -> Option<u32> {
+            self.soft_despawn_distance
+        }
+
+        fn max_loaded_chunks(&self) -> usize {
+            1_000
+        }
+    }
+
+    let world = |spawning_distance, soft_despawn_distance| FarWorld {
+        spawning_distance,
+        soft_despawn_distance,
+    };
+    assert_eq!(theoretical_chunk_count(&world(0, None)), 1);
+    assert_eq!(theoretical_chunk_count(&world(1, None)), 7);
+    assert_eq!(theoretical_chunk_count(&world(2, None)), 33);
+    assert_eq!(theoretical_chunk_count(&world(1, Some(2))), 33);
+
+    // Within the cap, the distances are used as configured
+    assert_eq!(capped_spawning_distance(&world(6, None)), 6);
+    assert_eq!(capped_soft_despawn_distance(&world(4, Some(6))), Some(6));
+
+    // Beyond it, they are lowered to the largest distance that fits
+    let far = world(100, Some(120));
+    assert!(theoretical_chunk_count(&far) > 4_000_000);
+    let clamped = capped_spawning_distance(&far);
+    assert!(theoretical_chunk_count(&world(clamped, None)) <= 1_000);
+    assert!(theoretical_chunk_count(&world(clamped + 1, None)) > 1_000);
+    assert_eq!(capped_soft_despawn_distance(&far), Some(clamped));
+    assert_eq!(capped_spawning_distance(&world(2, Some(120))), 2);
+}
+
+#[test]
+fn chunks_round_trip_through_network_payloads() {
+    use crate::chunk::ChunkTask;
+    use crate::voxel_world_internal::ModifiedVoxels;
+
+    let mut chunk_task = ChunkTask::<DefaultWorld, u8>::new(
+        Entity::PLACEHOLDER,
+        IVec3::new(-2, 1, 5),
+        ModifiedVoxels::default(),
+    );
+    chunk_task.generate(|pos| match pos.y {
+        y if y < 40 => WorldVoxel::Solid((pos.x.rem_euclid(3)) as u8),
+        y if y == 40 && pos.z == 170 => WorldVoxel::Solid(200),
+        _ => WorldVoxel::Air,
+    });
+    let terrain = chunk_task.chunk_data;
+
+    let codecs = [NetworkCodec::Raw, NetworkCodec::Deflate, NetworkCodec::Zstd];
+    for codec in codecs {
+        let bytes = terrain.to_network_bytes(codec);
+        let decoded = ChunkData::<u8>::from_network_bytes(&bytes).unwrap();
+        assert_eq!(decoded.position, terrain.position);
+        assert!(decoded.diff(&terrain).is_empty());
+        assert_eq!(decoded.voxels_hash, terrain.voxels_hash);
+        assert_eq!(decoded.fill_type, terrain.fill_type);
+    }
+
+    // Uniform chunks are a single run
+    let empty = ChunkData::<u8>::new();
+    let bytes = empty.to_network_bytes(NetworkCodec::Raw);
+    assert!(bytes.len() < 32);
+    assert_eq!(
+        ChunkData::<u8>::from_network_bytes(&bytes)
+            .unwrap()
+            .fill_type,
+        FillType::Empty
+    );
+
+    // Payloads from a newer version, or cut short, are rejected
+    let mut bytes = terrain.to_network_bytes(NetworkCodec::Deflate);
+    assert!(ChunkData::<u8>::from_network_bytes(&bytes[..bytes.len() / 2]).is_err());
+    bytes[4] = CHUNK_NETWORK_VERSION + 1;
+    assert!(ChunkData::<u8>::from_network_bytes(&bytes).is_err());
+    assert!(ChunkData::<u8>::from_network_bytes(b"BVWN").is_err());
+
+    // Empty runs, and more runs than voxels, are rejected before decoding them. The raw payload
+    // of an empty chunk ends with one run: its count, its length of three bytes and its index.
+    let bytes = empty.to_network_bytes(NetworkCodec::Raw);
+    let before_runs = &bytes[..bytes.len() - 5];
+    for runs in [&[0xff, 0xff, 0xff, 0xff, 0x0f][..], &[1, 0, 0]] {
+        let garbled = [before_runs, runs].concat();
+        let error = ChunkData::<u8>::from_network_bytes(&garbled).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
+
+#[test]
+fn edits_at_chunk_borders_remesh_the_neighbors() {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::utils::HashSet;
+
+    #[derive(Resource, Clone, Default)]
+    struct BorderWorld;
+
+    impl VoxelWorldConfig for BorderWorld {
+        type MaterialIndex = u8;
+
+        fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+            Box::new(|_| {
+                Box::new(|pos| {
+                    if pos.y < 4 {
+                        WorldVoxel::Solid(0)
+                    } else {
+                        WorldVoxel::Air
+                    }
+                })
+            })
+        }
+    }
+
+    let mut app = _mesh_test_app_with(
+        VoxelWorldPlugin::<BorderWorld>::minimal()
+            .with_fixed_region(IVec3::new(-1, 0, -1), IVec3::new(1, 0, 1)),
+    );
+
+    // The chunks that get remeshed until all chunks are done
+    let remeshed_until_done = |app: &mut App| {
+        let mut remeshed = HashSet::new();
+        let done = _update_until(app, |app| {
+            remeshed.extend(
+                app.world_mut()
+                    .resource_mut::<Events<ChunkWillRemesh<BorderWorld>>>()
+                    .drain()
+                    .map(|event| event.chunk_key),
+            );
+            !_is_meshing::<BorderWorld>(app)
+        });
+        assert!(done, "the chunks were never meshed");
+        remeshed
+    };
+    let set_voxel = |app: &mut App, position: IVec3| {
+        app.world_mut()
+            .run_system_once(move |mut voxel_world: VoxelWorld<BorderWorld>| {
+                voxel_world.set_voxel(position, WorldVoxel::Solid(1));
+            });
+        remeshed_until_done(app)
+    };
+    remeshed_until_done(&mut app);
+
+    // Inside a chunk, only that chunk changes
+    assert_eq!(
+        set_voxel(&mut app, IVec3::new(10, 4, 10)),
+        HashSet::from_iter([IVec3::ZERO])
+    );
+
+    // On a face, the neighbor across it has the voxel in its padding
+    assert_eq!(
+        set_voxel(&mut app, IVec3::new(CHUNK_SIZE_I - 1, 4, 10)),
+        HashSet::from_iter([IVec3::ZERO, IVec3::X])
+    );
+
+    // In a corner, the diagonal neighbors have it too, for their ambient occlusion
+    assert_eq!(
+        set_voxel(&mut app, IVec3::new(0, 4, 0)),
+        HashSet::from_iter([
+            IVec3::ZERO,
+            IVec3::NEG_X,
+            IVec3::NEG_Z,
+            IVec3::new(-1, 0, -1)
+        ])
+    );
+}
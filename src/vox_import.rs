@@ -0,0 +1,468 @@
+///
+/// MagicaVoxel import
+/// Loads `VoxelModel`s from MagicaVoxel `.vox` files, whole or split into parts.
+///
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    configuration::VoxelWorldConfig, voxel::WorldVoxel, voxel_model::VoxelModel,
+    voxel_preview::VoxelModelMesh,
+};
+
+const VOX_MAGIC: &[u8; 4] = b"VOX ";
+
+/// The furthest a node of the scene graph can move its children along each axis. MagicaVoxel
+/// scenes are much smaller than this.
+const MAX_VOX_TRANSLATION: i32 = 1 << 16;
+
+impl<I: Copy + PartialEq> VoxelModel<I> {
+    /// Load the first model of a MagicaVoxel `.vox` file. `material` maps the palette index of
+    /// each voxel (1 to 255) to a material index, and voxels it returns `None` for are left
+    /// `Unset`. MagicaVoxel models are z-up, so their `z` axis becomes `y` here, and their `y`
+    /// axis `-z`.
+    pub fn from_vox_file(
+        path: impl AsRef<Path>,
+        material: impl Fn(u8) -> Option<I>,
+    ) -> io::Result<Self> {
+        Self::from_vox_bytes(&fs::read(path)?, material)
+    }
+
+    /// Like `from_vox_file`, but with the contents of a `.vox` file
+    pub fn from_vox_bytes(bytes: &[u8], material: impl Fn(u8) -> Option<I>) -> io::Result<Self> {
+        let scene = VoxScene::parse(bytes)?;
+        let (vox_size, voxels) = scene
+            .models
+            .first()
+            .ok_or_else(|| invalid("no model in vox file"))?;
+        let mut model = VoxelModel::try_new(UVec3::new(vox_size.x, vox_size.z, vox_size.y))?;
+        for &[x, y, z, palette_index] in voxels {
+            if let Some(material) = material(palette_index) {
+                model.set(
+                    IVec3::new(x as i32, z as i32, vox_size.y as i32 - 1 - y as i32),
+                    WorldVoxel::Solid(material),
+                );
+            }
+        }
+        Ok(model)
+    }
+
+    /// Load all the models of a MagicaVoxel `.vox` file as separate parts, placed like in the
+    /// scene of the file, for voxel characters and props that are animated by moving their parts.
+    /// `split` decides whether each object or each layer becomes a part. Spawn the parts with
+    /// `spawn_vox_parts`. Rotations of objects in the scene are not applied.
+    ///
+    /// `VoxelModelMesh` only meshes the first `CHUNK_SIZE` voxels of a model along each axis, so
+    /// parts larger than that are cut off when spawned. Split bigger characters into objects or
+    /// layers that fit. Parts of more than `MAX_MODEL_VOXELS` voxels are an `InvalidData` error.
+    pub fn parts_from_vox_file(
+        path: impl AsRef<Path>,
+        material: impl Fn(u8) -> Option<I>,
+        split: VoxSplit,
+    ) -> io::Result<Vec<VoxPart<I>>> {
+        Self::parts_from_vox_bytes(&fs::read(path)?, material, split)
+    }
+
+    /// Like `parts_from_vox_file`, but with the contents of a `.vox` file
+    pub fn parts_from_vox_bytes(
+        bytes: &[u8],
+        material: impl Fn(u8) -> Option<I>,
+        split: VoxSplit,
+    ) -> io::Result<Vec<VoxPart<I>>> {
+        let scene = VoxScene::parse(bytes)?;
+        if scene.models.is_empty() {
+            return Err(invalid("no model in vox file"));
+        }
+
+        // The objects of the scene, by the part they go into
+        let mut groups: Vec<(i32, String, Vec<VoxInstance>)> = Vec::new();
+        for (index, instance) in scene.instances().into_iter().enumerate() {
+            let (key, name) = match split {
+                VoxSplit::Objects => (
+                    index as i32,
+                    instance
+                        .name
+                        .clone()
+                        .unwrap_or_else(|| format!("object {index}")),
+                ),
+                VoxSplit::Layers => (
+                    instance.layer,
+                    scene
+                        .layers
+                        .get(&instance.layer)
+                        .cloned()
+                        .unwrap_or_else(|| format!("layer {}", instance.layer)),
+                ),
+            };
+            match groups.iter_mut().find(|(group_key, ..)| *group_key == key) {
+                Some((.., instances)) => instances.push(instance),
+                None => groups.push((key, name, vec![instance])),
+            }
+        }
+
+        let mut parts = Vec::new();
+        for (_, name, instances) in groups {
+            // The voxels in the y-up space of the scene. Objects are placed by their center.
+            let voxels: Vec<(IVec3, u8)> = instances
+                .iter()
+                .filter_map(|instance| {
+                    let (size, voxels) = scene.models.get(instance.model)?;
+                    let center = (*size / 2).as_ivec3();
+                    Some(voxels.iter().map(move |&[x, y, z, palette_index]| {
+                        let vox = instance.translation + IVec3::new(x as i32, y as i32, z as i32)
+                            - center;
+                        (IVec3::new(vox.x, vox.z, -vox.y), palette_index)
+                    }))
+                })
+                .flatten()
+                .collect();
+            let Some(min) = voxels
+                .iter()
+                .map(|(position, _)| *position)
+                .reduce(IVec3::min)
+            else {
+                continue;
+            };
+            let max = voxels
+                .iter()
+                .map(|(position, _)| *position)
+                .fold(min, IVec3::max);
+
+            let size = (max - min + IVec3::ONE).as_uvec3();
+            let mut model = VoxelModel::try_new(size)?;
+            for (position, palette_index) in voxels {
+                if let Some(material) = material(palette_index) {
+                    model.set(position - min, WorldVoxel::Solid(material));
+                }
+            }
+            let pivot = size.as_vec3() / 2.0;
+            parts.push(VoxPart {
+                name,
+                model,
+                pivot,
+                translation: min.as_vec3() + pivot,
+            });
+        }
+        Ok(parts)
+    }
+}
+
+/// How `VoxelModel::parts_from_vox_file` splits a `.vox` file into parts
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoxSplit {
+    /// Every object in the scene is a part of its own, named after the object
+    #[default]
+    Objects,
+
+    /// The objects of each layer are merged into one part, named after the layer
+    Layers,
+}
+
+/// A part of a model loaded with `VoxelModel::parts_from_vox_file`
+#[derive(Clone, Debug)]
+pub struct VoxPart<I = u8> {
+    pub name: String,
+    pub model: VoxelModel<I>,
+
+    /// The point the part turns around, in voxels from the corner of the model. This is the
+    /// center of the model.
+    pub pivot: Vec3,
+
+    /// Where the pivot is in the scene, in voxels
+    pub translation: Vec3,
+}
+
+impl<I: Copy + PartialEq + Send + Sync + 'static> VoxPart<I> {
+    /// The components of an entity that shows the part, with its transform at the pivot, for
+    /// world `C`
+    pub fn into_bundle<C: VoxelWorldConfig<MaterialIndex = I>>(
+        self,
+        voxel_size: f32,
+    ) -> impl Bundle {
+        (
+            Name::new(self.name),
+            VoxelModelMesh::<C>::new(self.model).with_pivot(self.pivot),
+            SpatialBundle::from_transform(Transform::from_translation(
+                self.translation * voxel_size,
+            )),
+        )
+    }
+}
+
+/// Spawn the parts of a `.vox` file as children of a new entity, which is returned. Each part gets
+/// a `Name`, a `VoxelModelMesh` drawn with the material of world `C`, and a `Transform` at its
+/// pivot, so it can be animated like any other entity.
+pub fn spawn_vox_parts<C: VoxelWorldConfig>(
+    commands: &mut Commands,
+    parts: Vec<VoxPart<C::MaterialIndex>>,
+    voxel_size: f32,
+) -> Entity {
+    commands
+        .spawn(SpatialBundle::default())
+        .with_children(|parent| {
+            for part in parts {
+                parent.spawn(part.into_bundle::<C>(voxel_size));
+            }
+        })
+        .id()
+}
+
+/// An object placed in the scene of a vox file
+struct VoxInstance {
+    model: usize,
+    name: Option<String>,
+    layer: i32,
+    /// The position of the center of the model, in the z-up space of the file
+    translation: IVec3,
+}
+
+enum VoxNode {
+    Transform {
+        name: Option<String>,
+        child: i32,
+        layer: i32,
+        translation: IVec3,
+    },
+    Group {
+        children: Vec<i32>,
+    },
+    Shape {
+        models: Vec<i32>,
+    },
+}
+
+/// The chunks of a vox file that this importer reads
+#[derive(Default)]
+struct VoxScene {
+    /// The size and the `x, y, z, palette index` voxels of each model
+    models: Vec<(UVec3, Vec<[u8; 4]>)>,
+    nodes: HashMap<i32, VoxNode>,
+    layers: HashMap<i32, String>,
+}
+
+impl VoxScene {
+    fn parse(bytes: &[u8]) -> io::Result<Self> {
+        let mut r = bytes;
+
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != VOX_MAGIC {
+            return Err(invalid("not a vox file"));
+        }
+        let _version = read_u32(&mut r)?;
+
+        let (id, _, mut children) = read_chunk(&mut r)?;
+        if &id != b"MAIN" {
+            return Err(invalid("missing MAIN chunk"));
+        }
+
+        let mut scene = Self::default();
+        let mut size = None;
+        while !children.is_empty() {
+            let (id, mut content, _) = read_chunk(&mut children)?;
+            match &id {
+                b"SIZE" => {
+                    let x = read_u32(&mut content)?;
+                    let y = read_u32(&mut content)?;
+                    let z = read_u32(&mut content)?;
+                    size = Some(UVec3::new(x, y, z));
+                }
+                b"XYZI" => {
+                    let vox_size = size
+                        .take()
+                        .ok_or_else(|| invalid("XYZI chunk before SIZE"))?;
+                    let mut voxels = Vec::new();
+                    for _ in 0..read_u32(&mut content)? {
+                        let mut voxel = [0; 4];
+                        content.read_exact(&mut voxel)?;
+                        voxels.push(voxel);
+                    }
+                    scene.models.push((vox_size, voxels));
+                }
+                b"nTRN" => {
+                    let node_id = read_i32(&mut content)?;
+                    let attributes = read_dict(&mut content)?;
+                    let child = read_i32(&mut content)?;
+                    let _reserved = read_i32(&mut content)?;
+                    let layer = read_i32(&mut content)?;
+                    let frames = read_i32(&mut content)?;
+                    let mut translation = IVec3::ZERO;
+                    for frame in 0..frames {
+                        let frame_attributes = read_dict(&mut content)?;
+                        if frame == 0 {
+                            if let Some(t) = frame_attributes.get("_t") {
+                                translation = parse_translation(t)?;
+                            }
+                        }
+                    }
+                    scene.nodes.insert(
+                        node_id,
+                        VoxNode::Transform {
+                            name: attributes.get("_name").cloned(),
+                            child,
+                            layer,
+                            translation,
+                        },
+                    );
+                }
+                b"nGRP" => {
+                    let node_id = read_i32(&mut content)?;
+                    let _attributes = read_dict(&mut content)?;
+                    let children = (0..read_i32(&mut content)?)
+                        .map(|_| read_i32(&mut content))
+                        .collect::<io::Result<_>>()?;
+                    scene.nodes.insert(node_id, VoxNode::Group { children });
+                }
+                b"nSHP" => {
+                    let node_id = read_i32(&mut content)?;
+                    let _attributes = read_dict(&mut content)?;
+                    let mut models = Vec::new();
+                    for _ in 0..read_i32(&mut content)? {
+                        models.push(read_i32(&mut content)?);
+                        let _model_attributes = read_dict(&mut content)?;
+                    }
+                    scene.nodes.insert(node_id, VoxNode::Shape { models });
+                }
+                b"LAYR" => {
+                    let layer_id = read_i32(&mut content)?;
+                    let attributes = read_dict(&mut content)?;
+                    if let Some(name) = attributes.get("_name") {
+                        scene.layers.insert(layer_id, name.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(scene)
+    }
+
+    /// The objects in the scene, in the order of the scene graph. Files without a scene graph
+    /// have all their models at the origin.
+    fn instances(&self) -> Vec<VoxInstance> {
+        let mut instances = Vec::new();
+        if self.nodes.is_empty() {
+            instances.extend((0..self.models.len()).map(|model| VoxInstance {
+                model,
+                name: None,
+                layer: 0,
+                translation: IVec3::ZERO,
+            }));
+        } else {
+            self.collect_instances(0, IVec3::ZERO, None, 0, 0, &mut instances);
+        }
+        instances
+    }
+
+    fn collect_instances(
+        &self,
+        node_id: i32,
+        translation: IVec3,
+        name: Option<&String>,
+        layer: i32,
+        depth: u32,
+        instances: &mut Vec<VoxInstance>,
+    ) {
+        // Broken files could loop back on themselves
+        if depth > 64 {
+            return;
+        }
+        match self.nodes.get(&node_id) {
+            Some(VoxNode::Transform {
+                name: node_name,
+                child,
+                layer: node_layer,
+                translation: node_translation,
+            }) => self.collect_instances(
+                *child,
+                translation + *node_translation,
+                node_name.as_ref().or(name),
+                if *node_layer >= 0 { *node_layer } else { layer },
+                depth + 1,
+                instances,
+            ),
+            Some(VoxNode::Group { children }) => {
+                for child in children {
+                    self.collect_instances(*child, translation, name, layer, depth + 1, instances);
+                }
+            }
+            Some(VoxNode::Shape { models }) => {
+                instances.extend(models.iter().map(|model| VoxInstance {
+                    model: *model as usize,
+                    name: name.cloned(),
+                    layer,
+                    translation,
+                }));
+            }
+            None => {}
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Read a chunk of a vox file, returning its id, content and children
+fn read_chunk<'a>(r: &mut &'a [u8]) -> io::Result<([u8; 4], &'a [u8], &'a [u8])> {
+    let mut id = [0; 4];
+    r.read_exact(&mut id)?;
+    let content_len = read_u32(r)? as usize;
+    let children_len = read_u32(r)? as usize;
+    if r.len() < content_len + children_len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    let (content, rest) = r.split_at(content_len);
+    let (children, rest) = rest.split_at(children_len);
+    *r = rest;
+    Ok((id, content, children))
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_i32(r: &mut impl Read) -> io::Result<i32> {
+    read_u32(r).map(|value| value as i32)
+}
+
+fn read_string(r: &mut &[u8]) -> io::Result<String> {
+    let len = read_u32(r)? as usize;
+    // Check the length before allocating, so a broken length can't ask for gigabytes
+    if r.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let (bytes, rest) = r.split_at(len);
+    *r = rest;
+    String::from_utf8(bytes.to_vec()).map_err(|_| invalid("string is not utf-8"))
+}
+
+fn read_dict(r: &mut &[u8]) -> io::Result<HashMap<String, String>> {
+    (0..read_u32(r)?)
+        .map(|_| Ok((read_string(r)?, read_string(r)?)))
+        .collect()
+}
+
+/// Parse a translation like `"-3 0 12"`. Translations further out than `MAX_VOX_TRANSLATION`
+/// are rejected, so that the parts they place can't span more than `i32` can hold.
+fn parse_translation(t: &str) -> io::Result<IVec3> {
+    let values: Vec<i32> = t
+        .split_whitespace()
+        .map(|value| value.parse().map_err(|_| invalid("invalid translation")))
+        .collect::<io::Result<_>>()?;
+    match values[..] {
+        [x, y, z] if [x, y, z].iter().all(|v| v.abs() <= MAX_VOX_TRANSLATION) => {
+            Ok(IVec3::new(x, y, z))
+        }
+        [_, _, _] => Err(invalid("translation out of range")),
+        _ => Err(invalid("invalid translation")),
+    }
+}
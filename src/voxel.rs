@@ -1,6 +1,10 @@
+use std::{hash::Hash, sync::Arc};
+
 use bevy::{prelude::*, render::primitives::Aabb};
 use block_mesh::{MergeVoxel, Voxel, VoxelVisibility};
 
+use crate::configuration::TextureIndexMapperFn;
+
 pub const VOXEL_SIZE: f32 = 1.;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
@@ -11,6 +15,64 @@ pub enum WorldVoxel<I = u8> {
     Solid(I),
 }
 
+/// Asserts at compile time that `WorldVoxel<I>` takes up the given number of bytes.
+///
+/// Voxel data is stored as large arrays of `WorldVoxel`, so index types that leave a niche for
+/// the `Unset` and `Air` variants (such as enums with fewer than 255 variants) can halve or
+/// better the memory used per chunk. Use this to make sure a material index type keeps that
+/// property as it grows.
+///
+/// ```
+/// bevy_voxel_world::assert_voxel_size!(u8, 2);
+/// ```
+#[macro_export]
+macro_rules! assert_voxel_size {
+    ($index:ty, $bytes:expr) => {
+        const _: () = assert!(
+            ::core::mem::size_of::<$crate::prelude::WorldVoxel<$index>>() == $bytes,
+            "WorldVoxel does not have the expected size for this material index type"
+        );
+    };
+}
+
+/// Metadata for enum material index types. Derive it with `#[derive(VoxelIndex)]`, which also
+/// derives the traits required by `VoxelWorldConfig::MaterialIndex`.
+///
+/// ```
+/// use bevy_voxel_world::prelude::*;
+///
+/// #[derive(VoxelIndex, Debug)]
+/// enum BlockTexture {
+///     #[voxel(name = "Snowy brick", textures = [0, 1, 2])]
+///     SnowyBrick,
+///     #[voxel(default, textures = 3)]
+///     Grass,
+/// }
+///
+/// assert_eq!(BlockTexture::default(), BlockTexture::Grass);
+/// assert_eq!(BlockTexture::SnowyBrick.name(), "Snowy brick");
+/// assert_eq!(BlockTexture::Grass.texture_indexes(), Some([3, 3, 3]));
+/// ```
+pub trait VoxelIndex: Copy + Hash + Eq + Default + Send + Sync + 'static {
+    /// All variants, in declaration order
+    const VARIANTS: &'static [Self];
+
+    /// The position of this variant in `VARIANTS`
+    fn ordinal(&self) -> usize;
+
+    /// Display name, set with `#[voxel(name = "...")]`
+    fn name(&self) -> &'static str;
+
+    /// Texture indexes for `[top, sides, bottom]`, set with `#[voxel(textures = ...)]`
+    fn texture_indexes(&self) -> Option<[u32; 3]>;
+
+    /// A texture index mapper built from the `textures` metadata, for use in
+    /// `VoxelWorldConfig::texture_index_mapper`. Variants without textures map to index 0.
+    fn texture_index_mapper() -> TextureIndexMapperFn<Self> {
+        Arc::new(|index: Self| index.texture_indexes().unwrap_or([0, 0, 0]))
+    }
+}
+
 impl<I: PartialEq> WorldVoxel<I> {
     pub fn is_unset(&self) -> bool {
         *self == WorldVoxel::Unset
@@ -46,9 +108,8 @@ impl<I: PartialEq + Eq + Default + Copy> MergeVoxel for WorldVoxel<I> {
     }
 }
 
-#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Hash)]
 pub enum VoxelFace {
-    None,
     Bottom,
     Top,
     Left,
@@ -57,24 +118,225 @@ pub enum VoxelFace {
     Forward,
 }
 
-impl TryFrom<VoxelFace> for Vec3 {
-    type Error = ();
+impl VoxelFace {
+    /// All six faces
+    pub const ALL: [VoxelFace; 6] = [
+        VoxelFace::Bottom,
+        VoxelFace::Top,
+        VoxelFace::Left,
+        VoxelFace::Right,
+        VoxelFace::Back,
+        VoxelFace::Forward,
+    ];
+
+    /// Get the face pointing in the direction of the given axis aligned normal.
+    /// Returns `None` if the normal is not a unit axis.
+    pub fn from_normal(normal: IVec3) -> Option<Self> {
+        match normal {
+            IVec3::NEG_Y => Some(VoxelFace::Bottom),
+            IVec3::Y => Some(VoxelFace::Top),
+            IVec3::NEG_X => Some(VoxelFace::Left),
+            IVec3::X => Some(VoxelFace::Right),
+            IVec3::NEG_Z => Some(VoxelFace::Back),
+            IVec3::Z => Some(VoxelFace::Forward),
+            _ => None,
+        }
+    }
+
+    /// The unit vector the face points along
+    pub const fn normal(self) -> IVec3 {
+        match self {
+            VoxelFace::Bottom => IVec3::NEG_Y,
+            VoxelFace::Top => IVec3::Y,
+            VoxelFace::Left => IVec3::NEG_X,
+            VoxelFace::Right => IVec3::X,
+            VoxelFace::Back => IVec3::NEG_Z,
+            VoxelFace::Forward => IVec3::Z,
+        }
+    }
+
+    /// Same as `normal`, as a `Vec3`
+    pub fn direction(self) -> Vec3 {
+        self.normal().as_vec3()
+    }
+
+    /// The face on the other side of the voxel
+    pub const fn opposite(self) -> Self {
+        match self {
+            VoxelFace::Bottom => VoxelFace::Top,
+            VoxelFace::Top => VoxelFace::Bottom,
+            VoxelFace::Left => VoxelFace::Right,
+            VoxelFace::Right => VoxelFace::Left,
+            VoxelFace::Back => VoxelFace::Forward,
+            VoxelFace::Forward => VoxelFace::Back,
+        }
+    }
+
+    /// The neighboring voxel position on the other side of this face of the voxel at `position`
+    pub fn neighbor(self, position: IVec3) -> IVec3 {
+        position + self.normal()
+    }
+}
+
+impl From<VoxelFace> for Vec3 {
+    fn from(value: VoxelFace) -> Self {
+        value.direction()
+    }
+}
+
+/// Texture indexes for each of the six faces of a voxel
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct FaceTextureIndex {
+    pub top: u32,
+    pub bottom: u32,
+    pub left: u32,
+    pub right: u32,
+    pub back: u32,
+    pub forward: u32,
+}
+
+impl FaceTextureIndex {
+    /// Use `top` for the top face, `sides` for the four side faces, and `bottom` for the bottom
+    /// face, in the same order as the output of `texture_index_mapper`. Usable in constants, like
+    /// `const GRASS: FaceTextureIndex = FaceTextureIndex::new(0, 1, 2);`
+    pub const fn new(top: u32, sides: u32, bottom: u32) -> Self {
+        Self {
+            top,
+            bottom,
+            left: sides,
+            right: sides,
+            back: sides,
+            forward: sides,
+        }
+    }
+
+    /// Use the same texture index for all faces
+    pub const fn all(index: u32) -> Self {
+        Self::new(index, index, index)
+    }
+
+    /// Set the texture index of the top face. Together with the other `with_` methods, this
+    /// builds up blocks with differing faces one face at a time:
+    /// `FaceTextureIndex::all(4).with_top(0).with_forward(7)`
+    pub const fn with_top(mut self, index: u32) -> Self {
+        self.top = index;
+        self
+    }
+
+    /// Set the texture index of the bottom face
+    pub const fn with_bottom(mut self, index: u32) -> Self {
+        self.bottom = index;
+        self
+    }
+
+    /// Set the texture index of the left face, pointing towards negative `x`
+    pub const fn with_left(mut self, index: u32) -> Self {
+        self.left = index;
+        self
+    }
+
+    /// Set the texture index of the right face, pointing towards positive `x`
+    pub const fn with_right(mut self, index: u32) -> Self {
+        self.right = index;
+        self
+    }
+
+    /// Set the texture index of the back face, pointing towards negative `z`
+    pub const fn with_back(mut self, index: u32) -> Self {
+        self.back = index;
+        self
+    }
+
+    /// Set the texture index of the forward face, pointing towards positive `z`
+    pub const fn with_forward(mut self, index: u32) -> Self {
+        self.forward = index;
+        self
+    }
+
+    /// Set the texture index of the four side faces
+    pub const fn with_sides(self, index: u32) -> Self {
+        self.with_left(index)
+            .with_right(index)
+            .with_back(index)
+            .with_forward(index)
+    }
+
+    /// Set the texture index of the given face
+    pub const fn with_face(self, face: VoxelFace, index: u32) -> Self {
+        match face {
+            VoxelFace::Top => self.with_top(index),
+            VoxelFace::Bottom => self.with_bottom(index),
+            VoxelFace::Left => self.with_left(index),
+            VoxelFace::Right => self.with_right(index),
+            VoxelFace::Back => self.with_back(index),
+            VoxelFace::Forward => self.with_forward(index),
+        }
+    }
+
+    /// Get the texture index of the given face
+    pub fn get(&self, face: VoxelFace) -> u32 {
+        match face {
+            VoxelFace::Top => self.top,
+            VoxelFace::Bottom => self.bottom,
+            VoxelFace::Left => self.left,
+            VoxelFace::Right => self.right,
+            VoxelFace::Back => self.back,
+            VoxelFace::Forward => self.forward,
+        }
+    }
+}
+
+/// Same layout as the output of `texture_index_mapper` and the arguments of
+/// `FaceTextureIndex::new`: `[top, sides, bottom]`.
+impl From<[u32; 3]> for FaceTextureIndex {
+    fn from([top, sides, bottom]: [u32; 3]) -> Self {
+        Self::new(top, sides, bottom)
+    }
+}
+
+/// In the same order as the `VoxelFace` variants: `[bottom, top, left, right, back, forward]`.
+/// Prefer the named `with_` setters for blocks with many different faces.
+impl From<[u32; 6]> for FaceTextureIndex {
+    fn from([bottom, top, left, right, back, forward]: [u32; 6]) -> Self {
+        Self {
+            top,
+            bottom,
+            left,
+            right,
+            back,
+            forward,
+        }
+    }
+}
+
+/// The six voxels sharing a face with a voxel
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VoxelNeighbors<I = u8> {
+    pub above: WorldVoxel<I>,
+    pub below: WorldVoxel<I>,
+    pub left: WorldVoxel<I>,
+    pub right: WorldVoxel<I>,
+    pub back: WorldVoxel<I>,
+    pub forward: WorldVoxel<I>,
+}
 
-    fn try_from(value: VoxelFace) -> Result<Self, Self::Error> {
-        match value {
-            VoxelFace::None => Err(()),
-            VoxelFace::Bottom => Ok(-Vec3::Y),
-            VoxelFace::Top => Ok(Vec3::Y),
-            VoxelFace::Left => Ok(-Vec3::X),
-            VoxelFace::Right => Ok(Vec3::X),
-            VoxelFace::Back => Ok(-Vec3::Z),
-            VoxelFace::Forward => Ok(Vec3::Z),
+impl<I: Copy> VoxelNeighbors<I> {
+    /// Get the neighbor on the other side of the given face
+    pub fn get(&self, face: VoxelFace) -> WorldVoxel<I> {
+        match face {
+            VoxelFace::Top => self.above,
+            VoxelFace::Bottom => self.below,
+            VoxelFace::Left => self.left,
+            VoxelFace::Right => self.right,
+            VoxelFace::Back => self.back,
+            VoxelFace::Forward => self.forward,
         }
     }
 }
 
-#[allow(unused)]
-pub(crate) trait VoxelAabb {
+/// Ray intersection for the bounding boxes of chunks and voxels
+pub trait VoxelAabb {
+    /// The points where the ray enters and leaves the box, or `None` if it misses it
     fn ray_intersection(&self, ray: Ray3d) -> Option<(Vec3, Vec3)>;
 }
 
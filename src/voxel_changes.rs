@@ -0,0 +1,69 @@
+///
+/// Voxel changes
+/// A journal of the voxels changed through `VoxelWorld`, for replicating them to other clients.
+///
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{configuration::VoxelWorldConfig, voxel::WorldVoxel};
+
+/// A single voxel that was changed. `old` is `WorldVoxel::Unset` for voxels in chunks that
+/// were not generated yet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VoxelChange<I> {
+    pub position: IVec3,
+    pub old: WorldVoxel<I>,
+    pub new: WorldVoxel<I>,
+}
+
+/// Add this resource to the app to record every voxel changed through `VoxelWorld`. Changes are
+/// recorded when the write buffer is flushed, in the order they were made, and writes that leave a
+/// voxel as it was are left out. Drain them once per tick with `drain`, and apply the changes
+/// received from other clients with `VoxelWorld::apply_remote_changes`, which doesn't record them
+/// again.
+#[derive(Resource)]
+pub struct VoxelChangeTracker<C: VoxelWorldConfig> {
+    changes: Vec<VoxelChange<C::MaterialIndex>>,
+    remote: HashMap<IVec3, WorldVoxel<C::MaterialIndex>>,
+}
+
+impl<C: VoxelWorldConfig> Default for VoxelChangeTracker<C> {
+    fn default() -> Self {
+        Self {
+            changes: Vec::new(),
+            remote: HashMap::new(),
+        }
+    }
+}
+
+impl<C: VoxelWorldConfig> VoxelChangeTracker<C> {
+    /// The changes recorded since the last `drain`
+    pub fn changes(&self) -> &[VoxelChange<C::MaterialIndex>] {
+        &self.changes
+    }
+
+    /// Take the changes recorded since the last `drain`
+    pub fn drain(&mut self) -> Vec<VoxelChange<C::MaterialIndex>> {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// Remember a remote write, so that it doesn't get recorded when it is flushed
+    pub(crate) fn expect_remote(&mut self, position: IVec3, voxel: WorldVoxel<C::MaterialIndex>) {
+        self.remote.insert(position, voxel);
+    }
+
+    /// Record a flushed write, unless it is a remote write or didn't change the voxel
+    pub(crate) fn record(
+        &mut self,
+        position: IVec3,
+        old: WorldVoxel<C::MaterialIndex>,
+        new: WorldVoxel<C::MaterialIndex>,
+    ) {
+        if self.remote.get(&position) == Some(&new) {
+            self.remote.remove(&position);
+            return;
+        }
+        if old != new {
+            self.changes.push(VoxelChange { position, old, new });
+        }
+    }
+}
@@ -0,0 +1,184 @@
+///
+/// Voxel decals
+/// Textured quads on voxel faces, like posters, signs or bullet holes, that don't change the
+/// voxel data.
+///
+use std::marker::PhantomData;
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_asset::RenderAssetUsages,
+        render_resource::PrimitiveTopology,
+    },
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    chunk::CHUNK_SIZE_I, configuration::VoxelTextureMode, voxel::VoxelFace,
+    voxel_material::ATTRIBUTE_TEX_INDEX,
+};
+
+/// How far decals float in front of the voxel face, in voxels, to keep them from z-fighting
+/// with it
+pub const DECAL_OFFSET: f32 = 0.005;
+
+/// A textured quad on the face of a voxel, placed with `VoxelWorld::place_decal`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoxelDecal {
+    /// The voxel the decal is on
+    pub position: IVec3,
+
+    /// The face of the voxel the decal is on
+    pub face: VoxelFace,
+
+    /// The layer of the voxel texture to draw the decal with, or the tile of a texture atlas
+    pub texture_index: u32,
+
+    /// The size of the decal in voxels, centered on the face. Decals larger than one voxel
+    /// extend over the neighboring faces.
+    pub size: Vec2,
+}
+
+/// The decals of a world by chunk. Decals are kept when their chunk despawns, and show up again
+/// when it is spawned again.
+#[derive(Resource)]
+pub struct VoxelDecals<C> {
+    decals: HashMap<IVec3, Vec<VoxelDecal>>,
+    changed_chunks: HashSet<IVec3>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for VoxelDecals<C> {
+    fn default() -> Self {
+        Self {
+            decals: HashMap::new(),
+            changed_chunks: HashSet::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> VoxelDecals<C> {
+    pub fn insert(&mut self, decal: VoxelDecal) {
+        let chunk_position = decal.position.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+        self.decals.entry(chunk_position).or_default().push(decal);
+        self.changed_chunks.insert(chunk_position);
+    }
+
+    /// Remove all decals on the voxel at the given position
+    pub fn remove(&mut self, position: IVec3) {
+        let chunk_position = position.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+        if let Some(decals) = self.decals.get_mut(&chunk_position) {
+            decals.retain(|decal| decal.position != position);
+            if decals.is_empty() {
+                self.decals.remove(&chunk_position);
+            }
+            self.changed_chunks.insert(chunk_position);
+        }
+    }
+
+    /// The decals in the given chunk
+    pub fn get(&self, chunk_position: IVec3) -> &[VoxelDecal] {
+        self.decals
+            .get(&chunk_position)
+            .map_or(&[], |decals| decals.as_slice())
+    }
+
+    pub(crate) fn take_changed_chunks(&mut self) -> HashSet<IVec3> {
+        std::mem::take(&mut self.changed_chunks)
+    }
+}
+
+/// The mesh with all decals of a chunk. The entity is a child of the chunk, drawn with the voxel
+/// material, and gets replaced whenever the decals of the chunk change.
+#[derive(Component, Clone, Debug)]
+pub struct ChunkDecalMesh<C> {
+    pub chunk_position: IVec3,
+    _marker: PhantomData<C>,
+}
+
+impl<C> ChunkDecalMesh<C> {
+    pub fn new(chunk_position: IVec3) -> Self {
+        Self {
+            chunk_position,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Build a mesh with a quad for each decal. Positions are relative to the chunk entity, the same
+/// as the positions of the chunk mesh.
+pub(crate) fn decal_mesh(
+    decals: &[VoxelDecal],
+    chunk_position: IVec3,
+    texture_mode: VoxelTextureMode,
+) -> Mesh {
+    let chunk_origin = chunk_position * CHUNK_SIZE_I - IVec3::ONE;
+
+    let mut positions = Vec::with_capacity(decals.len() * 4);
+    let mut normals = Vec::with_capacity(decals.len() * 4);
+    let mut tex_coords = Vec::with_capacity(decals.len() * 4);
+    let mut tex_indexes = Vec::with_capacity(decals.len() * 4);
+    let mut indices = Vec::with_capacity(decals.len() * 6);
+
+    for decal in decals {
+        let normal = decal.face.direction();
+
+        // `u` points right and `v` up when looking at the face, so that `u × v` is the normal
+        let (u, v) = if normal.y != 0.0 {
+            (Vec3::X, Vec3::Z * -normal.y)
+        } else {
+            (Vec3::Y.cross(normal), Vec3::Y)
+        };
+        let center = (decal.position - chunk_origin).as_vec3()
+            + Vec3::splat(0.5)
+            + normal * (0.5 + DECAL_OFFSET);
+        let (half_u, half_v) = (u * decal.size.x / 2.0, v * decal.size.y / 2.0);
+
+        indices.extend([0, 1, 2, 0, 2, 3].map(|i| positions.len() as u32 + i));
+        positions.extend(
+            [
+                center - half_u - half_v,
+                center + half_u - half_v,
+                center + half_u + half_v,
+                center - half_u + half_v,
+            ]
+            .map(|corner| corner.to_array()),
+        );
+        normals.extend([normal.to_array(); 4]);
+        tex_coords.extend(
+            [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]
+                .map(|uv| texture_mode.tex_coord(uv, decal.texture_index)),
+        );
+        tex_indexes.extend([[decal.texture_index; 3]; 4]);
+    }
+
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_COLOR,
+        vec![[1.0, 1.0, 1.0, 1.0]; positions.len()],
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        VertexAttributeValues::Float32x3(positions),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        VertexAttributeValues::Float32x3(normals),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        VertexAttributeValues::Float32x2(tex_coords),
+    );
+    mesh.insert_attribute(
+        ATTRIBUTE_TEX_INDEX,
+        VertexAttributeValues::Uint32x3(tex_indexes),
+    );
+    mesh.insert_indices(Indices::U32(indices));
+    mesh
+}
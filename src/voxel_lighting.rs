@@ -0,0 +1,277 @@
+///
+/// Voxel lighting
+/// Block and sky light, flood-filled through the voxels around a chunk and baked into the
+/// vertices of its meshes.
+///
+use std::{
+    collections::VecDeque,
+    hash::{Hash, Hasher},
+};
+
+use bevy::{prelude::*, render::mesh::VertexAttributeValues};
+
+use crate::{
+    chunk::{CHUNK_SIZE_I, PADDED_CHUNK_SIZE},
+    configuration::{MaterialGroup, VoxelLighting, VoxelShape},
+    meshing::{dominant_axis, MeshingOptions},
+    voxel::WorldVoxel,
+    voxel_material::ATTRIBUTE_VOXEL_LIGHT,
+};
+
+/// The brightest light level, of the open sky and the brightest emissive voxels
+pub const MAX_LIGHT: u8 = 15;
+
+/// How far around the padded chunk voxels are looked up. Light from further away has faded out
+/// before it reaches the chunk.
+pub(crate) const LIGHT_MARGIN: i32 = MAX_LIGHT as i32 - 1;
+
+const LIGHT_BOX_SIZE: i32 = PADDED_CHUNK_SIZE as i32 + 2 * LIGHT_MARGIN;
+
+/// The block and sky light of the voxels in and around a padded chunk
+pub(crate) struct ChunkLight {
+    /// The world position of the lowest corner of the lit box
+    min: IVec3,
+    opaque: Vec<bool>,
+    block: Vec<u8>,
+    sky: Vec<u8>,
+}
+
+impl ChunkLight {
+    /// Flood-fill the light around a chunk. `voxel_at` is called once for every voxel of the lit
+    /// box, with its world position, and for the voxels above open voxels along the top of the
+    /// box, up to the first one blocking the light or the `sky_scan_height`. All of these are
+    /// within `lit_bounds`.
+    pub fn compute<I: Copy>(
+        chunk_position: IVec3,
+        mut voxel_at: impl FnMut(IVec3) -> WorldVoxel<I>,
+        meshing_options: &MeshingOptions<I>,
+        emission: &dyn Fn(I) -> u8,
+        lighting: VoxelLighting,
+    ) -> Self {
+        let cell_count = LIGHT_BOX_SIZE.pow(3) as usize;
+        let mut light = Self {
+            min: lit_bounds(chunk_position, lighting).0,
+            opaque: vec![false; cell_count],
+            block: vec![0; cell_count],
+            sky: vec![if lighting.sky_light { 0 } else { MAX_LIGHT }; cell_count],
+        };
+
+        let mut block_queue = VecDeque::new();
+        for index in 0..cell_count {
+            let voxel = voxel_at(light.min + delinearize(index));
+            if let WorldVoxel::Solid(material) = voxel {
+                light.opaque[index] = blocks_light(meshing_options, material);
+                let emitted = emission(material).min(MAX_LIGHT);
+                if emitted > 0 {
+                    light.block[index] = emitted;
+                    block_queue.push_back(index);
+                }
+            }
+        }
+        flood_fill(&mut light.block, &light.opaque, block_queue, false);
+
+        if lighting.sky_light {
+            // The sky shines into the open voxels along the top of the box, unless a voxel further
+            // up their column shades them
+            let top = IVec3::new(0, LIGHT_BOX_SIZE - 1, 0);
+            let scan_height = sky_scan_height(lighting);
+            let (min, opaque) = (light.min, &light.opaque);
+            let sky_queue: VecDeque<usize> = (0..LIGHT_BOX_SIZE)
+                .flat_map(|z| (0..LIGHT_BOX_SIZE).map(move |x| top + IVec3::new(x, 0, z)))
+                .filter(|&cell| {
+                    !opaque[linearize(cell)]
+                        && (1..=scan_height).all(|height| {
+                            let voxel = voxel_at((min + cell).saturating_add(IVec3::Y * height));
+                            !matches!(voxel, WorldVoxel::Solid(material)
+                                if blocks_light(meshing_options, material))
+                        })
+                })
+                .map(linearize)
+                .collect();
+            for &index in &sky_queue {
+                light.sky[index] = MAX_LIGHT;
+            }
+            flood_fill(&mut light.sky, &light.opaque, sky_queue, true);
+        }
+
+        light
+    }
+
+    /// A hash of the light values, to tell apart meshes of the same voxels that are lit
+    /// differently
+    pub fn light_hash(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.block.hash(&mut hasher);
+        self.sky.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Add `ATTRIBUTE_VOXEL_LIGHT` to a chunk mesh, or replace it. Each vertex gets the average
+    /// light of the open voxels touching it in front of its face, for smooth lighting across
+    /// faces.
+    pub fn apply(&self, mesh: &mut Mesh) {
+        let (Some(VertexAttributeValues::Float32x3(positions)), Some(normals)) =
+            (mesh.attribute(Mesh::ATTRIBUTE_POSITION), mesh_normals(mesh))
+        else {
+            return;
+        };
+
+        // Mesh positions are relative to the padded chunk, which starts inside the margin
+        let padded_min = Vec3::splat(LIGHT_MARGIN as f32);
+        let lights: Vec<[f32; 2]> = positions
+            .iter()
+            .zip(&normals)
+            .map(|(position, normal)| {
+                let axis = dominant_axis(*normal);
+                let [tangent_a, tangent_b] = match axis {
+                    IVec3 { x: 0, y: 0, .. } => [Vec3::X, Vec3::Y],
+                    IVec3 { x: 0, .. } => [Vec3::X, Vec3::Z],
+                    _ => [Vec3::Y, Vec3::Z],
+                };
+                let front = padded_min + Vec3::from(*position) + axis.as_vec3() * 0.5;
+
+                let (mut block, mut sky, mut count) = (0u32, 0u32, 0u32);
+                for (a, b) in [(-0.5, -0.5), (0.5, -0.5), (-0.5, 0.5), (0.5, 0.5)] {
+                    let cell = (front + tangent_a * a + tangent_b * b).floor().as_ivec3();
+                    if cell.cmplt(IVec3::ZERO).any()
+                        || cell.cmpge(IVec3::splat(LIGHT_BOX_SIZE)).any()
+                    {
+                        continue;
+                    }
+                    let index = linearize(cell);
+                    if !self.opaque[index] {
+                        block += self.block[index] as u32;
+                        sky += self.sky[index] as u32;
+                        count += 1;
+                    }
+                }
+
+                let level = |light: u32| light as f32 / (count.max(1) * MAX_LIGHT as u32) as f32;
+                [level(block), level(sky)]
+            })
+            .collect();
+
+        mesh.insert_attribute(
+            ATTRIBUTE_VOXEL_LIGHT,
+            VertexAttributeValues::Float32x2(lights),
+        );
+    }
+}
+
+/// The lowest and highest voxel, inclusive, that are looked up to light the chunk at
+/// `chunk_position`: the lit box, and the columns above it that are looked up for sky light
+pub(crate) fn lit_bounds(chunk_position: IVec3, lighting: VoxelLighting) -> (IVec3, IVec3) {
+    let min = chunk_position * CHUNK_SIZE_I - IVec3::ONE - LIGHT_MARGIN;
+    let mut max = min + IVec3::splat(LIGHT_BOX_SIZE - 1);
+    max.y = max.y.saturating_add(sky_scan_height(lighting));
+    (min, max)
+}
+
+/// The chunks whose light can change when the voxel at `position` changes: the chunks it is
+/// within `LIGHT_MARGIN` voxels of the padding of, or above that within the `sky_scan_height`
+pub(crate) fn chunks_lit_through(
+    position: IVec3,
+    lighting: VoxelLighting,
+) -> impl Iterator<Item = IVec3> {
+    let reach = IVec3::splat(LIGHT_MARGIN + 1);
+    let mut min = position - reach;
+    min.y = min.y.saturating_sub(sky_scan_height(lighting));
+    let min = min.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+    let max = (position + reach).div_euclid(IVec3::splat(CHUNK_SIZE_I));
+    (min.z..=max.z).flat_map(move |z| {
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec3::new(x, y, z)))
+    })
+}
+
+/// The normals of a chunk mesh, which are compacted after the light is first baked in with
+/// `VoxelWorldConfig::compact_vertex_attributes`
+fn mesh_normals(mesh: &Mesh) -> Option<Vec<Vec3>> {
+    match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+        Some(VertexAttributeValues::Float32x3(normals)) => {
+            Some(normals.iter().map(|normal| Vec3::from(*normal)).collect())
+        }
+        #[cfg(feature = "compact_vertices")]
+        _ => match mesh.attribute(crate::voxel_material::ATTRIBUTE_COMPACT_NORMAL) {
+            Some(VertexAttributeValues::Snorm8x4(normals)) => Some(
+                normals
+                    .iter()
+                    .map(|[x, y, z, _]| Vec3::new(*x as f32, *y as f32, *z as f32))
+                    .collect(),
+            ),
+            _ => None,
+        },
+        #[cfg(not(feature = "compact_vertices"))]
+        _ => None,
+    }
+}
+
+/// How far above the lit box columns are looked up for voxels shading them from the sky
+fn sky_scan_height(lighting: VoxelLighting) -> i32 {
+    if lighting.sky_light {
+        lighting.sky_scan_height.min(i32::MAX as u32) as i32
+    } else {
+        0
+    }
+}
+
+/// Whether light is stopped by a solid voxel of the given material
+fn blocks_light<I: Copy>(meshing_options: &MeshingOptions<I>, material: I) -> bool {
+    let opaque_group = meshing_options
+        .material_group
+        .as_ref()
+        .is_none_or(|material_group| material_group(material) == MaterialGroup::Opaque);
+    opaque_group && meshing_options.shape(material) == VoxelShape::FullCube
+}
+
+/// Spread light from the queued cells to their open neighbors, one level dimmer with each step.
+/// Sky light at full strength keeps its level going down.
+fn flood_fill(light: &mut [u8], opaque: &[bool], mut queue: VecDeque<usize>, sky: bool) {
+    const DIRECTIONS: [IVec3; 6] = [
+        IVec3::NEG_Y,
+        IVec3::Y,
+        IVec3::X,
+        IVec3::NEG_X,
+        IVec3::Z,
+        IVec3::NEG_Z,
+    ];
+
+    while let Some(index) = queue.pop_front() {
+        let level = light[index];
+        let cell = delinearize(index);
+        for direction in DIRECTIONS {
+            let neighbor = cell + direction;
+            if neighbor.cmplt(IVec3::ZERO).any()
+                || neighbor.cmpge(IVec3::splat(LIGHT_BOX_SIZE)).any()
+            {
+                continue;
+            }
+            let neighbor_index = linearize(neighbor);
+            if opaque[neighbor_index] {
+                continue;
+            }
+
+            let spread = if sky && level == MAX_LIGHT && direction == IVec3::NEG_Y {
+                MAX_LIGHT
+            } else {
+                level.saturating_sub(1)
+            };
+            if spread > light[neighbor_index] {
+                light[neighbor_index] = spread;
+                queue.push_back(neighbor_index);
+            }
+        }
+    }
+}
+
+fn linearize(cell: IVec3) -> usize {
+    (cell.x + LIGHT_BOX_SIZE * (cell.y + LIGHT_BOX_SIZE * cell.z)) as usize
+}
+
+fn delinearize(index: usize) -> IVec3 {
+    let index = index as i32;
+    IVec3::new(
+        index % LIGHT_BOX_SIZE,
+        (index / LIGHT_BOX_SIZE) % LIGHT_BOX_SIZE,
+        index / (LIGHT_BOX_SIZE * LIGHT_BOX_SIZE),
+    )
+}
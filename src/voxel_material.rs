@@ -1,5 +1,7 @@
+use std::marker::PhantomData;
+
 use bevy::{
-    pbr::{MaterialExtension, MaterialExtensionKey, MaterialExtensionPipeline},
+    pbr::{ExtendedMaterial, MaterialExtension, MaterialExtensionKey, MaterialExtensionPipeline},
     prelude::*,
     reflect::TypePath,
     render::{
@@ -11,6 +13,24 @@ use bevy::{
     },
 };
 
+/// The built-in voxel material of the world `C`. Every world gets its own instance, so that the
+/// tint of one world doesn't show up in another. Chunks of `C` use it over the shared
+/// `VoxelWorldMaterialHandle`.
+#[derive(Resource)]
+pub(crate) struct WorldMaterialHandle<C, M: Material> {
+    pub handle: Handle<M>,
+    _marker: PhantomData<C>,
+}
+
+impl<C, M: Material> WorldMaterialHandle<C, M> {
+    pub fn new(handle: Handle<M>) -> Self {
+        Self {
+            handle,
+            _marker: PhantomData,
+        }
+    }
+}
+
 /// Keeps track of the loading status of the image used for the voxel texture
 #[derive(Resource)]
 pub(crate) struct LoadingTexture {
@@ -26,6 +46,29 @@ pub const VOXEL_TEXTURE_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(6
 pub(crate) const ATTRIBUTE_TEX_INDEX: MeshVertexAttribute =
     MeshVertexAttribute::new("TextureIndex", 989640910, VertexFormat::Uint32x3);
 
+/// The block and sky light of a vertex, from 0.0 to 1.0, added to chunk meshes when
+/// `VoxelWorldConfig::voxel_lighting` is on
+pub const ATTRIBUTE_VOXEL_LIGHT: MeshVertexAttribute =
+    MeshVertexAttribute::new("VoxelLight", 989640911, VertexFormat::Float32x2);
+
+/// The normal of a vertex in chunk meshes with compact vertex attributes, see
+/// `VoxelWorldConfig::compact_vertex_attributes`
+#[cfg(feature = "compact_vertices")]
+pub const ATTRIBUTE_COMPACT_NORMAL: MeshVertexAttribute =
+    MeshVertexAttribute::new("CompactNormal", 989640912, VertexFormat::Snorm8x4);
+
+/// The texture coordinates of a vertex in chunk meshes with compact vertex attributes, as the
+/// bits of two half precision floats. Read them in a shader with
+/// `unpack2x16float(uv.x | (uv.y << 16u))`.
+#[cfg(feature = "compact_vertices")]
+pub const ATTRIBUTE_COMPACT_UV: MeshVertexAttribute =
+    MeshVertexAttribute::new("CompactUv", 989640913, VertexFormat::Uint16x2);
+
+/// The color of a vertex in chunk meshes with compact vertex attributes
+#[cfg(feature = "compact_vertices")]
+pub const ATTRIBUTE_COMPACT_COLOR: MeshVertexAttribute =
+    MeshVertexAttribute::new("CompactColor", 989640914, VertexFormat::Unorm8x4);
+
 pub fn vertex_layout() -> Vec<VertexAttributeDescriptor> {
     vec![
         Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
@@ -39,11 +82,44 @@ pub fn vertex_layout() -> Vec<VertexAttributeDescriptor> {
         ATTRIBUTE_TEX_INDEX.at_shader_location(8),
     ]
 }
+
+/// The vertex layout of chunk meshes with compact vertex attributes, at the same shader locations
+/// as `vertex_layout`
+#[cfg(feature = "compact_vertices")]
+pub fn compact_vertex_layout() -> Vec<VertexAttributeDescriptor> {
+    vec![
+        Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+        ATTRIBUTE_COMPACT_NORMAL.at_shader_location(1),
+        ATTRIBUTE_COMPACT_UV.at_shader_location(2),
+        ATTRIBUTE_COMPACT_COLOR.at_shader_location(5),
+        ATTRIBUTE_COMPACT_COLOR.at_shader_location(7),
+        ATTRIBUTE_TEX_INDEX.at_shader_location(8),
+    ]
+}
 #[derive(Asset, AsBindGroup, Debug, Clone, TypePath)]
+#[bind_group_data(StandardVoxelMaterialKey)]
 pub(crate) struct StandardVoxelMaterial {
     #[texture(100, dimension = "2d_array")]
     #[sampler(101)]
     pub voxels_texture: Handle<Image>,
+    /// The color the terrain is multiplied with, see `VoxelWorld::set_global_tint`
+    #[uniform(102)]
+    pub tint: LinearRgba,
+    /// Whether the texture is an atlas, stored as an array texture with a single layer
+    pub atlas: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) struct StandardVoxelMaterialKey {
+    atlas: bool,
+}
+
+impl From<&StandardVoxelMaterial> for StandardVoxelMaterialKey {
+    fn from(material: &StandardVoxelMaterial) -> Self {
+        Self {
+            atlas: material.atlas,
+        }
+    }
 }
 
 impl MaterialExtension for StandardVoxelMaterial {
@@ -59,10 +135,47 @@ impl MaterialExtension for StandardVoxelMaterial {
         _pipeline: &MaterialExtensionPipeline,
         descriptor: &mut RenderPipelineDescriptor,
         layout: &MeshVertexBufferLayoutRef,
-        _key: MaterialExtensionKey<Self>,
+        key: MaterialExtensionKey<Self>,
     ) -> Result<(), SpecializedMeshPipelineError> {
-        let vertex_layout = layout.0.get_layout(&vertex_layout())?;
+        #[cfg(feature = "compact_vertices")]
+        let mut attributes = if layout.0.contains(ATTRIBUTE_COMPACT_NORMAL) {
+            // The mesh pipeline only turns on the vertex inputs for the standard attributes, so
+            // turn them on for the compact ones too
+            if descriptor.vertex.shader == VOXEL_TEXTURE_SHADER_HANDLE {
+                for shader_def in [
+                    "VOXEL_COMPACT_VERTICES",
+                    "VERTEX_NORMALS",
+                    "VERTEX_UVS",
+                    "VERTEX_UVS_A",
+                    "VERTEX_COLORS",
+                ] {
+                    descriptor.vertex.shader_defs.push(shader_def.into());
+                    if let Some(fragment) = descriptor.fragment.as_mut() {
+                        fragment.shader_defs.push(shader_def.into());
+                    }
+                }
+            }
+            compact_vertex_layout()
+        } else {
+            vertex_layout()
+        };
+        #[cfg(not(feature = "compact_vertices"))]
+        let mut attributes = vertex_layout();
+        // Only lit chunk meshes have voxel light
+        if layout.0.contains(ATTRIBUTE_VOXEL_LIGHT) {
+            attributes.push(ATTRIBUTE_VOXEL_LIGHT.at_shader_location(9));
+            descriptor.vertex.shader_defs.push("VOXEL_LIGHT".into());
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push("VOXEL_LIGHT".into());
+            }
+        }
+        let vertex_layout = layout.0.get_layout(&attributes)?;
         descriptor.vertex.buffers = vec![vertex_layout];
+        if key.bind_group_data.atlas {
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push("VOXEL_TEXTURE_ATLAS".into());
+            }
+        }
         Ok(())
     }
 }
@@ -84,3 +197,44 @@ pub(crate) fn prepare_texture(
     let image = images.get_mut(&loading_texture.handle).unwrap();
     image.reinterpret_stacked_2d_as_array(texture_layers.0);
 }
+
+/// The tint of the built-in voxel material, set with `VoxelWorld::set_global_tint`
+#[derive(Resource)]
+pub(crate) struct GlobalTint<C> {
+    pub color: Color,
+    pub intensity: f32,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for GlobalTint<C> {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 1.0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> GlobalTint<C> {
+    fn linear(&self) -> LinearRgba {
+        let color = self.color.to_linear() * self.intensity;
+        color.with_alpha(1.0)
+    }
+}
+
+/// Copies the tint of the world into its built-in voxel material, when it changed
+pub(crate) fn apply_global_tint<C: Send + Sync + 'static>(
+    tint: Res<GlobalTint<C>>,
+    material_handle: Res<
+        WorldMaterialHandle<C, ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>>,
+    >,
+    mut materials: ResMut<Assets<ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>>>,
+) {
+    if !tint.is_changed() {
+        return;
+    }
+    if let Some(material) = materials.get_mut(&material_handle.handle) {
+        material.extension.tint = tint.linear();
+    }
+}
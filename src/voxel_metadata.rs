@@ -0,0 +1,103 @@
+///
+/// Voxel metadata
+/// Extra data on single voxels, like the contents of a chest or the growth stage of a crop, for
+/// worlds with a `VoxelMetadataConfig`.
+///
+use std::{any::Any, marker::PhantomData, sync::Arc};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{chunk::CHUNK_SIZE_I, configuration::VoxelMetadataConfig};
+
+/// Metadata is stored without its type, so that every world can hold it. The `Arc` lets it be
+/// handed out in events without cloning the data.
+pub(crate) type MetadataValue = Arc<dyn Any + Send + Sync>;
+
+/// The voxel metadata of a world by chunk. Metadata is not part of the voxel data, so it is kept
+/// when its chunk is remeshed or despawned, and is still there when the chunk spawns again.
+#[derive(Resource)]
+pub struct VoxelMetadataMap<C> {
+    chunks: HashMap<IVec3, HashMap<IVec3, MetadataValue>>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for VoxelMetadataMap<C> {
+    fn default() -> Self {
+        Self {
+            chunks: HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> VoxelMetadataMap<C> {
+    pub(crate) fn insert(&mut self, position: IVec3, value: MetadataValue) {
+        let chunk_position = position.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+        self.chunks
+            .entry(chunk_position)
+            .or_default()
+            .insert(position, value);
+    }
+
+    pub(crate) fn remove(&mut self, position: IVec3) -> Option<MetadataValue> {
+        let chunk_position = position.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+        let metadata = self.chunks.get_mut(&chunk_position)?;
+        let value = metadata.remove(&position);
+        if metadata.is_empty() {
+            self.chunks.remove(&chunk_position);
+        }
+        value
+    }
+
+    pub(crate) fn get(&self, position: IVec3) -> Option<&MetadataValue> {
+        let chunk_position = position.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+        self.chunks.get(&chunk_position)?.get(&position)
+    }
+
+    /// The metadata of the voxels in the given chunk
+    pub(crate) fn chunk(&self, chunk_position: IVec3) -> Vec<(IVec3, MetadataValue)> {
+        self.chunks
+            .get(&chunk_position)
+            .map(|metadata| {
+                metadata
+                    .iter()
+                    .map(|(position, value)| (*position, value.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Sent along with `ChunkWillDespawn` for chunks that have voxel metadata, so that it can be
+/// persisted together with the chunk
+#[derive(Event)]
+pub struct ChunkMetadataWillDespawn<C> {
+    pub chunk_key: IVec3,
+    pub entity: Entity,
+    metadata: Vec<(IVec3, MetadataValue)>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> ChunkMetadataWillDespawn<C> {
+    pub(crate) fn new(
+        chunk_key: IVec3,
+        entity: Entity,
+        metadata: Vec<(IVec3, MetadataValue)>,
+    ) -> Self {
+        Self {
+            chunk_key,
+            entity,
+            metadata,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: VoxelMetadataConfig> ChunkMetadataWillDespawn<C> {
+    /// The metadata of the voxels in the chunk, by voxel position
+    pub fn metadata(&self) -> impl Iterator<Item = (IVec3, &C::VoxelMetadata)> {
+        self.metadata
+            .iter()
+            .filter_map(|(position, value)| Some((*position, value.downcast_ref()?)))
+    }
+}
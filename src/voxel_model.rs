@@ -0,0 +1,138 @@
+///
+/// Voxel models
+/// Small prefab grids of voxels, like trees or houses, that can be stamped into the world.
+///
+use std::io;
+
+use bevy::prelude::*;
+
+use crate::voxel::WorldVoxel;
+
+/// The most voxels a `VoxelModel` can hold
+pub const MAX_MODEL_VOXELS: u64 = 1 << 26;
+
+/// A rotation of a `VoxelModel` around the vertical axis, in quarter turns counter-clockwise when
+/// seen from above
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ModelRotation {
+    #[default]
+    None,
+    Quarter,
+    Half,
+    ThreeQuarters,
+}
+
+impl ModelRotation {
+    /// Rotate a position around the vertical axis through the origin
+    pub fn rotate(self, position: IVec3) -> IVec3 {
+        let IVec3 { x, y, z } = position;
+        match self {
+            ModelRotation::None => position,
+            ModelRotation::Quarter => IVec3::new(z, y, -x),
+            ModelRotation::Half => IVec3::new(-x, y, -z),
+            ModelRotation::ThreeQuarters => IVec3::new(-z, y, x),
+        }
+    }
+}
+
+/// A small box of voxels with an anchor, for placing prefab structures with `VoxelWorld::stamp`.
+/// `Unset` voxels leave the world as it is when stamped, while `Air` voxels carve out space.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelModel<I = u8> {
+    size: UVec3,
+    voxels: Vec<WorldVoxel<I>>,
+
+    /// The position within the model that ends up at the position it is stamped at, and that it
+    /// is rotated around
+    pub anchor: IVec3,
+}
+
+impl<I: Copy + PartialEq> VoxelModel<I> {
+    /// An empty model of the given size, with every voxel `Unset` and the anchor at the origin.
+    /// Panics if the model would hold more than `MAX_MODEL_VOXELS` voxels, see `try_new`.
+    pub fn new(size: UVec3) -> Self {
+        Self::try_new(size).unwrap()
+    }
+
+    /// Like `new`, but returns an `InvalidData` error instead of panicking when the model would
+    /// hold more than `MAX_MODEL_VOXELS` voxels
+    pub fn try_new(size: UVec3) -> io::Result<Self> {
+        let voxel_count = (size.x as u64)
+            .checked_mul(size.y as u64)
+            .and_then(|count| count.checked_mul(size.z as u64))
+            .filter(|count| *count <= MAX_MODEL_VOXELS)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("a model of size {size} has too many voxels"),
+                )
+            })?;
+        Ok(Self {
+            size,
+            voxels: vec![WorldVoxel::Unset; voxel_count as usize],
+            anchor: IVec3::ZERO,
+        })
+    }
+
+    pub fn with_anchor(mut self, anchor: IVec3) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    pub fn size(&self) -> UVec3 {
+        self.size
+    }
+
+    /// Get the voxel at a position within the model. Positions outside of it are `Unset`.
+    pub fn get(&self, position: IVec3) -> WorldVoxel<I> {
+        self.index(position)
+            .map_or(WorldVoxel::Unset, |index| self.voxels[index])
+    }
+
+    /// Set the voxel at a position within the model. Positions outside of it are ignored.
+    pub fn set(&mut self, position: IVec3, voxel: WorldVoxel<I>) {
+        if let Some(index) = self.index(position) {
+            self.voxels[index] = voxel;
+        }
+    }
+
+    /// The voxels of the model that are not `Unset`, by position within the model
+    pub fn voxels(&self) -> impl Iterator<Item = (IVec3, WorldVoxel<I>)> + '_ {
+        let size = self.size.as_ivec3();
+        self.voxels
+            .iter()
+            .enumerate()
+            .filter(|(_, voxel)| !voxel.is_unset())
+            .map(move |(index, voxel)| {
+                let index = index as i32;
+                let position = IVec3::new(
+                    index % size.x,
+                    (index / size.x) % size.y,
+                    index / (size.x * size.y),
+                );
+                (position, *voxel)
+            })
+    }
+
+    /// The voxels of the model that are not `Unset`, by world position when the model is stamped
+    /// at `position` with the given rotation
+    pub fn placed_voxels(
+        &self,
+        position: IVec3,
+        rotation: ModelRotation,
+    ) -> impl Iterator<Item = (IVec3, WorldVoxel<I>)> + '_ {
+        self.voxels()
+            .map(move |(local, voxel)| (position + rotation.rotate(local - self.anchor), voxel))
+    }
+
+    fn index(&self, position: IVec3) -> Option<usize> {
+        if position.cmplt(IVec3::ZERO).any() || position.as_uvec3().cmpge(self.size).any() {
+            return None;
+        }
+        let position = position.as_uvec3();
+        Some(
+            (position.x + position.y * self.size.x + position.z * self.size.x * self.size.y)
+                as usize,
+        )
+    }
+}
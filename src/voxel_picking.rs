@@ -0,0 +1,170 @@
+///
+/// Voxel picking
+/// Resolves the mouse pointer to the voxel under it, for block placement and removal tools.
+///
+use std::marker::PhantomData;
+
+use bevy::{
+    input::InputSystem,
+    prelude::*,
+    render::camera::{CameraProjection, RenderTarget},
+    utils::HashMap,
+    window::{PrimaryWindow, WindowRef},
+};
+
+use crate::{
+    configuration::VoxelWorldConfig,
+    voxel::{VoxelFace, WorldVoxel},
+    voxel_world::{VoxelWorld, VoxelWorldCamera},
+    voxel_world_internal::ndc_ray,
+};
+
+/// Add this next to `VoxelWorldPlugin` to get a `VoxelPickEvent` whenever the mouse pointer moves
+/// onto a voxel, or a mouse button is pressed or released over one. The pointer is cast from every
+/// `VoxelWorldCamera` of the world into the window it renders to.
+pub struct VoxelWorldPickingPlugin<C> {
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for VoxelWorldPickingPlugin<C> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: VoxelWorldConfig> Plugin for VoxelWorldPickingPlugin<C> {
+    fn build(&self, app: &mut App) {
+        app.add_event::<VoxelPickEvent<C>>()
+            .add_systems(PreUpdate, send_voxel_pick_events::<C>.after(InputSystem));
+    }
+}
+
+/// What the pointer did on the picked voxel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoxelPickKind {
+    /// The pointer moved onto the voxel, or onto another face of it
+    Over,
+
+    /// The pointer moved off the voxel, or off the face of it that it was over
+    Out,
+
+    /// The mouse button was pressed over the voxel
+    Press(MouseButton),
+
+    /// The mouse button was released over the voxel
+    Release(MouseButton),
+}
+
+/// Sent by `VoxelWorldPickingPlugin` for the solid voxel under the mouse pointer
+#[derive(Event, Clone, Debug)]
+pub struct VoxelPickEvent<C: VoxelWorldConfig> {
+    /// The camera the pointer was cast from
+    pub camera: Entity,
+
+    pub kind: VoxelPickKind,
+
+    /// The position of the picked voxel
+    pub position: IVec3,
+
+    /// The face the pointer is over. Placing a block at `face.neighbor(position)` puts it against
+    /// that face. This is `None` when the camera is inside the voxel.
+    pub face: Option<VoxelFace>,
+
+    /// The material of the picked voxel
+    pub material: C::MaterialIndex,
+}
+
+/// Get the ray from the camera through the cursor, or `None` when the cursor is outside the
+/// viewport of the camera. The projection is used directly, so this works from the first frame.
+fn cursor_ray(
+    camera: &Camera,
+    cam_gtf: &GlobalTransform,
+    projection: &Projection,
+    window: &Window,
+) -> Option<Ray3d> {
+    let cursor = window.cursor_position()?;
+    let viewport = match &camera.viewport {
+        Some(viewport) => Rect::from_corners(
+            viewport.physical_position.as_vec2(),
+            (viewport.physical_position + viewport.physical_size).as_vec2(),
+        ),
+        None => Rect::from_corners(Vec2::ZERO, window.physical_size().as_vec2()),
+    };
+    let viewport = Rect::from_corners(
+        viewport.min / window.scale_factor(),
+        viewport.max / window.scale_factor(),
+    );
+    if !viewport.contains(cursor) {
+        return None;
+    }
+
+    // Flip the Y coordinate, since the window origin is at the top
+    let mut ndc = (cursor - viewport.min) * 2.0 / viewport.size() - Vec2::ONE;
+    ndc.y = -ndc.y;
+
+    ndc_ray(cam_gtf, projection.get_clip_from_view(), ndc)
+}
+
+#[allow(clippy::type_complexity)]
+fn send_voxel_pick_events<C: VoxelWorldConfig>(
+    cameras: Query<(Entity, &Camera, &GlobalTransform, &Projection), With<VoxelWorldCamera<C>>>,
+    windows: Query<&Window>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    mouse_buttons: Option<Res<ButtonInput<MouseButton>>>,
+    voxel_world: VoxelWorld<C>,
+    mut hovered: Local<HashMap<Entity, (IVec3, Option<VoxelFace>, C::MaterialIndex)>>,
+    mut ev_voxel_pick: EventWriter<VoxelPickEvent<C>>,
+) {
+    for (camera_entity, camera, cam_gtf, projection) in cameras.iter() {
+        let window_entity = match camera.target {
+            RenderTarget::Window(WindowRef::Primary) => primary_window.get_single().ok(),
+            RenderTarget::Window(WindowRef::Entity(entity)) => Some(entity),
+            _ => None,
+        };
+        let hit = window_entity
+            .and_then(|entity| windows.get(entity).ok())
+            .and_then(|window| cursor_ray(camera, cam_gtf, projection, window))
+            .and_then(|ray| voxel_world.raycast(ray, &|_| true))
+            .and_then(|hit| match hit.voxel {
+                WorldVoxel::Solid(material) => Some((hit.voxel_pos(), hit.face(), material)),
+                _ => None,
+            });
+
+        let pick_event = |kind, (position, face, material)| VoxelPickEvent::<C> {
+            camera: camera_entity,
+            kind,
+            position,
+            face,
+            material,
+        };
+
+        let previous = hovered.get(&camera_entity).copied();
+        if previous.map(|(position, face, _)| (position, face))
+            != hit.map(|(position, face, _)| (position, face))
+        {
+            if let Some(previous) = previous {
+                ev_voxel_pick.send(pick_event(VoxelPickKind::Out, previous));
+            }
+            if let Some(hit) = hit {
+                ev_voxel_pick.send(pick_event(VoxelPickKind::Over, hit));
+            }
+        }
+
+        let Some(hit) = hit else {
+            hovered.remove(&camera_entity);
+            continue;
+        };
+        hovered.insert(camera_entity, hit);
+
+        if let Some(mouse_buttons) = &mouse_buttons {
+            for button in mouse_buttons.get_just_pressed() {
+                ev_voxel_pick.send(pick_event(VoxelPickKind::Press(*button), hit));
+            }
+            for button in mouse_buttons.get_just_released() {
+                ev_voxel_pick.send(pick_event(VoxelPickKind::Release(*button), hit));
+            }
+        }
+    }
+}
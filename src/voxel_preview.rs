@@ -0,0 +1,383 @@
+///
+/// Voxel previews
+/// Renders a `VoxelModel` with the mesher and material of a world into an image, through a camera
+/// of its own, for things like inventory icons, or into the scene, for props and characters.
+///
+use std::{marker::PhantomData, sync::Arc};
+
+use bevy::utils::HashMap;
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{RenderTarget, ScalingMode},
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        view::RenderLayers,
+    },
+};
+use ndshape::ConstShape;
+
+use crate::{
+    chunk::{PaddedChunkShape, CHUNK_SIZE_I, CHUNK_SIZE_U},
+    configuration::VoxelWorldConfig,
+    mesh_cache::MeshRef,
+    meshing::{self, MeshingOptions},
+    voxel::WorldVoxel,
+    voxel_model::VoxelModel,
+    voxel_world_internal::NeedsMaterial,
+};
+
+/// The first render layer handed out to previews that don't pick one with `with_render_layer`.
+/// Each preview gets a layer of its own, counting up from this one.
+pub const PREVIEW_RENDER_LAYERS_START: usize = 64;
+
+/// Renders a model into an image, to show in the UI with a `UiImage`. The model is meshed and
+/// drawn with the material of world `C`, by a camera that only sees the preview.
+///
+/// Models can be up to `CHUNK_SIZE` voxels along each axis. Voxels beyond that are left out.
+/// The preview camera turns itself off once the image is rendered, and changing the component
+/// renders the preview again.
+#[derive(Component)]
+pub struct VoxelPreview<C: VoxelWorldConfig> {
+    pub model: VoxelModel<C::MaterialIndex>,
+
+    /// The direction the preview camera looks from, towards the center of the model. It should
+    /// not be straight up or down.
+    pub view_direction: Vec3,
+
+    /// The render layer of the preview, which should not be seen by any other camera
+    pub render_layer: Option<usize>,
+
+    image: Handle<Image>,
+    _marker: PhantomData<C>,
+}
+
+impl<C: VoxelWorldConfig> VoxelPreview<C> {
+    /// A preview of `model`, rendered into a new image of the given size in pixels
+    pub fn new(
+        model: VoxelModel<C::MaterialIndex>,
+        size: UVec2,
+        images: &mut Assets<Image>,
+    ) -> Self {
+        Self {
+            model,
+            view_direction: Vec3::new(1.0, 1.0, 1.0),
+            render_layer: None,
+            image: images.add(preview_image(size)),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn with_view_direction(mut self, view_direction: Vec3) -> Self {
+        self.view_direction = view_direction;
+        self
+    }
+
+    pub fn with_render_layer(mut self, render_layer: usize) -> Self {
+        self.render_layer = Some(render_layer);
+        self
+    }
+
+    /// The image the preview is rendered into
+    pub fn image(&self) -> Handle<Image> {
+        self.image.clone()
+    }
+}
+
+/// Shows a model in the scene, meshed and drawn with the material of world `C`, for props and
+/// characters made of voxels. The model is drawn with its `pivot` at the entity, so moving and
+/// turning the entity moves and turns the model around the pivot.
+///
+/// Models can be up to `CHUNK_SIZE` voxels along each axis. Voxels beyond that are left out.
+/// Changing the component meshes the model again.
+#[derive(Component)]
+pub struct VoxelModelMesh<C: VoxelWorldConfig> {
+    pub model: VoxelModel<C::MaterialIndex>,
+
+    /// The point of the model at the entity, in voxels from the corner of the model
+    pub pivot: Vec3,
+
+    mesh_entity: Option<Entity>,
+    _marker: PhantomData<C>,
+}
+
+impl<C: VoxelWorldConfig> VoxelModelMesh<C> {
+    /// Show `model` with its center at the entity
+    pub fn new(model: VoxelModel<C::MaterialIndex>) -> Self {
+        Self {
+            pivot: model.size().as_vec3() / 2.0,
+            model,
+            mesh_entity: None,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn with_pivot(mut self, pivot: Vec3) -> Self {
+        self.pivot = pivot;
+        self
+    }
+}
+
+/// An empty image that cameras can render into
+fn preview_image(size: UVec2) -> Image {
+    let size = Extent3d {
+        width: size.x.max(1),
+        height: size.y.max(1),
+        ..default()
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("voxel_preview"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    image.resize(size);
+    image
+}
+
+/// Mesh a model as if it were a chunk, with the model starting at the first voxel inside the
+/// padding
+pub(crate) fn mesh_model<I: PartialEq + Copy>(
+    model: &VoxelModel<I>,
+    meshing_options: &MeshingOptions<I>,
+) -> Mesh {
+    let mut voxels = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
+    for (position, voxel) in model.voxels() {
+        if position.cmplt(IVec3::splat(CHUNK_SIZE_I)).all() {
+            let padded = (position + IVec3::ONE).as_uvec3().to_array();
+            voxels[PaddedChunkShape::linearize(padded) as usize] = voxel;
+        }
+    }
+
+    #[allow(unused_mut)]
+    let mut mesh = meshing::generate_chunk_mesh(
+        Arc::new(voxels),
+        &PaddedChunkShape {},
+        IVec3::ZERO,
+        meshing_options,
+    );
+    #[cfg(feature = "compact_vertices")]
+    if meshing_options.compact_vertices {
+        meshing::compact_vertex_attributes(&mut mesh);
+    }
+    mesh
+}
+
+/// How many frames a preview camera stays on once the material of the preview is assigned. The
+/// render pipeline of a new material can take a few frames to compile, and nothing is drawn
+/// until it has.
+pub(crate) const PREVIEW_RENDER_FRAMES: u32 = 4;
+
+/// Counts up the render layers handed out to previews, and takes back the layers of despawned
+/// previews to hand out again
+#[derive(Resource)]
+pub(crate) struct NextPreviewLayer<C> {
+    layer: usize,
+    free: Vec<usize>,
+    handed_out: HashMap<Entity, usize>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for NextPreviewLayer<C> {
+    fn default() -> Self {
+        Self {
+            layer: PREVIEW_RENDER_LAYERS_START,
+            free: Vec::new(),
+            handed_out: HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> NextPreviewLayer<C> {
+    fn take(&mut self, entity: Entity) -> usize {
+        let layer = self.free.pop().unwrap_or_else(|| {
+            self.layer += 1;
+            self.layer - 1
+        });
+        self.handed_out.insert(entity, layer);
+        layer
+    }
+
+    fn give_back(&mut self, entity: Entity) {
+        if let Some(layer) = self.handed_out.remove(&entity) {
+            self.free.push(layer);
+        }
+    }
+}
+
+/// The camera of a preview, counting down the frames until it is turned off
+#[derive(Component)]
+pub(crate) struct PreviewCamera<C> {
+    frames_left: u32,
+    _marker: PhantomData<C>,
+}
+
+/// Spawn the mesh, camera and light of new and changed previews. The mesh gets its material from
+/// `assign_material`, like the meshes of chunks.
+#[allow(clippy::type_complexity)]
+pub(crate) fn render_voxel_previews<C: VoxelWorldConfig>(
+    mut commands: Commands,
+    mut previews: Query<(Entity, &mut VoxelPreview<C>, Has<Transform>), Changed<VoxelPreview<C>>>,
+    mut removed: RemovedComponents<VoxelPreview<C>>,
+    mut next_layer: ResMut<NextPreviewLayer<C>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    configuration: Res<C>,
+) {
+    for entity in removed.read() {
+        next_layer.give_back(entity);
+    }
+
+    let meshing_options = MeshingOptions::from_config(&*configuration);
+    for (entity, mut preview, has_transform) in previews.iter_mut() {
+        let render_layer = match preview.render_layer {
+            Some(render_layer) => render_layer,
+            None => {
+                let render_layer = next_layer.take(entity);
+                // Keep the layer, without rendering the preview again next frame
+                preview.bypass_change_detection().render_layer = Some(render_layer);
+                render_layer
+            }
+        };
+        // `RenderLayers::layer` only takes the first 64 layers
+        let layers = RenderLayers::none().with(render_layer);
+
+        let voxel_size = configuration.voxel_size();
+        let size = preview
+            .model
+            .size()
+            .min(UVec3::splat(CHUNK_SIZE_U))
+            .as_vec3()
+            * voxel_size;
+        let radius = (size.length() * 0.5).max(voxel_size);
+        let view_direction = preview.view_direction.try_normalize().unwrap_or(Vec3::ONE);
+
+        let mesh = meshes.add(mesh_model(&preview.model, &meshing_options));
+
+        let mut entity = commands.entity(entity);
+        if !has_transform {
+            entity.insert(SpatialBundle::default());
+        }
+        entity.despawn_descendants().with_children(|parent| {
+            parent.spawn((
+                MeshRef(Arc::new(mesh)),
+                NeedsMaterial::<C>(PhantomData),
+                // The mesh starts one voxel in, past the padding
+                Transform::from_translation(-(Vec3::splat(voxel_size) + size * 0.5))
+                    .with_scale(Vec3::splat(voxel_size)),
+                layers.clone(),
+            ));
+            parent.spawn((
+                Camera3dBundle {
+                    camera: Camera {
+                        target: RenderTarget::Image(preview.image.clone()),
+                        clear_color: ClearColorConfig::Custom(Color::NONE),
+                        order: -1,
+                        ..default()
+                    },
+                    projection: OrthographicProjection {
+                        scaling_mode: ScalingMode::Fixed {
+                            width: radius * 2.0,
+                            height: radius * 2.0,
+                        },
+                        near: 0.0,
+                        far: radius * 4.0,
+                        ..default()
+                    }
+                    .into(),
+                    transform: Transform::from_translation(view_direction * radius * 2.0)
+                        .looking_at(Vec3::ZERO, Vec3::Y),
+                    ..default()
+                },
+                PreviewCamera::<C> {
+                    frames_left: PREVIEW_RENDER_FRAMES,
+                    _marker: PhantomData,
+                },
+                layers.clone(),
+            ));
+            parent.spawn((
+                DirectionalLightBundle {
+                    // Light the model from above the camera, so that the faces facing it differ
+                    transform: Transform::from_translation(view_direction + Vec3::Y)
+                        .looking_at(Vec3::ZERO, Vec3::Y),
+                    ..default()
+                },
+                layers,
+            ));
+        });
+    }
+}
+
+/// Turn off preview cameras once their image is rendered. The countdown starts when the mesh of
+/// the preview has its material.
+pub(crate) fn finish_voxel_previews<C: VoxelWorldConfig>(
+    mut cameras: Query<(&mut Camera, &mut PreviewCamera<C>, &Parent)>,
+    children: Query<&Children>,
+    needs_material: Query<(), With<NeedsMaterial<C>>>,
+) {
+    for (mut camera, mut preview_camera, parent) in cameras.iter_mut() {
+        if !camera.is_active {
+            continue;
+        }
+        let waiting_for_material = children
+            .get(parent.get())
+            .is_ok_and(|children| children.iter().any(|&child| needs_material.contains(child)));
+        if waiting_for_material {
+            continue;
+        }
+        if preview_camera.frames_left == 0 {
+            camera.is_active = false;
+        } else {
+            preview_camera.frames_left -= 1;
+        }
+    }
+}
+
+/// Spawn the mesh of new and changed model meshes, as a child of their entity, replacing the one
+/// spawned before. Other children of the entity are left alone.
+#[allow(clippy::type_complexity)]
+pub(crate) fn mesh_voxel_models<C: VoxelWorldConfig>(
+    mut commands: Commands,
+    mut models: Query<(Entity, &mut VoxelModelMesh<C>, Has<Transform>), Changed<VoxelModelMesh<C>>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    configuration: Res<C>,
+) {
+    let meshing_options = MeshingOptions::from_config(&*configuration);
+    let voxel_size = configuration.voxel_size();
+    for (entity, mut model_mesh, has_transform) in models.iter_mut() {
+        if let Some(mesh_entity) = model_mesh.mesh_entity {
+            if let Some(mesh_entity) = commands.get_entity(mesh_entity) {
+                mesh_entity.despawn_recursive();
+            }
+        }
+
+        let mesh = meshes.add(mesh_model(&model_mesh.model, &meshing_options));
+        let mesh_entity = commands
+            .spawn((
+                MeshRef(Arc::new(mesh)),
+                NeedsMaterial::<C>(PhantomData),
+                // The mesh starts one voxel in, past the padding
+                Transform::from_translation(-(model_mesh.pivot + Vec3::ONE) * voxel_size)
+                    .with_scale(Vec3::splat(voxel_size)),
+            ))
+            .set_parent(entity)
+            .id();
+        // Keep the mesh entity, without meshing the model again next frame
+        model_mesh.bypass_change_detection().mesh_entity = Some(mesh_entity);
+
+        if !has_transform {
+            commands.entity(entity).insert(SpatialBundle::default());
+        }
+    }
+}
@@ -0,0 +1,230 @@
+///
+/// Voxel tasks
+/// User computations over the voxels of many chunks, run on the task pool a few chunks per frame,
+/// with the result for each chunk sent as an event.
+///
+use std::{any::Any, collections::VecDeque, marker::PhantomData, sync::Arc};
+
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use futures_lite::future;
+
+use crate::{
+    chunk::CHUNK_SIZE_I,
+    chunk_map::ChunkMap,
+    chunk_ref::ChunkRef,
+    configuration::VoxelWorldConfig,
+    voxel_world_internal::{ModifiedVoxels, VoxelWriteBuffer},
+};
+
+/// Identifies a task started with `VoxelWorld::spawn_task`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VoxelTaskId(u64);
+
+/// The result of a task started with `VoxelWorld::spawn_task` for one chunk. Results arrive in
+/// no particular order.
+#[derive(Event)]
+pub struct VoxelTaskResult<C> {
+    pub task: VoxelTaskId,
+    pub chunk_position: IVec3,
+    result: TaskOutput,
+    _marker: PhantomData<C>,
+}
+
+impl<C> VoxelTaskResult<C> {
+    /// The value the task returned for the chunk, or `None` if it is not a `T`
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.result.downcast_ref()
+    }
+}
+
+/// Sent when a task started with `VoxelWorld::spawn_task` has sent the results for all its
+/// chunks. Chunks of the region that were not spawned are skipped.
+#[derive(Event)]
+pub struct VoxelTaskFinished<C> {
+    pub task: VoxelTaskId,
+    _marker: PhantomData<C>,
+}
+
+/// The value a task returned for a chunk, without its type
+type TaskOutput = Arc<dyn Any + Send + Sync>;
+
+type PerChunkFn<I> = Arc<dyn Fn(&ChunkRef<I>) -> TaskOutput + Send + Sync>;
+
+struct QueuedTask<I> {
+    id: VoxelTaskId,
+    chunks: ChunkRange,
+    per_chunk: PerChunkFn<I>,
+}
+
+/// The chunk positions of a box that a task has not been started on yet, walked x first, then y,
+/// then z, without collecting them up front
+struct ChunkRange {
+    min: IVec3,
+    max: IVec3,
+    next: Option<IVec3>,
+}
+
+impl ChunkRange {
+    fn new(min: IVec3, max: IVec3) -> Self {
+        Self {
+            min,
+            max,
+            next: Some(min),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.next.is_none()
+    }
+}
+
+impl Iterator for ChunkRange {
+    type Item = IVec3;
+
+    fn next(&mut self) -> Option<IVec3> {
+        let current = self.next?;
+        let mut next = current;
+        self.next = if next.x < self.max.x {
+            next.x += 1;
+            Some(next)
+        } else if next.y < self.max.y {
+            next.x = self.min.x;
+            next.y += 1;
+            Some(next)
+        } else if next.z < self.max.z {
+            next.x = self.min.x;
+            next.y = self.min.y;
+            next.z += 1;
+            Some(next)
+        } else {
+            None
+        };
+        Some(current)
+    }
+}
+
+/// The tasks that still have chunks to run on, and the chunks that are running
+#[derive(Resource)]
+pub(crate) struct VoxelTasks<C, I> {
+    next_id: u64,
+    queued: VecDeque<QueuedTask<I>>,
+    running: Vec<(VoxelTaskId, IVec3, Task<TaskOutput>)>,
+    _marker: PhantomData<C>,
+}
+
+impl<C, I> Default for VoxelTasks<C, I> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            queued: VecDeque::new(),
+            running: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C, I> VoxelTasks<C, I> {
+    /// Queue a task over the chunks between `min` and `max`, inclusive
+    pub fn push<T: Send + Sync + 'static>(
+        &mut self,
+        min: IVec3,
+        max: IVec3,
+        per_chunk: impl Fn(&ChunkRef<I>) -> T + Send + Sync + 'static,
+    ) -> VoxelTaskId {
+        let id = VoxelTaskId(self.next_id);
+        self.next_id += 1;
+        let (min, max) = (min.min(max), min.max(max));
+        self.queued.push_back(QueuedTask {
+            id,
+            chunks: ChunkRange::new(min, max),
+            per_chunk: Arc::new(move |chunk| Arc::new(per_chunk(chunk))),
+        });
+        id
+    }
+}
+
+/// Start the queued chunks of the tasks on the task pool, within `max_task_chunks_per_frame`, and
+/// send the results of the chunks that are done
+pub(crate) fn run_voxel_tasks<C: VoxelWorldConfig>(
+    mut tasks: ResMut<VoxelTasks<C, C::MaterialIndex>>,
+    chunk_map: Res<ChunkMap<C, C::MaterialIndex>>,
+    modified_voxels: Res<ModifiedVoxels<C, C::MaterialIndex>>,
+    write_buffer: Res<VoxelWriteBuffer<C, C::MaterialIndex>>,
+    configuration: Res<C>,
+    mut ev_result: EventWriter<VoxelTaskResult<C>>,
+    mut ev_finished: EventWriter<VoxelTaskFinished<C>>,
+) {
+    let VoxelTasks {
+        queued, running, ..
+    } = tasks.as_mut();
+    let thread_pool = AsyncComputeTaskPool::get();
+    let mut budget = configuration.max_task_chunks_per_frame();
+
+    {
+        let chunk_map_read_lock = chunk_map.get_read_lock();
+        while budget > 0 {
+            let Some(task) = queued.iter_mut().find(|task| !task.chunks.is_empty()) else {
+                break;
+            };
+            let chunk_position = task.chunks.next().unwrap();
+            // Chunks that are skipped count too, so that a large region that is mostly not
+            // spawned doesn't get walked in one frame
+            budget -= 1;
+            let Some(chunk_data) =
+                ChunkMap::<C, C::MaterialIndex>::get(&chunk_position, &chunk_map_read_lock)
+            else {
+                continue;
+            };
+
+            // The voxels are copied on the task, only the pending writes are taken along
+            let pending_writes: Vec<_> = write_buffer
+                .iter()
+                .filter(|(position, _)| {
+                    position.div_euclid(IVec3::splat(CHUNK_SIZE_I)) == chunk_position
+                })
+                .copied()
+                .collect();
+            let modified_voxels = modified_voxels.clone();
+            let per_chunk = task.per_chunk.clone();
+            let thread = thread_pool.spawn(async move {
+                let chunk = ChunkRef::from_chunk_data(
+                    chunk_position,
+                    &chunk_data,
+                    &modified_voxels.read().unwrap(),
+                    &pending_writes,
+                );
+                per_chunk(&chunk)
+            });
+            running.push((task.id, chunk_position, thread));
+        }
+    }
+
+    running.retain_mut(|(id, chunk_position, thread)| {
+        let Some(result) = future::block_on(future::poll_once(thread)) else {
+            return true;
+        };
+        ev_result.send(VoxelTaskResult {
+            task: *id,
+            chunk_position: *chunk_position,
+            result,
+            _marker: PhantomData,
+        });
+        false
+    });
+
+    // A task is finished once none of its chunks are queued or running
+    queued.retain(|task| {
+        let finished =
+            task.chunks.is_empty() && !running.iter().any(|(running, ..)| *running == task.id);
+        if finished {
+            ev_finished.send(VoxelTaskFinished {
+                task: task.id,
+                _marker: PhantomData,
+            });
+        }
+        !finished
+    });
+}
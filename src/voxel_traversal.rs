@@ -42,7 +42,8 @@ pub fn voxel_cartesian_traversal<F: FnMut(IVec3) -> bool + Sized>(
 /// - The current voxel coordinates on the grid
 /// - The normalized time `t` along the ray at the moment the ray intersects with the current
 ///   voxel (such that `IntersectionPoint = t * (end - start)`)
-/// - The face through which the voxel was entered by the ray
+/// - The face through which the voxel was entered by the ray, or `None` for the voxel the ray
+///   starts in
 ///
 /// # Example
 /// ```
@@ -72,10 +73,10 @@ pub fn voxel_cartesian_traversal<F: FnMut(IVec3) -> bool + Sized>(
 ///
 ///         // If this is not the very first voxel visited (ie, the one including `start`), draw
 ///         // a debug circle on the face through which the trace entered the current voxel
-///         if let Ok(entered_face_normal) = face.try_into() {
+///         if let Some(entered_face) = face {
 ///             gizmos.circle(
-///                 voxel_center + (entered_face_normal * VOXEL_SIZE / 2.),
-///                 Dir3::new(entered_face_normal).unwrap(),
+///                 voxel_center + (entered_face.direction() * VOXEL_SIZE / 2.),
+///                 Dir3::new(entered_face.direction()).unwrap(),
 ///                 0.8 * VOXEL_SIZE / 2.,
 ///                 css::RED.with_alpha(0.5));
 ///         }
@@ -92,7 +93,7 @@ pub fn voxel_cartesian_traversal<F: FnMut(IVec3) -> bool + Sized>(
 /// what fixed increments of time over the ray are necessary to iterate each voxel exactly once.
 /// This is an implementation of J. Amanatides, A. Woo, "A Fast Voxel Traversal Algorithm for Ray
 /// Tracing", accessible online at http://www.cse.yorku.ca/~amana/research/grid.pdf
-pub fn voxel_line_traversal<F: FnMut(IVec3, f32, VoxelFace) -> bool + Sized>(
+pub fn voxel_line_traversal<F: FnMut(IVec3, f32, Option<VoxelFace>) -> bool + Sized>(
     start: Vec3,
     end: Vec3,
     mut visit_voxel: F,
@@ -137,7 +138,7 @@ pub fn voxel_line_traversal<F: FnMut(IVec3, f32, VoxelFace) -> bool + Sized>(
 
     let r_end_t = 1. / end_t;
     let mut time = max_t.min_element() * r_end_t;
-    let mut face = VoxelFace::None;
+    let mut face = None;
 
     let out_of_bounds = end_voxel + step;
     let mut reached_end = voxel == end_voxel;
@@ -162,7 +163,7 @@ pub fn voxel_line_traversal<F: FnMut(IVec3, f32, VoxelFace) -> bool + Sized>(
     while keep_going && !reached_end {
         if max_t.x < max_t.y && max_t.x < max_t.z {
             time = max_t.x * r_end_t;
-            face = x_face;
+            face = Some(x_face);
 
             voxel.x += step.x;
             max_t.x += delta_t.x;
@@ -170,7 +171,7 @@ pub fn voxel_line_traversal<F: FnMut(IVec3, f32, VoxelFace) -> bool + Sized>(
             reached_end = voxel.x == out_of_bounds.x;
         } else if max_t.y < max_t.z {
             time = max_t.y * r_end_t;
-            face = y_face;
+            face = Some(y_face);
 
             voxel.y += step.y;
             max_t.y += delta_t.y;
@@ -178,7 +179,7 @@ pub fn voxel_line_traversal<F: FnMut(IVec3, f32, VoxelFace) -> bool + Sized>(
             reached_end = voxel.y == out_of_bounds.y;
         } else {
             time = max_t.z * r_end_t;
-            face = z_face;
+            face = Some(z_face);
 
             voxel.z += step.z;
             max_t.z += delta_t.z;
@@ -4,19 +4,43 @@
 ///
 use std::marker::PhantomData;
 use std::sync::Arc;
+use std::time::Duration;
 
-use bevy::{ecs::system::SystemParam, math::bounding::RayCast3d, prelude::*};
+use bevy::{ecs::system::SystemParam, math::bounding::RayCast3d, prelude::*, utils::Instant};
+use rand::{seq::SliceRandom, Rng, RngCore};
 
 use crate::{
+    chunk::{ChunkData, FillType, CHUNK_SIZE_I, PADDED_CHUNK_SIZE},
     chunk_map::ChunkMap,
-    configuration::VoxelWorldConfig,
+    chunk_ref::{ChunkDataHandle, ChunkRef},
+    configuration::{VoxelMetadataConfig, VoxelNavigation, VoxelWorldConfig},
+    positions::{ChunkPos, VoxelPos},
     traversal_alg::voxel_line_traversal,
-    voxel::WorldVoxel,
-    voxel_world_internal::{get_chunk_voxel_position, ModifiedVoxels, VoxelWriteBuffer},
+    voxel::{VoxelFace, WorldVoxel},
+    voxel_changes::{VoxelChange, VoxelChangeTracker},
+    voxel_decal::{VoxelDecal, VoxelDecals},
+    voxel_material::GlobalTint,
+    voxel_metadata::VoxelMetadataMap,
+    voxel_model::{ModelRotation, VoxelModel},
+    voxel_tasks::{VoxelTaskId, VoxelTasks},
+    voxel_world_internal::{
+        apply_voxel_writes, chunk_in_world_bounds, chunks_in_view, get_chunk_voxel_position,
+        AppearanceVersion, ChunkInvalidations, FixedRegion, FlushedChunks, GenerateNowRegions,
+        InsertedChunks, ModifiedVoxels, ReadyChunks, SpawningDistances, VoxelWorldRng,
+        VoxelWriteBuffer,
+    },
+    world_pass::{VoxelRegion, WorldPassId, WorldPasses},
 };
 
 /// This component is used to mark the Camera that bevy_voxel_world should use to determine
-/// which chunks to spawn and despawn.
+/// which chunks to spawn and despawn. Several cameras can be marked, for example for split-screen,
+/// and chunks are then spawned for all of them, and only despawned when out of range of all of
+/// them.
+///
+/// The chunk the camera is in and its six face neighbors are always spawned, regardless of
+/// `spawning_distance` and `max_spawn_per_frame`, and their meshing starts before that of any
+/// other chunks. They are never hidden or despawned while the camera is there, so the ground
+/// below the camera doesn't go missing.
 #[derive(Component)]
 pub struct VoxelWorldCamera<C> {
     _marker: PhantomData<C>,
@@ -30,14 +54,82 @@ impl<C> Default for VoxelWorldCamera<C> {
     }
 }
 
+/// Add this to an entity with a `Transform` to keep the chunks within `radius` chunks of it
+/// spawned, without a camera. The chunks around it are spawned and despawned like around a
+/// camera, in all directions, with no view to cull them by. Use it on dedicated servers and other
+/// headless apps, for example on each player entity, to keep the world around the players
+/// loaded and queryable.
+#[derive(Component)]
+pub struct VoxelWorldSpawnAnchor<C> {
+    /// How far around the anchor chunks are spawned, in chunks, like `spawning_distance`
+    pub radius: u32,
+    _marker: PhantomData<C>,
+}
+
+impl<C> VoxelWorldSpawnAnchor<C> {
+    pub fn new(radius: u32) -> Self {
+        Self {
+            radius,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Add this next to `VoxelWorldCamera` on each eye camera of an XR rig, so that the eyes act as a
+/// single camera. Eyes with the same `rig` spawn and despawn chunks around the point between
+/// them, and share the chunks around it and their level of detail, so chunks don't thrash or pop
+/// in differently for each eye.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VoxelWorldCameraRig {
+    /// The entity the eyes belong to, for example the XR origin. It needs no components of its own.
+    pub rig: Entity,
+
+    /// Which chunks the rig spawns, see `RigSpawnVolume`
+    pub spawn_volume: RigSpawnVolume,
+}
+
+impl VoxelWorldCameraRig {
+    pub fn new(rig: Entity) -> Self {
+        Self {
+            rig,
+            spawn_volume: RigSpawnVolume::default(),
+        }
+    }
+}
+
+/// Which chunks a `VoxelWorldCameraRig` spawns
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RigSpawnVolume {
+    /// The chunks in the combined view of the eyes. The spawning rays are split between the eyes,
+    /// so a rig casts as many as a single camera.
+    #[default]
+    CombinedFrustum,
+
+    /// Every chunk within `spawning_distance` of the rig, whether an eye sees it or not. Rays are
+    /// cast in all directions, and chunks in range are never despawned for being out of view, so
+    /// turning the head doesn't make chunks come and go.
+    Sphere,
+}
+
+/// Add this next to `VoxelWorldCamera` to tell bevy_voxel_world how fast the camera is moving,
+/// in world units per second. Chunk spawning uses it to look ahead when
+/// `VoxelWorldConfig::prefetch_lookahead` is set. Without it, the velocity is estimated from the
+/// movement of the camera between frames.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct VoxelWorldMotionHint {
+    pub velocity: Vec3,
+}
+
+/// An event about a chunk. `K` tells the different kinds of chunk events apart, so that each kind
+/// gets its own event queue.
 #[derive(Event)]
-pub struct ChunkEvent<C> {
+pub struct ChunkEvent<C, K> {
     pub chunk_key: IVec3,
     pub entity: Entity,
-    _marker: PhantomData<C>,
+    _marker: PhantomData<(C, K)>,
 }
 
-impl<C> ChunkEvent<C> {
+impl<C, K> ChunkEvent<C, K> {
     pub fn new(chunk_key: IVec3, entity: Entity) -> Self {
         Self {
             chunk_key,
@@ -45,24 +137,182 @@ impl<C> ChunkEvent<C> {
             _marker: PhantomData,
         }
     }
+}
 
-    pub fn clone(&self) -> Self {
-        Self {
-            chunk_key: self.chunk_key,
-            entity: self.entity,
-            _marker: PhantomData,
-        }
+// Not derived, since that would require `C` and `K` to be `Clone`
+impl<C, K> Clone for ChunkEvent<C, K> {
+    fn clone(&self) -> Self {
+        Self::new(self.chunk_key, self.entity)
     }
 }
 
+/// The kind of `ChunkWillDespawn` events
+pub struct WillDespawn;
+
+/// The kind of `ChunkWillSpawn` events
+pub struct WillSpawn;
+
+/// The kind of `ChunkWillRemesh` events
+pub struct WillRemesh;
+
+/// The kind of `ChunkMeshReady` events
+pub struct MeshReady;
+
+/// The kind of `ChunkModified` events
+pub struct Modified;
+
+/// The kind of `ChunkExplored` events
+pub struct Explored;
+
 /// Fired when a chunk is about to be despawned.
-pub type ChunkWillDespawn<C> = ChunkEvent<C>;
+pub type ChunkWillDespawn<C> = ChunkEvent<C, WillDespawn>;
 
 /// Fired when a chunk is about to be spawned.
-pub type ChunkWillSpawn<C> = ChunkEvent<C>;
+pub type ChunkWillSpawn<C> = ChunkEvent<C, WillSpawn>;
 
 /// Fired when a chunk is about to be remeshed.
-pub type ChunkWillRemesh<C> = ChunkEvent<C>;
+pub type ChunkWillRemesh<C> = ChunkEvent<C, WillRemesh>;
+
+/// Fired when a chunk has finished generating and meshing, and its mesh, decorations and collider
+/// have been added. Chunks that are empty or completely filled have no mesh.
+pub type ChunkMeshReady<C> = ChunkEvent<C, MeshReady>;
+
+/// Fired when voxels in a spawned chunk have been changed with `set_voxel` or one of the bulk
+/// editing methods. Fired once per chunk and frame, no matter how many voxels changed.
+pub type ChunkModified<C> = ChunkEvent<C, Modified>;
+
+/// Fired the first time a chunk is meshed, for worlds with an `ExploredChunks` resource. Chunks
+/// that were explored before are not announced again when they spawn once more.
+pub type ChunkExplored<C> = ChunkEvent<C, Explored>;
+
+/// Sent once, when the chunk each camera is in and all chunks within `first_chunks_radius` of it
+/// have been meshed for the first time. Use it to release the player into the world, or to keep
+/// track of how long the world takes to start up. Times are measured from the first frame with a
+/// `VoxelWorldCamera`.
+#[derive(Event)]
+pub struct FirstChunksReady<C> {
+    /// The chunk the first camera was in when the chunks became ready
+    pub chunk_position: IVec3,
+
+    /// The number of chunks that had to be ready
+    pub chunk_count: usize,
+
+    /// How long it took for the first chunk to get meshed
+    pub time_to_first_chunk: Duration,
+
+    /// How long it took for all the chunks to get meshed
+    pub time_to_ready: Duration,
+
+    _marker: PhantomData<C>,
+}
+
+impl<C> FirstChunksReady<C> {
+    pub fn new(
+        chunk_position: IVec3,
+        chunk_count: usize,
+        time_to_first_chunk: Duration,
+        time_to_ready: Duration,
+    ) -> Self {
+        Self {
+            chunk_position,
+            chunk_count,
+            time_to_first_chunk,
+            time_to_ready,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// An event about a camera moving between chunks that are ready and chunks that are not. `K`
+/// tells the kinds apart, like for `ChunkEvent`.
+#[derive(Event)]
+pub struct CameraAreaEvent<C, K> {
+    /// The `VoxelWorldCamera`
+    pub camera: Entity,
+
+    /// The chunk the camera is in
+    pub chunk_position: IVec3,
+
+    _marker: PhantomData<(C, K)>,
+}
+
+impl<C, K> CameraAreaEvent<C, K> {
+    pub fn new(camera: Entity, chunk_position: IVec3) -> Self {
+        Self {
+            camera,
+            chunk_position,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// The kind of `CameraLeftGeneratedArea` events
+pub struct LeftGeneratedArea;
+
+/// The kind of `CameraEnteredGeneratedArea` events
+pub struct EnteredGeneratedArea;
+
+/// Sent when a camera is in a chunk that has not been generated and meshed yet, for example
+/// because it moves faster than chunks stream in. Also sent when a camera starts out in a chunk
+/// that is not ready. See `VoxelWorld::is_position_ready`.
+pub type CameraLeftGeneratedArea<C> = CameraAreaEvent<C, LeftGeneratedArea>;
+
+/// Sent when a camera that was outside of the generated area is in a ready chunk again
+pub type CameraEnteredGeneratedArea<C> = CameraAreaEvent<C, EnteredGeneratedArea>;
+
+/// Add this to the player, or anything else that falls with gravity, to keep it from falling
+/// through the world while the chunk it is in is not generated and meshed yet. The entity is held
+/// at the translation it had when its chunk turned out not to be ready, until the chunk is. With
+/// the `bevy_rapier3d` feature enabled, its rigid body is disabled for that time as well.
+#[derive(Component)]
+pub struct FreezeUntilGenerated<C> {
+    pub(crate) frozen_at: Option<Vec3>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for FreezeUntilGenerated<C> {
+    fn default() -> Self {
+        Self {
+            frozen_at: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> FreezeUntilGenerated<C> {
+    /// Whether the entity is being held in place, for character controllers to skip their own
+    /// movement
+    pub fn is_frozen(&self) -> bool {
+        self.frozen_at.is_some()
+    }
+}
+
+/// Sent when a chunk failed to generate, because the lookup delegate, the decorator or one of the
+/// mappers panicked, or because it took longer than `VoxelWorldConfig::chunk_generation_timeout`.
+/// Only that chunk fails: it keeps its previous mesh, or none, and gets
+/// generated again the next time it is remeshed. In debug builds, a magenta
+/// `ChunkErrorPlaceholder` box is shown in its place.
+#[derive(Event)]
+pub struct ChunkGenerationFailed<C> {
+    pub chunk_key: IVec3,
+    pub entity: Entity,
+
+    /// The message the panic was raised with, or the timeout that was hit
+    pub message: String,
+
+    _marker: PhantomData<C>,
+}
+
+impl<C> ChunkGenerationFailed<C> {
+    pub fn new(chunk_key: IVec3, entity: Entity, message: String) -> Self {
+        Self {
+            chunk_key,
+            entity,
+            message,
+            _marker: PhantomData,
+        }
+    }
+}
 
 pub trait FilterFn<I> {
     fn call(&self, input: (Vec3, WorldVoxel<I>)) -> bool;
@@ -94,6 +344,48 @@ impl<I> VoxelRaycastResult<I> {
     pub fn voxel_normal(&self) -> Option<IVec3> {
         self.normal.map(|n| n.floor().as_ivec3())
     }
+
+    /// Get the face of the voxel that the ray hit, or `None` if the hit has no normal
+    pub fn face(&self) -> Option<VoxelFace> {
+        self.voxel_normal().and_then(VoxelFace::from_normal)
+    }
+}
+
+/// How far `VoxelWorld::get_closest_surface` searches for a surface, in voxels
+pub const SURFACE_SEARCH_RADIUS: i32 = CHUNK_SIZE_I;
+
+/// How many columns `VoxelWorld::get_random_surface_position` tries before giving up
+const RANDOM_SURFACE_TRIES: usize = 32;
+
+/// The offsets that are exactly `distance` away from the origin along at least one axis
+fn cube_shell(distance: i32) -> impl Iterator<Item = IVec3> {
+    (-distance..=distance).flat_map(move |x| {
+        (-distance..=distance).flat_map(move |y| {
+            let on_side = x.abs() == distance || y.abs() == distance;
+            let step = if on_side {
+                1
+            } else {
+                (2 * distance).max(1) as usize
+            };
+            (-distance..=distance)
+                .step_by(step)
+                .map(move |z| IVec3::new(x, y, z))
+        })
+    })
+}
+
+/// System sets of the systems of every voxel world, for ordering your own systems around them
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VoxelWorldSet {
+    /// Applies the voxels set during the frame, in the streaming schedule of each world
+    FlushWrites,
+}
+
+/// Applies the pending voxel writes of world `C` right away, see
+/// `VoxelWorld::flush_pending_writes`. Add it before systems that need every write applied, like
+/// physics or AI reading the voxels from other threads.
+pub fn flush_voxel_writes<C: VoxelWorldConfig>(mut voxel_world: VoxelWorld<C>) {
+    voxel_world.flush_pending_writes();
 }
 
 /// Grants access to the VoxelWorld in systems
@@ -102,38 +394,528 @@ pub struct VoxelWorld<'w, C: VoxelWorldConfig> {
     chunk_map: Res<'w, ChunkMap<C, <C as VoxelWorldConfig>::MaterialIndex>>,
     modified_voxels: Res<'w, ModifiedVoxels<C, <C as VoxelWorldConfig>::MaterialIndex>>,
     voxel_write_buffer: ResMut<'w, VoxelWriteBuffer<C, <C as VoxelWorldConfig>::MaterialIndex>>,
-    #[allow(unused)]
     configuration: Res<'w, C>,
+    decals: ResMut<'w, VoxelDecals<C>>,
+    metadata: ResMut<'w, VoxelMetadataMap<C>>,
+    change_tracker: Option<ResMut<'w, VoxelChangeTracker<C>>>,
+    voxel_tasks: ResMut<'w, VoxelTasks<C, <C as VoxelWorldConfig>::MaterialIndex>>,
+    world_passes: ResMut<'w, WorldPasses<C, <C as VoxelWorldConfig>::MaterialIndex>>,
+    chunk_invalidations: ResMut<'w, ChunkInvalidations<C>>,
+    appearance_version: ResMut<'w, AppearanceVersion<C>>,
+    global_tint: ResMut<'w, GlobalTint<C>>,
+    fixed_region: Option<Res<'w, FixedRegion<C>>>,
+    flushed_chunks: ResMut<'w, FlushedChunks<C>>,
+    inserted_chunks: ResMut<'w, InsertedChunks<C, <C as VoxelWorldConfig>::MaterialIndex>>,
+    ready_chunks: Res<'w, ReadyChunks<C>>,
+    generate_now: ResMut<'w, GenerateNowRegions<C>>,
+    rng: Res<'w, VoxelWorldRng<C>>,
+    spawning_distances: Res<'w, SpawningDistances<C>>,
 }
 
 impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
+    /// Get the position of the voxel containing the given world space position. This is the
+    /// position divided by the configured `voxel_size`, rounded down.
+    pub fn world_to_voxel(&self, position: Vec3) -> IVec3 {
+        (position / self.configuration.voxel_size())
+            .floor()
+            .as_ivec3()
+    }
+
+    /// Get the world space position of the minimum corner of the given voxel
+    pub fn voxel_to_world(&self, position: impl Into<VoxelPos>) -> Vec3 {
+        let VoxelPos(position) = position.into();
+        position.as_vec3() * self.configuration.voxel_size()
+    }
+
     /// Get the voxel at the given position. The voxel will be WorldVoxel::Unset if there is no voxel at that position
-    pub fn get_voxel(&self, position: IVec3) -> WorldVoxel<C::MaterialIndex> {
-        self.get_voxel_fn()(position)
+    ///
+    /// Voxels set with `set_voxel` can be read back right away, in the same system or in any
+    /// system that runs after it, even though the write is only applied at the end of the frame.
+    /// When a voxel is set more than once, the last write is the one that is read back.
+    pub fn get_voxel(&self, position: impl Into<VoxelPos>) -> WorldVoxel<C::MaterialIndex> {
+        let VoxelPos(position) = position.into();
+        self.with_voxel_lookup(|lookup| lookup(position))
     }
 
     /// Set the voxel at the given position. This will create a new chunk if one does not exist at
-    /// the given position.
-    pub fn set_voxel(&mut self, position: IVec3, voxel: WorldVoxel<C::MaterialIndex>) {
+    /// the given position. The new voxel is visible to `get_voxel` right away, see `get_voxel`.
+    ///
+    /// Returns `false` without setting the voxel if the position is outside the configured
+    /// `world_bounds`.
+    pub fn set_voxel(
+        &mut self,
+        position: impl Into<VoxelPos>,
+        voxel: WorldVoxel<C::MaterialIndex>,
+    ) -> bool {
+        let VoxelPos(position) = position.into();
+        if !self.in_world_bounds(position) {
+            return false;
+        }
         self.voxel_write_buffer.push((position, voxel));
+        self.note_edit();
+        true
+    }
+
+    /// Set many voxels at once. Each chunk that gets touched is only queued for remeshing once,
+    /// no matter how many of its voxels are set. Voxels outside the configured `world_bounds` are
+    /// skipped.
+    pub fn set_voxels(
+        &mut self,
+        voxels: impl IntoIterator<Item = (impl Into<VoxelPos>, WorldVoxel<C::MaterialIndex>)>,
+    ) {
+        let world_bounds = self.configuration.world_bounds();
+        let fixed_region = self.fixed_region.as_deref();
+        let buffered = self.voxel_write_buffer.len();
+        let voxels = voxels
+            .into_iter()
+            .map(|(position, voxel)| (position.into().0, voxel));
+        self.voxel_write_buffer
+            .extend(voxels.filter(|(position, _)| {
+                let chunk_position = position.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+                chunk_in_world_bounds(world_bounds, chunk_position)
+                    && fixed_region.is_none_or(|region| region.contains(chunk_position))
+            }));
+        if self.voxel_write_buffer.len() > buffered {
+            self.note_edit();
+        }
+    }
+
+    /// Remember when the oldest of the voxels that are not flushed yet was set, to measure the
+    /// edit latency from
+    fn note_edit(&mut self) {
+        self.flushed_chunks
+            .first_edit
+            .get_or_insert_with(Instant::now);
+    }
+
+    /// Get a copy of all the voxels of a spawned chunk, including voxels that have been set but
+    /// not applied yet. Returns `None` if the chunk is not spawned.
+    pub fn get_chunk(
+        &self,
+        chunk_position: impl Into<ChunkPos>,
+    ) -> Option<ChunkRef<C::MaterialIndex>> {
+        let ChunkPos(chunk_position) = chunk_position.into();
+        if let Some(inserted) = self.inserted_chunks.get(&chunk_position) {
+            return Some(ChunkRef::from_inserted(
+                inserted,
+                &self.modified_voxels.read().unwrap(),
+                self.voxel_write_buffer.iter(),
+            ));
+        }
+        let chunk_data =
+            ChunkMap::<C, C::MaterialIndex>::get(&chunk_position, &self.chunk_map.get_read_lock())?;
+        Some(ChunkRef::from_chunk_data(
+            chunk_position,
+            &chunk_data,
+            &self.modified_voxels.read().unwrap(),
+            self.voxel_write_buffer.iter(),
+        ))
+    }
+
+    /// Replace all the voxels of a chunk with `chunk_data`, for example a chunk received from a
+    /// server with `ChunkData::from_network_bytes`, or built with `ChunkRef::filled` and converted
+    /// with `ChunkData::from`. The voxels are visible to `get_voxel` right away, and a spawned
+    /// chunk is remeshed at the end of the frame. Its voxels are never looked up from the lookup
+    /// delegate or chunk generator, and are kept when the chunk is regenerated with
+    /// `invalidate_region`, until the chunk despawns. Voxels of the chunk set before are replaced;
+    /// voxels set after are applied on top of it.
+    ///
+    /// A chunk that is not spawned yet is not spawned for this, but keeps the inserted voxels for
+    /// when the cameras spawn it, as long as no more than `max_unspawned_inserted_chunks` newer
+    /// chunks are waiting as well.
+    ///
+    /// Returns `false` without inserting the chunk if it is outside the configured `world_bounds`,
+    /// or if `chunk_data` only holds a sample of its voxels, like chunks above LOD level 0 do.
+    pub fn insert_chunk(
+        &mut self,
+        chunk_position: impl Into<ChunkPos>,
+        chunk_data: ChunkData<C::MaterialIndex>,
+    ) -> bool {
+        let ChunkPos(chunk_position) = chunk_position.into();
+        let has_voxels =
+            chunk_data.voxels.is_some() || !matches!(chunk_data.fill_type, FillType::Mixed);
+        if !self.chunk_in_bounds(chunk_position) || !chunk_data.is_full_detail() || !has_voxels {
+            return false;
+        }
+        let chunk = ChunkRef::from_inserted_data(chunk_position, &chunk_data);
+        self.voxel_write_buffer
+            .retain(|position| !chunk.contains(position));
+        self.modified_voxels
+            .write()
+            .unwrap()
+            .remove_chunk(chunk_position);
+        self.inserted_chunks.insert(chunk);
+        self.invalidate_region(chunk_position, chunk_position);
+        true
+    }
+
+    /// Get a handle to the generated voxels of a spawned chunk, without copying them. The handle
+    /// can be kept across frames, and keeps the voxels alive even after the chunk despawns. See
+    /// `ChunkDataHandle`. Returns `None` if the chunk is not spawned.
+    pub fn chunk_data_handle(
+        &self,
+        chunk_position: impl Into<ChunkPos>,
+    ) -> Option<ChunkDataHandle<C::MaterialIndex>> {
+        let ChunkPos(chunk_position) = chunk_position.into();
+        let chunk_data =
+            ChunkMap::<C, C::MaterialIndex>::get(&chunk_position, &self.chunk_map.get_read_lock())?;
+        Some(ChunkDataHandle::new(
+            chunk_position,
+            chunk_data,
+            &self.chunk_map.get_map(),
+        ))
+    }
+
+    /// Read and edit all the voxels of a spawned chunk at once. The voxels changed by `modify`
+    /// are set like with `set_voxels`, so the chunk is only remeshed once. Returns `false`
+    /// without calling `modify` if the chunk is not spawned.
+    pub fn modify_chunk(
+        &mut self,
+        chunk_position: impl Into<ChunkPos>,
+        modify: impl FnOnce(&mut ChunkRef<C::MaterialIndex>),
+    ) -> bool {
+        let Some(original) = self.get_chunk(chunk_position) else {
+            return false;
+        };
+        let mut chunk = original.clone();
+        modify(&mut chunk);
+        self.set_voxels(chunk.changes_since(&original));
+        true
+    }
+
+    /// Run `per_chunk` over every spawned chunk between the chunk positions `min_chunk` and
+    /// `max_chunk`, inclusive, on the task pool. The chunks are copied like with `get_chunk` and
+    /// started a few per frame, within `max_task_chunks_per_frame`, so that a large region
+    /// doesn't stall a frame. The value returned for each chunk is sent as a `VoxelTaskResult`,
+    /// and a `VoxelTaskFinished` is sent once all chunks are done. Chunks that are not spawned
+    /// when their turn comes are skipped.
+    pub fn spawn_task<T: Send + Sync + 'static>(
+        &mut self,
+        min_chunk: impl Into<ChunkPos>,
+        max_chunk: impl Into<ChunkPos>,
+        per_chunk: impl Fn(&ChunkRef<C::MaterialIndex>) -> T + Send + Sync + 'static,
+    ) -> VoxelTaskId {
+        let (ChunkPos(min_chunk), ChunkPos(max_chunk)) = (min_chunk.into(), max_chunk.into());
+        self.voxel_tasks.push(min_chunk, max_chunk, per_chunk)
+    }
+
+    /// Post-process the spawned chunks between the chunk positions `min_chunk` and `max_chunk`,
+    /// inclusive, for example with erosion or smoothing. `pass` is run `iterations` times on the
+    /// task pool. Each time it reads the voxels of the region as the last iteration left them,
+    /// and writes into a second buffer that starts out as a copy of them. A `WorldPassProgress`
+    /// is sent as iterations finish, and when all are done, the voxels that changed are set like
+    /// with `set_voxels` and a `WorldPassFinished` is sent.
+    ///
+    /// Voxels set while the pass is running are overwritten where the pass changed them, and
+    /// voxels in chunks that were not spawned are left `Unset` in the region. Returns `None`
+    /// without starting the pass if the region has too many voxels to be indexed with an `i32`.
+    pub fn spawn_world_pass(
+        &mut self,
+        min_chunk: impl Into<ChunkPos>,
+        max_chunk: impl Into<ChunkPos>,
+        iterations: u32,
+        pass: impl Fn(&VoxelRegion<C::MaterialIndex>, &mut VoxelRegion<C::MaterialIndex>)
+            + Send
+            + Sync
+            + 'static,
+    ) -> Option<WorldPassId> {
+        let (ChunkPos(min_chunk), ChunkPos(max_chunk)) = (min_chunk.into(), max_chunk.into());
+        self.world_passes.spawn(
+            (min_chunk, max_chunk),
+            iterations,
+            Arc::new(pass),
+            self.chunk_map.get_map(),
+            (**self.modified_voxels).clone(),
+            self.voxel_write_buffer.to_vec(),
+        )
+    }
+
+    /// Apply changes received from another client. The voxels are set like with `set_voxels`, but
+    /// are not recorded by the `VoxelChangeTracker`, so they don't get sent back. Only the `new`
+    /// voxel of each change is used.
+    pub fn apply_remote_changes(
+        &mut self,
+        changes: impl IntoIterator<Item = VoxelChange<C::MaterialIndex>>,
+    ) {
+        let buffered = self.voxel_write_buffer.len();
+        for change in changes {
+            let chunk_position = change.position.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+            if !self.chunk_in_bounds(chunk_position) {
+                continue;
+            }
+            if let Some(change_tracker) = &mut self.change_tracker {
+                change_tracker.expect_remote(change.position, change.new);
+            }
+            self.voxel_write_buffer.push((change.position, change.new));
+        }
+        if self.voxel_write_buffer.len() > buffered {
+            self.note_edit();
+        }
+    }
+
+    /// Apply the voxels set so far this frame now, instead of at the end of the frame. Afterwards
+    /// every way of reading the world sees them, including `get_voxel_fn` closures made before,
+    /// tasks and other threads. The chunks they changed are still remeshed at the end of the
+    /// frame, when the `ChunkModified` events are sent as well.
+    pub fn flush_pending_writes(&mut self) {
+        apply_voxel_writes(
+            &mut self.voxel_write_buffer,
+            &self.modified_voxels,
+            &self.chunk_map,
+            self.change_tracker.as_deref_mut(),
+            &mut self.flushed_chunks,
+            self.configuration.as_ref(),
+        );
+    }
+
+    /// Regenerate every spawned chunk, for example after the world seed or the terrain lookup
+    /// has changed. See `invalidate_region`.
+    pub fn invalidate_all(&mut self) {
+        self.chunk_invalidations.push(None);
+    }
+
+    /// Regenerate the spawned chunks between the chunk positions `min_chunk` and `max_chunk`,
+    /// inclusive, because the voxels the lookup delegate or chunk generator returns for them have
+    /// changed. At the end of the frame the chunks are queued for remeshing, which looks up their
+    /// voxels again. Until the new voxels and mesh are done, the chunks keep their old ones, so no
+    /// holes show up in the world. Voxels set with `set_voxel` are kept.
+    ///
+    /// Meshes are cached by the voxels they were built from, so the stale meshes are dropped once
+    /// no chunk uses them anymore. Caches kept by the lookup delegate itself, like a `ColumnCache`
+    /// or `VoxelCache`, have to be replaced along with the delegate.
+    pub fn invalidate_region(
+        &mut self,
+        min_chunk: impl Into<ChunkPos>,
+        max_chunk: impl Into<ChunkPos>,
+    ) {
+        let (ChunkPos(min_chunk), ChunkPos(max_chunk)) = (min_chunk.into(), max_chunk.into());
+        self.chunk_invalidations
+            .push(Some((min_chunk.min(max_chunk), min_chunk.max(max_chunk))));
+    }
+
+    /// Remesh every spawned chunk, because the texture mapper, the texture registry or the
+    /// resource pack has changed, while the voxels have not. Unlike `invalidate_all`, the voxels
+    /// are not looked up again, only textured and meshed anew. Cached meshes from before are not
+    /// reused. Chunks keep their old mesh until the new one is done.
+    pub fn invalidate_appearance(&mut self) {
+        self.appearance_version.version += 1;
+        self.appearance_version.pending = true;
+    }
+
+    /// Multiply the color of the whole world with `color`, for example to grade the terrain for
+    /// the time of day. Takes effect on the next frame, without remeshing. Only the built-in
+    /// material of this world is tinted. Other worlds keep their own tint.
+    pub fn set_global_tint(&mut self, color: impl Into<Color>) {
+        self.global_tint.color = color.into();
+    }
+
+    /// Scale the brightness of the global tint, `1.0` by default. See `set_global_tint`.
+    pub fn set_global_tint_intensity(&mut self, intensity: f32) {
+        self.global_tint.intensity = intensity;
+    }
+
+    /// The color and intensity of the global tint
+    pub fn global_tint(&self) -> (Color, f32) {
+        (self.global_tint.color, self.global_tint.intensity)
+    }
+
+    /// Iterate over the chunk positions a camera with the given projection and transform has in
+    /// view: the chunks around the camera, the chunks within `spawning_distance` that are at least
+    /// partly inside its view frustum, widened by `spawning_chunk_margin`, or the view volume of an
+    /// orthographic camera within `spawning_distance`. Chunks outside the world bounds are left
+    /// out. The chunks come from near to far, and don't need to be spawned, which makes this
+    /// useful for fog of war, or for deciding where enemies can spawn unseen.
+    ///
+    /// This is a test against the view frustum, not the rays the chunk spawner casts, so chunks
+    /// hidden behind others are included, and chunks at the edge of the view may be spawned later
+    /// or not at all.
+    pub fn visible_chunks(
+        &self,
+        projection: &Projection,
+        camera_transform: &GlobalTransform,
+    ) -> impl Iterator<Item = IVec3> + '_ {
+        chunks_in_view(
+            self.configuration.as_ref(),
+            &self.spawning_distances,
+            projection,
+            camera_transform,
+        )
+        .into_iter()
+        .filter(|chunk_position| self.chunk_in_bounds(*chunk_position))
+    }
+
+    /// Whether the chunk of the voxel at the given position has been generated and meshed, and
+    /// has its collider, so that things placed there won't fall through the world. Chunks stop
+    /// being ready once they are despawned, and retired chunks waiting to be despawned keep their
+    /// collider until then. Chunks that get regenerated stay ready, since they keep their old mesh
+    /// until the new one is done.
+    pub fn is_position_ready(&self, position: impl Into<VoxelPos>) -> bool {
+        self.ready_chunks.contains(&*position.into().chunk())
+    }
+
+    /// Generate and mesh the chunks between `min_chunk` and `max_chunk`, inclusive, for scripted
+    /// sequences that need them to exist by a given moment, wherever the cameras are. From the
+    /// next frame on, the missing chunks of the region are spawned, up to `max_spawn_per_frame`
+    /// each frame, skip the task budgets, and the main thread generates and meshes them itself. It
+    /// starts no more chunks once `max_millis_per_frame` is spent, but does at least one each
+    /// frame. The region then gets done in a predictable number of frames, without any frame
+    /// stalling for much longer than the budget.
+    ///
+    /// Returns whether all chunks of the region are ready, see `is_position_ready`. Chunks outside
+    /// of the world bounds are left out. The chunks are kept spawned, at full detail, until they
+    /// are ready, and for as long as this is called again every frame after that, so call it each
+    /// frame until the cutscene is over. Regions of more than `max_loaded_chunks` chunks are
+    /// refused, and always return `false`.
+    pub fn generate_now(
+        &mut self,
+        min_chunk: impl Into<ChunkPos>,
+        max_chunk: impl Into<ChunkPos>,
+        max_millis_per_frame: u32,
+    ) -> bool {
+        let (ChunkPos(min_chunk), ChunkPos(max_chunk)) = (min_chunk.into(), max_chunk.into());
+        let (min_chunk, max_chunk) = (min_chunk.min(max_chunk), min_chunk.max(max_chunk));
+        let size = (max_chunk.as_i64vec3() - min_chunk.as_i64vec3() + 1).as_u64vec3();
+        let chunk_count = size
+            .x
+            .checked_mul(size.y)
+            .and_then(|count| count.checked_mul(size.z));
+        if chunk_count.is_none_or(|count| count > self.configuration.max_loaded_chunks() as u64) {
+            warn_once!(
+                "generate_now region from {} to {} has more than max_loaded_chunks chunks",
+                min_chunk,
+                max_chunk
+            );
+            return false;
+        }
+        self.generate_now.request(
+            min_chunk,
+            max_chunk,
+            Duration::from_millis(max_millis_per_frame as u64),
+        );
+
+        (min_chunk.z..=max_chunk.z).all(|z| {
+            (min_chunk.y..=max_chunk.y).all(|y| {
+                (min_chunk.x..=max_chunk.x).all(|x| {
+                    let chunk_position = IVec3::new(x, y, z);
+                    !self.chunk_in_bounds(chunk_position)
+                        || self.ready_chunks.contains(&chunk_position)
+                })
+            })
+        })
+    }
+
+    /// Whether the voxel at the given position is within the configured `world_bounds`, and
+    /// within the fixed region of the world if it has one
+    pub fn in_world_bounds(&self, position: impl Into<VoxelPos>) -> bool {
+        self.chunk_in_bounds(*position.into().chunk())
+    }
+
+    fn chunk_in_bounds(&self, chunk_position: IVec3) -> bool {
+        chunk_in_world_bounds(self.configuration.world_bounds(), chunk_position)
+            && self
+                .fixed_region
+                .as_ref()
+                .is_none_or(|region| region.contains(chunk_position))
+    }
+
+    /// Place a decal, a textured quad of `size` voxels, on the given face of the voxel at
+    /// `position`. The decal is drawn with the voxel material using the texture layer
+    /// `texture_index`, slightly in front of the face. It does not change the voxel data, and is
+    /// spawned and despawned together with its chunk.
+    ///
+    /// Returns `false` without placing the decal if the position is outside the configured
+    /// `world_bounds`.
+    pub fn place_decal(
+        &mut self,
+        position: impl Into<VoxelPos>,
+        face: VoxelFace,
+        texture_index: u32,
+        size: Vec2,
+    ) -> bool {
+        let VoxelPos(position) = position.into();
+        if !self.in_world_bounds(position) {
+            return false;
+        }
+        self.decals.insert(VoxelDecal {
+            position,
+            face,
+            texture_index,
+            size,
+        });
+        true
+    }
+
+    /// Remove all decals placed on the voxel at the given position
+    pub fn remove_decals(&mut self, position: impl Into<VoxelPos>) {
+        let VoxelPos(position) = position.into();
+        self.decals.remove(position);
+    }
+
+    /// Set every voxel in the box between `min` and `max`, inclusive.
+    pub fn fill_box(
+        &mut self,
+        min: impl Into<VoxelPos>,
+        max: impl Into<VoxelPos>,
+        voxel: WorldVoxel<C::MaterialIndex>,
+    ) {
+        let (VoxelPos(min), VoxelPos(max)) = (min.into(), max.into());
+        let (min, max) = (min.min(max), min.max(max));
+        self.set_voxels((min.z..=max.z).flat_map(|z| {
+            (min.y..=max.y)
+                .flat_map(move |y| (min.x..=max.x).map(move |x| (IVec3::new(x, y, z), voxel)))
+        }));
+    }
+
+    /// Set every voxel within `radius` of `center`.
+    pub fn fill_sphere(
+        &mut self,
+        center: impl Into<VoxelPos>,
+        radius: u32,
+        voxel: WorldVoxel<C::MaterialIndex>,
+    ) {
+        let VoxelPos(center) = center.into();
+        let radius = radius as i32;
+        let extent = IVec3::splat(radius);
+        let (min, max) = (center - extent, center + extent);
+        self.set_voxels((min.z..=max.z).flat_map(|z| {
+            (min.y..=max.y).flat_map(move |y| {
+                (min.x..=max.x)
+                    .map(move |x| IVec3::new(x, y, z))
+                    .filter(move |pos| pos.distance_squared(center) <= radius.pow(2))
+                    .map(move |pos| (pos, voxel))
+            })
+        }));
+    }
+
+    /// Place a `VoxelModel` in the world, with its anchor at `position` and rotated around it. The
+    /// voxels are set in one batch like with `set_voxels`, so a model spanning several chunks only
+    /// queues each of them for remeshing once. `Unset` voxels of the model are left alone.
+    pub fn stamp(
+        &mut self,
+        model: &VoxelModel<C::MaterialIndex>,
+        position: impl Into<VoxelPos>,
+        rotation: ModelRotation,
+    ) {
+        let VoxelPos(position) = position.into();
+        self.set_voxels(model.placed_voxels(position, rotation));
     }
 
     /// Get a sendable closure that can be used to get the voxel at the given position
     /// This is useful for spawning tasks that need to access the voxel world
+    ///
+    /// The closure sees the voxels set before it was made, but not the ones set after, until
+    /// they are applied at the end of the frame.
     pub fn get_voxel_fn(&self) -> Arc<dyn Fn(IVec3) -> WorldVoxel<C::MaterialIndex> + Send + Sync> {
         let chunk_map = self.chunk_map.get_map();
-        let write_buffer = self.voxel_write_buffer.clone();
+        let write_buffer = self.voxel_write_buffer.latest_snapshot();
         let modified_voxels = self.modified_voxels.clone();
+        let inserted_chunks = self.inserted_chunks.snapshot();
 
         Arc::new(move |position| {
             let (chunk_pos, vox_pos) = get_chunk_voxel_position(position);
 
-            if let Some(voxel) = write_buffer
-                .iter()
-                .find(|(pos, _)| *pos == position)
-                .map(|(_, voxel)| *voxel)
-            {
-                return voxel;
+            if let Some(voxel) = write_buffer.get(&position) {
+                return *voxel;
             }
 
             {
@@ -142,6 +924,13 @@ impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
                 }
             }
 
+            if let Some(voxel) = inserted_chunks
+                .get(&chunk_pos)
+                .and_then(|chunk| chunk.get(position))
+            {
+                return voxel;
+            }
+
             let chunk_opt = {
                 let chun_map_read = chunk_map.read().unwrap();
                 chun_map_read.get(&chunk_pos).cloned()
@@ -159,8 +948,9 @@ impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
     /// Returns None if there is no surface voxel at or below the given position
     pub fn get_closest_surface_voxel(
         &self,
-        position: IVec3,
+        position: impl Into<VoxelPos>,
     ) -> Option<(IVec3, WorldVoxel<C::MaterialIndex>)> {
+        let VoxelPos(position) = position.into();
         let get_voxel = self.get_voxel_fn();
         let mut current_pos = position;
         let current_voxel = get_voxel(current_pos);
@@ -185,22 +975,30 @@ impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
         None
     }
 
+    /// Replace the random number generator of the world, which was created by
+    /// `VoxelWorldConfig::rng`. Seeding it again at the start of a replay makes the world draw the
+    /// same random numbers as in the recording.
+    pub fn set_rng(&self, rng: impl RngCore + Send + Sync + 'static) {
+        self.rng.set(Box::new(rng));
+    }
+
     /// Get a randowm surface voxel within the given radius of the given position
     /// Returns None if no surface voxel was found within the given radius
     pub fn get_random_surface_voxel(
         &self,
-        position: IVec3,
+        position: impl Into<VoxelPos>,
         radius: u32,
     ) -> Option<(IVec3, WorldVoxel<C::MaterialIndex>)> {
+        let VoxelPos(position) = position.into();
         let mut tries = 0;
 
         while tries < 100 {
             tries += 1;
 
             let r = radius as f32;
-            let x = rand::random::<f32>() * r * 2.0 - r;
-            let y = rand::random::<f32>() * r * 2.0 - r;
-            let z = rand::random::<f32>() * r * 2.0 - r;
+            let [x, y, z] = self
+                .rng
+                .with(|rng| [(); 3].map(|_| rng.gen::<f32>() * r * 2.0 - r));
 
             if y < 0.0 {
                 continue;
@@ -232,10 +1030,185 @@ impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
         })
     }
 
+    /// Get the topmost solid voxel in the column at `x`, `z`, among the loaded chunks. Only the
+    /// loaded chunks of the column are looked up, so this is much faster than probing `get_voxel`
+    /// downwards.
+    ///
+    /// Returns `None` if the column has no solid voxels, or none of its chunks are loaded.
+    pub fn get_surface_voxel_at_2d(
+        &self,
+        x: i32,
+        z: i32,
+    ) -> Option<(IVec3, WorldVoxel<C::MaterialIndex>)> {
+        let column = IVec3::new(x, 0, z).div_euclid(IVec3::splat(CHUNK_SIZE_I));
+        // Look up the chunks of the column directly, from the top of the loaded bounds down
+        let chunk_ys = {
+            let chunk_map = self.chunk_map.get_read_lock();
+            let bounds = ChunkMap::<C, C::MaterialIndex>::get_bounds(&chunk_map);
+            (bounds.min.y.floor() as i32..=bounds.max.y.floor() as i32)
+                .rev()
+                .filter(|chunk_y| chunk_map.contains_key(&IVec3::new(column.x, *chunk_y, column.z)))
+                .collect::<Vec<_>>()
+        };
+
+        self.with_voxel_lookup(|get_voxel| {
+            chunk_ys.into_iter().find_map(|chunk_y| {
+                (0..CHUNK_SIZE_I).rev().find_map(|y| {
+                    let position = IVec3::new(x, chunk_y * CHUNK_SIZE_I + y, z);
+                    let voxel = get_voxel(position);
+                    voxel.is_solid().then_some((position, voxel))
+                })
+            })
+        })
+    }
+
+    /// Get the surface voxel closest to the given world space `point`, within
+    /// `SURFACE_SEARCH_RADIUS` voxels of it. A surface voxel is a solid voxel without a solid
+    /// voxel above it. Unlike `get_closest_surface_voxel`, this also finds surfaces above `point`.
+    ///
+    /// Returns `None` if there is no surface voxel within the search radius.
+    pub fn get_closest_surface(
+        &self,
+        point: Vec3,
+    ) -> Option<(IVec3, WorldVoxel<C::MaterialIndex>)> {
+        let center = self.world_to_voxel(point);
+
+        self.with_voxel_lookup(|get_voxel| {
+            let mut closest: Option<(i32, IVec3, WorldVoxel<C::MaterialIndex>)> = None;
+
+            // Search cube shells of growing size, until no position in the next shell can be
+            // closer than the closest surface found so far
+            for distance in 0..=SURFACE_SEARCH_RADIUS {
+                if closest.is_some_and(|(distance_squared, ..)| distance_squared <= distance.pow(2))
+                {
+                    break;
+                }
+
+                for offset in cube_shell(distance) {
+                    let position = center + offset;
+                    let voxel = get_voxel(position);
+                    if !voxel.is_solid() || get_voxel(position + IVec3::Y).is_solid() {
+                        continue;
+                    }
+
+                    let distance_squared = offset.length_squared();
+                    if closest
+                        .is_none_or(|(closest_squared, ..)| distance_squared < closest_squared)
+                    {
+                        closest = Some((distance_squared, position, voxel));
+                    }
+                }
+            }
+
+            closest.map(|(_, position, voxel)| (position, voxel))
+        })
+    }
+
+    /// Get a random position on the surface within `area`, which spans the `x` and `z` voxel
+    /// coordinates to pick columns from, inclusive. The position is the empty voxel on top of the
+    /// topmost solid voxel of a column, see `get_surface_voxel_at_2d`.
+    ///
+    /// Returns `None` if no surface was found in a number of randomly picked columns.
+    pub fn get_random_surface_position(&self, rng: &mut impl Rng, area: IRect) -> Option<IVec3> {
+        // Accept rects given with their corners in any order
+        let area = IRect::from_corners(area.min, area.max);
+        (0..RANDOM_SURFACE_TRIES).find_map(|_| {
+            let x = rng.gen_range(area.min.x..=area.max.x);
+            let z = rng.gen_range(area.min.y..=area.max.y);
+            self.get_surface_voxel_at_2d(x, z)
+                .map(|(position, _)| position + IVec3::Y)
+        })
+    }
+
+    /// Get up to `count` random positions in the given chunk where something can stand, i.e. an
+    /// empty voxel with a solid voxel below it and another empty voxel above it. Positions are
+    /// voxel coordinates of the lower empty voxel.
+    ///
+    /// Returns an empty list if the chunk is not loaded.
+    pub fn sample_surface_positions(
+        &self,
+        chunk_pos: impl Into<ChunkPos>,
+        count: usize,
+        rng: &mut impl Rng,
+    ) -> Vec<IVec3> {
+        self.sample_surface_positions_with_clearance(chunk_pos, count, 2, rng)
+    }
+
+    /// Like `sample_surface_positions`, but with the number of empty voxels needed above the
+    /// solid ground given by `clearance`.
+    pub fn sample_surface_positions_with_clearance(
+        &self,
+        chunk_pos: impl Into<ChunkPos>,
+        count: usize,
+        clearance: u32,
+        rng: &mut impl Rng,
+    ) -> Vec<IVec3> {
+        let ChunkPos(chunk_pos) = chunk_pos.into();
+        let Some(chunk_data) =
+            ChunkMap::<C, C::MaterialIndex>::get(&chunk_pos, &self.chunk_map.get_read_lock())
+        else {
+            return Vec::new();
+        };
+
+        // Voxels beyond the padding of the chunk data are looked up in the world
+        let get_voxel = self.get_voxel_fn();
+        let padded_min = chunk_pos * CHUNK_SIZE_I - 1;
+        let is_empty = |position: IVec3| {
+            let local = position - padded_min;
+            let voxel = if local.cmplt(IVec3::splat(PADDED_CHUNK_SIZE as i32)).all() {
+                chunk_data.get_voxel(local.as_uvec3())
+            } else {
+                get_voxel(position)
+            };
+            !voxel.is_solid()
+        };
+
+        let mut candidates = Vec::new();
+        for x in 0..CHUNK_SIZE_I {
+            for z in 0..CHUNK_SIZE_I {
+                for y in 0..CHUNK_SIZE_I {
+                    let position = chunk_pos * CHUNK_SIZE_I + IVec3::new(x, y, z);
+                    // The voxel below is always within the padding
+                    if is_empty(position - IVec3::Y) {
+                        continue;
+                    }
+                    if (0..clearance as i32).all(|up| is_empty(position + IVec3::Y * up)) {
+                        candidates.push(position);
+                    }
+                }
+            }
+        }
+
+        candidates.choose_multiple(rng, count).copied().collect()
+    }
+
+    /// The navigation of the solid voxel at the given position, from
+    /// `VoxelWorldConfig::voxel_navigation`. Returns `None` if the voxel is not solid.
+    pub fn get_voxel_navigation(&self, position: impl Into<VoxelPos>) -> Option<VoxelNavigation> {
+        match self.get_voxel(position) {
+            WorldVoxel::Solid(index) => Some(self.configuration.voxel_navigation(index)),
+            _ => None,
+        }
+    }
+
+    /// The navigation of the ground below a creature standing in the voxel at the given position,
+    /// for the cost of a step in a path. Returns `None` if nothing can stand there, because the
+    /// voxel is solid, or the voxel below it is not solid or not walkable.
+    pub fn standing_navigation(&self, position: impl Into<VoxelPos>) -> Option<VoxelNavigation> {
+        let VoxelPos(position) = position.into();
+        if self.get_voxel(position).is_solid() {
+            return None;
+        }
+        self.get_voxel_navigation(position - IVec3::Y)
+            .filter(|navigation| navigation.walkable)
+    }
+
     /// Get the first solid voxel intersecting with the given ray.
     /// The `filter` function can be used to filter out voxels that should not be considered for the raycast.
     ///
-    /// Returns a `VoxelRaycastResult` with position, normal and voxel info. The position is given in world space.
+    /// The ray is given in world space. Returns a `VoxelRaycastResult` with position, normal and
+    /// voxel info. The position is given in voxel coordinates, use `voxel_to_world` to get the
+    /// world space position when a `voxel_size` is configured.
     /// Returns `None` if no voxel was intersected
     ///
     /// # Example
@@ -270,12 +1243,132 @@ impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
         raycast_fn(ray, filter)
     }
 
+    /// Get the first solid voxel along the ray from `origin` in `direction`, no further away than
+    /// `max_distance`. Only loaded chunks are traversed, unloaded voxels are passed through.
+    /// `origin` and `max_distance` are in world space, the returned position in voxel coordinates.
+    ///
+    /// Returns the position of the voxel, the voxel, and the face the ray entered through. The
+    /// face is `None` if `origin` is inside the voxel that was hit.
+    pub fn raycast_distance(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+    ) -> Option<(IVec3, WorldVoxel<C::MaterialIndex>, Option<VoxelFace>)> {
+        self.raycast_distance_filtered(origin, direction, max_distance, |_| true)
+    }
+
+    /// Like `raycast_distance`, but solid voxels with a material that `filter` returns `false`
+    /// for are passed through, for example to see through glass or water.
+    pub fn raycast_distance_filtered(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        filter: impl Fn(C::MaterialIndex) -> bool,
+    ) -> Option<(IVec3, WorldVoxel<C::MaterialIndex>, Option<VoxelFace>)> {
+        let direction = direction.try_normalize()?;
+        if max_distance <= 0.0 {
+            return None;
+        }
+        let voxel_size = self.configuration.voxel_size();
+        let origin = origin / voxel_size;
+        let max_distance = max_distance / voxel_size;
+
+        self.with_voxel_lookup(|get_voxel| {
+            let mut hit = None;
+            let end = origin + direction * max_distance;
+            voxel_line_traversal(origin, end, |voxel_coords, _time, face| {
+                let voxel = get_voxel(voxel_coords);
+                match voxel {
+                    WorldVoxel::Solid(material) if filter(material) => {
+                        hit = Some((voxel_coords, voxel, face));
+                        false
+                    }
+                    _ => true,
+                }
+            });
+
+            hit
+        })
+    }
+
+    /// Run `f` with a voxel lookup that holds the locks for as long as `f` runs, instead of taking
+    /// them for every voxel like `get_voxel` does
+    fn with_voxel_lookup<R>(
+        &self,
+        f: impl FnOnce(&mut dyn FnMut(IVec3) -> WorldVoxel<C::MaterialIndex>) -> R,
+    ) -> R {
+        let chunk_map = self.chunk_map.get_read_lock();
+        let modified_voxels = self.modified_voxels.read().unwrap();
+        let mut current_chunk: Option<(IVec3, Option<ChunkData<C::MaterialIndex>>)> = None;
+
+        f(&mut |position: IVec3| {
+            if let Some(voxel) = self.voxel_write_buffer.latest(position) {
+                return voxel;
+            }
+            if let Some(voxel) = modified_voxels.get(&position) {
+                return *voxel;
+            }
+            if let Some(voxel) = self.inserted_chunks.get_voxel(position) {
+                return voxel;
+            }
+
+            let (chunk_pos, vox_pos) = get_chunk_voxel_position(position);
+            if current_chunk.as_ref().map(|(p, _)| *p) != Some(chunk_pos) {
+                current_chunk = Some((
+                    chunk_pos,
+                    ChunkMap::<C, C::MaterialIndex>::get(&chunk_pos, &chunk_map),
+                ));
+            }
+            match &current_chunk {
+                Some((_, Some(chunk_data))) => chunk_data.get_voxel(vox_pos),
+                _ => WorldVoxel::Unset,
+            }
+        })
+    }
+
+    /// Get how much of the sun is visible from the given position, as a value between 0.0 (fully
+    /// blocked) and 1.0 (fully visible). This marches a few rays towards the sun over the loaded
+    /// chunks, and is meant for gameplay such as solar panels, rather than for rendering.
+    ///
+    /// `position` is in world space. The voxel containing it does not block the sun. Unloaded
+    /// chunks never block it.
+    pub fn sun_visibility(&self, position: Vec3, sun_dir: Vec3) -> f32 {
+        let Ok(direction) = Dir3::new(sun_dir) else {
+            return 0.0;
+        };
+
+        let raycast = self.raycast_fn();
+        let voxel_size = self.configuration.voxel_size();
+        let (u, v) = direction.any_orthonormal_pair();
+
+        // A few parallel rays around the position give a softer edge at shadow borders
+        let offsets = [Vec3::ZERO, u, -u, v, -v].map(|offset| offset * 0.25);
+        let lit = offsets
+            .iter()
+            .filter(|offset| {
+                let origin = position + **offset;
+                let start_voxel = (origin / voxel_size).floor();
+                raycast(Ray3d::new(origin, *direction), &|(pos, _)| {
+                    pos != start_voxel
+                })
+                .is_none()
+            })
+            .count();
+
+        lit as f32 / offsets.len() as f32
+    }
+
     /// Get a sendable closure that can be used to raycast into the voxel world
     pub fn raycast_fn(&self) -> Arc<RaycastFn<C::MaterialIndex>> {
         let chunk_map = self.chunk_map.get_map();
         let get_voxel = self.get_voxel_fn();
+        let voxel_size = self.configuration.voxel_size();
 
         Arc::new(move |ray, filter| {
+            // Trace in voxel coordinates
+            let ray = Ray3d::new(ray.origin / voxel_size, *ray.direction);
             let p = ray.origin;
             let d = *ray.direction;
 
@@ -312,7 +1405,7 @@ impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
                     if voxel.is_solid() {
                         raycast_result = Some(VoxelRaycastResult {
                             position: voxel_coords.as_vec3(),
-                            normal: face.try_into().ok(),
+                            normal: face.map(Vec3::from),
                             voxel,
                         });
 
@@ -332,3 +1425,39 @@ impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
         })
     }
 }
+
+impl<C: VoxelMetadataConfig> VoxelWorld<'_, C> {
+    /// Attach metadata to the voxel at the given position, replacing any it had. Metadata is kept
+    /// when the voxel changes, so remove it along with the voxel when it no longer applies.
+    ///
+    /// Returns `false` without setting the metadata if the position is outside the configured
+    /// `world_bounds`.
+    pub fn set_voxel_metadata(
+        &mut self,
+        position: impl Into<VoxelPos>,
+        metadata: C::VoxelMetadata,
+    ) -> bool {
+        let VoxelPos(position) = position.into();
+        if !self.in_world_bounds(position) {
+            return false;
+        }
+        self.metadata.insert(position, Arc::new(metadata));
+        true
+    }
+
+    /// Get the metadata of the voxel at the given position
+    pub fn get_voxel_metadata(&self, position: impl Into<VoxelPos>) -> Option<&C::VoxelMetadata> {
+        let VoxelPos(position) = position.into();
+        self.metadata.get(position)?.downcast_ref()
+    }
+
+    /// Remove the metadata of the voxel at the given position, and return it
+    pub fn remove_voxel_metadata(
+        &mut self,
+        position: impl Into<VoxelPos>,
+    ) -> Option<C::VoxelMetadata> {
+        let VoxelPos(position) = position.into();
+        let metadata = self.metadata.remove(position)?.downcast().ok()?;
+        Some(Arc::unwrap_or_clone(metadata))
+    }
+}
@@ -4,43 +4,333 @@
 ///
 use bevy::{
     ecs::system::SystemParam,
+    pbr::{ExtendedMaterial, NotShadowCaster},
     prelude::*,
-    tasks::AsyncComputeTaskPool,
-    utils::{HashMap, HashSet},
+    render::{
+        camera::CameraProjection,
+        primitives::{Aabb, Frustum},
+        render_asset::RenderAssetUsages,
+        view::RenderLayers,
+    },
+    tasks::{AsyncComputeTaskPool, IoTaskPool},
+    utils::{HashMap, HashSet, Instant},
 };
 use futures_lite::future;
+use rand::{Rng, RngCore};
 use std::{
+    any::Any,
     collections::VecDeque,
     marker::PhantomData,
-    sync::{Arc, RwLock},
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
 };
 
 use crate::{
     chunk::*,
+    chunk_collider::ChunkCollider,
+    chunk_generator::ChunkBuffer,
     chunk_map::*,
-    configuration::{ChunkDespawnStrategy, ChunkSpawnStrategy, VoxelWorldConfig},
+    chunk_persistence::{chunk_modified_voxels, load_persisted_chunk, PersistedChunks},
+    chunk_ref::ChunkRef,
+    chunk_timings::ChunkTimings,
+    configuration::{
+        capped_soft_despawn_distance, capped_spawning_distance, theoretical_chunk_count,
+        ChunkDespawnStrategy, ChunkGeneratorFn, ChunkHierarchy, ChunkPlaceholder,
+        ChunkSpawnStrategy, ChunkSpawnTransition, MaterialGroup, PaddingSource, VoxelLookupFn,
+        VoxelWorldConfig,
+    },
+    decoration_instancing::{instances_aabb, DecorationInstance, DecorationInstanceData},
+    explored_chunks::ExploredChunks,
+    lookup_cache::{DespawnedChunks, LookupFnPool},
     mesh_cache::*,
+    meshing::{self, MeshingOptions},
     plugin::VoxelWorldMaterialHandle,
     voxel::WorldVoxel,
-    voxel_material::LoadingTexture,
-    voxel_world::{ChunkWillDespawn, ChunkWillRemesh, ChunkWillSpawn, VoxelWorldCamera},
+    voxel_changes::VoxelChangeTracker,
+    voxel_decal::{decal_mesh, ChunkDecalMesh, VoxelDecals},
+    voxel_lighting::{chunks_lit_through, lit_bounds, ChunkLight},
+    voxel_material::{GlobalTint, LoadingTexture, StandardVoxelMaterial, WorldMaterialHandle},
+    voxel_metadata::{ChunkMetadataWillDespawn, VoxelMetadataMap},
+    voxel_tasks::VoxelTasks,
+    voxel_world::{
+        CameraEnteredGeneratedArea, CameraLeftGeneratedArea, ChunkExplored, ChunkGenerationFailed,
+        ChunkMeshReady, ChunkModified, ChunkWillDespawn, ChunkWillRemesh, ChunkWillSpawn,
+        FirstChunksReady, FreezeUntilGenerated, RigSpawnVolume, VoxelWorldCamera,
+        VoxelWorldCameraRig, VoxelWorldMotionHint, VoxelWorldSpawnAnchor,
+    },
+    world_pass::WorldPasses,
 };
 
 #[derive(SystemParam, Deref)]
-pub struct CameraInfo<'w, 's, C: VoxelWorldConfig>(
-    Query<'w, 's, (&'static Camera, &'static GlobalTransform), With<VoxelWorldCamera<C>>>,
-);
+pub struct CameraInfo<'w, 's, C: VoxelWorldConfig> {
+    #[deref]
+    cameras: Query<
+        'w,
+        's,
+        (Entity, &'static Camera, &'static GlobalTransform),
+        With<VoxelWorldCamera<C>>,
+    >,
+    projections: Query<'w, 's, &'static Projection, With<VoxelWorldCamera<C>>>,
+    rigs: Query<'w, 's, &'static VoxelWorldCameraRig, With<VoxelWorldCamera<C>>>,
+    motion_hints: Query<'w, 's, &'static VoxelWorldMotionHint, With<VoxelWorldCamera<C>>>,
+    spawn_anchors: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static VoxelWorldSpawnAnchor<C>,
+            &'static GlobalTransform,
+        ),
+    >,
+    distances: Res<'w, SpawningDistances<C>>,
+}
+
+/// A point chunks get spawned around: a camera, the eye cameras of a `VoxelWorldCameraRig`, or a
+/// `VoxelWorldSpawnAnchor`
+pub(crate) struct SpawnAnchor {
+    /// The camera, or the rig entity
+    pub entity: Entity,
+    /// The position in voxel coordinates, between the eyes for a rig
+    pub position: Vec3,
+    pub forward: Vec3,
+    pub motion_hint: Option<Vec3>,
+    pub spawn_volume: Option<RigSpawnVolume>,
+    /// The eye cameras of a rig, or just the camera. Empty for a `VoxelWorldSpawnAnchor`.
+    pub cameras: Vec<Entity>,
+    /// The radius of a `VoxelWorldSpawnAnchor`, which replaces `spawning_distance`
+    pub radius: Option<u32>,
+    /// How far around the anchor chunks are spawned, in chunks
+    pub spawning_distance: i32,
+}
+
+impl<C: VoxelWorldConfig> CameraInfo<'_, '_, C> {
+    /// Get the spawn anchors, one for every camera, except for the eyes of a rig, which share one.
+    /// Anchors come in the order of their first camera, followed by the `VoxelWorldSpawnAnchor`s.
+    pub(crate) fn anchors(&self, configuration: &C) -> Vec<SpawnAnchor> {
+        let mut anchors: Vec<SpawnAnchor> = Vec::new();
+        let mut forwards: Vec<Vec3> = Vec::new();
+
+        for (entity, _, cam_gtf) in self.iter() {
+            let rig = self.rigs.get(entity).ok();
+            let anchor_entity = rig.map_or(entity, |rig| rig.rig);
+            let position = camera_voxel_position(configuration, cam_gtf);
+            let forward = cam_gtf.forward().as_vec3();
+            let motion_hint = self.motion_hints.get(entity).ok().map(|hint| hint.velocity);
+
+            match anchors
+                .iter()
+                .position(|anchor| anchor.entity == anchor_entity)
+            {
+                Some(index) => {
+                    let anchor = &mut anchors[index];
+                    anchor.position += position;
+                    anchor.motion_hint = anchor.motion_hint.or(motion_hint);
+                    anchor.cameras.push(entity);
+                    forwards[index] += forward;
+                }
+                None => {
+                    anchors.push(SpawnAnchor {
+                        entity: anchor_entity,
+                        position,
+                        forward,
+                        motion_hint,
+                        spawn_volume: rig.map(|rig| rig.spawn_volume),
+                        cameras: vec![entity],
+                        radius: None,
+                        spawning_distance: self.distances.spawning as i32,
+                    });
+                    forwards.push(forward);
+                }
+            }
+        }
+
+        for (anchor, forward) in anchors.iter_mut().zip(forwards) {
+            anchor.position /= anchor.cameras.len() as f32;
+            anchor.forward = forward.normalize_or(anchor.forward);
+        }
+
+        anchors.extend(
+            self.spawn_anchors
+                .iter()
+                .map(|(entity, spawn_anchor, gtf)| SpawnAnchor {
+                    entity,
+                    position: camera_voxel_position(configuration, gtf),
+                    forward: Vec3::ZERO,
+                    motion_hint: None,
+                    spawn_volume: None,
+                    cameras: Vec::new(),
+                    radius: Some(spawn_anchor.radius),
+                    spawning_distance: spawn_anchor.radius as i32,
+                }),
+        );
+
+        anchors
+    }
+
+    /// The chunks every anchor is in, and their six face neighbors
+    fn camera_chunks(&self, configuration: &C) -> HashSet<IVec3> {
+        self.anchors(configuration)
+            .iter()
+            .flat_map(|anchor| camera_chunk_neighborhood(anchor.position))
+            .collect()
+    }
+
+    /// Get a sort key for the order chunks get generated and meshed in: the chunks around the
+    /// cameras first, then the chunks in front of a camera, each from near to far
+    fn chunk_priority(&self, configuration: &C) -> impl Fn(IVec3) -> (bool, bool, u32) {
+        let camera_chunks = self.camera_chunks(configuration);
+        let cameras: Vec<(Vec3, Vec3)> = self
+            .anchors(configuration)
+            .iter()
+            .map(|anchor| (anchor.position, anchor.forward))
+            .collect();
+
+        move |chunk_position| {
+            let center = chunk_position.as_vec3() * CHUNK_SIZE_F + CHUNK_SIZE_F / 2.0;
+            // Chunks the camera is partly inside of count as in front of it
+            let in_front = cameras.iter().any(|(camera_position, forward)| {
+                (center - *camera_position).dot(*forward) > -CHUNK_SIZE_F
+            });
+            let distance = cameras
+                .iter()
+                .map(|(camera_position, _)| center.distance(*camera_position))
+                .fold(f32::INFINITY, f32::min);
+            (
+                !camera_chunks.contains(&chunk_position),
+                !in_front,
+                distance as u32,
+            )
+        }
+    }
+
+    /// Get a sort key for the order finished chunks get their meshes inserted in: the chunks
+    /// around the cameras first, then the others from near to far, wherever the cameras look. The
+    /// chunks in view are already generated first, so this fills the holes closest to the player
+    /// first.
+    fn insert_priority(&self, configuration: &C) -> impl Fn(IVec3) -> (bool, u32) {
+        let chunk_priority = self.chunk_priority(configuration);
+        move |chunk_position| {
+            let (outside_camera_chunks, _, distance) = chunk_priority(chunk_position);
+            (outside_camera_chunks, distance)
+        }
+    }
+
+    fn is_orthographic(&self, entity: Entity) -> bool {
+        matches!(
+            self.projections.get(entity),
+            Ok(Projection::Orthographic(_))
+        )
+    }
+
+    /// The chunks within the view volume of every camera with an orthographic projection, and
+    /// within `spawning_chunk_margin` of it. The view volume is cut off at the spawning distance,
+    /// so that its chunks count against `max_loaded_chunks` like the chunks of other cameras.
+    fn orthographic_view_chunks(&self, configuration: &C) -> HashSet<IVec3> {
+        let mut chunks = HashSet::new();
+        for (entity, _, cam_gtf) in self.iter() {
+            if let Ok(Projection::Orthographic(projection)) = self.projections.get(entity) {
+                chunks.extend(orthographic_view_chunks(
+                    configuration,
+                    self.distances.spawning,
+                    projection,
+                    cam_gtf,
+                ));
+            }
+        }
+        chunks
+    }
+
+    /// The chunk every anchor is in
+    fn chunks_at_cameras(&self, configuration: &C) -> Vec<IVec3> {
+        self.anchors(configuration)
+            .iter()
+            .map(|anchor| chunk_containing(anchor.position))
+            .collect()
+    }
+}
+
+/// Modified voxels by world position, grouped by the chunk they are in, so that the voxels of one
+/// chunk can be found without going through all of them
+#[derive(Clone, Debug)]
+pub struct ModifiedVoxelMap<I> {
+    chunks: HashMap<IVec3, HashMap<IVec3, WorldVoxel<I>>>,
+}
+
+impl<I> Default for ModifiedVoxelMap<I> {
+    fn default() -> Self {
+        Self {
+            chunks: HashMap::new(),
+        }
+    }
+}
+
+impl<I: Copy> ModifiedVoxelMap<I> {
+    pub fn get(&self, position: &IVec3) -> Option<&WorldVoxel<I>> {
+        self.chunks
+            .get(&position.div_euclid(IVec3::splat(CHUNK_SIZE_I)))?
+            .get(position)
+    }
+
+    pub fn contains_key(&self, position: &IVec3) -> bool {
+        self.get(position).is_some()
+    }
+
+    /// Set the voxel at a position, returning the voxel that was modified there before
+    pub fn insert(&mut self, position: IVec3, voxel: WorldVoxel<I>) -> Option<WorldVoxel<I>> {
+        self.chunks
+            .entry(position.div_euclid(IVec3::splat(CHUNK_SIZE_I)))
+            .or_default()
+            .insert(position, voxel)
+    }
+
+    /// The modified voxels within the given chunk
+    pub fn in_chunk(&self, chunk_position: IVec3) -> Option<&HashMap<IVec3, WorldVoxel<I>>> {
+        self.chunks.get(&chunk_position)
+    }
+
+    /// Forget the modified voxels within the given chunk
+    pub fn remove_chunk(&mut self, chunk_position: IVec3) {
+        self.chunks.remove(&chunk_position);
+    }
+
+    /// The chunks that have modified voxels
+    pub fn chunks(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&IVec3, &WorldVoxel<I>)> {
+        self.chunks.values().flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.values().map(HashMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+}
+
+impl<I: Copy> std::ops::Index<&IVec3> for ModifiedVoxelMap<I> {
+    type Output = WorldVoxel<I>;
+
+    fn index(&self, position: &IVec3) -> &Self::Output {
+        self.get(position)
+            .expect("no modified voxel at this position")
+    }
+}
 
 /// Holds a map of modified voxels that will persist between chunk spawn/despawn
 #[derive(Resource, Deref, DerefMut, Clone)]
-pub struct ModifiedVoxels<C, I>(
-    #[deref] Arc<RwLock<HashMap<IVec3, WorldVoxel<I>>>>,
-    PhantomData<C>,
-);
+pub struct ModifiedVoxels<C, I>(#[deref] Arc<RwLock<ModifiedVoxelMap<I>>>, PhantomData<C>);
 
 impl<C: VoxelWorldConfig> Default for ModifiedVoxels<C, C::MaterialIndex> {
     fn default() -> Self {
-        Self(Arc::new(RwLock::new(HashMap::new())), PhantomData)
+        Self(
+            Arc::new(RwLock::new(ModifiedVoxelMap::default())),
+            PhantomData,
+        )
     }
 }
 
@@ -51,13 +341,424 @@ impl<C: VoxelWorldConfig> ModifiedVoxels<C, C::MaterialIndex> {
     }
 }
 
+/// The region entities chunks are grouped under with `ChunkHierarchy::Regions`, by region position
+#[derive(Resource, Deref, DerefMut)]
+pub(crate) struct ChunkRegions<C>(#[deref] HashMap<IVec3, Entity>, PhantomData<C>);
+
+impl<C> Default for ChunkRegions<C> {
+    fn default() -> Self {
+        Self(HashMap::new(), PhantomData)
+    }
+}
+
+/// `spawning_distance` and `soft_despawn_distance`, lowered to fit within `max_loaded_chunks`.
+/// Finding the lowered distances means counting chunks, so they are worked out once when the world
+/// is set up, and again when the configuration changes.
+#[derive(Resource)]
+pub(crate) struct SpawningDistances<C> {
+    pub spawning: u32,
+    pub soft_despawn: Option<u32>,
+    /// The chunk offsets within the spawning distance, nearest first
+    pub sphere_offsets: Vec<IVec3>,
+    _marker: PhantomData<C>,
+}
+
+impl<C: VoxelWorldConfig> SpawningDistances<C> {
+    pub fn from_config(configuration: &C) -> Self {
+        let spawning = capped_spawning_distance(configuration);
+        Self {
+            spawning,
+            soft_despawn: capped_soft_despawn_distance(configuration),
+            sphere_offsets: sphere_offsets(spawning as i32),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// The chunks that chunks get spawned and despawned around, one for each camera. This is the
+/// chunk the camera is in, moved ahead along the camera velocity when `prefetch_lookahead` is set.
+#[derive(Resource)]
+pub(crate) struct SpawnFocus<C> {
+    pub chunk_positions: HashMap<Entity, IVec3>,
+    last_camera_positions: HashMap<Entity, Vec3>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for SpawnFocus<C> {
+    fn default() -> Self {
+        Self {
+            chunk_positions: HashMap::new(),
+            last_camera_positions: HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Progress towards the `FirstChunksReady` event
+#[derive(Default)]
+pub(crate) struct FirstChunksProgress {
+    started: Option<Instant>,
+    first_chunk: Option<Instant>,
+    meshed: HashSet<IVec3>,
+    done: bool,
+}
+
+/// The chunks that have been generated and meshed, and are not despawned yet. Retired chunks
+/// stay in here until they are despawned, since they can still be kept. See
+/// `VoxelWorld::is_position_ready`.
+#[derive(Resource, Deref, DerefMut)]
+pub(crate) struct ReadyChunks<C>(#[deref] HashSet<IVec3>, PhantomData<C>);
+
+impl<C> Default for ReadyChunks<C> {
+    fn default() -> Self {
+        Self(HashSet::new(), PhantomData)
+    }
+}
+
+/// The random number generator of the world, from `VoxelWorldConfig::rng`. It sits behind a
+/// mutex, so that `VoxelWorld` can draw from it without mutable access.
+#[derive(Resource)]
+pub(crate) struct VoxelWorldRng<C> {
+    rng: Mutex<Box<dyn RngCore + Send + Sync>>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> VoxelWorldRng<C> {
+    pub fn new(rng: Box<dyn RngCore + Send + Sync>) -> Self {
+        Self {
+            rng: Mutex::new(rng),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn set(&self, rng: Box<dyn RngCore + Send + Sync>) {
+        *self.rng.lock().unwrap() = rng;
+    }
+
+    /// Draw from the generator
+    pub fn with<T>(&self, f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+        f(self.rng.lock().unwrap().as_mut())
+    }
+}
+
 /// A temporary buffer for voxel modifications that will get flushed to the `ModifiedVoxels` resource
 /// at the end of the frame.
+#[derive(Resource, Deref)]
+pub struct VoxelWriteBuffer<C, I> {
+    #[deref]
+    writes: Vec<(IVec3, WorldVoxel<I>)>,
+    /// The last write at each position, so that reading a voxel doesn't have to go through all
+    /// the writes. Shared with the closures of `VoxelWorld::get_voxel_fn`.
+    latest: Arc<HashMap<IVec3, WorldVoxel<I>>>,
+    _marker: PhantomData<C>,
+}
+
+impl<C, I> Default for VoxelWriteBuffer<C, I> {
+    fn default() -> Self {
+        Self {
+            writes: Vec::new(),
+            latest: Arc::new(HashMap::new()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C, I: Copy> VoxelWriteBuffer<C, I> {
+    pub(crate) fn push(&mut self, write: (IVec3, WorldVoxel<I>)) {
+        Arc::make_mut(&mut self.latest).insert(write.0, write.1);
+        self.writes.push(write);
+    }
+
+    pub(crate) fn extend(&mut self, writes: impl IntoIterator<Item = (IVec3, WorldVoxel<I>)>) {
+        for write in writes {
+            self.push(write);
+        }
+    }
+
+    /// Drop the writes to the positions `keep` returns `false` for
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(IVec3) -> bool) {
+        self.writes.retain(|(position, _)| keep(*position));
+        Arc::make_mut(&mut self.latest).retain(|position, _| keep(*position));
+    }
+
+    /// Take all the writes out of the buffer, in the order they were made
+    pub(crate) fn take(&mut self) -> Vec<(IVec3, WorldVoxel<I>)> {
+        self.latest = Arc::new(HashMap::new());
+        std::mem::take(&mut self.writes)
+    }
+
+    /// The voxel that was last written at the given position, if it has not been flushed yet.
+    /// Later writes win, like they do when the buffer is flushed.
+    pub(crate) fn latest(&self, position: IVec3) -> Option<WorldVoxel<I>> {
+        self.latest.get(&position).copied()
+    }
+
+    /// The last write at each position, which stays the same when more voxels are written
+    pub(crate) fn latest_snapshot(&self) -> Arc<HashMap<IVec3, WorldVoxel<I>>> {
+        self.latest.clone()
+    }
+}
+
+/// The chunks with voxels that were moved from the `VoxelWriteBuffer` into the `ModifiedVoxels`,
+/// but are not marked for remeshing yet. `VoxelWorld::flush_pending_writes` can't touch the
+/// chunk entities, so it leaves that to `flush_voxel_write_buffer`.
+#[derive(Resource)]
+pub(crate) struct FlushedChunks<C> {
+    pub touched: HashSet<IVec3>,
+    /// The neighbors that have the changed voxels in their padding, and so mesh their border
+    /// faces and ambient occlusion from them
+    pub bordering: HashSet<IVec3>,
+    /// The chunks the light of the changed voxels reaches, with `voxel_lighting`
+    pub lit: HashSet<IVec3>,
+    /// When the oldest voxel that is not marked for remeshing yet was set, which the edit
+    /// latency of the `ChunkTimings` is measured from
+    pub first_edit: Option<Instant>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for FlushedChunks<C> {
+    fn default() -> Self {
+        Self {
+            touched: HashSet::new(),
+            bordering: HashSet::new(),
+            lit: HashSet::new(),
+            first_edit: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Move the pending writes into the modified voxels, recording the changes on the way, and
+/// remember the chunks that need remeshing
+pub(crate) fn apply_voxel_writes<C: VoxelWorldConfig>(
+    buffer: &mut VoxelWriteBuffer<C, C::MaterialIndex>,
+    modified_voxels: &ModifiedVoxels<C, C::MaterialIndex>,
+    chunk_map: &ChunkMap<C, C::MaterialIndex>,
+    mut change_tracker: Option<&mut VoxelChangeTracker<C>>,
+    flushed_chunks: &mut FlushedChunks<C>,
+    configuration: &C,
+) {
+    if buffer.is_empty() {
+        return;
+    }
+    let lighting = configuration.voxel_lighting();
+    let chunk_map_read_lock = chunk_map.get_read_lock();
+    let mut modified_voxels = modified_voxels.write().unwrap();
+
+    for (position, voxel) in buffer.take() {
+        let (chunk_pos, vox_pos) = get_chunk_voxel_position(position);
+        let old = modified_voxels.insert(position, voxel);
+        flushed_chunks.touched.insert(chunk_pos);
+        flushed_chunks
+            .bordering
+            .extend(chunks_padded_with(position).filter(|chunk| *chunk != chunk_pos));
+        if let Some(lighting) = lighting {
+            flushed_chunks
+                .lit
+                .extend(chunks_lit_through(position, lighting));
+        }
+
+        if let Some(change_tracker) = &mut change_tracker {
+            let old = old.unwrap_or_else(|| {
+                ChunkMap::<C, C::MaterialIndex>::get(&chunk_pos, &chunk_map_read_lock)
+                    .map_or(WorldVoxel::Unset, |chunk_data| {
+                        chunk_data.get_voxel(vox_pos)
+                    })
+            });
+            change_tracker.record(position, old, voxel);
+        }
+    }
+}
+
+/// The chunks that have the voxel at `position` within their padded bounds. Only voxels within
+/// one voxel of a chunk border are in the padding of its neighbors.
+fn chunks_padded_with(position: IVec3) -> impl Iterator<Item = IVec3> {
+    let min = (position - IVec3::ONE).div_euclid(IVec3::splat(CHUNK_SIZE_I));
+    let max = (position + IVec3::ONE).div_euclid(IVec3::splat(CHUNK_SIZE_I));
+    (min.z..=max.z).flat_map(move |z| {
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec3::new(x, y, z)))
+    })
+}
+
+/// Chunks whose voxels were handed over with `VoxelWorld::insert_chunk`. They are used instead of
+/// the lookup delegate or chunk generator for as long as the chunk is spawned, so that they are
+/// also kept when the chunk is regenerated. Chunks inserted before they spawn wait for it, up to
+/// `VoxelWorldConfig::max_unspawned_inserted_chunks` of them.
+#[derive(Resource, Deref)]
+pub(crate) struct InsertedChunks<C, I> {
+    /// Shared with the closures of `VoxelWorld::get_voxel_fn`, and only copied when it changes
+    /// while one of them holds it
+    #[deref]
+    chunks: Arc<HashMap<IVec3, Arc<ChunkRef<I>>>>,
+    /// The inserted chunks that may not be spawned yet, oldest first
+    unspawned: VecDeque<IVec3>,
+    _marker: PhantomData<C>,
+}
+
+impl<C, I> Default for InsertedChunks<C, I> {
+    fn default() -> Self {
+        Self {
+            chunks: Arc::new(HashMap::new()),
+            unspawned: VecDeque::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C, I: Copy + PartialEq + std::hash::Hash> InsertedChunks<C, I> {
+    /// The inserted voxel at a position, if its chunk was inserted
+    pub fn get_voxel(&self, position: IVec3) -> Option<WorldVoxel<I>> {
+        self.get(&position.div_euclid(IVec3::splat(CHUNK_SIZE_I)))
+            .and_then(|chunk| chunk.get(position))
+    }
+
+    /// The inserted chunks as they are now, cheap to keep around
+    pub fn snapshot(&self) -> Arc<HashMap<IVec3, Arc<ChunkRef<I>>>> {
+        self.chunks.clone()
+    }
+
+    pub fn insert(&mut self, chunk: ChunkRef<I>) {
+        let chunk_position = chunk.chunk_position();
+        Arc::make_mut(&mut self.chunks).insert(chunk_position, Arc::new(chunk));
+        self.unspawned
+            .retain(|position| *position != chunk_position);
+        self.unspawned.push_back(chunk_position);
+    }
+
+    pub fn remove(&mut self, chunk_position: IVec3) {
+        if self.chunks.contains_key(&chunk_position) {
+            Arc::make_mut(&mut self.chunks).remove(&chunk_position);
+        }
+    }
+
+    /// Forget the chunks that have spawned since they were inserted, then drop the oldest of
+    /// those still waiting to spawn until at most `limit` of them are left
+    pub fn drop_unspawned(&mut self, limit: usize, is_spawned: impl Fn(IVec3) -> bool) {
+        let chunks = &self.chunks;
+        self.unspawned
+            .retain(|position| chunks.contains_key(position) && !is_spawned(*position));
+        while self.unspawned.len() > limit {
+            if let Some(chunk_position) = self.unspawned.pop_front() {
+                self.remove(chunk_position);
+            }
+        }
+    }
+}
+
+/// The boxes of chunk positions to regenerate at the end of the frame, queued by
+/// `VoxelWorld::invalidate_region`. `None` stands for every chunk.
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct ChunkInvalidations<C>(#[deref] Vec<Option<(IVec3, IVec3)>>, PhantomData<C>);
+
+/// Counts the calls to `VoxelWorld::invalidate_appearance`. The version is part of the mesh cache
+/// keys, so that meshes textured the old way are not reused.
+#[derive(Resource, Default)]
+pub(crate) struct AppearanceVersion<C> {
+    pub version: u64,
+    /// Whether the chunks still need to be queued for remeshing
+    pub pending: bool,
+    _marker: PhantomData<C>,
+}
+
+/// Marks chunks that are remeshed from the voxels they already have, instead of looking them up
+/// again
+#[derive(Component)]
+pub(crate) struct RemeshOnly;
+
+/// Entities of despawned chunks, emptied of all their components, kept for new chunks to reuse.
+/// Holds at most `VoxelWorldConfig::chunk_entity_pool_size` entities.
 #[derive(Resource, Deref, DerefMut, Default)]
-pub struct VoxelWriteBuffer<C, I>(#[deref] Vec<(IVec3, WorldVoxel<I>)>, PhantomData<C>);
+pub(crate) struct ChunkEntityPool<C>(#[deref] Vec<Entity>, PhantomData<C>);
+
+/// The chunks of a world added with `VoxelWorldPlugin::with_fixed_region`, as the minimum and
+/// maximum chunk position, inclusive. These are spawned regardless of the cameras, and never
+/// despawned.
+#[derive(Resource)]
+pub(crate) struct FixedRegion<C> {
+    pub min: IVec3,
+    pub max: IVec3,
+    _marker: PhantomData<C>,
+}
+
+impl<C> FixedRegion<C> {
+    pub fn new(min: IVec3, max: IVec3) -> Self {
+        Self {
+            min: min.min(max),
+            max: min.max(max),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn contains(&self, chunk_position: IVec3) -> bool {
+        chunk_position.cmpge(self.min).all() && chunk_position.cmple(self.max).all()
+    }
+}
+
+/// A region requested with `VoxelWorld::generate_now`, in chunk positions, inclusive
+pub(crate) struct GenerateNowRegion {
+    pub min: IVec3,
+    pub max: IVec3,
+    pub budget: Duration,
+    /// Whether `generate_now` was called for the region since the last frame
+    pub requested: bool,
+}
+
+impl GenerateNowRegion {
+    pub fn contains(&self, chunk_position: IVec3) -> bool {
+        chunk_position.cmpge(self.min).all() && chunk_position.cmple(self.max).all()
+    }
+
+    fn chunk_positions(&self) -> impl Iterator<Item = IVec3> {
+        let (min, max) = (self.min, self.max);
+        (min.z..=max.z)
+            .flat_map(move |z| (min.y..=max.y).map(move |y| (y, z)))
+            .flat_map(move |(y, z)| (min.x..=max.x).map(move |x| IVec3::new(x, y, z)))
+    }
+}
+
+/// The regions requested with `VoxelWorld::generate_now`. A region is dropped once it is ready
+/// and was not requested again in the frame before.
+#[derive(Resource)]
+pub(crate) struct GenerateNowRegions<C> {
+    pub regions: Vec<GenerateNowRegion>,
+    /// How long `remesh_dirty_chunks` may spend generating the chunks of the regions this frame
+    pub frame_budget: Duration,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for GenerateNowRegions<C> {
+    fn default() -> Self {
+        Self {
+            regions: Vec::new(),
+            frame_budget: Duration::ZERO,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> GenerateNowRegions<C> {
+    pub fn request(&mut self, min: IVec3, max: IVec3, budget: Duration) {
+        match self
+            .regions
+            .iter_mut()
+            .find(|region| region.min == min && region.max == max)
+        {
+            Some(region) => {
+                region.budget = budget;
+                region.requested = true;
+            }
+            None => self.regions.push(GenerateNowRegion {
+                min,
+                max,
+                budget,
+                requested: true,
+            }),
+        }
+    }
+}
 
 #[derive(Component)]
-pub(crate) struct NeedsMaterial<C>(PhantomData<C>);
+pub(crate) struct NeedsMaterial<C>(pub PhantomData<C>);
 
 pub(crate) struct Internals<C>(PhantomData<C>);
 
@@ -70,131 +771,482 @@ where
 {
     /// Init the resources used internally by bevy_voxel_world
     pub fn setup(mut commands: Commands, configuration: Res<C>) {
+        let distances = SpawningDistances::<C>::from_config(configuration.as_ref());
+        let chunk_count = theoretical_chunk_count(configuration.as_ref());
+        if chunk_count > configuration.max_loaded_chunks() {
+            warn!(
+                "bevy_voxel_world: the spawning distances load up to {} chunks per camera, more than \
+                 max_loaded_chunks ({}). Spawning distance is clamped to {}. Use lod_levels or \
+                 soft_despawn_distance to see further for less, or raise max_loaded_chunks.",
+                chunk_count,
+                configuration.max_loaded_chunks(),
+                distances.spawning,
+            );
+        }
+        commands.insert_resource(distances);
+
         commands.init_resource::<ChunkMap<C, C::MaterialIndex>>();
         commands.init_resource::<ChunkMapInsertBuffer<C, C::MaterialIndex>>();
         commands.init_resource::<ChunkMapUpdateBuffer<C, C::MaterialIndex>>();
         commands.init_resource::<ChunkMapRemoveBuffer<C>>();
         commands.init_resource::<MeshCache<C>>();
         commands.init_resource::<MeshCacheInsertBuffer<C>>();
+        commands.insert_resource(PersistedMeshes::<C>::load(configuration.as_ref()));
         commands.init_resource::<ModifiedVoxels<C, C::MaterialIndex>>();
         commands.init_resource::<VoxelWriteBuffer<C, C::MaterialIndex>>();
+        commands.init_resource::<ChunkInvalidations<C>>();
+        commands.init_resource::<AppearanceVersion<C>>();
+        commands.init_resource::<FlushedChunks<C>>();
+        commands.init_resource::<InsertedChunks<C, C::MaterialIndex>>();
+        commands.init_resource::<ReadyChunks<C>>();
+        commands.init_resource::<GenerateNowRegions<C>>();
+        commands.insert_resource(VoxelWorldRng::<C>::new(configuration.rng()));
+        commands.insert_resource(DespawnedChunks::<C, C::MaterialIndex>::new(
+            configuration.despawned_chunk_cache_size(),
+        ));
+        commands.insert_resource(LookupFnPool::<C, C::MaterialIndex>::new(
+            AsyncComputeTaskPool::get().thread_num(),
+        ));
+        commands.init_resource::<VoxelTasks<C, C::MaterialIndex>>();
+        commands.init_resource::<WorldPasses<C, C::MaterialIndex>>();
+        commands.init_resource::<PersistedChunks<C>>();
+        commands.init_resource::<ChunkTimings<C>>();
+        commands.init_resource::<SpawnFocus<C>>();
+        commands.init_resource::<ChunkRegions<C>>();
+        commands.init_resource::<ChunkEntityPool<C>>();
+        commands.init_resource::<VoxelDecals<C>>();
+        commands.init_resource::<VoxelMetadataMap<C>>();
+        commands.init_resource::<GlobalTint<C>>();
 
         // Create the root node and allow to modify it by the configuration.
         let world_root = commands
             .spawn((
                 WorldRoot::<C>(PhantomData),
                 VisibilityBundle::default(),
-                TransformBundle::default(),
+                // Chunks are built in voxel units, and scaled to world units through the root
+                TransformBundle::from_transform(Transform::from_scale(Vec3::splat(
+                    configuration.voxel_size(),
+                ))),
             ))
             .id();
         configuration.init_root(commands, world_root)
     }
 
-    /// Find and spawn chunks in need of spawning
-    pub fn spawn_chunks(
+    /// Queue the chunks within `startup_preseed_radius` of the cameras for spawning. These are
+    /// tagged with `PreseedChunk`, so that `spawn_meshes` waits for them to finish instead of
+    /// letting them trickle in over the following frames.
+    #[allow(clippy::too_many_arguments)]
+    pub fn preseed_chunks(
         mut commands: Commands,
+        mut done: Local<bool>,
         mut chunk_map_insert_buffer: ResMut<ChunkMapInsertBuffer<C, C::MaterialIndex>>,
+        mut chunk_regions: ResMut<ChunkRegions<C>>,
+        mut chunk_entity_pool: ResMut<ChunkEntityPool<C>>,
         world_root: Query<Entity, With<WorldRoot<C>>>,
         chunk_map: Res<ChunkMap<C, C::MaterialIndex>>,
         configuration: Res<C>,
         camera_info: CameraInfo<C>,
     ) {
-        // Panic if no root exists as it is already inserted in the setup.
-        let world_root = world_root.get_single().unwrap();
-
-        let (camera, cam_gtf) = camera_info.single();
-        let cam_pos = cam_gtf.translation().as_ivec3();
-
-        let spawning_distance = configuration.spawning_distance() as i32;
-        let spawning_distance_squared = spawning_distance.pow(2);
+        if *done {
+            return;
+        }
 
-        let viewport_size = camera.physical_viewport_size().unwrap_or_default();
+        let radius = configuration.startup_preseed_radius() as i32;
+        if radius == 0 {
+            *done = true;
+            return;
+        }
 
-        let mut visited = HashSet::new();
-        let mut chunks_deque =
-            VecDeque::with_capacity(configuration.spawning_rays() * spawning_distance as usize);
+        // Wait until there is a camera to seed around
+        let chunks_at_cameras = camera_info.chunks_at_cameras(configuration.as_ref());
+        if chunks_at_cameras.is_empty() {
+            return;
+        }
+        *done = true;
 
+        let world_root = world_root.get_single().unwrap();
         let chunk_map_read_lock = chunk_map.get_read_lock();
-
-        // Shoots a ray from the given point, and queue all (non-spawned) chunks intersecting the ray
-        let queue_chunks_intersecting_ray_from_point =
-            |point: Vec2, queue: &mut VecDeque<IVec3>| {
-                let Some(ray) = camera.viewport_to_world(cam_gtf, point) else {
-                    return;
-                };
-                let mut current = ray.origin;
-                let mut t = 0.0;
-                while t < (spawning_distance * CHUNK_SIZE_I) as f32 {
-                    let chunk_pos = current.as_ivec3() / CHUNK_SIZE_I;
-                    if let Some(chunk) =
-                        ChunkMap::<C, C::MaterialIndex>::get(&chunk_pos, &chunk_map_read_lock)
-                    {
-                        if chunk.is_full {
-                            // If we hit a full chunk, we can stop the ray early
-                            break;
+        let world_bounds = configuration.world_bounds();
+        let mut seeded = HashSet::new();
+
+        for chunk_at_camera in chunks_at_cameras.iter() {
+            for x in -radius..=radius {
+                for y in -radius..=radius {
+                    for z in -radius..=radius {
+                        let offset = IVec3::new(x, y, z);
+                        if offset.length_squared() > radius.pow(2) {
+                            continue;
                         }
-                    } else {
-                        queue.push_back(chunk_pos);
-                    }
-                    t += CHUNK_SIZE_F;
-                    current = ray.origin + ray.direction * t;
-                }
-            };
 
-        // Each frame we pick some random points on the screen
-        let m = configuration.spawning_ray_margin();
-        for _ in 0..configuration.spawning_rays() {
-            let random_point_in_viewport = {
-                let x = rand::random::<f32>() * (viewport_size.x + m * 2) as f32 - m as f32;
-                let y = rand::random::<f32>() * (viewport_size.y + m * 2) as f32 - m as f32;
-                Vec2::new(x, y)
-            };
-
-            // Then, for each point, we cast a ray, picking up any unspawned chunks along the ray
-            queue_chunks_intersecting_ray_from_point(random_point_in_viewport, &mut chunks_deque);
-        }
+                        let chunk_position = *chunk_at_camera + offset;
+                        if !seeded.insert(chunk_position)
+                            || !chunk_in_world_bounds(world_bounds, chunk_position)
+                            || ChunkMap::<C, C::MaterialIndex>::contains_chunk(
+                                &chunk_position,
+                                &chunk_map_read_lock,
+                            )
+                        {
+                            continue;
+                        }
 
-        // We also queue the chunks closest to the camera to make sure they will always spawn early
-        let chunk_at_camera = cam_pos / CHUNK_SIZE_I;
-        for x in -1..=1 {
-            for y in -1..=1 {
-                for z in -1..=1 {
-                    let queue_pos = chunk_at_camera + IVec3::new(x, y, z);
-                    chunks_deque.push_back(queue_pos);
+                        let chunk_entity = Self::spawn_chunk(
+                            &mut commands,
+                            world_root,
+                            chunk_position,
+                            chunk_lod_level(
+                                configuration.as_ref(),
+                                chunk_position,
+                                &chunks_at_cameras,
+                            ),
+                            &mut chunk_map_insert_buffer,
+                            &mut chunk_regions,
+                            &mut chunk_entity_pool,
+                            configuration.as_ref(),
+                        );
+                        commands.entity(chunk_entity).insert(PreseedChunk);
+                    }
                 }
             }
         }
+    }
 
-        // Then, when we have a queue of chunks, we can set them up for spawning
-        while let Some(chunk_position) = chunks_deque.pop_front() {
-            if visited.contains(&chunk_position)
-                || chunks_deque.len() > configuration.max_spawn_per_frame()
-            {
-                continue;
+    /// Spawn a new chunk entity under the world root, or its region, and queue it for insertion
+    /// in the chunk map. Entities of despawned chunks are reused when there are any in the pool.
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_chunk(
+        commands: &mut Commands,
+        world_root: Entity,
+        chunk_position: IVec3,
+        lod_level: u8,
+        chunk_map_insert_buffer: &mut ChunkMapInsertBuffer<C, C::MaterialIndex>,
+        chunk_regions: &mut ChunkRegions<C>,
+        chunk_entity_pool: &mut ChunkEntityPool<C>,
+        configuration: &C,
+    ) -> Entity {
+        let pooled_entity = std::iter::from_fn(|| chunk_entity_pool.pop()).find_map(|entity| {
+            commands
+                .get_entity(entity)
+                .map(|mut ent| ent.insert(NeedsRemesh).id())
+        });
+        let chunk_entity = pooled_entity.unwrap_or_else(|| commands.spawn(NeedsRemesh).id());
+        let hierarchy = configuration.chunk_hierarchy();
+        let world_name = || bevy::utils::get_short_name(std::any::type_name::<C>());
+        if hierarchy != ChunkHierarchy::Flat {
+            commands.entity(chunk_entity).insert(Name::new(format!(
+                "{} chunk {chunk_position}",
+                world_name()
+            )));
+        }
+        let parent = match hierarchy {
+            ChunkHierarchy::Flat | ChunkHierarchy::Named => world_root,
+            ChunkHierarchy::Regions { region_size } => {
+                let region_position =
+                    chunk_position.div_euclid(IVec3::splat(region_size.max(1) as i32));
+                *chunk_regions.entry(region_position).or_insert_with(|| {
+                    let region = commands
+                        .spawn((
+                            ChunkRegion::<C>::new(region_position),
+                            Name::new(format!("{} region {region_position}", world_name())),
+                            SpatialBundle::default(),
+                        ))
+                        .id();
+                    commands.entity(world_root).add_child(region);
+                    region
+                })
             }
-            visited.insert(chunk_position);
+        };
+        commands.entity(parent).add_child(chunk_entity);
+        let mut chunk = Chunk::<C>::new(chunk_position, chunk_entity);
+        chunk.lod_level = lod_level;
 
-            if chunk_position.distance_squared(chunk_at_camera) > spawning_distance_squared {
-                continue;
-            }
+        chunk_map_insert_buffer.push((chunk_position, ChunkData::with_entity(chunk.entity)));
 
-            let has_chunk = ChunkMap::<C, C::MaterialIndex>::contains_chunk(
-                &chunk_position,
-                &chunk_map_read_lock,
-            );
+        commands.entity(chunk.entity).try_insert((
+            chunk,
+            Transform::from_translation(chunk_translation(chunk_position)),
+        ));
 
-            if !has_chunk {
-                let chunk_entity = commands.spawn(NeedsRemesh).id();
-                commands.entity(world_root).add_child(chunk_entity);
-                let chunk = Chunk::<C>::new(chunk_position, chunk_entity);
+        chunk_entity
+    }
 
-                chunk_map_insert_buffer
-                    .push((chunk_position, ChunkData::with_entity(chunk.entity)));
+    /// Works out the capped spawning distances again when the configuration changes
+    pub fn update_spawning_distances(
+        mut distances: ResMut<SpawningDistances<C>>,
+        configuration: Res<C>,
+    ) {
+        if configuration.is_changed() {
+            *distances = SpawningDistances::from_config(configuration.as_ref());
+        }
+    }
 
-                commands.entity(chunk.entity).try_insert((
-                    chunk,
-                    Transform::from_translation(chunk_position.as_vec3() * CHUNK_SIZE_F - 1.0),
-                ));
+    /// Moves the spawn focus of each anchor ahead of it, based on its velocity
+    pub fn update_spawn_focus(
+        mut spawn_focus: ResMut<SpawnFocus<C>>,
+        camera_info: CameraInfo<C>,
+        configuration: Res<C>,
+        time: Res<Time>,
+    ) {
+        let max_lookahead = (camera_info.distances.spawning / 2) as f32 * CHUNK_SIZE_F;
+        let mut chunk_positions = HashMap::new();
+        let mut last_camera_positions = HashMap::new();
+
+        for anchor in camera_info.anchors(configuration.as_ref()) {
+            let (entity, cam_pos) = (anchor.entity, anchor.position);
+
+            let last_position = spawn_focus.last_camera_positions.get(&entity);
+            let velocity = match (anchor.motion_hint, last_position) {
+                (Some(velocity), _) => velocity,
+                (None, Some(last_position)) if time.delta_seconds() > 0.0 => {
+                    (cam_pos - *last_position) / time.delta_seconds()
+                }
+                _ => Vec3::ZERO,
+            };
+            last_camera_positions.insert(entity, cam_pos);
+
+            let lookahead =
+                (velocity * configuration.prefetch_lookahead()).clamp_length_max(max_lookahead);
+            chunk_positions.insert(entity, chunk_containing(cam_pos + lookahead));
+        }
+
+        // Cameras that are gone are dropped
+        spawn_focus.chunk_positions = chunk_positions;
+        spawn_focus.last_camera_positions = last_camera_positions;
+    }
+
+    /// Find and spawn chunks in need of spawning. Chunks are spawned around every camera, and
+    /// within the view of every camera.
+    #[allow(clippy::too_many_arguments)]
+    pub fn spawn_chunks(
+        mut commands: Commands,
+        mut chunk_map_insert_buffer: ResMut<ChunkMapInsertBuffer<C, C::MaterialIndex>>,
+        mut chunk_regions: ResMut<ChunkRegions<C>>,
+        mut chunk_entity_pool: ResMut<ChunkEntityPool<C>>,
+        world_root: Query<Entity, With<WorldRoot<C>>>,
+        chunk_map: Res<ChunkMap<C, C::MaterialIndex>>,
+        spawn_focus: Res<SpawnFocus<C>>,
+        configuration: Res<C>,
+        camera_info: CameraInfo<C>,
+        rng: Res<VoxelWorldRng<C>>,
+        mut anchor_offsets: Local<HashMap<u32, Vec<IVec3>>>,
+    ) {
+        // Panic if no root exists as it is already inserted in the setup.
+        let world_root = world_root.get_single().unwrap();
+
+        let spawning_distance = camera_info.distances.spawning as i32;
+        let world_bounds = configuration.world_bounds();
+
+        let anchors = camera_info.anchors(configuration.as_ref());
+        let chunks_at_cameras = camera_info.chunks_at_cameras(configuration.as_ref());
+        let focuses: Vec<IVec3> = anchors
+            .iter()
+            .zip(chunks_at_cameras.iter())
+            .map(|(anchor, chunk_at_camera)| {
+                // Cameras that were added this frame have no focus yet
+                spawn_focus
+                    .chunk_positions
+                    .get(&anchor.entity)
+                    .copied()
+                    .unwrap_or(*chunk_at_camera)
+            })
+            .collect();
+
+        // Chunks that are still waiting to be inserted in the chunk map must not be spawned again
+        let mut visited: HashSet<IVec3> = chunk_map_insert_buffer
+            .iter()
+            .map(|(position, _)| *position)
+            .collect();
+        let mut chunks_deque = VecDeque::with_capacity(
+            configuration.spawning_rays() * spawning_distance as usize * anchors.len(),
+        );
+
+        let chunk_map_read_lock = chunk_map.get_read_lock();
+
+        // The chunks around the cameras come first, regardless of distance and spawn limits
+        for chunk_position in camera_info.camera_chunks(configuration.as_ref()) {
+            if visited.insert(chunk_position)
+                && chunk_in_world_bounds(world_bounds, chunk_position)
+                && !ChunkMap::<C, C::MaterialIndex>::contains_chunk(
+                    &chunk_position,
+                    &chunk_map_read_lock,
+                )
+            {
+                Self::spawn_chunk(
+                    &mut commands,
+                    world_root,
+                    chunk_position,
+                    chunk_lod_level(configuration.as_ref(), chunk_position, &chunks_at_cameras),
+                    &mut chunk_map_insert_buffer,
+                    &mut chunk_regions,
+                    &mut chunk_entity_pool,
+                    configuration.as_ref(),
+                );
+            }
+        }
+
+        let margin_chunks = configuration
+            .spawning_chunk_margin()
+            .map(|margin| margin as i32);
+
+        // Shoots a ray from the given point, and queue all (non-spawned) chunks intersecting the ray.
+        // With a chunk margin, the chunks around each of them are queued as well, once per frame.
+        let queue_chunks_intersecting_ray =
+            |mut ray: Ray3d, queue: &mut VecDeque<IVec3>, ray_chunks: &mut HashSet<IVec3>| {
+                ray.origin /= configuration.voxel_size();
+                let mut current = ray.origin;
+                let mut t = 0.0;
+                while t < (spawning_distance * CHUNK_SIZE_I) as f32 {
+                    let chunk_pos = (current / CHUNK_SIZE_F).floor().as_ivec3();
+                    if let (Some(margin), true) = (margin_chunks, ray_chunks.insert(chunk_pos)) {
+                        for x in -margin..=margin {
+                            for y in -margin..=margin {
+                                for z in -margin..=margin {
+                                    let offset = IVec3::new(x, y, z);
+                                    if offset != IVec3::ZERO {
+                                        queue.push_back(chunk_pos + offset);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    if let Some(chunk) =
+                        ChunkMap::<C, C::MaterialIndex>::get(&chunk_pos, &chunk_map_read_lock)
+                    {
+                        if chunk.is_full {
+                            // If we hit a full chunk, we can stop the ray early
+                            break;
+                        }
+                    } else {
+                        queue.push_back(chunk_pos);
+                    }
+                    t += CHUNK_SIZE_F;
+                    current = ray.origin + ray.direction * t;
+                }
+            };
+
+        // The view of orthographic cameras is a box, which gets spawned as a whole
+        let orthographic_chunks = camera_info.orthographic_view_chunks(configuration.as_ref());
+        chunks_deque.extend(orthographic_chunks.iter().copied());
+
+        // Each frame we pick some random points in the viewport of each perspective camera. The
+        // points are picked in normalized device coordinates, so that each camera covers its own
+        // viewport in split-screen setups, and the pixel margin is converted to them. The eyes of
+        // a rig split the rays between them, and rigs spawning a sphere cast them in all
+        // directions from between the eyes instead.
+        let m = match margin_chunks {
+            Some(_) => 0,
+            None => configuration.spawning_ray_margin(),
+        };
+        let mut ray_chunks = HashSet::new();
+        let mut camera_rays = HashMap::new();
+        for (anchor, focus) in anchors.iter().zip(focuses.iter()) {
+            // Spawn anchors have no view to cast rays into, so the chunks around them that are
+            // missing are queued nearest first
+            if let Some(radius) = anchor.radius {
+                chunks_deque.extend(
+                    anchor_offsets
+                        .entry(radius)
+                        .or_insert_with(|| sphere_offsets(radius as i32))
+                        .iter()
+                        .map(|offset| *focus + *offset)
+                        .filter(|chunk_position| {
+                            !ChunkMap::<C, C::MaterialIndex>::contains_chunk(
+                                chunk_position,
+                                &chunk_map_read_lock,
+                            )
+                        })
+                        .take(configuration.max_spawn_per_frame()),
+                );
+                continue;
+            }
+            if anchor.spawn_volume == Some(RigSpawnVolume::Sphere) {
+                for _ in 0..configuration.spawning_rays() {
+                    let ray = Ray3d {
+                        origin: anchor.position * configuration.voxel_size(),
+                        direction: rng.with(random_direction),
+                    };
+                    queue_chunks_intersecting_ray(ray, &mut chunks_deque, &mut ray_chunks);
+                }
+                continue;
+            }
+            let rays = configuration.spawning_rays().div_ceil(anchor.cameras.len());
+            camera_rays.extend(anchor.cameras.iter().map(|camera| (*camera, rays)));
+        }
+        for (entity, camera, cam_gtf) in camera_info.iter() {
+            let Some(rays) = camera_rays.get(&entity).copied() else {
+                continue;
+            };
+            if camera_info.is_orthographic(entity) {
+                continue;
+            }
+            let clip_from_view = camera_info
+                .projections
+                .get(entity)
+                .map_or(camera.clip_from_view(), |projection| {
+                    projection.get_clip_from_view()
+                });
+            let ndc_extent = camera.physical_viewport_size().map_or(Vec2::ONE, |size| {
+                Vec2::ONE + Vec2::splat(m as f32 * 2.0) / size.as_vec2()
+            });
+            for _ in 0..rays {
+                let random_point_in_viewport =
+                    (rng.with(|rng| Vec2::new(rng.gen(), rng.gen())) * 2.0 - 1.0) * ndc_extent;
+
+                // Then, for each point, we cast a ray, picking up any unspawned chunks along the ray
+                if let Some(ray) = ndc_ray(cam_gtf, clip_from_view, random_point_in_viewport) {
+                    queue_chunks_intersecting_ray(ray, &mut chunks_deque, &mut ray_chunks);
+                }
+            }
+        }
+
+        // We also queue the chunks closest to the cameras, and to where they are heading, to make
+        // sure they will always spawn early
+        for (chunk_at_camera, focus) in chunks_at_cameras.iter().zip(focuses.iter()) {
+            for x in -1..=1 {
+                for y in -1..=1 {
+                    for z in -1..=1 {
+                        chunks_deque.push_back(*chunk_at_camera + IVec3::new(x, y, z));
+                        if focus != chunk_at_camera {
+                            chunks_deque.push_back(*focus + IVec3::new(x, y, z));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Then, when we have a queue of chunks, we can set them up for spawning
+        while let Some(chunk_position) = chunks_deque.pop_front() {
+            if visited.contains(&chunk_position)
+                || chunks_deque.len() > configuration.max_spawn_per_frame()
+            {
+                continue;
+            }
+            visited.insert(chunk_position);
+
+            // Chunks only need to be in range of one of the cameras or spawn anchors, or in the
+            // view of an orthographic camera
+            let in_range = anchors.iter().zip(focuses.iter()).any(|(anchor, focus)| {
+                chunk_position.distance_squared(*focus) <= anchor.spawning_distance.pow(2)
+            }) || orthographic_chunks.contains(&chunk_position);
+            if !in_range || !chunk_in_world_bounds(world_bounds, chunk_position) {
+                continue;
+            }
+
+            let has_chunk = ChunkMap::<C, C::MaterialIndex>::contains_chunk(
+                &chunk_position,
+                &chunk_map_read_lock,
+            );
+
+            if !has_chunk {
+                Self::spawn_chunk(
+                    &mut commands,
+                    world_root,
+                    chunk_position,
+                    chunk_lod_level(configuration.as_ref(), chunk_position, &chunks_at_cameras),
+                    &mut chunk_map_insert_buffer,
+                    &mut chunk_regions,
+                    &mut chunk_entity_pool,
+                    configuration.as_ref(),
+                );
             } else {
                 continue;
             }
@@ -218,231 +1270,2062 @@ where
         }
     }
 
-    /// Tags chunks that are eligible for despawning
-    pub fn retire_chunks(
+    /// Spawn the chunks of the `FixedRegion` that are not spawned yet, within
+    /// `max_spawn_per_frame`. This replaces the camera driven spawning and despawning. Once every
+    /// chunk is spawned, the region is not scanned again until a chunk is despawned.
+    #[allow(clippy::too_many_arguments)]
+    pub fn spawn_fixed_region(
+        mut complete: Local<bool>,
+        mut removed_chunks: RemovedComponents<Chunk<C>>,
+        mut commands: Commands,
+        mut chunk_map_insert_buffer: ResMut<ChunkMapInsertBuffer<C, C::MaterialIndex>>,
+        mut chunk_regions: ResMut<ChunkRegions<C>>,
+        mut chunk_entity_pool: ResMut<ChunkEntityPool<C>>,
+        chunk_map_remove_buffer: Res<ChunkMapRemoveBuffer<C>>,
+        world_root: Query<Entity, With<WorldRoot<C>>>,
+        chunk_map: Res<ChunkMap<C, C::MaterialIndex>>,
+        fixed_region: Res<FixedRegion<C>>,
+        configuration: Res<C>,
+    ) {
+        if removed_chunks.read().count() > 0 {
+            *complete = false;
+        }
+        if *complete {
+            return;
+        }
+
+        let world_root = world_root.get_single().unwrap();
+        let world_bounds = configuration.world_bounds();
+
+        let queued: HashSet<IVec3> = chunk_map_insert_buffer
+            .iter()
+            .map(|(position, _)| *position)
+            .collect();
+        let missing: Vec<IVec3> = {
+            let chunk_map_read_lock = chunk_map.get_read_lock();
+            let (min, max) = (fixed_region.min, fixed_region.max);
+            (min.z..=max.z)
+                .flat_map(|z| (min.y..=max.y).map(move |y| (y, z)))
+                .flat_map(|(y, z)| (min.x..=max.x).map(move |x| IVec3::new(x, y, z)))
+                .filter(|chunk_position| {
+                    !queued.contains(chunk_position)
+                        && chunk_in_world_bounds(world_bounds, *chunk_position)
+                        && !ChunkMap::<C, C::MaterialIndex>::contains_chunk(
+                            chunk_position,
+                            &chunk_map_read_lock,
+                        )
+                })
+                .take(configuration.max_spawn_per_frame())
+                .collect()
+        };
+        // Chunks still in the buffers are skipped or look spawned, so wait until the map has them
+        *complete = missing.is_empty() && queued.is_empty() && chunk_map_remove_buffer.is_empty();
+
+        for chunk_position in missing {
+            Self::spawn_chunk(
+                &mut commands,
+                world_root,
+                chunk_position,
+                0,
+                &mut chunk_map_insert_buffer,
+                &mut chunk_regions,
+                &mut chunk_entity_pool,
+                configuration.as_ref(),
+            );
+        }
+    }
+
+    /// Spawn the missing chunks of the regions requested with `VoxelWorld::generate_now`, and tag
+    /// all chunks in them with `GenerateNowChunk`. Regions that are ready and were not requested
+    /// again are dropped, and their chunks are left to the regular streaming.
+    #[allow(clippy::too_many_arguments)]
+    pub fn spawn_generate_now_regions(
+        mut commands: Commands,
+        mut generate_now: ResMut<GenerateNowRegions<C>>,
+        mut chunk_map_insert_buffer: ResMut<ChunkMapInsertBuffer<C, C::MaterialIndex>>,
+        mut chunk_regions: ResMut<ChunkRegions<C>>,
+        mut chunk_entity_pool: ResMut<ChunkEntityPool<C>>,
+        tagged_chunks: Query<&Chunk<C>, With<GenerateNowChunk>>,
+        world_root: Query<Entity, With<WorldRoot<C>>>,
+        chunk_map: Res<ChunkMap<C, C::MaterialIndex>>,
+        ready_chunks: Res<ReadyChunks<C>>,
+        fixed_region: Option<Res<FixedRegion<C>>>,
+        configuration: Res<C>,
+    ) {
+        let world_bounds = configuration.world_bounds();
+        let in_bounds = |chunk_position: IVec3| {
+            chunk_in_world_bounds(world_bounds, chunk_position)
+                && fixed_region
+                    .as_ref()
+                    .is_none_or(|region| region.contains(chunk_position))
+        };
+
+        generate_now.regions.retain_mut(|region| {
+            let keep = region.requested
+                || !region
+                    .chunk_positions()
+                    .filter(|chunk_position| in_bounds(*chunk_position))
+                    .all(|chunk_position| ready_chunks.contains(&chunk_position));
+            region.requested = false;
+            keep
+        });
+        generate_now.frame_budget = generate_now
+            .regions
+            .iter()
+            .map(|region| region.budget)
+            .max()
+            .unwrap_or_default();
+
+        for chunk in tagged_chunks.iter() {
+            if !generate_now
+                .regions
+                .iter()
+                .any(|region| region.contains(chunk.position))
+            {
+                commands.entity(chunk.entity).remove::<GenerateNowChunk>();
+            }
+        }
+
+        let Ok(world_root) = world_root.get_single() else {
+            return;
+        };
+        // Chunks spawned earlier this frame are still in the insert buffer
+        let queued: HashMap<IVec3, Entity> = chunk_map_insert_buffer
+            .iter()
+            .map(|(position, chunk_data)| (*position, chunk_data.entity))
+            .collect();
+        let chunk_map_read_lock = chunk_map.get_read_lock();
+        let mut region_chunks = HashMap::new();
+        // The rest of the missing chunks are spawned over the next frames
+        let mut spawn_budget = configuration.max_spawn_per_frame();
+        let max_chunks = configuration.max_loaded_chunks();
+
+        for region in generate_now.regions.iter() {
+            for chunk_position in region.chunk_positions() {
+                if region_chunks.len() >= max_chunks {
+                    break;
+                }
+                if region_chunks.contains_key(&chunk_position) || !in_bounds(chunk_position) {
+                    continue;
+                }
+                let chunk_entity = match queued.get(&chunk_position).copied().or_else(|| {
+                    ChunkMap::<C, C::MaterialIndex>::get(&chunk_position, &chunk_map_read_lock)
+                        .map(|chunk_data| chunk_data.entity)
+                }) {
+                    Some(chunk_entity) => chunk_entity,
+                    None if spawn_budget > 0 => {
+                        spawn_budget -= 1;
+                        Self::spawn_chunk(
+                            &mut commands,
+                            world_root,
+                            chunk_position,
+                            0,
+                            &mut chunk_map_insert_buffer,
+                            &mut chunk_regions,
+                            &mut chunk_entity_pool,
+                            configuration.as_ref(),
+                        )
+                    }
+                    None => continue,
+                };
+                region_chunks.insert(chunk_position, chunk_entity);
+            }
+        }
+
+        for chunk_entity in region_chunks.into_values() {
+            commands.entity(chunk_entity).try_insert(GenerateNowChunk);
+        }
+    }
+
+    /// Tags chunks that are eligible for despawning, and hides or shows chunks when
+    /// `soft_despawn_distance` is configured. Chunks only get retired when they are out of range
+    /// of all cameras. Retired chunks are hidden right away, and despawned over the next frames
+    /// within `max_despawn_per_frame`. Retired chunks that come back in range before they are
+    /// despawned are kept, and shown again. Hidden chunks can't tell whether they are in view, so
+    /// chunks retired for being out of view stay retired while they are in range.
+    #[allow(clippy::type_complexity)]
+    pub fn retire_chunks(
+        mut commands: Commands,
+        all_chunks: Query<
+            (
+                &Chunk<C>,
+                Option<&ViewVisibility>,
+                Has<HiddenChunk>,
+                Has<NeedsDespawn>,
+            ),
+            Without<GenerateNowChunk>,
+        >,
+        spawn_focus: Res<SpawnFocus<C>>,
+        configuration: Res<C>,
+        camera_info: CameraInfo<C>,
+        metadata: Res<VoxelMetadataMap<C>>,
+        mut events: (
+            EventWriter<ChunkWillDespawn<C>>,
+            EventWriter<ChunkMetadataWillDespawn<C>>,
+        ),
+    ) {
+        let camera_chunks = camera_info.camera_chunks(configuration.as_ref());
+        let orthographic_chunks = camera_info.orthographic_view_chunks(configuration.as_ref());
+
+        // Without a camera, there is nothing to measure distances from
+        if spawn_focus.chunk_positions.is_empty() {
+            return;
+        }
+
+        let spawning_distance = camera_info.distances.spawning as i32;
+        let spawning_distance_squared = spawning_distance.pow(2);
+        let despawn_distance_squared = camera_info
+            .distances
+            .soft_despawn
+            .map_or(spawning_distance_squared, |distance| {
+                (distance as i32).max(spawning_distance).pow(2)
+            });
+
+        let anchors = camera_info.anchors(configuration.as_ref());
+        let focus_of =
+            |anchor: &SpawnAnchor| spawn_focus.chunk_positions.get(&anchor.entity).copied();
+
+        // Rigs spawning a sphere keep every chunk in range, whether it is in view or not
+        let sphere_focuses: Vec<IVec3> = anchors
+            .iter()
+            .filter(|anchor| anchor.spawn_volume == Some(RigSpawnVolume::Sphere))
+            .filter_map(focus_of)
+            .collect();
+
+        // Spawn anchors keep the chunks within their own radius, and let go of the rest
+        let spawn_anchor_focuses: Vec<(IVec3, i32)> = anchors
+            .iter()
+            .filter(|anchor| anchor.radius.is_some())
+            .filter_map(|anchor| Some((focus_of(anchor)?, anchor.spawning_distance.pow(2))))
+            .collect();
+        let camera_focuses: Vec<IVec3> = anchors
+            .iter()
+            .filter(|anchor| anchor.radius.is_none())
+            .filter_map(focus_of)
+            .collect();
+
+        let culls_out_of_view =
+            configuration.chunk_despawn_strategy() == ChunkDespawnStrategy::FarAwayOrOutOfView;
+        let chunks_to_remove = {
+            let mut remove = Vec::with_capacity(1000);
+            for (chunk, view_visibility, is_hidden, is_retired) in all_chunks.iter() {
+                let near_spawn_anchor =
+                    spawn_anchor_focuses.iter().any(|(focus, radius_squared)| {
+                        chunk.position.distance_squared(*focus) <= radius_squared + 1
+                    });
+                if camera_chunks.contains(&chunk.position)
+                    || orthographic_chunks.contains(&chunk.position)
+                    || near_spawn_anchor
+                {
+                    if is_hidden {
+                        commands
+                            .entity(chunk.entity)
+                            .try_insert(Visibility::Inherited)
+                            .remove::<(HiddenChunk, NeedsDespawn)>();
+                    }
+                    continue;
+                }
+
+                let should_be_culled = {
+                    match configuration.chunk_despawn_strategy() {
+                        ChunkDespawnStrategy::FarAway => false,
+                        // Hidden chunks are never in view, they only go by distance
+                        ChunkDespawnStrategy::FarAwayOrOutOfView if is_hidden => false,
+                        ChunkDespawnStrategy::FarAwayOrOutOfView
+                            if sphere_focuses.iter().any(|focus| {
+                                chunk.position.distance_squared(*focus) <= spawning_distance_squared
+                            }) =>
+                        {
+                            false
+                        }
+                        ChunkDespawnStrategy::FarAwayOrOutOfView => {
+                            if let Some(visibility) = view_visibility {
+                                !visibility.get()
+                            } else {
+                                false
+                            }
+                        }
+                    }
+                };
+                // Without a camera, the chunks away from the spawn anchors are out of range
+                let dist_squared = camera_focuses
+                    .iter()
+                    .map(|focus| chunk.position.distance_squared(*focus))
+                    .min()
+                    .unwrap_or(i32::MAX);
+                if should_be_culled || dist_squared > despawn_distance_squared + 1 {
+                    if !is_retired {
+                        remove.push(chunk);
+                    }
+                } else if dist_squared > spawning_distance_squared + 1 {
+                    if is_retired {
+                        commands.entity(chunk.entity).remove::<NeedsDespawn>();
+                    } else if !is_hidden {
+                        commands
+                            .entity(chunk.entity)
+                            .try_insert((HiddenChunk, Visibility::Hidden));
+                    }
+                } else if is_retired && culls_out_of_view {
+                    // Showing the chunk again would retire it again, if it is still out of view
+                } else if is_hidden {
+                    commands
+                        .entity(chunk.entity)
+                        .try_insert(Visibility::Inherited)
+                        .remove::<(HiddenChunk, NeedsDespawn)>();
+                }
+            }
+            remove
+        };
+
+        for chunk in chunks_to_remove {
+            commands.entity(chunk.entity).try_insert((
+                NeedsDespawn,
+                HiddenChunk,
+                Visibility::Hidden,
+            ));
+
+            events
+                .0
+                .send(ChunkWillDespawn::<C>::new(chunk.position, chunk.entity));
+
+            let chunk_metadata = metadata.chunk(chunk.position);
+            if !chunk_metadata.is_empty() {
+                events.1.send(ChunkMetadataWillDespawn::<C>::new(
+                    chunk.position,
+                    chunk.entity,
+                    chunk_metadata,
+                ));
+            }
+        }
+    }
+
+    /// Moves chunks to the level of detail for their current distance from the closest camera,
+    /// and queues them for remeshing at that level
+    #[allow(clippy::type_complexity)]
+    pub fn update_chunk_lods(
+        mut commands: Commands,
+        mut all_chunks: Query<
+            &mut Chunk<C>,
+            (
+                Without<NeedsDespawn>,
+                Without<HiddenChunk>,
+                Without<GenerateNowChunk>,
+            ),
+        >,
+        configuration: Res<C>,
+        camera_info: CameraInfo<C>,
+    ) {
+        if configuration.lod_levels() <= 1 {
+            return;
+        }
+
+        let chunks_at_cameras = camera_info.chunks_at_cameras(configuration.as_ref());
+        if chunks_at_cameras.is_empty() {
+            return;
+        }
+
+        for mut chunk in all_chunks.iter_mut() {
+            let lod_level =
+                chunk_lod_level(configuration.as_ref(), chunk.position, &chunks_at_cameras);
+            if lod_level != chunk.lod_level {
+                chunk.lod_level = lod_level;
+                commands.entity(chunk.entity).try_insert(NeedsRemesh);
+            }
+        }
+    }
+
+    /// Despawns chunks that have been tagged for despawning, at most `max_despawn_per_frame` of
+    /// them per frame
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub fn despawn_retired_chunks(
+        mut commands: Commands,
+        mut chunk_map_remove_buffer: ResMut<ChunkMapRemoveBuffer<C>>,
+        mut chunk_entity_pool: ResMut<ChunkEntityPool<C>>,
+        chunk_map: Res<ChunkMap<C, C::MaterialIndex>>,
+        retired_chunks: Query<(Entity, &Chunk<C>, Has<NeedsRemesh>), With<NeedsDespawn>>,
+        mut despawned_chunks: ResMut<DespawnedChunks<C, C::MaterialIndex>>,
+        mut inserted_chunks: ResMut<InsertedChunks<C, C::MaterialIndex>>,
+        mut ready_chunks: ResMut<ReadyChunks<C>>,
+        modified_voxels: Res<ModifiedVoxels<C, C::MaterialIndex>>,
+        chunk_timings: Res<ChunkTimings<C>>,
+        configuration: Res<C>,
+    ) {
+        let persistence = configuration.chunk_persistence();
+        let read_lock = chunk_map.get_read_lock();
+        let despawnable_chunks = retired_chunks.iter().filter(|(_, chunk, _)| {
+            ChunkMap::<C, C::MaterialIndex>::contains_chunk(&chunk.position, &read_lock)
+        });
+        let pool_size = configuration.chunk_entity_pool_size();
+        for (entity, chunk, needs_remesh) in
+            despawnable_chunks.take(configuration.max_despawn_per_frame())
+        {
+            if chunk_entity_pool.len() < pool_size {
+                // Strip the entity down to nothing, including components added by the app
+                commands
+                    .entity(entity)
+                    .despawn_descendants()
+                    .remove_parent()
+                    .retain::<()>();
+                chunk_entity_pool.push(entity);
+            } else {
+                commands.entity(entity).despawn_recursive();
+            }
+            chunk_map_remove_buffer.push(chunk.position);
+            chunk_timings.discard_edits(chunk.position);
+            inserted_chunks.remove(chunk.position);
+            ready_chunks.remove(&chunk.position);
+
+            // The voxels of chunks waiting to be regenerated may be outdated. Chunks above LOD
+            // level 0 only hold a sample of their voxels.
+            if let (false, 0, Some(chunk_data)) = (
+                needs_remesh,
+                chunk.lod_level,
+                read_lock.get(&chunk.position),
+            ) {
+                despawned_chunks.insert(chunk.position, chunk_data.clone());
+            }
+
+            if let Some(persistence) = persistence.clone() {
+                let voxels = chunk_modified_voxels(&modified_voxels, chunk.position);
+                if !voxels.is_empty() {
+                    let chunk_position = chunk.position;
+                    IoTaskPool::get()
+                        .spawn(async move { persistence.save(chunk_position, &voxels) })
+                        .detach();
+                }
+            }
+        }
+
+        if !inserted_chunks.unspawned.is_empty() {
+            inserted_chunks.drop_unspawned(
+                configuration.max_unspawned_inserted_chunks(),
+                |chunk_position| {
+                    ChunkMap::<C, C::MaterialIndex>::contains_chunk(&chunk_position, &read_lock)
+                },
+            );
+        }
+    }
+
+    /// Despawn the regions of `ChunkHierarchy::Regions` that have no chunks left
+    pub fn despawn_empty_regions(
+        mut commands: Commands,
+        mut chunk_regions: ResMut<ChunkRegions<C>>,
+        regions: Query<(Entity, &ChunkRegion<C>, Option<&Children>)>,
+    ) {
+        // Despawning a child can leave an empty `Children` behind
+        let empty_regions = regions
+            .iter()
+            .filter(|(_, _, children)| children.is_none_or(|children| children.is_empty()));
+        for (entity, region, _) in empty_regions {
+            commands.entity(entity).despawn();
+            chunk_regions.remove(&region.position);
+        }
+    }
+
+    /// Adds the chunks that got meshed to `ReadyChunks`. They are taken out again by
+    /// `despawn_retired_chunks`.
+    pub fn track_ready_chunks(
+        mut ready_chunks: ResMut<ReadyChunks<C>>,
+        mut ev_chunk_mesh_ready: EventReader<ChunkMeshReady<C>>,
+    ) {
+        for event in ev_chunk_mesh_ready.read() {
+            ready_chunks.insert(event.chunk_key);
+        }
+    }
+
+    /// Sends `CameraLeftGeneratedArea` and `CameraEnteredGeneratedArea` when a camera moves out of
+    /// or back into the chunks that are ready, and freezes the entities with
+    /// `FreezeUntilGenerated` in place while the chunk they are in is not ready. Chunks that can't
+    /// be generated, outside of the world bounds or fixed region, never freeze anything.
+    #[allow(clippy::type_complexity)]
+    pub fn track_generated_area(
+        mut cameras_inside: Local<HashMap<Entity, bool>>,
+        cameras: Query<(Entity, &GlobalTransform), With<VoxelWorldCamera<C>>>,
+        mut frozen: Query<(
+            &mut FreezeUntilGenerated<C>,
+            &mut Transform,
+            &GlobalTransform,
+        )>,
+        ready_chunks: Res<ReadyChunks<C>>,
+        fixed_region: Option<Res<FixedRegion<C>>>,
+        configuration: Res<C>,
+        mut events: (
+            EventWriter<CameraLeftGeneratedArea<C>>,
+            EventWriter<CameraEnteredGeneratedArea<C>>,
+        ),
+    ) {
+        let world_bounds = configuration.world_bounds();
+        let chunk_of = |gtf: &GlobalTransform| {
+            chunk_containing(camera_voxel_position(configuration.as_ref(), gtf))
+        };
+        let is_ready = |chunk_position: IVec3| {
+            ready_chunks.contains(&chunk_position)
+                || !chunk_in_world_bounds(world_bounds, chunk_position)
+                || fixed_region
+                    .as_ref()
+                    .is_some_and(|region| !region.contains(chunk_position))
+        };
+
+        cameras_inside.retain(|camera, _| cameras.contains(*camera));
+        for (camera, cam_gtf) in cameras.iter() {
+            let chunk_position = chunk_of(cam_gtf);
+            let inside = is_ready(chunk_position);
+            // Cameras start out inside, so a camera waiting for its first chunk is reported too
+            let was_inside = cameras_inside.insert(camera, inside).unwrap_or(true);
+            if was_inside && !inside {
+                events
+                    .0
+                    .send(CameraLeftGeneratedArea::<C>::new(camera, chunk_position));
+            } else if !was_inside && inside {
+                events
+                    .1
+                    .send(CameraEnteredGeneratedArea::<C>::new(camera, chunk_position));
+            }
+        }
+
+        for (mut freeze, mut transform, gtf) in frozen.iter_mut() {
+            let ready = is_ready(chunk_of(gtf));
+            match (freeze.frozen_at, ready) {
+                (None, false) => freeze.frozen_at = Some(transform.translation),
+                (Some(frozen_at), false) => transform.translation = frozen_at,
+                (Some(_), true) => freeze.frozen_at = None,
+                (None, true) => {}
+            }
+        }
+    }
+
+    /// Sends `FirstChunksReady` once the chunks around every camera have been meshed
+    pub fn track_first_chunks(
+        mut progress: Local<FirstChunksProgress>,
+        mut ev_chunk_mesh_ready: EventReader<ChunkMeshReady<C>>,
+        mut ev_chunk_generation_failed: EventReader<ChunkGenerationFailed<C>>,
+        mut ev_chunk_will_despawn: EventReader<ChunkWillDespawn<C>>,
+        mut ev_first_chunks_ready: EventWriter<FirstChunksReady<C>>,
+        configuration: Res<C>,
+        camera_info: CameraInfo<C>,
+    ) {
+        if progress.done {
+            return;
+        }
+
+        let chunks_at_cameras: Vec<IVec3> = camera_info
+            .anchors(configuration.as_ref())
+            .iter()
+            .map(|anchor| camera_chunk_neighborhood(anchor.position)[0])
+            .collect();
+        let Some(chunk_at_camera) = chunks_at_cameras.first().copied() else {
+            return;
+        };
+        let now = Instant::now();
+        let started = *progress.started.get_or_insert(now);
+
+        for event in ev_chunk_will_despawn.read() {
+            progress.meshed.remove(&event.chunk_key);
+        }
+        for event in ev_chunk_mesh_ready.read() {
+            progress.first_chunk.get_or_insert(now);
+            progress.meshed.insert(event.chunk_key);
+        }
+        // A chunk that failed to generate won't get any better by waiting for it
+        for event in ev_chunk_generation_failed.read() {
+            progress.meshed.insert(event.chunk_key);
+        }
+
+        let radius = configuration.first_chunks_radius() as i32;
+        let mut first_chunks = HashSet::new();
+        for camera_chunk in chunks_at_cameras {
+            for x in -radius..=radius {
+                for y in -radius..=radius {
+                    for z in -radius..=radius {
+                        let offset = IVec3::new(x, y, z);
+                        if offset.length_squared() > radius.pow(2) {
+                            continue;
+                        }
+                        if !progress.meshed.contains(&(camera_chunk + offset)) {
+                            return;
+                        }
+                        first_chunks.insert(camera_chunk + offset);
+                    }
+                }
+            }
+        }
+        let chunk_count = first_chunks.len();
+
+        progress.done = true;
+        progress.meshed = HashSet::new();
+        ev_first_chunks_ready.send(FirstChunksReady::new(
+            chunk_at_camera,
+            chunk_count,
+            progress.first_chunk.unwrap_or(now) - started,
+            now - started,
+        ));
+    }
+
+    /// Adds newly meshed chunks to `ExploredChunks`, if the resource was added
+    pub fn track_explored_chunks(
+        explored_chunks: Option<ResMut<ExploredChunks<C>>>,
+        mut ev_chunk_mesh_ready: EventReader<ChunkMeshReady<C>>,
+        mut ev_chunk_explored: EventWriter<ChunkExplored<C>>,
+    ) {
+        let Some(mut explored_chunks) = explored_chunks else {
+            ev_chunk_mesh_ready.clear();
+            return;
+        };
+        for event in ev_chunk_mesh_ready.read() {
+            if explored_chunks.insert(event.chunk_key) {
+                ev_chunk_explored.send(ChunkExplored::<C>::new(event.chunk_key, event.entity));
+            }
+        }
+    }
+
+    /// Saves the modified voxels of all chunks when the app exits, if `chunk_persistence` is
+    /// configured
+    pub fn save_modified_chunks(
+        mut ev_app_exit: EventReader<AppExit>,
+        modified_voxels: Res<ModifiedVoxels<C, C::MaterialIndex>>,
+        persisted_chunks: Res<PersistedChunks<C>>,
+        configuration: Res<C>,
+    ) {
+        if ev_app_exit.read().count() == 0 {
+            return;
+        }
+
+        let Some(persistence) = configuration.chunk_persistence() else {
+            return;
+        };
+
+        let chunk_positions: Vec<IVec3> = modified_voxels.read().unwrap().chunks().collect();
+
+        for chunk_position in chunk_positions {
+            // Chunks that were edited without ever being spawned still need their saved
+            // voxels, or they would be lost when overwriting the file
+            load_persisted_chunk(
+                persistence.as_ref(),
+                &persisted_chunks,
+                &modified_voxels,
+                chunk_position,
+            );
+            persistence.save(
+                chunk_position,
+                &chunk_modified_voxels(&modified_voxels, chunk_position),
+            );
+        }
+    }
+
+    /// Spawn a thread for each chunk that has been marked by NeedsRemesh
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub fn remesh_dirty_chunks(
+        mut commands: Commands,
+        mut ev_chunk_will_remesh: EventWriter<ChunkWillRemesh<C>>,
+        dirty_chunks: Query<
+            (
+                &Chunk<C>,
+                Has<PreseedChunk>,
+                Has<GenerateNowChunk>,
+                Has<RemeshOnly>,
+                Option<&ChunkThread<C, C::MaterialIndex>>,
+            ),
+            (With<NeedsRemesh>, Without<HiddenChunk>),
+        >,
+        running_tasks: Query<(), With<ChunkThread<C, C::MaterialIndex>>>,
+        mesh_cache: Res<MeshCache<C>>,
+        persisted_meshes: Res<PersistedMeshes<C>>,
+        persisted_chunks: Res<PersistedChunks<C>>,
+        chunk_timings: Res<ChunkTimings<C>>,
+        generate_now_regions: Res<GenerateNowRegions<C>>,
+        (
+            mut despawned_chunks,
+            lookup_fn_pool,
+            appearance,
+            chunk_map,
+            chunk_map_update_buffer,
+            inserted_chunks,
+        ): (
+            ResMut<DespawnedChunks<C, C::MaterialIndex>>,
+            Res<LookupFnPool<C, C::MaterialIndex>>,
+            Res<AppearanceVersion<C>>,
+            Res<ChunkMap<C, C::MaterialIndex>>,
+            Res<ChunkMapUpdateBuffer<C, C::MaterialIndex>>,
+            Res<InsertedChunks<C, C::MaterialIndex>>,
+        ),
+        modified_voxels: Res<ModifiedVoxels<C, C::MaterialIndex>>,
+        configuration: Res<C>,
+        camera_info: CameraInfo<C>,
+    ) {
+        let thread_pool = AsyncComputeTaskPool::get();
+        let meshing_options = MeshingOptions::from_config(configuration.as_ref());
+        let mesh_cache_scope = MeshCacheScope::from_config(configuration.as_ref())
+            .with_appearance_version(appearance.version);
+        let decorator = configuration.chunk_decorator();
+        let persistence = configuration.chunk_persistence();
+        let collider_strategy = configuration.chunk_collider_strategy();
+        let shadow_lod = configuration.shadow_lod();
+        let chunk_generator = configuration.chunk_generator();
+        let reuse_lookup_fns = configuration.reuse_voxel_lookup_fns();
+        let padding_source = configuration.padding_source();
+
+        // Start the threads for the chunks around the cameras first, so they get done first
+        let chunk_priority = camera_info.chunk_priority(configuration.as_ref());
+        // Chunks that are still running a task keep their `NeedsRemesh`, and get remeshed once,
+        // with the latest voxels, when that task is done. Chunks requested with `generate_now`
+        // skip the task budget, the same as pre-seeded ones.
+        let mut dirty_chunks: Vec<(&Chunk<C>, bool, bool, bool)> = dirty_chunks
+            .iter()
+            .filter(|(.., thread)| thread.is_none_or(|thread| thread.is_finished()))
+            .map(|(chunk, is_preseed, generate_now, remesh_only, _)| {
+                (chunk, is_preseed || generate_now, generate_now, remesh_only)
+            })
+            .collect();
+        dirty_chunks.sort_by_cached_key(|(chunk, is_urgent, ..)| {
+            (!is_urgent, chunk_priority(chunk.position))
+        });
+        let chunk_map_read_lock = chunk_map.get_read_lock();
+
+        let world_bounds = configuration.world_bounds();
+        let mut task_budget = configuration
+            .generation_task_budget()
+            .saturating_sub(running_tasks.iter().len());
+
+        // Chunks requested with `generate_now` are generated and meshed right here, until the
+        // budget of the frame is spent. The rest keep their `NeedsRemesh` for the next frame, which
+        // gets at least one of them done, so that the region keeps making progress.
+        let generate_now_deadline = Instant::now() + generate_now_regions.frame_budget;
+        let mut generated_now = 0;
+
+        for (chunk, is_urgent, generate_now, remesh_only) in dirty_chunks {
+            if generate_now {
+                if generated_now > 0 && Instant::now() >= generate_now_deadline {
+                    continue;
+                }
+                generated_now += 1;
+            } else if !is_urgent {
+                if task_budget == 0 {
+                    break;
+                }
+                task_budget -= 1;
+            }
+
+            let mut lookup: VoxelLookupFn<C::MaterialIndex> = match chunk_generator.clone() {
+                Some(chunk_generator) => generator_lookup(chunk_generator, chunk.position),
+                None if reuse_lookup_fns => {
+                    lookup_fn_pool.take(|| (configuration.voxel_lookup_delegate())(chunk.position))
+                }
+                None => (configuration.voxel_lookup_delegate())(chunk.position),
+            };
+            // Chunks that despawned recently get their voxels back from the cache, and chunks
+            // that only need new textures keep the voxels they have. The voxels around the
+            // chunk, for lighting, are still looked up.
+            let cached_chunk = match (chunk.lod_level, remesh_only) {
+                // Data that has not made it into the chunk map yet is newer than what is there
+                (0, true) => chunk_map_update_buffer.latest(chunk.position).or_else(|| {
+                    ChunkMap::<C, C::MaterialIndex>::get(&chunk.position, &chunk_map_read_lock)
+                }),
+                (0, false) => despawned_chunks.take(chunk.position),
+                _ => None,
+            };
+            if let Some(chunk_data) = cached_chunk {
+                let padded_min = chunk.position * CHUNK_SIZE_I - IVec3::ONE;
+                let mut lookup_around = lookup;
+                lookup = Box::new(move |position| {
+                    let local = position - padded_min;
+                    if local.cmpge(IVec3::ZERO).all()
+                        && local.cmplt(IVec3::splat(PADDED_CHUNK_SIZE as i32)).all()
+                    {
+                        chunk_data.get_voxel(local.as_uvec3())
+                    } else {
+                        lookup_around(position)
+                    }
+                });
+            }
+            // Inserted chunks, this one or the ones around it, take the place of the lookup
+            let inserted_around: Vec<Arc<ChunkRef<C::MaterialIndex>>> = (-1..=1)
+                .flat_map(|x| {
+                    (-1..=1).flat_map(move |y| (-1..=1).map(move |z| IVec3::new(x, y, z)))
+                })
+                .filter_map(|offset| inserted_chunks.get(&(chunk.position + offset)).cloned())
+                .collect();
+            if !inserted_around.is_empty() {
+                let mut lookup_around = lookup;
+                lookup = Box::new(move |position| {
+                    inserted_around
+                        .iter()
+                        .find_map(|inserted| inserted.get(position))
+                        .unwrap_or_else(|| lookup_around(position))
+                });
+            }
+            // The padding comes from the configured source. Voxels modified by the user are still
+            // taken over it, when the chunk is generated.
+            match padding_source {
+                PaddingSource::Delegate => {}
+                PaddingSource::LoadedNeighbors => {
+                    let neighbors: HashMap<IVec3, ChunkData<C::MaterialIndex>> = (-1..=1)
+                        .flat_map(|x| {
+                            (-1..=1).flat_map(move |y| (-1..=1).map(move |z| IVec3::new(x, y, z)))
+                        })
+                        .filter(|offset| *offset != IVec3::ZERO)
+                        .filter_map(|offset| {
+                            let neighbor = chunk.position + offset;
+                            chunk_map_update_buffer
+                                .latest(neighbor)
+                                .or_else(|| {
+                                    ChunkMap::<C, C::MaterialIndex>::get(
+                                        &neighbor,
+                                        &chunk_map_read_lock,
+                                    )
+                                })
+                                // Chunks above LOD level 0 only hold a sample of their voxels
+                                .filter(|chunk_data| chunk_data.is_full_detail())
+                                .map(|chunk_data| (neighbor, chunk_data))
+                        })
+                        .collect();
+                    if !neighbors.is_empty() {
+                        let chunk_position = chunk.position;
+                        let mut lookup_inside = lookup;
+                        lookup = Box::new(move |position| {
+                            let neighbor = position.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+                            match neighbors.get(&neighbor) {
+                                Some(chunk_data) if neighbor != chunk_position => chunk_data
+                                    .get_voxel((position - neighbor * CHUNK_SIZE_I).as_uvec3() + 1),
+                                _ => lookup_inside(position),
+                            }
+                        });
+                    }
+                }
+                PaddingSource::Air => {
+                    let chunk_position = chunk.position;
+                    let mut lookup_inside = lookup;
+                    lookup = Box::new(move |position| {
+                        if position.div_euclid(IVec3::splat(CHUNK_SIZE_I)) == chunk_position {
+                            lookup_inside(position)
+                        } else {
+                            WorldVoxel::Air
+                        }
+                    });
+                }
+            }
+            // The padding of chunks at the edge of the world reaches outside of it
+            let mut voxel_data_fn = move |position: IVec3| {
+                if chunk_in_world_bounds(
+                    world_bounds,
+                    position.div_euclid(IVec3::splat(CHUNK_SIZE_I)),
+                ) {
+                    lookup(position)
+                } else {
+                    WorldVoxel::Air
+                }
+            };
+            let meshing_options = meshing_options.clone();
+            let decorator = decorator.clone();
+            let collider_strategy = collider_strategy.clone();
+            let persistence = persistence.clone();
+            let persisted_chunks = persisted_chunks.clone();
+            let modified_voxels = modified_voxels.clone();
+            let chunk_timings = chunk_timings.clone();
+
+            let mut chunk_task = ChunkTask::<C, C::MaterialIndex>::new(
+                chunk.entity,
+                chunk.position,
+                modified_voxels.clone(),
+            );
+            chunk_task.lod_level = chunk.lod_level;
+
+            let mesh_map = Arc::new(mesh_cache.get_map());
+            let persisted_meshes = persisted_meshes.meshes.clone();
+            let failed_modified_voxels = modified_voxels.clone();
+            let (chunk_entity, chunk_position, lod_level) =
+                (chunk.entity, chunk.position, chunk.lod_level);
+            let run = move || {
+                // A panicking delegate or mapper only fails this chunk, instead of taking down the
+                // task pool
+                panic::catch_unwind(AssertUnwindSafe(move || {
+                    // The padding of the chunk overlaps its neighbors, so load their saved voxels
+                    // too
+                    if let Some(persistence) = persistence {
+                        for x in -1..=1 {
+                            for y in -1..=1 {
+                                for z in -1..=1 {
+                                    load_persisted_chunk(
+                                        persistence.as_ref(),
+                                        &persisted_chunks,
+                                        &modified_voxels,
+                                        chunk_task.position + IVec3::new(x, y, z),
+                                    );
+                                }
+                            }
+                        }
+                    }
+
+                    let started = Instant::now();
+                    chunk_task.generate(&mut voxel_data_fn);
+                    chunk_timings.record_generation(started.elapsed());
+
+                    // Decorations are only placed at full detail, and the ones placed before are
+                    // removed when the chunk moves to a lower level
+                    if let Some(decorator) = decorator {
+                        if chunk_task.lod_level == 0 {
+                            chunk_task.decorate(&decorator);
+                        } else {
+                            chunk_task.decorations = Some(Vec::new());
+                        }
+                    }
+                    chunk_task.build_collider(&collider_strategy);
+                    chunk_task.mesh_cache_key =
+                        mesh_cache_scope.key(chunk_task.voxels_hash(), chunk_task.position);
+
+                    // No need to mesh if the chunk is empty, or full and all faces between solid
+                    // voxels get culled
+                    if chunk_task.is_empty()
+                        || (chunk_task.is_full() && !meshing_options.meshes_full_chunks())
+                    {
+                        return chunk_task;
+                    }
+
+                    // Lit meshes depend on the voxels around the chunk too, so they are cached by
+                    // their light as well
+                    if meshing_options.lighting.is_some() {
+                        chunk_task.light(&meshing_options, voxel_data_fn);
+                        chunk_task.mesh_cache_key =
+                            mesh_cache_scope.key(chunk_task.mesh_hash(), chunk_task.position);
+                    }
+
+                    // Also no need to mesh if a matching mesh is already cached. Group meshes are
+                    // not cached, and full chunks are told apart by whether they got a mesh.
+                    let mesh_cache_hit = meshing_options.material_group.is_none()
+                        && !chunk_task.is_full()
+                        && (mesh_map
+                            .read()
+                            .unwrap()
+                            .contains_key(&chunk_task.mesh_cache_key)
+                            || persisted_meshes.contains_key(&chunk_task.mesh_cache_key));
+                    if !mesh_cache_hit {
+                        let started = Instant::now();
+                        chunk_task.mesh(&meshing_options);
+                        chunk_timings.record_meshing(started.elapsed());
+                    }
+
+                    if let Some(shadow_lod) = shadow_lod {
+                        chunk_task.mesh_shadow(&meshing_options, shadow_lod.lod_level);
+                    }
+
+                    chunk_task
+                }))
+                .unwrap_or_else(|panic| {
+                    let mut failed_task = ChunkTask::<C, C::MaterialIndex>::new(
+                        chunk_entity,
+                        chunk_position,
+                        failed_modified_voxels,
+                    );
+                    failed_task.lod_level = lod_level;
+                    failed_task.error = Some(panic_message(panic.as_ref()));
+                    failed_task
+                })
+            };
+            let job = if generate_now {
+                ChunkJob::Done(Some(Box::new(run())))
+            } else {
+                ChunkJob::Running(thread_pool.spawn(async move { run() }))
+            };
+
+            commands
+                .entity(chunk.entity)
+                .try_insert(ChunkThread::<C, C::MaterialIndex>::new(job, chunk.position))
+                .remove::<(NeedsRemesh, RemeshOnly, NeedsRelight, RelightThread<C>)>();
+
+            ev_chunk_will_remesh.send(ChunkWillRemesh::<C>::new(chunk.position, chunk.entity));
+        }
+    }
+
+    /// Shows the configured placeholder for chunks that started generating without a mesh to show
+    /// in the meantime. `spawn_meshes` despawns it when the chunk task finishes.
+    #[allow(clippy::type_complexity)]
+    pub fn spawn_chunk_placeholders(
+        mut commands: Commands,
+        new_tasks: Query<
+            (Entity, &Chunk<C>, Has<HiddenChunk>),
+            (Added<ChunkThread<C, C::MaterialIndex>>, Without<MeshRef>),
+        >,
+        mut mesh_assets: ResMut<Assets<Mesh>>,
+        configuration: Res<C>,
+    ) {
+        let Some(ChunkPlaceholder::FlatBox {
+            color,
+            surface_height,
+        }) = configuration.chunk_placeholder()
+        else {
+            return;
+        };
+
+        for (entity, chunk, is_hidden) in new_tasks.iter() {
+            let chunk_min = chunk.position * CHUNK_SIZE_I;
+            let column = chunk_min.xz() + IVec2::splat(CHUNK_SIZE_I / 2);
+            let Some(height) = surface_height(column) else {
+                continue;
+            };
+            let height = height - chunk_min.y as f32;
+            if !(0.0..CHUNK_SIZE_F).contains(&height) {
+                continue;
+            }
+
+            commands
+                .entity(entity)
+                .try_insert((
+                    VisibilityBundle {
+                        visibility: if is_hidden {
+                            Visibility::Hidden
+                        } else {
+                            Visibility::Inherited
+                        },
+                        ..default()
+                    },
+                    GlobalTransform::default(),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        ChunkLoadingPlaceholder::<C>::new(chunk.position),
+                        MeshRef(Arc::new(
+                            mesh_assets.add(meshing::loading_placeholder_mesh(height, color)),
+                        )),
+                        NeedsMaterial::<C>(PhantomData),
+                        SpatialBundle::default(),
+                    ));
+                });
+        }
+    }
+
+    /// Inserts new meshes for chunks that have just finished remeshing
+    #[allow(clippy::type_complexity)]
+    pub fn spawn_meshes(
+        mut commands: Commands,
+        mut chunking_threads: Query<(
+            Entity,
+            &mut ChunkThread<C, C::MaterialIndex>,
+            &mut Chunk<C>,
+            &Transform,
+            Option<&Children>,
+            Has<PreseedChunk>,
+            Has<GenerateNowChunk>,
+            Has<HiddenChunk>,
+            Has<MeshRef>,
+            Has<NeedsRemesh>,
+        )>,
+        child_entities: (
+            Query<(), Or<(With<ChunkDecoration<C>>, With<ChunkDecorationInstances<C>>)>>,
+            Query<(), With<ChunkGroupMesh<C>>>,
+            Query<(), With<ChunkShadowMesh<C>>>,
+            Query<
+                (),
+                Or<(
+                    With<ChunkErrorPlaceholder<C>>,
+                    With<ChunkLoadingPlaceholder<C>>,
+                )>,
+            >,
+        ),
+        mut mesh_assets: ResMut<Assets<Mesh>>,
+        events: (
+            EventWriter<ChunkMeshReady<C>>,
+            EventWriter<ChunkGenerationFailed<C>>,
+        ),
+        buffers: (
+            ResMut<ChunkMapUpdateBuffer<C, C::MaterialIndex>>,
+            ResMut<MeshCacheInsertBuffer<C>>,
+        ),
+        res: (
+            Res<MeshCache<C>>,
+            Res<PersistedMeshes<C>>,
+            Res<LoadingTexture>,
+            Res<ChunkTimings<C>>,
+            Res<C>,
+            CameraInfo<C>,
+        ),
+    ) {
+        let (
+            mesh_cache,
+            persisted_meshes,
+            loading_texture,
+            chunk_timings,
+            configuration,
+            camera_info,
+        ) = res;
+        let decoration_instancing = configuration.decoration_instancing();
+        let mesh_asset_usage = configuration.chunk_mesh_asset_usage();
+        let retain_mesh_data = mesh_asset_usage.contains(RenderAssetUsages::MAIN_WORLD);
+        let spawn_transition = configuration.chunk_spawn_transition();
+
+        if !loading_texture.is_loaded {
+            return;
+        }
+
+        let (mut chunk_map_update_buffer, mut mesh_cache_insert_buffer) = buffers;
+        let (decoration_entities, group_mesh_entities, shadow_mesh_entities, placeholder_entities) =
+            child_entities;
+        let (mut ev_chunk_mesh_ready, mut ev_chunk_generation_failed) = events;
+        let shadow_lod = configuration.shadow_lod();
+
+        // Insert the chunks closest to the cameras first, so the budget goes to them
+        let insert_priority = camera_info.insert_priority(configuration.as_ref());
+        let mut chunk_order: Vec<(Entity, bool, bool, (bool, u32))> = chunking_threads
+            .iter()
+            .map(|(entity, _, chunk, _, _, is_preseed, generate_now, ..)| {
+                (
+                    entity,
+                    is_preseed,
+                    generate_now,
+                    insert_priority(chunk.position),
+                )
+            })
+            .collect();
+        chunk_order.sort_by_key(|(_, is_preseed, generate_now, priority)| {
+            (!is_preseed, !generate_now, *priority)
+        });
+        let mut insert_budget = configuration.max_mesh_inserts_per_frame();
+        let generation_timeout = configuration.chunk_generation_timeout();
+
+        for (entity, ..) in chunk_order {
+            let Ok((
+                entity,
+                mut thread,
+                chunk,
+                transform,
+                children,
+                is_preseed,
+                generate_now,
+                is_hidden,
+                has_mesh,
+                is_stale,
+            )) = chunking_threads.get_mut(entity)
+            else {
+                continue;
+            };
+
+            if !is_preseed && !generate_now && insert_budget == 0 {
+                break;
+            }
+
+            // Pre-seeded chunks are waited for, so that they are ready for the first frame
+            let mut thread_result = match (is_preseed, generation_timeout) {
+                (true, None) => Some(thread.block()),
+                (true, Some(timeout)) => loop {
+                    let thread_result = thread.poll();
+                    if thread_result.is_some() || thread.1.elapsed() >= timeout {
+                        break thread_result;
+                    }
+                    // Sleeping would panic on wasm, so keep polling
+                    std::thread::yield_now();
+                },
+                (false, _) => thread.poll(),
+            };
+
+            let timed_out = thread_result.is_none()
+                && generation_timeout.is_some_and(|timeout| thread.1.elapsed() >= timeout);
+            if thread_result.is_none() && !timed_out {
+                continue;
+            }
+
+            // The voxels changed while the task ran. Keep showing the current mesh until the task
+            // for the latest voxels is done, instead of inserting one that is already outdated.
+            if is_stale && has_mesh && thread_result.is_some() {
+                commands
+                    .entity(entity)
+                    .remove::<ChunkThread<C, C::MaterialIndex>>();
+                continue;
+            }
+
+            if !is_preseed && !generate_now {
+                insert_budget -= 1;
+            }
+            let task_started = thread.1;
+
+            for child in children.into_iter().flatten() {
+                if placeholder_entities.contains(*child) {
+                    commands.entity(*child).despawn_recursive();
+                }
+            }
+
+            // The task of a chunk that timed out is dropped along with its `ChunkThread`
+            let error = match &mut thread_result {
+                Some(chunk_task) => chunk_task.error.take(),
+                None => Some(format!(
+                    "timed out after {:?}",
+                    generation_timeout.unwrap_or_default()
+                )),
+            };
+
+            if let Some(message) = error {
+                error!("Chunk {} failed to generate: {}", chunk.position, message);
+
+                if cfg!(debug_assertions) {
+                    commands
+                        .entity(entity)
+                        .try_insert((
+                            VisibilityBundle {
+                                visibility: if is_hidden {
+                                    Visibility::Hidden
+                                } else {
+                                    Visibility::Inherited
+                                },
+                                ..default()
+                            },
+                            GlobalTransform::default(),
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                ChunkErrorPlaceholder::<C>::new(chunk.position),
+                                MeshRef(Arc::new(
+                                    mesh_assets.add(meshing::error_placeholder_mesh()),
+                                )),
+                                NeedsMaterial::<C>(PhantomData),
+                                SpatialBundle::default(),
+                            ));
+                        });
+                }
+
+                commands
+                    .entity(chunk.entity)
+                    .remove::<(ChunkThread<C, C::MaterialIndex>, PreseedChunk)>();
+
+                ev_chunk_generation_failed.send(ChunkGenerationFailed::<C>::new(
+                    chunk.position,
+                    entity,
+                    message,
+                ));
+                continue;
+            }
+
+            let mut chunk_task = thread_result.unwrap();
+
+            if let Some(decorations) = chunk_task.decorations.take() {
+                for child in children.into_iter().flatten() {
+                    if decoration_entities.contains(*child) {
+                        commands.entity(*child).despawn_recursive();
+                    }
+                }
+
+                if !decorations.is_empty() {
+                    let local_decorations = decorations.into_iter().map(|decoration| {
+                        // The chunk transform may be animated, so go by the chunk position
+                        let mut local_transform = decoration.transform;
+                        local_transform.translation -= chunk_translation(chunk.position);
+                        (decoration.kind, local_transform)
+                    });
+
+                    commands
+                        .entity(entity)
+                        .try_insert((
+                            VisibilityBundle {
+                                visibility: if is_hidden {
+                                    Visibility::Hidden
+                                } else {
+                                    Visibility::Inherited
+                                },
+                                ..default()
+                            },
+                            GlobalTransform::default(),
+                        ))
+                        .with_children(|parent| {
+                            if decoration_instancing {
+                                let mut instances: HashMap<u32, Vec<Transform>> = HashMap::new();
+                                for (kind, local_transform) in local_decorations {
+                                    instances.entry(kind).or_default().push(local_transform);
+                                }
+                                for (kind, transforms) in instances {
+                                    parent.spawn((
+                                        ChunkDecorationInstances::<C>::new(
+                                            kind,
+                                            chunk.position,
+                                            transforms,
+                                        ),
+                                        SpatialBundle::default(),
+                                    ));
+                                }
+                            } else {
+                                for (kind, local_transform) in local_decorations {
+                                    parent.spawn((
+                                        ChunkDecoration::<C>::new(kind, chunk.position),
+                                        SpatialBundle::from_transform(local_transform),
+                                    ));
+                                }
+                            }
+                        });
+                }
+            }
+
+            if let Some(collider) = chunk_task.collider.take() {
+                commands
+                    .entity(entity)
+                    .try_insert(ChunkCollider::<C>::new(collider));
+            } else {
+                commands.entity(entity).remove::<ChunkCollider<C>>();
+            }
+
+            if !chunk_task.is_empty() {
+                if !chunk_task.is_full() || chunk_task.mesh.is_some() {
+                    let mesh_handle = {
+                        if let Some(mesh_handle) = mesh_cache.get(&chunk_task.mesh_cache_key) {
+                            mesh_handle
+                        } else {
+                            let key = chunk_task.mesh_cache_key;
+                            let Some(mut mesh) = chunk_task
+                                .mesh
+                                .or_else(|| persisted_meshes.meshes.get(&key).cloned())
+                            else {
+                                commands
+                                    .entity(chunk.entity)
+                                    .try_insert(NeedsRemesh)
+                                    .remove::<ChunkThread<C, C::MaterialIndex>>();
+                                continue;
+                            };
+                            mesh.asset_usage = mesh_asset_usage;
+                            let mesh_ref = Arc::new(mesh_assets.add(mesh));
+                            mesh_cache_insert_buffer.push((key, mesh_ref.clone()));
+                            mesh_ref
+                        }
+                    };
+
+                    let mut chunk_commands = commands.entity(entity);
+                    chunk_commands.try_insert((
+                        *transform,
+                        MeshRef(mesh_handle),
+                        NeedsMaterial::<C>(PhantomData),
+                    ));
+
+                    if spawn_transition.is_some() && !has_mesh && !is_preseed {
+                        chunk_commands.try_insert(SpawnTransition::<C>::default());
+                    }
+
+                    // Use the bounds calculated on the chunk thread. Meshes that came from the
+                    // cache get theirs calculated by bevy, unless their CPU side data is gone,
+                    // in which case the bounds of the padded chunk are used instead.
+                    match chunk_task.mesh_aabb {
+                        Some(aabb) => chunk_commands.try_insert(aabb),
+                        None if retain_mesh_data => chunk_commands.remove::<Aabb>(),
+                        None => chunk_commands.try_insert(Aabb::from_min_max(
+                            Vec3::ZERO,
+                            Vec3::splat(PADDED_CHUNK_SIZE as f32),
+                        )),
+                    };
+                }
+
+                chunk_map_update_buffer.push((
+                    chunk.position,
+                    chunk_task.chunk_data,
+                    ChunkWillSpawn::<C>::new(chunk_task.position, entity),
+                ));
+            } else {
+                commands
+                    .entity(entity)
+                    .remove::<Handle<Mesh>>()
+                    .remove::<MeshRef>();
+            }
+
+            for child in children.into_iter().flatten() {
+                if group_mesh_entities.contains(*child) {
+                    commands.entity(*child).despawn_recursive();
+                }
+            }
+
+            if !chunk_task.group_meshes.is_empty() {
+                commands
+                    .entity(entity)
+                    .try_insert((
+                        VisibilityBundle {
+                            visibility: if is_hidden {
+                                Visibility::Hidden
+                            } else {
+                                Visibility::Inherited
+                            },
+                            ..default()
+                        },
+                        GlobalTransform::default(),
+                    ))
+                    .with_children(|parent| {
+                        for (group, mut mesh) in chunk_task.group_meshes {
+                            mesh.asset_usage = mesh_asset_usage;
+                            let mut group_commands = parent.spawn((
+                                ChunkGroupMesh::<C>::new(group, chunk.position),
+                                MeshRef(Arc::new(mesh_assets.add(mesh))),
+                                NeedsMaterial::<C>(PhantomData),
+                                SpatialBundle::default(),
+                            ));
+                            if !retain_mesh_data {
+                                group_commands.insert(Aabb::from_min_max(
+                                    Vec3::ZERO,
+                                    Vec3::splat(PADDED_CHUNK_SIZE as f32),
+                                ));
+                            }
+                        }
+                    });
+            }
+
+            for child in children.into_iter().flatten() {
+                if shadow_mesh_entities.contains(*child) {
+                    commands.entity(*child).despawn_recursive();
+                }
+            }
+
+            // The full mesh only casts shadows when the chunk didn't get a shadow mesh
+            match (chunk_task.shadow_mesh, shadow_lod) {
+                (Some(mut shadow_mesh), Some(shadow_lod)) => {
+                    shadow_mesh.asset_usage = mesh_asset_usage;
+                    commands
+                        .entity(entity)
+                        .try_insert((
+                            NotShadowCaster,
+                            VisibilityBundle {
+                                visibility: if is_hidden {
+                                    Visibility::Hidden
+                                } else {
+                                    Visibility::Inherited
+                                },
+                                ..default()
+                            },
+                            GlobalTransform::default(),
+                        ))
+                        .with_children(|parent| {
+                            let mut shadow_commands = parent.spawn((
+                                ChunkShadowMesh::<C>::new(shadow_lod.lod_level, chunk.position),
+                                MeshRef(Arc::new(mesh_assets.add(shadow_mesh))),
+                                NeedsMaterial::<C>(PhantomData),
+                                SpatialBundle::default(),
+                                RenderLayers::layer(shadow_lod.render_layer),
+                            ));
+                            if !retain_mesh_data {
+                                shadow_commands.insert(Aabb::from_min_max(
+                                    Vec3::ZERO,
+                                    Vec3::splat(PADDED_CHUNK_SIZE as f32),
+                                ));
+                            }
+                        });
+                }
+                _ => {
+                    commands.entity(entity).remove::<NotShadowCaster>();
+                }
+            }
+
+            commands
+                .entity(chunk.entity)
+                .remove::<(ChunkThread<C, C::MaterialIndex>, PreseedChunk)>();
+
+            chunk_timings.record_mesh_ready(chunk.position, task_started);
+            ev_chunk_mesh_ready.send(ChunkMeshReady::<C>::new(chunk.position, entity));
+        }
+    }
+
+    /// Start a task for each chunk marked by `NeedsRelight`, which bakes the light around the
+    /// chunk into copies of its meshes. The faces of these chunks didn't change, so this is much
+    /// cheaper than meshing them again. However many edits reached a chunk, it is relit once.
+    /// Chunks with meshes that have no CPU side data to copy are remeshed instead.
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub fn relight_chunks(
+        mut commands: Commands,
+        dirty_chunks: Query<
+            (Entity, &Chunk<C>, Option<&MeshRef>, Option<&Children>),
+            (
+                With<NeedsRelight>,
+                Without<NeedsRemesh>,
+                Without<ChunkThread<C, C::MaterialIndex>>,
+                Without<RelightThread<C>>,
+                Without<HiddenChunk>,
+            ),
+        >,
+        group_meshes: Query<&MeshRef, With<ChunkGroupMesh<C>>>,
+        running_tasks: Query<
+            (),
+            Or<(
+                With<ChunkThread<C, C::MaterialIndex>>,
+                With<RelightThread<C>>,
+            )>,
+        >,
+        mesh_assets: Res<Assets<Mesh>>,
+        (chunk_map, chunk_map_update_buffer, inserted_chunks, modified_voxels): (
+            Res<ChunkMap<C, C::MaterialIndex>>,
+            Res<ChunkMapUpdateBuffer<C, C::MaterialIndex>>,
+            Res<InsertedChunks<C, C::MaterialIndex>>,
+            Res<ModifiedVoxels<C, C::MaterialIndex>>,
+        ),
+        configuration: Res<C>,
+    ) {
+        let meshing_options = MeshingOptions::from_config(configuration.as_ref());
+        let (Some(lighting), Some(emission)) = (
+            meshing_options.lighting,
+            meshing_options.voxel_emission.clone(),
+        ) else {
+            return;
+        };
+        let thread_pool = AsyncComputeTaskPool::get();
+        let chunk_generator = configuration.chunk_generator();
+        let world_bounds = configuration.world_bounds();
+        let chunk_map_read_lock = chunk_map.get_read_lock();
+        let latest_chunk_data = |position: IVec3| {
+            chunk_map_update_buffer
+                .latest(position)
+                .or_else(|| ChunkMap::<C, C::MaterialIndex>::get(&position, &chunk_map_read_lock))
+        };
+        let mut task_budget = configuration
+            .generation_task_budget()
+            .saturating_sub(running_tasks.iter().len());
+
+        for (entity, chunk, mesh_ref, children) in dirty_chunks.iter() {
+            // Chunks without a mesh have no light to show, and chunks above LOD level 0 are not
+            // lit
+            let chunk_data = latest_chunk_data(chunk.position);
+            let (Some(mesh_ref), Some(chunk_data), 0) = (mesh_ref, chunk_data, chunk.lod_level)
+            else {
+                commands.entity(entity).remove::<NeedsRelight>();
+                continue;
+            };
+            if task_budget == 0 {
+                break;
+            }
+            task_budget -= 1;
+
+            let group_mesh_refs = children.into_iter().flatten().filter_map(|child| {
+                group_meshes
+                    .get(*child)
+                    .ok()
+                    .map(|mesh_ref| (*child, mesh_ref))
+            });
+            let meshes: Option<Vec<(Entity, Mesh)>> = std::iter::once((entity, mesh_ref))
+                .chain(group_mesh_refs)
+                .map(|(mesh_entity, mesh_ref)| {
+                    mesh_assets
+                        .get(mesh_ref.0.as_ref())
+                        .map(|mesh| (mesh_entity, mesh.clone()))
+                })
+                .collect();
+            let Some(mut meshes) = meshes else {
+                commands
+                    .entity(entity)
+                    .try_insert((NeedsRemesh, RemeshOnly))
+                    .remove::<NeedsRelight>();
+                continue;
+            };
+
+            // The voxels around the chunk come from the modified voxels, then from the inserted
+            // and loaded chunks around it, and only then from the generator or lookup delegate.
+            // Chunks above LOD level 0 don't hold every voxel, so they are looked up instead.
+            let chunk_position = chunk.position;
+            let padded_min = chunk_position * CHUNK_SIZE_I - IVec3::ONE;
+            let (lit_min, lit_max) = lit_bounds(chunk_position, lighting);
+            let modified = modified_voxels_within(&modified_voxels, lit_min, lit_max);
+            let (min_neighbor, max_neighbor) = (
+                lit_min.div_euclid(IVec3::splat(CHUNK_SIZE_I)),
+                lit_max.div_euclid(IVec3::splat(CHUNK_SIZE_I)),
+            );
+            let neighborhood = (min_neighbor.x..=max_neighbor.x).flat_map(move |x| {
+                (min_neighbor.y..=max_neighbor.y).flat_map(move |y| {
+                    (min_neighbor.z..=max_neighbor.z).map(move |z| IVec3::new(x, y, z))
+                })
+            });
+            let inserted_around: Vec<Arc<ChunkRef<C::MaterialIndex>>> = neighborhood
+                .clone()
+                .filter_map(|neighbor| inserted_chunks.get(&neighbor).cloned())
+                .collect();
+            let neighbors: HashMap<IVec3, ChunkData<C::MaterialIndex>> = neighborhood
+                .filter(|neighbor| *neighbor != chunk_position)
+                .filter_map(|neighbor| {
+                    latest_chunk_data(neighbor)
+                        .filter(ChunkData::is_full_detail)
+                        .map(|chunk_data| (neighbor, chunk_data))
+                })
+                .collect();
+            let mut lookup = match chunk_generator.clone() {
+                Some(chunk_generator) => generator_lookup(chunk_generator, chunk_position),
+                None => (configuration.voxel_lookup_delegate())(chunk_position),
+            };
+            let meshing_options = meshing_options.clone();
+            let emission = emission.clone();
+
+            let thread = thread_pool.spawn(async move {
+                // A panicking delegate leaves the chunk to be remeshed, which reports the failure
+                panic::catch_unwind(AssertUnwindSafe(move || {
+                    let voxel_at = |position: IVec3| {
+                        let neighbor = position.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+                        if let Some(voxel) = modified.get(&position) {
+                            *voxel
+                        } else if neighbor == chunk_position {
+                            chunk_data.get_voxel((position - padded_min).as_uvec3())
+                        } else if let Some(voxel) = inserted_around
+                            .iter()
+                            .find_map(|inserted| inserted.get(position))
+                        {
+                            voxel
+                        } else if let Some(chunk_data) = neighbors.get(&neighbor) {
+                            chunk_data
+                                .get_voxel((position - neighbor * CHUNK_SIZE_I).as_uvec3() + 1)
+                        } else if chunk_in_world_bounds(world_bounds, neighbor) {
+                            lookup(position)
+                        } else {
+                            WorldVoxel::Air
+                        }
+                    };
+                    let light = ChunkLight::compute(
+                        chunk_position,
+                        voxel_at,
+                        &meshing_options,
+                        emission.as_ref(),
+                        lighting,
+                    );
+                    for (_, mesh) in &mut meshes {
+                        light.apply(mesh);
+                    }
+                    meshes
+                }))
+                .ok()
+            });
+
+            commands
+                .entity(entity)
+                .try_insert(RelightThread::<C>::new(thread))
+                .remove::<NeedsRelight>();
+        }
+    }
+
+    /// Swap the relit meshes in, once the `RelightThread` of a chunk is done
+    pub fn spawn_relit_meshes(
+        mut commands: Commands,
+        mut relight_threads: Query<(Entity, &mut RelightThread<C>)>,
+        mut mesh_assets: ResMut<Assets<Mesh>>,
+        configuration: Res<C>,
+    ) {
+        let mesh_asset_usage = configuration.chunk_mesh_asset_usage();
+
+        for (entity, mut thread) in relight_threads.iter_mut() {
+            let Some(thread_result) = future::block_on(future::poll_once(&mut thread.0)) else {
+                continue;
+            };
+            commands.entity(entity).remove::<RelightThread<C>>();
+
+            let Some(meshes) = thread_result else {
+                commands
+                    .entity(entity)
+                    .try_insert((NeedsRemesh, RemeshOnly));
+                continue;
+            };
+            for (mesh_entity, mut mesh) in meshes {
+                mesh.asset_usage = mesh_asset_usage;
+                let mesh_handle = mesh_assets.add(mesh);
+                if let Some(mut mesh_commands) = commands.get_entity(mesh_entity) {
+                    mesh_commands.try_insert((mesh_handle.clone(), MeshRef(Arc::new(mesh_handle))));
+                }
+            }
+        }
+    }
+
+    /// Advances the spawn transitions of chunks, and moves rising chunks into place. Transitions
+    /// are removed the frame after they finish, so that `fade_chunk_spawns` sees them finish.
+    pub fn animate_chunk_spawns(
         mut commands: Commands,
-        all_chunks: Query<(&Chunk<C>, Option<&ViewVisibility>)>,
+        mut transitions: Query<(Entity, &Chunk<C>, &mut SpawnTransition<C>, &mut Transform)>,
         configuration: Res<C>,
-        camera_info: CameraInfo<C>,
-        mut ev_chunk_will_despawn: EventWriter<ChunkWillDespawn<C>>,
+        time: Res<Time>,
     ) {
-        let spawning_distance = configuration.spawning_distance() as i32;
-        let spawning_distance_squared = spawning_distance.pow(2);
-
-        let (_, cam_gtf) = camera_info.get_single().unwrap();
-        let cam_pos = cam_gtf.translation().as_ivec3();
-
-        let chunk_at_camera = cam_pos / CHUNK_SIZE_I;
-
-        let chunks_to_remove = {
-            let mut remove = Vec::with_capacity(1000);
-            for (chunk, view_visibility) in all_chunks.iter() {
-                let should_be_culled = {
-                    match configuration.chunk_despawn_strategy() {
-                        ChunkDespawnStrategy::FarAway => false,
-                        ChunkDespawnStrategy::FarAwayOrOutOfView => {
-                            if let Some(visibility) = view_visibility {
-                                !visibility.get()
-                            } else {
-                                false
-                            }
-                        }
-                    }
-                };
-                let dist_squared = chunk.position.distance_squared(chunk_at_camera);
-                if should_be_culled || dist_squared > spawning_distance_squared + 1 {
-                    remove.push(chunk);
-                }
-            }
-            remove
+        let Some(spawn_transition) = configuration.chunk_spawn_transition() else {
+            return;
         };
+        let duration = spawn_transition.duration().as_secs_f32();
 
-        for chunk in chunks_to_remove {
-            commands.entity(chunk.entity).try_insert(NeedsDespawn);
+        for (entity, chunk, mut transition, mut transform) in transitions.iter_mut() {
+            if transition.progress >= 1.0 {
+                commands.entity(entity).remove::<SpawnTransition<C>>();
+                continue;
+            }
 
-            ev_chunk_will_despawn.send(ChunkWillDespawn::<C>::new(chunk.position, chunk.entity));
-        }
-    }
+            transition.progress = if duration > 0.0 {
+                (transition.progress + time.delta_seconds() / duration).min(1.0)
+            } else {
+                1.0
+            };
 
-    /// Despawns chunks that have been tagged for despawning
-    pub fn despawn_retired_chunks(
-        mut commands: Commands,
-        mut chunk_map_remove_buffer: ResMut<ChunkMapRemoveBuffer<C>>,
-        chunk_map: Res<ChunkMap<C, C::MaterialIndex>>,
-        retired_chunks: Query<(Entity, &Chunk<C>), With<NeedsDespawn>>,
-    ) {
-        let read_lock = chunk_map.get_read_lock();
-        for (entity, chunk) in retired_chunks.iter() {
-            if ChunkMap::<C, C::MaterialIndex>::contains_chunk(&chunk.position, &read_lock) {
-                commands.entity(entity).despawn_recursive();
-                chunk_map_remove_buffer.push(chunk.position);
+            if let ChunkSpawnTransition::RiseFromBelow { depth, .. } = spawn_transition {
+                let t = transition.progress;
+                let eased = t * t * (3.0 - 2.0 * t);
+                transform.translation =
+                    chunk_translation(chunk.position) - Vec3::Y * depth * (1.0 - eased);
             }
         }
     }
 
-    /// Spawn a thread for each chunk that has been marked by NeedsRemesh
-    #[allow(clippy::too_many_arguments)]
-    pub fn remesh_dirty_chunks(
-        mut commands: Commands,
-        mut ev_chunk_will_remesh: EventWriter<ChunkWillRemesh<C>>,
-        dirty_chunks: Query<&Chunk<C>, With<NeedsRemesh>>,
-        mesh_cache: Res<MeshCache<C>>,
-        modified_voxels: Res<ModifiedVoxels<C, C::MaterialIndex>>,
+    /// Fades in chunks with a `FadeIn` spawn transition. Each fading chunk gets its own copy of
+    /// the voxel material, which is swapped back for the shared one when the fade finishes.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn fade_chunk_spawns(
+        mut transitions: Query<(
+            &SpawnTransition<C>,
+            &mut Handle<ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>>,
+        )>,
+        material_handle: Res<
+            WorldMaterialHandle<C, ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>>,
+        >,
+        mut materials: ResMut<Assets<ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>>>,
         configuration: Res<C>,
     ) {
-        let thread_pool = AsyncComputeTaskPool::get();
-
-        for chunk in dirty_chunks.iter() {
-            let voxel_data_fn = (configuration.voxel_lookup_delegate())(chunk.position);
-            let texture_index_mapper = configuration.texture_index_mapper().clone();
-
-            let mut chunk_task = ChunkTask::<C, C::MaterialIndex>::new(
-                chunk.entity,
-                chunk.position,
-                modified_voxels.clone(),
-            );
+        let Some(ChunkSpawnTransition::FadeIn { .. }) = configuration.chunk_spawn_transition()
+        else {
+            return;
+        };
 
-            let mesh_map = Arc::new(mesh_cache.get_map());
-            let thread = thread_pool.spawn(async move {
-                chunk_task.generate(voxel_data_fn);
+        for (transition, mut handle) in transitions.iter_mut() {
+            if transition.progress >= 1.0 {
+                *handle = material_handle.handle.clone();
+                continue;
+            }
 
-                // No need to mesh if the chunk is empty or full
-                if chunk_task.is_empty() || chunk_task.is_full() {
-                    return chunk_task;
-                }
+            if *handle == material_handle.handle {
+                let Some(shared) = materials.get(&material_handle.handle) else {
+                    continue;
+                };
+                let mut faded = shared.clone();
+                faded.base.alpha_mode = AlphaMode::Blend;
+                *handle = materials.add(faded);
+            }
 
-                // Also no need to mesh if a matching mesh is already cached
-                let mesh_cache_hit = mesh_map
-                    .read()
-                    .unwrap()
-                    .contains_key(&chunk_task.voxels_hash());
-                if !mesh_cache_hit {
-                    chunk_task.mesh(texture_index_mapper);
-                }
+            if let Some(faded) = materials.get_mut(handle.id()) {
+                faded.base.base_color.set_alpha(transition.progress);
+            }
+        }
+    }
 
-                chunk_task
-            });
+    /// Gives the group meshes of chunks a copy of the voxel material with the alpha mode of their
+    /// material group. The copies are shared between all chunks. Decal meshes are drawn like the
+    /// cutout group, so that the transparent parts of decal textures are not drawn.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn assign_group_materials(
+        mut group_meshes: Query<
+            (
+                Option<&ChunkGroupMesh<C>>,
+                &mut Handle<ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>>,
+            ),
+            (
+                Or<(With<ChunkGroupMesh<C>>, With<ChunkDecalMesh<C>>)>,
+                Changed<Handle<ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>>>,
+            ),
+        >,
+        material_handle: Res<
+            WorldMaterialHandle<C, ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>>,
+        >,
+        mut materials: ResMut<Assets<ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>>>,
+        mut group_materials: Local<
+            HashMap<
+                MaterialGroup,
+                Handle<ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>>,
+            >,
+        >,
+    ) {
+        for (group_mesh, mut handle) in group_meshes.iter_mut() {
+            let group = group_mesh.map_or(MaterialGroup::Cutout, |group_mesh| group_mesh.group);
+            let alpha_mode = group.alpha_mode();
+            if *handle != material_handle.handle || alpha_mode == AlphaMode::Opaque {
+                continue;
+            }
 
-            commands
-                .entity(chunk.entity)
-                .try_insert(ChunkThread::<C, C::MaterialIndex>::new(
-                    thread,
-                    chunk.position,
-                ))
-                .remove::<NeedsRemesh>();
+            if !group_materials.contains_key(&group) {
+                let Some(shared) = materials.get(&material_handle.handle) else {
+                    continue;
+                };
+                let mut group_material = shared.clone();
+                group_material.base.alpha_mode = alpha_mode;
+                group_materials.insert(group, materials.add(group_material));
+            }
 
-            ev_chunk_will_remesh.send(ChunkWillRemesh::<C>::new(chunk.position, chunk.entity));
+            *handle = group_materials[&group].clone();
         }
     }
 
-    /// Inserts new meshes for chunks that have just finished remeshing
+    /// Gives each instanced decoration entity the shared prop mesh of its kind, bounds that cover
+    /// all of its instances, and its instance transforms in world space. Entities whose prop mesh
+    /// is not registered or not loaded yet are retried on later frames, and the transforms are
+    /// rebuilt when the entity moves.
     #[allow(clippy::type_complexity)]
-    pub fn spawn_meshes(
+    pub fn build_decoration_instances(
         mut commands: Commands,
-        mut chunking_threads: Query<
+        instances: Query<
             (
                 Entity,
-                &mut ChunkThread<C, C::MaterialIndex>,
-                &mut Chunk<C>,
-                &Transform,
+                &ChunkDecorationInstances<C>,
+                &GlobalTransform,
+                Has<Handle<Mesh>>,
             ),
-            Without<NeedsRemesh>,
+            Or<(
+                Without<Handle<Mesh>>,
+                Changed<ChunkDecorationInstances<C>>,
+                Changed<GlobalTransform>,
+            )>,
         >,
-        mut mesh_assets: ResMut<Assets<Mesh>>,
-        buffers: (
-            ResMut<ChunkMapUpdateBuffer<C, C::MaterialIndex>>,
-            ResMut<MeshCacheInsertBuffer<C>>,
-        ),
-        res: (Res<MeshCache<C>>, Res<LoadingTexture>),
+        decoration_meshes: Option<Res<DecorationMeshes<C>>>,
+        mesh_assets: Res<Assets<Mesh>>,
     ) {
-        let (mesh_cache, loading_texture) = res;
-
-        if !loading_texture.is_loaded {
+        let Some(decoration_meshes) = decoration_meshes else {
             return;
-        }
-
-        let (mut chunk_map_update_buffer, mut mesh_cache_insert_buffer) = buffers;
+        };
 
-        for (entity, mut thread, chunk, transform) in &mut chunking_threads {
-            let thread_result = future::block_on(future::poll_once(&mut thread.0));
+        for (entity, instances, global_transform, has_mesh) in instances.iter() {
+            let instance_data = DecorationInstanceData(
+                instances
+                    .transforms
+                    .iter()
+                    .map(|transform| {
+                        DecorationInstance::from_transform(
+                            &global_transform.mul_transform(*transform),
+                        )
+                    })
+                    .collect(),
+            );
 
-            if thread_result.is_none() {
+            if has_mesh {
+                commands.entity(entity).try_insert(instance_data);
                 continue;
             }
 
-            let chunk_task = thread_result.unwrap();
+            let Some((prop_handle, prop_aabb)) = decoration_meshes
+                .meshes
+                .get(&instances.kind)
+                .and_then(|handle| Some((handle, mesh_assets.get(handle)?.compute_aabb()?)))
+            else {
+                continue;
+            };
 
-            if !chunk_task.is_empty() {
-                if !chunk_task.is_full() {
-                    let mesh_handle = {
-                        if let Some(mesh_handle) = mesh_cache.get(&chunk_task.voxels_hash()) {
-                            mesh_handle
-                        } else {
-                            if chunk_task.mesh.is_none() {
-                                commands
-                                    .entity(chunk.entity)
-                                    .try_insert(NeedsRemesh)
-                                    .remove::<ChunkThread<C, C::MaterialIndex>>();
-                                continue;
-                            }
-                            let hash = chunk_task.voxels_hash();
-                            let mesh_ref = Arc::new(mesh_assets.add(chunk_task.mesh.unwrap()));
-                            mesh_cache_insert_buffer.push((hash, mesh_ref.clone()));
-                            mesh_ref
-                        }
-                    };
+            commands.entity(entity).try_insert((
+                prop_handle.clone(),
+                instances_aabb(prop_aabb, &instances.transforms),
+                instance_data,
+            ));
+        }
+    }
 
-                    commands
-                        .entity(entity)
-                        .try_insert((
-                            *transform,
-                            MeshRef(mesh_handle),
-                            NeedsMaterial::<C>(PhantomData),
-                        ))
-                        .remove::<bevy::render::primitives::Aabb>();
+    /// Replaces the decal meshes of chunks whose decals changed, and gives newly spawned chunks
+    /// the decals placed in them
+    #[allow(clippy::type_complexity)]
+    pub fn update_chunk_decals(
+        mut commands: Commands,
+        mut decals: ResMut<VoxelDecals<C>>,
+        chunks: Query<(
+            Ref<Chunk<C>>,
+            Option<&Children>,
+            Has<Visibility>,
+            Has<HiddenChunk>,
+        )>,
+        decal_mesh_entities: Query<(), With<ChunkDecalMesh<C>>>,
+        mut mesh_assets: ResMut<Assets<Mesh>>,
+        configuration: Res<C>,
+    ) {
+        let changed_chunks = decals.take_changed_chunks();
+        let mesh_asset_usage = configuration.chunk_mesh_asset_usage();
+        let retain_mesh_data = mesh_asset_usage.contains(RenderAssetUsages::MAIN_WORLD);
+
+        for (chunk, children, has_visibility, is_hidden) in chunks.iter() {
+            let chunk_decals = decals.get(chunk.position);
+            let is_new = chunk.is_added() && !chunk_decals.is_empty();
+            if !is_new && !changed_chunks.contains(&chunk.position) {
+                continue;
+            }
+
+            for child in children.into_iter().flatten() {
+                if decal_mesh_entities.contains(*child) {
+                    commands.entity(*child).despawn_recursive();
                 }
+            }
 
-                chunk_map_update_buffer.push((
-                    chunk.position,
-                    chunk_task.chunk_data,
-                    ChunkWillSpawn::<C>::new(chunk_task.position, entity),
-                ));
-            } else {
-                commands
-                    .entity(entity)
-                    .remove::<Handle<Mesh>>()
-                    .remove::<MeshRef>();
+            if chunk_decals.is_empty() {
+                continue;
             }
 
-            commands
-                .entity(chunk.entity)
-                .remove::<ChunkThread<C, C::MaterialIndex>>();
+            let mut mesh = decal_mesh(
+                chunk_decals,
+                chunk.position,
+                configuration.voxel_texture_mode(),
+            );
+            mesh.asset_usage = mesh_asset_usage;
+
+            let mut chunk_commands = commands.entity(chunk.entity);
+            if !has_visibility {
+                chunk_commands.try_insert((
+                    VisibilityBundle {
+                        visibility: if is_hidden {
+                            Visibility::Hidden
+                        } else {
+                            Visibility::Inherited
+                        },
+                        ..default()
+                    },
+                    GlobalTransform::default(),
+                ));
+            }
+            chunk_commands.with_children(|parent| {
+                let mut decal_commands = parent.spawn((
+                    ChunkDecalMesh::<C>::new(chunk.position),
+                    MeshRef(Arc::new(mesh_assets.add(mesh))),
+                    NeedsMaterial::<C>(PhantomData),
+                    SpatialBundle::default(),
+                ));
+                if !retain_mesh_data {
+                    decal_commands.insert(Aabb::from_min_max(
+                        Vec3::splat(-1.0),
+                        Vec3::splat(PADDED_CHUNK_SIZE as f32 + 1.0),
+                    ));
+                }
+            });
         }
     }
 
+    /// Apply the pending writes, and mark the chunks they changed for remeshing, along with the
+    /// chunks changed by `VoxelWorld::flush_pending_writes` earlier in the frame
+    #[allow(clippy::too_many_arguments)]
     pub fn flush_voxel_write_buffer(
         mut commands: Commands,
         mut buffer: ResMut<VoxelWriteBuffer<C, C::MaterialIndex>>,
+        mut flushed_chunks: ResMut<FlushedChunks<C>>,
         chunk_map: Res<ChunkMap<C, C::MaterialIndex>>,
-        modified_voxels: ResMut<ModifiedVoxels<C, C::MaterialIndex>>,
+        modified_voxels: Res<ModifiedVoxels<C, C::MaterialIndex>>,
+        mut change_tracker: Option<ResMut<VoxelChangeTracker<C>>>,
+        chunk_timings: Res<ChunkTimings<C>>,
+        mut ev_chunk_modified: EventWriter<ChunkModified<C>>,
+        configuration: Res<C>,
     ) {
+        apply_voxel_writes(
+            &mut buffer,
+            &modified_voxels,
+            &chunk_map,
+            change_tracker.as_deref_mut(),
+            &mut flushed_chunks,
+            configuration.as_ref(),
+        );
+        if flushed_chunks.touched.is_empty() {
+            return;
+        }
         let chunk_map_read_lock = chunk_map.get_read_lock();
-        let mut modified_voxels = modified_voxels.write().unwrap();
-
-        for (position, voxel) in buffer.iter() {
-            let (chunk_pos, _vox_pos) = get_chunk_voxel_position(*position);
-            modified_voxels.insert(*position, *voxel);
-
-            // Mark the chunk as needing remeshing or spawn a new chunk if it doesn't exist
+        let FlushedChunks {
+            touched,
+            bordering,
+            lit,
+            first_edit,
+            ..
+        } = &mut *flushed_chunks;
+
+        // Mark each touched chunk as needing remeshing once, however many voxels were set in it.
+        // Chunks that aren't spawned won't get meshed, so their edits are not timed.
+        let mut remeshed = Vec::with_capacity(touched.len());
+        for &chunk_pos in touched.iter() {
             if let Some(chunk_data) =
                 ChunkMap::<C, C::MaterialIndex>::get(&chunk_pos, &chunk_map_read_lock)
             {
                 if let Some(mut ent) = commands.get_entity(chunk_data.entity) {
                     ent.try_insert(NeedsRemesh);
+                    ev_chunk_modified.send(ChunkModified::<C>::new(chunk_pos, chunk_data.entity));
+                    remeshed.push(chunk_pos);
+                }
+            }
+        }
+        if let Some(first_edit) = first_edit.take() {
+            chunk_timings.record_edits(remeshed, first_edit);
+        }
+
+        // Neighbors mesh their border faces and ambient occlusion from the voxels in their
+        // padding, and light from the changed voxels can reach further into the chunks around
+        // them. Remeshing those chunks doesn't change their own voxels, so it ends there. Chunks
+        // that are only reached by the light keep their faces, and only get relit.
+        for chunk_pos in bordering
+            .union(lit)
+            .filter(|chunk| !touched.contains(*chunk))
+        {
+            if let Some(chunk_data) =
+                ChunkMap::<C, C::MaterialIndex>::get(chunk_pos, &chunk_map_read_lock)
+            {
+                if let Some(mut ent) = commands.get_entity(chunk_data.entity) {
+                    if bordering.contains(chunk_pos) {
+                        ent.try_insert(NeedsRemesh);
+                    } else {
+                        ent.try_insert(NeedsRelight);
+                    }
+                }
+            }
+        }
+        touched.clear();
+        bordering.clear();
+        lit.clear();
+    }
+
+    /// Mark the spawned chunks in the invalidated regions for remeshing, which looks up their
+    /// voxels again. The chunks keep their old data and mesh until the new ones are done.
+    ///
+    /// After `VoxelWorld::invalidate_appearance`, chunks with a mesh are remeshed from the voxels
+    /// they have. Chunks that are still being generated are generated again, since they may be
+    /// textured the old way.
+    #[allow(clippy::type_complexity)]
+    pub fn invalidate_chunks(
+        mut commands: Commands,
+        mut invalidations: ResMut<ChunkInvalidations<C>>,
+        mut appearance: ResMut<AppearanceVersion<C>>,
+        mut despawned_chunks: ResMut<DespawnedChunks<C, C::MaterialIndex>>,
+        chunks: Query<
+            (Entity, Has<MeshRef>),
+            (
+                With<Chunk<C>>,
+                Without<NeedsRemesh>,
+                Without<ChunkThread<C, C::MaterialIndex>>,
+            ),
+        >,
+        generating_chunks: Query<
+            Entity,
+            (
+                With<Chunk<C>>,
+                Or<(With<NeedsRemesh>, With<ChunkThread<C, C::MaterialIndex>>)>,
+            ),
+        >,
+        chunk_map: Res<ChunkMap<C, C::MaterialIndex>>,
+    ) {
+        if appearance.pending {
+            appearance.pending = false;
+            for (entity, has_mesh) in chunks.iter() {
+                if has_mesh {
+                    commands
+                        .entity(entity)
+                        .try_insert((NeedsRemesh, RemeshOnly));
                 }
             }
+            for entity in generating_chunks.iter() {
+                commands.entity(entity).try_insert(NeedsRemesh);
+            }
+        }
+
+        if invalidations.is_empty() {
+            return;
+        }
+        for region in invalidations.iter() {
+            despawned_chunks.invalidate(*region);
+        }
+        let chunk_map_read_lock = chunk_map.get_read_lock();
+        for (chunk_position, chunk_data) in chunk_map_read_lock.iter() {
+            let invalidated = invalidations.iter().any(|region| {
+                region.is_none_or(|(min, max)| {
+                    chunk_position.cmpge(min).all() && chunk_position.cmple(max).all()
+                })
+            });
+            if !invalidated {
+                continue;
+            }
+            if let Some(mut ent) = commands.get_entity(chunk_data.entity) {
+                ent.try_insert(NeedsRemesh).remove::<RemeshOnly>();
+            }
+        }
+        invalidations.clear();
+    }
+
+    /// Writes the mesh cache to disk when the app exits, if `mesh_cache_path` is configured
+    pub fn save_mesh_cache(
+        mut ev_app_exit: EventReader<AppExit>,
+        mesh_cache: Res<MeshCache<C>>,
+        persisted_meshes: Res<PersistedMeshes<C>>,
+        mesh_assets: Res<Assets<Mesh>>,
+        configuration: Res<C>,
+    ) {
+        if ev_app_exit.read().count() == 0 {
+            return;
         }
-        buffer.clear();
+
+        persisted_meshes.save(
+            configuration.as_ref(),
+            mesh_cache.collect_meshes(&mesh_assets),
+        );
     }
 
     pub fn flush_mesh_cache_buffers(
@@ -467,22 +3350,35 @@ where
         );
     }
 
+    #[allow(clippy::type_complexity)]
     pub(crate) fn assign_material<M: Material>(
         mut commands: Commands,
-        mut needs_material: Query<(Entity, &MeshRef, &Transform), With<NeedsMaterial<C>>>,
+        mut needs_material: Query<
+            (Entity, &MeshRef, &Transform, Has<HiddenChunk>),
+            With<NeedsMaterial<C>>,
+        >,
+        world_material_handle: Option<Res<WorldMaterialHandle<C, M>>>,
         material_handle: Option<Res<VoxelWorldMaterialHandle<M>>>,
     ) {
-        let Some(material_handle) = material_handle else {
+        let Some(material) = world_material_handle
+            .map(|world_material_handle| world_material_handle.handle.clone())
+            .or_else(|| material_handle.map(|material_handle| material_handle.handle.clone()))
+        else {
             return;
         };
 
-        for (entity, mesh_ref, transform) in needs_material.iter_mut() {
+        for (entity, mesh_ref, transform, is_hidden) in needs_material.iter_mut() {
             commands
                 .entity(entity)
                 .try_insert(MaterialMeshBundle {
                     mesh: (*mesh_ref.0).clone(),
-                    material: material_handle.handle.clone(),
+                    material: material.clone(),
                     transform: *transform,
+                    visibility: if is_hidden {
+                        Visibility::Hidden
+                    } else {
+                        Visibility::Inherited
+                    },
                     ..default()
                 })
                 .remove::<NeedsMaterial<C>>();
@@ -490,6 +3386,270 @@ where
     }
 }
 
+/// Get the message a panic was raised with
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// A lookup that runs the chunk generator on the chunk thread, the first time a voxel is needed.
+/// Voxel lighting looks further out, into the buffers of the neighboring chunks.
+fn generator_lookup<I: Copy + Send + Sync + 'static>(
+    chunk_generator: ChunkGeneratorFn<I>,
+    chunk_position: IVec3,
+) -> VoxelLookupFn<I> {
+    let mut buffers: HashMap<IVec3, ChunkBuffer<I>> = HashMap::new();
+    Box::new(move |position| {
+        let in_chunk_buffer = buffers
+            .get(&chunk_position)
+            .is_none_or(|buffer| buffer.contains(position));
+        let buffer_position = if in_chunk_buffer {
+            chunk_position
+        } else {
+            position.div_euclid(IVec3::splat(CHUNK_SIZE_I))
+        };
+        buffers
+            .entry(buffer_position)
+            .or_insert_with(|| ChunkBuffer::generate(buffer_position, &chunk_generator))
+            .get(position)
+    })
+}
+
+/// Whether a chunk is within the given `VoxelWorldConfig::world_bounds`
+pub(crate) fn chunk_in_world_bounds(
+    world_bounds: Option<(IVec3, IVec3)>,
+    chunk_position: IVec3,
+) -> bool {
+    world_bounds
+        .is_none_or(|(min, max)| chunk_position.cmpge(min).all() && chunk_position.cmple(max).all())
+}
+
+/// Get the level of detail for a chunk, based on its distance from the closest chunk a camera
+/// is in
+fn chunk_lod_level<C: VoxelWorldConfig>(
+    configuration: &C,
+    chunk_position: IVec3,
+    chunks_at_cameras: &[IVec3],
+) -> u8 {
+    let distance_squared = chunks_at_cameras
+        .iter()
+        .map(|chunk_at_camera| chunk_position.distance_squared(*chunk_at_camera))
+        .min()
+        .unwrap_or_default();
+    (1..configuration.lod_levels().min(MAX_LOD_LEVELS))
+        .rev()
+        .find(|&level| distance_squared > (configuration.lod_distance(level) as i32).pow(2))
+        .unwrap_or(0)
+}
+
+/// The position of the camera in voxel coordinates, which only differ from world coordinates
+/// when `voxel_size` is configured
+#[inline]
+fn camera_voxel_position<C: VoxelWorldConfig>(
+    configuration: &C,
+    cam_gtf: &GlobalTransform,
+) -> Vec3 {
+    cam_gtf.translation() / configuration.voxel_size()
+}
+
+/// The chunks within the view volume of an orthographic camera, and within
+/// `spawning_chunk_margin` of it. Only the chunks within `spawning_distance` of the camera are
+/// taken, so that a large view volume doesn't load more than `max_loaded_chunks`.
+fn orthographic_view_chunks<C: VoxelWorldConfig>(
+    configuration: &C,
+    spawning_distance: u32,
+    projection: &OrthographicProjection,
+    cam_gtf: &GlobalTransform,
+) -> Vec<IVec3> {
+    let margin = configuration.spawning_chunk_margin().unwrap_or(0) as f32 * CHUNK_SIZE_F;
+
+    // The view volume in voxels, in the space of the camera, which looks down -Z. The area of the
+    // projection is already scaled.
+    let view_min =
+        projection.area.min.extend(-projection.far) / configuration.voxel_size() - margin;
+    let view_max =
+        projection.area.max.extend(-projection.near) / configuration.voxel_size() + margin;
+
+    let voxel_from_view = Transform {
+        translation: cam_gtf.translation() / configuration.voxel_size(),
+        rotation: cam_gtf.to_scale_rotation_translation().1,
+        scale: Vec3::ONE,
+    };
+    let view_from_voxel = voxel_from_view.compute_matrix().inverse();
+
+    let corners = (0..8).map(|i| {
+        voxel_from_view.transform_point(Vec3::select(
+            BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0),
+            view_max,
+            view_min,
+        ))
+    });
+    let (min, max) = corners.fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), corner| (min.min(corner), max.max(corner)),
+    );
+    let spawning_distance = spawning_distance as i32;
+    let chunk_at_camera = chunk_containing(voxel_from_view.translation);
+    let min_chunk = (min / CHUNK_SIZE_F)
+        .floor()
+        .as_ivec3()
+        .max(chunk_at_camera - spawning_distance);
+    let max_chunk = (max / CHUNK_SIZE_F)
+        .floor()
+        .as_ivec3()
+        .min(chunk_at_camera + spawning_distance);
+
+    // Chunks in the bounding box of a tilted view volume can still be outside of it
+    let chunk_radius = Vec3::splat(CHUNK_SIZE_F / 2.0).length();
+    let mut chunks = Vec::new();
+    for x in min_chunk.x..=max_chunk.x {
+        for y in min_chunk.y..=max_chunk.y {
+            for z in min_chunk.z..=max_chunk.z {
+                let chunk_position = IVec3::new(x, y, z);
+                if chunk_position.distance_squared(chunk_at_camera) > spawning_distance.pow(2) {
+                    continue;
+                }
+                let center = view_from_voxel
+                    .transform_point3(chunk_position.as_vec3() * CHUNK_SIZE_F + CHUNK_SIZE_F / 2.0);
+                if center.cmpge(view_min - chunk_radius).all()
+                    && center.cmple(view_max + chunk_radius).all()
+                {
+                    chunks.push(chunk_position);
+                }
+            }
+        }
+    }
+    chunks
+}
+
+/// The chunks a camera has in view: the chunks around it, the chunks within `spawning_distance`
+/// that are at least partly inside its view frustum, widened by `spawning_chunk_margin`, and for
+/// an orthographic camera its view volume within `spawning_distance`. Sorted from near to far.
+/// The spawner does not use this, it casts rays instead.
+pub(crate) fn chunks_in_view<C: VoxelWorldConfig>(
+    configuration: &C,
+    distances: &SpawningDistances<C>,
+    projection: &Projection,
+    cam_gtf: &GlobalTransform,
+) -> Vec<IVec3> {
+    let camera_position = camera_voxel_position(configuration, cam_gtf);
+    let chunk_at_camera = chunk_containing(camera_position);
+    let mut chunks: HashSet<IVec3> = camera_chunk_neighborhood(camera_position).into();
+
+    match projection {
+        Projection::Orthographic(projection) => {
+            chunks.extend(orthographic_view_chunks(
+                configuration,
+                distances.spawning,
+                projection,
+                cam_gtf,
+            ));
+        }
+        Projection::Perspective(_) => {
+            // The frustum in voxel space, so the chunk bounds don't have to be scaled
+            let voxel_from_view = Transform {
+                translation: camera_position,
+                rotation: cam_gtf.to_scale_rotation_translation().1,
+                scale: Vec3::ONE,
+            };
+            let clip_from_voxel =
+                projection.get_clip_from_view() * voxel_from_view.compute_matrix().inverse();
+            let frustum = Frustum::from_clip_from_world(&clip_from_voxel);
+            let margin = configuration.spawning_chunk_margin().unwrap_or(0) as f32 * CHUNK_SIZE_F;
+            let radius = Vec3::splat(CHUNK_SIZE_F / 2.0).length() + margin;
+
+            chunks.extend(
+                distances
+                    .sphere_offsets
+                    .iter()
+                    .map(|offset| chunk_at_camera + *offset)
+                    .filter(|chunk_position| {
+                        let center = chunk_position.as_vec3() * CHUNK_SIZE_F + CHUNK_SIZE_F / 2.0;
+                        // The far plane of perspective cameras is at infinity
+                        frustum.intersects_sphere(
+                            &bevy::render::primitives::Sphere {
+                                center: center.into(),
+                                radius,
+                            },
+                            false,
+                        )
+                    }),
+            );
+        }
+    }
+
+    let mut chunks: Vec<IVec3> = chunks.into_iter().collect();
+    chunks.sort_by_key(|chunk_position| chunk_position.distance_squared(chunk_at_camera));
+    chunks
+}
+
+/// The chunk a point in voxel space is in. Rounds down, so that points at negative coordinates end
+/// up in the same chunk as the voxels around them.
+#[inline]
+pub(crate) fn chunk_containing(position: Vec3) -> IVec3 {
+    (position / CHUNK_SIZE_F).floor().as_ivec3()
+}
+
+/// The chunk the camera is in, and its six face neighbors. These are spawned and remeshed before
+/// any other chunks, and never despawned or hidden, so there is always ground around the camera.
+pub(crate) fn camera_chunk_neighborhood(camera_position: Vec3) -> [IVec3; 7] {
+    let chunk_at_camera = chunk_containing(camera_position);
+    [
+        chunk_at_camera,
+        chunk_at_camera + IVec3::X,
+        chunk_at_camera + IVec3::NEG_X,
+        chunk_at_camera + IVec3::Y,
+        chunk_at_camera + IVec3::NEG_Y,
+        chunk_at_camera + IVec3::Z,
+        chunk_at_camera + IVec3::NEG_Z,
+    ]
+}
+
+/// The translation of a chunk entity. Chunk meshes include the padding, so they start one voxel
+/// before the chunk.
+#[inline]
+fn chunk_translation(chunk_position: IVec3) -> Vec3 {
+    chunk_position.as_vec3() * CHUNK_SIZE_F - 1.0
+}
+
+/// Get the ray through a point of the view of a camera, given in normalized device coordinates.
+/// The ray starts on the near plane.
+pub(crate) fn ndc_ray(cam_gtf: &GlobalTransform, clip_from_view: Mat4, ndc: Vec2) -> Option<Ray3d> {
+    let ndc_to_world = cam_gtf.compute_matrix() * clip_from_view.inverse();
+    let near = ndc_to_world.project_point3(ndc.extend(1.0));
+    // The far plane is at infinity, so use the smallest depth that isn't
+    let far = ndc_to_world.project_point3(ndc.extend(f32::EPSILON));
+
+    Dir3::new(far - near).ok().map(|direction| Ray3d {
+        origin: near,
+        direction,
+    })
+}
+
+/// The offsets within `radius` of the origin, nearest first
+fn sphere_offsets(radius: i32) -> Vec<IVec3> {
+    let mut offsets: Vec<IVec3> = (-radius..=radius)
+        .flat_map(|x| {
+            (-radius..=radius)
+                .flat_map(move |y| (-radius..=radius).map(move |z| IVec3::new(x, y, z)))
+        })
+        .filter(|offset| offset.length_squared() <= radius.pow(2))
+        .collect();
+    offsets.sort_by_key(|offset| offset.length_squared());
+    offsets
+}
+
+/// Get a direction picked uniformly from all directions
+fn random_direction(rng: &mut dyn RngCore) -> Dir3 {
+    let z = rng.gen::<f32>() * 2.0 - 1.0;
+    let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+    let radius = (1.0 - z * z).max(0.0).sqrt();
+    Dir3::new_unchecked(Vec3::new(radius * angle.cos(), radius * angle.sin(), z))
+}
+
 /// Check if the given world point is within the camera's view
 #[inline]
 #[allow(dead_code)]
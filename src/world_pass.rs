@@ -0,0 +1,323 @@
+///
+/// World passes
+/// Multi-iteration post-processing over a region of generated chunks, like erosion or smoothing,
+/// run on the task pool with double-buffered voxels.
+///
+use std::{
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+    utils::Instant,
+};
+use futures_lite::future;
+
+use crate::{
+    chunk::CHUNK_SIZE_I,
+    chunk_map::ChunkMapData,
+    chunk_ref::ChunkRef,
+    configuration::VoxelWorldConfig,
+    voxel::WorldVoxel,
+    voxel_world_internal::{FlushedChunks, ModifiedVoxelMap, VoxelWriteBuffer},
+};
+
+/// The voxels of a box of chunks, handed to the pass of `VoxelWorld::spawn_world_pass`.
+/// Positions are world voxel positions. Voxels in chunks that were not spawned are `Unset`.
+#[derive(Clone)]
+pub struct VoxelRegion<I> {
+    min: IVec3,
+    size: IVec3,
+    voxels: Vec<WorldVoxel<I>>,
+}
+
+impl<I: Copy + PartialEq> VoxelRegion<I> {
+    /// An `Unset` region covering the chunks between `min_chunk` and `max_chunk`, inclusive, or
+    /// `None` if its positions or voxel indices don't fit in an `i32`
+    fn new(min_chunk: IVec3, max_chunk: IVec3) -> Option<Self> {
+        let (min, size) = region_bounds(min_chunk, max_chunk)?;
+        Some(Self {
+            min,
+            size,
+            voxels: vec![WorldVoxel::Unset; (size.x * size.y * size.z) as usize],
+        })
+    }
+
+    /// The lowest position in the region, inclusive
+    pub fn min(&self) -> IVec3 {
+        self.min
+    }
+
+    /// The highest position in the region, inclusive
+    pub fn max(&self) -> IVec3 {
+        self.min + self.size - IVec3::ONE
+    }
+
+    /// Whether a position is within the region
+    pub fn contains(&self, position: IVec3) -> bool {
+        position.cmpge(self.min()).all() && position.cmple(self.max()).all()
+    }
+
+    /// Get the voxel at a position. Positions outside of the region are `Unset`.
+    pub fn get(&self, position: IVec3) -> WorldVoxel<I> {
+        if self.contains(position) {
+            self.voxels[self.index(position)]
+        } else {
+            WorldVoxel::Unset
+        }
+    }
+
+    /// Set the voxel at a position. Positions outside of the region are ignored.
+    pub fn set(&mut self, position: IVec3, voxel: WorldVoxel<I>) {
+        if self.contains(position) {
+            let index = self.index(position);
+            self.voxels[index] = voxel;
+        }
+    }
+
+    /// The `x` and `z` positions of every column of voxels in the region
+    pub fn columns(&self) -> impl Iterator<Item = IVec2> {
+        let (min, max) = (self.min(), self.max());
+        (min.z..=max.z).flat_map(move |z| (min.x..=max.x).map(move |x| IVec2::new(x, z)))
+    }
+
+    /// The highest position in a column that is not `Air` or `Unset`
+    pub fn surface_height(&self, column: IVec2) -> Option<i32> {
+        (self.min.y..=self.max().y).rev().find(|&y| {
+            matches!(
+                self.get(IVec3::new(column.x, y, column.y)),
+                WorldVoxel::Solid(_)
+            )
+        })
+    }
+
+    fn index(&self, position: IVec3) -> usize {
+        let local = position - self.min;
+        (local.x + self.size.x * (local.y + self.size.y * local.z)) as usize
+    }
+}
+
+/// The lowest voxel position and the size of the region covering the chunks between `min_chunk`
+/// and `max_chunk`, inclusive, if its positions and voxel indices fit in an `i32`
+fn region_bounds(min_chunk: IVec3, max_chunk: IVec3) -> Option<(IVec3, IVec3)> {
+    let min = [min_chunk.x, min_chunk.y, min_chunk.z].map(|chunk| chunk.checked_mul(CHUNK_SIZE_I));
+    let size = [
+        (min_chunk.x, max_chunk.x),
+        (min_chunk.y, max_chunk.y),
+        (min_chunk.z, max_chunk.z),
+    ]
+    .map(|(min, max)| {
+        max.checked_sub(min)?
+            .checked_add(1)?
+            .checked_mul(CHUNK_SIZE_I)
+    });
+    let (min, size) = (
+        IVec3::new(min[0]?, min[1]?, min[2]?),
+        IVec3::new(size[0]?, size[1]?, size[2]?),
+    );
+    min.x.checked_add(size.x)?;
+    min.y.checked_add(size.y)?;
+    min.z.checked_add(size.z)?;
+    size.x.checked_mul(size.y)?.checked_mul(size.z)?;
+    Some((min, size))
+}
+
+/// Identifies a pass started with `VoxelWorld::spawn_world_pass`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WorldPassId(u64);
+
+/// Sent when a world pass has finished one or more of its iterations
+#[derive(Event)]
+pub struct WorldPassProgress<C> {
+    pub pass: WorldPassId,
+    /// The number of iterations that are done
+    pub iteration: u32,
+    pub iterations: u32,
+    _marker: PhantomData<C>,
+}
+
+/// Sent when a world pass is done, and the voxels it changed have been set
+#[derive(Event)]
+pub struct WorldPassFinished<C> {
+    pub pass: WorldPassId,
+    /// The number of voxels the pass changed
+    pub changed_voxels: usize,
+    _marker: PhantomData<C>,
+}
+
+pub(crate) type WorldPassFn<I> = Arc<dyn Fn(&VoxelRegion<I>, &mut VoxelRegion<I>) + Send + Sync>;
+
+struct RunningPass<I> {
+    id: WorldPassId,
+    iterations: u32,
+    reported_iterations: u32,
+    done_iterations: Arc<AtomicU32>,
+    task: Task<Vec<(IVec3, WorldVoxel<I>)>>,
+}
+
+/// The world passes that are running
+#[derive(Resource)]
+pub(crate) struct WorldPasses<C, I> {
+    next_id: u64,
+    running: Vec<RunningPass<I>>,
+    _marker: PhantomData<C>,
+}
+
+impl<C, I> Default for WorldPasses<C, I> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            running: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: Send + Sync + 'static, I: Copy + PartialEq + std::hash::Hash + Send + Sync + 'static>
+    WorldPasses<C, I>
+{
+    /// Start a pass over the chunks between `min_chunk` and `max_chunk`, inclusive. The voxels are
+    /// copied on the task, so that the region doesn't have to be copied on the main thread.
+    /// Returns `None` without starting the pass if the region is too large.
+    pub fn spawn(
+        &mut self,
+        (min_chunk, max_chunk): (IVec3, IVec3),
+        iterations: u32,
+        pass: WorldPassFn<I>,
+        chunk_map: Arc<RwLock<ChunkMapData<I>>>,
+        modified_voxels: Arc<RwLock<ModifiedVoxelMap<I>>>,
+        pending_writes: Vec<(IVec3, WorldVoxel<I>)>,
+    ) -> Option<WorldPassId> {
+        let (min_chunk, max_chunk) = (min_chunk.min(max_chunk), min_chunk.max(max_chunk));
+        region_bounds(min_chunk, max_chunk)?;
+
+        let id = WorldPassId(self.next_id);
+        self.next_id += 1;
+        let done_iterations = Arc::new(AtomicU32::new(0));
+        let progress = done_iterations.clone();
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            // The bounds were checked before the task was started
+            let region = VoxelRegion::new(min_chunk, max_chunk).unwrap();
+            let original = read_region(
+                region,
+                min_chunk,
+                max_chunk,
+                &chunk_map,
+                &modified_voxels,
+                &pending_writes,
+            );
+
+            // Each iteration reads the result of the last one, and writes into the other buffer
+            let mut read = original.clone();
+            let mut write = original.clone();
+            for iteration in 0..iterations {
+                write.voxels.copy_from_slice(&read.voxels);
+                pass(&read, &mut write);
+                std::mem::swap(&mut read, &mut write);
+                progress.store(iteration + 1, Ordering::Relaxed);
+            }
+
+            read.voxels
+                .iter()
+                .zip(&original.voxels)
+                .enumerate()
+                .filter(|(_, (voxel, old))| voxel != old)
+                .map(|(index, (voxel, _))| {
+                    let index = index as i32;
+                    let size = read.size;
+                    let local = IVec3::new(
+                        index % size.x,
+                        (index / size.x) % size.y,
+                        index / (size.x * size.y),
+                    );
+                    (read.min + local, *voxel)
+                })
+                .collect()
+        });
+
+        self.running.push(RunningPass {
+            id,
+            iterations,
+            reported_iterations: 0,
+            done_iterations,
+            task,
+        });
+        Some(id)
+    }
+}
+
+/// Copy the voxels of the spawned chunks between `min_chunk` and `max_chunk` into a region. The
+/// locks are taken for one chunk at a time, so that the world isn't blocked while a large region
+/// is copied.
+fn read_region<I: Copy + PartialEq + std::hash::Hash>(
+    mut region: VoxelRegion<I>,
+    min_chunk: IVec3,
+    max_chunk: IVec3,
+    chunk_map: &RwLock<ChunkMapData<I>>,
+    modified_voxels: &RwLock<ModifiedVoxelMap<I>>,
+    pending_writes: &[(IVec3, WorldVoxel<I>)],
+) -> VoxelRegion<I> {
+    for z in min_chunk.z..=max_chunk.z {
+        for y in min_chunk.y..=max_chunk.y {
+            for x in min_chunk.x..=max_chunk.x {
+                let chunk_position = IVec3::new(x, y, z);
+                let Some(chunk_data) = chunk_map.read().unwrap().get(&chunk_position).cloned()
+                else {
+                    continue;
+                };
+                let chunk = ChunkRef::from_chunk_data(
+                    chunk_position,
+                    &chunk_data,
+                    &modified_voxels.read().unwrap(),
+                    pending_writes,
+                );
+                for (position, voxel) in chunk.iter() {
+                    region.set(position, voxel);
+                }
+            }
+        }
+    }
+    region
+}
+
+/// Send the progress of the running world passes, and set the voxels changed by the passes that
+/// are done
+pub(crate) fn run_world_passes<C: VoxelWorldConfig>(
+    mut passes: ResMut<WorldPasses<C, C::MaterialIndex>>,
+    mut write_buffer: ResMut<VoxelWriteBuffer<C, C::MaterialIndex>>,
+    mut flushed_chunks: ResMut<FlushedChunks<C>>,
+    mut ev_progress: EventWriter<WorldPassProgress<C>>,
+    mut ev_finished: EventWriter<WorldPassFinished<C>>,
+) {
+    passes.running.retain_mut(|pass| {
+        let done_iterations = pass.done_iterations.load(Ordering::Relaxed);
+        if done_iterations > pass.reported_iterations {
+            pass.reported_iterations = done_iterations;
+            ev_progress.send(WorldPassProgress {
+                pass: pass.id,
+                iteration: done_iterations,
+                iterations: pass.iterations,
+                _marker: PhantomData,
+            });
+        }
+
+        let Some(changes) = future::block_on(future::poll_once(&mut pass.task)) else {
+            return true;
+        };
+        if !changes.is_empty() {
+            flushed_chunks.first_edit.get_or_insert_with(Instant::now);
+        }
+        ev_finished.send(WorldPassFinished {
+            pass: pass.id,
+            changed_voxels: changes.len(),
+            _marker: PhantomData,
+        });
+        write_buffer.extend(changes);
+        false
+    });
+}